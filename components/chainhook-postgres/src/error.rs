@@ -0,0 +1,57 @@
+use deadpool_postgres::PoolError;
+
+/// Error raised while connecting to Postgres, opening a transaction or running a query.
+#[derive(Debug, thiserror::Error)]
+pub enum PgError {
+    #[error("unable to build pg connection pool: {0}")]
+    Pool(String),
+    #[error("unable to get pg client: {0}")]
+    Client(#[from] PoolError),
+    #[error("error connecting to postgres: {0}")]
+    Connect(tokio_postgres::Error),
+    #[error("unable to begin pg transaction: {0}")]
+    Transaction(tokio_postgres::Error),
+    /// A query failed. Holds the name of the helper that ran it.
+    #[error("{0}: {1}")]
+    Query(&'static str, tokio_postgres::Error),
+}
+
+impl PgError {
+    /// `true` if the same operation may succeed when attempted again, e.g. after the database comes back up or a
+    /// conflicting transaction finishes.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PgError::Pool(_) => false,
+            PgError::Client(e) => match e {
+                PoolError::Timeout(_) => true,
+                PoolError::Backend(e) => is_retryable_pg_error(e),
+                _ => false,
+            },
+            PgError::Connect(e) | PgError::Transaction(e) | PgError::Query(_, e) => {
+                is_retryable_pg_error(e)
+            }
+        }
+    }
+}
+
+impl From<PgError> for String {
+    fn from(e: PgError) -> Self {
+        e.to_string()
+    }
+}
+
+/// `true` for errors caused by the connection or by concurrent transactions rather than by the query itself.
+pub fn is_retryable_pg_error(e: &tokio_postgres::Error) -> bool {
+    if e.is_closed() {
+        return true;
+    }
+    let Some(code) = e.code() else {
+        // Errors without a SQLSTATE happened on the client side. Only socket I/O errors are worth retrying, the others
+        // come from encoding parameters or decoding rows.
+        return std::error::Error::source(e).is_some_and(|source| source.is::<std::io::Error>());
+    };
+    let code = code.code();
+    // Class 08: connection exception. 40001: serialization failure. 40P01: deadlock detected. 53300: too many
+    // connections. 57P01-57P03: server shutting down or starting up.
+    code.starts_with("08") || ["40001", "40P01", "53300", "57P01", "57P02", "57P03"].contains(&code)
+}
@@ -1,8 +1,11 @@
+pub mod error;
 pub mod types;
 pub mod utils;
 
+pub use error::PgError;
+
 use deadpool_postgres::{Manager, ManagerConfig, Object, Pool, RecyclingMethod, Transaction};
-use tokio_postgres::{Client, Config, NoTls, Row};
+use tokio_postgres::{Client, Config, IsolationLevel, NoTls, Row};
 
 /// Standard chunk size to use when we're batching multiple query inserts into a single SQL statement to save on DB round trips.
 /// This number is designed to not hit the postgres limit of 65536 query parameters in a single SQL statement, but results may
@@ -23,7 +26,7 @@ pub struct PgConnectionConfig {
 
 /// Creates a Postgres connection pool based on a single database config. You can then use this pool to create ad-hoc clients and
 /// transactions for interacting with the database.
-pub fn pg_pool(config: &PgConnectionConfig) -> Result<Pool, String> {
+pub fn pg_pool(config: &PgConnectionConfig) -> Result<Pool, PgError> {
     let mut pg_config = Config::new();
     pg_config
         .dbname(&config.dbname)
@@ -48,34 +51,45 @@ pub fn pg_pool(config: &PgConnectionConfig) -> Result<Pool, String> {
     if let Some(size) = config.pool_max_size {
         pool_builder = pool_builder.max_size(size);
     }
-    Ok(pool_builder
+    pool_builder
         .build()
-        .map_err(|e| format!("unable to build pg connection pool: {e}"))?)
+        .map_err(|e| PgError::Pool(e.to_string()))
 }
 
 /// Returns a new pg connection client taken from a pool.
-pub async fn pg_pool_client(pool: &Pool) -> Result<Object, String> {
-    pool.get()
-        .await
-        .map_err(|e| format!("unable to get pg client: {e}"))
+pub async fn pg_pool_client(pool: &Pool) -> Result<Object, PgError> {
+    Ok(pool.get().await?)
 }
 
 /// Returns a new pg transaction taken from an existing pool connection
-pub async fn pg_begin(client: &mut Object) -> Result<Transaction<'_>, String> {
+pub async fn pg_begin(client: &mut Object) -> Result<Transaction<'_>, PgError> {
+    client.transaction().await.map_err(PgError::Transaction)
+}
+
+/// Returns a new read-only repeatable read transaction, so every query made through it sees the same database snapshot even
+/// if other transactions commit in between.
+pub async fn pg_begin_read_snapshot(client: &mut Object) -> Result<Transaction<'_>, PgError> {
     client
-        .transaction()
+        .build_transaction()
+        .isolation_level(IsolationLevel::RepeatableRead)
+        .read_only(true)
+        .start()
         .await
-        .map_err(|e| format!("unable to begin pg transaction: {e}"))
+        .map_err(PgError::Transaction)
 }
 
 /// Connects to postgres directly (without a Pool) and returns an open client.
-pub async fn pg_connect(config: &PgConnectionConfig) -> Result<Client, String> {
+pub async fn pg_connect(config: &PgConnectionConfig) -> Result<Client, PgError> {
     let mut pg_config = Config::new();
     pg_config
         .dbname(&config.dbname)
         .host(&config.host)
         .port(config.port)
-        .user(&config.user);
+        .user(&config.user)
+        .options(format!(
+            "-csearch_path={}",
+            config.search_path.as_ref().unwrap_or(&"public".to_string())
+        ));
     if let Some(password) = &config.password {
         pg_config.password(password);
     }
@@ -88,7 +102,7 @@ pub async fn pg_connect(config: &PgConnectionConfig) -> Result<Client, String> {
             });
             Ok(client)
         }
-        Err(e) => Err(format!("error connecting to postgres: {e}")),
+        Err(e) => Err(PgError::Connect(e)),
     }
 }
 
@@ -98,7 +112,7 @@ pub async fn pg_connect_with_retry(config: &PgConnectionConfig) -> Client {
         match pg_connect(config).await {
             Ok(client) => return client,
             Err(e) => {
-                println!("error connecting to postgres: {e}");
+                println!("{e}");
                 std::thread::sleep(std::time::Duration::from_secs(1));
             }
         }
@@ -0,0 +1,18 @@
+use chainhook_sdk::utils::hex;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn hex_benchmark(c: &mut Criterion) {
+    // Roughly the size of a large inscription body.
+    let bytes: Vec<u8> = (0..400_000).map(|i| (i % 256) as u8).collect();
+    let encoded = hex::encode(&bytes);
+
+    c.bench_function("hex encode 400kB", |b| {
+        b.iter(|| hex::encode(black_box(&bytes)))
+    });
+    c.bench_function("hex decode 400kB", |b| {
+        b.iter(|| hex::decode(black_box(&encoded)).unwrap())
+    });
+}
+
+criterion_group!(benches, hex_benchmark);
+criterion_main!(benches);
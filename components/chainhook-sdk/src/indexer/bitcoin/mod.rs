@@ -1,8 +1,12 @@
+mod rate_limiter;
+mod rpc_endpoints;
+
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 
 use crate::observer::BitcoinConfig;
 use crate::try_debug;
-use crate::utils::Context;
+use crate::utils::{hex, Context};
 use bitcoincore_rpc::bitcoin::hashes::Hash;
 use bitcoincore_rpc::bitcoin::{self, Amount, BlockHash};
 use bitcoincore_rpc::jsonrpc::error::RpcError;
@@ -17,6 +21,9 @@ use hiro_system_kit::slog;
 use reqwest::Client as HttpClient;
 use serde::Deserialize;
 
+pub use rate_limiter::BitcoindRpcRateLimiter;
+pub use rpc_endpoints::BitcoindRpcEndpoints;
+
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BitcoinBlockFullBreakdown {
@@ -59,6 +66,9 @@ pub struct BitcoinTransactionFullBreakdown {
     pub txid: String,
     pub vin: Vec<BitcoinTransactionInputFullBreakdown>,
     pub vout: Vec<BitcoinTransactionOutputFullBreakdown>,
+    /// The serialized transaction, as returned by `getblock` with verbosity 3.
+    #[serde(default)]
+    pub hex: Option<String>,
 }
 
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -126,6 +136,8 @@ pub struct RewardParticipant {
     amt: u64,
 }
 
+/// Builds the client used for bitcoind RPC calls. Proxies are read from the `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`
+/// environment variables.
 pub fn build_http_client() -> HttpClient {
     HttpClient::builder()
         .timeout(Duration::from_secs(15))
@@ -133,7 +145,6 @@ pub fn build_http_client() -> HttpClient {
         .no_hickory_dns()
         .connect_timeout(Duration::from_secs(15))
         .tcp_keepalive(Some(Duration::from_secs(15)))
-        .no_proxy()
         .danger_accept_invalid_certs(true)
         .build()
         .expect("Unable to build http client")
@@ -148,10 +159,12 @@ pub async fn download_and_parse_block_with_retry(
     let mut errors_count = 0;
     let max_retries = 20;
     let block = loop {
+        let endpoint_index = bitcoin_config.rpc_endpoints.active_index();
         match download_and_parse_block(http_client, block_hash, bitcoin_config, ctx).await {
             Ok(result) => break result,
             Err(e) => {
                 errors_count += 1;
+                bitcoin_config.rpc_endpoints.fail_over(endpoint_index, ctx);
                 if errors_count > 3 && errors_count < max_retries {
                     ctx.try_log(|logger| {
                         slog::warn!(
@@ -178,10 +191,12 @@ pub async fn retrieve_block_hash_with_retry(
     let mut errors_count = 0;
     let max_retries = 10;
     let block_hash = loop {
+        let endpoint_index = bitcoin_config.rpc_endpoints.active_index();
         match retrieve_block_hash(http_client, block_height, bitcoin_config, ctx).await {
             Ok(result) => break result,
             Err(e) => {
                 errors_count += 1;
+                bitcoin_config.rpc_endpoints.fail_over(endpoint_index, ctx);
                 if errors_count > 3 && errors_count < max_retries {
                     ctx.try_log(|logger| {
                         slog::warn!(
@@ -203,7 +218,7 @@ pub async fn retrieve_block_hash(
     http_client: &HttpClient,
     block_height: &u64,
     bitcoin_config: &BitcoinConfig,
-    _ctx: &Context,
+    ctx: &Context,
 ) -> Result<String, String> {
     let body = json!({
         "jsonrpc": "1.0",
@@ -211,15 +226,8 @@ pub async fn retrieve_block_hash(
         "method": "getblockhash",
         "params": [block_height]
     });
-    let block_hash = http_client
-        .post(&bitcoin_config.rpc_url)
-        .basic_auth(&bitcoin_config.username, Some(&bitcoin_config.password))
-        .header("Content-Type", "application/json")
-        .header("Host", &bitcoin_config.rpc_url[7..])
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| format!("unable to send request ({})", e))?
+    let block_hash = send_rpc_request(http_client, &body, bitcoin_config, ctx)
+        .await?
         .json::<bitcoincore_rpc::jsonrpc::Response>()
         .await
         .map_err(|e| format!("unable to parse response ({})", e))?
@@ -229,6 +237,136 @@ pub async fn retrieve_block_hash(
     Ok(block_hash)
 }
 
+/// Retrieves the hash of the tip of bitcoind's best chain.
+pub async fn retrieve_best_block_hash(
+    http_client: &HttpClient,
+    bitcoin_config: &BitcoinConfig,
+    ctx: &Context,
+) -> Result<String, String> {
+    let body = json!({
+        "jsonrpc": "1.0",
+        "id": "chainhook-cli",
+        "method": "getbestblockhash",
+        "params": []
+    });
+    send_rpc_request(http_client, &body, bitcoin_config, ctx)
+        .await?
+        .json::<bitcoincore_rpc::jsonrpc::Response>()
+        .await
+        .map_err(|e| format!("unable to parse response ({})", e))?
+        .result::<String>()
+        .map_err(|e| format!("unable to parse response ({})", e))
+}
+
+/// Retrieves the height of the tip of bitcoind's best chain.
+pub async fn retrieve_block_count(
+    http_client: &HttpClient,
+    bitcoin_config: &BitcoinConfig,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let body = json!({
+        "jsonrpc": "1.0",
+        "id": "chainhook-cli",
+        "method": "getblockcount",
+        "params": []
+    });
+    send_rpc_request(http_client, &body, bitcoin_config, ctx)
+        .await?
+        .json::<bitcoincore_rpc::jsonrpc::Response>()
+        .await
+        .map_err(|e| format!("unable to parse response ({})", e))?
+        .result::<u64>()
+        .map_err(|e| format!("unable to parse response ({})", e))
+}
+
+pub async fn retrieve_block_hashes_with_retry(
+    http_client: &HttpClient,
+    block_heights: &[u64],
+    bitcoin_config: &BitcoinConfig,
+    ctx: &Context,
+) -> Result<Vec<String>, String> {
+    let mut errors_count = 0;
+    let max_retries = 10;
+    let block_hashes = loop {
+        let endpoint_index = bitcoin_config.rpc_endpoints.active_index();
+        match retrieve_block_hashes(http_client, block_heights, bitcoin_config, ctx).await {
+            Ok(result) => break result,
+            Err(e) => {
+                errors_count += 1;
+                bitcoin_config.rpc_endpoints.fail_over(endpoint_index, ctx);
+                if errors_count > 3 && errors_count < max_retries {
+                    ctx.try_log(|logger| {
+                        slog::warn!(
+                            logger,
+                            "unable to retrieve {} block hashes: will retry in a few seconds (attempt #{errors_count}). Error: {e}",
+                            block_heights.len()
+                        )
+                    });
+                } else if errors_count == max_retries {
+                    return Err(format!("unable to retrieve {} block hashes after {errors_count} attempts. Error: {e}", block_heights.len()));
+                }
+                std::thread::sleep(std::time::Duration::from_secs(2));
+            }
+        }
+    };
+    Ok(block_hashes)
+}
+
+/// Retrieves the hashes of several blocks with a single JSON-RPC batch call. Hashes are returned in the same order as
+/// `block_heights`.
+pub async fn retrieve_block_hashes(
+    http_client: &HttpClient,
+    block_heights: &[u64],
+    bitcoin_config: &BitcoinConfig,
+    ctx: &Context,
+) -> Result<Vec<String>, String> {
+    let body: Vec<_> = block_heights
+        .iter()
+        .enumerate()
+        .map(|(index, block_height)| {
+            json!({
+                "jsonrpc": "1.0",
+                "id": index,
+                "method": "getblockhash",
+                "params": [block_height]
+            })
+        })
+        .collect();
+    let responses = send_rpc_request(http_client, &body, bitcoin_config, ctx)
+        .await?
+        .json::<Vec<bitcoincore_rpc::jsonrpc::Response>>()
+        .await
+        .map_err(|e| format!("unable to parse response ({})", e))?;
+
+    order_batch_responses(responses, block_heights.len(), "getblockhash")
+}
+
+/// Retrieves the serialized (hex) transaction from bitcoind. The block hash is passed along so that nodes running without
+/// `txindex` can still locate the transaction.
+pub async fn retrieve_raw_transaction(
+    http_client: &HttpClient,
+    txid: &str,
+    block_hash: &str,
+    bitcoin_config: &BitcoinConfig,
+    ctx: &Context,
+) -> Result<String, String> {
+    let body = json!({
+        "jsonrpc": "1.0",
+        "id": "chainhook-cli",
+        "method": "getrawtransaction",
+        "params": [txid, false, block_hash]
+    });
+    let raw_transaction = send_rpc_request(http_client, &body, bitcoin_config, ctx)
+        .await?
+        .json::<bitcoincore_rpc::jsonrpc::Response>()
+        .await
+        .map_err(|e| format!("unable to parse response ({})", e))?
+        .result::<String>()
+        .map_err(|e| format!("unable to parse response ({})", e))?;
+
+    Ok(raw_transaction)
+}
+
 // not used internally by chainhook; exported for ordhook
 pub async fn try_download_block_bytes_with_retry(
     http_client: HttpClient,
@@ -238,16 +376,26 @@ pub async fn try_download_block_bytes_with_retry(
 ) -> Result<Vec<u8>, String> {
     let block_hash =
         retrieve_block_hash_with_retry(&http_client, &block_height, &bitcoin_config, &ctx)
-            .await
-            .unwrap();
+            .await?;
+    try_download_block_bytes_by_hash_with_retry(http_client, block_hash, bitcoin_config, ctx).await
+}
 
+/// Same as [try_download_block_bytes_with_retry], for callers that already resolved the block hash.
+pub async fn try_download_block_bytes_by_hash_with_retry(
+    http_client: HttpClient,
+    block_hash: String,
+    bitcoin_config: BitcoinConfig,
+    ctx: Context,
+) -> Result<Vec<u8>, String> {
     let mut errors_count = 0;
 
     let response = loop {
+        let endpoint_index = bitcoin_config.rpc_endpoints.active_index();
         match download_block(&http_client, &block_hash, &bitcoin_config, &ctx).await {
             Ok(result) => break result,
             Err(_e) => {
                 errors_count += 1;
+                bitcoin_config.rpc_endpoints.fail_over(endpoint_index, &ctx);
                 if errors_count > 1 {
                     ctx.try_log(|logger| {
                         slog::warn!(
@@ -264,32 +412,73 @@ pub async fn try_download_block_bytes_with_retry(
     Ok(response)
 }
 
+/// Posts a JSON-RPC request to the bitcoind node at `rpc_url`. Every call to bitcoind goes through here so that they all
+/// draw from the same `rate_limiter` budget. bitcoind answers 503 "Work queue depth exceeded" when more requests are
+/// queued than `-rpcworkqueue`, such responses pause every user of the rate limiter, see [BitcoindRpcRateLimiter].
+pub async fn send_bitcoind_rpc_request<B: serde::Serialize + ?Sized>(
+    http_client: &HttpClient,
+    rpc_url: &str,
+    (username, password): &(String, String),
+    body: &B,
+    rate_limiter: &BitcoindRpcRateLimiter,
+    ctx: &Context,
+) -> Result<reqwest::Response, String> {
+    rate_limiter.acquire().await;
+    let res = http_client
+        .post(rpc_url)
+        .basic_auth(username, Some(password))
+        .header("Content-Type", "application/json")
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| format!("unable to send request ({})", e))?;
+    if res.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE {
+        rate_limiter.report_overload(ctx);
+        return Err("bitcoind is overloaded (work queue depth exceeded)".to_string());
+    }
+    rate_limiter.report_success();
+    Ok(res)
+}
+
+/// Same as [send_bitcoind_rpc_request], for the active endpoint of `bitcoin_config`.
+async fn send_rpc_request<B: serde::Serialize + ?Sized>(
+    http_client: &HttpClient,
+    body: &B,
+    bitcoin_config: &BitcoinConfig,
+    ctx: &Context,
+) -> Result<reqwest::Response, String> {
+    send_bitcoind_rpc_request(
+        http_client,
+        bitcoin_config.rpc_url(),
+        &bitcoin_config.rpc_credentials()?,
+        body,
+        bitcoin_config.rpc_endpoints.rate_limiter(),
+        ctx,
+    )
+    .await
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct RpcErrorResponse {
     pub error: RpcError,
 }
 
+/// Downloads a block as the JSON-RPC response of `getblock`. Blocks come with the prevout of every input inline when
+/// bitcoind supports it, otherwise they are downloaded with verbosity 2 and their prevouts looked up separately.
 pub async fn download_block(
     http_client: &HttpClient,
     block_hash: &str,
     bitcoin_config: &BitcoinConfig,
-    _ctx: &Context,
+    ctx: &Context,
 ) -> Result<Vec<u8>, String> {
+    let inline_prevouts = bitcoin_config.rpc_endpoints.inline_prevouts();
     let body = json!({
         "jsonrpc": "1.0",
         "id": "chainhook-cli",
         "method": "getblock",
-        "params": [block_hash, 3]
+        "params": [block_hash, if inline_prevouts { 3 } else { 2 }]
     });
-    let res = http_client
-        .post(&bitcoin_config.rpc_url)
-        .basic_auth(&bitcoin_config.username, Some(&bitcoin_config.password))
-        .header("Content-Type", "application/json")
-        .header("Host", &bitcoin_config.rpc_url[7..])
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| format!("unable to send request ({})", e))?;
+    let res = send_rpc_request(http_client, &body, bitcoin_config, ctx).await?;
 
     // Check status code
     if !res.status().is_success() {
@@ -313,9 +502,220 @@ pub async fn download_block(
         ));
     }
 
+    if !inline_prevouts {
+        return resolve_block_prevouts(http_client, rpc_response_bytes, bitcoin_config, ctx).await;
+    }
     Ok(rpc_response_bytes)
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct PrevoutTransaction {
+    blockhash: String,
+    vout: Vec<PrevoutTransactionOutput>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PrevoutTransactionOutput {
+    #[serde(with = "bitcoin::amount::serde::as_btc")]
+    value: Amount,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PrevoutBlockHeader {
+    height: u64,
+}
+
+/// Transactions, and then block headers, looked up per JSON-RPC batch when resolving the prevouts of a block.
+const PREVOUT_RPC_BATCH_SIZE: usize = 100;
+
+/// Calls `method` once per entry of `params` with a single JSON-RPC batch request. Results are returned in the same order
+/// as `params`.
+async fn call_bitcoind_rpc_batch<T: serde::de::DeserializeOwned>(
+    http_client: &HttpClient,
+    method: &str,
+    params: Vec<serde_json::Value>,
+    bitcoin_config: &BitcoinConfig,
+    ctx: &Context,
+) -> Result<Vec<T>, String> {
+    if params.is_empty() {
+        return Ok(vec![]);
+    }
+    let body: Vec<_> = params
+        .iter()
+        .enumerate()
+        .map(|(index, params)| {
+            json!({
+                "jsonrpc": "1.0",
+                "id": index,
+                "method": method,
+                "params": params
+            })
+        })
+        .collect();
+    let responses = send_rpc_request(http_client, &body, bitcoin_config, ctx)
+        .await?
+        .json::<Vec<bitcoincore_rpc::jsonrpc::Response>>()
+        .await
+        .map_err(|e| format!("unable to parse response ({})", e))?;
+    order_batch_responses(responses, params.len(), method)
+}
+
+/// Matches the responses of a JSON-RPC batch to its `count` requests by id, since batch responses are not guaranteed to
+/// come back in request order.
+pub fn order_batch_responses<T: serde::de::DeserializeOwned>(
+    responses: Vec<bitcoincore_rpc::jsonrpc::Response>,
+    count: usize,
+    method: &str,
+) -> Result<Vec<T>, String> {
+    let mut results: Vec<Option<T>> = (0..count).map(|_| None).collect();
+    for response in responses.into_iter() {
+        let index = response
+            .id
+            .as_u64()
+            .map(|id| id as usize)
+            .filter(|id| *id < count)
+            .ok_or(format!("unexpected response id {}", response.id))?;
+        let result = response
+            .result::<T>()
+            .map_err(|e| format!("unable to parse {method} response ({})", e))?;
+        results[index] = Some(result);
+    }
+    results
+        .into_iter()
+        .enumerate()
+        .map(|(index, result)| result.ok_or(format!("missing {method} response #{index}")))
+        .collect()
+}
+
+/// Txids of the transactions spent by `block` that are neither in `spent_transactions` nor already carried as prevouts,
+/// each listed once.
+pub fn missing_spent_txids(
+    block: &BitcoinBlockFullBreakdown,
+    spent_transactions: &HashMap<String, (u64, Vec<Amount>)>,
+) -> Vec<String> {
+    let mut seen = HashSet::new();
+    block
+        .tx
+        .iter()
+        .flat_map(|tx| tx.vin.iter())
+        .filter(|input| input.prevout.is_none())
+        .filter_map(|input| input.txid.as_ref())
+        .filter(|txid| !spent_transactions.contains_key(*txid) && seen.insert(*txid))
+        .cloned()
+        .collect()
+}
+
+/// Sets the prevout of every input from the block height and output values of the transactions they spend, keyed by
+/// txid. Inputs that already carry a prevout are left as they are.
+pub fn fill_block_prevouts(
+    block: &mut BitcoinBlockFullBreakdown,
+    spent_transactions: &HashMap<String, (u64, Vec<Amount>)>,
+) -> Result<(), String> {
+    for tx in block.tx.iter_mut() {
+        for (index, input) in tx.vin.iter_mut().enumerate() {
+            if input.is_coinbase() || input.prevout.is_some() {
+                continue;
+            }
+            let (Some(txid), Some(vout)) = (&input.txid, input.vout) else {
+                continue;
+            };
+            let value = spent_transactions
+                .get(txid)
+                .and_then(|(height, values)| Some((*height, *values.get(vout as usize)?)));
+            let Some((height, value)) = value else {
+                return Err(format!(
+                    "unable to find prevout {txid}:{vout} of transaction {}, input #{index}",
+                    tx.txid
+                ));
+            };
+            input.prevout = Some(BitcoinTransactionInputPrevoutFullBreakdown { height, value });
+        }
+    }
+    Ok(())
+}
+
+/// Completes a block downloaded with `getblock` verbosity 2, which has no prevouts. Outputs spent within the block are
+/// read from the block itself, the others are looked up with batched `getrawtransaction` and `getblockheader` calls,
+/// which needs bitcoind to run with `txindex=1`. The block is re-encoded as a JSON-RPC response so it goes through
+/// [parse_downloaded_block] like any other download.
+async fn resolve_block_prevouts(
+    http_client: &HttpClient,
+    downloaded_block: Vec<u8>,
+    bitcoin_config: &BitcoinConfig,
+    ctx: &Context,
+) -> Result<Vec<u8>, String> {
+    let mut block = parse_downloaded_block(downloaded_block)?;
+    let mut spent_transactions: HashMap<String, (u64, Vec<Amount>)> = block
+        .tx
+        .iter()
+        .map(|tx| {
+            (
+                tx.txid.clone(),
+                (
+                    block.height as u64,
+                    tx.vout.iter().map(|output| output.value).collect(),
+                ),
+            )
+        })
+        .collect();
+    let mut block_heights: HashMap<String, u64> = HashMap::new();
+    for txids in missing_spent_txids(&block, &spent_transactions).chunks(PREVOUT_RPC_BATCH_SIZE) {
+        let transactions: Vec<PrevoutTransaction> = call_bitcoind_rpc_batch(
+            http_client,
+            "getrawtransaction",
+            txids.iter().map(|txid| json!([txid, true])).collect(),
+            bitcoin_config,
+            ctx,
+        )
+        .await?;
+        let unknown_block_hashes: Vec<String> = transactions
+            .iter()
+            .map(|transaction| transaction.blockhash.clone())
+            .filter(|block_hash| !block_heights.contains_key(block_hash))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        for block_hashes in unknown_block_hashes.chunks(PREVOUT_RPC_BATCH_SIZE) {
+            let headers: Vec<PrevoutBlockHeader> = call_bitcoind_rpc_batch(
+                http_client,
+                "getblockheader",
+                block_hashes
+                    .iter()
+                    .map(|block_hash| json!([block_hash, true]))
+                    .collect(),
+                bitcoin_config,
+                ctx,
+            )
+            .await?;
+            for (block_hash, header) in block_hashes.iter().zip(headers.into_iter()) {
+                block_heights.insert(block_hash.clone(), header.height);
+            }
+        }
+        for (txid, transaction) in txids.iter().zip(transactions.into_iter()) {
+            spent_transactions.insert(
+                txid.clone(),
+                (
+                    block_heights[&transaction.blockhash],
+                    transaction.vout.iter().map(|output| output.value).collect(),
+                ),
+            );
+        }
+    }
+    try_debug!(
+        ctx,
+        "Resolved prevouts of block #{} from {} transactions",
+        block.height,
+        spent_transactions.len() - block.tx.len()
+    );
+    fill_block_prevouts(&mut block, &spent_transactions)?;
+    serde_json::to_vec(&json!({
+        "result": block,
+        "error": null,
+        "id": "chainhook-cli"
+    }))
+    .map_err(|e| format!("unable to serialize block ({})", e))
+}
+
 pub fn parse_downloaded_block(
     downloaded_block: Vec<u8>,
 ) -> Result<BitcoinBlockFullBreakdown, String> {
@@ -457,6 +857,7 @@ pub fn standardize_bitcoin_block(
         timestamp: block.time as u32,
         metadata: BitcoinBlockMetadata {
             network: network.clone(),
+            first_operation_sequence: None,
         },
         transactions,
     })
@@ -0,0 +1,175 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hiro_system_kit::slog;
+
+use crate::try_warn;
+use crate::utils::Context;
+
+/// Pause after bitcoind first reports an overload, doubled on each overload reported after the pause.
+const MIN_OVERLOAD_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_OVERLOAD_BACKOFF: Duration = Duration::from_secs(30);
+/// Requests per second regained after each successful request, once an overload lowered the rate.
+const RATE_RECOVERY_STEP: f64 = 0.1;
+
+#[derive(Debug)]
+struct RateLimiterState {
+    /// Requests per second currently allowed, between 1 and the max.
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+    backoff: Duration,
+    paused_until: Option<Instant>,
+}
+
+/// Token bucket capping the requests sent to bitcoind, shared by every clone so that all download workers draw from the
+/// same budget. When bitcoind reports that its work queue is full (HTTP 503), every user pauses with an exponential
+/// backoff and the rate is halved, then it recovers gradually as requests succeed. Without a max rate only the backoff
+/// applies.
+#[derive(Debug, Clone)]
+pub struct BitcoindRpcRateLimiter {
+    max_rps: Option<u32>,
+    state: Arc<Mutex<RateLimiterState>>,
+}
+
+impl BitcoindRpcRateLimiter {
+    pub fn new(max_rps: Option<u32>) -> Self {
+        let max_rps = max_rps.filter(|max_rps| *max_rps > 0);
+        let rate = max_rps.unwrap_or(0) as f64;
+        BitcoindRpcRateLimiter {
+            max_rps,
+            state: Arc::new(Mutex::new(RateLimiterState {
+                rate,
+                tokens: rate,
+                last_refill: Instant::now(),
+                backoff: Duration::ZERO,
+                paused_until: None,
+            })),
+        }
+    }
+
+    pub fn max_rps(&self) -> Option<u32> {
+        self.max_rps
+    }
+
+    /// Waits until a request can be sent.
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes a token if one is available at `now`, otherwise returns how long to wait before trying again.
+    fn try_acquire(&self, now: Instant) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(paused_until) = state.paused_until {
+            if now < paused_until {
+                return Some(paused_until - now);
+            }
+            state.paused_until = None;
+        }
+        if self.max_rps.is_none() {
+            return None;
+        }
+        let elapsed = now.saturating_duration_since(state.last_refill);
+        state.tokens = (state.tokens + elapsed.as_secs_f64() * state.rate).min(state.rate);
+        state.last_refill = now;
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - state.tokens) / state.rate))
+        }
+    }
+
+    pub fn report_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.backoff = Duration::ZERO;
+        if let Some(max_rps) = self.max_rps {
+            state.rate = (state.rate + RATE_RECOVERY_STEP).min(max_rps as f64);
+        }
+    }
+
+    /// Pauses every user after bitcoind rejected a request because it is overloaded. Overloads reported during a pause
+    /// are ignored, so that workers failing together only back off once.
+    pub fn report_overload(&self, ctx: &Context) {
+        self.report_overload_at(Instant::now(), ctx)
+    }
+
+    fn report_overload_at(&self, now: Instant, ctx: &Context) {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .paused_until
+            .is_some_and(|paused_until| now < paused_until)
+        {
+            return;
+        }
+        state.backoff = (state.backoff * 2).clamp(MIN_OVERLOAD_BACKOFF, MAX_OVERLOAD_BACKOFF);
+        state.paused_until = Some(now + state.backoff);
+        if self.max_rps.is_some() {
+            state.rate = (state.rate / 2.0).max(1.0);
+            state.tokens = state.tokens.min(state.rate);
+        }
+        try_warn!(
+            ctx,
+            "bitcoind: Overloaded, pausing requests for {}ms",
+            state.backoff.as_millis()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::utils::Context;
+
+    use super::BitcoindRpcRateLimiter;
+
+    #[test]
+    fn limits_requests_per_second() {
+        let limiter = BitcoindRpcRateLimiter::new(Some(2));
+        let now = Instant::now();
+        assert_eq!(limiter.try_acquire(now), None);
+        assert_eq!(limiter.try_acquire(now), None);
+        let wait = limiter.try_acquire(now).unwrap();
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+        assert_eq!(limiter.try_acquire(now + Duration::from_millis(500)), None);
+    }
+
+    #[test]
+    fn backs_off_once_per_overload() {
+        let ctx = Context::empty();
+        let limiter = BitcoindRpcRateLimiter::new(Some(10));
+        let now = Instant::now();
+        limiter.report_overload_at(now, &ctx);
+        // A second worker rejected by the same overload must not extend the pause.
+        limiter.report_overload_at(now, &ctx);
+        assert_eq!(limiter.try_acquire(now), Some(Duration::from_millis(500)));
+        let after_pause = now + Duration::from_millis(500);
+        assert_eq!(limiter.try_acquire(after_pause), None);
+        assert_eq!(limiter.state.lock().unwrap().rate, 5.0);
+
+        limiter.report_overload_at(after_pause, &ctx);
+        assert_eq!(
+            limiter.try_acquire(after_pause),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(limiter.state.lock().unwrap().rate, 2.5);
+        limiter.report_success();
+        let state = limiter.state.lock().unwrap();
+        assert!((state.rate - 2.6).abs() < 1e-9);
+        assert_eq!(state.backoff, Duration::ZERO);
+    }
+
+    #[test]
+    fn only_backs_off_without_max_rate() {
+        let limiter = BitcoindRpcRateLimiter::new(None);
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert_eq!(limiter.try_acquire(now), None);
+        }
+        limiter.report_overload_at(now, &Context::empty());
+        assert_eq!(limiter.try_acquire(now), Some(Duration::from_millis(500)));
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use hiro_system_kit::slog;
+
+use crate::try_warn;
+use crate::utils::Context;
+
+use super::BitcoindRpcRateLimiter;
+
+/// The bitcoind RPC urls a node can be reached at, in order of preference, along with the one currently in use.
+///
+/// Clones share the active endpoint, so a failover decided by one download worker is picked up by every other user of
+/// the same configuration. All endpoints are expected to accept the same RPC credentials and run the same bitcoind
+/// version.
+#[derive(Debug, Clone)]
+pub struct BitcoindRpcEndpoints {
+    urls: Arc<Vec<String>>,
+    active: Arc<AtomicUsize>,
+    /// Whether `getblock` verbosity 3 returns the prevout of every input, see
+    /// [crate::utils::bitcoind::bitcoind_probe_inline_prevouts].
+    inline_prevouts: Arc<AtomicBool>,
+    rate_limiter: BitcoindRpcRateLimiter,
+}
+
+impl BitcoindRpcEndpoints {
+    pub fn new(primary_url: &str, fallback_urls: &[String]) -> Self {
+        let mut urls = vec![primary_url.to_string()];
+        urls.extend(fallback_urls.iter().cloned());
+        BitcoindRpcEndpoints {
+            urls: Arc::new(urls),
+            active: Arc::new(AtomicUsize::new(0)),
+            inline_prevouts: Arc::new(AtomicBool::new(true)),
+            rate_limiter: BitcoindRpcRateLimiter::new(None),
+        }
+    }
+
+    /// Caps the requests sent to the nodes at `max_rps` per second, across every clone.
+    pub fn with_max_rps(mut self, max_rps: Option<u32>) -> Self {
+        self.rate_limiter = BitcoindRpcRateLimiter::new(max_rps);
+        self
+    }
+
+    pub fn rate_limiter(&self) -> &BitcoindRpcRateLimiter {
+        &self.rate_limiter
+    }
+
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn active_url(&self) -> &str {
+        &self.urls[self.active_index()]
+    }
+
+    /// Moves on to the next endpoint after the one at `failed_index` errored. Callers pass the index they were using so
+    /// that several workers failing on the same node only advance once. Returns `false` when there is nothing to fail
+    /// over to.
+    pub fn fail_over(&self, failed_index: usize, ctx: &Context) -> bool {
+        if self.urls.len() < 2 {
+            return false;
+        }
+        let next_index = (failed_index + 1) % self.urls.len();
+        if self
+            .active
+            .compare_exchange(
+                failed_index,
+                next_index,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+        {
+            try_warn!(
+                ctx,
+                "bitcoind: Failing over from {} to {}",
+                self.urls[failed_index],
+                self.urls[next_index]
+            );
+        }
+        true
+    }
+
+    pub fn inline_prevouts(&self) -> bool {
+        self.inline_prevouts.load(Ordering::Relaxed)
+    }
+
+    pub fn set_inline_prevouts(&self, inline_prevouts: bool) {
+        self.inline_prevouts
+            .store(inline_prevouts, Ordering::Relaxed);
+    }
+
+    /// Makes the endpoint at `index` the active one, e.g. when the active node lags behind the others.
+    pub fn switch_to(&self, index: usize) {
+        if index < self.urls.len() {
+            self.active.store(index, Ordering::Relaxed);
+        }
+    }
+}
+
+impl PartialEq for BitcoindRpcEndpoints {
+    fn eq(&self, other: &Self) -> bool {
+        self.urls == other.urls
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::Context;
+
+    use super::BitcoindRpcEndpoints;
+
+    #[test]
+    fn fails_over_once_per_failed_endpoint() {
+        let ctx = Context::empty();
+        let endpoints =
+            BitcoindRpcEndpoints::new("http://node-a:8332", &["http://node-b:8332".to_string()]);
+        let shared = endpoints.clone();
+        assert!(endpoints.fail_over(0, &ctx));
+        // A second worker that was also using node-a must not move the shared endpoint back.
+        assert!(shared.fail_over(0, &ctx));
+        assert_eq!(shared.active_url(), "http://node-b:8332");
+        assert!(endpoints.fail_over(1, &ctx));
+        assert_eq!(shared.active_url(), "http://node-a:8332");
+    }
+
+    #[test]
+    fn keeps_single_endpoint() {
+        let endpoints = BitcoindRpcEndpoints::new("http://node-a:8332", &[]);
+        assert!(!endpoints.fail_over(0, &Context::empty()));
+        assert_eq!(endpoints.active_url(), "http://node-a:8332");
+    }
+}
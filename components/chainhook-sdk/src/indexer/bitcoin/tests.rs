@@ -204,3 +204,118 @@ fn test_bitcoin_vector_040() {
 // fn test_bitcoin_vector_041() {
 //     process_bitcoin_blocks_and_check_expectations(helpers::shapes::get_vector_041());
 // }
+
+#[test]
+fn fills_prevouts_of_blocks_downloaded_without_them() {
+    use std::collections::HashMap;
+
+    use bitcoincore_rpc::bitcoin::Amount;
+
+    use super::{fill_block_prevouts, BitcoinBlockFullBreakdown};
+
+    let mut block: BitcoinBlockFullBreakdown = serde_json::from_value(serde_json::json!({
+        "hash": "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5",
+        "height": 840000,
+        "time": 1713571767,
+        "nonce": 3932395645u32,
+        "previousblockhash": "0000000000000000000172014ba58d66455762add0512355ad651207918494ab",
+        "confirmations": 1,
+        "tx": [
+            { "txid": "aa", "vin": [{ "sequence": 0 }], "vout": [] },
+            {
+                "txid": "bb",
+                "vin": [
+                    { "sequence": 0, "txid": "cc", "vout": 1, "scriptSig": { "hex": "" } },
+                    { "sequence": 0, "txid": "aa", "vout": 0, "scriptSig": { "hex": "" } }
+                ],
+                "vout": []
+            }
+        ]
+    }))
+    .unwrap();
+    let mut spent_transactions = HashMap::new();
+    spent_transactions.insert(
+        "aa".to_string(),
+        (840000, vec![Amount::from_sat(312500000)]),
+    );
+    spent_transactions.insert(
+        "cc".to_string(),
+        (839999, vec![Amount::from_sat(1), Amount::from_sat(546)]),
+    );
+
+    fill_block_prevouts(&mut block, &spent_transactions).unwrap();
+
+    let prevouts: Vec<(u64, u64)> = block.tx[1]
+        .vin
+        .iter()
+        .map(|input| {
+            let prevout = input.prevout.as_ref().unwrap();
+            (prevout.height, prevout.value.to_sat())
+        })
+        .collect();
+    assert_eq!(prevouts, vec![(839999, 546), (840000, 312500000)]);
+    assert!(block.tx[0].vin[0].prevout.is_none());
+
+    spent_transactions.remove("cc");
+    block.tx[1].vin[0].prevout = None;
+    assert!(fill_block_prevouts(&mut block, &spent_transactions).is_err());
+}
+
+#[test]
+fn lists_each_missing_spent_transaction_once() {
+    use std::collections::HashMap;
+
+    use bitcoincore_rpc::bitcoin::Amount;
+
+    use super::{missing_spent_txids, BitcoinBlockFullBreakdown};
+
+    let block: BitcoinBlockFullBreakdown = serde_json::from_value(serde_json::json!({
+        "hash": "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5",
+        "height": 840000,
+        "time": 1713571767,
+        "nonce": 3932395645u32,
+        "previousblockhash": "0000000000000000000172014ba58d66455762add0512355ad651207918494ab",
+        "confirmations": 1,
+        "tx": [
+            { "txid": "aa", "vin": [{ "sequence": 0 }], "vout": [] },
+            {
+                "txid": "bb",
+                "vin": [
+                    { "sequence": 0, "txid": "cc", "vout": 0, "scriptSig": { "hex": "" } },
+                    { "sequence": 0, "txid": "aa", "vout": 0, "scriptSig": { "hex": "" } },
+                    { "sequence": 0, "txid": "cc", "vout": 1, "scriptSig": { "hex": "" } },
+                    { "sequence": 0, "txid": "dd", "vout": 0, "scriptSig": { "hex": "" } }
+                ],
+                "vout": []
+            }
+        ]
+    }))
+    .unwrap();
+    let mut spent_transactions = HashMap::new();
+    spent_transactions.insert("aa".to_string(), (840000, vec![Amount::from_sat(1)]));
+
+    assert_eq!(
+        missing_spent_txids(&block, &spent_transactions),
+        vec!["cc".to_string(), "dd".to_string()]
+    );
+}
+
+#[test]
+fn orders_batch_responses_by_id() {
+    use super::order_batch_responses;
+
+    let responses = || -> Vec<bitcoincore_rpc::jsonrpc::Response> {
+        serde_json::from_value(serde_json::json!([
+            { "result": "b", "error": null, "id": 1 },
+            { "result": "a", "error": null, "id": 0 }
+        ]))
+        .unwrap()
+    };
+    assert_eq!(
+        order_batch_responses::<String>(responses(), 2, "getblockhash").unwrap(),
+        vec!["a".to_string(), "b".to_string()]
+    );
+    // A request without a response, or a response to an unknown request.
+    assert!(order_batch_responses::<String>(responses(), 3, "getblockhash").is_err());
+    assert!(order_batch_responses::<String>(responses(), 1, "getblockhash").is_err());
+}
@@ -10,12 +10,23 @@ use chainhook_types::{
 use hiro_system_kit::slog;
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 
+/// Tracks recent block headers received from bitcoind, the forks they form and which of them is canonical. Blocks are
+/// pruned from the scratch pad once they are buried under [CONFIRMED_SEGMENT_MINIMUM_LENGTH] blocks.
 pub struct ForkScratchPad {
     canonical_fork_id: usize,
     orphans: BTreeSet<BlockIdentifier>,
     forks: BTreeMap<usize, ChainSegment>,
     headers_store: BTreeMap<BlockIdentifier, BlockHeader>,
 }
+
+/// Serializable representation of a [ForkScratchPad], used to persist or inspect fork state.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ForkScratchPadSnapshot {
+    pub canonical_fork_id: usize,
+    pub orphans: Vec<BlockIdentifier>,
+    pub forks: BTreeMap<usize, ChainSegment>,
+    pub headers: Vec<BlockHeader>,
+}
 pub const CONFIRMED_SEGMENT_MINIMUM_LENGTH: i32 = 7;
 impl Default for ForkScratchPad {
     fn default() -> Self {
@@ -36,6 +47,77 @@ impl ForkScratchPad {
         }
     }
 
+    /// Restores a scratch pad from a snapshot previously produced by [ForkScratchPad::to_snapshot].
+    pub fn from_snapshot(snapshot: ForkScratchPadSnapshot) -> ForkScratchPad {
+        let mut forks = snapshot.forks;
+        if forks.is_empty() {
+            forks.insert(0, ChainSegment::new());
+        }
+        ForkScratchPad {
+            canonical_fork_id: snapshot.canonical_fork_id,
+            orphans: snapshot.orphans.into_iter().collect(),
+            forks,
+            headers_store: snapshot
+                .headers
+                .into_iter()
+                .map(|h| (h.block_identifier.clone(), h))
+                .collect(),
+        }
+    }
+
+    pub fn to_snapshot(&self) -> ForkScratchPadSnapshot {
+        ForkScratchPadSnapshot {
+            canonical_fork_id: self.canonical_fork_id,
+            orphans: self.orphans.iter().cloned().collect(),
+            forks: self.forks.clone(),
+            headers: self.headers_store.values().cloned().collect(),
+        }
+    }
+
+    pub fn get_canonical_fork_id(&self) -> usize {
+        self.canonical_fork_id
+    }
+
+    pub fn get_canonical_fork(&self) -> Option<&ChainSegment> {
+        self.forks.get(&self.canonical_fork_id)
+    }
+
+    /// Returns the tip of the canonical fork, if any block has been processed yet.
+    pub fn get_canonical_tip(&self) -> Option<&BlockIdentifier> {
+        self.get_canonical_fork()
+            .and_then(|fork| fork.block_ids.front())
+    }
+
+    /// Returns every fork currently tracked, including the canonical one, keyed by fork id.
+    pub fn get_forks(&self) -> &BTreeMap<usize, ChainSegment> {
+        &self.forks
+    }
+
+    /// Returns blocks whose parent is unknown and that are waiting to be appended to a fork.
+    pub fn get_orphans(&self) -> &BTreeSet<BlockIdentifier> {
+        &self.orphans
+    }
+
+    pub fn get_header(&self, block_identifier: &BlockIdentifier) -> Option<&BlockHeader> {
+        self.headers_store.get(block_identifier)
+    }
+
+    /// Returns the heights of canonical blocks that have not yet been buried deep enough to be considered confirmed, in
+    /// ascending order.
+    pub fn get_heights_awaiting_confirmation(&self) -> Vec<u64> {
+        let Some(fork) = self.get_canonical_fork() else {
+            return vec![];
+        };
+        let mut heights = fork
+            .block_ids
+            .iter()
+            .take(CONFIRMED_SEGMENT_MINIMUM_LENGTH as usize - 1)
+            .map(|b| b.index)
+            .collect::<Vec<_>>();
+        heights.reverse();
+        heights
+    }
+
     pub fn can_process_header(&self, header: &BlockHeader) -> bool {
         if self.headers_store.is_empty() {
             return true;
@@ -412,3 +494,46 @@ impl ForkScratchPad {
         Err(ChainSegmentIncompatibility::ParentBlockUnknown)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::indexer::tests::helpers::bitcoin_blocks;
+    use crate::utils::{AbstractBlock, Context};
+
+    use super::ForkScratchPad;
+
+    #[test]
+    fn exposes_fork_state_and_survives_snapshot_roundtrip() {
+        let ctx = Context::empty();
+        let mut fork_scratch_pad = ForkScratchPad::new();
+        for block in [
+            bitcoin_blocks::A1(None),
+            bitcoin_blocks::B1(None),
+            bitcoin_blocks::B2(None),
+            bitcoin_blocks::C2(None),
+        ] {
+            fork_scratch_pad
+                .process_header(block.get_header(), &ctx)
+                .unwrap();
+        }
+
+        assert_eq!(
+            fork_scratch_pad.get_canonical_tip(),
+            Some(&bitcoin_blocks::C2(None).block_identifier)
+        );
+        assert_eq!(fork_scratch_pad.get_forks().len(), 2);
+        assert_eq!(
+            fork_scratch_pad.get_heights_awaiting_confirmation(),
+            vec![1, 2, 3]
+        );
+
+        let snapshot = fork_scratch_pad.to_snapshot();
+        let serialized = serde_json::to_string(&snapshot).unwrap();
+        let restored = ForkScratchPad::from_snapshot(serde_json::from_str(&serialized).unwrap());
+        assert_eq!(restored.to_snapshot(), snapshot);
+        assert_eq!(
+            restored.get_canonical_tip(),
+            fork_scratch_pad.get_canonical_tip()
+        );
+    }
+}
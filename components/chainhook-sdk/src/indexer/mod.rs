@@ -1,6 +1,7 @@
 pub mod bitcoin;
 pub mod fork_scratch_pad;
 
+use crate::utils::bitcoind::bitcoind_rpc_credentials;
 use crate::utils::{AbstractBlock, Context};
 
 use chainhook_types::{
@@ -10,6 +11,7 @@ use hiro_system_kit::slog;
 
 use std::collections::VecDeque;
 
+use self::bitcoin::BitcoindRpcEndpoints;
 use self::fork_scratch_pad::ForkScratchPad;
 
 #[derive(Deserialize, Debug, Clone, Default)]
@@ -35,13 +37,25 @@ impl BitcoinChainContext {
 #[derive(Debug, Clone, PartialEq)]
 pub struct IndexerConfig {
     pub bitcoin_network: BitcoinNetwork,
-    pub bitcoind_rpc_url: String,
+    pub bitcoind_rpc_endpoints: BitcoindRpcEndpoints,
     pub bitcoind_rpc_username: String,
     pub bitcoind_rpc_password: String,
+    /// bitcoind `.cookie` file to authenticate with instead of the username and password.
+    pub bitcoind_rpc_cookie_path: Option<String>,
     pub bitcoin_block_signaling: BitcoinBlockSignaling,
     pub prometheus_monitoring_port: Option<u16>,
 }
 
+impl IndexerConfig {
+    pub fn bitcoind_rpc_credentials(&self) -> Result<(String, String), String> {
+        bitcoind_rpc_credentials(
+            &self.bitcoind_rpc_username,
+            &self.bitcoind_rpc_password,
+            &self.bitcoind_rpc_cookie_path,
+        )
+    }
+}
+
 pub struct Indexer {
     pub config: IndexerConfig,
     bitcoin_blocks_pool: ForkScratchPad,
@@ -67,9 +81,13 @@ impl Indexer {
     ) -> Result<Option<BlockchainEvent>, String> {
         self.bitcoin_blocks_pool.process_header(header, ctx)
     }
+
+    pub fn get_fork_scratch_pad(&self) -> &ForkScratchPad {
+        &self.bitcoin_blocks_pool
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub struct ChainSegment {
     pub block_ids: VecDeque<BlockIdentifier>,
 }
@@ -51,6 +51,7 @@ pub fn generate_test_bitcoin_block(
         transactions,
         metadata: BitcoinBlockMetadata {
             network: chainhook_types::BitcoinNetwork::Regtest,
+            first_operation_sequence: None,
         },
     }
 }
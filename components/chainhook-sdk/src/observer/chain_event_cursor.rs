@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chainhook_types::BlockIdentifier;
+
+use crate::utils::{read_file_content_at_path, write_file_content_at_path};
+
+/// Number of most recently applied blocks remembered by a [ChainEventCursor]. Blocks older than this window are always
+/// considered as already delivered.
+pub const CHAIN_EVENT_CURSOR_WINDOW: usize = 32;
+
+/// Keeps track of the blocks that were last delivered to the sidecar as applied, so that apply events emitted again after
+/// a restart (e.g. when ZMQ re-sends a block or parents are re-fetched during a reorg) can be flagged as replays.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ChainEventCursor {
+    /// Recently applied blocks, in ascending height order.
+    pub applied_blocks: VecDeque<BlockIdentifier>,
+}
+
+impl ChainEventCursor {
+    pub fn new() -> ChainEventCursor {
+        ChainEventCursor {
+            applied_blocks: VecDeque::new(),
+        }
+    }
+
+    /// Loads a cursor from disk. A missing file yields an empty cursor.
+    pub fn load(path: &PathBuf) -> Result<ChainEventCursor, String> {
+        if !path.exists() {
+            return Ok(ChainEventCursor::new());
+        }
+        let bytes = read_file_content_at_path(path)?;
+        serde_json::from_slice(&bytes)
+            .map_err(|e| format!("unable to parse chain event cursor {}: {e}", path.display()))
+    }
+
+    pub fn save(&self, path: &PathBuf) -> Result<(), String> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| format!("unable to serialize chain event cursor: {e}"))?;
+        write_file_content_at_path(path, &bytes)
+    }
+
+    /// Returns true if an apply event for this block was already delivered.
+    pub fn is_replay(&self, block_identifier: &BlockIdentifier) -> bool {
+        if self
+            .applied_blocks
+            .iter()
+            .any(|b| b.index == block_identifier.index && b.hash == block_identifier.hash)
+        {
+            return true;
+        }
+        match self.applied_blocks.front() {
+            Some(oldest) if self.applied_blocks.len() == CHAIN_EVENT_CURSOR_WINDOW => {
+                block_identifier.index < oldest.index
+            }
+            _ => false,
+        }
+    }
+
+    pub fn record_apply(&mut self, block_identifier: &BlockIdentifier) {
+        self.applied_blocks
+            .retain(|b| b.index < block_identifier.index);
+        self.applied_blocks.push_back(block_identifier.clone());
+        while self.applied_blocks.len() > CHAIN_EVENT_CURSOR_WINDOW {
+            self.applied_blocks.pop_front();
+        }
+    }
+
+    pub fn record_rollback(&mut self, block_identifier: &BlockIdentifier) {
+        self.applied_blocks
+            .retain(|b| b.index < block_identifier.index);
+    }
+}
+
+/// Where a [ChainEventCursor] is persisted between restarts. Deployments without a durable local volume can provide a
+/// remote implementation so that the cursor survives the observer being rescheduled. Stores are called from the
+/// observer's async runtime, so remote implementations must await their I/O rather than block on it.
+#[async_trait]
+pub trait ChainEventCursorStore: Debug + Send + Sync {
+    /// Loads the stored cursor, or `None` if no cursor was ever saved to this store.
+    async fn load(&self) -> Result<Option<ChainEventCursor>, String>;
+
+    async fn save(&self, cursor: &ChainEventCursor) -> Result<(), String>;
+
+    /// Human readable location of the store, used in logs.
+    fn location(&self) -> String;
+}
+
+/// Stores the cursor as a JSON file on local disk.
+#[derive(Debug, Clone)]
+pub struct FileChainEventCursorStore {
+    pub path: PathBuf,
+}
+
+#[async_trait]
+impl ChainEventCursorStore for FileChainEventCursorStore {
+    async fn load(&self) -> Result<Option<ChainEventCursor>, String> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        ChainEventCursor::load(&self.path).map(Some)
+    }
+
+    async fn save(&self, cursor: &ChainEventCursor) -> Result<(), String> {
+        cursor.save(&self.path)
+    }
+
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_types::BlockIdentifier;
+
+    use super::{ChainEventCursor, ChainEventCursorStore, FileChainEventCursorStore};
+
+    fn block_id(index: u64, hash: &str) -> BlockIdentifier {
+        BlockIdentifier {
+            index,
+            hash: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn flags_replays_and_forgets_rolled_back_blocks() {
+        let mut cursor = ChainEventCursor::new();
+        cursor.record_apply(&block_id(100, "0xa100"));
+        cursor.record_apply(&block_id(101, "0xa101"));
+
+        assert!(cursor.is_replay(&block_id(101, "0xa101")));
+        assert!(!cursor.is_replay(&block_id(101, "0xb101")));
+        assert!(!cursor.is_replay(&block_id(102, "0xa102")));
+
+        cursor.record_rollback(&block_id(101, "0xa101"));
+        assert!(!cursor.is_replay(&block_id(101, "0xa101")));
+
+        let serialized = serde_json::to_vec(&cursor).unwrap();
+        let restored: ChainEventCursor = serde_json::from_slice(&serialized).unwrap();
+        assert_eq!(restored, cursor);
+    }
+
+    #[tokio::test]
+    async fn file_store_distinguishes_missing_cursor() {
+        let path =
+            std::env::temp_dir().join(format!("chain_event_cursor_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = FileChainEventCursorStore { path: path.clone() };
+        assert_eq!(store.load().await.unwrap(), None);
+
+        let mut cursor = ChainEventCursor::new();
+        cursor.record_apply(&block_id(100, "0xa100"));
+        store.save(&cursor).await.unwrap();
+        assert_eq!(store.load().await.unwrap(), Some(cursor));
+        let _ = std::fs::remove_file(&path);
+    }
+}
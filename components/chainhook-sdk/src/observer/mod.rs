@@ -1,23 +1,39 @@
+pub mod chain_event_cursor;
+mod p2p;
+mod poll;
 mod zmq;
 
+pub use p2p::start_p2p_block_hash_listener;
+pub use poll::start_block_hash_poller;
+pub use zmq::{start_zeromq_block_hash_listener, start_zeromq_raw_tx_listener};
+
 use crate::indexer::bitcoin::{
     build_http_client, download_and_parse_block_with_retry, standardize_bitcoin_block,
-    BitcoinBlockFullBreakdown,
+    BitcoinBlockFullBreakdown, BitcoindRpcEndpoints,
 };
+use crate::utils::bitcoind::bitcoind_rpc_credentials;
 use crate::utils::Context;
 
+use self::chain_event_cursor::{
+    ChainEventCursor, ChainEventCursorStore, FileChainEventCursorStore,
+};
+
 use chainhook_types::{
     BitcoinBlockData, BitcoinBlockSignaling, BitcoinChainEvent, BitcoinChainUpdatedWithBlocksData,
     BitcoinChainUpdatedWithReorgData, BitcoinNetwork, BlockIdentifier, BlockchainEvent,
+    OrdinalOperation,
 };
 use hiro_system_kit;
 use hiro_system_kit::slog;
 use rocket::serde::Deserialize;
 use rocket::Shutdown;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::path::PathBuf;
 use std::str;
 use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Deserialize)]
 pub struct NewTransaction {
@@ -36,9 +52,14 @@ pub enum Event {
 pub struct EventObserverConfig {
     pub bitcoind_rpc_username: String,
     pub bitcoind_rpc_password: String,
-    pub bitcoind_rpc_url: String,
+    pub bitcoind_rpc_cookie_path: Option<String>,
+    pub bitcoind_rpc_endpoints: BitcoindRpcEndpoints,
     pub bitcoin_block_signaling: BitcoinBlockSignaling,
     pub bitcoin_network: BitcoinNetwork,
+    /// When set, blocks delivered to the sidecar's chain event notifier are recorded in this store so that apply events
+    /// sent again after a restart are flagged as [HandleBlock::ReplayBlock]. The ZMQ and polling observers also resume
+    /// from the last block recorded, so blocks mined while they were stopped are delivered too.
+    pub chain_event_cursor_store: Option<Arc<dyn ChainEventCursorStore>>,
 }
 
 /// A builder that is used to create a general purpose [EventObserverConfig].
@@ -59,9 +80,14 @@ pub struct EventObserverConfig {
 pub struct EventObserverConfigBuilder {
     pub bitcoind_rpc_username: Option<String>,
     pub bitcoind_rpc_password: Option<String>,
+    pub bitcoind_rpc_cookie_path: Option<String>,
     pub bitcoind_rpc_url: Option<String>,
+    pub bitcoind_rpc_fallback_urls: Option<Vec<String>>,
     pub bitcoind_zmq_url: Option<String>,
+    pub bitcoind_poll_interval_ms: Option<u64>,
+    pub bitcoind_p2p_peer: Option<String>,
     pub bitcoin_network: Option<String>,
+    pub chain_event_cursor_path: Option<String>,
 }
 
 impl Default for EventObserverConfigBuilder {
@@ -75,9 +101,14 @@ impl EventObserverConfigBuilder {
         EventObserverConfigBuilder {
             bitcoind_rpc_username: None,
             bitcoind_rpc_password: None,
+            bitcoind_rpc_cookie_path: None,
             bitcoind_rpc_url: None,
+            bitcoind_rpc_fallback_urls: None,
             bitcoind_zmq_url: None,
+            bitcoind_poll_interval_ms: None,
+            bitcoind_p2p_peer: None,
             bitcoin_network: None,
+            chain_event_cursor_path: None,
         }
     }
 
@@ -93,24 +124,58 @@ impl EventObserverConfigBuilder {
         self
     }
 
+    /// Authenticates RPC calls with the bitcoind node's `.cookie` file instead of a username and password. The file is
+    /// read again on every call, so the node can be restarted.
+    pub fn bitcoind_rpc_cookie_path(&mut self, path: &str) -> &mut Self {
+        self.bitcoind_rpc_cookie_path = Some(path.to_string());
+        self
+    }
+
     /// Sets the bitcoind node's RPC url.
     pub fn bitcoind_rpc_url(&mut self, url: &str) -> &mut Self {
         self.bitcoind_rpc_url = Some(url.to_string());
         self
     }
 
+    /// Sets RPC urls of other bitcoind nodes to fail over to, in order, when the node at the RPC url errors or lags
+    /// behind. They must accept the same RPC credentials.
+    pub fn bitcoind_rpc_fallback_urls(&mut self, urls: &[String]) -> &mut Self {
+        self.bitcoind_rpc_fallback_urls = Some(urls.to_vec());
+        self
+    }
+
     /// Sets the bitcoind node's ZMQ url, used by the observer to receive new block events from bitcoind.
     pub fn bitcoind_zmq_url(&mut self, url: &str) -> &mut Self {
         self.bitcoind_zmq_url = Some(url.to_string());
         self
     }
 
+    /// Polls bitcoind for new blocks every `interval_ms` milliseconds instead of listening to ZMQ, for nodes that do
+    /// not expose it. Ignored when a ZMQ url is set.
+    pub fn bitcoind_poll_interval_ms(&mut self, interval_ms: u64) -> &mut Self {
+        self.bitcoind_poll_interval_ms = Some(interval_ms);
+        self
+    }
+
+    /// Follows the block announcements of a Bitcoin P2P peer (`host:port`) instead of listening to ZMQ. Blocks are
+    /// still downloaded over RPC. Ignored when a ZMQ url is set.
+    pub fn bitcoind_p2p_peer(&mut self, peer: &str) -> &mut Self {
+        self.bitcoind_p2p_peer = Some(peer.to_string());
+        self
+    }
+
     /// Sets the Bitcoin network. Must be a valid bitcoin network string according to [BitcoinNetwork::from_str].
     pub fn bitcoin_network(&mut self, network: &str) -> &mut Self {
         self.bitcoin_network = Some(network.to_string());
         self
     }
 
+    /// Sets the file used to remember which blocks were already delivered to the sidecar across restarts.
+    pub fn chain_event_cursor_path(&mut self, path: &str) -> &mut Self {
+        self.chain_event_cursor_path = Some(path.to_string());
+        self
+    }
+
     /// Attempts to convert a [EventObserverConfigBuilder] instance into an [EventObserverConfig], filling in
     /// defaults as necessary according to [EventObserverConfig::default].
     ///
@@ -126,11 +191,13 @@ impl EventObserverConfig {
         EventObserverConfig {
             bitcoind_rpc_username: "devnet".into(),
             bitcoind_rpc_password: "devnet".into(),
-            bitcoind_rpc_url: "http://localhost:18443".into(),
+            bitcoind_rpc_cookie_path: None,
+            bitcoind_rpc_endpoints: BitcoindRpcEndpoints::new("http://localhost:18443", &[]),
             bitcoin_block_signaling: BitcoinBlockSignaling::ZeroMQ(
                 "tcp://localhost:18543".to_string(),
             ),
             bitcoin_network: BitcoinNetwork::Regtest,
+            chain_event_cursor_store: None,
         }
     }
 
@@ -138,7 +205,8 @@ impl EventObserverConfig {
         BitcoinConfig {
             username: self.bitcoind_rpc_username.clone(),
             password: self.bitcoind_rpc_password.clone(),
-            rpc_url: self.bitcoind_rpc_url.clone(),
+            cookie_path: self.bitcoind_rpc_cookie_path.clone(),
+            rpc_endpoints: self.bitcoind_rpc_endpoints.clone(),
             network: self.bitcoin_network.clone(),
             bitcoin_block_signaling: self.bitcoin_block_signaling.clone(),
         }
@@ -164,16 +232,33 @@ impl EventObserverConfig {
             bitcoind_rpc_password: overrides
                 .and_then(|c| c.bitcoind_rpc_password.clone())
                 .unwrap_or_else(|| "devnet".to_string()),
-            bitcoind_rpc_url: overrides
-                .and_then(|c| c.bitcoind_rpc_url.clone())
-                .unwrap_or_else(|| "http://localhost:18443".to_string()),
-            bitcoin_block_signaling: overrides
-                .and_then(|c| c.bitcoind_zmq_url.as_ref())
-                .map(|url| BitcoinBlockSignaling::ZeroMQ(url.clone()))
-                .unwrap_or_else(|| {
-                    BitcoinBlockSignaling::ZeroMQ("tcp://localhost:18543".to_string())
-                }),
+            bitcoind_rpc_cookie_path: overrides.and_then(|c| c.bitcoind_rpc_cookie_path.clone()),
+            bitcoind_rpc_endpoints: BitcoindRpcEndpoints::new(
+                overrides
+                    .and_then(|c| c.bitcoind_rpc_url.as_deref())
+                    .unwrap_or("http://localhost:18443"),
+                overrides
+                    .and_then(|c| c.bitcoind_rpc_fallback_urls.as_deref())
+                    .unwrap_or_default(),
+            ),
+            bitcoin_block_signaling: match (
+                overrides.and_then(|c| c.bitcoind_zmq_url.as_ref()),
+                overrides.and_then(|c| c.bitcoind_p2p_peer.as_ref()),
+                overrides.and_then(|c| c.bitcoind_poll_interval_ms),
+            ) {
+                (Some(url), _, _) => BitcoinBlockSignaling::ZeroMQ(url.clone()),
+                (None, Some(peer), _) => BitcoinBlockSignaling::P2P(peer.clone()),
+                (None, None, Some(interval_ms)) => BitcoinBlockSignaling::Poll(interval_ms),
+                (None, None, None) => BitcoinBlockSignaling::ZeroMQ("tcp://localhost:18543".to_string()),
+            },
             bitcoin_network,
+            chain_event_cursor_store: overrides
+                .and_then(|c| c.chain_event_cursor_path.as_ref())
+                .map(|path| {
+                    Arc::new(FileChainEventCursorStore {
+                        path: PathBuf::from(path),
+                    }) as Arc<dyn ChainEventCursorStore>
+                }),
         };
         Ok(config)
     }
@@ -184,6 +269,8 @@ pub enum ObserverCommand {
     StandardizeBitcoinBlock(BitcoinBlockFullBreakdown),
     CacheBitcoinBlock(BitcoinBlockData),
     PropagateBitcoinChainEvent(BlockchainEvent),
+    /// Sent by the ZMQ runloop when block notifications were dropped, with the height of the last block it saw.
+    NotifyBitcoinBlocksMissed(Option<u64>),
     Terminate,
 }
 
@@ -204,9 +291,53 @@ pub enum ObserverEvent {
     Error(String),
     Fatal(String),
     Info(String),
+    /// Emitted once the [ObserverSidecar] has finished processing a block. `duration` covers the whole sidecar batch the
+    /// block was processed in.
+    BlockIndexed {
+        height: u64,
+        duration: Duration,
+        counts: BlockIndexedCounts,
+    },
+    /// Block notifications from bitcoind were dropped after `last_block_height`. Blocks mined since then may only reach
+    /// the [ObserverSidecar] once it catches up on its own.
+    BitcoinBlocksMissed {
+        last_block_height: Option<u64>,
+    },
     Terminate,
 }
 
+/// Activity found in a block after it was processed by the [ObserverSidecar].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlockIndexedCounts {
+    pub transactions: usize,
+    pub inscription_reveals: usize,
+    pub inscription_transfers: usize,
+    pub brc20_operations: usize,
+}
+
+impl BlockIndexedCounts {
+    pub fn from_block(block: &BitcoinBlockData) -> Self {
+        let mut counts = BlockIndexedCounts {
+            transactions: block.transactions.len(),
+            ..Default::default()
+        };
+        for tx in block.transactions.iter() {
+            for op in tx.metadata.ordinal_operations.iter() {
+                match op {
+                    OrdinalOperation::InscriptionRevealed(_) => counts.inscription_reveals += 1,
+                    OrdinalOperation::InscriptionTransferred(_) => {
+                        counts.inscription_transfers += 1
+                    }
+                }
+            }
+            if tx.metadata.brc20_operation.is_some() {
+                counts.brc20_operations += 1;
+            }
+        }
+        counts
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 /// JSONRPC Request
 pub struct BitcoinRPCRequest {
@@ -224,11 +355,25 @@ pub struct BitcoinRPCRequest {
 pub struct BitcoinConfig {
     pub username: String,
     pub password: String,
-    pub rpc_url: String,
+    /// bitcoind `.cookie` file to authenticate with instead of the username and password.
+    pub cookie_path: Option<String>,
+    pub rpc_endpoints: BitcoindRpcEndpoints,
     pub network: BitcoinNetwork,
     pub bitcoin_block_signaling: BitcoinBlockSignaling,
 }
 
+impl BitcoinConfig {
+    /// The RPC url of the bitcoind node currently in use.
+    pub fn rpc_url(&self) -> &str {
+        self.rpc_endpoints.active_url()
+    }
+
+    /// The username and password to authenticate RPC calls with, read from the cookie file when one is set.
+    pub fn rpc_credentials(&self) -> Result<(String, String), String> {
+        bitcoind_rpc_credentials(&self.username, &self.password, &self.cookie_path)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BitcoinBlockDataCached {
     pub block: BitcoinBlockData,
@@ -277,20 +422,28 @@ impl ObserverSidecar {
         }
     }
 
-    fn notify_chain_event(&self, chain_event: &BitcoinChainEvent, _ctx: &Context) {
+    fn notify_chain_event(
+        &self,
+        chain_event: &BitcoinChainEvent,
+        chain_event_cursor: &mut Option<ChainEventCursor>,
+        _ctx: &Context,
+    ) {
         if let Some(ref notifier) = self.bitcoin_chain_event_notifier {
             match chain_event {
                 BitcoinChainEvent::ChainUpdatedWithBlocks(data) => {
                     for block in data.new_blocks.iter() {
-                        let _ = notifier.send(HandleBlock::ApplyBlock(block.clone()));
+                        notify_apply_block(notifier, block, chain_event_cursor);
                     }
                 }
                 BitcoinChainEvent::ChainUpdatedWithReorg(data) => {
                     for block in data.blocks_to_rollback.iter() {
+                        if let Some(cursor) = chain_event_cursor.as_mut() {
+                            cursor.record_rollback(&block.block_identifier);
+                        }
                         let _ = notifier.send(HandleBlock::UndoBlock(block.clone()));
                     }
                     for block in data.blocks_to_apply.iter() {
-                        let _ = notifier.send(HandleBlock::ApplyBlock(block.clone()));
+                        notify_apply_block(notifier, block, chain_event_cursor);
                     }
                 }
             }
@@ -298,6 +451,24 @@ impl ObserverSidecar {
     }
 }
 
+fn notify_apply_block(
+    notifier: &crossbeam_channel::Sender<HandleBlock>,
+    block: &BitcoinBlockData,
+    chain_event_cursor: &mut Option<ChainEventCursor>,
+) {
+    let Some(cursor) = chain_event_cursor.as_mut() else {
+        let _ = notifier.send(HandleBlock::ApplyBlock(block.clone()));
+        return;
+    };
+    let is_replay = cursor.is_replay(&block.block_identifier);
+    cursor.record_apply(&block.block_identifier);
+    if is_replay {
+        let _ = notifier.send(HandleBlock::ReplayBlock(block.clone()));
+    } else {
+        let _ = notifier.send(HandleBlock::ApplyBlock(block.clone()));
+    }
+}
+
 /// A helper struct used to configure and call [start_event_observer], which spawns a thread to observer chain events.
 ///
 /// ### Examples
@@ -390,41 +561,49 @@ pub fn start_event_observer(
     ctx: Context,
 ) -> Result<(), Box<dyn Error>> {
     match config.bitcoin_block_signaling {
-        BitcoinBlockSignaling::ZeroMQ(ref url) => {
-            ctx.try_log(|logger| {
-                slog::info!(logger, "Observing Bitcoin chain events via ZeroMQ: {}", url)
-            });
-            let context_cloned = ctx.clone();
-            let event_observer_config_moved = config.clone();
-            let observer_commands_tx_moved = observer_commands_tx.clone();
-            let _ = hiro_system_kit::thread_named("Chainhook event observer")
-                .spawn(move || {
-                    let future = start_bitcoin_event_observer(
-                        event_observer_config_moved,
-                        observer_commands_tx_moved,
-                        observer_commands_rx,
-                        observer_events_tx.clone(),
-                        observer_sidecar,
-                        context_cloned.clone(),
-                    );
-                    match hiro_system_kit::nestable_block_on(future) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            if let Some(tx) = observer_events_tx {
-                                context_cloned.try_log(|logger| {
-                                    slog::crit!(
-                                        logger,
-                                        "Chainhook event observer thread failed with error: {e}",
-                                    )
-                                });
-                                let _ = tx.send(ObserverEvent::Terminate);
-                            }
-                        }
-                    }
-                })
-                .expect("unable to spawn thread");
-        }
+        BitcoinBlockSignaling::ZeroMQ(ref url) => ctx.try_log(|logger| {
+            slog::info!(logger, "Observing Bitcoin chain events via ZeroMQ: {}", url)
+        }),
+        BitcoinBlockSignaling::Poll(interval_ms) => ctx.try_log(|logger| {
+            slog::info!(
+                logger,
+                "Observing Bitcoin chain events by polling bitcoind every {}ms",
+                interval_ms
+            )
+        }),
+        BitcoinBlockSignaling::P2P(ref peer) => ctx.try_log(|logger| {
+            slog::info!(logger, "Observing Bitcoin chain events via P2P peer: {}", peer)
+        }),
     }
+    let context_cloned = ctx.clone();
+    let event_observer_config_moved = config.clone();
+    let observer_commands_tx_moved = observer_commands_tx.clone();
+    let _ = hiro_system_kit::thread_named("Chainhook event observer")
+        .spawn(move || {
+            let future = start_bitcoin_event_observer(
+                event_observer_config_moved,
+                observer_commands_tx_moved,
+                observer_commands_rx,
+                observer_events_tx.clone(),
+                observer_sidecar,
+                context_cloned.clone(),
+            );
+            match hiro_system_kit::nestable_block_on(future) {
+                Ok(_) => {}
+                Err(e) => {
+                    if let Some(tx) = observer_events_tx {
+                        context_cloned.try_log(|logger| {
+                            slog::crit!(
+                                logger,
+                                "Chainhook event observer thread failed with error: {e}",
+                            )
+                        });
+                        let _ = tx.send(ObserverEvent::Terminate);
+                    }
+                }
+            }
+        })
+        .expect("unable to spawn thread");
     Ok(())
 }
 
@@ -438,10 +617,29 @@ pub async fn start_bitcoin_event_observer(
 ) -> Result<(), Box<dyn Error>> {
     let ctx_moved = ctx.clone();
     let config_moved = config.clone();
-    let _ = hiro_system_kit::thread_named("ZMQ handler").spawn(move || {
-        let future = zmq::start_zeromq_runloop(&config_moved, _observer_commands_tx, &ctx_moved);
-        hiro_system_kit::nestable_block_on(future);
-    });
+    match config.bitcoin_block_signaling {
+        BitcoinBlockSignaling::ZeroMQ(_) => {
+            let _ = hiro_system_kit::thread_named("ZMQ handler").spawn(move || {
+                let future =
+                    zmq::start_zeromq_runloop(&config_moved, _observer_commands_tx, &ctx_moved);
+                hiro_system_kit::nestable_block_on(future);
+            });
+        }
+        BitcoinBlockSignaling::Poll(_) => {
+            let _ = hiro_system_kit::thread_named("Block poller").spawn(move || {
+                let future =
+                    poll::start_polling_runloop(&config_moved, _observer_commands_tx, &ctx_moved);
+                hiro_system_kit::nestable_block_on(future);
+            });
+        }
+        BitcoinBlockSignaling::P2P(_) => {
+            let _ = hiro_system_kit::thread_named("P2P handler").spawn(move || {
+                let future =
+                    p2p::start_p2p_runloop(&config_moved, _observer_commands_tx, &ctx_moved);
+                hiro_system_kit::nestable_block_on(future);
+            });
+        }
+    }
 
     // This loop is used for handling background jobs, emitted by HTTP calls.
     start_observer_commands_handler(
@@ -458,6 +656,9 @@ pub async fn start_bitcoin_event_observer(
 pub enum HandleBlock {
     ApplyBlock(BitcoinBlockData),
     UndoBlock(BitcoinBlockData),
+    /// An apply event for a block that was already delivered before the observer restarted. Only emitted when
+    /// [EventObserverConfig::chain_event_cursor_store] is set.
+    ReplayBlock(BitcoinBlockData),
 }
 
 pub async fn start_observer_commands_handler(
@@ -474,6 +675,28 @@ pub async fn start_observer_commands_handler(
         .as_ref()
         .and_then(|s| s.bitcoin_blocks_mutator.as_ref())
         .is_some();
+    let mut chain_event_cursor = match config.chain_event_cursor_store {
+        Some(ref store) => match store.load().await {
+            Ok(Some(cursor)) => Some(cursor),
+            Ok(None) => {
+                ctx.try_log(|logger| {
+                    slog::warn!(
+                        logger,
+                        "No chain event cursor found at {}, starting fresh",
+                        store.location()
+                    )
+                });
+                Some(ChainEventCursor::new())
+            }
+            Err(e) => {
+                ctx.try_log(|logger| {
+                    slog::warn!(logger, "Unable to load chain event cursor, starting fresh: {e}")
+                });
+                Some(ChainEventCursor::new())
+            }
+        },
+        None => None,
+    };
 
     loop {
         let command = match observer_commands_rx.recv() {
@@ -552,6 +775,11 @@ pub async fn start_observer_commands_handler(
                     },
                 );
             }
+            ObserverCommand::NotifyBitcoinBlocksMissed(last_block_height) => {
+                if let Some(ref tx) = observer_events_tx {
+                    let _ = tx.send(ObserverEvent::BitcoinBlocksMissed { last_block_height });
+                }
+            }
             ObserverCommand::CacheBitcoinBlock(block) => {
                 bitcoin_block_store.insert(
                     block.block_identifier.clone(),
@@ -596,11 +824,19 @@ pub async fn start_observer_commands_handler(
                         }
 
                         if let Some(ref sidecar) = observer_sidecar {
+                            let pending_blocks = get_blocks_pending_sidecar(&blocks_to_mutate);
+                            let stopwatch = Instant::now();
                             let updated_blocks = sidecar.perform_bitcoin_sidecar_mutations(
                                 blocks_to_mutate,
                                 vec![],
                                 &ctx,
                             );
+                            notify_blocks_indexed(
+                                &updated_blocks,
+                                &pending_blocks,
+                                stopwatch.elapsed(),
+                                &observer_events_tx,
+                            );
                             for cache in updated_blocks.into_iter() {
                                 bitcoin_block_store
                                     .insert(cache.block.block_identifier.clone(), cache.clone());
@@ -666,8 +902,11 @@ pub async fn start_observer_commands_handler(
                         let mut blocks_ids_to_rollback: Vec<BlockIdentifier> = vec![];
 
                         for header in data.headers_to_rollback.iter() {
-                            match bitcoin_block_store.get(&header.block_identifier) {
+                            match bitcoin_block_store.get_mut(&header.block_identifier) {
                                 Some(cache) => {
+                                    // The sidecar undoes its work on rollback, so the block must go through it again if
+                                    // a later reorg brings it back.
+                                    cache.processed_by_sidecar = false;
                                     blocks_ids_to_rollback.push(header.block_identifier.clone());
                                     blocks_to_rollback.push(cache.block.clone());
                                 }
@@ -684,11 +923,19 @@ pub async fn start_observer_commands_handler(
                         }
 
                         if let Some(ref sidecar) = observer_sidecar {
+                            let pending_blocks = get_blocks_pending_sidecar(&blocks_to_mutate);
+                            let stopwatch = Instant::now();
                             let updated_blocks = sidecar.perform_bitcoin_sidecar_mutations(
                                 blocks_to_mutate,
                                 blocks_ids_to_rollback,
                                 &ctx,
                             );
+                            notify_blocks_indexed(
+                                &updated_blocks,
+                                &pending_blocks,
+                                stopwatch.elapsed(),
+                                &observer_events_tx,
+                            );
                             for cache in updated_blocks.into_iter() {
                                 bitcoin_block_store
                                     .insert(cache.block.block_identifier.clone(), cache.clone());
@@ -727,7 +974,16 @@ pub async fn start_observer_commands_handler(
                 };
 
                 if let Some(ref sidecar) = observer_sidecar {
-                    sidecar.notify_chain_event(&chain_event, &ctx)
+                    sidecar.notify_chain_event(&chain_event, &mut chain_event_cursor, &ctx);
+                }
+                if let (Some(cursor), Some(store)) =
+                    (&chain_event_cursor, &config.chain_event_cursor_store)
+                {
+                    if let Err(e) = store.save(cursor).await {
+                        ctx.try_log(|logger| {
+                            slog::warn!(logger, "Unable to persist chain event cursor: {e}")
+                        });
+                    }
                 }
             }
         }
@@ -736,6 +992,36 @@ pub async fn start_observer_commands_handler(
     Ok(())
 }
 
+fn get_blocks_pending_sidecar(blocks: &Vec<BitcoinBlockDataCached>) -> HashSet<BlockIdentifier> {
+    blocks
+        .iter()
+        .filter(|cache| !cache.processed_by_sidecar)
+        .map(|cache| cache.block.block_identifier.clone())
+        .collect()
+}
+
+/// Emits an [ObserverEvent::BlockIndexed] for every block that went through the sidecar during this batch.
+fn notify_blocks_indexed(
+    updated_blocks: &Vec<BitcoinBlockDataCached>,
+    pending_blocks: &HashSet<BlockIdentifier>,
+    duration: Duration,
+    observer_events_tx: &Option<crossbeam_channel::Sender<ObserverEvent>>,
+) {
+    let Some(tx) = observer_events_tx else {
+        return;
+    };
+    for cache in updated_blocks.iter() {
+        if !cache.processed_by_sidecar || !pending_blocks.contains(&cache.block.block_identifier) {
+            continue;
+        }
+        let _ = tx.send(ObserverEvent::BlockIndexed {
+            height: cache.block.block_identifier.index,
+            duration,
+            counts: BlockIndexedCounts::from_block(&cache.block),
+        });
+    }
+}
+
 fn terminate(
     ingestion_shutdown: Option<Shutdown>,
     observer_events_tx: Option<crossbeam_channel::Sender<ObserverEvent>>,
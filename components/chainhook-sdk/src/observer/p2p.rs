@@ -0,0 +1,298 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bitcoin::consensus::encode::{deserialize_partial, serialize};
+use bitcoin::p2p::message::{NetworkMessage, RawNetworkMessage};
+use bitcoin::p2p::message_blockdata::Inventory;
+use bitcoin::p2p::message_network::VersionMessage;
+use bitcoin::p2p::{Address, Magic, ServiceFlags};
+use chainhook_types::{BitcoinBlockSignaling, BitcoinNetwork};
+use hiro_system_kit::slog;
+
+use crate::{
+    indexer::{bitcoin::build_http_client, fork_scratch_pad::ForkScratchPad},
+    try_info, try_warn,
+    utils::Context,
+};
+
+use super::{zmq::announce_block_hash, EventObserverConfig, ObserverCommand};
+
+/// Size of a P2P message header: magic, command, payload length and checksum.
+const MESSAGE_HEADER_SIZE: usize = 24;
+/// Largest payload accepted from a peer, matching bitcoind's `MAX_PROTOCOL_MESSAGE_LENGTH`.
+const MAX_MESSAGE_PAYLOAD_SIZE: usize = 4_000_000;
+const USER_AGENT: &str = "/ordhook:p2p/";
+
+fn network_magic(network: &BitcoinNetwork) -> Magic {
+    let network = match network {
+        BitcoinNetwork::Mainnet => bitcoin::Network::Bitcoin,
+        BitcoinNetwork::Testnet => bitcoin::Network::Testnet,
+        BitcoinNetwork::Signet => bitcoin::Network::Signet,
+        BitcoinNetwork::Regtest => bitcoin::Network::Regtest,
+    };
+    network.magic()
+}
+
+/// Takes the first complete message out of `buffer`, if it holds one.
+fn take_message(buffer: &mut Vec<u8>) -> Result<Option<RawNetworkMessage>, String> {
+    if buffer.len() < MESSAGE_HEADER_SIZE {
+        return Ok(None);
+    }
+    let payload_size =
+        u32::from_le_bytes([buffer[16], buffer[17], buffer[18], buffer[19]]) as usize;
+    if payload_size > MAX_MESSAGE_PAYLOAD_SIZE {
+        return Err(format!(
+            "message payload of {payload_size} bytes is too large"
+        ));
+    }
+    if buffer.len() < MESSAGE_HEADER_SIZE + payload_size {
+        return Ok(None);
+    }
+    let (message, consumed) = deserialize_partial::<RawNetworkMessage>(buffer)
+        .map_err(|e| format!("unable to decode message: {e}"))?;
+    buffer.drain(..consumed);
+    Ok(Some(message))
+}
+
+/// Hashes of the blocks announced by a message. Peers announce new blocks with `headers` once `sendheaders` is
+/// negotiated, and with `inv` otherwise.
+fn announced_block_hashes(message: &NetworkMessage) -> Vec<String> {
+    match message {
+        NetworkMessage::Inv(inventory) => inventory
+            .iter()
+            .filter_map(|item| match item {
+                Inventory::Block(hash) | Inventory::WitnessBlock(hash) => Some(hash.to_string()),
+                _ => None,
+            })
+            .collect(),
+        NetworkMessage::Headers(headers) => headers
+            .iter()
+            .map(|header| header.block_hash().to_string())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Connection to a single Bitcoin P2P peer, used to learn about new blocks without ZMQ.
+struct PeerConnection {
+    stream: TcpStream,
+    magic: Magic,
+    buffer: Vec<u8>,
+}
+
+impl PeerConnection {
+    fn connect(peer: &str, network: &BitcoinNetwork) -> Result<PeerConnection, String> {
+        let peer_addr: SocketAddr = peer
+            .to_socket_addrs()
+            .map_err(|e| format!("unable to resolve {peer}: {e}"))?
+            .next()
+            .ok_or(format!("unable to resolve {peer}"))?;
+        let stream = TcpStream::connect_timeout(&peer_addr, Duration::from_secs(10))
+            .map_err(|e| format!("unable to connect to {peer}: {e}"))?;
+        // Wake up periodically so the stop flag is honored even when the peer is quiet.
+        stream
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .map_err(|e| format!("unable to configure socket: {e}"))?;
+        let local_addr = stream
+            .local_addr()
+            .map_err(|e| format!("unable to configure socket: {e}"))?;
+        let mut connection = PeerConnection {
+            stream,
+            magic: network_magic(network),
+            buffer: vec![],
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        connection.send(NetworkMessage::Version(VersionMessage::new(
+            ServiceFlags::NONE,
+            now.as_secs() as i64,
+            Address::new(&peer_addr, ServiceFlags::NONE),
+            Address::new(&local_addr, ServiceFlags::NONE),
+            // Only used by peers to detect connections to themselves.
+            now.as_nanos() as u64,
+            USER_AGENT.to_string(),
+            0,
+        )))?;
+        Ok(connection)
+    }
+
+    fn send(&mut self, payload: NetworkMessage) -> Result<(), String> {
+        let message = RawNetworkMessage::new(self.magic, payload);
+        self.stream
+            .write_all(&serialize(&message))
+            .map_err(|e| format!("unable to send {} message: {e}", message.cmd()))
+    }
+
+    /// Waits for the next message from the peer. Returns `None` when nothing arrived within the read timeout.
+    fn receive(&mut self) -> Result<Option<NetworkMessage>, String> {
+        loop {
+            if let Some(message) = take_message(&mut self.buffer)? {
+                if *message.magic() != self.magic {
+                    return Err("peer is on another network".to_string());
+                }
+                return Ok(Some(message.payload().clone()));
+            }
+            let mut chunk = [0u8; 64 * 1024];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err("connection closed by peer".to_string()),
+                Ok(len) => self.buffer.extend_from_slice(&chunk[..len]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                Err(e) => return Err(format!("unable to read from peer: {e}")),
+            }
+        }
+    }
+}
+
+/// Follows a peer's block announcements and passes every announced block hash to `on_block_hash` until it returns
+/// `false` or `stop` is set. Reconnects when the connection drops.
+fn listen_to_peer_block_announcements<F: FnMut(String) -> bool>(
+    peer: &str,
+    network: &BitcoinNetwork,
+    stop: Arc<AtomicBool>,
+    ctx: &Context,
+    mut on_block_hash: F,
+) {
+    while !stop.load(Ordering::Relaxed) {
+        let mut connection = match PeerConnection::connect(peer, network) {
+            Ok(connection) => connection,
+            Err(e) => {
+                try_warn!(ctx, "p2p: {e}");
+                std::thread::sleep(Duration::from_secs(5));
+                continue;
+            }
+        };
+        try_info!(
+            ctx,
+            "p2p: Connected to {peer}, waiting for block announcements"
+        );
+        while !stop.load(Ordering::Relaxed) {
+            let message = match connection.receive() {
+                Ok(Some(message)) => message,
+                Ok(None) => continue,
+                Err(e) => {
+                    try_warn!(ctx, "p2p: Disconnected from {peer}: {e}");
+                    break;
+                }
+            };
+            let reply = match &message {
+                NetworkMessage::Version(_) => Some(NetworkMessage::Verack),
+                // Ask for new blocks to be announced with their headers once the handshake is done.
+                NetworkMessage::Verack => Some(NetworkMessage::SendHeaders),
+                NetworkMessage::Ping(nonce) => Some(NetworkMessage::Pong(*nonce)),
+                _ => None,
+            };
+            if let Some(reply) = reply {
+                if let Err(e) = connection.send(reply) {
+                    try_warn!(ctx, "p2p: Disconnected from {peer}: {e}");
+                    break;
+                }
+            }
+            for block_hash in announced_block_hashes(&message) {
+                if !on_block_hash(block_hash) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Connects to a Bitcoin P2P peer and forwards the hash of every block it announces to `block_hash_tx`, without
+/// downloading anything. Returns once `stop` is set or the receiving end is dropped.
+pub fn start_p2p_block_hash_listener(
+    peer: &str,
+    network: &BitcoinNetwork,
+    block_hash_tx: crossbeam_channel::Sender<String>,
+    stop: Arc<AtomicBool>,
+    ctx: &Context,
+) {
+    listen_to_peer_block_announcements(peer, network, stop, ctx, |block_hash| {
+        block_hash_tx.send(block_hash).is_ok()
+    });
+}
+
+/// Learns about new blocks from a Bitcoin P2P peer instead of ZMQ. Block bodies are still retrieved over RPC: the
+/// standardized blocks need the previous outputs of every input, which P2P blocks do not carry.
+pub async fn start_p2p_runloop(
+    config: &EventObserverConfig,
+    observer_commands_tx: Sender<ObserverCommand>,
+    ctx: &Context,
+) {
+    let BitcoinBlockSignaling::P2P(ref peer) = config.bitcoin_block_signaling else {
+        return;
+    };
+    let bitcoin_config = config.get_bitcoin_config();
+    let http_client = build_http_client();
+    let mut bitcoin_blocks_pool = ForkScratchPad::new();
+
+    let (block_hash_tx, block_hash_rx) = crossbeam_channel::unbounded();
+    let moved_peer = peer.clone();
+    let moved_network = config.bitcoin_network.clone();
+    let moved_ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("P2P block listener").spawn(move || {
+        start_p2p_block_hash_listener(
+            &moved_peer,
+            &moved_network,
+            block_hash_tx,
+            Arc::new(AtomicBool::new(false)),
+            &moved_ctx,
+        );
+    });
+
+    while let Ok(block_hash) = block_hash_rx.recv() {
+        try_info!(ctx, "p2p: Bitcoin block hash announced {block_hash}");
+        announce_block_hash(
+            block_hash,
+            &http_client,
+            &bitcoin_config,
+            &mut bitcoin_blocks_pool,
+            &observer_commands_tx,
+            "p2p",
+            ctx,
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bitcoin::consensus::encode::serialize;
+    use bitcoin::p2p::message::{NetworkMessage, RawNetworkMessage};
+    use bitcoin::p2p::message_blockdata::Inventory;
+    use bitcoin::BlockHash;
+    use chainhook_types::BitcoinNetwork;
+
+    use super::{announced_block_hashes, network_magic, take_message};
+
+    #[test]
+    fn takes_complete_messages_only() {
+        let block_hash: BlockHash =
+            "00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054"
+                .parse()
+                .unwrap();
+        let message = RawNetworkMessage::new(
+            network_magic(&BitcoinNetwork::Mainnet),
+            NetworkMessage::Inv(vec![Inventory::Block(block_hash)]),
+        );
+        let bytes = serialize(&message);
+
+        let mut buffer = bytes[..bytes.len() - 1].to_vec();
+        assert_eq!(take_message(&mut buffer), Ok(None));
+
+        buffer.push(bytes[bytes.len() - 1]);
+        buffer.extend_from_slice(&bytes);
+        let first = take_message(&mut buffer).unwrap().unwrap();
+        assert_eq!(
+            announced_block_hashes(first.payload()),
+            vec!["00000000000000000002a7c4c1e48d76c5a37902165a270156b7a8d72728a054".to_string()]
+        );
+        assert!(take_message(&mut buffer).unwrap().is_some());
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chainhook_types::BitcoinBlockSignaling;
+use hiro_system_kit::slog;
+use reqwest::Client as HttpClient;
+use tokio::time::sleep;
+
+use crate::{
+    indexer::{
+        bitcoin::{
+            build_http_client, download_and_parse_block_with_retry, retrieve_best_block_hash,
+        },
+        fork_scratch_pad::ForkScratchPad,
+    },
+    try_info, try_warn,
+    utils::Context,
+};
+
+use super::{
+    zmq::{announce_block_hash, resume_from_chain_event_cursor},
+    BitcoinConfig, EventObserverConfig, ObserverCommand,
+};
+
+/// Tracks bitcoind's best block hash between polls.
+struct BestBlockHashPoller {
+    last_block_hash: Option<String>,
+}
+
+impl BestBlockHashPoller {
+    fn new() -> Self {
+        BestBlockHashPoller {
+            last_block_hash: None,
+        }
+    }
+
+    /// Returns the new best block hash if it changed since the last poll. The first poll only records the current tip,
+    /// which has already been indexed by the time the observer starts.
+    fn observe(&mut self, block_hash: String) -> Option<String> {
+        if self.last_block_hash.as_ref() == Some(&block_hash) {
+            return None;
+        }
+        let previous = self.last_block_hash.replace(block_hash.clone());
+        previous.map(|_| block_hash)
+    }
+}
+
+/// Polls bitcoind's `getbestblockhash` and forwards every new tip to `block_hash_tx`, for nodes that do not expose ZMQ.
+/// Returns once `stop` is set or the receiving end is dropped.
+pub fn start_block_hash_poller(
+    bitcoin_config: &BitcoinConfig,
+    interval_ms: u64,
+    block_hash_tx: crossbeam_channel::Sender<String>,
+    stop: Arc<AtomicBool>,
+    ctx: &Context,
+) {
+    let http_client = build_http_client();
+    let mut poller = BestBlockHashPoller::new();
+    hiro_system_kit::nestable_block_on(async {
+        while !stop.load(Ordering::Relaxed) {
+            match retrieve_best_block_hash(&http_client, bitcoin_config, ctx).await {
+                Ok(block_hash) => {
+                    if let Some(block_hash) = poller.observe(block_hash) {
+                        if block_hash_tx.send(block_hash).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(e) => try_warn!(ctx, "poll: Unable to retrieve best block hash: {e}"),
+            }
+            sleep(Duration::from_millis(interval_ms)).await;
+        }
+    });
+}
+
+/// Seeds the fork scratch pad with the header of the current tip, so blocks mined between two polls are fetched by
+/// walking back to it instead of being skipped.
+pub(crate) async fn seed_blocks_pool(
+    block_hash: &str,
+    http_client: &HttpClient,
+    bitcoin_config: &BitcoinConfig,
+    bitcoin_blocks_pool: &mut ForkScratchPad,
+    ctx: &Context,
+) -> Result<(), String> {
+    let block =
+        download_and_parse_block_with_retry(http_client, block_hash, bitcoin_config, ctx).await?;
+    bitcoin_blocks_pool.process_header(block.get_block_header(), ctx)?;
+    Ok(())
+}
+
+pub async fn start_polling_runloop(
+    config: &EventObserverConfig,
+    observer_commands_tx: Sender<ObserverCommand>,
+    ctx: &Context,
+) {
+    let BitcoinBlockSignaling::Poll(interval_ms) = config.bitcoin_block_signaling else {
+        return;
+    };
+    let bitcoin_config = config.get_bitcoin_config();
+    let http_client = build_http_client();
+    let mut bitcoin_blocks_pool = ForkScratchPad::new();
+    let mut poller = BestBlockHashPoller::new();
+    poller.last_block_hash = resume_from_chain_event_cursor(
+        config,
+        &http_client,
+        &bitcoin_config,
+        &mut bitcoin_blocks_pool,
+        "poll",
+        ctx,
+    )
+    .await;
+
+    try_info!(
+        ctx,
+        "poll: Polling bitcoind for new blocks every {interval_ms}ms"
+    );
+
+    loop {
+        match retrieve_best_block_hash(&http_client, &bitcoin_config, ctx).await {
+            Ok(block_hash) => {
+                let is_first_poll = poller.last_block_hash.is_none();
+                match poller.observe(block_hash.clone()) {
+                    Some(block_hash) => {
+                        try_info!(ctx, "poll: Bitcoin block hash announced {block_hash}");
+                        announce_block_hash(
+                            block_hash,
+                            &http_client,
+                            &bitcoin_config,
+                            &mut bitcoin_blocks_pool,
+                            &observer_commands_tx,
+                            "poll",
+                            ctx,
+                        )
+                        .await;
+                    }
+                    None if is_first_poll => {
+                        if let Err(e) = seed_blocks_pool(
+                            &block_hash,
+                            &http_client,
+                            &bitcoin_config,
+                            &mut bitcoin_blocks_pool,
+                            ctx,
+                        )
+                        .await
+                        {
+                            try_warn!(ctx, "poll: Unable to load chain tip {block_hash}: {e}");
+                        }
+                    }
+                    None => {}
+                }
+            }
+            Err(e) => try_warn!(ctx, "poll: Unable to retrieve best block hash: {e}"),
+        }
+        sleep(Duration::from_millis(interval_ms)).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BestBlockHashPoller;
+
+    #[test]
+    fn reports_changed_tips_only() {
+        let mut poller = BestBlockHashPoller::new();
+        assert_eq!(poller.observe("aa".to_string()), None);
+        assert_eq!(poller.observe("aa".to_string()), None);
+        assert_eq!(poller.observe("bb".to_string()), Some("bb".to_string()));
+        assert_eq!(poller.observe("bb".to_string()), None);
+    }
+}
@@ -1,11 +1,17 @@
-use chainhook_types::BitcoinBlockSignaling;
+use chainhook_types::{BitcoinBlockSignaling, BlockIdentifier};
 use hiro_system_kit::slog;
+use reqwest::Client as HttpClient;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
 use zmq::Socket;
 
 use crate::{
     indexer::{
-        bitcoin::{build_http_client, download_and_parse_block_with_retry},
+        bitcoin::{
+            build_http_client, download_and_parse_block_with_retry, retrieve_block_count,
+            retrieve_block_hash,
+        },
         fork_scratch_pad::ForkScratchPad,
     },
     try_info, try_warn,
@@ -13,13 +19,41 @@ use crate::{
 };
 use std::collections::VecDeque;
 
-use super::{EventObserverConfig, ObserverCommand};
+use super::{
+    chain_event_cursor::CHAIN_EVENT_CURSOR_WINDOW, poll::seed_blocks_pool, BitcoinConfig,
+    EventObserverConfig, ObserverCommand,
+};
+
+/// Tracks the sequence numbers bitcoind attaches to its `hashblock` notifications, to notice when some were dropped.
+#[derive(Default)]
+struct ZmqSequenceTracker {
+    last_sequence: Option<u32>,
+}
+
+impl ZmqSequenceTracker {
+    /// Records the sequence number of a notification. Returns the previous sequence number if the notification does not
+    /// directly follow it, which happens when messages were dropped or bitcoind restarted.
+    fn observe(&mut self, sequence: u32) -> Option<u32> {
+        let previous = self.last_sequence.replace(sequence)?;
+        if previous.wrapping_add(1) == sequence {
+            None
+        } else {
+            Some(previous)
+        }
+    }
+}
 
 fn new_zmq_socket() -> Socket {
+    new_zmq_socket_for_topic(b"hashblock")
+}
+
+fn new_zmq_socket_for_topic(topic: &[u8]) -> Socket {
     let context = zmq::Context::new();
     let socket = context.socket(zmq::SUB).unwrap();
-    assert!(socket.set_subscribe(b"hashblock").is_ok());
+    assert!(socket.set_subscribe(topic).is_ok());
     assert!(socket.set_rcvhwm(0).is_ok());
+    // Allow IPv6 literals such as `tcp://[::1]:28332`.
+    assert!(socket.set_ipv6(true).is_ok());
     // We override the OS default behavior:
     assert!(socket.set_tcp_keepalive(1).is_ok());
     // The keepalive routine will wait for 5 minutes
@@ -31,12 +65,83 @@ fn new_zmq_socket() -> Socket {
     socket
 }
 
+/// Subscribes to bitcoind's `hashblock` notifications and forwards every announced block hash to `block_hash_tx`, without
+/// downloading or standardizing anything. Returns once `stop` is set or the receiving end is dropped.
+pub fn start_zeromq_block_hash_listener(
+    bitcoind_zmq_url: &str,
+    block_hash_tx: crossbeam_channel::Sender<String>,
+    stop: Arc<AtomicBool>,
+    ctx: &Context,
+) {
+    listen_to_zeromq_topic(bitcoind_zmq_url, b"hashblock", stop, ctx, |data| {
+        block_hash_tx.send(hex::encode(data)).is_ok()
+    });
+}
+
+/// Subscribes to bitcoind's `rawtx` notifications and forwards every serialized transaction to `raw_tx_tx`. bitcoind
+/// publishes transactions when they enter the mempool and again when they are mined. Returns once `stop` is set or the
+/// receiving end is dropped.
+pub fn start_zeromq_raw_tx_listener(
+    bitcoind_zmq_url: &str,
+    raw_tx_tx: crossbeam_channel::Sender<Vec<u8>>,
+    stop: Arc<AtomicBool>,
+    ctx: &Context,
+) {
+    listen_to_zeromq_topic(bitcoind_zmq_url, b"rawtx", stop, ctx, |data| {
+        raw_tx_tx.send(data.to_vec()).is_ok()
+    });
+}
+
+/// Passes the payload of every message published on `topic` to `on_message` until it returns `false` or `stop` is set.
+fn listen_to_zeromq_topic<F: FnMut(&[u8]) -> bool>(
+    bitcoind_zmq_url: &str,
+    topic: &[u8],
+    stop: Arc<AtomicBool>,
+    ctx: &Context,
+    mut on_message: F,
+) {
+    let mut socket = new_zmq_socket_for_topic(topic);
+    // Wake up periodically so the stop flag is honored even when nothing is published.
+    assert!(socket.set_rcvtimeo(1_000).is_ok());
+    assert!(socket.connect(bitcoind_zmq_url).is_ok());
+
+    while !stop.load(Ordering::Relaxed) {
+        let msg = match socket.recv_multipart(0) {
+            Ok(msg) => msg,
+            Err(zmq::Error::EAGAIN) => continue,
+            Err(e) => {
+                try_warn!(ctx, "zmq: Unable to receive ZMQ message: {e}");
+                socket = new_zmq_socket_for_topic(topic);
+                assert!(socket.set_rcvtimeo(1_000).is_ok());
+                assert!(socket.connect(bitcoind_zmq_url).is_ok());
+                continue;
+            }
+        };
+        let [message_topic, data, ..] = &msg[..] else {
+            try_warn!(
+                ctx,
+                "zmq: Ignoring malformed message with {} frames",
+                msg.len()
+            );
+            continue;
+        };
+        if !message_topic.eq(topic) {
+            continue;
+        }
+        if !on_message(data) {
+            break;
+        }
+    }
+}
+
 pub async fn start_zeromq_runloop(
     config: &EventObserverConfig,
     observer_commands_tx: Sender<ObserverCommand>,
     ctx: &Context,
 ) {
-    let BitcoinBlockSignaling::ZeroMQ(ref bitcoind_zmq_url) = config.bitcoin_block_signaling;
+    let BitcoinBlockSignaling::ZeroMQ(ref bitcoind_zmq_url) = config.bitcoin_block_signaling else {
+        return;
+    };
 
     let bitcoind_zmq_url = bitcoind_zmq_url.clone();
     let bitcoin_config = config.get_bitcoin_config();
@@ -55,6 +160,16 @@ pub async fn start_zeromq_runloop(
     );
 
     let mut bitcoin_blocks_pool = ForkScratchPad::new();
+    let mut sequence_tracker = ZmqSequenceTracker::default();
+    resume_from_chain_event_cursor(
+        config,
+        &http_client,
+        &bitcoin_config,
+        &mut bitcoin_blocks_pool,
+        "zmq",
+        ctx,
+    )
+    .await;
 
     loop {
         let msg = match socket.recv_multipart(0) {
@@ -66,13 +181,20 @@ pub async fn start_zeromq_runloop(
                 continue;
             }
         };
-        let (topic, data, _sequence) = (&msg[0], &msg[1], &msg[2]);
+        let [topic, data, sequence] = &msg[..] else {
+            try_warn!(
+                ctx,
+                "zmq: Ignoring malformed message with {} frames",
+                msg.len()
+            );
+            continue;
+        };
 
         if !topic.eq(b"hashblock") {
             try_warn!(
                 ctx,
                 "zmq: {} Topic not supported",
-                String::from_utf8(topic.clone()).unwrap()
+                String::from_utf8_lossy(topic)
             );
             continue;
         }
@@ -81,61 +203,220 @@ pub async fn start_zeromq_runloop(
 
         try_info!(ctx, "zmq: Bitcoin block hash announced {block_hash}");
 
-        let mut block_hashes: VecDeque<String> = VecDeque::new();
-        block_hashes.push_front(block_hash);
+        let sequence = <[u8; 4]>::try_from(sequence.as_slice()).map(u32::from_le_bytes);
+        if let Some(previous_sequence) = sequence.ok().and_then(|s| sequence_tracker.observe(s)) {
+            let last_block_height = bitcoin_blocks_pool.get_canonical_tip().map(|tip| tip.index);
+            try_warn!(
+                ctx,
+                "zmq: Block notifications missed after sequence {previous_sequence} (last block seen: {})",
+                last_block_height
+                    .map(|height| format!("#{height}"))
+                    .unwrap_or("none".to_string())
+            );
+            let _ = observer_commands_tx.send(ObserverCommand::NotifyBitcoinBlocksMissed(
+                last_block_height,
+            ));
+            // Leave the missed blocks to the indexer's catch-up instead of walking back to the last known header one
+            // block at a time.
+            bitcoin_blocks_pool = ForkScratchPad::new();
+        }
 
-        while let Some(block_hash) = block_hashes.pop_front() {
-            let block = match download_and_parse_block_with_retry(
-                &http_client,
-                &block_hash,
-                &bitcoin_config,
+        announce_block_hash(
+            block_hash,
+            &http_client,
+            &bitcoin_config,
+            &mut bitcoin_blocks_pool,
+            &observer_commands_tx,
+            "zmq",
+            ctx,
+        )
+        .await;
+    }
+}
+
+/// Whether the observer can resume after `last_block`, given the hash bitcoind now has at its height and the height of
+/// bitcoind's tip. The block must still be canonical and less than [CHAIN_EVENT_CURSOR_WINDOW] blocks behind the tip.
+fn is_resumable(last_block: &BlockIdentifier, canonical_hash: &str, chain_tip: u64) -> bool {
+    canonical_hash == last_block.get_hash_bytes_str()
+        && chain_tip < last_block.index + CHAIN_EVENT_CURSOR_WINDOW as u64
+}
+
+/// Seeds the fork scratch pad with the last block delivered before the observer stopped, as recorded by the chain event
+/// cursor, so that the first announced block walks back to it and the blocks mined in between are delivered as well.
+/// Blocks that can't be resumed from, see [is_resumable], are left to the indexer's catch-up, as when notifications are
+/// missed. Returns the hash of the block the scratch pad was seeded with.
+pub(crate) async fn resume_from_chain_event_cursor(
+    config: &EventObserverConfig,
+    http_client: &HttpClient,
+    bitcoin_config: &BitcoinConfig,
+    bitcoin_blocks_pool: &mut ForkScratchPad,
+    source: &str,
+    ctx: &Context,
+) -> Option<String> {
+    let store = config.chain_event_cursor_store.as_ref()?;
+    let cursor = match store.load().await {
+        Ok(cursor) => cursor?,
+        Err(e) => {
+            try_warn!(ctx, "{source}: Unable to load chain event cursor: {e}");
+            return None;
+        }
+    };
+    let last_block = cursor.applied_blocks.back()?;
+    let block_hash = last_block.get_hash_bytes_str().to_string();
+    let (canonical_hash, chain_tip) = match tokio::try_join!(
+        retrieve_block_hash(http_client, &last_block.index, bitcoin_config, ctx),
+        retrieve_block_count(http_client, bitcoin_config, ctx)
+    ) {
+        Ok(res) => res,
+        Err(e) => {
+            try_warn!(
                 ctx,
-            )
-            .await
-            {
-                Ok(block) => block,
+                "{source}: Unable to check last delivered block {last_block}: {e}"
+            );
+            return None;
+        }
+    };
+    if !is_resumable(last_block, &canonical_hash, chain_tip) {
+        try_info!(
+            ctx,
+            "{source}: Last delivered block {last_block} is no longer canonical or too far behind tip #{chain_tip}"
+        );
+        return None;
+    }
+    if let Err(e) = seed_blocks_pool(
+        &block_hash,
+        http_client,
+        bitcoin_config,
+        bitcoin_blocks_pool,
+        ctx,
+    )
+    .await
+    {
+        try_warn!(
+            ctx,
+            "{source}: Unable to load last delivered block {last_block}: {e}"
+        );
+        return None;
+    }
+    try_info!(
+        ctx,
+        "{source}: Resuming after last delivered block {last_block}"
+    );
+    Some(block_hash)
+}
+
+/// Downloads an announced block, hands it to the observer and propagates the resulting chain event. When the block
+/// does not extend a known header, e.g. after a re-org or when announcements were missed, its ancestors are fetched
+/// first until one connects to the known headers.
+pub(crate) async fn announce_block_hash(
+    block_hash: String,
+    http_client: &HttpClient,
+    bitcoin_config: &BitcoinConfig,
+    bitcoin_blocks_pool: &mut ForkScratchPad,
+    observer_commands_tx: &Sender<ObserverCommand>,
+    source: &str,
+    ctx: &Context,
+) {
+    let mut block_hashes: VecDeque<String> = VecDeque::new();
+    block_hashes.push_front(block_hash);
+
+    while let Some(block_hash) = block_hashes.pop_front() {
+        let block = match download_and_parse_block_with_retry(
+            http_client,
+            &block_hash,
+            bitcoin_config,
+            ctx,
+        )
+        .await
+        {
+            Ok(block) => block,
+            Err(e) => {
+                try_warn!(ctx, "{source}: Unable to download block: {e}");
+                continue;
+            }
+        };
+
+        let header = block.get_block_header();
+        try_info!(
+            ctx,
+            "{source}: Standardizing bitcoin block #{}",
+            block.height
+        );
+        let _ = observer_commands_tx.send(ObserverCommand::StandardizeBitcoinBlock(block));
+
+        if bitcoin_blocks_pool.can_process_header(&header) {
+            match bitcoin_blocks_pool.process_header(header, ctx) {
+                Ok(Some(event)) => {
+                    let _ = observer_commands_tx
+                        .send(ObserverCommand::PropagateBitcoinChainEvent(event));
+                }
                 Err(e) => {
-                    try_warn!(ctx, "zmq: Unable to download block: {e}");
-                    continue;
+                    try_warn!(ctx, "{source}: Unable to append block: {e}");
                 }
-            };
-
-            let header = block.get_block_header();
-            try_info!(ctx, "zmq: Standardizing bitcoin block #{}", block.height);
-            let _ = observer_commands_tx.send(ObserverCommand::StandardizeBitcoinBlock(block));
-
-            if bitcoin_blocks_pool.can_process_header(&header) {
-                match bitcoin_blocks_pool.process_header(header, ctx) {
-                    Ok(Some(event)) => {
-                        let _ = observer_commands_tx
-                            .send(ObserverCommand::PropagateBitcoinChainEvent(event));
-                    }
-                    Err(e) => {
-                        try_warn!(ctx, "zmq: Unable to append block: {e}");
-                    }
-                    Ok(None) => {
-                        try_warn!(ctx, "zmq: Unable to append block");
-                    }
+                Ok(None) => {
+                    try_warn!(ctx, "{source}: Unable to append block");
                 }
-            } else {
-                // Handle a behaviour specific to ZMQ usage in bitcoind.
-                // Considering a simple re-org:
-                // A (1) - B1 (2) - C1 (3)
-                //       \ B2 (4) - C2 (5) - D2 (6)
-                // When D2 is being discovered (making A -> B2 -> C2 -> D2 the new canonical fork)
-                // it looks like ZMQ is only publishing D2.
-                // Without additional operation, we end up with a block that we can't append.
-                let parent_block_hash = header
-                    .parent_block_identifier
-                    .get_hash_bytes_str()
-                    .to_string();
-                try_info!(
-                    ctx,
-                    "zmq: Re-org detected, retrieving parent block {parent_block_hash}"
-                );
-                block_hashes.push_front(block_hash);
-                block_hashes.push_front(parent_block_hash);
             }
+        } else {
+            // Handle a behaviour specific to ZMQ usage in bitcoind.
+            // Considering a simple re-org:
+            // A (1) - B1 (2) - C1 (3)
+            //       \ B2 (4) - C2 (5) - D2 (6)
+            // When D2 is being discovered (making A -> B2 -> C2 -> D2 the new canonical fork)
+            // it looks like ZMQ is only publishing D2.
+            // Without additional operation, we end up with a block that we can't append.
+            let parent_block_hash = header
+                .parent_block_identifier
+                .get_hash_bytes_str()
+                .to_string();
+            try_info!(
+                ctx,
+                "{source}: Re-org detected, retrieving parent block {parent_block_hash}"
+            );
+            block_hashes.push_front(block_hash);
+            block_hashes.push_front(parent_block_hash);
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chainhook_types::BlockIdentifier;
+
+    use crate::observer::chain_event_cursor::CHAIN_EVENT_CURSOR_WINDOW;
+
+    use super::{is_resumable, ZmqSequenceTracker};
+
+    #[test]
+    fn reports_sequence_gaps() {
+        let mut tracker = ZmqSequenceTracker::default();
+        assert_eq!(tracker.observe(7), None);
+        assert_eq!(tracker.observe(8), None);
+        assert_eq!(tracker.observe(10), Some(8));
+        assert_eq!(tracker.observe(11), None);
+        // bitcoind restarted.
+        assert_eq!(tracker.observe(0), Some(11));
+        let mut tracker = ZmqSequenceTracker::default();
+        tracker.observe(u32::MAX);
+        assert_eq!(tracker.observe(0), None);
+    }
+
+    #[test]
+    fn resumes_within_the_cursor_window_only() {
+        let last_block = BlockIdentifier {
+            index: 800000,
+            hash: format!("0x{}", "aa".repeat(32)),
+        };
+        let canonical_hash = "aa".repeat(32);
+        let window = CHAIN_EVENT_CURSOR_WINDOW as u64;
+        assert!(is_resumable(&last_block, &canonical_hash, 800000));
+        assert!(is_resumable(
+            &last_block,
+            &canonical_hash,
+            800000 + window - 1
+        ));
+        assert!(!is_resumable(&last_block, &canonical_hash, 800000 + window));
+        // Re-orged out.
+        assert!(!is_resumable(&last_block, &"bb".repeat(32), 800001));
+    }
+}
@@ -1,56 +1,222 @@
-use std::{thread::sleep, time::Duration};
+use std::time::Duration;
 
-use bitcoincore_rpc::{Auth, Client, RpcApi};
+use bitcoincore_rpc_json::GetBlockchainInfoResult;
 use hiro_system_kit::slog;
-use crate::utils::Context;
+use reqwest::Client as HttpClient;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::time::sleep;
+
+use crate::indexer::bitcoin::{build_http_client, send_bitcoind_rpc_request};
 use crate::indexer::IndexerConfig;
+use crate::utils::Context;
 
-use crate::{try_error, try_info};
+use crate::{try_error, try_info, try_warn};
 
-fn bitcoind_get_client(config: &IndexerConfig, ctx: &Context) -> Client {
-    loop {
-        let auth = Auth::UserPass(
-            config.bitcoind_rpc_username.clone(),
-            config.bitcoind_rpc_password.clone(),
+/// Number of blocks the active bitcoind node may fall behind another endpoint before failing over to that endpoint.
+const MAX_BITCOIND_ENDPOINT_LAG: u64 = 2;
+
+/// Oldest bitcoind release trusted to return prevouts inline with `getblock` verbosity 3.
+const MIN_BITCOIND_VERSION_WITH_INLINE_PREVOUTS: u64 = 250000;
+
+#[derive(Debug, Clone, Deserialize)]
+struct BitcoindNetworkInfo {
+    version: u64,
+}
+
+/// Reads the `user:password` credentials bitcoind writes to its `.cookie` file.
+pub fn read_bitcoind_cookie(cookie_path: &str) -> Result<(String, String), String> {
+    let cookie = std::fs::read_to_string(cookie_path)
+        .map_err(|e| format!("unable to read bitcoind cookie file {cookie_path}: {e}"))?;
+    match cookie.trim().split_once(':') {
+        Some((username, password)) => Ok((username.to_string(), password.to_string())),
+        None => Err(format!("invalid bitcoind cookie file {cookie_path}")),
+    }
+}
+
+/// Returns the credentials bitcoind RPC calls authenticate with. When a cookie file is configured it takes precedence over
+/// the username and password, and it is read again on every call since bitcoind writes a new cookie each time it starts.
+pub fn bitcoind_rpc_credentials(
+    username: &str,
+    password: &str,
+    cookie_path: &Option<String>,
+) -> Result<(String, String), String> {
+    match cookie_path {
+        Some(cookie_path) => read_bitcoind_cookie(cookie_path),
+        None => Ok((username.to_string(), password.to_string())),
+    }
+}
+
+/// Calls `getblockchaininfo` through the shared HTTP client, so the request honors proxy settings and IPv6 RPC URLs.
+async fn bitcoind_get_blockchain_info(
+    http_client: &HttpClient,
+    config: &IndexerConfig,
+    rpc_url: &str,
+    ctx: &Context,
+) -> Result<GetBlockchainInfoResult, String> {
+    let body = json!({
+        "jsonrpc": "1.0",
+        "id": "chainhook-cli",
+        "method": "getblockchaininfo",
+        "params": []
+    });
+    send_bitcoind_rpc_request(
+        http_client,
+        rpc_url,
+        &config.bitcoind_rpc_credentials()?,
+        &body,
+        config.bitcoind_rpc_endpoints.rate_limiter(),
+        ctx,
+    )
+    .await?
+    .json::<bitcoincore_rpc::jsonrpc::Response>()
+    .await
+    .map_err(|e| format!("unable to parse response ({})", e))?
+    .result::<GetBlockchainInfoResult>()
+    .map_err(|e| format!("unable to parse response ({})", e))
+}
+
+/// Picks the endpoint to use given the block height each endpoint reported, `None` for the ones that errored. The active
+/// endpoint is kept unless it errored or lags more than [MAX_BITCOIND_ENDPOINT_LAG] blocks behind the highest one.
+fn select_bitcoind_endpoint(active_index: usize, block_heights: &[Option<u64>]) -> Option<usize> {
+    let (best_index, best_height) = block_heights
+        .iter()
+        .enumerate()
+        .filter_map(|(index, height)| height.map(|height| (index, height)))
+        .fold(
+            None,
+            |best: Option<(usize, u64)>, (index, height)| match best {
+                Some((_, best_height)) if best_height >= height => best,
+                _ => Some((index, height)),
+            },
+        )?;
+    match block_heights.get(active_index).copied().flatten() {
+        Some(height) if height + MAX_BITCOIND_ENDPOINT_LAG >= best_height => Some(active_index),
+        _ => Some(best_index),
+    }
+}
+
+/// Retrieves the blockchain info from every bitcoind endpoint and returns the one of the active endpoint, after failing
+/// over to another endpoint if the active one errored or lags behind.
+async fn bitcoind_get_active_blockchain_info(
+    http_client: &HttpClient,
+    config: &IndexerConfig,
+    ctx: &Context,
+) -> Result<GetBlockchainInfoResult, String> {
+    let endpoints = &config.bitcoind_rpc_endpoints;
+    if endpoints.urls().len() == 1 {
+        return bitcoind_get_blockchain_info(http_client, config, endpoints.active_url(), ctx)
+            .await;
+    }
+    let mut results = vec![];
+    for rpc_url in endpoints.urls().iter() {
+        results.push(bitcoind_get_blockchain_info(http_client, config, rpc_url, ctx).await);
+    }
+    let block_heights: Vec<Option<u64>> = results
+        .iter()
+        .map(|result| result.as_ref().ok().map(|info| info.blocks))
+        .collect();
+    let active_index = endpoints.active_index();
+    let Some(index) = select_bitcoind_endpoint(active_index, &block_heights) else {
+        return results.swap_remove(active_index);
+    };
+    if index != active_index {
+        try_warn!(
+            ctx,
+            "bitcoind: Failing over from {} to {} (active node errored or lags behind)",
+            endpoints.urls()[active_index],
+            endpoints.urls()[index]
         );
-        match Client::new(&config.bitcoind_rpc_url, auth) {
-            Ok(con) => {
-                return con;
-            }
-            Err(e) => {
-                try_error!(ctx, "bitcoind: Unable to get client: {}", e.to_string());
-                sleep(Duration::from_secs(1));
+        endpoints.switch_to(index);
+    }
+    results.swap_remove(index)
+}
+
+/// Checks the version of the active bitcoind node to decide whether blocks are downloaded with their prevouts inline.
+/// Blocks from older nodes are downloaded with `getblock` verbosity 2 and their prevouts are looked up one transaction at
+/// a time, which needs `txindex=1`. The previous choice is kept if the node can't be reached.
+pub async fn bitcoind_probe_inline_prevouts(config: &IndexerConfig, ctx: &Context) -> bool {
+    let endpoints = &config.bitcoind_rpc_endpoints;
+    let body = json!({
+        "jsonrpc": "1.0",
+        "id": "chainhook-cli",
+        "method": "getnetworkinfo",
+        "params": []
+    });
+    let network_info = async {
+        send_bitcoind_rpc_request(
+            &build_http_client(),
+            endpoints.active_url(),
+            &config.bitcoind_rpc_credentials()?,
+            &body,
+            endpoints.rate_limiter(),
+            ctx,
+        )
+        .await?
+        .json::<bitcoincore_rpc::jsonrpc::Response>()
+        .await
+        .map_err(|e| format!("unable to parse response ({})", e))?
+        .result::<BitcoindNetworkInfo>()
+        .map_err(|e| format!("unable to parse response ({})", e))
+    };
+    match network_info.await {
+        Ok(network_info) => {
+            let inline_prevouts = network_info.version >= MIN_BITCOIND_VERSION_WITH_INLINE_PREVOUTS;
+            if !inline_prevouts {
+                try_warn!(
+                    ctx,
+                    "bitcoind: Version {} does not return prevouts with blocks, they will be looked up separately",
+                    network_info.version
+                );
             }
+            endpoints.set_inline_prevouts(inline_prevouts);
+        }
+        Err(e) => {
+            try_warn!(ctx, "bitcoind: Unable to check node version: {e}");
         }
     }
+    endpoints.inline_prevouts()
 }
 
-/// Retrieves the block height from bitcoind.
-pub fn bitcoind_get_block_height(config: &IndexerConfig, ctx: &Context) -> u64 {
-    let bitcoin_rpc = bitcoind_get_client(config, ctx);
+/// Retrieves the block height from the active bitcoind node once, without retrying on errors.
+pub async fn bitcoind_try_get_block_height(
+    config: &IndexerConfig,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let http_client = build_http_client();
+    bitcoind_get_blockchain_info(
+        &http_client,
+        config,
+        config.bitcoind_rpc_endpoints.active_url(),
+        ctx,
+    )
+    .await
+    .map(|result| result.blocks)
+}
+
+/// Retrieves the block height from bitcoind. When several bitcoind endpoints are configured, this is also where a lagging
+/// active node gets swapped for the most up to date one.
+pub async fn bitcoind_get_block_height(config: &IndexerConfig, ctx: &Context) -> u64 {
+    let http_client = build_http_client();
     loop {
-        match bitcoin_rpc.get_blockchain_info() {
+        match bitcoind_get_active_blockchain_info(&http_client, config, ctx).await {
             Ok(result) => {
                 return result.blocks;
             }
             Err(e) => {
-                try_error!(
-                    ctx,
-                    "bitcoind: Unable to get block height: {}",
-                    e.to_string()
-                );
-                sleep(Duration::from_secs(1));
+                try_error!(ctx, "bitcoind: Unable to get block height: {}", e);
+                sleep(Duration::from_secs(1)).await;
             }
         };
     }
 }
 
 /// Checks if bitcoind is still synchronizing blocks and waits until it's finished if that is the case.
-pub fn bitcoind_wait_for_chain_tip(config: &IndexerConfig, ctx: &Context) {
-    let bitcoin_rpc = bitcoind_get_client(config, ctx);
+pub async fn bitcoind_wait_for_chain_tip(config: &IndexerConfig, ctx: &Context) {
+    let http_client = build_http_client();
     let mut confirmations = 0;
     loop {
-        match bitcoin_rpc.get_blockchain_info() {
+        match bitcoind_get_active_blockchain_info(&http_client, config, ctx).await {
             Ok(result) => {
                 if result.initial_block_download == false && result.blocks == result.headers {
                     confirmations += 1;
@@ -63,17 +229,61 @@ pub fn bitcoind_wait_for_chain_tip(config: &IndexerConfig, ctx: &Context) {
                     try_info!(ctx, "bitcoind: Verifying chain tip");
                 } else {
                     confirmations = 0;
-                    try_info!(ctx, "bitcoind: Node has not reached chain tip, trying again");
+                    try_info!(
+                        ctx,
+                        "bitcoind: Node has not reached chain tip, trying again"
+                    );
                 }
             }
             Err(e) => {
-                try_error!(
-                    ctx,
-                    "bitcoind: Unable to check for chain tip: {}",
-                    e.to_string()
-                );
+                try_error!(ctx, "bitcoind: Unable to check for chain tip: {}", e);
             }
         };
-        sleep(Duration::from_secs(1));
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bitcoind_rpc_credentials, select_bitcoind_endpoint};
+
+    #[test]
+    fn keeps_active_endpoint_unless_it_errors_or_lags() {
+        assert_eq!(
+            select_bitcoind_endpoint(0, &[Some(850000), Some(850002)]),
+            Some(0)
+        );
+        assert_eq!(
+            select_bitcoind_endpoint(0, &[Some(850000), Some(850003)]),
+            Some(1)
+        );
+        assert_eq!(select_bitcoind_endpoint(0, &[None, Some(849000)]), Some(1));
+        assert_eq!(select_bitcoind_endpoint(1, &[Some(850000), None]), Some(0));
+        assert_eq!(select_bitcoind_endpoint(0, &[None, None]), None);
+    }
+
+    #[test]
+    fn reads_credentials_from_cookie_file() {
+        let cookie_path = std::env::temp_dir().join("chainhook-sdk-bitcoind-test.cookie");
+        let cookie_path_str = Some(cookie_path.to_str().unwrap().to_string());
+        std::fs::write(&cookie_path, "__cookie__:first\n").unwrap();
+        assert_eq!(
+            bitcoind_rpc_credentials("devnet", "devnet", &cookie_path_str),
+            Ok(("__cookie__".to_string(), "first".to_string()))
+        );
+        // bitcoind writes a new cookie when it restarts.
+        std::fs::write(&cookie_path, "__cookie__:second").unwrap();
+        assert_eq!(
+            bitcoind_rpc_credentials("devnet", "devnet", &cookie_path_str),
+            Ok(("__cookie__".to_string(), "second".to_string()))
+        );
+        std::fs::write(&cookie_path, "garbage").unwrap();
+        assert!(bitcoind_rpc_credentials("devnet", "devnet", &cookie_path_str).is_err());
+        std::fs::remove_file(&cookie_path).unwrap();
+        assert!(bitcoind_rpc_credentials("devnet", "devnet", &cookie_path_str).is_err());
+        assert_eq!(
+            bitcoind_rpc_credentials("devnet", "secret", &None),
+            Ok(("devnet".to_string(), "secret".to_string()))
+        );
     }
 }
@@ -0,0 +1,44 @@
+//! Hex conversions used while standardizing blocks and handling inscription content. Building with the `faster-hex`
+//! feature swaps the `hex` crate for SIMD accelerated routines, which matters during catch-up where every script,
+//! witness and inscription body goes through these functions.
+
+#[cfg(feature = "faster-hex")]
+pub fn encode<T: AsRef<[u8]>>(data: T) -> String {
+    faster_hex::hex_string(data.as_ref())
+}
+
+#[cfg(not(feature = "faster-hex"))]
+pub fn encode<T: AsRef<[u8]>>(data: T) -> String {
+    hex::encode(data)
+}
+
+#[cfg(feature = "faster-hex")]
+pub fn decode<T: AsRef<[u8]>>(data: T) -> Result<Vec<u8>, String> {
+    let src = data.as_ref();
+    if src.len() % 2 != 0 {
+        return Err(format!("odd number of hex digits: {}", src.len()));
+    }
+    let mut bytes = vec![0; src.len() / 2];
+    faster_hex::hex_decode(src, &mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "faster-hex"))]
+pub fn decode<T: AsRef<[u8]>>(data: T) -> Result<Vec<u8>, String> {
+    hex::decode(data).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode};
+
+    #[test]
+    fn round_trips_bytes() {
+        let bytes = vec![0x00, 0x63, 0x03, 0x6f, 0x72, 0x64, 0xff];
+        assert_eq!(encode(&bytes), "0063036f7264ff");
+        assert_eq!(decode("0063036f7264ff").unwrap(), bytes);
+        assert_eq!(decode("0063036F7264FF").unwrap(), bytes);
+        assert!(decode("006").is_err());
+        assert!(decode("zz").is_err());
+    }
+}
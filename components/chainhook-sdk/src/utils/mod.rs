@@ -1,4 +1,5 @@
 pub mod bitcoind;
+pub mod hex;
 
 use std::{
     collections::{BTreeSet, VecDeque},
@@ -11,6 +11,8 @@ pub enum OrdinalOperation {
 pub struct OrdinalInscriptionTransferData {
     pub ordinal_number: u64,
     pub destination: OrdinalInscriptionTransferDestination,
+    /// Address that held the inscription before this transfer, if its output had one.
+    pub from_address: Option<String>,
     pub satpoint_pre_transfer: String,
     pub satpoint_post_transfer: String,
     pub post_transfer_output_value: Option<u64>,
@@ -72,17 +72,27 @@ impl Eq for BlockIdentifier {}
 pub struct BitcoinBlockData {
     pub block_identifier: BlockIdentifier,
     pub parent_block_identifier: BlockIdentifier,
-    /// The timestamp of the block in milliseconds since the Unix Epoch. The
-    /// timestamp is stored in milliseconds because some blockchains produce
-    /// blocks more often than once a second.
+    /// The timestamp of the block header, in seconds since the Unix Epoch (UTC).
+    /// Use `timestamp_ms` for milliseconds.
     pub timestamp: u32,
     pub transactions: Vec<BitcoinTransactionData>,
     pub metadata: BitcoinBlockMetadata,
 }
 
+impl BitcoinBlockData {
+    /// The timestamp of the block header in milliseconds since the Unix Epoch
+    /// (UTC).
+    pub fn timestamp_ms(&self) -> u64 {
+        self.timestamp as u64 * 1000
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct BitcoinBlockMetadata {
     pub network: BitcoinNetwork,
+    /// Sequence number of the first ordinal or BRC-20 operation of the block, assigned once the block is indexed.
+    #[serde(default)]
+    pub first_operation_sequence: Option<u64>,
 }
 
 /// The timestamp of the block in milliseconds since the Unix Epoch. The
@@ -378,7 +388,7 @@ pub struct BlockchainUpdatedWithReorg {
     pub confirmed_headers: Vec<BlockHeader>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct BlockHeader {
     pub block_identifier: BlockIdentifier,
     pub parent_block_identifier: BlockIdentifier,
@@ -451,6 +461,11 @@ impl BitcoinNetwork {
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub enum BitcoinBlockSignaling {
     ZeroMQ(String),
+    /// Polls bitcoind's `getbestblockhash` every given number of milliseconds, for nodes that don't expose ZMQ.
+    Poll(u64),
+    /// Listens to the block announcements of a Bitcoin P2P peer at the given `host:port`. Blocks are still downloaded
+    /// over RPC.
+    P2P(String),
 }
 
 impl BitcoinBlockSignaling {
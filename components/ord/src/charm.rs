@@ -80,6 +80,14 @@ impl Charm {
             .filter(|charm| charm.is_set(charms))
             .collect()
     }
+
+    /// Names of the charms set in a bitfield, e.g. `["cursed", "vindicated"]`.
+    pub fn names(charms: u16) -> Vec<String> {
+        Self::charms(charms)
+            .into_iter()
+            .map(|charm| charm.to_string())
+            .collect()
+    }
 }
 
 impl Display for Charm {
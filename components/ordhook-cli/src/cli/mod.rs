@@ -1,8 +1,10 @@
 use crate::config::file::ConfigFile;
 use crate::config::generator::generate_config;
 use chainhook_sdk::utils::{BlockHeights, Context};
+use chainhook_types::BitcoinBlockData;
 use clap::{Parser, Subcommand};
 use hiro_system_kit;
+use ordhook::core::block_fixtures::record_block_fixture;
 use ordhook::core::first_inscription_height;
 use ordhook::core::pipeline::bitcoind_download_blocks;
 use ordhook::core::pipeline::processors::block_archiving::start_block_archiving_processor;
@@ -11,10 +13,28 @@ use ordhook::db::blocks::{
     open_blocks_db_with_retry, open_readonly_blocks_db,
 };
 use ordhook::db::cursor::BlockBytesCursor;
-use ordhook::db::{migrate_dbs, reset_dbs};
+use ordhook::db::{migrate_dbs, reset_dbs, MigrationError};
+use ordhook::service::activity_report::{
+    build_activity_report, write_activity_report, ActivityReportFormat,
+};
+use ordhook::service::activity_stream::{find_filter_occurrences, ActivityStreamFilter};
+use ordhook::service::brc20_backfill::backfill_brc20_from_ordinals_index;
+use ordhook::service::brc20_export::{export_brc20_balances, Brc20BalanceExportFormat};
+use ordhook::service::brc20_verify::verify_brc20_state;
+use ordhook::service::consistency::{reindex_from_block, verify_index_consistency};
+use ordhook::service::experiment_schemas::{
+    create_experiment_schemas, drop_experiment_schemas, use_experiment_schema,
+};
+use ordhook::service::replay::replay_blocks;
+use ordhook::service::status::get_index_status;
+use ordhook::service::takedown::take_down_inscription_content;
+use ordhook::service::utxo_export::{export_inscribed_utxos, UtxoExportFormat};
 use ordhook::service::Service;
 use ordhook::try_info;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::thread::sleep;
 use std::time::Duration;
 use std::{process, u64};
@@ -24,6 +44,152 @@ use std::{process, u64};
 struct Opts {
     #[clap(subcommand)]
     command: Command,
+    /// Write command results to stdout as text or json
+    #[clap(long = "output-format", global = true, default_value = "text")]
+    output_format: String,
+}
+
+/// Format of the results commands write to stdout. Logs are not affected.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format {s}, expected text or json")),
+        }
+    }
+}
+
+/// Kind of failure a command ended with, reported through the process exit code so orchestration can tell them apart.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum CliErrorKind {
+    /// Any failure that does not fall in one of the other kinds.
+    Failure,
+    /// Invalid arguments or configuration, including a database this release can't migrate.
+    Config,
+    /// Postgres, bitcoind or another remote service could not be reached or failed a request.
+    Connectivity,
+    /// The index does not match what it was checked against.
+    DataCorruption,
+    /// A confirmation prompt was declined.
+    UserAbort,
+}
+
+impl CliErrorKind {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliErrorKind::Failure => 1,
+            CliErrorKind::Config => 2,
+            CliErrorKind::Connectivity => 3,
+            CliErrorKind::DataCorruption => 4,
+            CliErrorKind::UserAbort => 5,
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            CliErrorKind::Failure => "failure",
+            CliErrorKind::Config => "config",
+            CliErrorKind::Connectivity => "connectivity",
+            CliErrorKind::DataCorruption => "data_corruption",
+            CliErrorKind::UserAbort => "user_abort",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct CliError {
+    kind: CliErrorKind,
+    message: String,
+}
+
+impl CliError {
+    fn config(message: String) -> Self {
+        CliError {
+            kind: CliErrorKind::Config,
+            message,
+        }
+    }
+
+    fn connectivity(message: String) -> Self {
+        CliError {
+            kind: CliErrorKind::Connectivity,
+            message,
+        }
+    }
+
+    fn data_corruption(message: String) -> Self {
+        CliError {
+            kind: CliErrorKind::DataCorruption,
+            message,
+        }
+    }
+
+    fn user_abort(message: String) -> Self {
+        CliError {
+            kind: CliErrorKind::UserAbort,
+            message,
+        }
+    }
+}
+
+impl From<MigrationError> for CliError {
+    fn from(error: MigrationError) -> Self {
+        match error {
+            MigrationError::Connectivity(message) => CliError::connectivity(message),
+            MigrationError::Schema(message) => CliError::config(message),
+        }
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError {
+            kind: CliErrorKind::Failure,
+            message,
+        }
+    }
+}
+
+/// Reads a block fixture: a JSON file holding either one indexed block or an array of blocks.
+fn read_block_fixtures(path: &Path) -> Result<Vec<BitcoinBlockData>, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+    let value: serde_json::Value = serde_json::from_slice(&bytes)
+        .map_err(|e| format!("unable to parse {}: {e}", path.display()))?;
+    let blocks = match value {
+        serde_json::Value::Array(_) => serde_json::from_value(value),
+        _ => serde_json::from_value(value).map(|block| vec![block]),
+    };
+    blocks.map_err(|e| format!("{} is not a block fixture: {e}", path.display()))
+}
+
+/// Writes a command result to stdout, as `text` or as a single line of `json`.
+fn print_result(format: OutputFormat, text: &str, json: serde_json::Value) {
+    match format {
+        OutputFormat::Text => println!("{text}"),
+        OutputFormat::Json => println!("{json}"),
+    }
+}
+
+/// Asks for a confirmation and returns the answer. With json output the question goes to stderr so stdout only holds
+/// results.
+fn prompt(format: OutputFormat, question: &str) -> String {
+    match format {
+        OutputFormat::Text => println!("{question}"),
+        OutputFormat::Json => eprintln!("{question}"),
+    }
+    let mut buffer = String::new();
+    std::io::stdin().read_line(&mut buffer).unwrap();
+    buffer
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -38,8 +204,135 @@ enum Command {
     #[clap(subcommand)]
     Index(IndexCommand),
     /// Database operations
-    #[clap(subcommand)]
+    #[clap(subcommand, aliases = &["db"])]
     Database(DatabaseCommand),
+    /// Query indexed ordinals data
+    #[clap(subcommand)]
+    Ordinals(OrdinalsCommand),
+    /// Generate aggregate reports from the index
+    #[clap(subcommand)]
+    Report(ReportCommand),
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum ReportCommand {
+    /// Report inscriptions, fees and BRC-20 volume by ticker per day over a date range
+    #[clap(name = "activity", bin_name = "activity")]
+    Activity(ActivityReportCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ActivityReportCommand {
+    /// First day of the report, as YYYY-MM-DD (UTC)
+    #[clap(long = "from-date")]
+    pub from_date: String,
+    /// Last day of the report, included, as YYYY-MM-DD (UTC)
+    #[clap(long = "to-date")]
+    pub to_date: String,
+    /// Report format, either `csv` or `json`
+    #[clap(long = "format", default_value = "csv")]
+    pub format: String,
+    /// File to write the report to, defaults to stdout
+    #[clap(long = "output")]
+    pub output: Option<PathBuf>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum OrdinalsCommand {
+    /// BRC-20 related commands
+    #[clap(subcommand)]
+    Brc20(Brc20Command),
+    /// Evaluate an activity stream filter against block fixtures, without a node or a database
+    #[clap(name = "match-filter", bin_name = "match-filter")]
+    MatchFilter(MatchFilterCommand),
+    /// Blank the stored content of an inscription and record a takedown tombstone, keeping its consensus data
+    #[clap(name = "takedown", bin_name = "takedown")]
+    Takedown(TakedownCommand),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct TakedownCommand {
+    /// Id of the inscription whose content is taken down
+    #[clap(long = "inscription-id")]
+    pub inscription_id: String,
+    /// Reason recorded in the tombstone, e.g. a reference to the takedown request
+    #[clap(long = "reason")]
+    pub reason: String,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct MatchFilterCommand {
+    /// Filter query string, as given to the activity stream, e.g. `address=bc1p...&sat_from=0`
+    #[clap(long = "filter", default_value = "")]
+    pub filter: String,
+    /// JSON file holding an indexed block or an array of blocks, can be repeated. Blocks are evaluated in the given
+    /// order
+    #[clap(long = "block", required = true)]
+    pub blocks: Vec<PathBuf>,
+    /// Load config file path, used for the `[sinks]` payload format
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Subcommand, PartialEq, Clone, Debug)]
+enum Brc20Command {
+    /// Export the balances of every BRC-20 holder at a given block height
+    #[clap(name = "export-balances", bin_name = "export-balances")]
+    ExportBalances(ExportBrc20BalancesCommand),
+    /// Compare BRC-20 tokens and balances against a reference indexer
+    #[clap(name = "verify", bin_name = "verify")]
+    Verify(VerifyBrc20Command),
+    /// Build the BRC-20 index from an existing ordinals index, without downloading blocks again
+    #[clap(name = "backfill", bin_name = "backfill")]
+    Backfill(BackfillBrc20Command),
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ExportBrc20BalancesCommand {
+    /// Block height to take the snapshot at
+    #[clap(long = "height")]
+    pub height: u64,
+    /// Export format, either `csv` or `json`
+    #[clap(long = "format", default_value = "csv")]
+    pub format: String,
+    /// File to write the export to, defaults to stdout
+    #[clap(long = "output")]
+    pub output: Option<PathBuf>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct VerifyBrc20Command {
+    /// Base URL of the reference ordinals API, e.g. https://api.hiro.so/ordinals/v1
+    #[clap(long = "against")]
+    pub against: String,
+    /// Only compare these tickers, can be repeated
+    #[clap(long = "ticker")]
+    pub tickers: Vec<String>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct BackfillBrc20Command {
+    /// First block to backfill, defaults to the BRC-20 activation height
+    #[clap(long = "start-block")]
+    pub start_block: Option<u64>,
+    /// Last block to backfill, defaults to the ordinals index chain tip
+    #[clap(long = "end-block")]
+    pub end_block: Option<u64>,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -50,6 +343,15 @@ enum DatabaseCommand {
     /// Resets database to an empty state
     #[clap(name = "reset", bin_name = "reset")]
     Reset(DatabaseMigrateCommand),
+    /// Creates an experiment schema so another index variant can live in the same databases
+    #[clap(name = "create-schema", bin_name = "create-schema")]
+    CreateSchema(DatabaseCreateSchemaCommand),
+    /// Drops an experiment schema
+    #[clap(name = "drop-schema", bin_name = "drop-schema")]
+    DropSchema(DatabaseDropSchemaCommand),
+    /// Cross-check the block hashes of the blocks DB against the ordinals and BRC-20 databases
+    #[clap(name = "verify", bin_name = "verify")]
+    Verify(DatabaseVerifyCommand),
 }
 
 #[derive(Parser, PartialEq, Clone, Debug)]
@@ -57,6 +359,46 @@ struct DatabaseMigrateCommand {
     /// Load config file path
     #[clap(long = "config-path")]
     pub config_path: Option<String>,
+    /// Use an experiment schema created with `database create-schema` instead of the configured search paths
+    #[clap(long = "schema")]
+    pub schema: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct DatabaseCreateSchemaCommand {
+    /// Schema name, BRC-20 tables go to `<schema>_brc20`
+    pub schema: String,
+    /// Start from a copy of the configured schemas instead of an empty index
+    #[clap(long = "copy-live")]
+    pub copy_live: bool,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct DatabaseDropSchemaCommand {
+    /// Schema name, BRC-20 tables are dropped from `<schema>_brc20`
+    pub schema: String,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct DatabaseVerifyCommand {
+    /// First block to compare, defaults to the first inscription height
+    #[clap(long = "from")]
+    pub from: Option<u64>,
+    /// Last block to compare, defaults to the ordinals index chain tip
+    #[clap(long = "to")]
+    pub to: Option<u64>,
+    /// Roll back to the first divergent block and index again from there
+    #[clap(long = "repair")]
+    pub repair: bool,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -192,6 +534,12 @@ struct StartCommand {
     /// Check blocks integrity
     #[clap(long = "check-blocks-integrity")]
     pub block_integrity_check: bool,
+    /// Index without writing to Postgres or delivering webhooks
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+    /// Use an experiment schema created with `database create-schema` instead of the configured search paths
+    #[clap(long = "schema")]
+    pub schema: Option<String>,
 }
 
 #[derive(Subcommand, PartialEq, Clone, Debug)]
@@ -208,9 +556,21 @@ enum IndexCommand {
     /// Check integrity
     #[clap(name = "check", bin_name = "check")]
     Check(CheckDbCommand),
+    /// Show the chain tips of the local stores next to bitcoind's
+    #[clap(name = "status", bin_name = "status")]
+    Status(StatusCommand),
     /// Db maintenance related commands
     #[clap(subcommand)]
     Repair(RepairCommand),
+    /// Re-index a block range into a scratch schema and diff it against the live index
+    #[clap(name = "replay", bin_name = "replay")]
+    Replay(ReplayOrdhookDbCommand),
+    /// Export the current location of every inscription for seeding external databases
+    #[clap(name = "export-utxos", bin_name = "export-utxos")]
+    ExportUtxos(ExportUtxosCommand),
+    /// Download a range of blocks from bitcoind into a compressed fixture, to index them in tests without a node
+    #[clap(name = "record-fixture", bin_name = "record-fixture")]
+    RecordFixture(RecordFixtureCommand),
 }
 
 #[derive(Parser, PartialEq, Clone, Debug)]
@@ -231,6 +591,9 @@ struct SyncOrdhookDbCommand {
     /// Load config file path
     #[clap(long = "config-path")]
     pub config_path: Option<String>,
+    /// Use an experiment schema created with `database create-schema` instead of the configured search paths
+    #[clap(long = "schema")]
+    pub schema: Option<String>,
 }
 
 #[derive(Parser, PartialEq, Clone, Debug)]
@@ -255,6 +618,61 @@ struct MigrateOrdhookDbCommand {
     pub config_path: Option<String>,
 }
 
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct StatusCommand {
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ReplayOrdhookDbCommand {
+    /// Starting block
+    #[clap(long = "from")]
+    pub from: u64,
+    /// Ending block
+    #[clap(long = "to")]
+    pub to: u64,
+    /// Scratch schema to replay into, it is dropped and re-created
+    #[clap(long = "scratch-schema", default_value = "ordhook_replay")]
+    pub scratch_schema: String,
+    /// Keep the scratch schema after the diff for manual inspection
+    #[clap(long = "keep-scratch-schema")]
+    pub keep_scratch_schema: bool,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct ExportUtxosCommand {
+    /// File to write the export to
+    #[clap(long = "output")]
+    pub output: PathBuf,
+    /// Export format, either `csv` or `binary`
+    #[clap(long = "format", default_value = "csv")]
+    pub format: String,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
+#[derive(Parser, PartialEq, Clone, Debug)]
+struct RecordFixtureCommand {
+    /// First block to record
+    #[clap(long = "start-block")]
+    pub start_block: u64,
+    /// Last block to record
+    #[clap(long = "end-block")]
+    pub end_block: u64,
+    /// File to write the fixture to, e.g. `blocks.jsonl.gz`
+    #[clap(long = "output")]
+    pub output: PathBuf,
+    /// Load config file path
+    #[clap(long = "config-path")]
+    pub config_path: Option<String>,
+}
+
 #[derive(Parser, PartialEq, Clone, Debug)]
 struct CheckDbCommand {
     /// Starting block
@@ -278,18 +696,36 @@ pub fn main() {
         Ok(opts) => opts,
         Err(e) => {
             println!("{}", e);
-            process::exit(1);
+            process::exit(if e.use_stderr() {
+                CliErrorKind::Config.exit_code()
+            } else {
+                0
+            });
+        }
+    };
+
+    let output_format = match OutputFormat::from_str(&opts.output_format) {
+        Ok(format) => format,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(CliErrorKind::Config.exit_code());
         }
     };
 
-    if let Err(e) = hiro_system_kit::nestable_block_on(handle_command(opts, &ctx)) {
-        error!(ctx.expect_logger(), "{e}");
+    if let Err(e) = hiro_system_kit::nestable_block_on(handle_command(opts, output_format, &ctx)) {
+        if output_format == OutputFormat::Json {
+            println!(
+                "{}",
+                serde_json::json!({ "error": e.message, "kind": e.kind.name() })
+            );
+        }
+        error!(ctx.expect_logger(), "{}", e.message);
         std::thread::sleep(std::time::Duration::from_millis(500));
-        process::exit(1);
+        process::exit(e.kind.exit_code());
     }
 }
 
-async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
+async fn handle_command(opts: Opts, format: OutputFormat, ctx: &Context) -> Result<(), CliError> {
     match opts.command {
         Command::Service(subcmd) => match subcmd {
             ServiceCommand::Start(cmd) => {
@@ -300,23 +736,41 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     sleep(Duration::from_secs(u64::MAX))
                 }
 
-                let config = ConfigFile::default(
+                let mut config = ConfigFile::default(
                     cmd.regtest,
                     cmd.testnet,
                     cmd.mainnet,
                     &cmd.config_path,
                     &None,
-                )?;
+                )
+                .map_err(CliError::config)?;
+                config.dry_run = cmd.dry_run;
+                if let Some(schema) = &cmd.schema {
+                    use_experiment_schema(&mut config, schema).map_err(CliError::config)?;
+                }
 
-                migrate_dbs(&config, ctx).await?;
+                if config.dry_run {
+                    try_info!(
+                        ctx,
+                        "Dry-run mode: Postgres writes and webhooks are disabled"
+                    );
+                } else {
+                    migrate_dbs(&config, ctx).await?;
+                }
 
                 let mut service = Service::new(&config, ctx);
                 // TODO(rafaelcr): This only works if there's a rocksdb file already containing blocks previous to the first
                 // inscription height.
-                let start_block = service.get_index_chain_tip().await?;
+                let start_block = service
+                    .get_index_chain_tip()
+                    .await
+                    .map_err(CliError::connectivity)?;
                 try_info!(ctx, "Index chain tip is at #{start_block}");
 
-                return service.run(cmd.block_integrity_check).await;
+                return service
+                    .run(cmd.block_integrity_check)
+                    .await
+                    .map_err(CliError::from);
             }
         },
         Command::Config(subcmd) => match subcmd {
@@ -324,7 +778,8 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 use std::fs::File;
                 use std::io::Write;
                 let config =
-                    ConfigFile::default(cmd.regtest, cmd.testnet, cmd.mainnet, &None, &None)?;
+                    ConfigFile::default(cmd.regtest, cmd.testnet, cmd.mainnet, &None, &None)
+                        .map_err(CliError::config)?;
                 let config_content = generate_config(&config.network.bitcoin_network);
                 let mut file_path = PathBuf::new();
                 file_path.push("Ordhook.toml");
@@ -332,23 +787,33 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     .map_err(|e| format!("unable to open file {}\n{}", file_path.display(), e))?;
                 file.write_all(config_content.as_bytes())
                     .map_err(|e| format!("unable to write file {}\n{}", file_path.display(), e))?;
-                println!("Created file Ordhook.toml");
+                print_result(
+                    format,
+                    "Created file Ordhook.toml",
+                    serde_json::json!({ "config_path": file_path }),
+                );
             }
         },
         Command::Index(IndexCommand::New(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
             migrate_dbs(&config, ctx).await?;
             open_blocks_db_with_retry(true, &config, ctx);
         }
         Command::Index(IndexCommand::Sync(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let mut config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            if let Some(schema) = &cmd.schema {
+                use_experiment_schema(&mut config, schema).map_err(CliError::config)?;
+            }
             migrate_dbs(&config, ctx).await?;
             let service = Service::new(&config, ctx);
             service.catch_up_to_bitcoin_chain_tip().await?;
         }
         Command::Index(IndexCommand::Repair(subcmd)) => match subcmd {
             RepairCommand::Blocks(cmd) => {
-                let mut config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+                let mut config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                    .map_err(CliError::config)?;
                 if let Some(network_threads) = cmd.network_threads {
                     config.resources.bitcoind_rpc_threads = network_threads;
                 }
@@ -361,6 +826,7 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                     first_inscription_height(&config),
                     &block_ingestion_processor,
                     10_000,
+                    None,
                     ctx,
                 )
                 .await?;
@@ -380,53 +846,506 @@ async fn handle_command(opts: Opts, ctx: &Context) -> Result<(), String> {
                 }
             }
         },
+        Command::Index(IndexCommand::Replay(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            migrate_dbs(&config, ctx).await?;
+            let report = replay_blocks(
+                &config,
+                cmd.from,
+                cmd.to,
+                &cmd.scratch_schema,
+                cmd.keep_scratch_schema,
+                ctx,
+            )
+            .await?;
+            let mut lines = vec![];
+            if !report.skipped_tables.is_empty() {
+                lines.push(format!(
+                    "Skipped state tables, the live index is past #{}: {}",
+                    cmd.to,
+                    report.skipped_tables.join(", ")
+                ));
+            }
+            if report.diffs.is_empty() {
+                lines.push(format!(
+                    "Replay of #{} to #{} matches the live index",
+                    cmd.from, cmd.to
+                ));
+            }
+            for diff in report.diffs.iter() {
+                lines.push(format!(
+                    "{}: {} live rows missing from replay, {} unexpected replayed rows",
+                    diff.table, diff.missing_rows, diff.unexpected_rows
+                ));
+            }
+            let diffs: Vec<serde_json::Value> = report
+                .diffs
+                .iter()
+                .map(|diff| {
+                    serde_json::json!({
+                        "table": diff.table,
+                        "missing_rows": diff.missing_rows,
+                        "unexpected_rows": diff.unexpected_rows,
+                    })
+                })
+                .collect();
+            print_result(
+                format,
+                &lines.join("\n"),
+                serde_json::json!({
+                    "from": cmd.from,
+                    "to": cmd.to,
+                    "matches": report.diffs.is_empty(),
+                    "skipped_tables": report.skipped_tables,
+                    "diffs": diffs,
+                }),
+            );
+            if !report.diffs.is_empty() {
+                return Err(CliError::data_corruption(format!(
+                    "Replay diverged from the live index in {} tables",
+                    report.diffs.len()
+                )));
+            }
+        }
+        Command::Index(IndexCommand::ExportUtxos(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let export_format =
+                UtxoExportFormat::from_str(&cmd.format).map_err(CliError::config)?;
+            let file = File::create(&cmd.output)
+                .map_err(|e| format!("unable to create {}: {e}", cmd.output.display()))?;
+            let mut writer = BufWriter::new(file);
+            let (chain_tip, count) =
+                export_inscribed_utxos(&config, export_format, &mut writer, ctx).await?;
+            print_result(
+                format,
+                &format!(
+                    "Exported {count} inscriptions at block #{chain_tip} to {}",
+                    cmd.output.display()
+                ),
+                serde_json::json!({
+                    "block_height": chain_tip,
+                    "count": count,
+                    "output": cmd.output,
+                }),
+            );
+        }
+        Command::Index(IndexCommand::RecordFixture(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let file = File::create(&cmd.output)
+                .map_err(|e| format!("unable to create {}: {e}", cmd.output.display()))?;
+            let count = record_block_fixture(
+                &config,
+                cmd.start_block,
+                cmd.end_block,
+                BufWriter::new(file),
+                ctx,
+            )
+            .await
+            .map_err(CliError::connectivity)?;
+            print_result(
+                format,
+                &format!("Recorded {count} blocks to {}", cmd.output.display()),
+                serde_json::json!({
+                    "start_block": cmd.start_block,
+                    "end_block": cmd.end_block,
+                    "count": count,
+                    "output": cmd.output,
+                }),
+            );
+        }
         Command::Index(IndexCommand::Check(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
             {
                 let blocks_db = open_readonly_blocks_db(&config, ctx)?;
                 let tip = find_last_block_inserted(&blocks_db);
-                println!("Tip: {}", tip);
                 let missing_blocks = find_missing_blocks(&blocks_db, 1, tip, ctx);
-                println!("{:?}", missing_blocks);
+                print_result(
+                    format,
+                    &format!("Tip: {}\n{:?}", tip, missing_blocks),
+                    serde_json::json!({ "tip": tip, "missing_blocks": missing_blocks }),
+                );
+            }
+        }
+        Command::Index(IndexCommand::Status(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let status = get_index_status(&config, ctx).await;
+            let height = |block_height: Option<u64>| {
+                block_height.map_or("unknown".to_string(), |h| format!("#{h}"))
+            };
+            let behind = |blocks: Option<u64>| {
+                blocks.map_or("".to_string(), |b| format!(" ({b} blocks behind)"))
+            };
+            let mut lines = vec![
+                format!("bitcoind: {}", height(status.bitcoind_block_height)),
+                format!(
+                    "blocks db: {}{}",
+                    height(status.blocks_db_block_height),
+                    behind(status.blocks_db_blocks_behind())
+                ),
+                format!(
+                    "ordinals db: {}{}",
+                    height(status.ordinals_block_height),
+                    behind(status.ordinals_blocks_behind())
+                ),
+            ];
+            if config.meta_protocols.brc20 {
+                lines.push(format!(
+                    "brc20 db: last operation at {}",
+                    height(status.brc20_last_operation_block_height)
+                ));
+            }
+            for error in status.errors.iter() {
+                lines.push(format!("error: {error}"));
+            }
+            print_result(
+                format,
+                &lines.join("\n"),
+                serde_json::json!({
+                    "bitcoind_block_height": status.bitcoind_block_height,
+                    "blocks_db_block_height": status.blocks_db_block_height,
+                    "blocks_db_blocks_behind": status.blocks_db_blocks_behind(),
+                    "ordinals_block_height": status.ordinals_block_height,
+                    "ordinals_blocks_behind": status.ordinals_blocks_behind(),
+                    "brc20_last_operation_block_height": status.brc20_last_operation_block_height,
+                    "errors": status.errors,
+                }),
+            );
+            if !status.errors.is_empty() {
+                return Err(CliError::connectivity(format!(
+                    "unable to read {} chain tips",
+                    status.errors.len()
+                )));
             }
         }
         Command::Index(IndexCommand::Drop(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
 
             let service = Service::new(&config, ctx);
-            let chain_tip = service.get_index_chain_tip().await?;
-            println!("Index chain tip is at #{chain_tip}");
-            println!(
-                "{} blocks will be dropped. New index chain tip will be at #{}. Confirm? [Y/n]",
-                cmd.blocks,
-                chain_tip - cmd.blocks as u64
+            let chain_tip = service
+                .get_index_chain_tip()
+                .await
+                .map_err(CliError::connectivity)?;
+            let buffer = prompt(
+                format,
+                &format!(
+                    "Index chain tip is at #{chain_tip}\n{} blocks will be dropped. New index chain tip will be at #{}. Confirm? [Y/n]",
+                    cmd.blocks,
+                    chain_tip - cmd.blocks as u64
+                ),
             );
-            let mut buffer = String::new();
-            std::io::stdin().read_line(&mut buffer).unwrap();
             if buffer.starts_with('n') {
-                return Err("Deletion aborted".to_string());
+                return Err(CliError::user_abort("Deletion aborted".to_string()));
             }
 
             let service = Service::new(&config, ctx);
             let block_heights: Vec<u64> = ((chain_tip - cmd.blocks as u64)..=chain_tip).collect();
             service.rollback(&block_heights).await?;
-            println!("{} blocks dropped", cmd.blocks);
+            print_result(
+                format,
+                &format!("{} blocks dropped", cmd.blocks),
+                serde_json::json!({
+                    "dropped_blocks": cmd.blocks,
+                    "chain_tip": chain_tip - cmd.blocks as u64,
+                }),
+            );
+        }
+        Command::Ordinals(OrdinalsCommand::Brc20(Brc20Command::ExportBalances(cmd))) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let export_format =
+                Brc20BalanceExportFormat::from_str(&cmd.format).map_err(CliError::config)?;
+            let count = match &cmd.output {
+                Some(output) => {
+                    let file = File::create(output)
+                        .map_err(|e| format!("unable to create {}: {e}", output.display()))?;
+                    export_brc20_balances(
+                        &config,
+                        cmd.height,
+                        export_format,
+                        &mut BufWriter::new(file),
+                        ctx,
+                    )
+                    .await?
+                }
+                None => {
+                    export_brc20_balances(
+                        &config,
+                        cmd.height,
+                        export_format,
+                        &mut io::stdout().lock(),
+                        ctx,
+                    )
+                    .await?
+                }
+            };
+            try_info!(
+                ctx,
+                "Exported {count} BRC-20 balances at block #{}",
+                cmd.height
+            );
+            // Without an output file the balances themselves are written to stdout.
+            if let (OutputFormat::Json, Some(output)) = (format, &cmd.output) {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "block_height": cmd.height,
+                        "count": count,
+                        "output": output,
+                    })
+                );
+            }
+        }
+        Command::Ordinals(OrdinalsCommand::MatchFilter(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let filter = ActivityStreamFilter::from_query(Some(&cmd.filter))
+                .map_err(|e| CliError::config(format!("invalid filter: {e}")))?;
+            let mut blocks = vec![];
+            for path in cmd.blocks.iter() {
+                blocks.extend(read_block_fixtures(path).map_err(CliError::config)?);
+            }
+            let occurrences =
+                find_filter_occurrences(&filter, &blocks, config.sinks.payload_format());
+            let mut lines = vec![];
+            for event in occurrences.iter() {
+                lines.push(serde_json::to_string(event).map_err(|e| e.to_string())?);
+            }
+            lines.push(format!(
+                "{} occurrences in {} blocks",
+                occurrences.len(),
+                blocks.len()
+            ));
+            print_result(
+                format,
+                &lines.join("\n"),
+                serde_json::to_value(&occurrences).map_err(|e| e.to_string())?,
+            );
+        }
+        Command::Ordinals(OrdinalsCommand::Takedown(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let buffer = prompt(
+                format,
+                &format!(
+                    "WARNING: The stored content of inscription {} will be blanked and cannot be restored. Confirm? [Y/n]",
+                    cmd.inscription_id
+                ),
+            );
+            if buffer.to_lowercase().starts_with('n') {
+                return Err(CliError::user_abort("Aborted".to_string()));
+            }
+            migrate_dbs(&config, ctx).await?;
+            let report =
+                take_down_inscription_content(&config, &cmd.inscription_id, &cmd.reason, ctx)
+                    .await?;
+            let message = if report.indexed {
+                format!("Content of inscription {} taken down", cmd.inscription_id)
+            } else {
+                format!(
+                    "Inscription {} is not indexed yet, its content will be blanked when it is",
+                    cmd.inscription_id
+                )
+            };
+            print_result(
+                format,
+                &message,
+                serde_json::to_value(&report).map_err(|e| e.to_string())?,
+            );
+        }
+        Command::Report(ReportCommand::Activity(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let report_format =
+                ActivityReportFormat::from_str(&cmd.format).map_err(CliError::config)?;
+            let report = build_activity_report(&config, &cmd.from_date, &cmd.to_date, ctx).await?;
+            match &cmd.output {
+                Some(output) => {
+                    let file = File::create(output)
+                        .map_err(|e| format!("unable to create {}: {e}", output.display()))?;
+                    write_activity_report(&report, report_format, &mut BufWriter::new(file))?;
+                }
+                None => write_activity_report(&report, report_format, &mut io::stdout().lock())?,
+            }
+            try_info!(
+                ctx,
+                "Reported activity of {} days from {} to {}",
+                report.days.len(),
+                cmd.from_date,
+                cmd.to_date
+            );
+        }
+        Command::Ordinals(OrdinalsCommand::Brc20(Brc20Command::Verify(cmd))) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let mut report = vec![];
+            let mismatches = match format {
+                OutputFormat::Text => {
+                    verify_brc20_state(
+                        &config,
+                        &cmd.against,
+                        &cmd.tickers,
+                        &mut io::stdout().lock(),
+                        ctx,
+                    )
+                    .await?
+                }
+                OutputFormat::Json => {
+                    verify_brc20_state(&config, &cmd.against, &cmd.tickers, &mut report, ctx)
+                        .await?
+                }
+            };
+            if format == OutputFormat::Json {
+                let report = String::from_utf8_lossy(&report);
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "against": cmd.against,
+                        "mismatches": report.lines().collect::<Vec<_>>(),
+                    })
+                );
+            }
+            if mismatches > 0 {
+                return Err(CliError::data_corruption(format!(
+                    "found {mismatches} BRC-20 mismatches against {}",
+                    cmd.against
+                )));
+            }
+            try_info!(ctx, "BRC-20 state matches {}", cmd.against);
+        }
+        Command::Ordinals(OrdinalsCommand::Brc20(Brc20Command::Backfill(cmd))) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            migrate_dbs(&config, ctx).await?;
+            let count =
+                backfill_brc20_from_ordinals_index(&config, cmd.start_block, cmd.end_block, ctx)
+                    .await?;
+            try_info!(ctx, "Backfilled BRC-20 operations of {count} blocks");
+            if format == OutputFormat::Json {
+                println!("{}", serde_json::json!({ "blocks": count }));
+            }
         }
         Command::Database(DatabaseCommand::Migrate(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
+            let mut config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            if let Some(schema) = &cmd.schema {
+                use_experiment_schema(&mut config, schema).map_err(CliError::config)?;
+            }
             migrate_dbs(&config, ctx).await?;
         }
         Command::Database(DatabaseCommand::Reset(cmd)) => {
-            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)?;
-            println!(
-                "WARNING: This operation will delete ALL index data and cannot be undone. Confirm? [Y/n]"
+            let mut config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            if let Some(schema) = &cmd.schema {
+                use_experiment_schema(&mut config, schema).map_err(CliError::config)?;
+            }
+            let buffer = prompt(
+                format,
+                "WARNING: This operation will delete ALL index data and cannot be undone. Confirm? [Y/n]",
+            );
+            if buffer.to_lowercase().starts_with('n') {
+                return Err(CliError::user_abort("Aborted".to_string()));
+            }
+            reset_dbs(&config, ctx)
+                .await
+                .map_err(CliError::connectivity)?;
+            if format == OutputFormat::Json {
+                println!("{}", serde_json::json!({ "reset": true }));
+            }
+        }
+        Command::Database(DatabaseCommand::CreateSchema(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            create_experiment_schemas(&config, &cmd.schema, cmd.copy_live, ctx).await?;
+            print_result(
+                format,
+                &format!(
+                    "Created schema {}, index into it with --schema {}",
+                    cmd.schema, cmd.schema
+                ),
+                serde_json::json!({ "schema": cmd.schema }),
+            );
+        }
+        Command::Database(DatabaseCommand::DropSchema(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let buffer = prompt(
+                format,
+                &format!(
+                    "WARNING: This operation will delete ALL index data in schema {} and cannot be undone. Confirm? [Y/n]",
+                    cmd.schema
+                ),
+            );
+            if buffer.to_lowercase().starts_with('n') {
+                return Err(CliError::user_abort("Aborted".to_string()));
+            }
+            drop_experiment_schemas(&config, &cmd.schema, ctx).await?;
+            if format == OutputFormat::Json {
+                println!("{}", serde_json::json!({ "dropped_schema": cmd.schema }));
+            }
+        }
+        Command::Database(DatabaseCommand::Verify(cmd)) => {
+            let config = ConfigFile::default(false, false, false, &cmd.config_path, &None)
+                .map_err(CliError::config)?;
+            let report = verify_index_consistency(&config, cmd.from, cmd.to, ctx)
+                .await
+                .map_err(CliError::connectivity)?;
+            let mut lines = vec![format!(
+                "Compared block hashes from #{} to #{}",
+                report.from, report.to
+            )];
+            for divergence in report.height_divergences.iter() {
+                lines.push(format!("height: {divergence}"));
+            }
+            for divergence in report.hash_divergences.iter() {
+                lines.push(format!(
+                    "#{}: {} db has {}, blocks db has {}",
+                    divergence.block_height,
+                    divergence.store,
+                    divergence.store_block_hash,
+                    divergence.blocks_db_block_hash
+                ));
+            }
+            if report.is_consistent() {
+                lines.push("Blocks DB and indexes are consistent".to_string());
+            }
+            print_result(
+                format,
+                &lines.join("\n"),
+                serde_json::to_value(&report).map_err(|e| e.to_string())?,
+            );
+            if report.is_consistent() {
+                return Ok(());
+            }
+            let Some(first_divergent_block_height) = report.first_divergent_block_height() else {
+                return Err(CliError::data_corruption(format!(
+                    "{} chain tips diverge",
+                    report.height_divergences.len()
+                )));
+            };
+            if !cmd.repair {
+                return Err(CliError::data_corruption(format!(
+                    "{} blocks diverge, first at #{first_divergent_block_height}, run with --repair to re-index them",
+                    report.hash_divergences.len()
+                )));
+            }
+            let buffer = prompt(
+                format,
+                &format!(
+                    "Indexes will be rolled back to #{} and re-indexed from there. Confirm? [Y/n]",
+                    first_divergent_block_height.saturating_sub(1)
+                ),
             );
-            let mut buffer = String::new();
-            std::io::stdin().read_line(&mut buffer).unwrap();
             if buffer.to_lowercase().starts_with('n') {
-                return Err("Aborted".to_string());
+                return Err(CliError::user_abort("Repair aborted".to_string()));
             }
-            reset_dbs(&config, ctx).await?;
+            migrate_dbs(&config, ctx).await?;
+            reindex_from_block(&config, first_divergent_block_height, ctx).await?;
         }
     }
     Ok(())
@@ -1,13 +1,20 @@
 use chainhook_types::{BitcoinBlockSignaling, BitcoinNetwork};
+use chainhook_sdk::indexer::bitcoin::BitcoindRpcEndpoints;
 use chainhook_sdk::indexer::IndexerConfig;
 use ordhook::config::{
-    Config, LogConfig, MetaProtocolsConfig, ResourcesConfig, SnapshotConfig,
-    SnapshotConfigDownloadUrls, StorageConfig, DEFAULT_BITCOIND_RPC_THREADS,
-    DEFAULT_BITCOIND_RPC_TIMEOUT, DEFAULT_BRC20_LRU_CACHE_SIZE, DEFAULT_MEMORY_AVAILABLE,
-    DEFAULT_ULIMIT,
+    AddressClusteringConfig, AddressWatchConfig, AdminConfig, ApiConfig, Brc20ModulesConfig,
+    Brc20Strictness, Config, ContentPolicyConfig, GrpcConfig, ListenAddress, LogConfig,
+    MetaProtocolsConfig, NatsConfig, ObserversStateConfig, RedisConfig, ResourcesConfig,
+    S3StateConfig, ShadowConfig, SinksConfig, SnapshotConfig, SnapshotConfigDownloadUrls,
+    StorageConfig, WebhookAuthorizationSource, WebhookClientTlsConfig, WebhookConfig,
+    DEFAULT_API_RESPONSE_CACHE_SIZE, DEFAULT_BITCOIND_POLL_INTERVAL_MS,
+    DEFAULT_BITCOIND_RPC_THREADS, DEFAULT_BITCOIND_RPC_TIMEOUT, DEFAULT_BLOCKS_PER_COMMIT, DEFAULT_BRC20_LRU_CACHE_SIZE, DEFAULT_MEMORY_AVAILABLE,
+    DEFAULT_THROTTLE_MAX_WAL_BYTES_PER_SEC, DEFAULT_ULIMIT, DEFAULT_WEBHOOK_MAX_ATTEMPTS,
 };
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::{BufReader, Read};
+use std::net::Ipv4Addr;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct ConfigFile {
@@ -20,6 +27,17 @@ pub struct ConfigFile {
     pub logs: Option<LogConfigFile>,
     pub snapshot: Option<SnapshotConfigFile>,
     pub meta_protocols: Option<MetaProtocolsConfigFile>,
+    pub address_watch: Option<AddressWatchConfigFile>,
+    pub api: Option<ApiConfigFile>,
+    pub grpc: Option<GrpcConfigFile>,
+    pub admin: Option<AdminConfigFile>,
+    pub shadow: Option<ShadowConfigFile>,
+    pub nats: Option<NatsConfigFile>,
+    pub redis: Option<RedisConfigFile>,
+    pub sinks: Option<SinksConfigFile>,
+    pub address_clustering: Option<AddressClusteringConfigFile>,
+    pub content_policy: Option<ContentPolicyConfigFile>,
+    pub webhook: Option<WebhookConfigFile>,
 }
 
 impl ConfigFile {
@@ -49,6 +67,70 @@ impl ConfigFile {
             "signet" => BitcoinNetwork::Signet,
             _ => return Err("network.mode not supported".to_string()),
         };
+        if config_file.network.bitcoind_rpc_cookie_path.is_none()
+            && config_file.network.bitcoind_rpc_username.is_empty()
+        {
+            return Err(
+                "network: bitcoind_rpc_username or bitcoind_rpc_cookie_path is required".into(),
+            );
+        }
+        require_feature("grpc", config_file.grpc.is_some(), cfg!(feature = "grpc"))?;
+        require_feature("nats", config_file.nats.is_some(), cfg!(feature = "nats"))?;
+        require_feature(
+            "redis",
+            config_file.redis.is_some(),
+            cfg!(feature = "redis"),
+        )?;
+
+        let observers_state = match config_file.storage.observers_state.as_deref() {
+            None | Some("local") => ObserversStateConfig::Local,
+            Some("postgres") => ObserversStateConfig::Postgres,
+            Some("s3") => {
+                if !cfg!(feature = "s3") {
+                    return Err(
+                        "storage.observers_state: ordhook was built without the s3 feature".into(),
+                    );
+                }
+                let Some(bucket) = config_file.storage.observers_s3_bucket.clone() else {
+                    return Err(
+                        "storage.observers_s3_bucket is required by s3 observers_state".into(),
+                    );
+                };
+                ObserversStateConfig::S3(S3StateConfig {
+                    bucket,
+                    prefix: config_file
+                        .storage
+                        .observers_s3_prefix
+                        .clone()
+                        .unwrap_or_default(),
+                    region: config_file
+                        .storage
+                        .observers_s3_region
+                        .clone()
+                        .unwrap_or("us-east-1".into()),
+                    endpoint: config_file.storage.observers_s3_endpoint.clone(),
+                })
+            }
+            Some(_) => {
+                return Err("storage.observers_state must be local, postgres or s3".to_string())
+            }
+        };
+
+        let brc20_strictness = match config_file
+            .meta_protocols
+            .as_ref()
+            .and_then(|l| l.brc20_strictness.as_deref())
+        {
+            None | Some("strict") => Brc20Strictness::Strict,
+            Some("vindicated") => Brc20Strictness::Vindicated,
+            Some("permissive") => Brc20Strictness::Permissive,
+            Some(_) => {
+                return Err(
+                    "meta_protocols.brc20_strictness must be strict, vindicated or permissive"
+                        .to_string(),
+                )
+            }
+        };
 
         let snapshot = match config_file.snapshot {
             Some(bootstrap) => match bootstrap.ordinals_url {
@@ -61,6 +143,134 @@ impl ConfigFile {
             None => SnapshotConfig::Build,
         };
 
+        let address_watch = match config_file.address_watch {
+            Some(address_watch) => {
+                let mut addresses: HashSet<String> = address_watch
+                    .addresses
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect();
+                if let Some(addresses_file) = address_watch.addresses_file {
+                    let content = std::fs::read_to_string(&addresses_file).map_err(|e| {
+                        format!("unable to read address watch file {addresses_file}: {e}")
+                    })?;
+                    addresses.extend(
+                        content
+                            .lines()
+                            .map(|l| l.trim())
+                            .filter(|l| !l.is_empty())
+                            .map(|l| l.to_string()),
+                    );
+                }
+                let authorization = webhook_authorization_source(
+                    "address_watch",
+                    address_watch.authorization_header_env,
+                    address_watch.authorization_header_file,
+                )?;
+                let tls = webhook_client_tls(
+                    "address_watch",
+                    address_watch.client_certificate,
+                    address_watch.client_key,
+                    address_watch.ca_certificate,
+                )?;
+                Some(AddressWatchConfig {
+                    url: address_watch.url,
+                    addresses,
+                    parents: address_watch
+                        .parents
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    delegates: address_watch
+                        .delegates
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    authorization,
+                    tls,
+                    digest_interval_secs: address_watch.digest_interval_secs,
+                })
+            }
+            None => None,
+        };
+
+        let webhook = match config_file.webhook {
+            Some(webhook) => Some(WebhookConfig {
+                url: webhook.url,
+                authorization: webhook_authorization_source(
+                    "webhook",
+                    webhook.authorization_header_env,
+                    webhook.authorization_header_file,
+                )?,
+                tls: webhook_client_tls(
+                    "webhook",
+                    webhook.client_certificate,
+                    webhook.client_key,
+                    webhook.ca_certificate,
+                )?,
+                max_attempts: webhook.max_attempts.unwrap_or(DEFAULT_WEBHOOK_MAX_ATTEMPTS),
+            }),
+            None => None,
+        };
+
+        let prometheus_listen_address = ListenAddress::from_settings(
+            config_file
+                .network
+                .prometheus_monitoring_bind_address
+                .as_deref(),
+            config_file.network.prometheus_monitoring_port,
+        )?;
+
+        let api = match config_file.api {
+            Some(api) => {
+                let Some(listen_address) =
+                    ListenAddress::from_settings(api.bind_address.as_deref(), api.http_port)?
+                else {
+                    return Err("api: http_port or a unix bind_address is required".into());
+                };
+                Some(ApiConfig {
+                    listen_address,
+                    response_cache_size: api
+                        .response_cache_size
+                        .unwrap_or(DEFAULT_API_RESPONSE_CACHE_SIZE),
+                    mempool_reveals: api.mempool_reveals.unwrap_or(false),
+                })
+            }
+            None => None,
+        };
+
+        let grpc = match config_file.grpc {
+            Some(grpc) => {
+                let Some(listen_address) =
+                    ListenAddress::from_settings(grpc.bind_address.as_deref(), grpc.grpc_port)?
+                else {
+                    return Err("grpc: grpc_port or a unix bind_address is required".into());
+                };
+                Some(GrpcConfig { listen_address })
+            }
+            None => None,
+        };
+
+        let admin = match config_file.admin {
+            Some(admin) => {
+                let Some(listen_address) = ListenAddress::from_settings_with_default_ip(
+                    admin.bind_address.as_deref(),
+                    admin.http_port,
+                    Ipv4Addr::LOCALHOST.into(),
+                )?
+                else {
+                    return Err("admin: http_port or a unix bind_address is required".into());
+                };
+                Some(AdminConfig {
+                    listen_address,
+                    default_max_wal_bytes_per_sec: admin
+                        .throttle_max_wal_bytes_per_sec
+                        .unwrap_or(DEFAULT_THROTTLE_MAX_WAL_BYTES_PER_SEC),
+                })
+            }
+            None => None,
+        };
+
         let config = Config {
             storage: StorageConfig {
                 working_dir: config_file.storage.working_dir.unwrap_or("ordhook".into()),
@@ -68,6 +278,15 @@ impl ConfigFile {
                     .storage
                     .observers_working_dir
                     .unwrap_or("observers".into()),
+                raw_transactions_index: config_file.storage.raw_transactions_index.unwrap_or(false),
+                text_search_index: config_file.storage.text_search_index.unwrap_or(false),
+                max_stored_content_bytes: config_file.storage.max_stored_content_bytes,
+                blocks_per_commit: config_file
+                    .storage
+                    .blocks_per_commit
+                    .unwrap_or(DEFAULT_BLOCKS_PER_COMMIT)
+                    .max(1),
+                observers_state,
             },
             ordinals_db: ordhook::config::PgConnectionConfig {
                 dbname: config_file.ordinals_db.database,
@@ -109,6 +328,7 @@ impl ConfigFile {
                     .resources
                     .bitcoind_rpc_timeout
                     .unwrap_or(DEFAULT_BITCOIND_RPC_TIMEOUT),
+                bitcoind_rpc_max_rps: config_file.resources.bitcoind_rpc_max_rps,
                 expected_observers_count: config_file
                     .resources
                     .expected_observers_count
@@ -117,14 +337,37 @@ impl ConfigFile {
                     .resources
                     .brc20_lru_cache_size
                     .unwrap_or(DEFAULT_BRC20_LRU_CACHE_SIZE),
+                block_compression_cores: config_file.resources.block_compression_cores,
+                traversal_worker_cores: config_file.resources.traversal_worker_cores,
+                // `provisional_indexing` is the former name of the setting.
+                tip_priority_lane: config_file.resources.tip_priority_lane.unwrap_or(false)
+                    || config_file.resources.provisional_indexing.unwrap_or(false),
             },
             network: IndexerConfig {
-                bitcoind_rpc_url: config_file.network.bitcoind_rpc_url.to_string(),
+                bitcoind_rpc_endpoints: BitcoindRpcEndpoints::new(
+                    &config_file.network.bitcoind_rpc_url,
+                    config_file
+                        .network
+                        .bitcoind_rpc_fallback_urls
+                        .as_deref()
+                        .unwrap_or_default(),
+                )
+                .with_max_rps(config_file.resources.bitcoind_rpc_max_rps),
                 bitcoind_rpc_username: config_file.network.bitcoind_rpc_username.to_string(),
                 bitcoind_rpc_password: config_file.network.bitcoind_rpc_password.to_string(),
-                bitcoin_block_signaling: match config_file.network.bitcoind_zmq_url {
-                    Some(ref zmq_url) => BitcoinBlockSignaling::ZeroMQ(zmq_url.clone()),
-                    None => BitcoinBlockSignaling::ZeroMQ("".to_string()),
+                bitcoind_rpc_cookie_path: config_file.network.bitcoind_rpc_cookie_path.clone(),
+                bitcoin_block_signaling: match (
+                    &config_file.network.bitcoind_zmq_url,
+                    &config_file.network.bitcoind_p2p_peer,
+                ) {
+                    (Some(zmq_url), _) => BitcoinBlockSignaling::ZeroMQ(zmq_url.clone()),
+                    (None, Some(peer)) => BitcoinBlockSignaling::P2P(peer.clone()),
+                    (None, None) => BitcoinBlockSignaling::Poll(
+                        config_file
+                            .network
+                            .bitcoind_poll_interval_ms
+                            .unwrap_or(DEFAULT_BITCOIND_POLL_INTERVAL_MS),
+                    ),
                 },
                 bitcoin_network,
                 prometheus_monitoring_port: config_file.network.prometheus_monitoring_port,
@@ -147,7 +390,97 @@ impl ConfigFile {
                     .as_ref()
                     .and_then(|l| l.brc20)
                     .unwrap_or(false),
+                brc20_five_byte_tickers: config_file
+                    .meta_protocols
+                    .as_ref()
+                    .and_then(|l| l.brc20_five_byte_tickers)
+                    .unwrap_or(true),
+                brc20_self_mint_activation_height: config_file
+                    .meta_protocols
+                    .as_ref()
+                    .and_then(|l| l.brc20_self_mint_activation_height),
+                brc20_strictness,
+                brc20_modules: Brc20ModulesConfig {
+                    url: config_file
+                        .meta_protocols
+                        .as_ref()
+                        .and_then(|l| l.brc20_module_url.clone()),
+                    custom: vec![],
+                },
+                brc20_mempool_zmq_url: config_file
+                    .meta_protocols
+                    .as_ref()
+                    .and_then(|l| l.brc20_mempool_zmq_url.clone()),
+            },
+            address_watch,
+            api,
+            grpc,
+            admin,
+            shadow: config_file.shadow.map(|shadow| ShadowConfig {
+                primary_ordinals_schema: shadow.primary_ordinals_schema,
+                primary_brc20_schema: shadow.primary_brc20_schema,
+            }),
+            nats: config_file.nats.map(|nats| NatsConfig {
+                url: nats.url,
+                subject_prefix: nats.subject_prefix.unwrap_or("ordhook".to_string()),
+                stream_name: nats.stream_name.unwrap_or("ORDHOOK".to_string()),
+            }),
+            redis: config_file.redis.map(|redis| RedisConfig {
+                url: redis.url,
+                channel_prefix: redis.channel_prefix.unwrap_or("ordhook".to_string()),
+            }),
+            sinks: SinksConfig {
+                stdout_jsonl: config_file
+                    .sinks
+                    .as_ref()
+                    .and_then(|sinks| sinks.stdout_jsonl)
+                    .unwrap_or(false),
+                charms_bitfield: config_file
+                    .sinks
+                    .as_ref()
+                    .and_then(|sinks| sinks.charms_bitfield)
+                    .unwrap_or(false),
+                legacy_timestamps: config_file
+                    .sinks
+                    .as_ref()
+                    .and_then(|sinks| sinks.legacy_timestamps)
+                    .unwrap_or(false),
+                custom: vec![],
             },
+            address_clustering: AddressClusteringConfig {
+                url: config_file
+                    .address_clustering
+                    .and_then(|address_clustering| address_clustering.url),
+                custom: vec![],
+            },
+            content_policy: match config_file.content_policy {
+                Some(content_policy) => ContentPolicyConfig {
+                    max_content_bytes: content_policy.max_content_bytes,
+                    denied_mime_types: content_policy
+                        .denied_mime_types
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    blocked_content_hashes: content_policy
+                        .blocked_content_hashes
+                        .unwrap_or_default()
+                        .into_iter()
+                        .collect(),
+                    custom: vec![],
+                    scanner_url: content_policy.scanner_url,
+                    scanner_timeout: content_policy.scanner_timeout,
+                    scan_from_block_height: content_policy.scan_from_block_height,
+                    scanners: vec![],
+                },
+                None => ContentPolicyConfig::default(),
+            },
+            webhook,
+            dry_run: false,
+            prometheus_listen_address,
+            cpu_profiling: config_file
+                .network
+                .prometheus_monitoring_cpu_profiling
+                .unwrap_or(false),
         };
         Ok(config)
     }
@@ -176,6 +509,50 @@ impl ConfigFile {
     }
 }
 
+/// Rejects a config section for an integration this binary was built without.
+fn require_feature(section: &str, configured: bool, built: bool) -> Result<(), String> {
+    if configured && !built {
+        return Err(format!(
+            "{section}: ordhook was built without the {section} feature"
+        ));
+    }
+    Ok(())
+}
+
+fn webhook_authorization_source(
+    section: &str,
+    env: Option<String>,
+    file: Option<String>,
+) -> Result<Option<WebhookAuthorizationSource>, String> {
+    match (env, file) {
+        (Some(_), Some(_)) => Err(format!(
+            "{section}: set either authorization_header_env or authorization_header_file"
+        )),
+        (Some(var), None) => Ok(Some(WebhookAuthorizationSource::Env(var))),
+        (None, Some(path)) => Ok(Some(WebhookAuthorizationSource::File(path))),
+        (None, None) => Ok(None),
+    }
+}
+
+fn webhook_client_tls(
+    section: &str,
+    client_certificate: Option<String>,
+    client_key: Option<String>,
+    ca_certificate: Option<String>,
+) -> Result<Option<WebhookClientTlsConfig>, String> {
+    match (client_certificate, client_key) {
+        (Some(client_certificate_path), Some(client_key_path)) => {
+            Ok(Some(WebhookClientTlsConfig {
+                client_certificate_path,
+                client_key_path,
+                ca_certificate_path: ca_certificate,
+            }))
+        }
+        (None, None) => Ok(None),
+        _ => Err(format!("{section}: incomplete client certificate")),
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct LogConfigFile {
     pub ordinals_internals: Option<bool>,
@@ -197,6 +574,77 @@ pub struct PostgresConfigFile {
 pub struct StorageConfigFile {
     pub working_dir: Option<String>,
     pub observers_working_dir: Option<String>,
+    pub raw_transactions_index: Option<bool>,
+    pub text_search_index: Option<bool>,
+    pub max_stored_content_bytes: Option<u64>,
+    pub blocks_per_commit: Option<usize>,
+    pub observers_state: Option<String>,
+    pub observers_s3_bucket: Option<String>,
+    pub observers_s3_prefix: Option<String>,
+    pub observers_s3_region: Option<String>,
+    pub observers_s3_endpoint: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiConfigFile {
+    pub http_port: Option<u16>,
+    pub bind_address: Option<String>,
+    pub response_cache_size: Option<usize>,
+    pub mempool_reveals: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GrpcConfigFile {
+    pub grpc_port: Option<u16>,
+    pub bind_address: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AdminConfigFile {
+    pub http_port: Option<u16>,
+    pub bind_address: Option<String>,
+    pub throttle_max_wal_bytes_per_sec: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ShadowConfigFile {
+    pub primary_ordinals_schema: String,
+    pub primary_brc20_schema: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NatsConfigFile {
+    pub url: String,
+    pub subject_prefix: Option<String>,
+    pub stream_name: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RedisConfigFile {
+    pub url: String,
+    pub channel_prefix: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SinksConfigFile {
+    pub stdout_jsonl: Option<bool>,
+    pub charms_bitfield: Option<bool>,
+    pub legacy_timestamps: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AddressClusteringConfigFile {
+    pub url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ContentPolicyConfigFile {
+    pub max_content_bytes: Option<u64>,
+    pub denied_mime_types: Option<Vec<String>>,
+    pub blocked_content_hashes: Option<Vec<String>>,
+    pub scanner_url: Option<String>,
+    pub scanner_timeout: Option<u64>,
+    pub scan_from_block_height: Option<u64>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -216,6 +664,37 @@ pub struct SnapshotConfigFile {
 #[derive(Deserialize, Debug, Clone)]
 pub struct MetaProtocolsConfigFile {
     pub brc20: Option<bool>,
+    pub brc20_five_byte_tickers: Option<bool>,
+    pub brc20_self_mint_activation_height: Option<u64>,
+    pub brc20_strictness: Option<String>,
+    pub brc20_module_url: Option<String>,
+    pub brc20_mempool_zmq_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AddressWatchConfigFile {
+    pub url: String,
+    pub addresses: Option<Vec<String>>,
+    pub addresses_file: Option<String>,
+    pub parents: Option<Vec<String>>,
+    pub delegates: Option<Vec<String>>,
+    pub authorization_header_env: Option<String>,
+    pub authorization_header_file: Option<String>,
+    pub client_certificate: Option<String>,
+    pub client_key: Option<String>,
+    pub ca_certificate: Option<String>,
+    pub digest_interval_secs: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookConfigFile {
+    pub url: String,
+    pub max_attempts: Option<u32>,
+    pub authorization_header_env: Option<String>,
+    pub authorization_header_file: Option<String>,
+    pub client_certificate: Option<String>,
+    pub client_key: Option<String>,
+    pub ca_certificate: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -225,16 +704,30 @@ pub struct ResourcesConfigFile {
     pub memory_available: Option<usize>,
     pub bitcoind_rpc_threads: Option<usize>,
     pub bitcoind_rpc_timeout: Option<u32>,
+    pub bitcoind_rpc_max_rps: Option<u32>,
     pub expected_observers_count: Option<usize>,
     pub brc20_lru_cache_size: Option<usize>,
+    pub block_compression_cores: Option<Vec<usize>>,
+    pub traversal_worker_cores: Option<Vec<usize>>,
+    pub tip_priority_lane: Option<bool>,
+    pub provisional_indexing: Option<bool>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct NetworkConfigFile {
     pub mode: String,
     pub bitcoind_rpc_url: String,
+    pub bitcoind_rpc_fallback_urls: Option<Vec<String>>,
+    #[serde(default)]
     pub bitcoind_rpc_username: String,
+    #[serde(default)]
     pub bitcoind_rpc_password: String,
+    /// bitcoind `.cookie` file, used instead of the username and password when set.
+    pub bitcoind_rpc_cookie_path: Option<String>,
     pub bitcoind_zmq_url: Option<String>,
+    pub bitcoind_poll_interval_ms: Option<u64>,
+    pub bitcoind_p2p_peer: Option<String>,
     pub prometheus_monitoring_port: Option<u16>,
+    pub prometheus_monitoring_bind_address: Option<String>,
+    pub prometheus_monitoring_cpu_profiling: Option<bool>,
 }
@@ -5,6 +5,29 @@ pub fn generate_config(network: &BitcoinNetwork) -> String {
     let conf = format!(
         r#"[storage]
 working_dir = "ordhook"
+# Index the block of every inscription reveal transaction so the
+# API can serve them by txid, even if bitcoind runs without txindex:
+# raw_transactions_index = true
+# Index the content of text and JSON inscriptions so they can
+# be searched through the API:
+# text_search_index = true
+# Only keep the hash, length and content type of inscriptions
+# whose content is larger than this many bytes:
+# max_stored_content_bytes = 1000000
+# Blocks written per Postgres transaction while catching up.
+# Larger batches write less WAL but a crash re-indexes the
+# whole batch:
+# blocks_per_commit = 1
+# Where the observer keeps its state across restarts: "local"
+# (observers_working_dir), "postgres" (ordinals database) or
+# "s3". Use a remote backend when the local volume is not
+# persistent. S3 requires ordhook to be built with the s3
+# feature, credentials are read from the AWS env vars:
+# observers_state = "s3"
+# observers_s3_bucket = "my-bucket"
+# observers_s3_prefix = "ordhook/mainnet"
+# observers_s3_region = "us-east-1"
+# observers_s3_endpoint = "https://s3.example.com"
 
 # The Http Api allows you to register / deregister
 # dynamically predicates.
@@ -13,11 +36,176 @@ working_dir = "ordhook"
 # [http_api]
 # http_port = 20456
 
+# Read-only HTTP API serving indexed data:
+#   GET /inscriptions/<inscription_id>
+#   GET /inscriptions/<inscription_id>/content
+#   GET /inscriptions?block=<block_height> (paginate with limit= and
+#   offset=)
+#   GET /search?q=<words> (requires text_search_index)
+#   GET /addresses/<address>/inscriptions (paginate with limit= and
+#   offset=)
+#   GET /clusters/<cluster_id>/addresses and
+#   GET /clusters/<cluster_id>/inscriptions (paginate with limit= and
+#   offset=)
+#   GET /sequences/<sequence> (block holding the operation with that
+#   sequence number)
+#   GET /brc20/tokens/<ticker> (requires brc20)
+#   GET /brc20/balances/<address> (requires brc20)
+#   GET /brc20/activity/<address> (requires brc20, filter with operation=,
+#   paginate with limit= and offset=)
+#   GET /tx/<txid>/raw (requires raw_transactions_index, only
+#   inscription reveal transactions are indexed)
+#   GET /stream/ordinals (WebSocket, requires the websocket feature, filter
+#   with address=, inscription_id=, sat_from= and sat_to=)
+#   GET /stream/events (Server-Sent Events of inscription reveals,
+#   transfers and BRC-20 operations)
+#   POST /psbt/check (body {{"psbt": "<base64>"}} or {{"inputs": ["<txid>:<vout>"]}},
+#   reports the inscriptions and rare sats each input would spend)
+# Add at_block_hash=<block_hash> to any query except the stream to get a
+# 409 Conflict when that block is not part of the indexed chain anymore.
+# Add at_height=<block_height> to /addresses/<address>/inscriptions and
+# /brc20/balances/<address> to get their state as of that block.
+# Disabled by default.
+#
+# [api]
+# http_port = 3099
+# Listen on a specific IP address instead of 0.0.0.0, or on a
+# unix socket with "unix:/path/to/ordhook-api.sock" and no
+# http_port:
+# bind_address = "127.0.0.1"
+# Successful responses are cached in memory until the next block is
+# indexed or rolled back. Set to 0 to disable.
+# response_cache_size = 10000
+# Poll the bitcoind mempool for inscription reveals and serve them at
+# GET /mempool/inscriptions, numbered in the order they entered the
+# mempool. Projected numbers are estimates flagged as provisional.
+# mempool_reveals = true
+
+# gRPC server exposing the StreamBlocks, GetInscription and
+# GetTransfersForSat calls described in ordhook.proto. Requires
+# ordhook to be built with the grpc feature.
+# Disabled by default.
+#
+# [grpc]
+# grpc_port = 50051
+# Listen on a specific IP address instead of 0.0.0.0, or on a
+# unix socket with "unix:/path/to/ordhook-grpc.sock" and no
+# grpc_port:
+# bind_address = "127.0.0.1"
+
+# Admin API, not authenticated: it listens on 127.0.0.1 when only
+# http_port is set, keep it on a loopback address or a unix socket.
+# Backup jobs can bound the WAL written by indexing
+# while they run:
+#   PUT /throttle (body {{"max_wal_bytes_per_sec": 8388608,
+#   "duration_secs": 7200}}, both optional)
+#   DELETE /throttle resumes full speed
+#   GET /throttle
+# Disabled by default.
+#
+# [admin]
+# bind_address = "unix:/var/run/ordhook-admin.sock"
+# throttle_max_wal_bytes_per_sec = 16777216
+
+# Shadow a primary deployment that writes to other schemas of
+# the same databases, and compare every block both have indexed.
+# Disabled by default.
+#
+# [shadow]
+# primary_ordinals_schema = "public"
+# primary_brc20_schema = "public"
+
+# Publish the inscription reveals and transfers of every indexed
+# block to a NATS JetStream stream, on subject
+# <subject_prefix>.blocks.<block_height>. Rollbacks are published
+# on <subject_prefix>.rollbacks.<block_height>. Consumers can
+# replay from a given block by looking up the last message on its
+# subject and starting from that stream sequence. Requires ordhook
+# to be built with the nats feature.
+# Disabled by default.
+#
+# [nats]
+# url = "nats://localhost:4222"
+# subject_prefix = "ordhook"
+# stream_name = "ORDHOOK"
+
+# Publishes indexed activity on Redis pub/sub channels
+# <channel_prefix>:reveals, <channel_prefix>:transfers,
+# <channel_prefix>:brc20 and <channel_prefix>:rollbacks, one JSON
+# message per operation. Messages are not persisted, subscribers that
+# are disconnected miss them. Requires ordhook to be built with the
+# redis feature.
+# Disabled by default.
+#
+# [redis]
+# url = "redis://localhost:6379"
+# channel_prefix = "ordhook"
+
+# Writes every applied or rolled back block to stdout as one JSON line,
+# in the same format as the webhook payloads.
+# Disabled by default.
+#
+# [sinks]
+# stdout_jsonl = true
+#
+# Inscription charms are written as a list of names (e.g. ["cursed", "vindicated"])
+# in webhook, NATS, Redis, stdout and activity stream payloads. Set to true to
+# keep the ord bitfield integer instead.
+# charms_bitfield = true
+#
+# Block timestamps are written as `timestamp` in seconds and `timestamp_ms` in
+# milliseconds since the Unix epoch (UTC). Set to true to leave `timestamp_ms`
+# out, for consumers that reject unknown fields.
+# legacy_timestamps = true
+
+# Posts the addresses involved in every indexed block as
+# {{"block_height": ..., "addresses": [...]}} and expects a JSON
+# object mapping addresses to cluster ids in return. Clusters are
+# served at GET /clusters/<cluster_id>/addresses and
+# GET /clusters/<cluster_id>/inscriptions. A failing clusterer is
+# logged and never stops indexing.
+# Disabled by default.
+#
+# [address_clustering]
+# url = "http://localhost:3000/clusters"
+
+# Content policies mark matching inscriptions as filtered. Their
+# consensus data is still indexed, but GET /inscriptions/<id>/content
+# responds with a 451 and search leaves them out. MIME types can
+# be denied one by one or by top-level type, and content hashes
+# are hex encoded SHA-256.
+# Disabled by default.
+#
+# [content_policy]
+# max_content_bytes = 400000
+# denied_mime_types = ["video/*", "image/svg+xml"]
+# blocked_content_hashes = []
+# Inscriptions can also be posted to a scanning service (e.g. a
+# malware or NSFW classifier) in the background. It receives the
+# raw content with the X-Inscription-Id header and answers with
+# {"flagged": true, "reason": "..."}. Flagged inscriptions are
+# filtered. Inscriptions the service fails on are retried with a
+# backoff, from one minute up to a day.
+# scanner_url = "http://localhost:3000/scan"
+# scanner_timeout = 30
+# scan_from_block_height = 840000
+
 [network]
 mode = "{network}"
+# IPv6 literals are supported, e.g. "http://[::1]:8332". Outbound
+# HTTP honors the HTTP_PROXY, HTTPS_PROXY and NO_PROXY env vars.
 bitcoind_rpc_url = "http://0.0.0.0:8332"
 bitcoind_rpc_username = "devnet"
 bitcoind_rpc_password = "devnet"
+# Nodes that only expose cookie authentication can be reached
+# with their .cookie file instead of a username and password.
+# It is read again on every call, so bitcoind can restart:
+# bitcoind_rpc_cookie_path = "/home/bitcoin/.bitcoin/.cookie"
+# Other bitcoind nodes to fail over to, in order, when the node
+# above errors or lags behind. They must accept the same RPC
+# credentials. The active node is reported by the
+# bitcoind_rpc_endpoint_active metric:
+# bitcoind_rpc_fallback_urls = ["http://10.0.0.2:8332"]
 # Bitcoin block events can be received by Chainhook
 # either through a Bitcoin node's ZeroMQ interface,
 # or through the Stacks node. Zmq is being
@@ -25,6 +213,21 @@ bitcoind_rpc_password = "devnet"
 bitcoind_zmq_url = "tcp://0.0.0.0:18543"
 # but stacks can also be used:
 # stacks_node_rpc_url = "http://0.0.0.0:20443"
+# Without bitcoind_zmq_url, e.g. on managed nodes that don't
+# expose ZeroMQ, new blocks are detected by polling bitcoind's
+# getbestblockhash RPC (every 5000ms by default):
+# bitcoind_poll_interval_ms = 5000
+# New blocks can also be learned from the announcements of a
+# Bitcoin P2P peer, e.g. a remote node that doesn't expose ZeroMQ.
+# Blocks are still downloaded through bitcoind_rpc_url:
+# bitcoind_p2p_peer = "0.0.0.0:8333"
+# Prometheus metrics, served on 0.0.0.0 unless a bind address
+# (an IP address or "unix:/path/to/socket") is given:
+# prometheus_monitoring_port = 9153
+# prometheus_monitoring_bind_address = "127.0.0.1"
+# Serve CPU flamegraphs at /debug/pprof/flamegraph on the same
+# server. Requires ordhook to be built with the profiling feature:
+# prometheus_monitoring_cpu_profiling = true
 
 [resources]
 ulimit = 2048
@@ -33,6 +236,20 @@ memory_available = 32
 bitcoind_rpc_threads = 4
 bitcoind_rpc_timeout = 15
 expected_observers_count = 1
+# Cap the requests sent to a shared bitcoind, across block
+# downloads, polling and mempool watching. Requests also
+# pause and slow down whenever bitcoind answers
+# "Work queue depth exceeded", with or without a cap:
+# bitcoind_rpc_max_rps = 20
+# Optionally pin block processing threads to disjoint sets
+# of CPU cores so catch-up doesn't starve other services:
+# block_compression_cores = [0, 1, 2, 3]
+# traversal_worker_cores = [4, 5, 6, 7]
+# Index newly mined blocks provisionally while catching up.
+# Their inscriptions are stored until the backfill reaches
+# them. The API serves them, flagged with "provisional": true,
+# on the inscriptions endpoints with ?include_provisional=true:
+# tip_priority_lane = true
 
 # Disable the following section if the state
 # must be built locally
@@ -43,6 +260,71 @@ brc20_url = "https://archive.hiro.so/mainnet/ordhook/mainnet-ordhook-brc20-lates
 [logs]
 ordinals_internals = true
 chainhook_internals = true
+
+# BRC-20 indexing, also enabled with --meta-protocols=brc20.
+# When enabling it on an existing ordinals index, build the
+# BRC-20 history first with `ordhook ordinals brc20 backfill`.
+# 5-byte self-mint tickers are accepted from the network's
+# activation height (837090 on mainnet) unless disabled, and the
+# height can be overridden to match another indexer.
+# brc20_strictness decides which cursed inscriptions count as
+# BRC-20 operations: "strict" (none), "vindicated" (those
+# vindicated by the jubilee) or "permissive" (all of them).
+# brc20_module_url receives the validated BRC-20 operations of
+# every block as JSON before they are committed, and rollbacks as
+# they happen. A failed delivery stops indexing at that block.
+# brc20_mempool_zmq_url points to bitcoind's zmqpubrawtx endpoint
+# to pre-validate BRC-20 operations of mempool transactions. They
+# are served unconfirmed at GET /mempool/brc20 and sent to sinks.
+#
+# [meta_protocols]
+# brc20 = true
+# brc20_five_byte_tickers = true
+# brc20_self_mint_activation_height = 837090
+# brc20_strictness = "strict"
+# brc20_module_url = "http://localhost:3000/brc20-module"
+# brc20_mempool_zmq_url = "tcp://0.0.0.0:18544"
+
+# Report inscription and BRC-20 activity involving a set of
+# addresses to a webhook as new blocks are streamed. Activity is
+# queued in the ordinals database and delivered in the background,
+# in order, so a slow endpoint never holds up indexing. Failed
+# deliveries are retried, and activity from blocks that were
+# reorged out is dropped.
+# Disabled by default.
+#
+# [address_watch]
+# url = "http://localhost:3000/api/address-activity"
+# addresses = ["bc1p..."]
+# addresses_file = "watched_addresses.txt"
+# Also report new children of these parent inscriptions, and new
+# inscriptions that delegate to these inscriptions:
+# parents = ["<inscription_id>"]
+# delegates = ["<inscription_id>"]
+# Authorization header value, read from an env var or a file before
+# every delivery. A file can be rotated without a restart, an env
+# var can't:
+# authorization_header_env = "ADDRESS_WATCH_AUTHORIZATION"
+# authorization_header_file = "/run/secrets/address_watch_authorization"
+# Client certificate (PEM) for endpoints that require mutual TLS:
+# client_certificate = "/run/secrets/address_watch.crt"
+# client_key = "/run/secrets/address_watch.key"
+# ca_certificate = "/run/secrets/internal_ca.crt"
+# Post one digest summarizing the activity of every watched address
+# at this interval instead of one delivery per block:
+# digest_interval_secs = 86400
+
+# Post an event with the inscription reveals and transfers of
+# every indexed or rolled back block. Events are queued in the
+# ordinals database and retried with an exponential backoff, in
+# order. Events that still fail after max_attempts are moved to
+# the webhook_dead_letters table.
+# Disabled by default.
+#
+# [webhook]
+# url = "http://localhost:3000/api/ordinal-events"
+# max_attempts = 10
+# authorization_header_env = "WEBHOOK_AUTHORIZATION"
 "#,
         network = network.to_lowercase(),
     );
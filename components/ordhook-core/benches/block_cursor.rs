@@ -0,0 +1,23 @@
+use chainhook_sdk::indexer::bitcoin::parse_downloaded_block;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ordhook::db::cursor::BlockBytesCursor;
+
+fn block_cursor_benchmark(c: &mut Criterion) {
+    let block = include_str!("../src/db/fixtures/blocks_json/279671.json");
+    let decoded_block =
+        parse_downloaded_block(block.as_bytes().to_vec()).expect("unable to decode block");
+    let bytes = BlockBytesCursor::from_full_block(&decoded_block).expect("unable to serialize");
+
+    c.bench_function("BlockBytesCursor::from_full_block", |b| {
+        b.iter(|| BlockBytesCursor::from_full_block(black_box(&decoded_block)).unwrap())
+    });
+    c.bench_function("BlockBytesCursor iterate transactions", |b| {
+        b.iter(|| {
+            let cursor = BlockBytesCursor::new(black_box(&bytes));
+            cursor.iter_tx().count()
+        })
+    });
+}
+
+criterion_group!(benches, block_cursor_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,94 @@
+use chainhook_postgres::{pg_begin, pg_connect, pg_pool, pg_pool_client, PgConnectionConfig};
+use chainhook_sdk::utils::Context;
+use chainhook_types::{BitcoinNetwork, BlockIdentifier};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ord::inscription::Inscription;
+use ordhook::config::Brc20Strictness;
+use ordhook::core::meta_protocols::brc20::{
+    brc20_pg, brc20_self_mint_activation_height,
+    cache::Brc20MemoryCache,
+    parser::{parse_brc20_operation, ParsedBrc20Operation, ParsedBrc20TokenDeployData},
+    test_utils::Brc20RevealBuilder,
+    verifier::verify_brc20_operation,
+};
+
+/// Same database the unit tests use, verification benchmarks are skipped when it is not reachable.
+fn pg_bench_config() -> PgConnectionConfig {
+    PgConnectionConfig {
+        dbname: "postgres".to_string(),
+        host: "localhost".to_string(),
+        port: 5432,
+        user: "postgres".to_string(),
+        password: Some("postgres".to_string()),
+        search_path: None,
+        pool_max_size: None,
+    }
+}
+
+fn brc20_parsing_benchmark(c: &mut Criterion) {
+    let inscription = Inscription {
+        content_type: Some("text/plain;charset=utf-8".as_bytes().to_vec()),
+        body: Some(
+            r#"{"p":"brc-20","op":"mint","tick":"ordi","amt":"1000"}"#
+                .as_bytes()
+                .to_vec(),
+        ),
+        ..Default::default()
+    };
+    c.bench_function("parse_brc20_operation", |b| {
+        b.iter(|| parse_brc20_operation(black_box(&inscription)).unwrap())
+    });
+}
+
+fn brc20_verification_benchmark(c: &mut Criterion) {
+    let ctx = Context::empty();
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let config = pg_bench_config();
+    let Ok(mut pg_client) = rt.block_on(pg_connect(&config)) else {
+        println!("postgres is not reachable, skipping BRC-20 verification benchmarks");
+        return;
+    };
+    rt.block_on(brc20_pg::migrate(&mut pg_client)).unwrap();
+    let pool = pg_pool(&config).unwrap();
+    let mut brc20_client = rt.block_on(pg_pool_client(&pool)).unwrap();
+    // Nothing is written, the transaction is rolled back when dropped.
+    let db_tx = rt.block_on(pg_begin(&mut brc20_client)).unwrap();
+
+    let operation = ParsedBrc20Operation::Deploy(ParsedBrc20TokenDeployData {
+        tick: "pepe".to_string(),
+        display_tick: "pepe".to_string(),
+        max: "21000000".to_string(),
+        lim: "1000".to_string(),
+        dec: "18".to_string(),
+        self_mint: false,
+    });
+    let reveal = Brc20RevealBuilder::new().build();
+    let block_identifier = BlockIdentifier {
+        index: 830000,
+        hash: "00000000000000000002d8ba402150b259ddb2b30a1d32ab4a881d4653bceb5b".to_string(),
+    };
+    let mut cache = Brc20MemoryCache::new(50);
+
+    c.bench_function("verify_brc20_operation deploy", |b| {
+        b.iter(|| {
+            rt.block_on(verify_brc20_operation(
+                black_box(&operation),
+                &reveal,
+                &block_identifier,
+                Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                Brc20Strictness::Strict,
+                &mut cache,
+                &db_tx,
+                &ctx,
+            ))
+            .unwrap()
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    brc20_parsing_benchmark,
+    brc20_verification_benchmark
+);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ordhook::core::protocol::inscription_parsing::parse_inscriptions_from_witness;
+
+const TXID: &str = "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735";
+
+fn brc20_deploy_witness() -> Vec<Vec<u8>> {
+    vec![
+        hex::decode("6c00eb3c4d35fedd257051333b4ca81d1a25a37a9af4891f1fec2869edd56b14180eafbda8851d63138a724c9b15384bc5f0536de658bd294d426a36212e6f08").unwrap(),
+        hex::decode("209e2849b90a2353691fccedd467215c88eec89a5d0dcf468e6cf37abed344d746ac0063036f7264010118746578742f706c61696e3b636861727365743d7574662d38004c5e7b200a20202270223a20226272632d3230222c0a2020226f70223a20226465706c6f79222c0a2020227469636b223a20226f726469222c0a2020226d6178223a20223231303030303030222c0a2020226c696d223a202231303030220a7d68").unwrap(),
+        hex::decode("c19e2849b90a2353691fccedd467215c88eec89a5d0dcf468e6cf37abed344d746").unwrap(),
+    ]
+}
+
+fn envelope_parsing_benchmark(c: &mut Criterion) {
+    let witness = brc20_deploy_witness();
+    c.bench_function("parse inscription envelope", |b| {
+        b.iter(|| parse_inscriptions_from_witness(0, black_box(witness.clone()), TXID).unwrap())
+    });
+}
+
+criterion_group!(benches, envelope_parsing_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+use bitcoin::Network;
+use chainhook_sdk::utils::Context;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ordhook::core::{
+    compute_next_satpoint_data, protocol::satoshi_tracking::get_script_pubkey_destination,
+};
+
+fn satpoint_benchmark(c: &mut Criterion) {
+    let ctx = Context::empty();
+    // A batched transfer with many inputs and outputs, where the tracked sat sits in the last input.
+    let inputs: Vec<u64> = (0..200).map(|i| 10_000 + i).collect();
+    let outputs: Vec<u64> = (0..200).map(|i| 9_000 + i).collect();
+
+    c.bench_function("compute_next_satpoint_data", |b| {
+        b.iter(|| {
+            compute_next_satpoint_data(
+                black_box(199),
+                black_box(&inputs),
+                black_box(&outputs),
+                black_box(5_000),
+                None,
+            )
+        })
+    });
+    c.bench_function("get_script_pubkey_destination", |b| {
+        b.iter(|| {
+            get_script_pubkey_destination(
+                black_box("5120694b38ea24908e86a857279105c376a82cd1556f51655abb2ebef398b57daa8b"),
+                &Network::Bitcoin,
+                &ctx,
+            )
+        })
+    });
+}
+
+criterion_group!(benches, satpoint_benchmark);
+criterion_main!(benches);
@@ -1,7 +1,22 @@
 pub use chainhook_postgres::PgConnectionConfig;
-use chainhook_sdk::{indexer::IndexerConfig, observer::EventObserverConfig};
-use chainhook_types::{BitcoinBlockSignaling, BitcoinNetwork};
+use chainhook_sdk::indexer::bitcoin::BitcoindRpcEndpoints;
+use chainhook_sdk::indexer::IndexerConfig;
+use chainhook_sdk::observer::chain_event_cursor::FileChainEventCursorStore;
+use chainhook_sdk::observer::EventObserverConfig;
+use chainhook_types::{BitcoinBlockSignaling, BitcoinNetwork, OrdinalInscriptionNumber};
+use std::collections::HashSet;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::core::meta_protocols::brc20::brc20_self_mint_activation_height;
+use crate::core::meta_protocols::brc20::modules::Brc20Module;
+use crate::service::address_clusters::AddressClusterer;
+use crate::service::block_events::{CharmsFormat, PayloadFormat};
+use crate::service::content_policy::ContentPolicy;
+use crate::service::content_scanner::ContentScanner;
+use crate::service::sinks::EventSink;
 
 const DEFAULT_MAINNET_ORDINALS_SQLITE_ARCHIVE: &str =
     "https://archive.hiro.so/mainnet/ordhook/mainnet-ordhook-sqlite-latest";
@@ -14,6 +29,12 @@ pub const DEFAULT_MEMORY_AVAILABLE: usize = 8;
 pub const DEFAULT_BITCOIND_RPC_THREADS: usize = 4;
 pub const DEFAULT_BITCOIND_RPC_TIMEOUT: u32 = 15;
 pub const DEFAULT_BRC20_LRU_CACHE_SIZE: usize = 50_000;
+pub const DEFAULT_WEBHOOK_MAX_ATTEMPTS: u32 = 10;
+pub const DEFAULT_API_RESPONSE_CACHE_SIZE: usize = 10_000;
+pub const DEFAULT_THROTTLE_MAX_WAL_BYTES_PER_SEC: u64 = 16 * 1024 * 1024;
+pub const DEFAULT_BLOCKS_PER_COMMIT: usize = 1;
+pub const DEFAULT_BITCOIND_POLL_INTERVAL_MS: u64 = 5_000;
+pub const DEFAULT_CONTENT_SCANNER_TIMEOUT: u64 = 30;
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -25,11 +46,97 @@ pub struct Config {
     pub snapshot: SnapshotConfig,
     pub meta_protocols: MetaProtocolsConfig,
     pub logs: LogConfig,
+    pub address_watch: Option<AddressWatchConfig>,
+    pub api: Option<ApiConfig>,
+    pub grpc: Option<GrpcConfig>,
+    pub admin: Option<AdminConfig>,
+    pub shadow: Option<ShadowConfig>,
+    pub nats: Option<NatsConfig>,
+    pub redis: Option<RedisConfig>,
+    pub sinks: SinksConfig,
+    pub address_clustering: AddressClusteringConfig,
+    pub content_policy: ContentPolicyConfig,
+    pub webhook: Option<WebhookConfig>,
+    /// Runs every computation but discards Postgres writes and skips webhook deliveries.
+    pub dry_run: bool,
+    pub prometheus_listen_address: Option<ListenAddress>,
+    /// Serves CPU flamegraphs on the Prometheus monitoring server. Requires the `profiling` feature.
+    pub cpu_profiling: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct MetaProtocolsConfig {
     pub brc20: bool,
+    /// Accepts 5-byte tickers deployed with `self_mint`.
+    pub brc20_five_byte_tickers: bool,
+    /// Height 5-byte self-mint tickers activate at, when it differs from the network's.
+    pub brc20_self_mint_activation_height: Option<u64>,
+    /// Which cursed inscriptions count as BRC-20 operations.
+    pub brc20_strictness: Brc20Strictness,
+    /// Modules validated BRC-20 operations are forwarded to once they are committed.
+    pub brc20_modules: Brc20ModulesConfig,
+    /// bitcoind `zmqpubrawtx` endpoint watched for BRC-20 operations in mempool transactions. Disabled when `None`.
+    pub brc20_mempool_zmq_url: Option<String>,
+}
+
+/// External modules that receive the validated BRC-20 operations of every block, see `Brc20Module`.
+#[derive(Clone, Default)]
+pub struct Brc20ModulesConfig {
+    /// Posts the operations of every block to this URL.
+    pub url: Option<String>,
+    /// Modules registered by applications that embed ordhook, called after the HTTP one.
+    pub custom: Vec<Arc<dyn Brc20Module>>,
+}
+
+impl fmt::Debug for Brc20ModulesConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Brc20ModulesConfig")
+            .field("url", &self.url)
+            .field(
+                "custom",
+                &self
+                    .custom
+                    .iter()
+                    .map(|module| module.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// How BRC-20 treats inscriptions that were cursed when revealed. Indexers disagree on this, so it has to match the one
+/// balances are compared against.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Brc20Strictness {
+    /// Only inscriptions with a positive classic number are operations.
+    Strict,
+    /// Cursed inscriptions vindicated by the jubilee are operations too.
+    Vindicated,
+    /// Every inscription is an operation, cursed or not.
+    Permissive,
+}
+
+impl Brc20Strictness {
+    pub fn accepts(&self, inscription_number: &OrdinalInscriptionNumber) -> bool {
+        match self {
+            Brc20Strictness::Strict => inscription_number.classic >= 0,
+            Brc20Strictness::Vindicated => inscription_number.jubilee >= 0,
+            Brc20Strictness::Permissive => true,
+        }
+    }
+}
+
+impl MetaProtocolsConfig {
+    /// Height from which 5-byte self-mint deploys are valid, or `None` if they are disabled.
+    pub fn brc20_self_mint_activation_height(&self, network: &BitcoinNetwork) -> Option<u64> {
+        if !self.brc20_five_byte_tickers {
+            return None;
+        }
+        Some(
+            self.brc20_self_mint_activation_height
+                .unwrap_or(brc20_self_mint_activation_height(network)),
+        )
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -38,10 +145,336 @@ pub struct LogConfig {
     pub chainhook_internals: bool,
 }
 
+/// Read-only HTTP API served by the ordhook service.
+#[derive(Clone, Debug)]
+pub struct ApiConfig {
+    pub listen_address: ListenAddress,
+    /// Number of responses kept in memory, `0` disables the cache.
+    pub response_cache_size: usize,
+    /// Watch the bitcoind mempool for inscription reveals and serve them with projected inscription numbers.
+    pub mempool_reveals: bool,
+}
+
+/// HTTP API operators use to change the behavior of a running service, e.g. to throttle writes during database backups.
+/// It is not authenticated, so it listens on `127.0.0.1` unless another address or a unix socket is configured.
+#[derive(Clone, Debug)]
+pub struct AdminConfig {
+    pub listen_address: ListenAddress,
+    /// WAL generation rate enforced when throttling is requested without one.
+    pub default_max_wal_bytes_per_sec: u64,
+}
+
+/// gRPC server exposing inscriptions, sat transfers and a live stream of indexed blocks. Requires the `grpc` feature.
+#[derive(Clone, Debug)]
+pub struct GrpcConfig {
+    pub listen_address: ListenAddress,
+}
+
+/// Where an HTTP server listens: a TCP socket, or a unix domain socket path.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl ListenAddress {
+    /// Builds a listen address from an optional `bind_address` setting and port. `bind_address` is an IP address, which
+    /// requires a port, or `unix:<path>`, which doesn't take one. It defaults to `0.0.0.0` when only a port is given.
+    pub fn from_settings(
+        bind_address: Option<&str>,
+        port: Option<u16>,
+    ) -> Result<Option<ListenAddress>, String> {
+        ListenAddress::from_settings_with_default_ip(
+            bind_address,
+            port,
+            Ipv4Addr::UNSPECIFIED.into(),
+        )
+    }
+
+    /// Same as `ListenAddress::from_settings`, but listens on `default_ip` when only a port is given.
+    pub fn from_settings_with_default_ip(
+        bind_address: Option<&str>,
+        port: Option<u16>,
+        default_ip: IpAddr,
+    ) -> Result<Option<ListenAddress>, String> {
+        match (bind_address, port) {
+            (Some(bind_address), port) => {
+                if let Some(path) = bind_address.strip_prefix("unix:") {
+                    if port.is_some() {
+                        return Err(format!("bind address {bind_address} does not take a port"));
+                    }
+                    return Ok(Some(ListenAddress::Unix(PathBuf::from(path))));
+                }
+                // IPv6 addresses may be written in brackets, as in URLs.
+                let ip: IpAddr = bind_address
+                    .strip_prefix('[')
+                    .and_then(|ip| ip.strip_suffix(']'))
+                    .unwrap_or(bind_address)
+                    .parse()
+                    .map_err(|e| format!("invalid bind address {bind_address}: {e}"))?;
+                let Some(port) = port else {
+                    return Err(format!("bind address {bind_address} requires a port"));
+                };
+                Ok(Some(ListenAddress::Tcp(SocketAddr::new(ip, port))))
+            }
+            (None, Some(port)) => Ok(Some(ListenAddress::Tcp(SocketAddr::new(default_ip, port)))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl std::fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddress::Tcp(addr) => write!(f, "{addr}"),
+            ListenAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Runs the service as a shadow of a primary deployment that indexes into other schemas of the same databases. Every
+/// block indexed by both is compared and divergences are reported through metrics, so an upgrade can be validated
+/// against production traffic before it is promoted. A shadow never notifies anything outside of its database, see
+/// `Config::without_outbound_side_effects`.
+#[derive(Clone, Debug)]
+pub struct ShadowConfig {
+    pub primary_ordinals_schema: String,
+    pub primary_brc20_schema: Option<String>,
+}
+
+/// Publishes the ordinal activity of every indexed block to a NATS JetStream stream, one message per block on subject
+/// `<subject_prefix>.blocks.<height>` and one per rolled back block on `<subject_prefix>.rollbacks.<height>`. Requires
+/// the `nats` feature.
+#[derive(Clone, Debug)]
+pub struct NatsConfig {
+    pub url: String,
+    pub subject_prefix: String,
+    pub stream_name: String,
+}
+
+/// Publishes the activity of every indexed block on Redis pub/sub channels `<channel_prefix>:reveals`,
+/// `<channel_prefix>:transfers`, `<channel_prefix>:brc20` and `<channel_prefix>:rollbacks`. Delivery is best effort:
+/// messages published while a subscriber is disconnected are lost. Requires the `redis` feature.
+#[derive(Clone, Debug)]
+pub struct RedisConfig {
+    pub url: String,
+    pub channel_prefix: String,
+}
+
+/// Extra destinations for block events, on top of the webhook, NATS and Redis outputs.
+#[derive(Clone, Default)]
+pub struct SinksConfig {
+    /// Writes every block event to stdout as a JSON line.
+    pub stdout_jsonl: bool,
+    /// Writes revealed inscription charms as the ord bitfield integer instead of a list of names.
+    pub charms_bitfield: bool,
+    /// Leaves `timestamp_ms` out of block and activity payloads, which then only carry `timestamp` in seconds.
+    pub legacy_timestamps: bool,
+    /// Sinks registered by applications that embed ordhook, called after the built-in ones.
+    pub custom: Vec<Arc<dyn EventSink>>,
+}
+
+impl fmt::Debug for SinksConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SinksConfig")
+            .field("stdout_jsonl", &self.stdout_jsonl)
+            .field("charms_bitfield", &self.charms_bitfield)
+            .field("legacy_timestamps", &self.legacy_timestamps)
+            .field(
+                "custom",
+                &self
+                    .custom
+                    .iter()
+                    .map(|sink| sink.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl SinksConfig {
+    pub fn payload_format(&self) -> PayloadFormat {
+        PayloadFormat {
+            charms: if self.charms_bitfield {
+                CharmsFormat::Bitfield
+            } else {
+                CharmsFormat::Names
+            },
+            legacy_timestamps: self.legacy_timestamps,
+        }
+    }
+}
+
+/// Services mapping the addresses of every indexed block to cluster or entity ids, see `AddressClusterer`.
+#[derive(Clone, Default)]
+pub struct AddressClusteringConfig {
+    /// Posts the addresses of every block to this URL.
+    pub url: Option<String>,
+    /// Clusterers registered by applications that embed ordhook, called after the HTTP one.
+    pub custom: Vec<Arc<dyn AddressClusterer>>,
+}
+
+impl fmt::Debug for AddressClusteringConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AddressClusteringConfig")
+            .field("url", &self.url)
+            .field(
+                "custom",
+                &self
+                    .custom
+                    .iter()
+                    .map(|clusterer| clusterer.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Rules marking inscriptions whose content must not be served, see `ContentPolicy`.
+#[derive(Clone, Default)]
+pub struct ContentPolicyConfig {
+    /// Filters inscriptions with more content bytes than this.
+    pub max_content_bytes: Option<u64>,
+    /// Filters these MIME types. `type/*` entries match a whole top-level type.
+    pub denied_mime_types: HashSet<String>,
+    /// Filters contents with one of these hex encoded SHA-256 hashes.
+    pub blocked_content_hashes: HashSet<String>,
+    /// Policies registered by applications that embed ordhook, checked after the built-in ones.
+    pub custom: Vec<Arc<dyn ContentPolicy>>,
+    /// Content scanning service every indexed inscription is posted to, see `HttpContentScanner`.
+    pub scanner_url: Option<String>,
+    /// Seconds to wait for the scanning service to answer. Defaults to `DEFAULT_CONTENT_SCANNER_TIMEOUT`.
+    pub scanner_timeout: Option<u64>,
+    /// Only inscriptions revealed at or after this block are scanned. Defaults to the whole index.
+    pub scan_from_block_height: Option<u64>,
+    /// Scanners registered by applications that embed ordhook, run along the one at `scanner_url`.
+    pub scanners: Vec<Arc<dyn ContentScanner>>,
+}
+
+impl fmt::Debug for ContentPolicyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContentPolicyConfig")
+            .field("max_content_bytes", &self.max_content_bytes)
+            .field("denied_mime_types", &self.denied_mime_types)
+            .field("blocked_content_hashes", &self.blocked_content_hashes)
+            .field(
+                "custom",
+                &self
+                    .custom
+                    .iter()
+                    .map(|policy| policy.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field("scanner_url", &self.scanner_url)
+            .field("scanner_timeout", &self.scanner_timeout)
+            .field("scan_from_block_height", &self.scan_from_block_height)
+            .field(
+                "scanners",
+                &self
+                    .scanners
+                    .iter()
+                    .map(|scanner| scanner.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Addresses whose inscription and BRC-20 activity should be reported to a webhook as blocks are streamed.
+#[derive(Clone, Debug)]
+pub struct AddressWatchConfig {
+    pub url: String,
+    pub addresses: HashSet<String>,
+    /// Inscription ids whose new children are reported.
+    pub parents: HashSet<String>,
+    /// Inscription ids whose new delegating copies are reported.
+    pub delegates: HashSet<String>,
+    pub authorization: Option<WebhookAuthorizationSource>,
+    pub tls: Option<WebhookClientTlsConfig>,
+    /// When set, activity is queued and posted as one digest every this many seconds instead of once per block.
+    pub digest_interval_secs: Option<u64>,
+}
+
+/// Posts an event for every block applied to or rolled back from the index. Events are queued in the ordinals database
+/// and retried with an exponential backoff, deliveries that still fail after `max_attempts` are moved to a dead letter
+/// table.
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+    pub url: String,
+    pub authorization: Option<WebhookAuthorizationSource>,
+    pub tls: Option<WebhookClientTlsConfig>,
+    pub max_attempts: u32,
+}
+
+/// Client certificate presented to webhook endpoints that require mutual TLS. Paths point to PEM files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebhookClientTlsConfig {
+    pub client_certificate_path: String,
+    pub client_key_path: String,
+    /// Extra CA trusted when verifying the endpoint, for consumers behind a private PKI.
+    pub ca_certificate_path: Option<String>,
+}
+
+/// Where the `Authorization` header of a webhook delivery is read from. The value is resolved again before every
+/// delivery, so a secret kept in a file can be rotated without restarting the service. The environment of a running
+/// process can't be changed from outside, so rotating a secret kept in an env var still takes a restart.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebhookAuthorizationSource {
+    Env(String),
+    File(String),
+}
+
+impl WebhookAuthorizationSource {
+    pub async fn resolve(&self) -> Result<String, String> {
+        let value = match self {
+            WebhookAuthorizationSource::Env(var) => std::env::var(var)
+                .map_err(|e| format!("unable to read authorization env var {var}: {e}"))?,
+            WebhookAuthorizationSource::File(path) => tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| format!("unable to read authorization file {path}: {e}"))?,
+        };
+        Ok(value.trim().to_string())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct StorageConfig {
     pub working_dir: String,
     pub observers_working_dir: String,
+    /// Index the block of every inscription reveal transaction so the API can serve them by txid, with bitcoind
+    /// returning the bytes without needing `txindex`.
+    pub raw_transactions_index: bool,
+    /// Maintain a full-text index over the content of text and JSON inscriptions for the API's search endpoint.
+    pub text_search_index: bool,
+    /// Inscription contents larger than this are not stored, only their hash, length and content type are kept.
+    pub max_stored_content_bytes: Option<u64>,
+    /// Most blocks written in a single Postgres transaction while catching up. Larger batches generate less WAL per block
+    /// but a crash or reorg re-indexes the whole batch. Blocks received at the chain tip are always committed one by one.
+    pub blocks_per_commit: usize,
+    /// Where the observer keeps the state it needs across restarts.
+    pub observers_state: ObserversStateConfig,
+}
+
+/// Backend for the observer's state. Stateless deployments should use a remote backend, since a state lost with its
+/// local volume makes the observer forget which blocks it already delivered.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObserversStateConfig {
+    /// Files in `observers_working_dir`.
+    Local,
+    /// The `observer_state` table of the ordinals database.
+    Postgres,
+    /// Objects in an S3 compatible bucket. Requires the `s3` feature.
+    S3(S3StateConfig),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct S3StateConfig {
+    pub bucket: String,
+    /// Prepended to the object keys, e.g. `ordhook/mainnet`.
+    pub prefix: String,
+    pub region: String,
+    /// Endpoint of a non-AWS S3 compatible service. Buckets are then addressed path-style.
+    pub endpoint: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -73,8 +506,17 @@ pub struct ResourcesConfig {
     pub memory_available: usize,
     pub bitcoind_rpc_threads: usize,
     pub bitcoind_rpc_timeout: u32,
+    /// Requests per second sent to bitcoind, shared by block downloads, polling and mempool watchers. Unlimited when
+    /// `None`, see `BitcoindRpcRateLimiter`.
+    pub bitcoind_rpc_max_rps: Option<u32>,
     pub expected_observers_count: usize,
     pub brc20_lru_cache_size: usize,
+    /// CPU cores the "Block data compression" threads are pinned to during block downloads. Unpinned when `None`.
+    pub block_compression_cores: Option<Vec<usize>>,
+    /// CPU cores the satoshi traversal workers are pinned to. Unpinned when `None`.
+    pub traversal_worker_cores: Option<Vec<usize>>,
+    /// Index blocks announced over ZMQ provisionally while catching up to the chain tip, see `TipPriorityLane`.
+    pub tip_priority_lane: bool,
 }
 
 impl ResourcesConfig {
@@ -91,9 +533,15 @@ impl Config {
         EventObserverConfig {
             bitcoind_rpc_username: self.network.bitcoind_rpc_username.clone(),
             bitcoind_rpc_password: self.network.bitcoind_rpc_password.clone(),
-            bitcoind_rpc_url: self.network.bitcoind_rpc_url.clone(),
+            bitcoind_rpc_cookie_path: self.network.bitcoind_rpc_cookie_path.clone(),
+            bitcoind_rpc_endpoints: self.network.bitcoind_rpc_endpoints.clone(),
             bitcoin_block_signaling: self.network.bitcoin_block_signaling.clone(),
             bitcoin_network: self.network.bitcoin_network.clone(),
+            chain_event_cursor_store: Some(Arc::new(FileChainEventCursorStore {
+                path: self
+                    .expected_observers_cache_path()
+                    .join("chain_event_cursor.json"),
+            })),
         }
     }
 
@@ -116,11 +564,30 @@ impl Config {
         destination_path
     }
 
+    /// Copy of this config that indexes exactly the same data but notifies nothing outside of the database: no
+    /// webhooks, message brokers, event sinks, BRC-20 modules, address clusterers nor address watch digests.
+    pub fn without_outbound_side_effects(&self) -> Config {
+        let mut config = self.clone();
+        config.nats = None;
+        config.redis = None;
+        config.sinks = SinksConfig::default();
+        config.address_clustering = AddressClusteringConfig::default();
+        config.meta_protocols.brc20_modules = Brc20ModulesConfig::default();
+        config.webhook = None;
+        config.address_watch = None;
+        config
+    }
+
     pub fn devnet_default() -> Config {
         Config {
             storage: StorageConfig {
                 working_dir: default_cache_path(),
                 observers_working_dir: default_observers_cache_path(),
+                raw_transactions_index: false,
+                text_search_index: false,
+                max_stored_content_bytes: None,
+                blocks_per_commit: DEFAULT_BLOCKS_PER_COMMIT,
+                observers_state: ObserversStateConfig::Local,
             },
             ordinals_db: PgConnectionConfig {
                 dbname: "ordinals".to_string(),
@@ -139,13 +606,18 @@ impl Config {
                 ulimit: DEFAULT_ULIMIT,
                 bitcoind_rpc_threads: DEFAULT_BITCOIND_RPC_THREADS,
                 bitcoind_rpc_timeout: DEFAULT_BITCOIND_RPC_TIMEOUT,
+                bitcoind_rpc_max_rps: None,
                 expected_observers_count: 1,
                 brc20_lru_cache_size: DEFAULT_BRC20_LRU_CACHE_SIZE,
+                block_compression_cores: None,
+                traversal_worker_cores: None,
+                tip_priority_lane: false,
             },
             network: IndexerConfig {
-                bitcoind_rpc_url: "http://0.0.0.0:18443".into(),
+                bitcoind_rpc_endpoints: BitcoindRpcEndpoints::new("http://0.0.0.0:18443", &[]),
                 bitcoind_rpc_username: "devnet".into(),
                 bitcoind_rpc_password: "devnet".into(),
+                bitcoind_rpc_cookie_path: None,
                 bitcoin_block_signaling: BitcoinBlockSignaling::ZeroMQ(
                     "http://0.0.0.0:18543".into(),
                 ),
@@ -156,7 +628,28 @@ impl Config {
                 ordinals_internals: true,
                 chainhook_internals: false,
             },
-            meta_protocols: MetaProtocolsConfig { brc20: false },
+            meta_protocols: MetaProtocolsConfig {
+                brc20: false,
+                brc20_five_byte_tickers: true,
+                brc20_self_mint_activation_height: None,
+                brc20_strictness: Brc20Strictness::Strict,
+                brc20_modules: Brc20ModulesConfig::default(),
+                brc20_mempool_zmq_url: None,
+            },
+            address_watch: None,
+            api: None,
+            grpc: None,
+            admin: None,
+            shadow: None,
+            nats: None,
+            redis: None,
+            sinks: SinksConfig::default(),
+            address_clustering: AddressClusteringConfig::default(),
+            content_policy: ContentPolicyConfig::default(),
+            webhook: None,
+            dry_run: false,
+            prometheus_listen_address: None,
+            cpu_profiling: false,
         }
     }
 
@@ -165,6 +658,11 @@ impl Config {
             storage: StorageConfig {
                 working_dir: default_cache_path(),
                 observers_working_dir: default_observers_cache_path(),
+                raw_transactions_index: false,
+                text_search_index: false,
+                max_stored_content_bytes: None,
+                blocks_per_commit: DEFAULT_BLOCKS_PER_COMMIT,
+                observers_state: ObserversStateConfig::Local,
             },
             ordinals_db: PgConnectionConfig {
                 dbname: "ordinals".to_string(),
@@ -183,13 +681,18 @@ impl Config {
                 ulimit: DEFAULT_ULIMIT,
                 bitcoind_rpc_threads: DEFAULT_BITCOIND_RPC_THREADS,
                 bitcoind_rpc_timeout: DEFAULT_BITCOIND_RPC_TIMEOUT,
+                bitcoind_rpc_max_rps: None,
                 expected_observers_count: 1,
                 brc20_lru_cache_size: DEFAULT_BRC20_LRU_CACHE_SIZE,
+                block_compression_cores: None,
+                traversal_worker_cores: None,
+                tip_priority_lane: false,
             },
             network: IndexerConfig {
-                bitcoind_rpc_url: "http://0.0.0.0:18332".into(),
+                bitcoind_rpc_endpoints: BitcoindRpcEndpoints::new("http://0.0.0.0:18332", &[]),
                 bitcoind_rpc_username: "devnet".into(),
                 bitcoind_rpc_password: "devnet".into(),
+                bitcoind_rpc_cookie_path: None,
                 bitcoin_block_signaling: BitcoinBlockSignaling::ZeroMQ(
                     "http://0.0.0.0:18543".into(),
                 ),
@@ -200,7 +703,28 @@ impl Config {
                 ordinals_internals: true,
                 chainhook_internals: false,
             },
-            meta_protocols: MetaProtocolsConfig { brc20: false },
+            meta_protocols: MetaProtocolsConfig {
+                brc20: false,
+                brc20_five_byte_tickers: true,
+                brc20_self_mint_activation_height: None,
+                brc20_strictness: Brc20Strictness::Strict,
+                brc20_modules: Brc20ModulesConfig::default(),
+                brc20_mempool_zmq_url: None,
+            },
+            address_watch: None,
+            api: None,
+            grpc: None,
+            admin: None,
+            shadow: None,
+            nats: None,
+            redis: None,
+            sinks: SinksConfig::default(),
+            address_clustering: AddressClusteringConfig::default(),
+            content_policy: ContentPolicyConfig::default(),
+            webhook: None,
+            dry_run: false,
+            prometheus_listen_address: Some(ListenAddress::Tcp(([0, 0, 0, 0], 9153).into())),
+            cpu_profiling: false,
         }
     }
 
@@ -209,6 +733,11 @@ impl Config {
             storage: StorageConfig {
                 working_dir: default_cache_path(),
                 observers_working_dir: default_observers_cache_path(),
+                raw_transactions_index: false,
+                text_search_index: false,
+                max_stored_content_bytes: None,
+                blocks_per_commit: DEFAULT_BLOCKS_PER_COMMIT,
+                observers_state: ObserversStateConfig::Local,
             },
             ordinals_db: PgConnectionConfig {
                 dbname: "ordinals".to_string(),
@@ -230,13 +759,18 @@ impl Config {
                 ulimit: DEFAULT_ULIMIT,
                 bitcoind_rpc_threads: DEFAULT_BITCOIND_RPC_THREADS,
                 bitcoind_rpc_timeout: DEFAULT_BITCOIND_RPC_TIMEOUT,
+                bitcoind_rpc_max_rps: None,
                 expected_observers_count: 1,
                 brc20_lru_cache_size: DEFAULT_BRC20_LRU_CACHE_SIZE,
+                block_compression_cores: None,
+                traversal_worker_cores: None,
+                tip_priority_lane: false,
             },
             network: IndexerConfig {
-                bitcoind_rpc_url: "http://0.0.0.0:8332".into(),
+                bitcoind_rpc_endpoints: BitcoindRpcEndpoints::new("http://0.0.0.0:8332", &[]),
                 bitcoind_rpc_username: "devnet".into(),
                 bitcoind_rpc_password: "devnet".into(),
+                bitcoind_rpc_cookie_path: None,
                 bitcoin_block_signaling: BitcoinBlockSignaling::ZeroMQ(
                     "http://0.0.0.0:18543".into(),
                 ),
@@ -247,7 +781,28 @@ impl Config {
                 ordinals_internals: true,
                 chainhook_internals: false,
             },
-            meta_protocols: MetaProtocolsConfig { brc20: false },
+            meta_protocols: MetaProtocolsConfig {
+                brc20: false,
+                brc20_five_byte_tickers: true,
+                brc20_self_mint_activation_height: None,
+                brc20_strictness: Brc20Strictness::Strict,
+                brc20_modules: Brc20ModulesConfig::default(),
+                brc20_mempool_zmq_url: None,
+            },
+            address_watch: None,
+            api: None,
+            grpc: None,
+            admin: None,
+            shadow: None,
+            nats: None,
+            redis: None,
+            sinks: SinksConfig::default(),
+            address_clustering: AddressClusteringConfig::default(),
+            content_policy: ContentPolicyConfig::default(),
+            webhook: None,
+            dry_run: false,
+            prometheus_listen_address: Some(ListenAddress::Tcp(([0, 0, 0, 0], 9153).into())),
+            cpu_profiling: false,
         }
     }
 
@@ -272,3 +827,98 @@ pub fn default_observers_cache_path() -> String {
     cache_path.push("observers");
     format!("{}", cache_path.display())
 }
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::{ListenAddress, WebhookAuthorizationSource};
+
+    #[tokio::test]
+    async fn resolves_the_latest_authorization_before_every_delivery() {
+        let dir =
+            std::env::temp_dir().join(format!("ordhook_authorization_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("authorization");
+        let source = WebhookAuthorizationSource::File(path.display().to_string());
+        assert!(source.resolve().await.is_err());
+
+        std::fs::write(&path, "Bearer first\n").unwrap();
+        assert_eq!(source.resolve().await, Ok("Bearer first".to_string()));
+        std::fs::write(&path, "Bearer second").unwrap();
+        assert_eq!(source.resolve().await, Ok("Bearer second".to_string()));
+
+        std::env::set_var("ORDHOOK_TEST_WEBHOOK_AUTHORIZATION", " Bearer env ");
+        assert_eq!(
+            WebhookAuthorizationSource::Env("ORDHOOK_TEST_WEBHOOK_AUTHORIZATION".to_string())
+                .resolve()
+                .await,
+            Ok("Bearer env".to_string())
+        );
+        assert!(
+            WebhookAuthorizationSource::Env("ORDHOOK_TEST_UNSET_AUTHORIZATION".to_string())
+                .resolve()
+                .await
+                .is_err()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn listens_on_default_ip_when_only_a_port_is_given() {
+        assert_eq!(
+            ListenAddress::from_settings_with_default_ip(
+                None,
+                Some(20457),
+                Ipv4Addr::LOCALHOST.into()
+            ),
+            Ok(Some(ListenAddress::Tcp(([127, 0, 0, 1], 20457).into())))
+        );
+        assert_eq!(
+            ListenAddress::from_settings_with_default_ip(
+                Some("0.0.0.0"),
+                Some(20457),
+                Ipv4Addr::LOCALHOST.into()
+            ),
+            Ok(Some(ListenAddress::Tcp(([0, 0, 0, 0], 20457).into())))
+        );
+        assert_eq!(
+            ListenAddress::from_settings(None, Some(20457)),
+            Ok(Some(ListenAddress::Tcp(([0, 0, 0, 0], 20457).into())))
+        );
+    }
+
+    #[test]
+    fn parses_bind_addresses() {
+        assert_eq!(
+            ListenAddress::from_settings(Some("[::1]"), Some(20456)),
+            Ok(Some(ListenAddress::Tcp(
+                (Ipv6Addr::LOCALHOST, 20456).into()
+            )))
+        );
+        assert_eq!(
+            ListenAddress::from_settings(Some("::1"), Some(20456)),
+            Ok(Some(ListenAddress::Tcp(
+                (Ipv6Addr::LOCALHOST, 20456).into()
+            )))
+        );
+        assert!(ListenAddress::from_settings(Some("[::1"), Some(20456)).is_err());
+        assert_eq!(
+            ListenAddress::from_settings(Some("unix:/run/ordhook/api.sock"), None),
+            Ok(Some(ListenAddress::Unix("/run/ordhook/api.sock".into())))
+        );
+        assert_eq!(ListenAddress::from_settings(None, None), Ok(None));
+    }
+
+    #[test]
+    fn rejects_inconsistent_ports() {
+        assert_eq!(
+            ListenAddress::from_settings(Some("127.0.0.1"), None),
+            Err("bind address 127.0.0.1 requires a port".to_string())
+        );
+        assert_eq!(
+            ListenAddress::from_settings(Some("unix:/run/ordhook/api.sock"), Some(20456)),
+            Err("bind address unix:/run/ordhook/api.sock does not take a port".to_string())
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+use chainhook_sdk::{
+    indexer::bitcoin::{
+        build_http_client, download_and_parse_block_with_retry, retrieve_block_hash_with_retry,
+        standardize_bitcoin_block,
+    },
+    utils::Context,
+};
+use chainhook_types::BitcoinBlockData;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{config::Config, try_info};
+
+/// Writes standardized blocks as a block fixture: gzip compressed JSON lines, one block per line, in chain order.
+pub fn write_block_fixture<W: Write>(blocks: &[BitcoinBlockData], writer: W) -> Result<(), String> {
+    let mut encoder = GzEncoder::new(writer, Compression::best());
+    for block in blocks.iter() {
+        serde_json::to_writer(&mut encoder, block).map_err(|e| {
+            format!(
+                "unable to serialize block #{}: {e}",
+                block.block_identifier.index
+            )
+        })?;
+        encoder
+            .write_all(b"\n")
+            .map_err(|e| format!("unable to write fixture: {e}"))?;
+    }
+    encoder
+        .finish()
+        .and_then(|mut writer| writer.flush())
+        .map_err(|e| format!("unable to write fixture: {e}"))
+}
+
+/// Reads the blocks of a block fixture and checks that each of them builds on the previous one.
+pub fn read_block_fixture<R: Read>(reader: R) -> Result<Vec<BitcoinBlockData>, String> {
+    let mut blocks: Vec<BitcoinBlockData> = vec![];
+    for (i, line) in BufReader::new(GzDecoder::new(reader)).lines().enumerate() {
+        let line = line.map_err(|e| format!("unable to read fixture: {e}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let block: BitcoinBlockData = serde_json::from_str(&line)
+            .map_err(|e| format!("invalid block on line {}: {e}", i + 1))?;
+        if let Some(previous) = blocks.last() {
+            if block.parent_block_identifier != previous.block_identifier {
+                return Err(format!(
+                    "block #{} does not build on block #{}",
+                    block.block_identifier.index, previous.block_identifier.index
+                ));
+            }
+        }
+        blocks.push(block);
+    }
+    Ok(blocks)
+}
+
+pub fn load_block_fixture(path: &Path) -> Result<Vec<BitcoinBlockData>, String> {
+    let file = File::open(path).map_err(|e| format!("unable to open {}: {e}", path.display()))?;
+    read_block_fixture(file).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Archives the blocks of a fixture in the blocks DB, as the block archiving processor does before blocks get indexed,
+/// so satoshi traversals can walk back through them.
+#[cfg(any(test, feature = "test-kit"))]
+pub fn archive_block_fixture(
+    blocks: &[BitcoinBlockData],
+    blocks_db_rw: &rocksdb::DB,
+    ctx: &Context,
+) {
+    for block in blocks.iter() {
+        crate::db::blocks::insert_standardized_block(block, blocks_db_rw, ctx);
+    }
+}
+
+/// Archives the blocks of the committed fixture `name` and indexes its last block with the pools of `config`, then
+/// returns that block. BRC-20 operations are indexed too when `config` enables them.
+#[cfg(test)]
+pub async fn index_committed_fixture(
+    name: &str,
+    config: &Config,
+    ctx: &Context,
+) -> Result<BitcoinBlockData, String> {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use crate::{
+        core::{
+            meta_protocols::brc20::cache::brc20_new_cache, new_traversals_lazy_cache,
+            pipeline::processors::inscription_indexing::index_block,
+            protocol::sequence_cursor::SequenceCursor,
+        },
+        db::blocks::open_blocks_db_with_retry,
+        service::Service,
+        utils::monitoring::PrometheusMonitoring,
+    };
+
+    let blocks = load_block_fixture(Path::new(&format!(
+        "{}/fixtures/blocks/{name}",
+        env!("CARGO_MANIFEST_DIR")
+    )))?;
+    {
+        let blocks_db = open_blocks_db_with_retry(true, config, ctx);
+        archive_block_fixture(&blocks, &blocks_db, ctx);
+    }
+    let mut block = blocks
+        .last()
+        .ok_or(format!("fixture {name} is empty"))?
+        .clone();
+    index_block(
+        &mut block,
+        &vec![],
+        &mut SequenceCursor::new(),
+        &mut BTreeMap::new(),
+        &Arc::new(new_traversals_lazy_cache(100)),
+        brc20_new_cache(config).as_mut(),
+        &PrometheusMonitoring::new(),
+        config,
+        &Service::new(config, ctx).pg_pools,
+        ctx,
+    )
+    .await?;
+    Ok(block)
+}
+
+/// Downloads blocks `start_block..=end_block` from bitcoind and writes them as a block fixture. Blocks are written as
+/// standardized, before any indexing. Satoshi traversals of inscriptions revealed in the fixture must not leave it, so
+/// fixtures are best recorded on a regtest chain or around hand picked transactions.
+pub async fn record_block_fixture<W: Write>(
+    config: &Config,
+    start_block: u64,
+    end_block: u64,
+    writer: W,
+    ctx: &Context,
+) -> Result<usize, String> {
+    if start_block > end_block {
+        return Err(format!(
+            "start block #{start_block} is after end block #{end_block}"
+        ));
+    }
+    let bitcoin_config = config.get_event_observer_config().get_bitcoin_config();
+    let http_client = build_http_client();
+    let mut blocks = vec![];
+    for block_height in start_block..=end_block {
+        let block_hash =
+            retrieve_block_hash_with_retry(&http_client, &block_height, &bitcoin_config, ctx)
+                .await?;
+        let block =
+            download_and_parse_block_with_retry(&http_client, &block_hash, &bitcoin_config, ctx)
+                .await?;
+        let block = standardize_bitcoin_block(block, &config.network.bitcoin_network, ctx)
+            .map_err(|(e, _)| e)?;
+        try_info!(ctx, "Recorded block #{block_height} {block_hash}");
+        blocks.push(block);
+    }
+    write_block_fixture(&blocks, writer)?;
+    Ok(blocks.len())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use chainhook_sdk::utils::Context;
+
+    use crate::{
+        config::Config,
+        core::test_builders::{TestBlockBuilder, TestTransactionBuilder},
+        db::{drop_all_dbs, ordinals_pg, pg_reset_db, pg_test_config, pg_test_connection},
+    };
+
+    use super::{
+        index_committed_fixture, load_block_fixture, read_block_fixture, write_block_fixture,
+    };
+
+    fn fixture_path(name: &str) -> String {
+        format!("{}/fixtures/blocks/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn round_trips_blocks() {
+        let block = TestBlockBuilder::new()
+            .add_transaction(TestTransactionBuilder::new().build())
+            .build();
+        let mut bytes = vec![];
+        write_block_fixture(&[block.clone()], &mut bytes).unwrap();
+        assert_eq!(read_block_fixture(bytes.as_slice()).unwrap(), vec![block]);
+    }
+
+    #[test]
+    fn rejects_blocks_out_of_order() {
+        let blocks = vec![
+            TestBlockBuilder::new().height(850000).build(),
+            TestBlockBuilder::new().height(849999).build(),
+        ];
+        let mut bytes = vec![];
+        write_block_fixture(&blocks, &mut bytes).unwrap();
+        assert!(read_block_fixture(bytes.as_slice()).is_err());
+    }
+
+    #[tokio::test]
+    async fn indexes_committed_fixture_without_bitcoind() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/block_fixtures".to_string();
+        config.ordinals_db = pg_test_config();
+        let blocks = load_block_fixture(Path::new(&fixture_path("inscription_reveal.jsonl.gz")))?;
+        assert_eq!(blocks.len(), 3);
+
+        drop_all_dbs(&config);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        let block = index_committed_fixture("inscription_reveal.jsonl.gz", &config, &ctx).await?;
+        assert_eq!(block.block_identifier, blocks[2].block_identifier);
+
+        assert_eq!(
+            ordinals_pg::get_chain_tip_block_height(&pg_client).await?,
+            Some(850000)
+        );
+        let inscriptions = ordinals_pg::get_inscriptions_at_block(&pg_client, 850000).await?;
+        let traversal = inscriptions
+            .get("b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0")
+            .ok_or("inscription not indexed")?;
+        assert_eq!(traversal.ordinal_number, 1971874375000000);
+        pg_reset_db(&mut pg_client).await?;
+        drop_all_dbs(&config);
+        Ok(())
+    }
+}
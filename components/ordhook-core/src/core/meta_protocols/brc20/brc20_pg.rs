@@ -2,13 +2,13 @@ use std::collections::HashMap;
 
 use chainhook_postgres::{
     types::{PgNumericU128, PgNumericU64},
-    utils, FromPgRow, BATCH_QUERY_CHUNK_SIZE,
+    utils, FromPgRow, PgError, BATCH_QUERY_CHUNK_SIZE,
 };
 use deadpool_postgres::GenericClient;
 use refinery::embed_migrations;
 use tokio_postgres::{types::ToSql, Client};
 
-use super::models::{DbOperation, DbToken};
+use super::models::{DbBalance, DbOperation, DbToken};
 
 embed_migrations!("../../migrations/ordinals-brc20");
 pub async fn migrate(pg_client: &mut Client) -> Result<(), String> {
@@ -22,31 +22,55 @@ pub async fn migrate(pg_client: &mut Client) -> Result<(), String> {
     };
 }
 
+/// Returns the version of the latest migration embedded in this binary.
+pub fn latest_migration_version() -> i64 {
+    migrations::runner()
+        .get_migrations()
+        .iter()
+        .map(|m| m.version() as i64)
+        .max()
+        .unwrap_or(0)
+}
+
 pub async fn get_token<T: GenericClient>(
     ticker: &String,
     client: &T,
-) -> Result<Option<DbToken>, String> {
+) -> Result<Option<DbToken>, PgError> {
     let row = client
         .query_opt("SELECT * FROM tokens WHERE ticker = $1", &[&ticker])
         .await
-        .map_err(|e| format!("get_token: {e}"))?;
+        .map_err(|e| PgError::Query("get_token", e))?;
     let Some(row) = row else {
         return Ok(None);
     };
     Ok(Some(DbToken::from_pg_row(&row)))
 }
 
+pub async fn get_tokens<T: GenericClient>(
+    tickers: &Vec<String>,
+    client: &T,
+) -> Result<Vec<DbToken>, PgError> {
+    if tickers.is_empty() {
+        return Ok(vec![]);
+    }
+    let rows = client
+        .query("SELECT * FROM tokens WHERE ticker = ANY($1)", &[&tickers])
+        .await
+        .map_err(|e| PgError::Query("get_tokens", e))?;
+    Ok(rows.iter().map(|row| DbToken::from_pg_row(row)).collect())
+}
+
 pub async fn get_token_minted_supply<T: GenericClient>(
     ticker: &String,
     client: &T,
-) -> Result<Option<u128>, String> {
+) -> Result<Option<u128>, PgError> {
     let row = client
         .query_opt(
             "SELECT minted_supply FROM tokens WHERE ticker = $1",
             &[&ticker],
         )
         .await
-        .map_err(|e| format!("get_token_minted_supply: {e}"))?;
+        .map_err(|e| PgError::Query("get_token_minted_supply", e))?;
     let Some(row) = row else {
         return Ok(None);
     };
@@ -58,14 +82,14 @@ pub async fn get_token_available_balance_for_address<T: GenericClient>(
     ticker: &String,
     address: &String,
     client: &T,
-) -> Result<Option<u128>, String> {
+) -> Result<Option<u128>, PgError> {
     let row = client
         .query_opt(
             "SELECT avail_balance FROM balances WHERE ticker = $1 AND address = $2",
             &[&ticker, &address],
         )
         .await
-        .map_err(|e| format!("get_token_available_balance_for_address: {e}"))?;
+        .map_err(|e| PgError::Query("get_token_available_balance_for_address", e))?;
     let Some(row) = row else {
         return Ok(None);
     };
@@ -73,10 +97,95 @@ pub async fn get_token_available_balance_for_address<T: GenericClient>(
     Ok(Some(supply.0))
 }
 
+/// Returns the available balance of every `(ticker, address)` pair that has one.
+pub async fn get_token_available_balances_for_addresses<T: GenericClient>(
+    keys: &Vec<(String, String)>,
+    client: &T,
+) -> Result<HashMap<(String, String), u128>, PgError> {
+    let mut results = HashMap::new();
+    for chunk in keys.chunks(5000) {
+        let tickers: Vec<&String> = chunk.iter().map(|(ticker, _)| ticker).collect();
+        let addresses: Vec<&String> = chunk.iter().map(|(_, address)| address).collect();
+        let rows = client
+            .query(
+                "SELECT b.ticker, b.address, b.avail_balance
+                FROM balances AS b
+                INNER JOIN UNNEST($1::text[], $2::text[]) AS k(ticker, address)
+                    ON b.ticker = k.ticker AND b.address = k.address",
+                &[&tickers, &addresses],
+            )
+            .await
+            .map_err(|e| PgError::Query("get_token_available_balances_for_addresses", e))?;
+        for row in rows.iter() {
+            let balance: PgNumericU128 = row.get("avail_balance");
+            results.insert((row.get("ticker"), row.get("address")), balance.0);
+        }
+    }
+    Ok(results)
+}
+
+pub async fn get_balances_for_address<T: GenericClient>(
+    address: &String,
+    client: &T,
+) -> Result<Vec<DbBalance>, PgError> {
+    let rows = client
+        .query(
+            "SELECT * FROM balances WHERE address = $1 ORDER BY ticker",
+            &[&address],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_balances_for_address", e))?;
+    Ok(rows.iter().map(|row| DbBalance::from_pg_row(row)).collect())
+}
+
+/// Balances of `address` as of the end of `block_height`, read from the balance history.
+pub async fn get_balances_for_address_at_height<T: GenericClient>(
+    address: &String,
+    block_height: u64,
+    client: &T,
+) -> Result<Vec<DbBalance>, PgError> {
+    let rows = client
+        .query(
+            "SELECT DISTINCT ON (ticker) ticker, address, avail_balance, trans_balance, total_balance
+            FROM balances_history
+            WHERE address = $1 AND block_height <= $2
+            ORDER BY ticker, block_height DESC",
+            &[&address, &PgNumericU64(block_height)],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_balances_for_address_at_height", e))?;
+    Ok(rows.iter().map(|row| DbBalance::from_pg_row(row)).collect())
+}
+
+/// Operations that changed the balances of `address`, newest first, optionally only those of a single `operation`.
+pub async fn get_address_operations<T: GenericClient>(
+    address: &String,
+    operation: Option<&str>,
+    limit: i64,
+    offset: i64,
+    client: &T,
+) -> Result<Vec<DbOperation>, PgError> {
+    let rows = client
+        .query(
+            "SELECT o.* FROM address_operations AS a
+            INNER JOIN operations AS o ON o.inscription_id = a.inscription_id AND o.operation = a.operation
+            WHERE a.address = $1 AND ($2::text IS NULL OR a.operation = $2)
+            ORDER BY a.block_height DESC, a.tx_index DESC
+            LIMIT $3 OFFSET $4",
+            &[&address, &operation, &limit, &offset],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_address_operations", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| DbOperation::from_pg_row(row))
+        .collect())
+}
+
 pub async fn get_unsent_token_transfers<T: GenericClient>(
     ordinal_numbers: &Vec<u64>,
     client: &T,
-) -> Result<Vec<DbOperation>, String> {
+) -> Result<Vec<DbOperation>, PgError> {
     if ordinal_numbers.is_empty() {
         return Ok(vec![]);
     }
@@ -106,7 +215,7 @@ pub async fn get_unsent_token_transfers<T: GenericClient>(
                 &[&params],
             )
             .await
-            .map_err(|e| format!("get_unsent_token_transfers: {e}"))?;
+            .map_err(|e| PgError::Query("get_unsent_token_transfers", e))?;
         results.extend(rows.iter().map(|row| DbOperation::from_pg_row(row)));
     }
     Ok(results)
@@ -115,7 +224,7 @@ pub async fn get_unsent_token_transfers<T: GenericClient>(
 pub async fn insert_tokens<T: GenericClient>(
     tokens: &Vec<DbToken>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if tokens.len() == 0 {
         return Ok(());
     }
@@ -137,19 +246,20 @@ pub async fn insert_tokens<T: GenericClient>(
             params.push(&row.self_mint);
             params.push(&row.minted_supply);
             params.push(&row.tx_count);
+            params.push(&row.holders);
             params.push(&row.timestamp);
         }
         client
             .query(
                 &format!("INSERT INTO tokens
                     (ticker, display_ticker, inscription_id, inscription_number, block_height, block_hash, tx_id, tx_index,
-                    address, max, \"limit\", decimals, self_mint, minted_supply, tx_count, timestamp)
+                    address, max, \"limit\", decimals, self_mint, minted_supply, tx_count, holders, timestamp)
                     VALUES {}
-                    ON CONFLICT (ticker) DO NOTHING", utils::multi_row_query_param_str(chunk.len(), 16)),
+                    ON CONFLICT (ticker) DO NOTHING", utils::multi_row_query_param_str(chunk.len(), 17)),
                 &params,
             )
             .await
-            .map_err(|e| format!("insert_tokens: {e}"))?;
+            .map_err(|e| PgError::Query("insert_tokens", e))?;
     }
     Ok(())
 }
@@ -157,7 +267,7 @@ pub async fn insert_tokens<T: GenericClient>(
 pub async fn insert_operations<T: GenericClient>(
     operations: &Vec<DbOperation>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if operations.len() == 0 {
         return Ok(());
     }
@@ -191,7 +301,12 @@ pub async fn insert_operations<T: GenericClient>(
                         tx_index, output, \"offset\", timestamp, address, to_address, amount)
                         VALUES {}
                         ON CONFLICT (inscription_id, operation) DO NOTHING
-                        RETURNING address, ticker, operation, amount, block_height
+                        RETURNING address, ticker, operation, amount, block_height, inscription_id, tx_index
+                    ),
+                    address_operation_inserts AS (
+                        INSERT INTO address_operations (address, ticker, operation, inscription_id, block_height, tx_index)
+                        (SELECT address, ticker, operation, inscription_id, block_height, tx_index FROM inserts)
+                        ON CONFLICT (address, inscription_id, operation) DO NOTHING
                     ),
                     balance_changes AS (
                         SELECT ticker, address,
@@ -228,6 +343,22 @@ pub async fn insert_operations<T: GenericClient>(
                             total_balance = balances.total_balance + EXCLUDED.total_balance
                         RETURNING ticker, address, avail_balance, trans_balance, total_balance,
                             (SELECT MAX(block_height) FROM grouped_balance_changes) AS block_height
+                    ),
+                    -- An address becomes a holder when its overall balance goes from zero to positive, and stops being one
+                    -- when it goes back to zero.
+                    holder_changes AS (
+                        SELECT b.ticker, SUM(
+                            (CASE WHEN b.total_balance > 0 THEN 1 ELSE 0 END)
+                            - (CASE WHEN b.total_balance - g.total_balance > 0 THEN 1 ELSE 0 END)
+                        ) AS holders
+                        FROM balance_inserts AS b
+                        INNER JOIN grouped_balance_changes AS g ON g.ticker = b.ticker AND g.address = b.address
+                        GROUP BY b.ticker
+                    ),
+                    holder_updates AS (
+                        UPDATE tokens SET holders = tokens.holders + h.holders
+                        FROM holder_changes AS h
+                        WHERE tokens.ticker = h.ticker AND h.holders <> 0
                     )
                     INSERT INTO balances_history (ticker, address, block_height, avail_balance, trans_balance, total_balance)
                     (SELECT ticker, address, block_height, avail_balance, trans_balance, total_balance FROM balance_inserts)
@@ -239,7 +370,7 @@ pub async fn insert_operations<T: GenericClient>(
                 &params,
             )
             .await
-            .map_err(|e| format!("insert_operations: {e}"))?;
+            .map_err(|e| PgError::Query("insert_operations", e))?;
     }
     Ok(())
 }
@@ -247,7 +378,7 @@ pub async fn insert_operations<T: GenericClient>(
 pub async fn update_operation_counts<T: GenericClient>(
     counts: &HashMap<String, i32>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if counts.len() == 0 {
         return Ok(());
     }
@@ -266,14 +397,14 @@ pub async fn update_operation_counts<T: GenericClient>(
             &params,
         )
         .await
-        .map_err(|e| format!("update_operation_counts: {e}"))?;
+        .map_err(|e| PgError::Query("update_operation_counts", e))?;
     Ok(())
 }
 
 pub async fn update_address_operation_counts<T: GenericClient>(
     counts: &HashMap<String, HashMap<String, i32>>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if counts.len() == 0 {
         return Ok(());
     }
@@ -303,7 +434,7 @@ pub async fn update_address_operation_counts<T: GenericClient>(
                 &params,
             )
             .await
-            .map_err(|e| format!("update_address_operation_counts: {e}"))?;
+            .map_err(|e| PgError::Query("update_address_operation_counts", e))?;
     }
     Ok(())
 }
@@ -311,7 +442,7 @@ pub async fn update_address_operation_counts<T: GenericClient>(
 pub async fn update_token_operation_counts<T: GenericClient>(
     counts: &HashMap<String, i32>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if counts.len() == 0 {
         return Ok(());
     }
@@ -344,7 +475,7 @@ pub async fn update_token_operation_counts<T: GenericClient>(
                 &params,
             )
             .await
-            .map_err(|e| format!("update_token_operation_counts: {e}"))?;
+            .map_err(|e| PgError::Query("update_token_operation_counts", e))?;
     }
     Ok(())
 }
@@ -352,7 +483,7 @@ pub async fn update_token_operation_counts<T: GenericClient>(
 pub async fn update_token_minted_supplies<T: GenericClient>(
     supplies: &HashMap<String, PgNumericU128>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if supplies.len() == 0 {
         return Ok(());
     }
@@ -385,15 +516,54 @@ pub async fn update_token_minted_supplies<T: GenericClient>(
                 &params,
             )
             .await
-            .map_err(|e| format!("update_token_minted_supplies: {e}"))?;
+            .map_err(|e| PgError::Query("update_token_minted_supplies", e))?;
     }
     Ok(())
 }
 
+/// Returns the highest block that produced a BRC-20 operation.
+pub async fn get_highest_operation_block_height<T: GenericClient>(
+    client: &T,
+) -> Result<Option<u64>, PgError> {
+    let row = client
+        .query_one(
+            "SELECT MAX(block_height) AS block_height FROM operations",
+            &[],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_highest_operation_block_height", e))?;
+    let max: Option<PgNumericU64> = row.get("block_height");
+    Ok(max.map(|v| v.0))
+}
+
+/// Returns the height and hash of every block in `from..=to` that produced BRC-20 operations, ordered by height.
+pub async fn get_operation_block_hashes<T: GenericClient>(
+    from: u64,
+    to: u64,
+    client: &T,
+) -> Result<Vec<(u64, String)>, PgError> {
+    let rows = client
+        .query(
+            "SELECT DISTINCT block_height, block_hash FROM operations
+            WHERE block_height BETWEEN $1 AND $2
+            ORDER BY block_height",
+            &[&PgNumericU64(from), &PgNumericU64(to)],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_operation_block_hashes", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let block_height: PgNumericU64 = row.get("block_height");
+            (block_height.0, row.get("block_hash"))
+        })
+        .collect())
+}
+
 pub async fn rollback_block_operations<T: GenericClient>(
     block_height: u64,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     client
         .execute(
             "WITH ops AS (SELECT * FROM operations WHERE block_height = $1),
@@ -441,8 +611,20 @@ pub async fn rollback_block_operations<T: GenericClient>(
                     WHERE grouped_balance_changes.ticker = balances.ticker AND grouped_balance_changes.address = balances.address
                 )
             ),
+            holder_changes AS (
+                SELECT g.ticker, SUM(
+                    (CASE WHEN b.total_balance - g.total_balance > 0 THEN 1 ELSE 0 END)
+                    - (CASE WHEN b.total_balance > 0 THEN 1 ELSE 0 END)
+                ) AS holders
+                FROM grouped_balance_changes AS g
+                INNER JOIN balances AS b ON b.ticker = g.ticker AND b.address = g.address
+                GROUP BY g.ticker
+            ),
             token_updates AS (
                 UPDATE tokens SET
+                    holders = holders + COALESCE((
+                        SELECT h.holders::int FROM holder_changes AS h WHERE h.ticker = tokens.ticker
+                    ), 0),
                     minted_supply = COALESCE((
                         SELECT tokens.minted_supply - SUM(ops.amount)
                         FROM ops
@@ -484,12 +666,13 @@ pub async fn rollback_block_operations<T: GenericClient>(
                 )
             ),
             token_deletes AS (DELETE FROM tokens WHERE block_height = $1),
-            balances_history_deletes AS (DELETE FROM balances_history WHERE block_height = $1)
+            balances_history_deletes AS (DELETE FROM balances_history WHERE block_height = $1),
+            address_operations_deletes AS (DELETE FROM address_operations WHERE block_height = $1)
             DELETE FROM operations WHERE block_height = $1",
             &[&PgNumericU64(block_height)],
         )
         .await
-        .map_err(|e| format!("rollback_block_operations: {e}"))?;
+        .map_err(|e| PgError::Query("rollback_block_operations", e))?;
     Ok(())
 }
 
@@ -511,7 +694,7 @@ mod test {
         core::meta_protocols::brc20::{
             brc20_pg::{self, get_token_minted_supply},
             cache::Brc20MemoryCache,
-            models::{DbOperation, DbToken},
+            models::{DbBalance, DbOperation, DbToken},
             test_utils::{Brc20RevealBuilder, Brc20TransferBuilder},
             verifier::{
                 VerifiedBrc20BalanceData, VerifiedBrc20TokenDeployData, VerifiedBrc20TransferData,
@@ -584,6 +767,27 @@ mod test {
         Some((avail_balance, trans_balance, total_balance))
     }
 
+    async fn get_address_operation_names<T: GenericClient>(
+        address: &str,
+        client: &T,
+    ) -> Result<Vec<String>, String> {
+        Ok(
+            brc20_pg::get_address_operations(&address.to_string(), None, 20, 0, client)
+                .await?
+                .into_iter()
+                .map(|op| op.operation)
+                .collect(),
+        )
+    }
+
+    async fn get_token_holders<T: GenericClient>(ticker: &str, client: &T) -> i32 {
+        let row = client
+            .query_one("SELECT holders FROM tokens WHERE ticker = $1", &[&ticker])
+            .await
+            .unwrap();
+        row.get("holders")
+    }
+
     async fn get_address_token_balance_at_block<T: GenericClient>(
         address: &str,
         ticker: &str,
@@ -687,6 +891,7 @@ mod test {
                         self_mint: false,
                         minted_supply: PgNumericU128(0),
                         tx_count: 1,
+                        holders: 0,
                         timestamp: PgBigIntU32(0)
                     }
                 );
@@ -735,6 +940,7 @@ mod test {
                 cache.db_cache.flush(&client).await?;
                 let operations = get_operations_at_block(800001, &client).await?;
                 assert_eq!(1, operations.len());
+                assert_eq!(1, get_token_holders("pepe", &client).await);
                 assert_eq!((1, 1, 0, 0), get_counts_by_operation(&client).await);
                 assert_eq!(
                     (1, 1, 0, 0),
@@ -840,6 +1046,20 @@ mod test {
                     )
                     .await
                 );
+                assert_eq!(
+                    vec![DbBalance {
+                        ticker: "pepe".to_string(),
+                        address: "324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp".to_string(),
+                        avail_balance: PgNumericU128(500_000000000000000000),
+                        trans_balance: PgNumericU128(500_000000000000000000),
+                        total_balance: PgNumericU128(1000_000000000000000000),
+                    }],
+                    brc20_pg::get_balances_for_address(
+                        &"324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp".to_string(),
+                        &client
+                    )
+                    .await?
+                );
             }
             // Transfer send
             {
@@ -878,6 +1098,20 @@ mod test {
                     .await?;
                 cache.db_cache.flush(&client).await?;
                 assert_eq!((1, 1, 1, 1), get_counts_by_operation(&client).await);
+                assert_eq!(2, get_token_holders("pepe", &client).await);
+                assert_eq!(
+                    vec!["transfer_send", "transfer", "mint", "deploy"],
+                    get_address_operation_names("324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp", &client)
+                        .await?
+                );
+                assert_eq!(
+                    vec!["transfer_receive"],
+                    get_address_operation_names(
+                        "bc1pngjqgeamkmmhlr6ft5yllgdmfllvcvnw5s7ew2ler3rl0z47uaesrj6jte",
+                        &client
+                    )
+                    .await?
+                );
                 assert_eq!(
                     Some(1000_000000000000000000),
                     get_token_minted_supply(&"pepe".to_string(), &client).await?
@@ -947,6 +1181,18 @@ mod test {
             {
                 brc20_pg::rollback_block_operations(800003, &client).await?;
                 assert_eq!((1, 1, 1, 0), get_counts_by_operation(&client).await);
+                assert_eq!(1, get_token_holders("pepe", &client).await);
+                assert_eq!(
+                    vec!["transfer", "mint", "deploy"],
+                    get_address_operation_names("324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp", &client)
+                        .await?
+                );
+                assert!(get_address_operation_names(
+                    "bc1pngjqgeamkmmhlr6ft5yllgdmfllvcvnw5s7ew2ler3rl0z47uaesrj6jte",
+                    &client
+                )
+                .await?
+                .is_empty());
                 assert_eq!(
                     Some(1000_000000000000000000),
                     get_token_minted_supply(&"pepe".to_string(), &client).await?
@@ -1010,6 +1256,7 @@ mod test {
             {
                 brc20_pg::rollback_block_operations(800001, &client).await?;
                 assert_eq!((1, 0, 0, 0), get_counts_by_operation(&client).await);
+                assert_eq!(0, get_token_holders("pepe", &client).await);
                 assert_eq!(
                     (1, 0, 0, 0),
                     get_counts_by_address_operation("324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp", &client)
@@ -14,6 +14,7 @@ use maplit::hashmap;
 
 use crate::{
     config::Config, core::protocol::satoshi_tracking::parse_output_and_offset_from_satpoint,
+    error::OrdhookError,
 };
 
 use super::{
@@ -53,7 +54,7 @@ impl Brc20DbCache {
         }
     }
 
-    pub async fn flush<T: GenericClient>(&mut self, client: &T) -> Result<(), String> {
+    pub async fn flush<T: GenericClient>(&mut self, client: &T) -> Result<(), OrdhookError> {
         brc20_pg::insert_tokens(&self.token_rows, client).await?;
         self.token_rows.clear();
         brc20_pg::insert_operations(&self.operations, client).await?;
@@ -96,7 +97,7 @@ impl Brc20MemoryCache {
         &mut self,
         tick: &String,
         client: &T,
-    ) -> Result<Option<DbToken>, String> {
+    ) -> Result<Option<DbToken>, OrdhookError> {
         if let Some(token) = self.tokens.get(tick) {
             return Ok(Some(token.clone()));
         }
@@ -114,7 +115,7 @@ impl Brc20MemoryCache {
         &mut self,
         tick: &String,
         client: &T,
-    ) -> Result<Option<u128>, String> {
+    ) -> Result<Option<u128>, OrdhookError> {
         if let Some(minted) = self.token_minted_supplies.get(tick) {
             return Ok(Some(minted.clone()));
         }
@@ -132,7 +133,7 @@ impl Brc20MemoryCache {
         tick: &String,
         address: &String,
         client: &T,
-    ) -> Result<Option<u128>, String> {
+    ) -> Result<Option<u128>, OrdhookError> {
         let key = format!("{}:{}", tick, address);
         if let Some(balance) = self.token_addr_avail_balances.get(&key) {
             return Ok(Some(balance.clone()));
@@ -151,7 +152,7 @@ impl Brc20MemoryCache {
         &mut self,
         ordinal_numbers: &Vec<&u64>,
         client: &T,
-    ) -> Result<Vec<DbOperation>, String> {
+    ) -> Result<Vec<DbOperation>, OrdhookError> {
         let mut results = vec![];
         let mut cache_missed_ordinal_numbers = HashSet::new();
         for ordinal_number in ordinal_numbers.iter() {
@@ -187,6 +188,47 @@ impl Brc20MemoryCache {
         return Ok(results);
     }
 
+    /// Loads the tokens and receiver balances needed to apply a batch of verified transfers, so applying them does not
+    /// take a DB round trip per transfer. Cache misses are fetched with one query per kind, and both queries run
+    /// concurrently on the same connection. Receivers without a balance are cached at zero, which is what applying
+    /// their `transfer_send` would default to anyway.
+    pub async fn prefetch_transfer_sends<T: GenericClient>(
+        &mut self,
+        transfers: &Vec<&VerifiedBrc20TransferData>,
+        client: &T,
+    ) -> Result<(), OrdhookError> {
+        let mut missing_tickers = HashSet::new();
+        let mut missing_balances = HashSet::new();
+        for data in transfers.iter() {
+            if !self.tokens.contains(&data.tick) {
+                missing_tickers.insert(data.tick.clone());
+            }
+            let key = format!("{}:{}", data.tick, data.receiver_address);
+            if !self.token_addr_avail_balances.contains(&key) {
+                missing_balances.insert((data.tick.clone(), data.receiver_address.clone()));
+            }
+        }
+        if missing_tickers.is_empty() && missing_balances.is_empty() {
+            return Ok(());
+        }
+        self.handle_cache_miss(client).await?;
+        let missing_tickers: Vec<String> = missing_tickers.into_iter().collect();
+        let missing_balances: Vec<(String, String)> = missing_balances.into_iter().collect();
+        let (tokens, balances) = tokio::try_join!(
+            brc20_pg::get_tokens(&missing_tickers, client),
+            brc20_pg::get_token_available_balances_for_addresses(&missing_balances, client)
+        )?;
+        for token in tokens.into_iter() {
+            self.tokens.put(token.ticker.clone(), token);
+        }
+        for key in missing_balances.into_iter() {
+            let balance = balances.get(&key).cloned().unwrap_or(0);
+            self.token_addr_avail_balances
+                .put(format!("{}:{}", key.0, key.1), balance);
+        }
+        Ok(())
+    }
+
     /// Marks an ordinal number as ignored so we don't bother computing its transfers for BRC20 purposes.
     pub fn ignore_inscription(&mut self, ordinal_number: u64) {
         self.ignored_inscriptions.put(ordinal_number, true);
@@ -200,7 +242,7 @@ impl Brc20MemoryCache {
         timestamp: u32,
         tx_identifier: &TransactionIdentifier,
         tx_index: u64,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
         let (output, offset) =
             parse_output_and_offset_from_satpoint(&reveal.satpoint_post_inscription)?;
         let token = DbToken {
@@ -219,6 +261,7 @@ impl Brc20MemoryCache {
             decimals: PgSmallIntU8(data.dec),
             minted_supply: PgNumericU128(0),
             tx_count: 0,
+            holders: 0,
             timestamp: PgBigIntU32(timestamp),
         };
         self.tokens.put(token.ticker.clone(), token.clone());
@@ -260,7 +303,7 @@ impl Brc20MemoryCache {
         tx_identifier: &TransactionIdentifier,
         tx_index: u64,
         client: &T,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
         let Some(minted) = self.get_token_minted_supply(&data.tick, client).await? else {
             unreachable!("BRC-20 deployed token should have a minted supply entry");
         };
@@ -315,7 +358,7 @@ impl Brc20MemoryCache {
         tx_identifier: &TransactionIdentifier,
         tx_index: u64,
         client: &T,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
         let Some(balance) = self
             .get_token_address_avail_balance(&data.tick, &data.address, client)
             .await?
@@ -365,7 +408,7 @@ impl Brc20MemoryCache {
         tx_identifier: &TransactionIdentifier,
         tx_index: u64,
         client: &T,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
         let (output, offset) =
             parse_output_and_offset_from_satpoint(&transfer.satpoint_post_transfer)?;
         let transfer_row = self
@@ -466,7 +509,7 @@ impl Brc20MemoryCache {
         &mut self,
         ordinal_number: u64,
         client: &T,
-    ) -> Result<DbOperation, String> {
+    ) -> Result<DbOperation, OrdhookError> {
         if let Some(transfer) = self.unsent_transfers.get(&ordinal_number) {
             return Ok(transfer.clone());
         }
@@ -479,7 +522,10 @@ impl Brc20MemoryCache {
         return Ok(transfer.clone());
     }
 
-    async fn handle_cache_miss<T: GenericClient>(&mut self, client: &T) -> Result<(), String> {
+    async fn handle_cache_miss<T: GenericClient>(
+        &mut self,
+        client: &T,
+    ) -> Result<(), OrdhookError> {
         // TODO: Measure this event somewhere
         self.db_cache.flush(client).await?;
         Ok(())
@@ -493,13 +539,14 @@ mod test {
     use test_case::test_case;
 
     use crate::{
+        config::Brc20Strictness,
         core::meta_protocols::brc20::{
-            brc20_pg,
+            brc20_pg, brc20_self_mint_activation_height,
             parser::{ParsedBrc20BalanceData, ParsedBrc20Operation},
             test_utils::{get_test_ctx, Brc20RevealBuilder},
             verifier::{
                 verify_brc20_operation, VerifiedBrc20BalanceData, VerifiedBrc20Operation,
-                VerifiedBrc20TokenDeployData,
+                VerifiedBrc20TokenDeployData, VerifiedBrc20TransferData,
             },
         },
         db::{pg_reset_db, pg_test_connection, pg_test_connection_pool},
@@ -639,12 +686,14 @@ mod test {
                     .inscriber_address(Some(address1.clone()))
                     .build(),
                 &block,
-                &BitcoinNetwork::Mainnet,
+                Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                Brc20Strictness::Strict,
                 &mut cache,
                 &client,
                 &ctx,
             )
-            .await;
+            .await
+            .map_err(String::from);
             assert!(
                 result
                     == Ok(Some(VerifiedBrc20Operation::TokenTransfer(
@@ -761,4 +810,102 @@ mod test {
         pg_reset_db(&mut pg_client).await?;
         result
     }
+
+    #[tokio::test]
+    async fn test_brc20_memory_cache_prefetch_transfer_sends() -> Result<(), String> {
+        let mut pg_client = pg_test_connection().await;
+        brc20_pg::migrate(&mut pg_client).await?;
+        {
+            let mut ord_client = pg_pool_client(&pg_test_connection_pool()).await?;
+            let client = pg_begin(&mut ord_client).await?;
+
+            let address1 = "324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp".to_string();
+            let address2 =
+                "bc1pngjqgeamkmmhlr6ft5yllgdmfllvcvnw5s7ew2ler3rl0z47uaesrj6jte".to_string();
+            let mut writer = Brc20MemoryCache::new(10);
+            writer.insert_token_deploy(
+                &VerifiedBrc20TokenDeployData {
+                    tick: "pepe".to_string(),
+                    display_tick: "pepe".to_string(),
+                    max: 21000000_000000000000000000,
+                    lim: 1000_000000000000000000,
+                    dec: 18,
+                    address: address1.clone(),
+                    self_mint: false,
+                },
+                &Brc20RevealBuilder::new().inscription_number(0).build(),
+                &BlockIdentifier {
+                    index: 800000,
+                    hash: "00000000000000000002d8ba402150b259ddb2b30a1d32ab4a881d4653bceb5b"
+                        .to_string(),
+                },
+                0,
+                &TransactionIdentifier {
+                    hash: "8c8e37ce3ddd869767f8d839d16acc7ea4ec9dd7e3c73afd42a0abb859d7d391"
+                        .to_string(),
+                },
+                0,
+            )?;
+            writer
+                .insert_token_mint(
+                    &VerifiedBrc20BalanceData {
+                        tick: "pepe".to_string(),
+                        amt: 1000_000000000000000000,
+                        address: address1.clone(),
+                    },
+                    &Brc20RevealBuilder::new().inscription_number(1).build(),
+                    &BlockIdentifier {
+                        index: 800001,
+                        hash: "00000000000000000002d8ba402150b259ddb2b30a1d32ab4a881d4653bceb5b"
+                            .to_string(),
+                    },
+                    0,
+                    &TransactionIdentifier {
+                        hash: "8c8e37ce3ddd869767f8d839d16acc7ea4ec9dd7e3c73afd42a0abb859d7d392"
+                            .to_string(),
+                    },
+                    1,
+                    &client,
+                )
+                .await?;
+            writer.db_cache.flush(&client).await?;
+
+            let mut cache = Brc20MemoryCache::new(10);
+            let transfers = vec![
+                VerifiedBrc20TransferData {
+                    tick: "pepe".to_string(),
+                    amt: 1,
+                    sender_address: address2.clone(),
+                    receiver_address: address1.clone(),
+                },
+                VerifiedBrc20TransferData {
+                    tick: "pepe".to_string(),
+                    amt: 1,
+                    sender_address: address1.clone(),
+                    receiver_address: address2.clone(),
+                },
+            ];
+            cache
+                .prefetch_transfer_sends(&transfers.iter().collect(), &client)
+                .await?;
+
+            assert!(cache.tokens.contains(&"pepe".to_string()));
+            assert_eq!(
+                cache
+                    .token_addr_avail_balances
+                    .get(&format!("pepe:{address1}"))
+                    .cloned(),
+                Some(1000_000000000000000000)
+            );
+            assert_eq!(
+                cache
+                    .token_addr_avail_balances
+                    .get(&format!("pepe:{address2}"))
+                    .cloned(),
+                Some(0)
+            );
+        }
+        pg_reset_db(&mut pg_client).await?;
+        Ok(())
+    }
 }
@@ -7,7 +7,10 @@ use chainhook_types::{
 };
 use deadpool_postgres::Transaction;
 
-use crate::{core::meta_protocols::brc20::u128_amount_to_decimals_str, try_info};
+use crate::{
+    config::Brc20Strictness, core::meta_protocols::brc20::u128_amount_to_decimals_str,
+    error::OrdhookError, try_info,
+};
 
 use super::{
     brc20_activation_height,
@@ -17,6 +20,10 @@ use super::{
 };
 
 /// Index ordinal transfers in a single Bitcoin block looking for BRC-20 transfers.
+///
+/// Lookups are batched for the whole block, but verified transfers are then applied one at a time in `tx_index` order,
+/// not concurrently per ticker. Once prefetched, applying a transfer only updates the in-memory cache, and any cache
+/// miss first flushes the pending writes of every ticker, so per-ticker tasks would have nothing left to overlap.
 async fn index_unverified_brc20_transfers(
     transfers: &Vec<(&TransactionIdentifier, &OrdinalInscriptionTransferData)>,
     block_identifier: &BlockIdentifier,
@@ -24,7 +31,7 @@ async fn index_unverified_brc20_transfers(
     brc20_cache: &mut Brc20MemoryCache,
     brc20_db_tx: &Transaction<'_>,
     ctx: &Context,
-) -> Result<Vec<(usize, Brc20Operation)>, String> {
+) -> Result<Vec<(usize, Brc20Operation)>, OrdhookError> {
     if transfers.is_empty() {
         return Ok(vec![]);
     }
@@ -33,6 +40,15 @@ async fn index_unverified_brc20_transfers(
         verify_brc20_transfers(transfers, brc20_cache, &brc20_db_tx, &ctx).await?;
     // Sort verified transfers by tx_index to make sure they are applied in the order they came through.
     verified_brc20_transfers.sort_by(|a, b| a.2.tx_index.cmp(&b.2.tx_index));
+    brc20_cache
+        .prefetch_transfer_sends(
+            &verified_brc20_transfers
+                .iter()
+                .map(|(_, data, _, _)| data)
+                .collect(),
+            brc20_db_tx,
+        )
+        .await?;
 
     for (inscription_id, data, transfer, tx_identifier) in verified_brc20_transfers.into_iter() {
         let Some(token) = brc20_cache.get_token(&data.tick, brc20_db_tx).await? else {
@@ -76,10 +92,12 @@ async fn index_unverified_brc20_transfers(
 pub async fn index_block_and_insert_brc20_operations(
     block: &mut BitcoinBlockData,
     brc20_operation_map: &mut HashMap<String, ParsedBrc20Operation>,
+    self_mint_activation_height: Option<u64>,
+    strictness: Brc20Strictness,
     brc20_cache: &mut Brc20MemoryCache,
     brc20_db_tx: &Transaction<'_>,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<(), OrdhookError> {
     if block.block_identifier.index < brc20_activation_height(&block.metadata.network) {
         return Ok(());
     }
@@ -117,7 +135,8 @@ pub async fn index_block_and_insert_brc20_operations(
                         parsed_brc20_operation,
                         reveal,
                         &block.block_identifier,
-                        &block.metadata.network,
+                        self_mint_activation_height,
+                        strictness,
                         brc20_cache,
                         &brc20_db_tx,
                         &ctx,
@@ -265,14 +284,15 @@ mod test {
 
     use chainhook_postgres::{pg_begin, pg_pool_client};
     use chainhook_types::{
-        Brc20BalanceData, Brc20Operation, Brc20TokenDeployData, Brc20TransferData,
-        OrdinalInscriptionTransferDestination, OrdinalOperation,
+        BitcoinBlockData, BitcoinNetwork, Brc20BalanceData, Brc20Operation, Brc20TokenDeployData,
+        Brc20TransferData, OrdinalInscriptionTransferDestination, OrdinalOperation,
     };
 
     use crate::{
+        config::Brc20Strictness,
         core::{
             meta_protocols::brc20::{
-                brc20_pg,
+                brc20_pg, brc20_self_mint_activation_height,
                 cache::Brc20MemoryCache,
                 index::index_block_and_insert_brc20_operations,
                 parser::{
@@ -382,11 +402,14 @@ mod test {
             let result = index_block_and_insert_brc20_operations(
                 &mut block,
                 &mut operation_map,
+                Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                Brc20Strictness::Strict,
                 &mut cache,
                 &client,
                 &ctx,
             )
-            .await;
+            .await
+            .map_err(String::from);
 
             assert_eq!(
                 block
@@ -470,4 +493,140 @@ mod test {
         pg_reset_db(&mut pg_client).await?;
         result
     }
+
+    fn brc20_reveal_block(
+        height: u64,
+        inscription_number: i64,
+        inscription_id: &str,
+    ) -> BitcoinBlockData {
+        TestBlockBuilder::new()
+            .height(height)
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(
+                        Brc20RevealBuilder::new()
+                            .inscription_number(inscription_number)
+                            .ordinal_number(100 * (inscription_number as u64 + 1))
+                            .inscription_id(inscription_id)
+                            .inscriber_address(Some(
+                                "19PFYXeUuArA3vRDHh2zz8tupAYNFqjBCP".to_string(),
+                            ))
+                            .build(),
+                    ))
+                    .build(),
+            )
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_streamed_block_after_catch_up_sees_missed_mints() -> Result<(), String> {
+        let ctx = get_test_ctx();
+        let tick = "pepe".to_string();
+        let address = "19PFYXeUuArA3vRDHh2zz8tupAYNFqjBCP".to_string();
+        let deploy_id = "01d6876703d25747bf5767f3d830548ebe09ffcade91d49e558eb9b6fd2d6d56i0";
+        let first_mint_id = "2e72578e1259b7dab363cb422ae1979ea329ffc0978c4a7552af907238db354ci0";
+        let missed_mint_id = "2e72578e1259b7dab363cb422ae1979ea329ffc0978c4a7552af907238db354ci1";
+        let transfer_id = "a8494261df7d4980af988dfc0241bb7ec95051afdbb86e3bea9c3ab055e898f3i0";
+        let mint = |amt: &str| {
+            ParsedBrc20Operation::Mint(ParsedBrc20BalanceData {
+                tick: "pepe".to_string(),
+                amt: amt.to_string(),
+            })
+        };
+        let mut operation_map: HashMap<String, ParsedBrc20Operation> = HashMap::new();
+        operation_map.insert(
+            deploy_id.to_string(),
+            ParsedBrc20Operation::Deploy(ParsedBrc20TokenDeployData {
+                tick: "pepe".to_string(),
+                display_tick: "pepe".to_string(),
+                max: "100".to_string(),
+                lim: "10".to_string(),
+                dec: "0".to_string(),
+                self_mint: false,
+            }),
+        );
+        operation_map.insert(first_mint_id.to_string(), mint("10"));
+        operation_map.insert(missed_mint_id.to_string(), mint("10"));
+        operation_map.insert(
+            transfer_id.to_string(),
+            ParsedBrc20Operation::Transfer(ParsedBrc20BalanceData {
+                tick: "pepe".to_string(),
+                amt: "20".to_string(),
+            }),
+        );
+        let self_mint_height = Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet));
+
+        let mut pg_client = pg_test_connection().await;
+        let _ = brc20_pg::migrate(&mut pg_client).await;
+        let result = {
+            let mut brc20_client = pg_pool_client(&pg_test_connection_pool()).await?;
+            let client = pg_begin(&mut brc20_client).await?;
+
+            // The sidecar streams the deploy and a first mint, and caches the resulting balance.
+            let mut sidecar_cache = Brc20MemoryCache::new(10);
+            for mut block in [
+                brc20_reveal_block(818000, 0, deploy_id),
+                brc20_reveal_block(818001, 1, first_mint_id),
+            ] {
+                index_block_and_insert_brc20_operations(
+                    &mut block,
+                    &mut operation_map,
+                    self_mint_height,
+                    Brc20Strictness::Strict,
+                    &mut sidecar_cache,
+                    &client,
+                    &ctx,
+                )
+                .await?;
+            }
+            // A block whose notification was missed is indexed by the catch-up, which has its own cache.
+            let mut catch_up_cache = Brc20MemoryCache::new(10);
+            index_block_and_insert_brc20_operations(
+                &mut brc20_reveal_block(818002, 2, missed_mint_id),
+                &mut operation_map,
+                self_mint_height,
+                Brc20Strictness::Strict,
+                &mut catch_up_cache,
+                &client,
+                &ctx,
+            )
+            .await?;
+            assert_eq!(
+                sidecar_cache
+                    .get_token_address_avail_balance(&tick, &address, &client)
+                    .await?,
+                Some(10)
+            );
+
+            // Rebuilt like the sidecar does after a catch-up, the cache sees the missed mint.
+            let mut sidecar_cache = Brc20MemoryCache::new(10);
+            let mut block = brc20_reveal_block(818003, 3, transfer_id);
+            index_block_and_insert_brc20_operations(
+                &mut block,
+                &mut operation_map,
+                self_mint_height,
+                Brc20Strictness::Strict,
+                &mut sidecar_cache,
+                &client,
+                &ctx,
+            )
+            .await?;
+            assert_eq!(
+                block.transactions[0].metadata.brc20_operation,
+                Some(Brc20Operation::Transfer(Brc20BalanceData {
+                    tick: "pepe".to_string(),
+                    amt: "20".to_string(),
+                    address: address.clone(),
+                    inscription_id: transfer_id.to_string(),
+                }))
+            );
+            assert_eq!(
+                brc20_pg::get_token_available_balance_for_address(&tick, &address, &client).await?,
+                Some(0)
+            );
+            Ok(())
+        };
+        pg_reset_db(&mut pg_client).await?;
+        result
+    }
 }
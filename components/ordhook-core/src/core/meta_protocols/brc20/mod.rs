@@ -4,6 +4,7 @@ pub mod brc20_pg;
 pub mod cache;
 pub mod index;
 pub mod models;
+pub mod modules;
 pub mod parser;
 pub mod test_utils;
 pub mod verifier;
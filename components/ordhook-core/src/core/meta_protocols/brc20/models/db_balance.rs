@@ -0,0 +1,23 @@
+use chainhook_postgres::{types::PgNumericU128, FromPgRow};
+use tokio_postgres::Row;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbBalance {
+    pub ticker: String,
+    pub address: String,
+    pub avail_balance: PgNumericU128,
+    pub trans_balance: PgNumericU128,
+    pub total_balance: PgNumericU128,
+}
+
+impl FromPgRow for DbBalance {
+    fn from_pg_row(row: &Row) -> Self {
+        DbBalance {
+            ticker: row.get("ticker"),
+            address: row.get("address"),
+            avail_balance: row.get("avail_balance"),
+            trans_balance: row.get("trans_balance"),
+            total_balance: row.get("total_balance"),
+        }
+    }
+}
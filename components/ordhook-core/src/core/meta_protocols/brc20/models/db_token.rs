@@ -21,6 +21,8 @@ pub struct DbToken {
     pub self_mint: bool,
     pub minted_supply: PgNumericU128,
     pub tx_count: i32,
+    /// Addresses with a positive overall balance.
+    pub holders: i32,
     pub timestamp: PgBigIntU32,
 }
 
@@ -42,6 +44,7 @@ impl FromPgRow for DbToken {
             self_mint: row.get("self_mint"),
             minted_supply: row.get("minted_supply"),
             tx_count: row.get("tx_count"),
+            holders: row.get("holders"),
             timestamp: row.get("timestamp"),
         }
     }
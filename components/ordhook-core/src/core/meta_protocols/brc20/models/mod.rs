@@ -1,5 +1,7 @@
+mod db_balance;
 mod db_operation;
 mod db_token;
 
+pub use db_balance::DbBalance;
 pub use db_operation::DbOperation;
 pub use db_token::DbToken;
@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chainhook_sdk::utils::Context;
+use chainhook_types::{BitcoinBlockData, BlockIdentifier, Brc20Operation};
+use reqwest::header::CONTENT_TYPE;
+use tokio::sync::mpsc::Sender;
+
+use crate::{config::Config, try_debug};
+
+use super::brc20_activation_height;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Brc20ModuleOperation {
+    /// Position of the operation in the total order of ordinal and BRC-20 operations, see `BlockEvent::first_sequence`.
+    pub sequence: Option<u64>,
+    pub tx_index: usize,
+    pub tx_id: String,
+    pub operation: Brc20Operation,
+}
+
+/// Event forwarded to BRC-20 modules. `Apply` carries the operations of a block once they are validated, `Rollback` is
+/// sent when the operations of a block are undone because of a reorg.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Brc20ModuleEvent {
+    Apply {
+        block_identifier: BlockIdentifier,
+        timestamp: u32,
+        operations: Vec<Brc20ModuleOperation>,
+    },
+    Rollback {
+        block_height: u64,
+    },
+}
+
+impl Brc20ModuleEvent {
+    pub fn apply(block: &BitcoinBlockData) -> Self {
+        let mut operations = vec![];
+        let mut offset = 0;
+        for (tx_index, tx) in block.transactions.iter().enumerate() {
+            offset += tx.metadata.ordinal_operations.len() as u64;
+            if let Some(operation) = &tx.metadata.brc20_operation {
+                operations.push(Brc20ModuleOperation {
+                    sequence: block
+                        .metadata
+                        .first_operation_sequence
+                        .map(|first| first + offset),
+                    tx_index,
+                    tx_id: tx.transaction_identifier.hash.clone(),
+                    operation: operation.clone(),
+                });
+                offset += 1;
+            }
+        }
+        Brc20ModuleEvent::Apply {
+            block_identifier: block.block_identifier.clone(),
+            timestamp: block.timestamp,
+            operations,
+        }
+    }
+}
+
+/// External module that receives validated BRC-20 operations once they are committed, so new BRC-20 semantics can be
+/// prototyped on top of the indexer. Modules run in order for every block from the BRC-20 activation height on. An error
+/// stops indexing, but the block stays committed so it is not sent again after a restart.
+#[async_trait]
+pub trait Brc20Module: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &str;
+
+    async fn handle_event(&self, event: &Brc20ModuleEvent, ctx: &Context) -> Result<(), String>;
+}
+
+/// Posts every event as JSON to a URL. Any response other than a 2xx fails the block.
+pub struct HttpBrc20Module {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl HttpBrc20Module {
+    pub fn new(url: &str) -> Self {
+        HttpBrc20Module {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Brc20Module for HttpBrc20Module {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn handle_event(&self, event: &Brc20ModuleEvent, _ctx: &Context) -> Result<(), String> {
+        let payload = serde_json::to_string(event)
+            .map_err(|e| format!("unable to serialize brc20 module event: {e}"))?;
+        let res = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(payload)
+            .send()
+            .await
+            .map_err(|e| format!("unable to reach brc20 module: {e}"))?;
+        if !res.status().is_success() {
+            return Err(format!("brc20 module returned {}", res.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Sends every event to a channel, for modules running in the same process as the indexer. Sending waits while the
+/// channel is full, so a slow receiver slows indexing down instead of missing events.
+pub struct ChannelBrc20Module(pub Sender<Brc20ModuleEvent>);
+
+#[async_trait]
+impl Brc20Module for ChannelBrc20Module {
+    fn name(&self) -> &str {
+        "channel"
+    }
+
+    async fn handle_event(&self, event: &Brc20ModuleEvent, _ctx: &Context) -> Result<(), String> {
+        self.0
+            .send(event.clone())
+            .await
+            .map_err(|_| "brc20 module channel is closed".to_string())
+    }
+}
+
+/// Module posting to `meta_protocols.brc20_modules.url`, followed by the custom ones. Modules are disabled on dry runs.
+pub fn configured_brc20_modules(config: &Config) -> Vec<Arc<dyn Brc20Module>> {
+    let mut modules: Vec<Arc<dyn Brc20Module>> = vec![];
+    if config.dry_run {
+        return modules;
+    }
+    if let Some(url) = &config.meta_protocols.brc20_modules.url {
+        modules.push(Arc::new(HttpBrc20Module::new(url)));
+    }
+    modules.extend(config.meta_protocols.brc20_modules.custom.iter().cloned());
+    modules
+}
+
+async fn send_brc20_module_event(
+    event: &Brc20ModuleEvent,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    for module in configured_brc20_modules(config) {
+        module
+            .handle_event(event, ctx)
+            .await
+            .map_err(|e| format!("{} brc20 module: {e}", module.name()))?;
+    }
+    Ok(())
+}
+
+/// Forwards the validated BRC-20 operations of a block to the configured modules.
+pub async fn forward_brc20_block_to_modules(
+    block: &BitcoinBlockData,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    if block.block_identifier.index < brc20_activation_height(&block.metadata.network) {
+        return Ok(());
+    }
+    let event = Brc20ModuleEvent::apply(block);
+    if let Brc20ModuleEvent::Apply { operations, .. } = &event {
+        try_debug!(
+            ctx,
+            "Forwarding {} BRC-20 operations at block #{} to modules",
+            operations.len(),
+            block.block_identifier.index
+        );
+    }
+    send_brc20_module_event(&event, config, ctx).await
+}
+
+/// Tells the configured modules that the BRC-20 operations of a block were rolled back.
+pub async fn forward_brc20_rollback_to_modules(
+    block_height: u64,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    send_brc20_module_event(&Brc20ModuleEvent::Rollback { block_height }, config, ctx).await
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use chainhook_types::{Brc20BalanceData, Brc20Operation};
+    use tokio::sync::mpsc::channel;
+
+    use crate::{
+        config::Config,
+        core::{
+            meta_protocols::brc20::test_utils::get_test_ctx,
+            test_builders::{TestBlockBuilder, TestTransactionBuilder},
+        },
+    };
+
+    use super::{forward_brc20_block_to_modules, Brc20ModuleEvent, ChannelBrc20Module};
+
+    #[tokio::test]
+    async fn forwards_block_operations_to_channel() {
+        let mint = Brc20Operation::Mint(Brc20BalanceData {
+            tick: "pepe".to_string(),
+            amt: "1".to_string(),
+            address: "19PFYXeUuArA3vRDHh2zz8tupAYNFqjBCP".to_string(),
+            inscription_id: "2e72578e1259b7dab363cb422ae1979ea329ffc0978c4a7552af907238db354ci0"
+                .to_string(),
+        });
+        let block = TestBlockBuilder::new()
+            .height(840_000)
+            .add_transaction(TestTransactionBuilder::new().build())
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .brc20_operation(Some(mint.clone()))
+                    .build(),
+            )
+            .build();
+        let (tx, mut rx) = channel(1);
+        let mut config = Config::devnet_default();
+        config
+            .meta_protocols
+            .brc20_modules
+            .custom
+            .push(Arc::new(ChannelBrc20Module(tx)));
+
+        forward_brc20_block_to_modules(&block, &config, &get_test_ctx())
+            .await
+            .unwrap();
+        let Some(Brc20ModuleEvent::Apply { operations, .. }) = rx.recv().await else {
+            panic!("expected an apply event");
+        };
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].tx_index, 1);
+        assert_eq!(operations[0].operation, mint);
+
+        drop(rx);
+        assert!(
+            forward_brc20_block_to_modules(&block, &config, &get_test_ctx())
+                .await
+                .is_err()
+        );
+    }
+}
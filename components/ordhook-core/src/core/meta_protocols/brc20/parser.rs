@@ -1,7 +1,7 @@
 use ord::inscription::Inscription;
 use ord::media::{Language, Media};
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Serialize)]
 pub struct ParsedBrc20TokenDeployData {
     pub tick: String,
     pub display_tick: String,
@@ -11,7 +11,7 @@ pub struct ParsedBrc20TokenDeployData {
     pub self_mint: bool,
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Serialize)]
 pub struct ParsedBrc20BalanceData {
     pub tick: String,
     pub amt: String,
@@ -23,7 +23,8 @@ impl ParsedBrc20BalanceData {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
 pub enum ParsedBrc20Operation {
     Deploy(ParsedBrc20TokenDeployData),
     Mint(ParsedBrc20BalanceData),
@@ -133,6 +133,7 @@ impl Brc20TransferBuilder {
         OrdinalInscriptionTransferData {
             ordinal_number: self.ordinal_number,
             destination: self.destination,
+            from_address: None,
             satpoint_pre_transfer: "".to_string(),
             satpoint_post_transfer: self.satpoint_post_transfer,
             post_transfer_output_value: Some(500),
@@ -1,17 +1,17 @@
 use std::collections::HashMap;
 
 use chainhook_types::{
-    BitcoinNetwork, BlockIdentifier, OrdinalInscriptionRevealData, OrdinalInscriptionTransferData,
+    BlockIdentifier, OrdinalInscriptionRevealData, OrdinalInscriptionTransferData,
     OrdinalInscriptionTransferDestination, TransactionIdentifier,
 };
 use chainhook_sdk::utils::Context;
 use deadpool_postgres::Transaction;
 
-use crate::try_debug;
+use crate::{config::Brc20Strictness, error::OrdhookError, try_debug};
 
 use super::cache::Brc20MemoryCache;
+use super::decimals_str_amount_to_u128;
 use super::parser::{amt_has_valid_decimals, ParsedBrc20Operation};
-use super::{brc20_self_mint_activation_height, decimals_str_amount_to_u128};
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct VerifiedBrc20TokenDeployData {
@@ -51,11 +51,12 @@ pub async fn verify_brc20_operation(
     operation: &ParsedBrc20Operation,
     reveal: &OrdinalInscriptionRevealData,
     block_identifier: &BlockIdentifier,
-    network: &BitcoinNetwork,
+    self_mint_activation_height: Option<u64>,
+    strictness: Brc20Strictness,
     cache: &mut Brc20MemoryCache,
     db_tx: &Transaction<'_>,
     ctx: &Context,
-) -> Result<Option<VerifiedBrc20Operation>, String> {
+) -> Result<Option<VerifiedBrc20Operation>, OrdhookError> {
     let Some(inscriber_address) = &reveal.inscriber_address else {
         try_debug!(ctx, "BRC-20: Invalid inscriber address");
         return Ok(None);
@@ -64,7 +65,7 @@ pub async fn verify_brc20_operation(
         try_debug!(ctx, "BRC-20: Empty inscriber address");
         return Ok(None);
     }
-    if reveal.inscription_number.classic < 0 {
+    if !strictness.accepts(&reveal.inscription_number) {
         try_debug!(ctx, "BRC-20: Inscription is cursed");
         return Ok(None);
     }
@@ -74,7 +75,9 @@ pub async fn verify_brc20_operation(
                 try_debug!(ctx, "BRC-20: Token {} already exists", &data.tick);
                 return Ok(None);
             }
-            if data.self_mint && block_identifier.index < brc20_self_mint_activation_height(network)
+            if data.self_mint
+                && !self_mint_activation_height
+                    .is_some_and(|height| block_identifier.index >= height)
             {
                 try_debug!(
                     ctx,
@@ -106,6 +109,16 @@ pub async fn verify_brc20_operation(
                 return Ok(None);
             };
             if data.tick.len() == 5 {
+                if !self_mint_activation_height
+                    .is_some_and(|height| block_identifier.index >= height)
+                {
+                    try_debug!(
+                        ctx,
+                        "BRC-20: Self-minted token mint {} prohibited before activation height",
+                        &data.tick
+                    );
+                    return Ok(None);
+                }
                 if reveal.parents.len() == 0 {
                     try_debug!(
                         ctx,
@@ -230,7 +243,7 @@ pub async fn verify_brc20_transfers(
         OrdinalInscriptionTransferData,
         TransactionIdentifier,
     )>,
-    String,
+    OrdhookError,
 > {
     try_debug!(
         ctx,
@@ -298,15 +311,16 @@ pub async fn verify_brc20_transfers(
 mod test {
     use chainhook_postgres::{pg_begin, pg_pool_client};
     use chainhook_types::{
-        BitcoinNetwork, BlockIdentifier, OrdinalInscriptionRevealData,
+        BitcoinNetwork, BlockIdentifier, OrdinalInscriptionNumber, OrdinalInscriptionRevealData,
         OrdinalInscriptionTransferData, OrdinalInscriptionTransferDestination,
         TransactionIdentifier,
     };
     use test_case::test_case;
 
     use crate::{
+        config::Brc20Strictness,
         core::meta_protocols::brc20::{
-            brc20_pg,
+            brc20_pg, brc20_self_mint_activation_height,
             cache::Brc20MemoryCache,
             parser::{ParsedBrc20BalanceData, ParsedBrc20Operation, ParsedBrc20TokenDeployData},
             test_utils::{get_test_ctx, Brc20RevealBuilder, Brc20TransferBuilder},
@@ -449,17 +463,103 @@ mod test {
                     hash: "00000000000000000002d8ba402150b259ddb2b30a1d32ab4a881d4653bceb5b"
                         .to_string(),
                 },
-                &BitcoinNetwork::Mainnet,
+                Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                Brc20Strictness::Strict,
                 &mut Brc20MemoryCache::new(50),
                 &client,
                 &ctx,
             )
             .await
+            .map_err(String::from)
         };
         pg_reset_db(&mut pg_client).await?;
         result
     }
 
+    #[tokio::test]
+    async fn test_brc20_verify_self_mint_deploy_when_disabled() -> Result<(), String> {
+        let ctx = get_test_ctx();
+        let mut pg_client = pg_test_connection().await;
+        let _ = brc20_pg::migrate(&mut pg_client).await;
+        let result = {
+            let mut brc20_client = pg_pool_client(&pg_test_connection_pool()).await?;
+            let client = pg_begin(&mut brc20_client).await?;
+
+            verify_brc20_operation(
+                &ParsedBrc20Operation::Deploy(ParsedBrc20TokenDeployData {
+                    tick: "$pepe".to_string(),
+                    display_tick: "$pepe".to_string(),
+                    max: "21000000".to_string(),
+                    lim: "1000".to_string(),
+                    dec: "18".to_string(),
+                    self_mint: true,
+                }),
+                &Brc20RevealBuilder::new().build(),
+                &BlockIdentifier {
+                    index: 840000,
+                    hash: "00000000000000000002d8ba402150b259ddb2b30a1d32ab4a881d4653bceb5b"
+                        .to_string(),
+                },
+                None,
+                Brc20Strictness::Strict,
+                &mut Brc20MemoryCache::new(50),
+                &client,
+                &ctx,
+            )
+            .await
+        };
+        pg_reset_db(&mut pg_client).await?;
+        assert_eq!(result?, None);
+        Ok(())
+    }
+
+    #[test_case(Brc20Strictness::Strict, -1, -1 => false; "strict with cursed")]
+    #[test_case(Brc20Strictness::Strict, -1, 100 => false; "strict with vindicated")]
+    #[test_case(Brc20Strictness::Vindicated, -1, -1 => false; "vindicated with cursed")]
+    #[test_case(Brc20Strictness::Vindicated, -1, 100 => true; "vindicated with vindicated")]
+    #[test_case(Brc20Strictness::Permissive, -1, -1 => true; "permissive with cursed")]
+    #[tokio::test]
+    async fn test_brc20_verify_deploy_with_strictness(
+        strictness: Brc20Strictness,
+        classic: i64,
+        jubilee: i64,
+    ) -> bool {
+        let ctx = get_test_ctx();
+        let mut pg_client = pg_test_connection().await;
+        let _ = brc20_pg::migrate(&mut pg_client).await;
+        let mut reveal = Brc20RevealBuilder::new().build();
+        reveal.inscription_number = OrdinalInscriptionNumber { classic, jubilee };
+        let result = {
+            let mut brc20_client = pg_pool_client(&pg_test_connection_pool()).await.unwrap();
+            let client = pg_begin(&mut brc20_client).await.unwrap();
+
+            verify_brc20_operation(
+                &ParsedBrc20Operation::Deploy(ParsedBrc20TokenDeployData {
+                    tick: "pepe".to_string(),
+                    display_tick: "pepe".to_string(),
+                    max: "21000000".to_string(),
+                    lim: "1000".to_string(),
+                    dec: "18".to_string(),
+                    self_mint: false,
+                }),
+                &reveal,
+                &BlockIdentifier {
+                    index: 830000,
+                    hash: "00000000000000000002d8ba402150b259ddb2b30a1d32ab4a881d4653bceb5b"
+                        .to_string(),
+                },
+                Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                strictness,
+                &mut Brc20MemoryCache::new(50),
+                &client,
+                &ctx,
+            )
+            .await
+        };
+        pg_reset_db(&mut pg_client).await.unwrap();
+        result.unwrap().is_some()
+    }
+
     #[test_case(
         ParsedBrc20Operation::Deploy(ParsedBrc20TokenDeployData {
             tick: "pepe".to_string(),
@@ -562,12 +662,14 @@ mod test {
                 &op,
                 &reveal,
                 &block,
-                &BitcoinNetwork::Mainnet,
+                Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                Brc20Strictness::Strict,
                 &mut cache,
                 &client,
                 &ctx,
             )
             .await
+            .map_err(String::from)
         };
         pg_reset_db(&mut pg_client).await?;
         result
@@ -649,12 +751,84 @@ mod test {
                 &op,
                 &reveal,
                 &block,
-                &BitcoinNetwork::Mainnet,
+                Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                Brc20Strictness::Strict,
+                &mut cache,
+                &client,
+                &ctx,
+            )
+            .await
+            .map_err(String::from)
+        };
+        pg_reset_db(&mut pg_client).await?;
+        result
+    }
+
+    #[test_case(None => Ok(None); "with self mint disabled")]
+    #[test_case(Some(850000) => Ok(None); "before activation height")]
+    #[test_case(Some(840000) => Ok(Some(VerifiedBrc20Operation::TokenMint(VerifiedBrc20BalanceData {
+            tick: "$pepe".to_string(),
+            amt: 100_000000000000000000,
+            address: "324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp".to_string()
+        }))); "at activation height")]
+    #[tokio::test]
+    async fn test_brc20_verify_self_mint_token_mint_activation(
+        self_mint_activation_height: Option<u64>,
+    ) -> Result<Option<VerifiedBrc20Operation>, String> {
+        let ctx = get_test_ctx();
+        let mut pg_client = pg_test_connection().await;
+        let _ = brc20_pg::migrate(&mut pg_client).await;
+        let result = {
+            let mut brc20_client = pg_pool_client(&pg_test_connection_pool()).await?;
+            let client = pg_begin(&mut brc20_client).await?;
+
+            let block = BlockIdentifier {
+                index: 840000,
+                hash: "00000000000000000002d8ba402150b259ddb2b30a1d32ab4a881d4653bceb5b"
+                    .to_string(),
+            };
+            let tx = TransactionIdentifier {
+                hash: "8c8e37ce3ddd869767f8d839d16acc7ea4ec9dd7e3c73afd42a0abb859d7d391"
+                    .to_string(),
+            };
+            let mut cache = Brc20MemoryCache::new(10);
+            cache.insert_token_deploy(
+                &VerifiedBrc20TokenDeployData {
+                    tick: "$pepe".to_string(),
+                    display_tick: "$pepe".to_string(),
+                    max: 21000000_000000000000000000,
+                    lim: 1000_000000000000000000,
+                    dec: 18,
+                    address: "324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp".to_string(),
+                    self_mint: true,
+                },
+                &Brc20RevealBuilder::new().inscription_number(0).build(),
+                &block,
+                0,
+                &tx,
+                0,
+            )?;
+            verify_brc20_operation(
+                &ParsedBrc20Operation::Mint(ParsedBrc20BalanceData {
+                    tick: "$pepe".to_string(),
+                    amt: "100.00".to_string(),
+                }),
+                &Brc20RevealBuilder::new()
+                    .inscription_number(1)
+                    .parents(vec![
+                        "9bb2314d666ae0b1db8161cb373fcc1381681f71445c4e0335aa80ea9c37fcddi0"
+                            .to_string(),
+                    ])
+                    .build(),
+                &block,
+                self_mint_activation_height,
+                Brc20Strictness::Strict,
                 &mut cache,
                 &client,
                 &ctx,
             )
             .await
+            .map_err(String::from)
         };
         pg_reset_db(&mut pg_client).await?;
         result
@@ -726,12 +900,14 @@ mod test {
                 &op,
                 &reveal,
                 &block,
-                &BitcoinNetwork::Mainnet,
+                Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                Brc20Strictness::Strict,
                 &mut cache,
                 &client,
                 &ctx,
             )
             .await
+            .map_err(String::from)
         };
         pg_reset_db(&mut pg_client).await?;
         result
@@ -806,12 +982,14 @@ mod test {
                 &op,
                 &reveal,
                 &block,
-                &BitcoinNetwork::Mainnet,
+                Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                Brc20Strictness::Strict,
                 &mut cache,
                 &client,
                 &ctx,
             )
             .await
+            .map_err(String::from)
         };
         pg_reset_db(&mut pg_client).await?;
         result
@@ -970,12 +1148,14 @@ mod test {
                     &op,
                     &reveal,
                     &block,
-                    &BitcoinNetwork::Mainnet,
+                    Some(brc20_self_mint_activation_height(&BitcoinNetwork::Mainnet)),
+                    Brc20Strictness::Strict,
                     &mut cache,
                     &client,
                     &ctx,
                 )
                 .await
+                .map_err(String::from)
             };
         pg_reset_db(&mut pg_client).await?;
         result
@@ -1,7 +1,10 @@
+pub mod block_fixtures;
 pub mod meta_protocols;
 pub mod pipeline;
 pub mod protocol;
-#[cfg(test)]
+#[cfg(any(test, feature = "test-kit"))]
+pub mod reorg_simulator;
+#[cfg(any(test, feature = "test-kit"))]
 pub mod test_builders;
 
 use chainhook_postgres::pg_pool_client;
@@ -164,7 +167,7 @@ pub async fn should_sync_ordinals_db(
     };
 
     // TODO: Gracefully handle Regtest, Testnet and Signet
-    let end_block = bitcoind_get_block_height(&config.network, ctx);
+    let end_block = bitcoind_get_block_height(&config.network, ctx).await;
     let (mut end_block, speed) = if start_block < 200_000 {
         (end_block.min(200_000), 10_000)
     } else if start_block < 550_000 {
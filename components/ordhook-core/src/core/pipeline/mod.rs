@@ -1,25 +1,41 @@
 pub mod processors;
+pub mod tip_lane;
 
 use chainhook_sdk::observer::BitcoinConfig;
 use chainhook_sdk::utils::Context;
-use chainhook_types::BitcoinBlockData;
-use crossbeam_channel::bounded;
+use chainhook_types::{BitcoinBlockData, BitcoinNetwork};
+use crossbeam_channel::{bounded, TryRecvError};
+use rocksdb::DB;
 use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
 use std::thread::{sleep, JoinHandle};
 use std::time::Duration;
 use tokio::task::JoinSet;
 
+use self::tip_lane::TipPriorityLane;
 use crate::config::Config;
 use crate::db::cursor::BlockBytesCursor;
-use crate::{try_debug, try_info};
+use crate::db::raw_transactions::{
+    extract_reveal_transaction_offsets, insert_raw_transaction, shared_raw_transactions_db_rw,
+};
+use crate::utils::pin_current_thread_to_cores;
+use crate::{try_debug, try_info, try_warn};
 
 use chainhook_sdk::indexer::bitcoin::{
-    build_http_client, parse_downloaded_block, standardize_bitcoin_block,
-    try_download_block_bytes_with_retry,
+    build_http_client, parse_downloaded_block, retrieve_block_hashes_with_retry,
+    standardize_bitcoin_block, try_download_block_bytes_by_hash_with_retry,
 };
 
+/// Number of block hashes resolved per `getblockhash` JSON-RPC batch.
+const BLOCK_HASHES_BATCH_SIZE: usize = 100;
+
+/// Block processed by a compression worker: `(height, hash, standardized block, compacted bytes)`. The standardized block
+/// is only built for blocks that need to be indexed.
+type CompressedBlock = (u64, String, Option<BitcoinBlockData>, Vec<u8>);
+
 pub enum PostProcessorCommand {
-    ProcessBlocks(Vec<(u64, Vec<u8>)>, Vec<BitcoinBlockData>),
+    /// Compacted blocks as `(height, hash, bytes)` tuples, followed by the standardized blocks to index.
+    ProcessBlocks(Vec<(u64, String, Vec<u8>)>, Vec<BitcoinBlockData>),
     Terminate,
 }
 
@@ -34,20 +50,122 @@ pub struct PostProcessorController {
     pub thread_handle: JoinHandle<()>,
 }
 
+/// Sends raw block bytes to the next worker of the compression pool, waiting for room if every worker queue is full.
+fn dispatch_block_bytes(
+    tx_thread_pool: &Vec<crossbeam_channel::Sender<Option<Vec<u8>>>>,
+    round_robin_worker_thread_index: &mut usize,
+    block_bytes: Vec<u8>,
+) {
+    loop {
+        let res = tx_thread_pool[*round_robin_worker_thread_index].send(Some(block_bytes.clone()));
+        *round_robin_worker_thread_index =
+            (*round_robin_worker_thread_index + 1) % tx_thread_pool.len();
+        if res.is_ok() {
+            break;
+        }
+        sleep(Duration::from_millis(500));
+    }
+}
+
+/// Pops the next block height that needs to be downloaded from bitcoind. Blocks already fetched by the tip lane are
+/// dispatched to the compression pool directly.
+fn pop_next_block_height_to_download(
+    block_heights: &mut VecDeque<u64>,
+    tip_lane: Option<&TipPriorityLane>,
+    tx_thread_pool: &Vec<crossbeam_channel::Sender<Option<Vec<u8>>>>,
+    round_robin_worker_thread_index: &mut usize,
+) -> Option<u64> {
+    while let Some(block_height) = block_heights.pop_front() {
+        match tip_lane.and_then(|lane| lane.take_block(block_height)) {
+            Some(block_bytes) => {
+                dispatch_block_bytes(tx_thread_pool, round_robin_worker_thread_index, block_bytes)
+            }
+            None => return Some(block_height),
+        }
+    }
+    None
+}
+
+/// Parses block bytes downloaded from bitcoind, indexes its reveal transactions when the raw transactions index is
+/// enabled and compacts it.
+fn compress_downloaded_block(
+    block_bytes: Vec<u8>,
+    start_sequencing_blocks_at_height: u64,
+    bitcoin_network: &BitcoinNetwork,
+    raw_transactions_db: Option<&DB>,
+    ctx: &Context,
+) -> Result<CompressedBlock, String> {
+    let raw_block_data = parse_downloaded_block(block_bytes)?;
+    if let Some(raw_transactions_db) = raw_transactions_db {
+        for (txid, offset) in extract_reveal_transaction_offsets(&raw_block_data) {
+            if let Err(e) = insert_raw_transaction(
+                &txid,
+                raw_block_data.height as u32,
+                offset,
+                raw_transactions_db,
+            ) {
+                try_warn!(ctx, "{e}");
+            }
+        }
+    }
+    let block_height = raw_block_data.height as u64;
+    let compressed_block = BlockBytesCursor::from_full_block(&raw_block_data)
+        .map_err(|e| format!("unable to compress block #{block_height}: {e}"))?;
+    let block_hash = raw_block_data.hash.clone();
+    let block_data = if block_height >= start_sequencing_blocks_at_height {
+        let block = standardize_bitcoin_block(raw_block_data, bitcoin_network, ctx)
+            .map_err(|(e, _)| format!("unable to standardize block #{block_height}: {e}"))?;
+        Some(block)
+    } else {
+        None
+    };
+    Ok((block_height, block_hash, block_data, compressed_block))
+}
+
+/// Returns the hash of `block_height`. On a cache miss, the hashes of the next heights queued for download are resolved
+/// in the same batch call, so the download loop does not pay one `getblockhash` round trip per block.
+async fn get_block_hash_to_download(
+    block_height: u64,
+    block_heights: &VecDeque<u64>,
+    block_hashes: &mut HashMap<u64, String>,
+    bitcoin_config: &BitcoinConfig,
+    ctx: &Context,
+) -> Result<String, String> {
+    if let Some(block_hash) = block_hashes.remove(&block_height) {
+        return Ok(block_hash);
+    }
+    let mut batch = vec![block_height];
+    batch.extend(
+        block_heights
+            .iter()
+            .filter(|height| !block_hashes.contains_key(height))
+            .take(BLOCK_HASHES_BATCH_SIZE - 1),
+    );
+    let http_client = build_http_client();
+    let hashes =
+        retrieve_block_hashes_with_retry(&http_client, &batch, bitcoin_config, ctx).await?;
+    block_hashes.extend(batch.into_iter().zip(hashes));
+    block_hashes
+        .remove(&block_height)
+        .ok_or(format!("unable to retrieve block hash for #{block_height}"))
+}
+
 /// Downloads blocks from bitcoind's RPC interface and pushes them to a `PostProcessorController` so they can be indexed or
-/// ingested as needed.
+/// ingested as needed. Blocks already prefetched by a `TipPriorityLane` skip the RPC queue.
 pub async fn bitcoind_download_blocks(
     config: &Config,
     blocks: Vec<u64>,
     start_sequencing_blocks_at_height: u64,
     blocks_post_processor: &PostProcessorController,
     speed: usize,
+    tip_lane: Option<&TipPriorityLane>,
     ctx: &Context,
 ) -> Result<(), String> {
     let bitcoin_config = BitcoinConfig {
         username: config.network.bitcoind_rpc_username.clone(),
         password: config.network.bitcoind_rpc_password.clone(),
-        rpc_url: config.network.bitcoind_rpc_url.clone(),
+        cookie_path: config.network.bitcoind_rpc_cookie_path.clone(),
+        rpc_endpoints: config.network.bitcoind_rpc_endpoints.clone(),
         network: config.network.bitcoin_network.clone(),
         bitcoin_block_signaling: config.network.bitcoin_block_signaling.clone(),
     };
@@ -63,9 +181,11 @@ pub async fn bitcoind_download_blocks(
 
     let mut set = JoinSet::new();
 
-    let start_block = *blocks.first().expect("no blocks to pipeline");
-    let end_block = *blocks.last().expect("no blocks to pipeline");
+    let (Some(&start_block), Some(&end_block)) = (blocks.first(), blocks.last()) else {
+        return Err("no blocks to pipeline".to_string());
+    };
     let mut block_heights = VecDeque::from(blocks);
+    let mut block_hashes = HashMap::new();
 
     // All the requests are being processed on the same thread.
     // As soon as we are getting the bytes back from wire, the
@@ -82,24 +202,13 @@ pub async fn bitcoind_download_blocks(
     // Start blocking networking when each worker has a backlog of 8 blocks seems reasonable.
     let worker_queue_size = 2;
 
-    for _ in 0..config.resources.bitcoind_rpc_threads {
-        if let Some(block_height) = block_heights.pop_front() {
-            let config = moved_config.clone();
-            let ctx = moved_ctx.clone();
-            let http_client = moved_http_client.clone();
-            // We interleave the initial requests to avoid DDOSing bitcoind from the get go.
-            sleep(Duration::from_millis(500));
-            set.spawn(try_download_block_bytes_with_retry(
-                http_client,
-                block_height,
-                config,
-                ctx,
-            ));
-        }
-    }
-
     let moved_ctx: Context = ctx.clone();
     let moved_bitcoin_network = bitcoin_config.network.clone();
+    let raw_transactions_db = if config.storage.raw_transactions_index {
+        Some(shared_raw_transactions_db_rw(config, ctx)?)
+    } else {
+        None
+    };
 
     let mut tx_thread_pool = vec![];
     let mut rx_thread_pool = vec![];
@@ -115,30 +224,20 @@ pub async fn bitcoind_download_blocks(
         let block_compressed_tx_moved = block_compressed_tx.clone();
         let moved_ctx: Context = moved_ctx.clone();
         let moved_bitcoin_network = moved_bitcoin_network.clone();
+        let moved_cores = config.resources.block_compression_cores.clone();
+        let moved_raw_transactions_db = raw_transactions_db.clone();
 
         let handle = hiro_system_kit::thread_named("Block data compression")
             .spawn(move || {
+                pin_current_thread_to_cores(&moved_cores, thread_index, &moved_ctx);
                 while let Ok(Some(block_bytes)) = rx.recv() {
-                    let raw_block_data =
-                        parse_downloaded_block(block_bytes).expect("unable to parse block");
-                    let compressed_block = BlockBytesCursor::from_full_block(&raw_block_data)
-                        .expect("unable to compress block");
-                    let block_height = raw_block_data.height as u64;
-                    let block_data = if block_height >= start_sequencing_blocks_at_height {
-                        let block = standardize_bitcoin_block(
-                            raw_block_data,
-                            &moved_bitcoin_network,
-                            &moved_ctx,
-                        )
-                        .expect("unable to deserialize block");
-                        Some(block)
-                    } else {
-                        None
-                    };
-                    let _ = block_compressed_tx_moved.send(Some((
-                        block_height,
-                        block_data,
-                        compressed_block,
+                    // A block that can't be processed stops the pipeline, the dispatcher reports the error.
+                    let _ = block_compressed_tx_moved.send(Some(compress_downloaded_block(
+                        block_bytes,
+                        start_sequencing_blocks_at_height,
+                        &moved_bitcoin_network,
+                        moved_raw_transactions_db.as_deref(),
+                        &moved_ctx,
                     )));
                 }
                 try_debug!(moved_ctx, "Exiting processing thread {thread_index}");
@@ -164,23 +263,33 @@ pub async fn bitcoind_download_blocks(
                         "#{blocks_processed} blocks successfully sent to processor"
                     );
                     let _ = blocks_post_processor_commands_tx.send(PostProcessorCommand::Terminate);
-                    break;
+                    return Ok(());
                 }
 
                 // Dequeue all the blocks available
                 let mut new_blocks = vec![];
-                while let Ok(message) = block_compressed_rx.try_recv() {
-                    match message {
-                        Some((block_height, block, compacted_block)) => {
-                            new_blocks.push((block_height, block, compacted_block));
+                loop {
+                    match block_compressed_rx.try_recv() {
+                        Ok(Some(Ok(compressed_block))) => {
+                            new_blocks.push(compressed_block);
                             // Max batch size: 10_000 blocks
                             if new_blocks.len() >= 10_000 {
                                 break;
                             }
                         }
-                        None => {
+                        Ok(None) | Err(TryRecvError::Empty) => {
                             break;
                         }
+                        Ok(Some(Err(e))) => {
+                            let _ = blocks_post_processor_commands_tx
+                                .send(PostProcessorCommand::Terminate);
+                            return Err(e);
+                        }
+                        Err(TryRecvError::Disconnected) => {
+                            let _ = blocks_post_processor_commands_tx
+                                .send(PostProcessorCommand::Terminate);
+                            return Err("block pipeline disconnected".to_string());
+                        }
                     }
                 }
 
@@ -195,11 +304,16 @@ pub async fn bitcoind_download_blocks(
                 }
 
                 let mut ooo_compacted_blocks = vec![];
-                for (block_height, block_opt, compacted_block) in new_blocks.into_iter() {
+                for (block_height, block_hash, block_opt, compacted_block) in new_blocks.into_iter()
+                {
                     if let Some(block) = block_opt {
-                        inbox.insert(block_height, (block, compacted_block.to_vec()));
+                        inbox.insert(block_height, (block, block_hash, compacted_block.to_vec()));
                     } else {
-                        ooo_compacted_blocks.push((block_height, compacted_block.to_vec()));
+                        ooo_compacted_blocks.push((
+                            block_height,
+                            block_hash,
+                            compacted_block.to_vec(),
+                        ));
                     }
                 }
 
@@ -218,8 +332,8 @@ pub async fn bitcoind_download_blocks(
                 // In order processing: construct the longest sequence of known blocks
                 let mut compacted_blocks = vec![];
                 let mut blocks = vec![];
-                while let Some((block, compacted_block)) = inbox.remove(&inbox_cursor) {
-                    compacted_blocks.push((inbox_cursor, compacted_block));
+                while let Some((block, block_hash, compacted_block)) = inbox.remove(&inbox_cursor) {
+                    compacted_blocks.push((inbox_cursor, block_hash, compacted_block));
                     blocks.push(block);
                     inbox_cursor += 1;
                 }
@@ -236,45 +350,88 @@ pub async fn bitcoind_download_blocks(
                     stop_runloop = true;
                 }
             }
-            ()
         })
         .expect("unable to spawn thread");
 
     let mut round_robin_worker_thread_index = 0;
-    while let Some(res) = set.join_next().await {
-        let block = res
-            .expect("unable to retrieve block")
-            .expect("unable to deserialize block");
-
-        loop {
-            let res = tx_thread_pool[round_robin_worker_thread_index].send(Some(block.clone()));
-            round_robin_worker_thread_index = (round_robin_worker_thread_index + 1)
-                % thread_pool_network_response_processing_capacity;
-            if res.is_ok() {
-                break;
+    let download_result: Result<(), String> = async {
+        for _ in 0..config.resources.bitcoind_rpc_threads {
+            if let Some(block_height) = pop_next_block_height_to_download(
+                &mut block_heights,
+                tip_lane,
+                &tx_thread_pool,
+                &mut round_robin_worker_thread_index,
+            ) {
+                let block_hash = get_block_hash_to_download(
+                    block_height,
+                    &block_heights,
+                    &mut block_hashes,
+                    &bitcoin_config,
+                    ctx,
+                )
+                .await?;
+                let config = moved_config.clone();
+                let ctx = moved_ctx.clone();
+                let http_client = moved_http_client.clone();
+                // We interleave the initial requests to avoid DDOSing bitcoind from the get go.
+                sleep(Duration::from_millis(500));
+                set.spawn(try_download_block_bytes_by_hash_with_retry(
+                    http_client,
+                    block_hash,
+                    config,
+                    ctx,
+                ));
             }
-            sleep(Duration::from_millis(500));
         }
 
-        if let Some(block_height) = block_heights.pop_front() {
-            let config = moved_config.clone();
-            let ctx = ctx.clone();
-            let http_client = moved_http_client.clone();
-            set.spawn(try_download_block_bytes_with_retry(
-                http_client,
-                block_height,
-                config,
-                ctx,
-            ));
+        while let Some(res) = set.join_next().await {
+            // The dispatcher only stops early when a block could not be processed.
+            if storage_thread.is_finished() {
+                break;
+            }
+            let block = res.map_err(|e| format!("unable to retrieve block: {e}"))??;
+
+            dispatch_block_bytes(&tx_thread_pool, &mut round_robin_worker_thread_index, block);
+
+            if let Some(block_height) = pop_next_block_height_to_download(
+                &mut block_heights,
+                tip_lane,
+                &tx_thread_pool,
+                &mut round_robin_worker_thread_index,
+            ) {
+                let block_hash = get_block_hash_to_download(
+                    block_height,
+                    &block_heights,
+                    &mut block_hashes,
+                    &bitcoin_config,
+                    ctx,
+                )
+                .await?;
+                let config = moved_config.clone();
+                let ctx = ctx.clone();
+                let http_client = moved_http_client.clone();
+                set.spawn(try_download_block_bytes_by_hash_with_retry(
+                    http_client,
+                    block_hash,
+                    config,
+                    ctx,
+                ));
+            }
         }
+        Ok(())
+    }
+    .await;
+    if let Err(e) = &download_result {
+        // Lets the dispatcher terminate the post processor so the pipeline can be torn down.
+        let _ = block_compressed_tx.send(Some(Err(e.clone())));
+    } else {
+        try_debug!(
+            ctx,
+            "Pipeline successfully fed with sequence of blocks ({} to {})",
+            start_block,
+            end_block
+        );
     }
-
-    try_debug!(
-        ctx,
-        "Pipeline successfully fed with sequence of blocks ({} to {})",
-        start_block,
-        end_block
-    );
 
     for tx in tx_thread_pool.iter() {
         let _ = tx.send(None);
@@ -288,18 +445,20 @@ pub async fn bitcoind_download_blocks(
 
     try_debug!(ctx, "Pipeline successfully terminated");
 
-    loop {
-        if let Ok(signal) = blocks_post_processor.events_rx.recv() {
-            match signal {
-                PostProcessorEvent::Terminated | PostProcessorEvent::Expired => break,
-            }
+    while let Ok(signal) = blocks_post_processor.events_rx.recv() {
+        match signal {
+            PostProcessorEvent::Terminated | PostProcessorEvent::Expired => break,
         }
     }
 
     let _ = block_compressed_tx.send(None);
 
-    let _ = storage_thread.join();
+    let dispatch_result = storage_thread
+        .join()
+        .map_err(|_| "block processor dispatcher panicked".to_string())?;
     let _ = set.shutdown();
+    download_result?;
+    dispatch_result?;
 
     try_info!(
         ctx,
@@ -71,16 +71,17 @@ pub fn start_block_archiving_processor(
 }
 
 pub fn store_compacted_blocks(
-    mut compacted_blocks: Vec<(u64, Vec<u8>)>,
+    mut compacted_blocks: Vec<(u64, String, Vec<u8>)>,
     update_tip: bool,
     blocks_db_rw: &DB,
     ctx: &Context,
 ) {
-    compacted_blocks.sort_by(|(a, _), (b, _)| a.cmp(b));
+    compacted_blocks.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
 
-    for (block_height, compacted_block) in compacted_blocks.into_iter() {
+    for (block_height, block_hash, compacted_block) in compacted_blocks.into_iter() {
         insert_entry_in_blocks(
             block_height as u32,
+            &block_hash,
             &compacted_block,
             update_tip,
             &blocks_db_rw,
@@ -11,6 +11,7 @@ use chainhook_types::{BitcoinBlockData, TransactionIdentifier};
 use crossbeam_channel::TryRecvError;
 
 use dashmap::DashMap;
+use deadpool_postgres::Transaction;
 use fxhash::FxHasher;
 use std::hash::BuildHasherDefault;
 
@@ -20,6 +21,7 @@ use crate::{
             brc20_pg,
             cache::{brc20_new_cache, Brc20MemoryCache},
             index::index_block_and_insert_brc20_operations,
+            modules::{forward_brc20_block_to_modules, forward_brc20_rollback_to_modules},
         },
         pipeline::processors::block_archiving::store_compacted_blocks,
         protocol::{
@@ -33,9 +35,16 @@ use crate::{
             sequence_cursor::SequenceCursor,
         },
     },
-    db::{blocks::open_blocks_db_with_retry, cursor::TransactionBytesCursor, ordinals_pg},
-    service::PgConnectionPools,
-    try_crit, try_debug, try_info,
+    db::{
+        blocks::open_blocks_db_with_retry, cursor::TransactionBytesCursor, ordinals_pg,
+        pg_commit_unless_dry_run,
+    },
+    error::{BlockErrorContext, IndexingStage, OrdhookError},
+    service::{
+        address_clusters::cluster_block_addresses, block_events::count_sequenced_operations,
+        content_policy::apply_content_policies, sinks::configured_event_sinks, PgConnectionPools,
+    },
+    try_crit, try_debug, try_info, try_warn,
     utils::monitoring::PrometheusMonitoring,
 };
 
@@ -126,7 +135,7 @@ pub fn start_inscription_indexing_processor(
                     {
                         Ok(blocks) => blocks,
                         Err(e) => {
-                            try_crit!(ctx, "Error indexing blocks: {e}");
+                            report_indexing_error(&e, &prometheus, &ctx);
                             std::process::exit(1);
                         }
                     };
@@ -158,32 +167,64 @@ async fn process_blocks(
     config: &Config,
     pg_pools: &PgConnectionPools,
     ctx: &Context,
-) -> Result<Vec<BitcoinBlockData>, String> {
+) -> Result<Vec<BitcoinBlockData>, OrdhookError> {
     let mut cache_l1 = BTreeMap::new();
     let mut updated_blocks = vec![];
 
-    for _cursor in 0..next_blocks.len() {
-        let mut block = next_blocks.remove(0);
-
-        index_block(
-            &mut block,
-            &next_blocks,
-            sequence_cursor,
-            &mut cache_l1,
-            cache_l2,
-            brc20_cache.as_mut(),
-            prometheus,
-            config,
-            pg_pools,
-            ctx,
-        )
-        .await?;
+    while !next_blocks.is_empty() {
+        let batch_size = config.storage.blocks_per_commit.clamp(1, next_blocks.len());
+        let first_block_height = next_blocks[0].block_identifier.index;
 
-        updated_blocks.push(block);
+        let mut ord_client = pg_pool_client(&pg_pools.ordinals)
+            .await
+            .at_block(first_block_height, IndexingStage::OrdinalsWrite)?;
+        let ord_tx = pg_begin(&mut ord_client)
+            .await
+            .at_block(first_block_height, IndexingStage::OrdinalsWrite)?;
+        let mut brc20_client = match (brc20_cache.is_some(), &pg_pools.brc20) {
+            (true, Some(brc20_pool)) => Some(
+                pg_pool_client(brc20_pool)
+                    .await
+                    .at_block(first_block_height, IndexingStage::Brc20)?,
+            ),
+            _ => None,
+        };
+        let brc20_tx = match brc20_client.as_mut() {
+            Some(brc20_client) => Some(
+                pg_begin(brc20_client)
+                    .await
+                    .at_block(first_block_height, IndexingStage::Brc20)?,
+            ),
+            None => None,
+        };
+
+        let mut batch = vec![];
+        for _ in 0..batch_size {
+            let mut block = next_blocks.remove(0);
+            index_block_in_transactions(
+                &mut block,
+                &next_blocks,
+                sequence_cursor,
+                &mut cache_l1,
+                cache_l2,
+                brc20_cache.as_mut().zip(brc20_tx.as_ref()),
+                prometheus,
+                config,
+                &ord_tx,
+                ctx,
+            )
+            .await?;
+            batch.push(block);
+        }
+        let brc20_enabled = brc20_tx.is_some();
+        commit_indexed_blocks(&batch, ord_tx, brc20_tx, prometheus, config, pg_pools, ctx).await?;
+        publish_committed_blocks(&batch, brc20_enabled, config, pg_pools, ctx).await?;
+        updated_blocks.extend(batch);
     }
     Ok(updated_blocks)
 }
 
+/// Indexes and commits a single block, as done for blocks received at the chain tip.
 pub async fn index_block(
     block: &mut BitcoinBlockData,
     next_blocks: &Vec<BitcoinBlockData>,
@@ -195,7 +236,78 @@ pub async fn index_block(
     config: &Config,
     pg_pools: &PgConnectionPools,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<(), OrdhookError> {
+    let block_height = block.block_identifier.index;
+    let mut ord_client = pg_pool_client(&pg_pools.ordinals)
+        .await
+        .at_block(block_height, IndexingStage::OrdinalsWrite)?;
+    let ord_tx = pg_begin(&mut ord_client)
+        .await
+        .at_block(block_height, IndexingStage::OrdinalsWrite)?;
+    let mut brc20_client = match (brc20_cache.is_some(), &pg_pools.brc20) {
+        (true, Some(brc20_pool)) => Some(
+            pg_pool_client(brc20_pool)
+                .await
+                .at_block(block_height, IndexingStage::Brc20)?,
+        ),
+        _ => None,
+    };
+    let brc20_tx = match brc20_client.as_mut() {
+        Some(brc20_client) => Some(
+            pg_begin(brc20_client)
+                .await
+                .at_block(block_height, IndexingStage::Brc20)?,
+        ),
+        None => None,
+    };
+
+    index_block_in_transactions(
+        block,
+        next_blocks,
+        sequence_cursor,
+        cache_l1,
+        cache_l2,
+        brc20_cache.zip(brc20_tx.as_ref()),
+        prometheus,
+        config,
+        &ord_tx,
+        ctx,
+    )
+    .await?;
+    let brc20_enabled = brc20_tx.is_some();
+    commit_indexed_blocks(
+        std::slice::from_ref(block),
+        ord_tx,
+        brc20_tx,
+        prometheus,
+        config,
+        pg_pools,
+        ctx,
+    )
+    .await?;
+    publish_committed_blocks(
+        std::slice::from_ref(block),
+        brc20_enabled,
+        config,
+        pg_pools,
+        ctx,
+    )
+    .await
+}
+
+/// Writes the ordinals and BRC-20 data of a block to the given transactions, which are committed by the caller.
+async fn index_block_in_transactions(
+    block: &mut BitcoinBlockData,
+    next_blocks: &Vec<BitcoinBlockData>,
+    sequence_cursor: &mut SequenceCursor,
+    cache_l1: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
+    cache_l2: &Arc<DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>>>,
+    brc20: Option<(&mut Brc20MemoryCache, &Transaction<'_>)>,
+    prometheus: &PrometheusMonitoring,
+    config: &Config,
+    ord_tx: &Transaction<'_>,
+    ctx: &Context,
+) -> Result<(), OrdhookError> {
     let stopwatch = std::time::Instant::now();
     let block_height = block.block_identifier.index;
     try_info!(ctx, "Indexing block #{block_height}");
@@ -207,61 +319,95 @@ pub async fn index_block(
         sequence_cursor.reset();
     }
 
-    {
-        let mut ord_client = pg_pool_client(&pg_pools.ordinals).await?;
-        let ord_tx = pg_begin(&mut ord_client).await?;
+    // Parsed BRC20 ops will be deposited here for this block.
+    let mut brc20_operation_map = HashMap::new();
+    parse_inscriptions_in_standardized_block(block, &mut brc20_operation_map, config, &ctx);
 
-        // Parsed BRC20 ops will be deposited here for this block.
-        let mut brc20_operation_map = HashMap::new();
-        parse_inscriptions_in_standardized_block(block, &mut brc20_operation_map, config, &ctx);
-
-        let has_inscription_reveals = parallelize_inscription_data_computations(
-            &block,
-            &next_blocks,
+    let has_inscription_reveals = parallelize_inscription_data_computations(
+        &block,
+        &next_blocks,
+        cache_l1,
+        cache_l2,
+        config,
+        ctx,
+    )
+    .at_block(block_height, IndexingStage::Traversals)?;
+    if has_inscription_reveals {
+        update_block_inscriptions_with_consensus_sequence_data(
+            block,
+            sequence_cursor,
             cache_l1,
-            cache_l2,
-            config,
+            ord_tx,
             ctx,
-        )?;
-        if has_inscription_reveals {
-            update_block_inscriptions_with_consensus_sequence_data(block, sequence_cursor, cache_l1, &ord_tx, ctx)
-                .await?;
-        }
-        augment_block_with_transfers(block, &ord_tx, ctx).await?;
-
-        // Write data
-        ordinals_pg::insert_block(block, &ord_tx).await?;
-
-        // BRC-20
-        if let (Some(brc20_cache), Some(brc20_pool)) = (brc20_cache, &pg_pools.brc20) {
-            let mut brc20_client = pg_pool_client(brc20_pool).await?;
-            let brc20_tx = pg_begin(&mut brc20_client).await?;
-
-            index_block_and_insert_brc20_operations(
-                block,
-                &mut brc20_operation_map,
-                brc20_cache,
-                &brc20_tx,
-                &ctx,
-            )
-            .await?;
-
-            brc20_tx
-                .commit()
-                .await
-                .map_err(|e| format!("unable to commit brc20 pg transaction: {e}"))?;
-        }
+        )
+        .await
+        .at_block(block_height, IndexingStage::Sequencing)?;
+    }
+    augment_block_with_transfers(block, ord_tx, ctx)
+        .await
+        .at_block(block_height, IndexingStage::Transfers)?;
+
+    // Write data
+    ordinals_pg::insert_block(block, config.storage.max_stored_content_bytes, ord_tx)
+        .await
+        .at_block(block_height, IndexingStage::OrdinalsWrite)?;
+    if config.storage.text_search_index {
+        ordinals_pg::insert_inscription_texts(block, ord_tx)
+            .await
+            .at_block(block_height, IndexingStage::OrdinalsWrite)?;
+    }
+    apply_content_policies(block, config, ord_tx, ctx)
+        .await
+        .at_block(block_height, IndexingStage::OrdinalsWrite)?;
+
+    // BRC-20
+    if let Some((brc20_cache, brc20_tx)) = brc20 {
+        let self_mint_activation_height = config
+            .meta_protocols
+            .brc20_self_mint_activation_height(&block.metadata.network);
+        index_block_and_insert_brc20_operations(
+            block,
+            &mut brc20_operation_map,
+            self_mint_activation_height,
+            config.meta_protocols.brc20_strictness,
+            brc20_cache,
+            brc20_tx,
+            &ctx,
+        )
+        .await
+        .at_block(block_height, IndexingStage::Brc20)?;
+    }
 
-        prometheus.metrics_block_indexed(block_height);
-        prometheus.metrics_inscription_indexed(
-            ordinals_pg::get_highest_inscription_number(&ord_tx)
-                .await?
-                .unwrap_or(0) as u64,
-        );
-        ord_tx
-            .commit()
+    // Number operations once BRC-20 ones are known, so every payload sent from here on carries their sequence.
+    let first_operation_sequence = ordinals_pg::assign_block_operation_sequence(
+        block_height,
+        count_sequenced_operations(block),
+        ord_tx,
+    )
+    .await
+    .at_block(block_height, IndexingStage::OrdinalsWrite)?;
+    block.metadata.first_operation_sequence = Some(first_operation_sequence);
+
+    cluster_block_addresses(block, config, ord_tx, ctx)
+        .await
+        .at_block(block_height, IndexingStage::OrdinalsWrite)?;
+
+    prometheus.metrics_block_indexed(block_height);
+    prometheus.metrics_bitcoind_rpc_endpoint(&config.network.bitcoind_rpc_endpoints);
+    prometheus.metrics_inscription_indexed(
+        ordinals_pg::get_highest_inscription_number(ord_tx)
+            .await
+            .at_block(block_height, IndexingStage::OrdinalsWrite)?
+            .unwrap_or(0) as u64,
+    );
+    for sink in configured_event_sinks(config)
+        .iter()
+        .filter(|sink| sink.is_transactional())
+    {
+        sink.apply_block(block, ord_tx, ctx)
             .await
-            .map_err(|e| format!("unable to commit ordinals pg transaction: {e}"))?;
+            .map_err(|e| format!("{} sink: {e}", sink.name()))
+            .at_block(block_height, IndexingStage::Sinks)?;
     }
 
     try_info!(
@@ -272,48 +418,220 @@ pub async fn index_block(
     Ok(())
 }
 
+/// Commits the transactions `blocks` were indexed in, BRC-20 first, then paces indexing if writes are throttled.
+async fn commit_indexed_blocks(
+    blocks: &[BitcoinBlockData],
+    ord_tx: Transaction<'_>,
+    brc20_tx: Option<Transaction<'_>>,
+    prometheus: &PrometheusMonitoring,
+    config: &Config,
+    pg_pools: &PgConnectionPools,
+    ctx: &Context,
+) -> Result<(), OrdhookError> {
+    let Some(last_block) = blocks.last() else {
+        return Ok(());
+    };
+    let block_height = last_block.block_identifier.index;
+    if let Some(brc20_tx) = brc20_tx {
+        pg_commit_unless_dry_run(brc20_tx, config, "brc20")
+            .await
+            .at_block(block_height, IndexingStage::Commit)?;
+    }
+    pg_commit_unless_dry_run(ord_tx, config, "ordinals")
+        .await
+        .at_block(block_height, IndexingStage::Commit)?;
+    prometheus.metrics_blocks_committed(blocks.len());
+    for block in blocks.iter() {
+        prometheus.metrics_block_operations_indexed(block);
+    }
+    if blocks.len() > 1 {
+        try_debug!(
+            ctx,
+            "Committed blocks #{} to #{block_height}",
+            blocks[0].block_identifier.index
+        );
+    }
+    if !config.dry_run {
+        pg_pools
+            .write_throttle
+            .pace(&pg_pools.ordinals, pg_pools.brc20.as_ref(), ctx)
+            .await;
+    }
+    Ok(())
+}
+
+/// Forwards `blocks` to the BRC-20 modules and to the sinks that don't write through the ordinals transaction, once the
+/// transactions they were indexed in are committed, so that none of them sees a block that is rolled back along with a
+/// failed batch. Such sinks get an ordinals transaction of their own.
+async fn publish_committed_blocks(
+    blocks: &[BitcoinBlockData],
+    brc20_enabled: bool,
+    config: &Config,
+    pg_pools: &PgConnectionPools,
+    ctx: &Context,
+) -> Result<(), OrdhookError> {
+    let Some(last_block) = blocks.last() else {
+        return Ok(());
+    };
+    if brc20_enabled {
+        for block in blocks.iter() {
+            forward_brc20_block_to_modules(block, config, ctx)
+                .await
+                .at_block(block.block_identifier.index, IndexingStage::Brc20)?;
+        }
+    }
+    let sinks: Vec<_> = configured_event_sinks(config)
+        .into_iter()
+        .filter(|sink| !sink.is_transactional())
+        .collect();
+    if sinks.is_empty() {
+        return Ok(());
+    }
+    let block_height = last_block.block_identifier.index;
+    let mut ord_client = pg_pool_client(&pg_pools.ordinals)
+        .await
+        .at_block(block_height, IndexingStage::Sinks)?;
+    let ord_tx = pg_begin(&mut ord_client)
+        .await
+        .at_block(block_height, IndexingStage::Sinks)?;
+    for block in blocks.iter() {
+        for sink in sinks.iter() {
+            sink.apply_block(block, &ord_tx, ctx)
+                .await
+                .map_err(|e| format!("{} sink: {e}", sink.name()))
+                .at_block(block.block_identifier.index, IndexingStage::Sinks)?;
+        }
+    }
+    pg_commit_unless_dry_run(ord_tx, config, "ordinals")
+        .await
+        .at_block(block_height, IndexingStage::Sinks)
+}
+
+/// Makes sure indexing can resume right after the last block committed to the ordinals DB. Each batch of blocks commits
+/// its BRC-20 writes just before its ordinals writes, so a crash between both commits leaves BRC-20 operations above the
+/// ordinals chain tip. Those are rolled back here, otherwise re-indexing that block would conflict with them.
+pub async fn resume_from_ordinals_chain_tip(
+    config: &Config,
+    pg_pools: &PgConnectionPools,
+    ctx: &Context,
+) -> Result<(), String> {
+    let ord_client = pg_pool_client(&pg_pools.ordinals).await?;
+    let chain_tip = ordinals_pg::get_chain_tip_block_height(&ord_client).await?;
+    if let (Some(chain_tip), true, Some(brc20_pool)) =
+        (chain_tip, config.meta_protocols.brc20, &pg_pools.brc20)
+    {
+        let mut brc20_client = pg_pool_client(brc20_pool).await?;
+        let brc20_tx = pg_begin(&mut brc20_client).await?;
+        let brc20_height = brc20_pg::get_highest_operation_block_height(&brc20_tx).await?;
+        if let Some(brc20_height) = brc20_height {
+            for block_height in ((chain_tip + 1)..=brc20_height).rev() {
+                try_warn!(
+                    ctx,
+                    "BRC-20 operations at block #{block_height} are ahead of the ordinals chain tip, rolling them back"
+                );
+                brc20_pg::rollback_block_operations(block_height, &brc20_tx).await?;
+            }
+        }
+        pg_commit_unless_dry_run(brc20_tx, config, "brc20").await?;
+    }
+    if let Some(chain_tip) = chain_tip {
+        try_info!(
+            ctx,
+            "Resuming inscription indexing after block #{chain_tip}"
+        );
+    }
+    Ok(())
+}
+
 pub async fn rollback_block(
     block_height: u64,
     config: &Config,
     pg_pools: &PgConnectionPools,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<(), OrdhookError> {
     try_info!(ctx, "Rolling back block #{block_height}");
-    {
-        let mut ord_client = pg_pool_client(&pg_pools.ordinals).await?;
-        let ord_tx = pg_begin(&mut ord_client).await?;
+    rollback_block_data(block_height, config, pg_pools, ctx)
+        .await
+        .at_block(block_height, IndexingStage::Rollback)
+}
 
-        ordinals_pg::rollback_block(block_height, &ord_tx).await?;
+async fn rollback_block_data(
+    block_height: u64,
+    config: &Config,
+    pg_pools: &PgConnectionPools,
+    ctx: &Context,
+) -> Result<(), OrdhookError> {
+    let mut ord_client = pg_pool_client(&pg_pools.ordinals).await?;
+    let ord_tx = pg_begin(&mut ord_client).await?;
 
-        // BRC-20
-        if let (true, Some(brc20_pool)) = (config.meta_protocols.brc20, &pg_pools.brc20) {
-            let mut brc20_client = pg_pool_client(brc20_pool).await?;
-            let brc20_tx = pg_begin(&mut brc20_client).await?;
+    ordinals_pg::rollback_block(block_height, &ord_tx).await?;
 
-            brc20_pg::rollback_block_operations(block_height, &brc20_tx).await?;
+    // BRC-20
+    if let (true, Some(brc20_pool)) = (config.meta_protocols.brc20, &pg_pools.brc20) {
+        let mut brc20_client = pg_pool_client(brc20_pool).await?;
+        let brc20_tx = pg_begin(&mut brc20_client).await?;
 
-            brc20_tx
-                .commit()
-                .await
-                .map_err(|e| format!("unable to commit brc20 pg transaction: {e}"))?;
-            try_info!(
-                ctx,
-                "Rolled back BRC-20 operations at block #{block_height}"
-            );
-        }
+        brc20_pg::rollback_block_operations(block_height, &brc20_tx).await?;
 
-        ord_tx
-            .commit()
-            .await
-            .map_err(|e| format!("unable to commit ordinals pg transaction: {e}"))?;
+        pg_commit_unless_dry_run(brc20_tx, config, "brc20").await?;
         try_info!(
             ctx,
-            "Rolled back inscription activity at block #{block_height}"
+            "Rolled back BRC-20 operations at block #{block_height}"
         );
     }
+
+    let (transactional_sinks, sinks): (Vec<_>, Vec<_>) = configured_event_sinks(config)
+        .into_iter()
+        .partition(|sink| sink.is_transactional());
+    for sink in transactional_sinks.iter() {
+        sink.rollback_block(block_height, &ord_tx, ctx)
+            .await
+            .map_err(|e| format!("{} sink: {e}", sink.name()))?;
+    }
+    pg_commit_unless_dry_run(ord_tx, config, "ordinals").await?;
+    try_info!(
+        ctx,
+        "Rolled back inscription activity at block #{block_height}"
+    );
+
+    // Other consumers only hear about the rollback once it is committed.
+    if config.meta_protocols.brc20 && pg_pools.brc20.is_some() {
+        forward_brc20_rollback_to_modules(block_height, config, ctx).await?;
+    }
+    if !sinks.is_empty() {
+        let ord_tx = pg_begin(&mut ord_client).await?;
+        for sink in sinks.iter() {
+            sink.rollback_block(block_height, &ord_tx, ctx)
+                .await
+                .map_err(|e| format!("{} sink: {e}", sink.name()))?;
+        }
+        pg_commit_unless_dry_run(ord_tx, config, "ordinals").await?;
+    }
     Ok(())
 }
 
+/// Logs an error that stopped block indexing along with its block, stage and retryability, and counts it in metrics.
+pub fn report_indexing_error(
+    error: &OrdhookError,
+    prometheus: &PrometheusMonitoring,
+    ctx: &Context,
+) {
+    prometheus.metrics_indexing_error(error);
+    let block_height = error
+        .block_height()
+        .map(|height| format!("#{height}"))
+        .unwrap_or("unknown".to_string());
+    let stage = error
+        .stage()
+        .map(|stage| stage.as_str())
+        .unwrap_or("unknown");
+    try_crit!(
+        ctx,
+        "Error indexing blocks: {error} (block: {block_height}, stage: {stage}, retryable: {})",
+        error.is_retryable()
+    );
+}
+
 // #[cfg(test)]
 // mod test {
 //     use std::{thread, time::Duration};
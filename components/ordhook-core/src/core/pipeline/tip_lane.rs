@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use chainhook_postgres::pg_pool_client;
+use chainhook_sdk::indexer::bitcoin::{
+    build_http_client, download_block, parse_downloaded_block, standardize_bitcoin_block,
+};
+use chainhook_sdk::observer::{
+    start_block_hash_poller, start_p2p_block_hash_listener, start_zeromq_block_hash_listener,
+    BitcoinConfig,
+};
+use chainhook_sdk::utils::Context;
+use chainhook_types::BitcoinBlockSignaling;
+use dashmap::DashMap;
+
+use crate::config::Config;
+use crate::core::protocol::inscription_parsing::parse_inscriptions_in_standardized_block;
+use crate::db::ordinals_pg;
+use crate::service::PgConnectionPools;
+use crate::{try_debug, try_info, try_warn};
+
+/// Maximum number of tip blocks kept in memory while waiting for the backfill to reach them.
+const TIP_LANE_MAX_PREFETCHED_BLOCKS: usize = 16;
+
+/// Small dedicated lane that processes blocks announced by bitcoind while a backfill is in progress, instead of letting
+/// them queue behind thousands of historical RPC requests.
+///
+/// Inscription numbers and sat positions depend on every block that came before, so a tip block can't be indexed
+/// canonically ahead of history. The lane indexes it provisionally instead: the inscriptions it reveals are stored in
+/// the `provisional_inscriptions` table as soon as the block is mined, which the backfill reconciles as it indexes that
+/// height. The raw block is also kept so the pipeline can hand it to the indexer without downloading it again.
+///
+/// The lane's threads are stopped and joined when it is dropped, so an early return from the catch-up loop can't leak
+/// them.
+pub struct TipPriorityLane {
+    prefetched_blocks: Arc<DashMap<u64, Vec<u8>>>,
+    stop: Arc<AtomicBool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl TipPriorityLane {
+    pub fn start(config: &Config, pg_pools: &PgConnectionPools, ctx: &Context) -> TipPriorityLane {
+        let bitcoin_config = BitcoinConfig {
+            username: config.network.bitcoind_rpc_username.clone(),
+            password: config.network.bitcoind_rpc_password.clone(),
+            cookie_path: config.network.bitcoind_rpc_cookie_path.clone(),
+            rpc_endpoints: config.network.bitcoind_rpc_endpoints.clone(),
+            network: config.network.bitcoin_network.clone(),
+            bitcoin_block_signaling: config.network.bitcoin_block_signaling.clone(),
+        };
+        let prefetched_blocks = Arc::new(DashMap::new());
+        let stop = Arc::new(AtomicBool::new(false));
+        let (block_hash_tx, block_hash_rx) = crossbeam_channel::unbounded();
+
+        let moved_signaling = config.network.bitcoin_block_signaling.clone();
+        let moved_bitcoin_config = bitcoin_config.clone();
+        let moved_stop = stop.clone();
+        let moved_ctx = ctx.clone();
+        let listener_handle = hiro_system_kit::thread_named("Tip block listener")
+            .spawn(move || match moved_signaling {
+                BitcoinBlockSignaling::ZeroMQ(ref url) => {
+                    start_zeromq_block_hash_listener(url, block_hash_tx, moved_stop, &moved_ctx)
+                }
+                BitcoinBlockSignaling::Poll(interval_ms) => start_block_hash_poller(
+                    &moved_bitcoin_config,
+                    interval_ms,
+                    block_hash_tx,
+                    moved_stop,
+                    &moved_ctx,
+                ),
+                BitcoinBlockSignaling::P2P(ref peer) => start_p2p_block_hash_listener(
+                    peer,
+                    &moved_bitcoin_config.network,
+                    block_hash_tx,
+                    moved_stop,
+                    &moved_ctx,
+                ),
+            })
+            .expect("unable to spawn thread");
+
+        let moved_prefetched_blocks = prefetched_blocks.clone();
+        let moved_ctx = ctx.clone();
+        let moved_config = config.clone();
+        let ordinals_pool = pg_pools.ordinals.clone();
+        let worker_handle = hiro_system_kit::thread_named("Tip priority lane")
+            .spawn(move || {
+                let http_client = build_http_client();
+                hiro_system_kit::nestable_block_on(async move {
+                    while let Ok(block_hash) = block_hash_rx.recv() {
+                        let block_bytes = match download_block(
+                            &http_client,
+                            &block_hash,
+                            &bitcoin_config,
+                            &moved_ctx,
+                        )
+                        .await
+                        {
+                            Ok(block_bytes) => block_bytes,
+                            Err(e) => {
+                                try_warn!(
+                                    moved_ctx,
+                                    "Tip lane: unable to download block {block_hash}: {e}"
+                                );
+                                continue;
+                            }
+                        };
+                        let block_height = match parse_downloaded_block(block_bytes.clone()) {
+                            Ok(block) => block.height as u64,
+                            Err(e) => {
+                                try_warn!(
+                                    moved_ctx,
+                                    "Tip lane: unable to parse block {block_hash}: {e}"
+                                );
+                                continue;
+                            }
+                        };
+                        try_info!(moved_ctx, "Tip lane: prefetched block #{block_height}");
+                        if let Err(e) = index_provisional_block(
+                            block_bytes.clone(),
+                            &ordinals_pool,
+                            &moved_config,
+                            &moved_ctx,
+                        )
+                        .await
+                        {
+                            try_warn!(
+                                moved_ctx,
+                                "Tip lane: unable to index provisional block #{block_height}: {e}"
+                            );
+                        }
+                        // A re-org announces a new block at an already known height, latest announcement wins.
+                        moved_prefetched_blocks.insert(block_height, block_bytes);
+                        while moved_prefetched_blocks.len() > TIP_LANE_MAX_PREFETCHED_BLOCKS {
+                            let Some(lowest) =
+                                moved_prefetched_blocks.iter().map(|e| *e.key()).min()
+                            else {
+                                break;
+                            };
+                            moved_prefetched_blocks.remove(&lowest);
+                        }
+                    }
+                })
+            })
+            .expect("unable to spawn thread");
+
+        TipPriorityLane {
+            prefetched_blocks,
+            stop,
+            handles: vec![listener_handle, worker_handle],
+        }
+    }
+
+    /// Takes the raw `getblock` response for a height if the lane already fetched it.
+    pub fn take_block(&self, block_height: u64) -> Option<Vec<u8>> {
+        self.prefetched_blocks
+            .remove(&block_height)
+            .map(|(_, block_bytes)| block_bytes)
+    }
+
+    pub fn terminate(mut self, ctx: &Context) {
+        self.stop_threads();
+        try_debug!(ctx, "Tip lane: terminated");
+    }
+
+    fn stop_threads(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // The worker exits once the listener returns and drops its end of the block hash channel.
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for TipPriorityLane {
+    fn drop(&mut self) {
+        self.stop_threads();
+    }
+}
+
+/// Parses the inscriptions revealed in a raw tip block and stores them as provisional data.
+async fn index_provisional_block(
+    block_bytes: Vec<u8>,
+    pool: &deadpool_postgres::Pool,
+    config: &Config,
+    ctx: &Context,
+) -> Result<(), String> {
+    let raw_block = parse_downloaded_block(block_bytes)?;
+    let mut block = standardize_bitcoin_block(raw_block, &config.network.bitcoin_network, ctx)
+        .map_err(|(e, _)| e)?;
+    parse_inscriptions_in_standardized_block(&mut block, &mut HashMap::new(), config, ctx);
+    if config.dry_run {
+        return Ok(());
+    }
+    let client = pg_pool_client(pool).await?;
+    ordinals_pg::insert_provisional_block(&block, &client).await?;
+    try_info!(
+        ctx,
+        "Tip lane: stored provisional data for block #{}",
+        block.block_identifier.index
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use chainhook_sdk::{indexer::bitcoin::BitcoindRpcEndpoints, utils::Context};
+    use chainhook_types::BitcoinBlockSignaling;
+
+    use crate::{config::Config, service::Service};
+
+    use super::TipPriorityLane;
+
+    #[test]
+    fn stops_its_threads_when_dropped() {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.network.bitcoind_rpc_endpoints =
+            BitcoindRpcEndpoints::new("http://127.0.0.1:1", &[]);
+        config.network.bitcoin_block_signaling = BitcoinBlockSignaling::Poll(10);
+        let lane = TipPriorityLane::start(&config, &Service::new(&config, &ctx).pg_pools, &ctx);
+        let stop = lane.stop.clone();
+        let prefetched_blocks = lane.prefetched_blocks.clone();
+
+        // Same as leaving the catch-up loop early with `?`.
+        drop(lane);
+
+        // Both threads have exited and released their clones.
+        assert_eq!(Arc::strong_count(&stop), 1);
+        assert_eq!(Arc::strong_count(&prefetched_blocks), 1);
+    }
+}
@@ -1,6 +1,6 @@
 use bitcoin::hash_types::Txid;
 use bitcoin::Witness;
-use chainhook_sdk::utils::Context;
+use chainhook_sdk::utils::{hex, Context};
 use chainhook_types::{
     BitcoinBlockData, BitcoinNetwork, BitcoinTransactionData, BlockIdentifier,
     OrdinalInscriptionCurseType, OrdinalInscriptionNumber, OrdinalInscriptionRevealData,
@@ -54,7 +54,7 @@ pub fn parse_inscriptions_from_witness(
         };
 
         let inscription_id = InscriptionId {
-            txid: Txid::from_str(txid).unwrap(),
+            txid: Txid::from_str(txid).ok()?,
             index: input_index as u32,
         };
 
@@ -120,11 +120,19 @@ pub fn parse_inscriptions_from_standardized_tx(
 ) -> Vec<OrdinalOperation> {
     let mut operations = vec![];
     for (input_index, input) in tx.metadata.inputs.iter().enumerate() {
-        let witness_bytes: Vec<Vec<u8>> = input
+        let Some(witness_bytes) = input
             .witness
             .iter()
-            .map(|w| hex::decode(&w[2..]).unwrap())
-            .collect();
+            .map(|w| w.get(2..).and_then(|w| hex::decode(w).ok()))
+            .collect::<Option<Vec<Vec<u8>>>>()
+        else {
+            try_warn!(
+                ctx,
+                "Skipping input {input_index} of tx {} with a malformed witness",
+                tx.transaction_identifier.hash
+            );
+            continue;
+        };
 
         if let Some(inscriptions) = parse_inscriptions_from_witness(
             input_index,
@@ -216,4 +224,23 @@ mod test {
         assert_eq!(reveal.content_bytes, "0x7b200a20202270223a20226272632d3230222c0a2020226f70223a20226465706c6f79222c0a2020227469636b223a20226f726469222c0a2020226d6178223a20223231303030303030222c0a2020226c696d223a202231303030220a7d".to_string());
         assert_eq!(reveal.content_length, 94);
     }
+
+    #[test]
+    fn skips_malformed_witnesses() {
+        let ctx = Context::empty();
+        let config = Config::test_default();
+        let mut block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_input(
+                        TestTxInBuilder::new()
+                            .witness(vec!["0xzz".to_string(), "0".to_string()])
+                            .build(),
+                    )
+                    .build(),
+            )
+            .build();
+        parse_inscriptions_in_standardized_block(&mut block, &mut HashMap::new(), &config, &ctx);
+        assert!(block.transactions[0].metadata.ordinal_operations.is_empty());
+    }
 }
@@ -21,8 +21,9 @@ use crate::{
     config::Config,
     core::resolve_absolute_pointer,
     db::{self, cursor::TransactionBytesCursor, ordinals_pg},
+    error::OrdhookError,
     try_debug, try_error, try_info,
-    utils::format_inscription_id,
+    utils::{format_inscription_id, pin_current_thread_to_cores},
 };
 use ord::{charm::Charm, sat::Sat};
 
@@ -30,7 +31,7 @@ use std::sync::mpsc::channel;
 
 use super::{
     satoshi_numbering::{compute_satoshi_number, TraversalResult},
-    satoshi_tracking::compute_satpoint_post_transfer,
+    satoshi_tracking::{compute_satpoint_post_transfer_with_values, get_transaction_values},
     sequence_cursor::SequenceCursor,
 };
 
@@ -109,6 +110,11 @@ pub fn parallelize_inscription_data_computations(
 
         let handle = hiro_system_kit::thread_named("Worker")
             .spawn(move || {
+                pin_current_thread_to_cores(
+                    &moved_config.resources.traversal_worker_cores,
+                    thread_index,
+                    &moved_ctx,
+                );
                 while let Ok(Some((
                     transaction_id,
                     block_identifier,
@@ -398,7 +404,7 @@ pub async fn update_block_inscriptions_with_consensus_sequence_data(
     inscriptions_data: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
     db_tx: &Transaction<'_>,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<(), OrdhookError> {
     // Check if we've previously inscribed over any satoshi being inscribed to in this new block. This would be a reinscription.
     let mut reinscriptions_data =
         ordinals_pg::get_reinscriptions_for_block(inscriptions_data, db_tx).await?;
@@ -472,17 +478,12 @@ async fn update_tx_inscriptions_with_consensus_sequence_data(
     reinscriptions_data: &mut HashMap<u64, String>,
     db_tx: &Transaction<'_>,
     ctx: &Context,
-) -> Result<bool, String> {
+) -> Result<bool, OrdhookError> {
     if tx.metadata.ordinal_operations.is_empty() {
         return Ok(false);
     }
 
-    let tx_input_values = tx
-        .metadata
-        .inputs
-        .iter()
-        .map(|i| i.previous_output.value)
-        .collect::<Vec<u64>>();
+    let (tx_input_values, tx_output_values) = get_transaction_values(tx);
     let mut mut_operations = vec![];
     mut_operations.append(&mut tx.metadata.ordinal_operations);
 
@@ -509,7 +510,8 @@ async fn update_tx_inscriptions_with_consensus_sequence_data(
                     return Err(format!(
                         "Unable to retrieve backward traversal result for inscription in tx {}",
                         tx.transaction_identifier.hash
-                    ));
+                    )
+                    .into());
                 }
             };
 
@@ -561,7 +563,15 @@ async fn update_tx_inscriptions_with_consensus_sequence_data(
         }
 
         let (destination, satpoint_post_transfer, output_value) =
-            compute_satpoint_post_transfer(&&*tx, input_index, relative_offset, network, ctx);
+            compute_satpoint_post_transfer_with_values(
+                &&*tx,
+                &tx_input_values,
+                &tx_output_values,
+                input_index,
+                relative_offset,
+                network,
+                ctx,
+            );
         inscription.satpoint_post_inscription = satpoint_post_transfer;
         inscription_subindex += 1;
 
@@ -680,7 +690,7 @@ mod test {
                     data.unbound_sequence = Some(curr_sequence);
                 };
                 let block = TestBlockBuilder::new().transactions(vec![tx]).build();
-                insert_block(&block, &client).await?;
+                insert_block(&block, None, &client).await?;
             }
 
             // Insert new block
@@ -176,7 +176,12 @@ pub fn compute_satoshi_number(
         let block_cursor = BlockBytesCursor::new(pinned_block_bytes.as_ref());
         let txid = tx_cursor.0;
         let mut block_cursor_tx_iter = block_cursor.iter_tx();
-        let coinbase = block_cursor_tx_iter.next().expect("empty block");
+        let Some(coinbase) = block_cursor_tx_iter.next() else {
+            return Err(format!(
+                "block #{ordinal_block_number} has no transactions (traversing {} / {} in progress)",
+                transaction_identifier.hash, block_identifier.index
+            ));
+        };
 
         // evaluate exit condition: did we reach the **final** coinbase transaction
         if coinbase.txid.eq(&txid) {
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, num::NonZeroUsize, sync::Mutex};
 
 use bitcoin::{Address, Network, ScriptBuf};
 use chainhook_sdk::utils::Context;
@@ -7,10 +7,12 @@ use chainhook_types::{
     OrdinalInscriptionTransferDestination, OrdinalOperation,
 };
 use deadpool_postgres::Transaction;
+use lru::LruCache;
 
 use crate::{
     core::{compute_next_satpoint_data, SatPosition},
     db::ordinals_pg,
+    error::OrdhookError,
     try_info,
     utils::format_outpoint_to_watch,
 };
@@ -20,10 +22,24 @@ use super::inscription_sequencing::get_bitcoin_network;
 pub const UNBOUND_INSCRIPTION_SATPOINT: &str =
     "0000000000000000000000000000000000000000000000000000000000000000:0";
 
+/// Number of `script_pubkey` to destination conversions remembered across blocks.
+const SCRIPT_DESTINATION_CACHE_SIZE: usize = 100_000;
+
+lazy_static! {
+    // Hot addresses receive inscriptions block after block, parsing their script every time is a sizeable share of the
+    // per-block CPU time.
+    static ref SCRIPT_DESTINATION_CACHE: Mutex<LruCache<(Network, String), OrdinalInscriptionTransferDestination>> =
+        Mutex::new(LruCache::new(
+            NonZeroUsize::new(SCRIPT_DESTINATION_CACHE_SIZE).unwrap()
+        ));
+}
+
 #[derive(Clone, Debug, Ord, PartialOrd, PartialEq, Eq)]
 pub struct WatchedSatpoint {
     pub ordinal_number: u64,
     pub offset: u64,
+    /// Address of the output currently holding the sat.
+    pub address: Option<String>,
 }
 
 pub fn parse_output_and_offset_from_satpoint(
@@ -50,7 +66,7 @@ pub async fn augment_block_with_transfers(
     block: &mut BitcoinBlockData,
     db_tx: &Transaction<'_>,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<(), OrdhookError> {
     let network = get_bitcoin_network(&block.metadata.network);
     for (tx_index, tx) in block.transactions.iter_mut().enumerate() {
         let _ = augment_transaction_with_ordinal_transfers(
@@ -66,6 +82,60 @@ pub async fn augment_block_with_transfers(
     Ok(())
 }
 
+/// Returns the input and output values of a transaction, as expected by [compute_satpoint_post_transfer_with_values].
+pub fn get_transaction_values(tx: &BitcoinTransactionData) -> (Vec<u64>, Vec<u64>) {
+    let inputs = tx
+        .metadata
+        .inputs
+        .iter()
+        .map(|o| o.previous_output.value)
+        .collect::<_>();
+    let outputs = tx.metadata.outputs.iter().map(|o| o.value).collect::<_>();
+    (inputs, outputs)
+}
+
+/// Converts an output `script_pubkey` into the destination of the sats it receives. Results are cached per network.
+pub fn get_script_pubkey_destination(
+    script_pub_key_hex: &str,
+    network: &Network,
+    ctx: &Context,
+) -> OrdinalInscriptionTransferDestination {
+    let cache_key = (network.clone(), script_pub_key_hex.to_string());
+    if let Some(destination) = SCRIPT_DESTINATION_CACHE
+        .lock()
+        .ok()
+        .and_then(|mut cache| cache.get(&cache_key).cloned())
+    {
+        return destination;
+    }
+    let destination = match ScriptBuf::from_hex(script_pub_key_hex) {
+        Ok(script) => match Address::from_script(&script, network.clone()) {
+            Ok(address) => OrdinalInscriptionTransferDestination::Transferred(address.to_string()),
+            Err(e) => {
+                try_info!(
+                    ctx,
+                    "unable to retrieve address from {script_pub_key_hex}: {}",
+                    e.to_string()
+                );
+                OrdinalInscriptionTransferDestination::Burnt(script.to_string())
+            }
+        },
+        Err(e) => {
+            try_info!(
+                ctx,
+                "unable to retrieve address from {script_pub_key_hex}: {}",
+                e.to_string()
+            );
+            OrdinalInscriptionTransferDestination::Burnt(script_pub_key_hex.to_string())
+        }
+    };
+    // The cache is only an optimization, a poisoned lock just skips it.
+    if let Ok(mut cache) = SCRIPT_DESTINATION_CACHE.lock() {
+        cache.put(cache_key, destination.clone());
+    }
+    destination
+}
+
 pub fn compute_satpoint_post_transfer(
     tx: &BitcoinTransactionData,
     input_index: usize,
@@ -73,17 +143,33 @@ pub fn compute_satpoint_post_transfer(
     network: &Network,
     ctx: &Context,
 ) -> (OrdinalInscriptionTransferDestination, String, Option<u64>) {
-    let inputs: Vec<u64> = tx
-        .metadata
-        .inputs
-        .iter()
-        .map(|o| o.previous_output.value)
-        .collect::<_>();
-    let outputs = tx.metadata.outputs.iter().map(|o| o.value).collect::<_>();
-    let post_transfer_data = compute_next_satpoint_data(
-        input_index,
+    let (inputs, outputs) = get_transaction_values(tx);
+    compute_satpoint_post_transfer_with_values(
+        tx,
         &inputs,
         &outputs,
+        input_index,
+        relative_pointer_value,
+        network,
+        ctx,
+    )
+}
+
+/// Same as [compute_satpoint_post_transfer], for callers that move several sats out of the same transaction and can
+/// compute its input and output values once.
+pub fn compute_satpoint_post_transfer_with_values(
+    tx: &BitcoinTransactionData,
+    inputs: &Vec<u64>,
+    outputs: &Vec<u64>,
+    input_index: usize,
+    relative_pointer_value: u64,
+    network: &Network,
+    ctx: &Context,
+) -> (OrdinalInscriptionTransferDestination, String, Option<u64>) {
+    let post_transfer_data = compute_next_satpoint_data(
+        input_index,
+        inputs,
+        outputs,
         relative_pointer_value,
         Some(ctx),
     );
@@ -93,29 +179,8 @@ pub fn compute_satpoint_post_transfer(
             SatPosition::Output((output_index, offset)) => {
                 let outpoint = format_outpoint_to_watch(&tx.transaction_identifier, output_index);
                 let script_pub_key_hex = tx.metadata.outputs[output_index].get_script_pubkey_hex();
-                let updated_address = match ScriptBuf::from_hex(&script_pub_key_hex) {
-                    Ok(script) => match Address::from_script(&script, network.clone()) {
-                        Ok(address) => {
-                            OrdinalInscriptionTransferDestination::Transferred(address.to_string())
-                        }
-                        Err(e) => {
-                            try_info!(
-                                ctx,
-                                "unable to retrieve address from {script_pub_key_hex}: {}",
-                                e.to_string()
-                            );
-                            OrdinalInscriptionTransferDestination::Burnt(script.to_string())
-                        }
-                    },
-                    Err(e) => {
-                        try_info!(
-                            ctx,
-                            "unable to retrieve address from {script_pub_key_hex}: {}",
-                            e.to_string()
-                        );
-                        OrdinalInscriptionTransferDestination::Burnt(script_pub_key_hex.to_string())
-                    }
-                };
+                let updated_address =
+                    get_script_pubkey_destination(script_pub_key_hex, network, ctx);
 
                 (
                     outpoint,
@@ -150,7 +215,7 @@ pub async fn augment_transaction_with_ordinal_transfers(
     network: &Network,
     db_tx: &Transaction<'_>,
     ctx: &Context,
-) -> Result<Vec<OrdinalInscriptionTransferData>, String> {
+) -> Result<Vec<OrdinalInscriptionTransferData>, OrdhookError> {
     let mut transfers = vec![];
 
     // The transfers are inserted in storage after the inscriptions.
@@ -163,6 +228,7 @@ pub async fn augment_transaction_with_ordinal_transfers(
     }
 
     // For each satpoint inscribed retrieved, we need to compute the next outpoint to watch
+    let (input_values, output_values) = get_transaction_values(tx);
     let input_entries =
         ordinals_pg::get_inscribed_satpoints_at_tx_inputs(&tx.metadata.inputs, db_tx).await?;
     for (input_index, input) in tx.metadata.inputs.iter().enumerate() {
@@ -183,8 +249,10 @@ pub async fn augment_transaction_with_ordinal_transfers(
             );
 
             let (destination, satpoint_post_transfer, post_transfer_output_value) =
-                compute_satpoint_post_transfer(
+                compute_satpoint_post_transfer_with_values(
                     &&*tx,
+                    &input_values,
+                    &output_values,
                     input_index,
                     watched_satpoint.offset,
                     network,
@@ -194,6 +262,7 @@ pub async fn augment_transaction_with_ordinal_transfers(
             let transfer_data = OrdinalInscriptionTransferData {
                 ordinal_number: watched_satpoint.ordinal_number,
                 destination,
+                from_address: watched_satpoint.address.clone(),
                 tx_index,
                 satpoint_pre_transfer: satpoint_pre_transfer.clone(),
                 satpoint_post_transfer: satpoint_post_transfer.clone(),
@@ -226,7 +295,7 @@ mod test {
 
     use crate::core::test_builders::{TestTransactionBuilder, TestTxInBuilder, TestTxOutBuilder};
 
-    use super::compute_satpoint_post_transfer;
+    use super::{compute_satpoint_post_transfer, get_script_pubkey_destination};
 
     #[test]
     fn computes_satpoint_spent_as_fee() {
@@ -278,4 +347,25 @@ mod test {
         );
         assert_eq!(value, Some(9000));
     }
+
+    #[test]
+    fn caches_script_pubkey_destinations_per_network() {
+        let ctx = Context::empty();
+        let script_pubkey = "5120694b38ea24908e86a857279105c376a82cd1556f51655abb2ebef398b57daa8b";
+
+        for _ in 0..2 {
+            assert_eq!(
+                get_script_pubkey_destination(script_pubkey, &Network::Bitcoin, &ctx),
+                OrdinalInscriptionTransferDestination::Transferred(
+                    "bc1pd99n363yjz8gd2zhy7gstsmk4qkdz4t029j44wewhmee3dta429sm5xqrd".to_string()
+                )
+            );
+        }
+        assert_eq!(
+            get_script_pubkey_destination(script_pubkey, &Network::Testnet, &ctx),
+            OrdinalInscriptionTransferDestination::Transferred(
+                "tb1pd99n363yjz8gd2zhy7gstsmk4qkdz4t029j44wewhmee3dta429svus0ez".to_string()
+            )
+        );
+    }
 }
@@ -2,7 +2,7 @@ use bitcoin::Network;
 use chainhook_types::OrdinalInscriptionNumber;
 use deadpool_postgres::GenericClient;
 
-use crate::db::ordinals_pg;
+use crate::{db::ordinals_pg, error::OrdhookError};
 
 use super::inscription_sequencing;
 
@@ -45,7 +45,7 @@ impl SequenceCursor {
         block_height: u64,
         network: &Network,
         client: &T,
-    ) -> Result<OrdinalInscriptionNumber, String> {
+    ) -> Result<OrdinalInscriptionNumber, OrdhookError> {
         if block_height < self.current_block_height {
             self.reset();
         }
@@ -69,7 +69,7 @@ impl SequenceCursor {
         &mut self,
         cursed: bool,
         client: &T,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
         self.increment_jubilee_number(client).await?;
         if cursed {
             self.increment_neg_classic(client).await?;
@@ -79,13 +79,19 @@ impl SequenceCursor {
         Ok(())
     }
 
-    pub async fn increment_unbound<T: GenericClient>(&mut self, client: &T) -> Result<i64, String> {
+    pub async fn increment_unbound<T: GenericClient>(
+        &mut self,
+        client: &T,
+    ) -> Result<i64, OrdhookError> {
         let next = self.pick_next_unbound(client).await?;
         self.unbound_cursor = Some(next);
         Ok(next)
     }
 
-    async fn pick_next_pos_classic<T: GenericClient>(&mut self, client: &T) -> Result<i64, String> {
+    async fn pick_next_pos_classic<T: GenericClient>(
+        &mut self,
+        client: &T,
+    ) -> Result<i64, OrdhookError> {
         match self.pos_cursor {
             None => {
                 match ordinals_pg::get_highest_blessed_classic_inscription_number(client).await? {
@@ -103,7 +109,7 @@ impl SequenceCursor {
     async fn pick_next_jubilee_number<T: GenericClient>(
         &mut self,
         client: &T,
-    ) -> Result<i64, String> {
+    ) -> Result<i64, OrdhookError> {
         match self.jubilee_cursor {
             None => match ordinals_pg::get_highest_inscription_number(client).await? {
                 Some(inscription_number) => {
@@ -116,7 +122,10 @@ impl SequenceCursor {
         }
     }
 
-    async fn pick_next_neg_classic<T: GenericClient>(&mut self, client: &T) -> Result<i64, String> {
+    async fn pick_next_neg_classic<T: GenericClient>(
+        &mut self,
+        client: &T,
+    ) -> Result<i64, OrdhookError> {
         match self.neg_cursor {
             None => {
                 match ordinals_pg::get_lowest_cursed_classic_inscription_number(client).await? {
@@ -131,7 +140,10 @@ impl SequenceCursor {
         }
     }
 
-    async fn pick_next_unbound<T: GenericClient>(&mut self, client: &T) -> Result<i64, String> {
+    async fn pick_next_unbound<T: GenericClient>(
+        &mut self,
+        client: &T,
+    ) -> Result<i64, OrdhookError> {
         match self.unbound_cursor {
             None => match ordinals_pg::get_highest_unbound_inscription_sequence(client).await? {
                 Some(unbound_sequence) => {
@@ -144,12 +156,18 @@ impl SequenceCursor {
         }
     }
 
-    async fn increment_neg_classic<T: GenericClient>(&mut self, client: &T) -> Result<(), String> {
+    async fn increment_neg_classic<T: GenericClient>(
+        &mut self,
+        client: &T,
+    ) -> Result<(), OrdhookError> {
         self.neg_cursor = Some(self.pick_next_neg_classic(client).await?);
         Ok(())
     }
 
-    async fn increment_pos_classic<T: GenericClient>(&mut self, client: &T) -> Result<(), String> {
+    async fn increment_pos_classic<T: GenericClient>(
+        &mut self,
+        client: &T,
+    ) -> Result<(), OrdhookError> {
         self.pos_cursor = Some(self.pick_next_pos_classic(client).await?);
         Ok(())
     }
@@ -157,7 +175,7 @@ impl SequenceCursor {
     async fn increment_jubilee_number<T: GenericClient>(
         &mut self,
         client: &T,
-    ) -> Result<(), String> {
+    ) -> Result<(), OrdhookError> {
         self.jubilee_cursor = Some(self.pick_next_jubilee_number(client).await?);
         Ok(())
     }
@@ -197,7 +215,7 @@ mod test {
                 .transactions(vec![TestTransactionBuilder::new_with_operation().build()])
                 .build();
             block.block_identifier.index = block_height;
-            insert_block(&block, &client).await?;
+            insert_block(&block, None, &client).await?;
 
             // Pick next twice so we can test all cases.
             let mut cursor = SequenceCursor::new();
@@ -212,7 +230,7 @@ mod test {
             cursor.increment(cursed, &client).await?;
 
             block.block_identifier.index = block.block_identifier.index + 1;
-            insert_block(&block, &client).await?;
+            insert_block(&block, None, &client).await?;
             let next = cursor
                 .pick_next(
                     cursed,
@@ -245,7 +263,7 @@ mod test {
                 data.unbound_sequence = curr_sequence;
             };
             let block = TestBlockBuilder::new().transactions(vec![tx]).build();
-            insert_block(&block, &client).await?;
+            insert_block(&block, None, &client).await?;
 
             let mut cursor = SequenceCursor::new();
             cursor.increment_unbound(&client).await?
@@ -0,0 +1,352 @@
+use std::{
+    hash::BuildHasherDefault,
+    sync::{
+        mpsc::{channel, Sender},
+        Arc,
+    },
+};
+
+use chainhook_sdk::{
+    indexer::fork_scratch_pad::ForkScratchPad,
+    observer::{
+        start_observer_commands_handler, BitcoinBlockDataCached, ObserverCommand, ObserverSidecar,
+    },
+    utils::Context,
+};
+use chainhook_types::{BitcoinBlockData, BlockHeader, BlockIdentifier};
+use dashmap::DashMap;
+use fxhash::FxHasher;
+
+use crate::{
+    config::Config,
+    core::{
+        meta_protocols::brc20::cache::{brc20_new_cache, Brc20MemoryCache},
+        new_traversals_lazy_cache,
+        test_builders::TestBlockBuilder,
+    },
+    db::cursor::TransactionBytesCursor,
+    error::OrdhookError,
+    service::{
+        activity_stream::{new_activity_stream, ActivityStreamSender},
+        chainhook_sidecar_mutate_blocks, PgConnectionPools,
+    },
+    utils::monitoring::PrometheusMonitoring,
+};
+
+/// A deterministic block hash for the block at `height` of the fork named `fork`, so competing forks never collide.
+pub fn test_block_hash(fork: &str, height: u64) -> String {
+    let fork_id: String = fork.bytes().take(24).map(|b| format!("{b:02x}")).collect();
+    format!("0x{fork_id:0<48}{height:016x}")
+}
+
+/// Builds a chain of test blocks on top of a parent block, linking every block to the previous one. Forks built from
+/// the same parent compete with each other once announced to a [ReorgSimulator].
+pub struct TestChainBuilder {
+    fork: String,
+    tip: BlockIdentifier,
+    blocks: Vec<BitcoinBlockData>,
+}
+
+impl TestChainBuilder {
+    pub fn new(fork: &str, parent: &BlockIdentifier) -> Self {
+        TestChainBuilder {
+            fork: fork.to_string(),
+            tip: parent.clone(),
+            blocks: vec![],
+        }
+    }
+
+    /// Appends a block to the chain. Its height, hash and parent hash are overwritten to extend the current tip.
+    pub fn add_block(mut self, block: TestBlockBuilder) -> Self {
+        let height = self.tip.index + 1;
+        let block = block
+            .height(height)
+            .hash(test_block_hash(&self.fork, height))
+            .parent_hash(self.tip.hash.clone())
+            .build();
+        self.tip = block.block_identifier.clone();
+        self.blocks.push(block);
+        self
+    }
+
+    /// Appends `count` blocks without transactions.
+    pub fn add_empty_blocks(mut self, count: usize) -> Self {
+        for _ in 0..count {
+            self = self.add_block(TestBlockBuilder::new());
+        }
+        self
+    }
+
+    pub fn build(self) -> Vec<BitcoinBlockData> {
+        self.blocks
+    }
+}
+
+/// The blocks rolled back and applied by the indexer after a block announcement, in the order they were handled.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimulatedChainUpdate {
+    pub rolled_back: Vec<BlockIdentifier>,
+    pub applied: Vec<BlockIdentifier>,
+}
+
+/// Replays block announcements through the same path live blocks take: the fork scratch pad decides which fork is
+/// canonical, the observer turns its chain events into blocks to apply and roll back, and the indexer sidecar writes
+/// them to the DBs. Lets tests assert the DB state left behind by arbitrary reorg sequences without bitcoind.
+pub struct ReorgSimulator {
+    fork_scratch_pad: ForkScratchPad,
+    observer_commands_tx: Sender<ObserverCommand>,
+    block_mutator_in_rx:
+        crossbeam_channel::Receiver<(Vec<BitcoinBlockDataCached>, Vec<BlockIdentifier>)>,
+    block_mutator_out_tx: crossbeam_channel::Sender<Vec<BitcoinBlockDataCached>>,
+    cache_l2: Arc<DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>>>,
+    brc20_cache: Option<Brc20MemoryCache>,
+    prometheus: PrometheusMonitoring,
+    activity_stream: ActivityStreamSender,
+}
+
+impl ReorgSimulator {
+    /// Starts an observer commands handler on its own thread, with the simulator acting as its sidecar.
+    pub fn new(config: &Config, ctx: &Context) -> Self {
+        let (observer_commands_tx, observer_commands_rx) = channel();
+        let (block_mutator_in_tx, block_mutator_in_rx) = crossbeam_channel::unbounded();
+        let (block_mutator_out_tx, block_mutator_out_rx) = crossbeam_channel::unbounded();
+        let observer_sidecar = ObserverSidecar {
+            bitcoin_blocks_mutator: Some((block_mutator_in_tx, block_mutator_out_rx)),
+            bitcoin_chain_event_notifier: None,
+        };
+        let mut event_observer_config = config.get_event_observer_config();
+        event_observer_config.chain_event_cursor_store = None;
+        let moved_ctx = ctx.clone();
+        hiro_system_kit::thread_named("Reorg simulator observer")
+            .spawn(move || {
+                let _ = hiro_system_kit::nestable_block_on(start_observer_commands_handler(
+                    event_observer_config,
+                    observer_commands_rx,
+                    None,
+                    None,
+                    Some(observer_sidecar),
+                    moved_ctx,
+                ));
+            })
+            .expect("unable to spawn reorg simulator observer");
+        ReorgSimulator {
+            fork_scratch_pad: ForkScratchPad::new(),
+            observer_commands_tx,
+            block_mutator_in_rx,
+            block_mutator_out_tx,
+            cache_l2: Arc::new(new_traversals_lazy_cache(100)),
+            brc20_cache: brc20_new_cache(config),
+            prometheus: PrometheusMonitoring::new(),
+            activity_stream: new_activity_stream(),
+        }
+    }
+
+    /// Announces a block as bitcoind would, and waits for the indexer to apply the resulting chain event if the block
+    /// changed the canonical chain. Returns `None` when it did not, e.g. for a block extending a shorter fork.
+    pub async fn announce_block(
+        &mut self,
+        block: &BitcoinBlockData,
+        config: &Config,
+        pg_pools: &PgConnectionPools,
+        ctx: &Context,
+    ) -> Result<Option<SimulatedChainUpdate>, OrdhookError> {
+        let header = BlockHeader {
+            block_identifier: block.block_identifier.clone(),
+            parent_block_identifier: block.parent_block_identifier.clone(),
+        };
+        let _ = self
+            .observer_commands_tx
+            .send(ObserverCommand::CacheBitcoinBlock(block.clone()));
+        let Some(chain_event) = self.fork_scratch_pad.process_header(header, ctx)? else {
+            return Ok(None);
+        };
+        self.observer_commands_tx
+            .send(ObserverCommand::PropagateBitcoinChainEvent(chain_event))
+            .map_err(|e| format!("observer stopped: {e}"))?;
+        let (mut blocks_to_mutate, block_ids_to_rollback) = self
+            .block_mutator_in_rx
+            .recv()
+            .map_err(|e| format!("observer stopped: {e}"))?;
+        let result = chainhook_sidecar_mutate_blocks(
+            &mut blocks_to_mutate,
+            &block_ids_to_rollback,
+            &self.cache_l2,
+            &mut self.brc20_cache,
+            &self.prometheus,
+            &self.activity_stream,
+            None,
+            config,
+            pg_pools,
+            ctx,
+        )
+        .await;
+        let update = SimulatedChainUpdate {
+            rolled_back: block_ids_to_rollback,
+            applied: blocks_to_mutate
+                .iter()
+                .map(|cached| cached.block.block_identifier.clone())
+                .collect(),
+        };
+        // Hand the blocks back even on error so the observer doesn't wait forever.
+        let _ = self.block_mutator_out_tx.send(blocks_to_mutate);
+        result?;
+        Ok(Some(update))
+    }
+
+    /// Announces blocks one after the other and returns the chain updates they caused.
+    pub async fn announce_blocks(
+        &mut self,
+        blocks: &[BitcoinBlockData],
+        config: &Config,
+        pg_pools: &PgConnectionPools,
+        ctx: &Context,
+    ) -> Result<Vec<SimulatedChainUpdate>, OrdhookError> {
+        let mut updates = vec![];
+        for block in blocks.iter() {
+            if let Some(update) = self.announce_block(block, config, pg_pools, ctx).await? {
+                updates.push(update);
+            }
+        }
+        Ok(updates)
+    }
+}
+
+impl Drop for ReorgSimulator {
+    fn drop(&mut self) {
+        let _ = self.observer_commands_tx.send(ObserverCommand::Terminate);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{path::Path, sync::Arc};
+
+    use chainhook_postgres::pg_pool_client;
+    use chainhook_sdk::utils::Context;
+
+    use crate::{
+        config::Config,
+        core::block_fixtures::load_block_fixture,
+        db::{drop_all_dbs, ordinals_pg, pg_reset_db, pg_test_connection, pg_test_connection_pool},
+        service::{write_throttle::WriteThrottle, PgConnectionPools},
+    };
+
+    use super::{test_block_hash, ReorgSimulator, TestChainBuilder};
+
+    const INSCRIPTION_ID: &str =
+        "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0";
+
+    #[test]
+    fn links_chain_blocks() {
+        let genesis = TestChainBuilder::new("a", &Default::default())
+            .add_empty_blocks(1)
+            .build();
+        let blocks = TestChainBuilder::new("b", &genesis[0].block_identifier)
+            .add_empty_blocks(2)
+            .build();
+        assert_eq!(blocks[0].block_identifier.index, 2);
+        assert_eq!(blocks[0].block_identifier.hash, test_block_hash("b", 2));
+        assert_eq!(
+            blocks[0].parent_block_identifier,
+            genesis[0].block_identifier
+        );
+        assert_eq!(
+            blocks[1].parent_block_identifier,
+            blocks[0].block_identifier
+        );
+        assert_ne!(test_block_hash("a", 2), test_block_hash("b", 2));
+    }
+
+    #[tokio::test]
+    async fn reorgs_inscription_out_and_back_in() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/reorg_simulator".to_string();
+        drop_all_dbs(&config);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        let pg_pools = PgConnectionPools {
+            ordinals: pg_test_connection_pool(),
+            brc20: None,
+            write_throttle: Arc::new(WriteThrottle::default()),
+        };
+        let fixture = load_block_fixture(Path::new(&format!(
+            "{}/fixtures/blocks/inscription_reveal.jsonl.gz",
+            env!("CARGO_MANIFEST_DIR")
+        )))?;
+        let inscription_block = fixture[2].block_identifier.clone();
+        let fork_b = TestChainBuilder::new("b", &fixture[1].block_identifier)
+            .add_empty_blocks(2)
+            .build();
+        let fork_a = TestChainBuilder::new("a", &inscription_block)
+            .add_empty_blocks(2)
+            .build();
+
+        let mut simulator = ReorgSimulator::new(&config, &ctx);
+        simulator
+            .announce_blocks(&fixture, &config, &pg_pools, &ctx)
+            .await?;
+        {
+            let ord_client = pg_pool_client(&pg_pools.ordinals).await?;
+            let inscriptions = ordinals_pg::get_inscriptions_at_block(&ord_client, 850000).await?;
+            assert!(inscriptions.contains_key(INSCRIPTION_ID));
+        }
+
+        // A longer fork without the reveal replaces the inscription block.
+        let updates = simulator
+            .announce_blocks(&fork_b, &config, &pg_pools, &ctx)
+            .await?;
+        assert!(updates
+            .iter()
+            .any(|update| update.rolled_back.contains(&inscription_block)));
+        {
+            let ord_client = pg_pool_client(&pg_pools.ordinals).await?;
+            assert_eq!(
+                ordinals_pg::get_chain_tip_block_height(&ord_client).await?,
+                Some(850001)
+            );
+            let inscriptions = ordinals_pg::get_inscriptions_at_block(&ord_client, 850000).await?;
+            assert!(inscriptions.is_empty());
+            let indexed =
+                ordinals_pg::get_indexed_block_hashes(850000, 850001, &ord_client).await?;
+            let expected: Vec<(u64, String)> = fork_b
+                .iter()
+                .map(|block| {
+                    (
+                        block.block_identifier.index,
+                        block.block_identifier.get_hash_bytes_str().to_string(),
+                    )
+                })
+                .collect();
+            assert_eq!(indexed, expected);
+        }
+
+        // The original chain overtakes it again and the inscription block is indexed once more.
+        simulator
+            .announce_blocks(&fork_a, &config, &pg_pools, &ctx)
+            .await?;
+        {
+            let ord_client = pg_pool_client(&pg_pools.ordinals).await?;
+            assert_eq!(
+                ordinals_pg::get_chain_tip_block_height(&ord_client).await?,
+                Some(850002)
+            );
+            let inscriptions = ordinals_pg::get_inscriptions_at_block(&ord_client, 850000).await?;
+            let traversal = inscriptions
+                .get(INSCRIPTION_ID)
+                .ok_or("inscription not indexed after reorg")?;
+            assert_eq!(traversal.ordinal_number, 1971874375000000);
+            let indexed =
+                ordinals_pg::get_indexed_block_hashes(850000, 850000, &ord_client).await?;
+            assert_eq!(
+                indexed,
+                vec![(850000, inscription_block.get_hash_bytes_str().to_string())]
+            );
+        }
+
+        drop(simulator);
+        pg_reset_db(&mut pg_client).await?;
+        drop_all_dbs(&config);
+        Ok(())
+    }
+}
@@ -8,6 +8,7 @@ use chainhook_types::{
 pub struct TestBlockBuilder {
     pub height: u64,
     pub hash: String,
+    pub parent_hash: String,
     pub transactions: Vec<BitcoinTransactionData>,
 }
 
@@ -16,6 +17,8 @@ impl TestBlockBuilder {
         TestBlockBuilder {
             height: 838964,
             hash: "0x000000000000000000018ddf8a6484db391fb85c9f9ddc384f03a92729423aaf".to_string(),
+            parent_hash: "0x000000000000000000021f8b96d34c0f223281d7d825dd3588c2858c96e689d4"
+                .to_string(),
             transactions: vec![],
         }
     }
@@ -30,6 +33,11 @@ impl TestBlockBuilder {
         self
     }
 
+    pub fn parent_hash(mut self, parent_hash: String) -> Self {
+        self.parent_hash = parent_hash;
+        self
+    }
+
     pub fn transactions(mut self, transactions: Vec<BitcoinTransactionData>) -> Self {
         self.transactions = transactions;
         self
@@ -47,14 +55,14 @@ impl TestBlockBuilder {
                 hash: self.hash.clone(),
             },
             parent_block_identifier: BlockIdentifier {
-                hash: "0x000000000000000000021f8b96d34c0f223281d7d825dd3588c2858c96e689d4"
-                    .to_string(),
+                hash: self.parent_hash.clone(),
                 index: self.height - 1,
             },
             timestamp: 1712982301,
             transactions: self.transactions.clone(),
             metadata: BitcoinBlockMetadata {
                 network: BitcoinNetwork::Mainnet,
+                first_operation_sequence: None,
             },
         }
     }
@@ -2,9 +2,12 @@ use std::{path::PathBuf, thread::sleep, time::Duration};
 
 use chainhook_sdk::utils::Context;
 use rand::{rng, Rng};
-use rocksdb::{DBPinnableSlice, Options, DB};
+use rocksdb::{DBPinnableSlice, Options, WriteBatch, DB};
 
-use crate::{config::Config, try_error, try_warn};
+use crate::{config::Config, try_error, try_warn, utils::raise_open_files_limit};
+
+/// File descriptors left for sockets and other files when sizing RocksDB's `max_open_files`.
+const OPEN_FILES_HEADROOM: u64 = 256;
 
 fn get_default_blocks_db_path(base_dir: &PathBuf) -> PathBuf {
     let mut destination_path = base_dir.clone();
@@ -12,7 +15,7 @@ fn get_default_blocks_db_path(base_dir: &PathBuf) -> PathBuf {
     destination_path
 }
 
-fn rocks_db_default_options(ulimit: usize, _memory_available: usize) -> Options {
+pub(crate) fn rocks_db_default_options(ulimit: usize, _memory_available: usize) -> Options {
     let mut opts = Options::default();
     // Per rocksdb's documentation:
     // If cache_index_and_filter_blocks is false (which is default),
@@ -26,7 +29,13 @@ fn rocks_db_default_options(ulimit: usize, _memory_available: usize) -> Options
     // opts.set_write_buffer_size(64 * 1024 * 1024);
     // opts.set_blob_file_size(1 * 1024 * 1024 * 1024);
     // opts.set_target_file_size_base(64 * 1024 * 1024);
-    opts.set_max_open_files(ulimit as i32);
+    // Macs start with a soft limit of 256 open files, too low for the default `ulimit`. Raise it when allowed, and keep
+    // RocksDB under whatever limit is in effect so it doesn't fail with "Too many open files".
+    let max_open_files = match raise_open_files_limit(ulimit as u64 + OPEN_FILES_HEADROOM) {
+        Ok(limit) => ulimit.min(limit.saturating_sub(OPEN_FILES_HEADROOM).max(limit / 2) as usize),
+        Err(_) => ulimit,
+    };
+    opts.set_max_open_files(max_open_files as i32);
     opts.create_if_missing(true);
     // opts.set_allow_mmap_reads(true);
 
@@ -89,17 +98,47 @@ fn open_readwrite_blocks_db(config: &Config, _ctx: &Context) -> Result<DB, Strin
     Ok(db)
 }
 
+fn block_hash_bytes(block_hash: &str) -> Vec<u8> {
+    hex::decode(block_hash.trim_start_matches("0x")).unwrap_or_default()
+}
+
+fn block_hash_key(block_hash: &str) -> Vec<u8> {
+    let mut key = b"hash::".to_vec();
+    key.extend(block_hash_bytes(block_hash));
+    key
+}
+
+fn block_height_hash_key(block_height: u32) -> Vec<u8> {
+    let mut key = b"height_hash::".to_vec();
+    key.extend(block_height.to_be_bytes());
+    key
+}
+
 pub fn insert_entry_in_blocks(
     block_height: u32,
+    block_hash: &str,
     block_bytes: &[u8],
     update_tip: bool,
     blocks_db_rw: &DB,
     ctx: &Context,
 ) {
     let block_height_bytes = block_height.to_be_bytes();
+    // If a block from a stale fork was stored at this height, its hash must no longer resolve.
+    if let Some(previous_hash) = find_block_hash_at_block_height(block_height, blocks_db_rw) {
+        if previous_hash != block_hash.trim_start_matches("0x") {
+            let _ = blocks_db_rw.delete(block_hash_key(&previous_hash));
+        }
+    }
     let mut retries = 0;
     loop {
-        let res = blocks_db_rw.put(&block_height_bytes, block_bytes);
+        let mut batch = WriteBatch::default();
+        batch.put(&block_height_bytes, block_bytes);
+        batch.put(
+            block_height_hash_key(block_height),
+            block_hash_bytes(block_hash),
+        );
+        batch.put(block_hash_key(block_hash), &block_height_bytes);
+        let res = blocks_db_rw.write(batch);
         match res {
             Ok(_) => break,
             Err(e) => {
@@ -123,6 +162,23 @@ pub fn insert_entry_in_blocks(
     }
 }
 
+/// Returns the hash (hex, without `0x` prefix) of the block stored at a height. Blocks archived before hashes were
+/// recorded return `None`.
+pub fn find_block_hash_at_block_height(block_height: u32, blocks_db: &DB) -> Option<String> {
+    match blocks_db.get(block_height_hash_key(block_height)) {
+        Ok(Some(bytes)) => Some(hex::encode(bytes)),
+        _ => None,
+    }
+}
+
+/// Returns the height of a stored block given its hash, if that block is the one currently stored at its height.
+pub fn find_block_height_by_hash(block_hash: &str, blocks_db: &DB) -> Option<u32> {
+    match blocks_db.get(block_hash_key(block_hash)) {
+        Ok(Some(bytes)) => Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        _ => None,
+    }
+}
+
 pub fn find_last_block_inserted(blocks_db: &DB) -> u32 {
     match blocks_db.get(b"metadata::last_insert") {
         Ok(Some(bytes)) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
@@ -214,7 +270,29 @@ pub fn find_missing_blocks(blocks_db: &DB, start: u32, end: u32, ctx: &Context)
     missing_blocks
 }
 
+/// Returns the heights whose stored block hash differs from the canonical hash reported by bitcoind, i.e. blocks that
+/// belong to a stale fork and must be re-fetched. Heights without a recorded hash are not considered stale.
+pub fn find_stale_blocks(blocks_db: &DB, canonical_block_hashes: &Vec<(u32, String)>) -> Vec<u32> {
+    let mut stale_blocks = vec![];
+    for (block_height, canonical_hash) in canonical_block_hashes.iter() {
+        if let Some(stored_hash) = find_block_hash_at_block_height(*block_height, blocks_db) {
+            if stored_hash != canonical_hash.trim_start_matches("0x") {
+                stale_blocks.push(*block_height);
+            }
+        }
+    }
+    stale_blocks
+}
+
 pub fn remove_entry_from_blocks(block_height: u32, blocks_db_rw: &DB, ctx: &Context) {
+    if let Some(block_hash) = find_block_hash_at_block_height(block_height, blocks_db_rw) {
+        if let Err(e) = blocks_db_rw.delete(block_hash_key(&block_hash)) {
+            try_error!(ctx, "{}", e.to_string());
+        }
+        if let Err(e) = blocks_db_rw.delete(block_height_hash_key(block_height)) {
+            try_error!(ctx, "{}", e.to_string());
+        }
+    }
     if let Err(e) = blocks_db_rw.delete(block_height.to_be_bytes()) {
         try_error!(ctx, "{}", e.to_string());
     }
@@ -235,7 +313,7 @@ pub fn delete_blocks_in_block_range(
         .expect("unable to insert metadata");
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "test-kit"))]
 pub fn insert_standardized_block(
     block: &chainhook_types::BitcoinBlockData,
     blocks_db_rw: &DB,
@@ -255,6 +333,7 @@ pub fn insert_standardized_block(
     };
     insert_entry_in_blocks(
         block.block_identifier.index as u32,
+        &block.block_identifier.hash,
         &block_bytes,
         true,
         &blocks_db_rw,
@@ -264,3 +343,50 @@ pub fn insert_standardized_block(
         try_error!(ctx, "{}", e.to_string());
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+
+    use crate::{config::Config, db::drop_all_dbs};
+
+    use super::{
+        find_block_hash_at_block_height, find_block_height_by_hash, find_stale_blocks,
+        insert_entry_in_blocks, open_blocks_db_with_retry, remove_entry_from_blocks,
+    };
+
+    #[test]
+    fn looks_up_blocks_by_hash_and_detects_stale_forks() {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/blocks_hash_lookups".to_string();
+        drop_all_dbs(&config);
+        let blocks_db = open_blocks_db_with_retry(true, &config, &ctx);
+        let stale_hash = "0x00000000000000000001b228f9faca9e7d11fcecff9d463bd05546ff0aa4651a";
+        let canonical_hash = "00000000000000000002a0b5db2a7f8d9087464c2586b546be7bce8eb53b8187";
+
+        insert_entry_in_blocks(850000, stale_hash, &[0, 0], true, &blocks_db, &ctx);
+        assert_eq!(
+            find_block_height_by_hash(stale_hash, &blocks_db),
+            Some(850000)
+        );
+        assert_eq!(
+            find_stale_blocks(&blocks_db, &vec![(850000, canonical_hash.to_string())]),
+            vec![850000]
+        );
+
+        insert_entry_in_blocks(850000, canonical_hash, &[0, 0], true, &blocks_db, &ctx);
+        assert_eq!(find_block_height_by_hash(stale_hash, &blocks_db), None);
+        assert_eq!(
+            find_block_hash_at_block_height(850000, &blocks_db),
+            Some(canonical_hash.to_string())
+        );
+        assert!(
+            find_stale_blocks(&blocks_db, &vec![(850000, canonical_hash.to_string())]).is_empty()
+        );
+
+        remove_entry_from_blocks(850000, &blocks_db, &ctx);
+        assert_eq!(find_block_height_by_hash(canonical_hash, &blocks_db), None);
+        drop_all_dbs(&config);
+    }
+}
@@ -1,9 +1,20 @@
-use std::io::Cursor;
-use std::io::{Read, Write};
+use std::io::{Cursor, Error, ErrorKind, Read, Write};
 
 use chainhook_sdk::indexer::bitcoin::BitcoinBlockFullBreakdown;
+use chainhook_sdk::utils::hex;
 use chainhook_types::BitcoinBlockData;
 
+/// First 8 bytes of a hex encoded txid, as stored in compacted blocks.
+fn txid_prefix(txid: &str) -> std::io::Result<[u8; 8]> {
+    hex::decode(txid)
+        .ok()
+        .and_then(|bytes| bytes.get(..8).and_then(|prefix| prefix.try_into().ok()))
+        .ok_or(Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid txid {txid}"),
+        ))
+}
+
 #[derive(Debug)]
 pub struct BlockBytesCursor<'a> {
     pub bytes: &'a [u8],
@@ -218,12 +229,7 @@ impl<'a> BlockBytesCursor<'a> {
         // For each transaction:
         for tx in block.tx.iter() {
             // txid - 8 first bytes
-            let txid = {
-                let txid = hex::decode(tx.txid.to_string()).unwrap();
-                [
-                    txid[0], txid[1], txid[2], txid[3], txid[4], txid[5], txid[6], txid[7],
-                ]
-            };
+            let txid = txid_prefix(&tx.txid.to_string())?;
             buffer.write_all(&txid)?;
 
             let inputs_len = if tx.vin.len() > u16_max {
@@ -244,21 +250,22 @@ impl<'a> BlockBytesCursor<'a> {
                 let Some(input_txid) = input.txid.as_ref() else {
                     continue;
                 };
-                let txin = {
-                    let txid = hex::decode(input_txid).unwrap();
-                    [
-                        txid[0], txid[1], txid[2], txid[3], txid[4], txid[5], txid[6], txid[7],
-                    ]
-                };
+                let txin = txid_prefix(input_txid)?;
                 buffer.write_all(&txin)?;
+                let (Some(prevout), Some(vout)) = (input.prevout.as_ref(), input.vout) else {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("input {i} of tx {} is missing its prevout", tx.txid),
+                    ));
+                };
                 // txin's block height
-                let block_height = input.prevout.as_ref().unwrap().height as u32;
+                let block_height = prevout.height as u32;
                 buffer.write(&block_height.to_be_bytes())?;
                 // txin's vout index
-                let vout = input.vout.unwrap() as u16;
+                let vout = vout as u16;
                 buffer.write(&vout.to_be_bytes())?;
                 // txin's sats value
-                let sats = input.prevout.as_ref().unwrap().value.to_sat();
+                let sats = prevout.value.to_sat();
                 buffer.write(&sats.to_be_bytes())?;
             }
             // For each transaction output:
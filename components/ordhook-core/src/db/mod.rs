@@ -2,23 +2,171 @@ pub mod blocks;
 pub mod cursor;
 pub mod models;
 pub mod ordinals_pg;
+pub mod raw_transactions;
 
 use chainhook_postgres::pg_connect_with_retry;
+use deadpool_postgres::Transaction;
 
 use chainhook_sdk::utils::Context;
+use tokio_postgres::Client;
 
 use crate::{config::Config, core::meta_protocols::brc20::brc20_pg, try_info, try_warn};
 
-pub async fn migrate_dbs(config: &Config, ctx: &Context) -> Result<(), String> {
+/// Reason [migrate_dbs] failed, so callers can tell an unreachable database from one this binary can't migrate.
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    /// A query run around the migrations failed.
+    #[error("{0}")]
+    Connectivity(String),
+    /// The schema was migrated by a newer release, or a migration failed to apply.
+    #[error("{0}")]
+    Schema(String),
+}
+
+impl From<MigrationError> for String {
+    fn from(error: MigrationError) -> Self {
+        error.to_string()
+    }
+}
+
+async fn get_schema_version(pg_client: &Client) -> Result<Option<i64>, String> {
+    let row = pg_client
+        .query_one(
+            "SELECT to_regclass('pgmigrations') IS NOT NULL AS migrated",
+            &[],
+        )
+        .await
+        .map_err(|e| format!("unable to look up migrations table: {e}"))?;
+    if !row.get::<_, bool>("migrated") {
+        return Ok(None);
+    }
+    let row = pg_client
+        .query_one(
+            "SELECT COALESCE(MAX(version), 0)::BIGINT AS version FROM pgmigrations",
+            &[],
+        )
+        .await
+        .map_err(|e| format!("unable to read schema version: {e}"))?;
+    Ok(Some(row.get("version")))
+}
+
+async fn get_recorded_ordhook_version(pg_client: &Client) -> Result<Option<String>, String> {
+    let row = pg_client
+        .query_one(
+            "SELECT to_regclass('ordhook_version') IS NOT NULL AS recorded",
+            &[],
+        )
+        .await
+        .map_err(|e| format!("unable to look up ordhook version table: {e}"))?;
+    if !row.get::<_, bool>("recorded") {
+        return Ok(None);
+    }
+    let row = pg_client
+        .query_opt("SELECT version FROM ordhook_version", &[])
+        .await
+        .map_err(|e| format!("unable to read ordhook version: {e}"))?;
+    Ok(row.map(|r| r.get("version")))
+}
+
+/// Refuses to run against a schema that was migrated by a newer ordhook release. This binary would not know about the
+/// newer tables and columns, and would keep writing rows the newer schema does not expect.
+async fn check_schema_compatibility(
+    pg_client: &Client,
+    latest_migration_version: i64,
+    db_name: &str,
+    ctx: &Context,
+) -> Result<(), MigrationError> {
+    let Some(schema_version) = get_schema_version(pg_client)
+        .await
+        .map_err(MigrationError::Connectivity)?
+    else {
+        return Ok(());
+    };
+    if schema_version < latest_migration_version {
+        try_info!(
+            ctx,
+            "{db_name} DB schema is at version {schema_version}, upgrading to version {latest_migration_version}"
+        );
+    }
+    if schema_version <= latest_migration_version {
+        return Ok(());
+    }
+    let migrated_by = match get_recorded_ordhook_version(pg_client)
+        .await
+        .map_err(MigrationError::Connectivity)?
+    {
+        Some(version) => format!(" by ordhook v{version}"),
+        None => "".to_string(),
+    };
+    Err(MigrationError::Schema(format!(
+        "{db_name} DB schema is at version {schema_version}{migrated_by}, but ordhook v{} only supports schema versions \
+        up to {latest_migration_version}. Upgrade ordhook to a release that supports this schema, or point it to a \
+        database restored from a snapshot produced by this version.",
+        env!("CARGO_PKG_VERSION")
+    )))
+}
+
+/// Records the ordhook release and schema version that last migrated a database.
+async fn record_ordhook_version(
+    pg_client: &Client,
+    latest_migration_version: i64,
+) -> Result<(), String> {
+    pg_client
+        .execute(
+            "INSERT INTO ordhook_version (version, schema_version) VALUES ($1, $2)
+            ON CONFLICT (id) DO UPDATE SET version = EXCLUDED.version,
+                schema_version = EXCLUDED.schema_version, updated_at = NOW()",
+            &[
+                &env!("CARGO_PKG_VERSION"),
+                &(latest_migration_version as i32),
+            ],
+        )
+        .await
+        .map_err(|e| format!("unable to record ordhook version: {e}"))?;
+    Ok(())
+}
+
+/// Commits a pg transaction, or rolls it back when the service runs in dry-run mode.
+pub async fn pg_commit_unless_dry_run(
+    tx: Transaction<'_>,
+    config: &Config,
+    db_name: &str,
+) -> Result<(), String> {
+    if config.dry_run {
+        tx.rollback()
+            .await
+            .map_err(|e| format!("unable to roll back {db_name} pg transaction: {e}"))
+    } else {
+        tx.commit()
+            .await
+            .map_err(|e| format!("unable to commit {db_name} pg transaction: {e}"))
+    }
+}
+
+pub async fn migrate_dbs(config: &Config, ctx: &Context) -> Result<(), MigrationError> {
     {
         try_info!(ctx, "Running ordinals DB migrations");
         let mut pg_client = pg_connect_with_retry(&config.ordinals_db).await;
-        ordinals_pg::migrate(&mut pg_client).await?;
+        let latest_migration_version = ordinals_pg::latest_migration_version();
+        check_schema_compatibility(&pg_client, latest_migration_version, "Ordinals", ctx).await?;
+        ordinals_pg::migrate(&mut pg_client)
+            .await
+            .map_err(MigrationError::Schema)?;
+        record_ordhook_version(&pg_client, latest_migration_version)
+            .await
+            .map_err(MigrationError::Connectivity)?;
     }
     if let (Some(brc20_db), true) = (&config.brc20_db, config.meta_protocols.brc20) {
         try_info!(ctx, "Running brc20 DB migrations");
         let mut pg_client = pg_connect_with_retry(&brc20_db).await;
-        brc20_pg::migrate(&mut pg_client).await?;
+        let latest_migration_version = brc20_pg::latest_migration_version();
+        check_schema_compatibility(&pg_client, latest_migration_version, "BRC-20", ctx).await?;
+        brc20_pg::migrate(&mut pg_client)
+            .await
+            .map_err(MigrationError::Schema)?;
+        record_ordhook_version(&pg_client, latest_migration_version)
+            .await
+            .map_err(MigrationError::Connectivity)?;
     }
     Ok(())
 }
@@ -126,3 +274,36 @@ pub fn drop_all_dbs(config: &Config) {
         std::fs::remove_dir_all(dir_path).unwrap();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+
+    use super::{
+        check_schema_compatibility, ordinals_pg, pg_reset_db, pg_test_connection,
+        record_ordhook_version,
+    };
+
+    #[tokio::test]
+    async fn refuses_schemas_migrated_by_newer_versions() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        let latest_migration_version = ordinals_pg::latest_migration_version();
+        record_ordhook_version(&pg_client, latest_migration_version).await?;
+
+        assert!(
+            check_schema_compatibility(&pg_client, latest_migration_version, "Ordinals", &ctx)
+                .await
+                .is_ok()
+        );
+        let error =
+            check_schema_compatibility(&pg_client, latest_migration_version - 1, "Ordinals", &ctx)
+                .await
+                .unwrap_err();
+        assert!(error.contains(&format!("by ordhook v{}", env!("CARGO_PKG_VERSION"))));
+
+        pg_reset_db(&mut pg_client).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+/// Verdict a content scanner returned for an inscription. Every inscription is scanned once per scanner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbContentScan {
+    pub inscription_id: String,
+    /// Name of the scanner that returned the verdict.
+    pub scanner: String,
+    pub flagged: bool,
+    pub reason: Option<String>,
+}
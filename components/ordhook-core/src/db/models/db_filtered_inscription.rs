@@ -0,0 +1,8 @@
+/// Inscription a content policy flagged. Its consensus data is kept, only its content is no longer served.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbFilteredInscription {
+    pub inscription_id: String,
+    /// Name of the policy that flagged it.
+    pub policy: String,
+    pub reason: String,
+}
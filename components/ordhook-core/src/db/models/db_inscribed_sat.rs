@@ -0,0 +1,26 @@
+use chainhook_postgres::{types::PgNumericU64, FromPgRow};
+use tokio_postgres::Row;
+
+/// Inscription currently sitting in an output, along with the rarity of the sat that carries it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbInscribedSat {
+    pub output: String,
+    pub offset: Option<PgNumericU64>,
+    pub ordinal_number: PgNumericU64,
+    pub rarity: String,
+    pub inscription_id: String,
+    pub number: i64,
+}
+
+impl FromPgRow for DbInscribedSat {
+    fn from_pg_row(row: &Row) -> Self {
+        DbInscribedSat {
+            output: row.get("output"),
+            offset: row.get("offset"),
+            ordinal_number: row.get("ordinal_number"),
+            rarity: row.get("rarity"),
+            inscription_id: row.get("inscription_id"),
+            number: row.get("number"),
+        }
+    }
+}
@@ -1,7 +1,9 @@
+use bitcoin::hashes::{sha256, Hash};
 use chainhook_postgres::{
     types::{PgBigIntU32, PgNumericU64},
     FromPgRow,
 };
+use chainhook_sdk::utils::hex;
 use chainhook_types::{
     BlockIdentifier, OrdinalInscriptionCurseType, OrdinalInscriptionRevealData,
     TransactionIdentifier,
@@ -23,6 +25,10 @@ pub struct DbInscription {
     pub content_type: String,
     pub content_length: PgBigIntU32,
     pub content: Vec<u8>,
+    /// Set when `content` was not stored because it exceeded the configured size limit or was taken down.
+    pub content_omitted: bool,
+    /// Hex encoded SHA-256 of the content. Stored contents are kept once per hash, in the `inscription_contents` table.
+    pub content_hash: String,
     pub fee: PgNumericU64,
     pub curse_type: Option<String>,
     pub recursive: bool,
@@ -48,6 +54,7 @@ impl DbInscription {
         let mut content_type_bytes = reveal.content_type.clone().into_bytes();
         content_type_bytes.retain(|&x| x != 0);
         let content_type = String::from_utf8(content_type_bytes).unwrap();
+        let content = hex::decode(&reveal.content_bytes[2..]).unwrap();
         DbInscription {
             inscription_id: reveal.inscription_id.clone(),
             ordinal_number: PgNumericU64(reveal.ordinal_number),
@@ -61,7 +68,9 @@ impl DbInscription {
             mime_type: content_type.split(';').nth(0).unwrap().to_string(),
             content_type,
             content_length: PgBigIntU32(reveal.content_length as u32),
-            content: hex::decode(&reveal.content_bytes[2..]).unwrap(),
+            content_hash: sha256::Hash::hash(&content).to_string(),
+            content,
+            content_omitted: false,
             fee: PgNumericU64(reveal.inscription_fee),
             curse_type: reveal.curse_type.as_ref().map(|c| match c {
                 OrdinalInscriptionCurseType::DuplicateField => "duplicate_field".to_string(),
@@ -88,6 +97,15 @@ impl DbInscription {
             unbound_sequence: reveal.unbound_sequence,
         }
     }
+
+    /// Drops the content if it is larger than `max_bytes`, keeping only its hash, length and type.
+    pub fn omit_content_above(&mut self, max_bytes: u64) {
+        if self.content.len() as u64 <= max_bytes {
+            return;
+        }
+        self.content = vec![];
+        self.content_omitted = true;
+    }
 }
 
 impl FromPgRow for DbInscription {
@@ -106,6 +124,8 @@ impl FromPgRow for DbInscription {
             content_type: row.get("content_type"),
             content_length: row.get("content_length"),
             content: row.get("content"),
+            content_omitted: row.get("content_omitted"),
+            content_hash: row.get("content_hash"),
             fee: row.get("fee"),
             curse_type: row.get("curse_type"),
             recursive: row.get("recursive"),
@@ -120,3 +140,41 @@ impl FromPgRow for DbInscription {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use chainhook_types::{BlockIdentifier, TransactionIdentifier};
+
+    use crate::core::meta_protocols::brc20::test_utils::Brc20RevealBuilder;
+
+    use super::DbInscription;
+
+    #[test]
+    fn omits_content_above_limit() {
+        let mut reveal = Brc20RevealBuilder::new().build();
+        reveal.content_bytes = "0x68656c6c6f".to_string();
+        let mut inscription = DbInscription::from_reveal(
+            &reveal,
+            &BlockIdentifier {
+                index: 840_000,
+                hash: "0x00".to_string(),
+            },
+            &TransactionIdentifier {
+                hash: "0x00".to_string(),
+            },
+            0,
+            0,
+        );
+        inscription.omit_content_above(5);
+        assert!(!inscription.content_omitted);
+        assert_eq!(inscription.content, b"hello".to_vec());
+
+        inscription.omit_content_above(4);
+        assert!(inscription.content_omitted);
+        assert!(inscription.content.is_empty());
+        assert_eq!(
+            inscription.content_hash,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use chainhook_sdk::utils::hex;
 use chainhook_types::OrdinalInscriptionRevealData;
 use regex::Regex;
 
@@ -0,0 +1,7 @@
+/// Tombstone left when an operator takes down the content of an inscription, e.g. after a legal request. It has no
+/// foreign key on `inscriptions` so it outlives rollbacks, and the content stays blank if the inscription is indexed again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbInscriptionTakedown {
+    pub inscription_id: String,
+    pub reason: String,
+}
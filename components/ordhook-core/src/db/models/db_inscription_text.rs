@@ -0,0 +1,65 @@
+use chainhook_sdk::utils::hex;
+use chainhook_types::OrdinalInscriptionRevealData;
+
+/// Max number of content bytes fed to the full-text index. Postgres rejects `tsvector` values over 1MB.
+const MAX_INDEXED_TEXT_BYTES: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbInscriptionText {
+    pub inscription_id: String,
+    pub text: String,
+}
+
+impl DbInscriptionText {
+    /// Returns the searchable text of `text/*` and JSON inscriptions whose content is valid UTF-8.
+    pub fn from_reveal(reveal: &OrdinalInscriptionRevealData) -> Option<Self> {
+        let mime_type = reveal.content_type.split(';').next().unwrap_or("").trim();
+        if !mime_type.starts_with("text/") && mime_type != "application/json" {
+            return None;
+        }
+        let bytes = hex::decode(reveal.content_bytes.get(2..)?).ok()?;
+        let mut text = String::from_utf8(bytes).ok()?.replace('\0', "");
+        if text.len() > MAX_INDEXED_TEXT_BYTES {
+            let mut end = MAX_INDEXED_TEXT_BYTES;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+        }
+        if text.trim().is_empty() {
+            return None;
+        }
+        Some(DbInscriptionText {
+            inscription_id: reveal.inscription_id.clone(),
+            text,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::core::meta_protocols::brc20::test_utils::Brc20RevealBuilder;
+
+    use super::DbInscriptionText;
+
+    #[test]
+    fn extracts_searchable_text() {
+        let mut reveal = Brc20RevealBuilder::new().build();
+        reveal.content_type = "text/plain;charset=utf-8".to_string();
+        reveal.content_bytes = "0x68656c6c6f00".to_string();
+        assert_eq!(
+            Some("hello".to_string()),
+            DbInscriptionText::from_reveal(&reveal).map(|t| t.text)
+        );
+
+        reveal.content_type = "application/json".to_string();
+        assert!(DbInscriptionText::from_reveal(&reveal).is_some());
+
+        reveal.content_type = "image/png".to_string();
+        assert!(DbInscriptionText::from_reveal(&reveal).is_none());
+
+        reveal.content_type = "text/plain".to_string();
+        reveal.content_bytes = "0xfffe".to_string();
+        assert!(DbInscriptionText::from_reveal(&reveal).is_none());
+    }
+}
@@ -22,6 +22,8 @@ pub struct DbLocation {
     pub offset: Option<PgNumericU64>,
     pub prev_output: Option<String>,
     pub prev_offset: Option<PgNumericU64>,
+    /// Address the sat was held by before this location, for transfers.
+    pub prev_address: Option<String>,
     pub value: Option<PgNumericU64>,
     pub transfer_type: String,
     pub timestamp: PgBigIntU32,
@@ -48,6 +50,7 @@ impl DbLocation {
             offset: offset.map(|o| PgNumericU64(o)),
             prev_output: None,
             prev_offset: None,
+            prev_address: None,
             value: Some(PgNumericU64(reveal.inscription_output_value)),
             transfer_type: match reveal.inscriber_address {
                 Some(_) => "transferred".to_string(),
@@ -91,6 +94,7 @@ impl DbLocation {
             offset: offset.map(|o| PgNumericU64(o)),
             prev_output: Some(prev_output),
             prev_offset: prev_offset.map(|o| PgNumericU64(o)),
+            prev_address: transfer.from_address.clone(),
             value: transfer.post_transfer_output_value.map(|v| PgNumericU64(v)),
             transfer_type: match transfer.destination {
                 OrdinalInscriptionTransferDestination::Transferred(_) => "transferred".to_string(),
@@ -115,6 +119,7 @@ impl FromPgRow for DbLocation {
             offset: row.get("offset"),
             prev_output: row.get("prev_output"),
             prev_offset: row.get("prev_offset"),
+            prev_address: row.get("prev_address"),
             value: row.get("value"),
             transfer_type: row.get("transfer_type"),
             timestamp: row.get("timestamp"),
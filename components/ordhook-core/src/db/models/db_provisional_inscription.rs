@@ -0,0 +1,44 @@
+use chainhook_postgres::{
+    types::{PgBigIntU32, PgNumericU64},
+    FromPgRow,
+};
+use tokio_postgres::Row;
+
+/// Inscription revealed in a tip block the historical backfill has not indexed yet. It has no number, sat or fee until
+/// the backfill indexes its block and replaces it with a [super::DbInscription].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbProvisionalInscription {
+    pub inscription_id: String,
+    pub block_height: PgNumericU64,
+    pub block_hash: String,
+    pub tx_id: String,
+    pub tx_index: PgBigIntU32,
+    pub mime_type: String,
+    pub content_type: String,
+    pub content_length: PgBigIntU32,
+    pub content: Vec<u8>,
+    pub curse_type: Option<String>,
+    pub metaprotocol: Option<String>,
+    pub delegate: Option<String>,
+    pub timestamp: PgBigIntU32,
+}
+
+impl FromPgRow for DbProvisionalInscription {
+    fn from_pg_row(row: &Row) -> Self {
+        DbProvisionalInscription {
+            inscription_id: row.get("inscription_id"),
+            block_height: row.get("block_height"),
+            block_hash: row.get("block_hash"),
+            tx_id: row.get("tx_id"),
+            tx_index: row.get("tx_index"),
+            mime_type: row.get("mime_type"),
+            content_type: row.get("content_type"),
+            content_length: row.get("content_length"),
+            content: row.get("content"),
+            curse_type: row.get("curse_type"),
+            metaprotocol: row.get("metaprotocol"),
+            delegate: row.get("delegate"),
+            timestamp: row.get("timestamp"),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use chainhook_postgres::{types::PgNumericU64, FromPgRow};
+use tokio_postgres::Row;
+
+/// A block event waiting to be posted to the configured webhook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbWebhookDelivery {
+    pub id: i64,
+    pub block_height: PgNumericU64,
+    pub payload: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
+
+impl FromPgRow for DbWebhookDelivery {
+    fn from_pg_row(row: &Row) -> Self {
+        DbWebhookDelivery {
+            id: row.get("id"),
+            block_height: row.get("block_height"),
+            payload: row.get("payload"),
+            attempts: row.get("attempts"),
+            last_error: row.get("last_error"),
+        }
+    }
+}
@@ -1,13 +1,27 @@
+mod db_content_scan;
 mod db_current_location;
+mod db_filtered_inscription;
+mod db_inscribed_sat;
 mod db_inscription;
 mod db_inscription_recursion;
+mod db_inscription_takedown;
+mod db_inscription_text;
 mod db_inscription_parent;
 mod db_location;
+mod db_provisional_inscription;
 mod db_satoshi;
+mod db_webhook_delivery;
 
+pub use db_content_scan::DbContentScan;
 pub use db_current_location::DbCurrentLocation;
+pub use db_filtered_inscription::DbFilteredInscription;
+pub use db_inscribed_sat::DbInscribedSat;
 pub use db_inscription::DbInscription;
 pub use db_inscription_recursion::DbInscriptionRecursion;
+pub use db_inscription_takedown::DbInscriptionTakedown;
+pub use db_inscription_text::DbInscriptionText;
 pub use db_location::DbLocation;
+pub use db_provisional_inscription::DbProvisionalInscription;
 pub use db_satoshi::DbSatoshi;
+pub use db_webhook_delivery::DbWebhookDelivery;
 pub use db_inscription_parent::DbInscriptionParent;
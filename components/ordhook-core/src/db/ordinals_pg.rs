@@ -2,7 +2,7 @@ use std::collections::{BTreeMap, HashMap};
 
 use chainhook_postgres::{
     types::{PgBigIntU32, PgNumericU64},
-    utils,
+    utils, FromPgRow, PgError,
 };
 use chainhook_types::{
     bitcoin::TxIn, BitcoinBlockData, OrdinalInscriptionNumber, OrdinalOperation,
@@ -14,12 +14,14 @@ use tokio_postgres::{types::ToSql, Client};
 
 use crate::{
     core::protocol::{satoshi_numbering::TraversalResult, satoshi_tracking::WatchedSatpoint},
+    error::OrdhookError,
     utils::format_outpoint_to_watch,
 };
 
 use super::models::{
-    DbCurrentLocation, DbInscription, DbInscriptionParent, DbInscriptionRecursion, DbLocation,
-    DbSatoshi,
+    DbContentScan, DbCurrentLocation, DbFilteredInscription, DbInscribedSat, DbInscription,
+    DbInscriptionParent, DbInscriptionRecursion, DbInscriptionTakedown, DbInscriptionText,
+    DbLocation, DbProvisionalInscription, DbSatoshi, DbWebhookDelivery,
 };
 
 embed_migrations!("../../migrations/ordinals");
@@ -36,13 +38,30 @@ pub async fn migrate(client: &mut Client) -> Result<(), String> {
     };
 }
 
+/// Content column of inscriptions aliased as `i`, read through [INSCRIPTION_CONTENT_JOIN]. Omitted contents read as
+/// empty.
+const INSCRIPTION_CONTENT_COLUMN: &str = "COALESCE(ic.content, ''::bytea) AS content";
+/// Identical contents are stored once in `inscription_contents`, keyed by their hash.
+const INSCRIPTION_CONTENT_JOIN: &str = "LEFT JOIN inscription_contents AS ic
+    ON ic.content_hash = i.content_hash AND NOT i.content_omitted";
+
+/// Returns the version of the latest migration embedded in this binary.
+pub fn latest_migration_version() -> i64 {
+    migrations::runner()
+        .get_migrations()
+        .iter()
+        .map(|m| m.version() as i64)
+        .max()
+        .unwrap_or(0)
+}
+
 pub async fn get_chain_tip_block_height<T: GenericClient>(
     client: &T,
-) -> Result<Option<u64>, String> {
+) -> Result<Option<u64>, PgError> {
     let row = client
         .query_opt("SELECT block_height FROM chain_tip", &[])
         .await
-        .map_err(|e| format!("get_chain_tip_block_height: {e}"))?;
+        .map_err(|e| PgError::Query("get_chain_tip_block_height", e))?;
     let Some(row) = row else {
         return Ok(None);
     };
@@ -52,11 +71,11 @@ pub async fn get_chain_tip_block_height<T: GenericClient>(
 
 pub async fn get_highest_inscription_number<T: GenericClient>(
     client: &T,
-) -> Result<Option<i64>, String> {
+) -> Result<Option<i64>, PgError> {
     let row = client
         .query_opt("SELECT MAX(number) AS max FROM inscriptions", &[])
         .await
-        .map_err(|e| format!("get_highest_inscription_number: {e}"))?;
+        .map_err(|e| PgError::Query("get_highest_inscription_number", e))?;
     let Some(row) = row else {
         return Ok(None);
     };
@@ -66,14 +85,14 @@ pub async fn get_highest_inscription_number<T: GenericClient>(
 
 pub async fn get_highest_blessed_classic_inscription_number<T: GenericClient>(
     client: &T,
-) -> Result<Option<i64>, String> {
+) -> Result<Option<i64>, PgError> {
     let row = client
         .query_opt(
             "SELECT MAX(classic_number) AS max FROM inscriptions WHERE classic_number >= 0",
             &[],
         )
         .await
-        .map_err(|e| format!("get_highest_blessed_classic_inscription_number: {e}"))?;
+        .map_err(|e| PgError::Query("get_highest_blessed_classic_inscription_number", e))?;
     let Some(row) = row else {
         return Ok(None);
     };
@@ -83,14 +102,14 @@ pub async fn get_highest_blessed_classic_inscription_number<T: GenericClient>(
 
 pub async fn get_lowest_cursed_classic_inscription_number<T: GenericClient>(
     client: &T,
-) -> Result<Option<i64>, String> {
+) -> Result<Option<i64>, PgError> {
     let row = client
         .query_opt(
             "SELECT MIN(classic_number) AS min FROM inscriptions WHERE classic_number < 0",
             &[],
         )
         .await
-        .map_err(|e| format!("get_lowest_cursed_classic_inscription_number: {e}"))?;
+        .map_err(|e| PgError::Query("get_lowest_cursed_classic_inscription_number", e))?;
     let Some(row) = row else {
         return Ok(None);
     };
@@ -100,11 +119,11 @@ pub async fn get_lowest_cursed_classic_inscription_number<T: GenericClient>(
 
 pub async fn get_highest_unbound_inscription_sequence<T: GenericClient>(
     client: &T,
-) -> Result<Option<i64>, String> {
+) -> Result<Option<i64>, PgError> {
     let row = client
         .query_opt("SELECT MAX(unbound_sequence) AS max FROM inscriptions", &[])
         .await
-        .map_err(|e| format!("get_highest_unbound_inscription_sequence: {e}"))?;
+        .map_err(|e| PgError::Query("get_highest_unbound_inscription_sequence", e))?;
     let Some(row) = row else {
         return Ok(None);
     };
@@ -115,7 +134,7 @@ pub async fn get_highest_unbound_inscription_sequence<T: GenericClient>(
 pub async fn get_reinscriptions_for_block<T: GenericClient>(
     inscriptions_data: &mut BTreeMap<(TransactionIdentifier, usize, u64), TraversalResult>,
     client: &T,
-) -> Result<HashMap<u64, String>, String> {
+) -> Result<HashMap<u64, String>, PgError> {
     let mut ordinal_numbers = vec![];
     for (_, value) in inscriptions_data {
         if value.ordinal_number != 0 {
@@ -131,7 +150,7 @@ pub async fn get_reinscriptions_for_block<T: GenericClient>(
             &[&number_refs],
         )
         .await
-        .map_err(|e| format!("get_reinscriptions_for_block: {e}"))?;
+        .map_err(|e| PgError::Query("get_reinscriptions_for_block", e))?;
     let mut results = HashMap::new();
     for row in rows.iter() {
         let ordinal_number: PgNumericU64 = row.get("ordinal_number");
@@ -144,21 +163,21 @@ pub async fn get_reinscriptions_for_block<T: GenericClient>(
 pub async fn has_ordinal_activity_at_block<T: GenericClient>(
     client: &T,
     block_height: u64,
-) -> Result<bool, String> {
+) -> Result<bool, PgError> {
     let row = client
         .query_opt(
             "SELECT 1 FROM locations WHERE block_height = $1 LIMIT 1",
             &[&PgNumericU64(block_height)],
         )
         .await
-        .map_err(|e| format!("has_ordinal_activity_at_block: {e}"))?;
+        .map_err(|e| PgError::Query("has_ordinal_activity_at_block", e))?;
     Ok(row.is_some())
 }
 
 pub async fn get_inscriptions_at_block<T: GenericClient>(
     client: &T,
     block_height: u64,
-) -> Result<BTreeMap<String, TraversalResult>, String> {
+) -> Result<BTreeMap<String, TraversalResult>, PgError> {
     let rows = client
         .query(
             "SELECT number, classic_number, ordinal_number, inscription_id, input_index, tx_id
@@ -167,7 +186,7 @@ pub async fn get_inscriptions_at_block<T: GenericClient>(
             &[&PgNumericU64(block_height)],
         )
         .await
-        .map_err(|e| format!("get_inscriptions_at_block: {e}"))?;
+        .map_err(|e| PgError::Query("get_inscriptions_at_block", e))?;
     let mut results = BTreeMap::new();
     for row in rows.iter() {
         let inscription_number = OrdinalInscriptionNumber {
@@ -190,10 +209,377 @@ pub async fn get_inscriptions_at_block<T: GenericClient>(
     Ok(results)
 }
 
+pub async fn get_inscription_by_id<T: GenericClient>(
+    inscription_id: &str,
+    client: &T,
+) -> Result<Option<DbInscription>, PgError> {
+    let row = client
+        .query_opt(
+            &format!(
+                "SELECT i.*, {INSCRIPTION_CONTENT_COLUMN} FROM inscriptions AS i {INSCRIPTION_CONTENT_JOIN}
+                WHERE i.inscription_id = $1"
+            ),
+            &[&inscription_id],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_inscription_by_id", e))?;
+    Ok(row.map(|row| DbInscription::from_pg_row(&row)))
+}
+
+/// Returns the policy and reason an inscription was filtered for, if a content policy flagged it.
+pub async fn get_inscription_filter<T: GenericClient>(
+    inscription_id: &str,
+    client: &T,
+) -> Result<Option<DbFilteredInscription>, PgError> {
+    let row = client
+        .query_opt(
+            "SELECT * FROM filtered_inscriptions WHERE inscription_id = $1",
+            &[&inscription_id],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_inscription_filter", e))?;
+    Ok(row.map(|row| DbFilteredInscription {
+        inscription_id: row.get("inscription_id"),
+        policy: row.get("policy"),
+        reason: row.get("reason"),
+    }))
+}
+
+/// Returns the tombstone left when the content of an inscription was taken down, if any.
+pub async fn get_inscription_takedown<T: GenericClient>(
+    inscription_id: &str,
+    client: &T,
+) -> Result<Option<DbInscriptionTakedown>, PgError> {
+    let row = client
+        .query_opt(
+            "SELECT * FROM inscription_takedowns WHERE inscription_id = $1",
+            &[&inscription_id],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_inscription_takedown", e))?;
+    Ok(row.map(|row| DbInscriptionTakedown {
+        inscription_id: row.get("inscription_id"),
+        reason: row.get("reason"),
+    }))
+}
+
+/// Whether an inscription revealed in transaction `tx_id` was taken down.
+pub async fn is_transaction_taken_down<T: GenericClient>(
+    tx_id: &str,
+    client: &T,
+) -> Result<bool, PgError> {
+    let row = client
+        .query_opt(
+            "SELECT 1 FROM inscription_takedowns AS t
+            INNER JOIN inscriptions AS i ON i.inscription_id = t.inscription_id
+            WHERE i.tx_id = $1
+            LIMIT 1",
+            &[&tx_id],
+        )
+        .await
+        .map_err(|e| PgError::Query("is_transaction_taken_down", e))?;
+    Ok(row.is_some())
+}
+
+/// Inscriptions revealed in a block, in reveal order. Returns every one of them when `limit` is `None`.
+pub async fn get_inscriptions_revealed_at_block<T: GenericClient>(
+    block_height: u64,
+    limit: Option<i64>,
+    offset: i64,
+    client: &T,
+) -> Result<Vec<DbInscription>, PgError> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT i.inscription_id, i.ordinal_number, i.number, i.classic_number, i.block_height, i.block_hash,
+                    i.tx_id, i.tx_index, i.address, i.mime_type, i.content_type, i.content_length,
+                    {INSCRIPTION_CONTENT_COLUMN}, i.content_omitted, i.content_hash, i.fee, i.curse_type, i.recursive,
+                    i.input_index, i.pointer, i.metadata, i.metaprotocol, i.delegate, i.timestamp, i.charms,
+                    i.unbound_sequence
+                FROM inscriptions AS i {INSCRIPTION_CONTENT_JOIN}
+                WHERE i.block_height = $1
+                ORDER BY i.tx_index, i.number
+                LIMIT $2 OFFSET $3"
+            ),
+            &[&PgNumericU64(block_height), &limit, &offset],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_inscriptions_revealed_at_block", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| DbInscription::from_pg_row(row))
+        .collect())
+}
+
+/// Returns the inscriptions whose text content matches every word of `query`, newest first.
+pub async fn search_inscriptions<T: GenericClient>(
+    query: &str,
+    limit: i64,
+    offset: i64,
+    client: &T,
+) -> Result<Vec<DbInscription>, PgError> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT i.*, {INSCRIPTION_CONTENT_COLUMN} FROM inscription_texts AS t
+                INNER JOIN inscriptions AS i ON i.inscription_id = t.inscription_id
+                {INSCRIPTION_CONTENT_JOIN}
+                WHERE t.content_text @@ plainto_tsquery('simple', $1)
+                    AND NOT EXISTS (SELECT 1 FROM filtered_inscriptions AS f WHERE f.inscription_id = t.inscription_id)
+                ORDER BY i.number DESC
+                LIMIT $2 OFFSET $3"
+            ),
+            &[&query, &limit, &offset],
+        )
+        .await
+        .map_err(|e| PgError::Query("search_inscriptions", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| DbInscription::from_pg_row(row))
+        .collect())
+}
+
+/// Returns the oldest inscriptions revealed at or after `from_block_height` that `scanner` has not returned a verdict
+/// for yet. Omitted contents and inscriptions already filtered by another policy are skipped, and so are the ones the
+/// scanner failed on until their backoff expires: one minute after the first failure, doubling with each attempt up to a
+/// day.
+pub async fn get_unscanned_inscriptions<T: GenericClient>(
+    scanner: &str,
+    from_block_height: u64,
+    limit: i64,
+    client: &T,
+) -> Result<Vec<DbInscription>, PgError> {
+    let rows = client
+        .query(
+            &format!(
+                "SELECT i.*, {INSCRIPTION_CONTENT_COLUMN} FROM inscriptions AS i {INSCRIPTION_CONTENT_JOIN}
+                WHERE i.block_height >= $2 AND NOT i.content_omitted
+                    AND NOT EXISTS (SELECT 1 FROM content_scans AS s WHERE s.inscription_id = i.inscription_id AND s.scanner = $1)
+                    AND NOT EXISTS (SELECT 1 FROM filtered_inscriptions AS f WHERE f.inscription_id = i.inscription_id)
+                    AND NOT EXISTS (
+                        SELECT 1 FROM content_scan_failures AS e
+                        WHERE e.inscription_id = i.inscription_id AND e.scanner = $1
+                            AND e.last_attempt_at + LEAST(60 * POWER(2, e.attempts - 1), 86400) * INTERVAL '1 second' > NOW()
+                    )
+                ORDER BY i.number
+                LIMIT $3"
+            ),
+            &[&scanner, &PgNumericU64(from_block_height), &limit],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_unscanned_inscriptions", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| DbInscription::from_pg_row(row))
+        .collect())
+}
+
+/// Returns the verdicts content scanners returned for an inscription.
+pub async fn get_content_scans<T: GenericClient>(
+    inscription_id: &str,
+    client: &T,
+) -> Result<Vec<DbContentScan>, PgError> {
+    let rows = client
+        .query(
+            "SELECT * FROM content_scans WHERE inscription_id = $1 ORDER BY scanner",
+            &[&inscription_id],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_content_scans", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| DbContentScan {
+            inscription_id: row.get("inscription_id"),
+            scanner: row.get("scanner"),
+            flagged: row.get("flagged"),
+            reason: row.get("reason"),
+        })
+        .collect())
+}
+
+pub async fn get_current_locations<T: GenericClient>(
+    ordinal_numbers: &Vec<u64>,
+    client: &T,
+) -> Result<HashMap<u64, DbCurrentLocation>, PgError> {
+    let mut results = HashMap::new();
+    for chunk in ordinal_numbers.chunks(5000) {
+        let params: Vec<PgNumericU64> = chunk.iter().map(|n| PgNumericU64(*n)).collect();
+        let rows = client
+            .query(
+                "SELECT * FROM current_locations WHERE ordinal_number = ANY($1)",
+                &[&params],
+            )
+            .await
+            .map_err(|e| PgError::Query("get_current_locations", e))?;
+        for row in rows.iter() {
+            let location = DbCurrentLocation::from_pg_row(row);
+            results.insert(location.ordinal_number.0, location);
+        }
+    }
+    Ok(results)
+}
+
+/// Returns the inscriptions held by `address`, newest first, along with their location. When `at_height` is set, the
+/// holdings are those at the end of that block, as recorded in the location history.
+pub async fn get_inscriptions_held_by_address<T: GenericClient>(
+    address: &String,
+    at_height: Option<u64>,
+    limit: i64,
+    offset: i64,
+    client: &T,
+) -> Result<Vec<(DbInscription, DbCurrentLocation)>, PgError> {
+    let rows = match at_height {
+        None => client
+            .query(
+                &format!(
+                    "SELECT i.*, {INSCRIPTION_CONTENT_COLUMN}, l.block_height AS location_block_height,
+                        l.tx_id AS location_tx_id, l.tx_index AS location_tx_index, l.address AS location_address,
+                        l.output, l.\"offset\"
+                    FROM current_locations AS l
+                    INNER JOIN inscriptions AS i ON i.ordinal_number = l.ordinal_number
+                    {INSCRIPTION_CONTENT_JOIN}
+                    WHERE l.address = $1
+                    ORDER BY i.number DESC
+                    LIMIT $2 OFFSET $3"
+                ),
+                &[address, &limit, &offset],
+            )
+            .await,
+        Some(at_height) => client
+            .query(
+                &format!(
+                    "WITH candidates AS (
+                        SELECT DISTINCT ordinal_number FROM locations WHERE address = $1 AND block_height <= $2
+                    ),
+                    locations_at_height AS (
+                        SELECT DISTINCT ON (l.ordinal_number) l.*
+                        FROM locations AS l
+                        INNER JOIN candidates AS c ON c.ordinal_number = l.ordinal_number
+                        WHERE l.block_height <= $2
+                        ORDER BY l.ordinal_number, l.block_height DESC, l.tx_index DESC
+                    )
+                    SELECT i.*, {INSCRIPTION_CONTENT_COLUMN}, l.block_height AS location_block_height,
+                        l.tx_id AS location_tx_id, l.tx_index AS location_tx_index, l.address AS location_address,
+                        l.output, l.\"offset\"
+                    FROM locations_at_height AS l
+                    INNER JOIN inscriptions AS i ON i.ordinal_number = l.ordinal_number
+                    {INSCRIPTION_CONTENT_JOIN}
+                    WHERE l.address = $1 AND i.block_height <= $2
+                    ORDER BY i.number DESC
+                    LIMIT $3 OFFSET $4"
+                ),
+                &[address, &PgNumericU64(at_height), &limit, &offset],
+            )
+            .await,
+    }
+    .map_err(|e| PgError::Query("get_inscriptions_held_by_address", e))?;
+    Ok(rows.iter().map(held_inscription_from_row).collect())
+}
+
+/// Reads an inscription row joined with the location columns aliased as `location_*`.
+fn held_inscription_from_row(row: &tokio_postgres::Row) -> (DbInscription, DbCurrentLocation) {
+    let inscription = DbInscription::from_pg_row(row);
+    let location = DbCurrentLocation {
+        ordinal_number: inscription.ordinal_number,
+        block_height: row.get("location_block_height"),
+        tx_id: row.get("location_tx_id"),
+        tx_index: row.get("location_tx_index"),
+        address: row.get("location_address"),
+        output: row.get("output"),
+        offset: row.get("offset"),
+    };
+    (inscription, location)
+}
+
+/// Returns the inscriptions currently held by each of `outputs`, formatted as `txid:vout`, ordered by output and offset.
+pub async fn get_inscribed_sats_at_outputs<T: GenericClient>(
+    outputs: &Vec<String>,
+    client: &T,
+) -> Result<Vec<DbInscribedSat>, PgError> {
+    let mut results = vec![];
+    for chunk in outputs.chunks(5000) {
+        let rows = client
+            .query(
+                "SELECT l.output, l.\"offset\", l.ordinal_number, s.rarity, i.inscription_id, i.number
+                FROM current_locations AS l
+                INNER JOIN satoshis AS s ON s.ordinal_number = l.ordinal_number
+                INNER JOIN inscriptions AS i ON i.ordinal_number = l.ordinal_number
+                WHERE l.output = ANY($1)
+                ORDER BY l.output, l.\"offset\", i.number",
+                &[&chunk],
+            )
+            .await
+            .map_err(|e| PgError::Query("get_inscribed_sats_at_outputs", e))?;
+        results.extend(rows.iter().map(|row| DbInscribedSat::from_pg_row(row)));
+    }
+    Ok(results)
+}
+
+/// Returns every location recorded for a sat, oldest first.
+pub async fn get_locations_for_ordinal_number<T: GenericClient>(
+    ordinal_number: u64,
+    client: &T,
+) -> Result<Vec<DbLocation>, PgError> {
+    let rows = client
+        .query(
+            "SELECT * FROM locations WHERE ordinal_number = $1 ORDER BY block_height ASC, tx_index ASC",
+            &[&PgNumericU64(ordinal_number)],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_locations_for_ordinal_number", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| DbLocation::from_pg_row(row))
+        .collect())
+}
+
+/// Returns every location recorded at a block, reveals included, in transaction order.
+pub async fn get_locations_at_block<T: GenericClient>(
+    block_height: u64,
+    client: &T,
+) -> Result<Vec<DbLocation>, PgError> {
+    let rows = client
+        .query(
+            "SELECT * FROM locations WHERE block_height = $1 ORDER BY tx_index ASC",
+            &[&PgNumericU64(block_height)],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_locations_at_block", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| DbLocation::from_pg_row(row))
+        .collect())
+}
+
+/// Returns the parents of the inscriptions revealed at a block, keyed by child inscription id.
+pub async fn get_inscription_parents_at_block<T: GenericClient>(
+    block_height: u64,
+    client: &T,
+) -> Result<HashMap<String, Vec<String>>, PgError> {
+    let rows = client
+        .query(
+            "SELECT p.inscription_id, p.parent_inscription_id
+            FROM inscription_parents AS p
+            INNER JOIN inscriptions AS i ON i.inscription_id = p.inscription_id
+            WHERE i.block_height = $1",
+            &[&PgNumericU64(block_height)],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_inscription_parents_at_block", e))?;
+    let mut results: HashMap<String, Vec<String>> = HashMap::new();
+    for row in rows.iter() {
+        results
+            .entry(row.get("inscription_id"))
+            .or_default()
+            .push(row.get("parent_inscription_id"));
+    }
+    Ok(results)
+}
+
 pub async fn get_inscribed_satpoints_at_tx_inputs<T: GenericClient>(
     inputs: &Vec<TxIn>,
     client: &T,
-) -> Result<HashMap<usize, Vec<WatchedSatpoint>>, String> {
+) -> Result<HashMap<usize, Vec<WatchedSatpoint>>, PgError> {
     let mut results = HashMap::new();
     for chunk in inputs.chunks(500) {
         let outpoints: Vec<(String, String)> = chunk
@@ -218,7 +604,7 @@ pub async fn get_inscribed_satpoints_at_tx_inputs<T: GenericClient>(
             .query(
                 &format!(
                     "WITH inputs (vin, output) AS (VALUES {})
-                    SELECT i.vin, l.ordinal_number, l.\"offset\"
+                    SELECT i.vin, l.ordinal_number, l.\"offset\", l.address
                     FROM current_locations AS l
                     INNER JOIN inputs AS i ON i.output = l.output",
                     utils::multi_row_query_param_str(chunk.len(), 2)
@@ -226,7 +612,7 @@ pub async fn get_inscribed_satpoints_at_tx_inputs<T: GenericClient>(
                 &params,
             )
             .await
-            .map_err(|e| format!("get_inscriptions_at_tx_inputs: {e}"))?;
+            .map_err(|e| PgError::Query("get_inscriptions_at_tx_inputs", e))?;
         for row in rows.iter() {
             let vin: String = row.get("vin");
             let vin_key = vin.parse::<usize>().unwrap();
@@ -236,6 +622,7 @@ pub async fn get_inscribed_satpoints_at_tx_inputs<T: GenericClient>(
             entry.push(WatchedSatpoint {
                 ordinal_number: ordinal_number.0,
                 offset: offset.0,
+                address: row.get("address"),
             });
         }
     }
@@ -245,11 +632,12 @@ pub async fn get_inscribed_satpoints_at_tx_inputs<T: GenericClient>(
 async fn insert_inscriptions<T: GenericClient>(
     inscriptions: &Vec<DbInscription>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if inscriptions.len() == 0 {
         return Ok(());
     }
     for chunk in inscriptions.chunks(500) {
+        insert_inscription_contents(chunk, client).await?;
         let mut params: Vec<&(dyn ToSql + Sync)> = vec![];
         for row in chunk.iter() {
             params.push(&row.inscription_id);
@@ -264,7 +652,8 @@ async fn insert_inscriptions<T: GenericClient>(
             params.push(&row.mime_type);
             params.push(&row.content_type);
             params.push(&row.content_length);
-            params.push(&row.content);
+            params.push(&row.content_omitted);
+            params.push(&row.content_hash);
             params.push(&row.fee);
             params.push(&row.curse_type);
             params.push(&row.recursive);
@@ -281,22 +670,85 @@ async fn insert_inscriptions<T: GenericClient>(
             .query(
                 &format!("INSERT INTO inscriptions
                     (inscription_id, ordinal_number, number, classic_number, block_height, block_hash, tx_id, tx_index, address,
-                    mime_type, content_type, content_length, content, fee, curse_type, recursive, input_index, pointer, metadata,
-                    metaprotocol, delegate, timestamp, charms, unbound_sequence)
+                    mime_type, content_type, content_length, content_omitted, content_hash, fee, curse_type, recursive,
+                    input_index, pointer, metadata, metaprotocol, delegate, timestamp, charms, unbound_sequence)
                     VALUES {}
-                    ON CONFLICT (number) DO NOTHING", utils::multi_row_query_param_str(chunk.len(), 24)),
+                    ON CONFLICT (number) DO NOTHING", utils::multi_row_query_param_str(chunk.len(), 25)),
                 &params,
             )
             .await
-            .map_err(|e| format!("insert_inscriptions: {e}"))?;
+            .map_err(|e| PgError::Query("insert_inscriptions", e))?;
+        // Inscriptions indexed again after a rollback keep their content blank if it was taken down.
+        let inscription_ids: Vec<&String> = chunk.iter().map(|row| &row.inscription_id).collect();
+        let rows = client
+            .query(
+                "UPDATE inscriptions AS i SET content_omitted = TRUE
+                FROM inscription_takedowns AS t
+                WHERE t.inscription_id = i.inscription_id AND i.inscription_id = ANY($1)
+                RETURNING i.content_hash",
+                &[&inscription_ids],
+            )
+            .await
+            .map_err(|e| PgError::Query("insert_inscriptions", e))?;
+        let content_hashes: Vec<String> = rows.iter().map(|row| row.get("content_hash")).collect();
+        delete_unreferenced_contents(&content_hashes, client).await?;
+    }
+    Ok(())
+}
+
+/// Stores the contents of `inscriptions` that were not omitted, once per content hash. Collections inscribing the same
+/// bytes thousands of times share a single row.
+async fn insert_inscription_contents<T: GenericClient>(
+    inscriptions: &[DbInscription],
+    client: &T,
+) -> Result<(), PgError> {
+    let contents: BTreeMap<&String, &Vec<u8>> = inscriptions
+        .iter()
+        .filter(|row| !row.content_omitted)
+        .map(|row| (&row.content_hash, &row.content))
+        .collect();
+    if contents.is_empty() {
+        return Ok(());
     }
+    let content_hashes: Vec<&String> = contents.keys().copied().collect();
+    let contents: Vec<&Vec<u8>> = contents.values().copied().collect();
+    client
+        .query(
+            "INSERT INTO inscription_contents (content_hash, content)
+            SELECT * FROM UNNEST($1::text[], $2::bytea[])
+            ON CONFLICT (content_hash) DO NOTHING",
+            &[&content_hashes, &contents],
+        )
+        .await
+        .map_err(|e| PgError::Query("insert_inscription_contents", e))?;
+    Ok(())
+}
+
+/// Deletes the stored contents among `content_hashes` that no inscription serves anymore.
+async fn delete_unreferenced_contents<T: GenericClient>(
+    content_hashes: &Vec<String>,
+    client: &T,
+) -> Result<(), PgError> {
+    if content_hashes.is_empty() {
+        return Ok(());
+    }
+    client
+        .execute(
+            "DELETE FROM inscription_contents AS ic
+            WHERE ic.content_hash = ANY($1) AND NOT EXISTS (
+                SELECT 1 FROM inscriptions AS i WHERE i.content_hash = ic.content_hash AND NOT i.content_omitted
+            )",
+            &[content_hashes],
+        )
+        .await
+        .map_err(|e| PgError::Query("delete_unreferenced_contents", e))?;
     Ok(())
 }
 
 async fn insert_inscription_recursions<T: GenericClient>(
     inscription_recursions: &Vec<DbInscriptionRecursion>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if inscription_recursions.len() == 0 {
         return Ok(());
     }
@@ -318,15 +770,177 @@ async fn insert_inscription_recursions<T: GenericClient>(
                 &params,
             )
             .await
-            .map_err(|e| format!("insert_inscription_recursions: {e}"))?;
+            .map_err(|e| PgError::Query("insert_inscription_recursions", e))?;
     }
     Ok(())
 }
 
+/// Adds the text and JSON inscriptions revealed in a block to the full-text search index.
+pub async fn insert_inscription_texts<T: GenericClient>(
+    block: &BitcoinBlockData,
+    client: &T,
+) -> Result<(), PgError> {
+    let texts: Vec<DbInscriptionText> = block
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.metadata.ordinal_operations.iter())
+        .filter_map(|operation| match operation {
+            OrdinalOperation::InscriptionRevealed(reveal) => DbInscriptionText::from_reveal(reveal),
+            OrdinalOperation::InscriptionTransferred(_) => None,
+        })
+        .collect();
+    for chunk in texts.chunks(500) {
+        let inscription_ids: Vec<&String> = chunk.iter().map(|t| &t.inscription_id).collect();
+        let contents: Vec<&String> = chunk.iter().map(|t| &t.text).collect();
+        client
+            .query(
+                "INSERT INTO inscription_texts (inscription_id, content_text)
+                SELECT k.inscription_id, to_tsvector('simple', k.content)
+                FROM UNNEST($1::text[], $2::text[]) AS k(inscription_id, content)
+                WHERE NOT EXISTS (SELECT 1 FROM inscription_takedowns AS d WHERE d.inscription_id = k.inscription_id)
+                ON CONFLICT (inscription_id) DO NOTHING",
+                &[&inscription_ids, &contents],
+            )
+            .await
+            .map_err(|e| PgError::Query("insert_inscription_texts", e))?;
+    }
+    Ok(())
+}
+
+/// Marks inscriptions flagged by content policies as filtered.
+pub async fn insert_filtered_inscriptions<T: GenericClient>(
+    filtered: &Vec<DbFilteredInscription>,
+    client: &T,
+) -> Result<(), PgError> {
+    for chunk in filtered.chunks(500) {
+        let inscription_ids: Vec<&String> = chunk.iter().map(|f| &f.inscription_id).collect();
+        let policies: Vec<&String> = chunk.iter().map(|f| &f.policy).collect();
+        let reasons: Vec<&String> = chunk.iter().map(|f| &f.reason).collect();
+        client
+            .query(
+                "INSERT INTO filtered_inscriptions (inscription_id, policy, reason)
+                SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[])
+                ON CONFLICT (inscription_id) DO NOTHING",
+                &[&inscription_ids, &policies, &reasons],
+            )
+            .await
+            .map_err(|e| PgError::Query("insert_filtered_inscriptions", e))?;
+    }
+    Ok(())
+}
+
+/// Stores the verdict of a content scanner. Verdicts are final, scanning an inscription again keeps the first one.
+pub async fn insert_content_scan<T: GenericClient>(
+    scan: &DbContentScan,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "INSERT INTO content_scans (inscription_id, scanner, flagged, reason)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (inscription_id, scanner) DO NOTHING",
+            &[
+                &scan.inscription_id,
+                &scan.scanner,
+                &scan.flagged,
+                &scan.reason,
+            ],
+        )
+        .await
+        .map_err(|e| PgError::Query("insert_content_scan", e))?;
+    Ok(())
+}
+
+/// Records a failed attempt of `scanner` at an inscription, see `get_unscanned_inscriptions` for the retry backoff.
+/// Returns the number of attempts so far.
+pub async fn insert_content_scan_failure<T: GenericClient>(
+    inscription_id: &str,
+    scanner: &str,
+    error: &str,
+    client: &T,
+) -> Result<i32, PgError> {
+    let row = client
+        .query_one(
+            "INSERT INTO content_scan_failures (inscription_id, scanner, attempts, error)
+            VALUES ($1, $2, 1, $3)
+            ON CONFLICT (inscription_id, scanner) DO UPDATE SET
+                attempts = content_scan_failures.attempts + 1,
+                error = EXCLUDED.error,
+                last_attempt_at = NOW()
+            RETURNING attempts",
+            &[&inscription_id, &scanner, &error],
+        )
+        .await
+        .map_err(|e| PgError::Query("insert_content_scan_failure", e))?;
+    Ok(row.get("attempts"))
+}
+
+/// Forgets the failed attempts of `scanner` at an inscription once it returned a verdict.
+pub async fn delete_content_scan_failure<T: GenericClient>(
+    inscription_id: &str,
+    scanner: &str,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "DELETE FROM content_scan_failures WHERE inscription_id = $1 AND scanner = $2",
+            &[&inscription_id, &scanner],
+        )
+        .await
+        .map_err(|e| PgError::Query("delete_content_scan_failure", e))?;
+    Ok(())
+}
+
+/// Records a takedown tombstone and blanks the stored content of the inscription, in the index, the provisional reveals
+/// and the full-text search index. Numbers, locations, content type and length are left untouched, and the hash of the
+/// blanked content is kept so the content can still be matched against a blocklist. The stored bytes are deleted unless
+/// another inscription with the same content still serves them. Returns the reveal txid of the inscription if it is
+/// indexed.
+pub async fn take_down_inscription_content<T: GenericClient>(
+    takedown: &DbInscriptionTakedown,
+    client: &T,
+) -> Result<Option<String>, PgError> {
+    client
+        .execute(
+            "INSERT INTO inscription_takedowns (inscription_id, reason) VALUES ($1, $2)
+            ON CONFLICT (inscription_id) DO UPDATE SET reason = EXCLUDED.reason",
+            &[&takedown.inscription_id, &takedown.reason],
+        )
+        .await
+        .map_err(|e| PgError::Query("take_down_inscription_content", e))?;
+    client
+        .execute(
+            "UPDATE provisional_inscriptions SET content = ''::bytea WHERE inscription_id = $1",
+            &[&takedown.inscription_id],
+        )
+        .await
+        .map_err(|e| PgError::Query("take_down_inscription_content", e))?;
+    client
+        .execute(
+            "DELETE FROM inscription_texts WHERE inscription_id = $1",
+            &[&takedown.inscription_id],
+        )
+        .await
+        .map_err(|e| PgError::Query("take_down_inscription_content", e))?;
+    let row = client
+        .query_opt(
+            "UPDATE inscriptions SET content_omitted = TRUE
+            WHERE inscription_id = $1
+            RETURNING tx_id, content_hash",
+            &[&takedown.inscription_id],
+        )
+        .await
+        .map_err(|e| PgError::Query("take_down_inscription_content", e))?;
+    if let Some(row) = &row {
+        delete_unreferenced_contents(&vec![row.get("content_hash")], client).await?;
+    }
+    Ok(row.map(|row| row.get("tx_id")))
+}
+
 async fn insert_inscription_parents<T: GenericClient>(
     inscription_parents: &Vec<DbInscriptionParent>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if inscription_parents.len() == 0 {
         return Ok(());
     }
@@ -348,7 +962,7 @@ async fn insert_inscription_parents<T: GenericClient>(
                 &params,
             )
             .await
-            .map_err(|e| format!("insert_inscription_parents: {e}"))?;
+            .map_err(|e| PgError::Query("insert_inscription_parents", e))?;
     }
     Ok(())
 }
@@ -356,7 +970,7 @@ async fn insert_inscription_parents<T: GenericClient>(
 async fn insert_locations<T: GenericClient>(
     locations: &Vec<DbLocation>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if locations.len() == 0 {
         return Ok(());
     }
@@ -373,6 +987,7 @@ async fn insert_locations<T: GenericClient>(
             params.push(&row.offset);
             params.push(&row.prev_output);
             params.push(&row.prev_offset);
+            params.push(&row.prev_address);
             params.push(&row.value);
             params.push(&row.transfer_type);
             params.push(&row.timestamp);
@@ -384,7 +999,7 @@ async fn insert_locations<T: GenericClient>(
                 &format!(
                     "WITH location_inserts AS (
                         INSERT INTO locations (ordinal_number, block_height, tx_index, tx_id, block_hash, address, output,
-                            \"offset\", prev_output, prev_offset, value, transfer_type, timestamp)
+                            \"offset\", prev_output, prev_offset, prev_address, value, transfer_type, timestamp)
                         VALUES {}
                         ON CONFLICT (ordinal_number, block_height, tx_index) DO NOTHING
                         RETURNING ordinal_number, block_height, block_hash, tx_index
@@ -433,12 +1048,12 @@ async fn insert_locations<T: GenericClient>(
                             FROM moved_inscriptions
                         )
                         ON CONFLICT (block_height, block_transfer_index) DO NOTHING",
-                    utils::multi_row_query_param_str(chunk.len(), 13)
+                    utils::multi_row_query_param_str(chunk.len(), 14)
                 ),
                 &params,
             )
             .await
-            .map_err(|e| format!("insert_locations: {e}"))?;
+            .map_err(|e| PgError::Query("insert_locations", e))?;
     }
     Ok(())
 }
@@ -446,7 +1061,7 @@ async fn insert_locations<T: GenericClient>(
 async fn insert_satoshis<T: GenericClient>(
     satoshis: &Vec<DbSatoshi>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if satoshis.len() == 0 {
         return Ok(());
     }
@@ -469,7 +1084,7 @@ async fn insert_satoshis<T: GenericClient>(
                 &params,
             )
             .await
-            .map_err(|e| format!("insert_satoshis: {e}"))?;
+            .map_err(|e| PgError::Query("insert_satoshis", e))?;
     }
     Ok(())
 }
@@ -477,7 +1092,7 @@ async fn insert_satoshis<T: GenericClient>(
 async fn insert_current_locations<T: GenericClient>(
     current_locations: &HashMap<PgNumericU64, DbCurrentLocation>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     let moved_sats: Vec<&PgNumericU64> = current_locations.keys().collect();
     let new_locations: Vec<&DbCurrentLocation> = current_locations.values().collect();
     // Deduct counts from previous owners
@@ -501,7 +1116,7 @@ async fn insert_current_locations<T: GenericClient>(
                 &[&c],
             )
             .await
-            .map_err(|e| format!("insert_current_locations: {e}"))?;
+            .map_err(|e| PgError::Query("insert_current_locations", e))?;
     }
     // Insert locations
     for chunk in new_locations.chunks(500) {
@@ -536,7 +1151,7 @@ async fn insert_current_locations<T: GenericClient>(
                 &params,
             )
             .await
-            .map_err(|e| format!("insert_current_locations: {e}"))?;
+            .map_err(|e| PgError::Query("insert_current_locations", e))?;
     }
     // Update owner counts
     for chunk in moved_sats.chunks(500) {
@@ -555,7 +1170,7 @@ async fn insert_current_locations<T: GenericClient>(
                 &[&c],
             )
             .await
-            .map_err(|e| format!("insert_current_locations: {e}"))?;
+            .map_err(|e| PgError::Query("insert_current_locations", e))?;
     }
     Ok(())
 }
@@ -563,7 +1178,7 @@ async fn insert_current_locations<T: GenericClient>(
 async fn update_mime_type_counts<T: GenericClient>(
     counts: &HashMap<String, i32>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if counts.len() == 0 {
         return Ok(());
     }
@@ -582,14 +1197,14 @@ async fn update_mime_type_counts<T: GenericClient>(
             &params,
         )
         .await
-        .map_err(|e| format!("update_mime_type_counts: {e}"))?;
+        .map_err(|e| PgError::Query("update_mime_type_counts", e))?;
     Ok(())
 }
 
 async fn update_sat_rarity_counts<T: GenericClient>(
     counts: &HashMap<String, i32>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if counts.len() == 0 {
         return Ok(());
     }
@@ -608,14 +1223,14 @@ async fn update_sat_rarity_counts<T: GenericClient>(
             &params,
         )
         .await
-        .map_err(|e| format!("update_sat_rarity_counts: {e}"))?;
+        .map_err(|e| PgError::Query("update_sat_rarity_counts", e))?;
     Ok(())
 }
 
 async fn update_inscription_type_counts<T: GenericClient>(
     counts: &HashMap<String, i32>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if counts.len() == 0 {
         return Ok(());
     }
@@ -634,14 +1249,14 @@ async fn update_inscription_type_counts<T: GenericClient>(
             &params,
         )
         .await
-        .map_err(|e| format!("update_inscription_type_counts: {e}"))?;
+        .map_err(|e| PgError::Query("update_inscription_type_counts", e))?;
     Ok(())
 }
 
 async fn update_genesis_address_counts<T: GenericClient>(
     counts: &HashMap<String, i32>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if counts.len() == 0 {
         return Ok(());
     }
@@ -660,14 +1275,14 @@ async fn update_genesis_address_counts<T: GenericClient>(
             &params,
         )
         .await
-        .map_err(|e| format!("update_genesis_address_counts: {e}"))?;
+        .map_err(|e| PgError::Query("update_genesis_address_counts", e))?;
     Ok(())
 }
 
 async fn update_recursive_counts<T: GenericClient>(
     counts: &HashMap<bool, i32>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     if counts.len() == 0 {
         return Ok(());
     }
@@ -676,67 +1291,328 @@ async fn update_recursive_counts<T: GenericClient>(
         params.push(key);
         params.push(value);
     }
-    client
-        .query(
-            &format!(
-                "INSERT INTO counts_by_recursive (recursive, count) VALUES {}
-                ON CONFLICT (recursive) DO UPDATE SET count = counts_by_recursive.count + EXCLUDED.count",
-                utils::multi_row_query_param_str(counts.len(), 2)
-            ),
-            &params,
+    client
+        .query(
+            &format!(
+                "INSERT INTO counts_by_recursive (recursive, count) VALUES {}
+                ON CONFLICT (recursive) DO UPDATE SET count = counts_by_recursive.count + EXCLUDED.count",
+                utils::multi_row_query_param_str(counts.len(), 2)
+            ),
+            &params,
+        )
+        .await
+        .map_err(|e| PgError::Query("update_recursive_counts", e))?;
+    Ok(())
+}
+
+async fn update_counts_by_block<T: GenericClient>(
+    block_height: u64,
+    block_hash: &String,
+    inscription_count: usize,
+    timestamp: u32,
+    client: &T,
+) -> Result<(), PgError> {
+    if inscription_count == 0 {
+        return Ok(());
+    }
+    client
+        .query(
+        "WITH prev_entry AS (
+                SELECT inscription_count_accum
+                FROM counts_by_block
+                WHERE block_height < $1
+                ORDER BY block_height DESC
+                LIMIT 1
+            )
+            INSERT INTO counts_by_block (block_height, block_hash, inscription_count, inscription_count_accum, timestamp)
+            VALUES ($1, $2, $3, COALESCE((SELECT inscription_count_accum FROM prev_entry), 0) + $3, $4)",
+            &[&PgNumericU64(block_height), block_hash, &(inscription_count as i32), &PgBigIntU32(timestamp)],
+        )
+        .await
+        .map_err(|e| PgError::Query("update_counts_by_block", e))?;
+    Ok(())
+}
+
+async fn insert_indexed_block<T: GenericClient>(
+    block_height: u64,
+    block_hash: &String,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "INSERT INTO indexed_blocks (block_height, block_hash) VALUES ($1, $2)
+            ON CONFLICT (block_height) DO UPDATE SET block_hash = EXCLUDED.block_hash",
+            &[&PgNumericU64(block_height), block_hash],
+        )
+        .await
+        .map_err(|e| PgError::Query("insert_indexed_block", e))?;
+    Ok(())
+}
+
+/// Numbers the operations of an indexed block right after those of the closest lower block that has been numbered, and
+/// returns the sequence number of its first operation. Indexes upgraded from a version without sequences start at 0.
+pub async fn assign_block_operation_sequence<T: GenericClient>(
+    block_height: u64,
+    operation_count: u64,
+    client: &T,
+) -> Result<u64, PgError> {
+    let row = client
+        .query_one(
+            "UPDATE indexed_blocks SET operation_count = $2, first_operation_sequence = COALESCE(
+                (
+                    SELECT first_operation_sequence + operation_count FROM indexed_blocks
+                    WHERE block_height < $1 AND first_operation_sequence IS NOT NULL
+                    ORDER BY block_height DESC LIMIT 1
+                ),
+                0
+            )
+            WHERE block_height = $1
+            RETURNING first_operation_sequence",
+            &[&PgNumericU64(block_height), &PgNumericU64(operation_count)],
+        )
+        .await
+        .map_err(|e| PgError::Query("assign_block_operation_sequence", e))?;
+    let first_operation_sequence: PgNumericU64 = row.get("first_operation_sequence");
+    Ok(first_operation_sequence.0)
+}
+
+/// Returns the height and hash of the block holding the operation with sequence number `sequence`, along with the
+/// sequence of its first operation.
+pub async fn get_block_by_operation_sequence<T: GenericClient>(
+    sequence: u64,
+    client: &T,
+) -> Result<Option<(u64, String, u64)>, PgError> {
+    let row = client
+        .query_opt(
+            "SELECT block_height, block_hash, first_operation_sequence FROM indexed_blocks
+            WHERE first_operation_sequence <= $1 AND first_operation_sequence + operation_count > $1
+            ORDER BY first_operation_sequence DESC
+            LIMIT 1",
+            &[&PgNumericU64(sequence)],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_block_by_operation_sequence", e))?;
+    Ok(row.map(|row| {
+        let block_height: PgNumericU64 = row.get("block_height");
+        let first_operation_sequence: PgNumericU64 = row.get("first_operation_sequence");
+        (
+            block_height.0,
+            row.get("block_hash"),
+            first_operation_sequence.0,
+        )
+    }))
+}
+
+/// Returns the height and hash (without `0x` prefix) of every indexed block in `from..=to`, ordered by height.
+pub async fn get_indexed_block_hashes<T: GenericClient>(
+    from: u64,
+    to: u64,
+    client: &T,
+) -> Result<Vec<(u64, String)>, PgError> {
+    let rows = client
+        .query(
+            "SELECT block_height, block_hash FROM indexed_blocks
+            WHERE block_height BETWEEN $1 AND $2
+            ORDER BY block_height",
+            &[&PgNumericU64(from), &PgNumericU64(to)],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_indexed_block_hashes", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let block_height: PgNumericU64 = row.get("block_height");
+            (block_height.0, row.get("block_hash"))
+        })
+        .collect())
+}
+
+/// Returns up to `limit` heights in `from..=to` that have no `indexed_blocks` row, in ascending order. Indexes upgraded
+/// from a version without `indexed_blocks` only have rows for the blocks that revealed inscriptions.
+pub async fn get_unrecorded_indexed_block_heights<T: GenericClient>(
+    from: u64,
+    to: u64,
+    limit: i64,
+    client: &T,
+) -> Result<Vec<u64>, PgError> {
+    let rows = client
+        .query(
+            "SELECT h FROM generate_series($1::bigint, $2::bigint) AS h
+            WHERE NOT EXISTS (SELECT 1 FROM indexed_blocks WHERE block_height = h)
+            ORDER BY h
+            LIMIT $3",
+            &[&(from as i64), &(to as i64), &limit],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_unrecorded_indexed_block_heights", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| row.get::<_, i64>("h") as u64)
+        .collect())
+}
+
+/// Records the hash (without `0x` prefix) of an already indexed block. A row written by the indexer in the meantime is
+/// kept.
+pub async fn insert_backfilled_indexed_block<T: GenericClient>(
+    block_height: u64,
+    block_hash: &String,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "INSERT INTO indexed_blocks (block_height, block_hash) VALUES ($1, $2)
+            ON CONFLICT (block_height) DO NOTHING",
+            &[&PgNumericU64(block_height), block_hash],
+        )
+        .await
+        .map_err(|e| PgError::Query("insert_backfilled_indexed_block", e))?;
+    Ok(())
+}
+
+/// Returns `true` if the block with this hash (without `0x` prefix) is part of the canonical chain we have indexed.
+pub async fn is_block_hash_indexed<T: GenericClient>(
+    block_hash: &str,
+    client: &T,
+) -> Result<bool, PgError> {
+    let row = client
+        .query_opt(
+            "SELECT 1 FROM indexed_blocks WHERE block_hash = $1",
+            &[&block_hash],
+        )
+        .await
+        .map_err(|e| PgError::Query("is_block_hash_indexed", e))?;
+    Ok(row.is_some())
+}
+
+pub async fn update_chain_tip<T: GenericClient>(
+    block_height: u64,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "UPDATE chain_tip SET block_height = $1",
+            &[&PgNumericU64(block_height)],
+        )
+        .await
+        .map_err(|e| PgError::Query("update_chain_tip", e))?;
+    Ok(())
+}
+
+/// Stores the inscriptions revealed in a live block received before the historical backfill reached it. These rows have no
+/// inscription numbers or sat positions yet and are superseded by [insert_block] once the backfill catches up.
+pub async fn insert_provisional_block<T: GenericClient>(
+    block: &BitcoinBlockData,
+    client: &T,
+) -> Result<(), PgError> {
+    // A re-org may replace a provisional block at the same height.
+    client
+        .query(
+            "DELETE FROM provisional_inscriptions WHERE block_height = $1",
+            &[&PgNumericU64(block.block_identifier.index)],
+        )
+        .await
+        .map_err(|e| PgError::Query("insert_provisional_block", e))?;
+    let mut inscriptions = vec![];
+    for (tx_index, tx) in block.transactions.iter().enumerate() {
+        for operation in tx.metadata.ordinal_operations.iter() {
+            if let OrdinalOperation::InscriptionRevealed(reveal) = operation {
+                inscriptions.push(DbInscription::from_reveal(
+                    reveal,
+                    &block.block_identifier,
+                    &tx.transaction_identifier,
+                    tx_index,
+                    block.timestamp,
+                ));
+            }
+        }
+    }
+    for chunk in inscriptions.chunks(500) {
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![];
+        for row in chunk.iter() {
+            params.push(&row.inscription_id);
+            params.push(&row.block_height);
+            params.push(&row.block_hash);
+            params.push(&row.tx_id);
+            params.push(&row.tx_index);
+            params.push(&row.input_index);
+            params.push(&row.mime_type);
+            params.push(&row.content_type);
+            params.push(&row.content_length);
+            params.push(&row.content);
+            params.push(&row.curse_type);
+            params.push(&row.metaprotocol);
+            params.push(&row.delegate);
+            params.push(&row.timestamp);
+        }
+        client
+            .query(
+                &format!("INSERT INTO provisional_inscriptions
+                    (inscription_id, block_height, block_hash, tx_id, tx_index, input_index, mime_type, content_type,
+                    content_length, content, curse_type, metaprotocol, delegate, timestamp)
+                    VALUES {}
+                    ON CONFLICT (inscription_id) DO NOTHING", utils::multi_row_query_param_str(chunk.len(), 14)),
+                &params,
+            )
+            .await
+            .map_err(|e| PgError::Query("insert_provisional_block", e))?;
+    }
+    Ok(())
+}
+
+pub async fn get_provisional_inscription_by_id<T: GenericClient>(
+    inscription_id: &str,
+    client: &T,
+) -> Result<Option<DbProvisionalInscription>, PgError> {
+    let row = client
+        .query_opt(
+            "SELECT * FROM provisional_inscriptions WHERE inscription_id = $1",
+            &[&inscription_id],
         )
         .await
-        .map_err(|e| format!("update_recursive_counts: {e}"))?;
-    Ok(())
+        .map_err(|e| PgError::Query("get_provisional_inscription_by_id", e))?;
+    Ok(row.map(|row| DbProvisionalInscription::from_pg_row(&row)))
 }
 
-async fn update_counts_by_block<T: GenericClient>(
+pub async fn get_provisional_inscriptions_at_block<T: GenericClient>(
     block_height: u64,
-    block_hash: &String,
-    inscription_count: usize,
-    timestamp: u32,
     client: &T,
-) -> Result<(), String> {
-    if inscription_count == 0 {
-        return Ok(());
-    }
-    client
+) -> Result<Vec<DbProvisionalInscription>, PgError> {
+    let rows = client
         .query(
-        "WITH prev_entry AS (
-                SELECT inscription_count_accum
-                FROM counts_by_block
-                WHERE block_height < $1
-                ORDER BY block_height DESC
-                LIMIT 1
-            )
-            INSERT INTO counts_by_block (block_height, block_hash, inscription_count, inscription_count_accum, timestamp)
-            VALUES ($1, $2, $3, COALESCE((SELECT inscription_count_accum FROM prev_entry), 0) + $3, $4)",
-            &[&PgNumericU64(block_height), block_hash, &(inscription_count as i32), &PgBigIntU32(timestamp)],
+            "SELECT * FROM provisional_inscriptions WHERE block_height = $1
+            ORDER BY tx_index, input_index",
+            &[&PgNumericU64(block_height)],
         )
         .await
-        .map_err(|e| format!("update_counts_by_block: {e}"))?;
-    Ok(())
+        .map_err(|e| PgError::Query("get_provisional_inscriptions_at_block", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| DbProvisionalInscription::from_pg_row(row))
+        .collect())
 }
 
-pub async fn update_chain_tip<T: GenericClient>(
+/// Drops provisional inscriptions for every block that has now been fully indexed.
+pub async fn reconcile_provisional_inscriptions<T: GenericClient>(
     block_height: u64,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), PgError> {
     client
         .query(
-            "UPDATE chain_tip SET block_height = $1",
+            "DELETE FROM provisional_inscriptions WHERE block_height <= $1",
             &[&PgNumericU64(block_height)],
         )
         .await
-        .map_err(|e| format!("update_chain_tip: {e}"))?;
+        .map_err(|e| PgError::Query("reconcile_provisional_inscriptions", e))?;
     Ok(())
 }
 
-/// Inserts an indexed ordinals block into the DB.
+/// Inserts every inscription reveal and transfer of `block`. Inscription contents larger than `max_stored_content_bytes`
+/// are replaced by their hash.
 pub async fn insert_block<T: GenericClient>(
     block: &BitcoinBlockData,
+    max_stored_content_bytes: Option<u64>,
     client: &T,
-) -> Result<(), String> {
+) -> Result<(), OrdhookError> {
     let mut satoshis = vec![];
     let mut inscriptions = vec![];
     let mut locations = vec![];
@@ -776,6 +1652,9 @@ pub async fn insert_block<T: GenericClient>(
                         tx_index,
                         block.timestamp,
                     );
+                    if let Some(max_bytes) = max_stored_content_bytes {
+                        inscription.omit_content_above(max_bytes);
+                    }
                     let mime_type = inscription.mime_type.clone();
                     let genesis_address = inscription.address.clone();
                     let recursions = DbInscriptionRecursion::from_reveal(reveal)?;
@@ -874,12 +1753,257 @@ pub async fn insert_block<T: GenericClient>(
         client,
     )
     .await?;
+    insert_indexed_block(
+        block.block_identifier.index,
+        &block.block_identifier.hash[2..].to_string(),
+        client,
+    )
+    .await?;
+    reconcile_provisional_inscriptions(block.block_identifier.index, client).await?;
     update_chain_tip(block.block_identifier.index, client).await?;
 
     Ok(())
 }
 
-pub async fn rollback_block<T: GenericClient>(block_height: u64, client: &T) -> Result<(), String> {
+pub async fn insert_webhook_delivery<T: GenericClient>(
+    block_height: u64,
+    payload: &String,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "INSERT INTO webhook_deliveries (block_height, payload) VALUES ($1, $2)",
+            &[&PgNumericU64(block_height), payload],
+        )
+        .await
+        .map_err(|e| PgError::Query("insert_webhook_delivery", e))?;
+    Ok(())
+}
+
+pub async fn insert_address_watch_digest_entry<T: GenericClient>(
+    block_height: u64,
+    block_hash: &str,
+    payload: &String,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "INSERT INTO address_watch_digest_entries (block_height, block_hash, payload) VALUES ($1, $2, $3)",
+            &[&PgNumericU64(block_height), &block_hash, payload],
+        )
+        .await
+        .map_err(|e| PgError::Query("insert_address_watch_digest_entry", e))?;
+    Ok(())
+}
+
+/// Returns every queued address watch digest entry as `(id, block_height, canonical, payload)`, oldest first. `canonical`
+/// is false when the entry's block is not part of the indexed chain, either because it was reorged out or because it
+/// was not indexed yet.
+pub async fn get_address_watch_digest_entries<T: GenericClient>(
+    client: &T,
+) -> Result<Vec<(i64, u64, bool, String)>, PgError> {
+    let rows = client
+        .query(
+            "SELECT e.id, e.block_height, e.payload, b.block_height IS NOT NULL AS canonical
+            FROM address_watch_digest_entries AS e
+            LEFT JOIN indexed_blocks AS b ON b.block_height = e.block_height AND b.block_hash = e.block_hash
+            ORDER BY e.id",
+            &[],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_address_watch_digest_entries", e))?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let block_height: PgNumericU64 = row.get("block_height");
+            (
+                row.get("id"),
+                block_height.0,
+                row.get("canonical"),
+                row.get("payload"),
+            )
+        })
+        .collect())
+}
+
+pub async fn delete_address_watch_digest_entries<T: GenericClient>(
+    ids: &Vec<i64>,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "DELETE FROM address_watch_digest_entries WHERE id = ANY($1)",
+            &[ids],
+        )
+        .await
+        .map_err(|e| PgError::Query("delete_address_watch_digest_entries", e))?;
+    Ok(())
+}
+
+/// Returns the oldest queued webhook delivery if it is due. Deliveries are attempted strictly in order, so a newer one
+/// is never returned while an older one is waiting for its retry.
+pub async fn get_due_webhook_delivery<T: GenericClient>(
+    client: &T,
+) -> Result<Option<DbWebhookDelivery>, PgError> {
+    let row = client
+        .query_opt(
+            "SELECT id, block_height, payload, attempts, last_error
+            FROM webhook_deliveries
+            WHERE id = (SELECT MIN(id) FROM webhook_deliveries) AND next_attempt_at <= NOW()",
+            &[],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_due_webhook_delivery", e))?;
+    Ok(row.map(|row| DbWebhookDelivery::from_pg_row(&row)))
+}
+
+pub async fn delete_webhook_delivery<T: GenericClient>(id: i64, client: &T) -> Result<(), PgError> {
+    client
+        .query("DELETE FROM webhook_deliveries WHERE id = $1", &[&id])
+        .await
+        .map_err(|e| PgError::Query("delete_webhook_delivery", e))?;
+    Ok(())
+}
+
+/// Records a failed attempt and schedules the next one `delay_secs` from now.
+pub async fn postpone_webhook_delivery<T: GenericClient>(
+    id: i64,
+    error: &String,
+    delay_secs: f64,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "UPDATE webhook_deliveries
+            SET attempts = attempts + 1, last_error = $2, next_attempt_at = NOW() + make_interval(secs => $3)
+            WHERE id = $1",
+            &[&id, error, &delay_secs],
+        )
+        .await
+        .map_err(|e| PgError::Query("postpone_webhook_delivery", e))?;
+    Ok(())
+}
+
+/// Records a final failed attempt and moves the delivery to the dead letter table.
+pub async fn dead_letter_webhook_delivery<T: GenericClient>(
+    id: i64,
+    error: &String,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "WITH moved AS (DELETE FROM webhook_deliveries WHERE id = $1 RETURNING *)
+            INSERT INTO webhook_dead_letters (id, block_height, payload, attempts, last_error)
+            SELECT id, block_height, payload, attempts + 1, $2 FROM moved",
+            &[&id, error],
+        )
+        .await
+        .map_err(|e| PgError::Query("dead_letter_webhook_delivery", e))?;
+    Ok(())
+}
+
+pub async fn get_observer_state<T: GenericClient>(
+    key: &str,
+    client: &T,
+) -> Result<Option<String>, PgError> {
+    let row = client
+        .query_opt("SELECT value FROM observer_state WHERE key = $1", &[&key])
+        .await
+        .map_err(|e| PgError::Query("get_observer_state", e))?;
+    Ok(row.map(|row| row.get("value")))
+}
+
+pub async fn upsert_observer_state<T: GenericClient>(
+    key: &str,
+    value: &String,
+    client: &T,
+) -> Result<(), PgError> {
+    client
+        .query(
+            "INSERT INTO observer_state (key, value) VALUES ($1, $2)
+            ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value, updated_at = NOW()",
+            &[&key, value],
+        )
+        .await
+        .map_err(|e| PgError::Query("upsert_observer_state", e))?;
+    Ok(())
+}
+
+/// Stores the cluster ids an address clusterer returned, replacing the previous cluster of each address.
+pub async fn upsert_address_clusters<T: GenericClient>(
+    clusters: &HashMap<String, String>,
+    source: &str,
+    block_height: u64,
+    client: &T,
+) -> Result<(), PgError> {
+    let mut clusters: Vec<(&String, &String)> = clusters.iter().collect();
+    clusters.sort();
+    for chunk in clusters.chunks(500) {
+        let addresses: Vec<&String> = chunk.iter().map(|(address, _)| *address).collect();
+        let cluster_ids: Vec<&String> = chunk.iter().map(|(_, cluster_id)| *cluster_id).collect();
+        client
+            .query(
+                "INSERT INTO address_clusters (address, cluster_id, source, block_height)
+                (SELECT address, cluster_id, $3, $4 FROM UNNEST($1::text[], $2::text[]) AS k(address, cluster_id))
+                ON CONFLICT (address) DO UPDATE SET
+                    cluster_id = EXCLUDED.cluster_id,
+                    source = EXCLUDED.source,
+                    block_height = EXCLUDED.block_height,
+                    updated_at = NOW()",
+                &[&addresses, &cluster_ids, &source, &PgNumericU64(block_height)],
+            )
+            .await
+            .map_err(|e| PgError::Query("upsert_address_clusters", e))?;
+    }
+    Ok(())
+}
+
+/// Returns the addresses assigned to a cluster, sorted.
+pub async fn get_cluster_addresses<T: GenericClient>(
+    cluster_id: &String,
+    limit: i64,
+    offset: i64,
+    client: &T,
+) -> Result<Vec<String>, PgError> {
+    let rows = client
+        .query(
+            "SELECT address FROM address_clusters WHERE cluster_id = $1
+            ORDER BY address LIMIT $2 OFFSET $3",
+            &[cluster_id, &limit, &offset],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_cluster_addresses", e))?;
+    Ok(rows.iter().map(|row| row.get("address")).collect())
+}
+
+/// Returns the inscriptions currently held by any address of a cluster, newest first.
+pub async fn get_inscriptions_held_by_cluster<T: GenericClient>(
+    cluster_id: &String,
+    limit: i64,
+    offset: i64,
+    client: &T,
+) -> Result<Vec<(DbInscription, DbCurrentLocation)>, PgError> {
+    let rows = client
+        .query(
+            "SELECT i.*, l.block_height AS location_block_height, l.tx_id AS location_tx_id,
+                l.tx_index AS location_tx_index, l.address AS location_address, l.output, l.\"offset\"
+            FROM address_clusters AS c
+            INNER JOIN current_locations AS l ON l.address = c.address
+            INNER JOIN inscriptions AS i ON i.ordinal_number = l.ordinal_number
+            WHERE c.cluster_id = $1
+            ORDER BY i.number DESC
+            LIMIT $2 OFFSET $3",
+            &[cluster_id, &limit, &offset],
+        )
+        .await
+        .map_err(|e| PgError::Query("get_inscriptions_held_by_cluster", e))?;
+    Ok(rows.iter().map(held_inscription_from_row).collect())
+}
+
+pub async fn rollback_block<T: GenericClient>(
+    block_height: u64,
+    client: &T,
+) -> Result<(), PgError> {
     // Delete previous current locations, deduct owner counts, remove orphaned sats
     let moved_sat_rows = client
         .query(
@@ -925,13 +2049,21 @@ pub async fn rollback_block<T: GenericClient>(block_height: u64, client: &T) ->
             &[&PgNumericU64(block_height)],
         )
         .await
-        .map_err(|e| format!("rollback_block (1): {e}"))?;
+        .map_err(|e| PgError::Query("rollback_block (1)", e))?;
     // Delete inscriptions and locations
     client
         .execute(
             "WITH transfer_deletes AS (DELETE FROM inscription_transfers WHERE block_height = $1),
             inscription_deletes AS (
-                DELETE FROM inscriptions WHERE block_height = $1 RETURNING mime_type, classic_number, address, recursive
+                DELETE FROM inscriptions WHERE block_height = $1
+                RETURNING mime_type, classic_number, address, recursive, content_hash
+            ),
+            content_deletes AS (
+                DELETE FROM inscription_contents AS ic
+                WHERE ic.content_hash IN (SELECT content_hash FROM inscription_deletes) AND NOT EXISTS (
+                    SELECT 1 FROM inscriptions AS i
+                    WHERE i.content_hash = ic.content_hash AND NOT i.content_omitted AND i.block_height <> $1
+                )
             ),
             inscription_delete_types AS (
                 SELECT 'cursed' AS type, COUNT(*) AS count
@@ -941,6 +2073,7 @@ pub async fn rollback_block<T: GenericClient>(block_height: u64, client: &T) ->
                 FROM inscription_deletes WHERE classic_number >= 0
             ),
             counts_by_block_deletes AS (DELETE FROM counts_by_block WHERE block_height = $1),
+            indexed_block_deletes AS (DELETE FROM indexed_blocks WHERE block_height = $1),
             type_count_updates AS (
                 UPDATE counts_by_type SET count = (
                     SELECT counts_by_type.count - count
@@ -980,7 +2113,7 @@ pub async fn rollback_block<T: GenericClient>(block_height: u64, client: &T) ->
             &[&PgNumericU64(block_height)],
         )
         .await
-        .map_err(|e| format!("rollback_block (2): {e}"))?;
+        .map_err(|e| PgError::Query("rollback_block (2)", e))?;
     // Re-compute current location and owners
     let moved_sats: Vec<PgNumericU64> = moved_sat_rows
         .iter()
@@ -998,7 +2131,7 @@ pub async fn rollback_block<T: GenericClient>(block_height: u64, client: &T) ->
             &[&moved_sats]
         )
         .await
-        .map_err(|e| format!("rollback_block (3): {e}"))?;
+        .map_err(|e| PgError::Query("rollback_block (3)", e))?;
     client
         .execute(
             "WITH new_owners AS (
@@ -1013,7 +2146,7 @@ pub async fn rollback_block<T: GenericClient>(block_height: u64, client: &T) ->
             &[&moved_sats],
         )
         .await
-        .map_err(|e| format!("rollback_block (4): {e}"))?;
+        .map_err(|e| PgError::Query("rollback_block (4)", e))?;
     update_chain_tip(block_height - 1, client).await?;
     Ok(())
 }
@@ -1026,18 +2159,22 @@ mod test {
         FromPgRow,
     };
     use chainhook_types::{
-        OrdinalInscriptionNumber, OrdinalInscriptionRevealData, OrdinalInscriptionTransferData,
-        OrdinalInscriptionTransferDestination, OrdinalOperation,
+        BitcoinTransactionData, OrdinalInscriptionNumber, OrdinalInscriptionRevealData,
+        OrdinalInscriptionTransferData, OrdinalInscriptionTransferDestination, OrdinalOperation,
     };
     use deadpool_postgres::GenericClient;
 
     use crate::{
         core::test_builders::{TestBlockBuilder, TestTransactionBuilder},
         db::{
-            models::{DbCurrentLocation, DbInscription, DbLocation, DbSatoshi},
+            models::{
+                DbCurrentLocation, DbFilteredInscription, DbInscription, DbInscriptionTakedown,
+                DbLocation, DbSatoshi,
+            },
             ordinals_pg::{
-                self, get_chain_tip_block_height, get_inscriptions_at_block, insert_block,
-                rollback_block,
+                self, get_chain_tip_block_height, get_inscriptions_at_block,
+                get_inscriptions_held_by_address, insert_block, rollback_block,
+                INSCRIPTION_CONTENT_COLUMN, INSCRIPTION_CONTENT_JOIN,
             },
             pg_reset_db, pg_test_connection, pg_test_connection_pool,
         },
@@ -1074,7 +2211,10 @@ mod test {
     ) -> Option<DbInscription> {
         let row = client
             .query_opt(
-                "SELECT * FROM inscriptions WHERE inscription_id = $1",
+                &format!(
+                    "SELECT i.*, {INSCRIPTION_CONTENT_COLUMN} FROM inscriptions AS i {INSCRIPTION_CONTENT_JOIN}
+                    WHERE i.inscription_id = $1"
+                ),
                 &[&inscription_id],
             )
             .await
@@ -1227,7 +2367,7 @@ mod test {
                             .build()
                     )
                     .build();
-                insert_block(&block, &client).await?;
+                insert_block(&block, None, &client).await?;
                 assert_eq!(1, get_inscriptions_at_block(&client, 800000).await?.len());
                 assert!(get_inscription(
                     "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0",
@@ -1235,6 +2375,81 @@ mod test {
                 )
                 .await
                 .is_some());
+                assert!(ordinals_pg::get_inscription_by_id(
+                    "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0",
+                    &client
+                )
+                .await?
+                .is_some());
+                assert_eq!(
+                    1,
+                    ordinals_pg::get_inscriptions_revealed_at_block(800000, None, 0, &client)
+                        .await?
+                        .len()
+                );
+                assert!(ordinals_pg::get_inscriptions_revealed_at_block(
+                    800000,
+                    Some(20),
+                    1,
+                    &client
+                )
+                .await?
+                .is_empty());
+                assert!(ordinals_pg::get_current_locations(&vec![7000], &client)
+                    .await?
+                    .contains_key(&7000));
+                assert_eq!(
+                    get_locations(7000, &client).await,
+                    ordinals_pg::get_locations_for_ordinal_number(7000, &client).await?
+                );
+                ordinals_pg::insert_inscription_texts(&block, &client).await?;
+                assert_eq!(
+                    1,
+                    ordinals_pg::search_inscriptions("ordi deploy", 20, 0, &client)
+                        .await?
+                        .len()
+                );
+                assert!(ordinals_pg::search_inscriptions("pepe", 20, 0, &client)
+                    .await?
+                    .is_empty());
+                ordinals_pg::insert_filtered_inscriptions(
+                    &vec![DbFilteredInscription {
+                        inscription_id:
+                            "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0"
+                                .to_string(),
+                        policy: "denied_mime_types".to_string(),
+                        reason: "mime type text/plain is denied".to_string(),
+                    }],
+                    &client,
+                )
+                .await?;
+                assert!(
+                    ordinals_pg::search_inscriptions("ordi deploy", 20, 0, &client)
+                        .await?
+                        .is_empty()
+                );
+                assert_eq!(
+                    Some("denied_mime_types".to_string()),
+                    ordinals_pg::get_inscription_filter(
+                        "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0",
+                        &client
+                    )
+                    .await?
+                    .map(|filtered| filtered.policy)
+                );
+                assert_eq!(
+                    0,
+                    ordinals_pg::assign_block_operation_sequence(800000, 1, &client).await?
+                );
+                assert_eq!(
+                    Some(800000),
+                    ordinals_pg::get_block_by_operation_sequence(0, &client)
+                        .await?
+                        .map(|(block_height, _, _)| block_height)
+                );
+                assert!(ordinals_pg::get_block_by_operation_sequence(1, &client)
+                    .await?
+                    .is_none());
                 let locations = get_locations(7000, &client).await;
                 assert_eq!(1, locations.len());
                 assert_eq!(
@@ -1254,6 +2469,7 @@ mod test {
                         offset: Some(PgNumericU64(0)),
                         prev_output: None,
                         prev_offset: None,
+                        prev_address: None,
                         value: Some(PgNumericU64(10000)),
                         transfer_type: "transferred".to_string(),
                         timestamp: PgBigIntU32(1712982301)
@@ -1310,6 +2526,7 @@ mod test {
                                 OrdinalInscriptionTransferData {
                                     ordinal_number: 7000,
                                     destination: OrdinalInscriptionTransferDestination::Transferred("3DnzPvLPH1jA9EqQzq3Fgo9BMDya4eG1ay".to_string()),
+                                    from_address: Some("324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp".to_string()),
                                     satpoint_pre_transfer: "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735:0:0".to_string(),
                                     satpoint_post_transfer: "4862db07b588ebfd8627371045d6d17a99a66a01759782d7dd3009f68adb860f:0:0".to_string(),
                                     post_transfer_output_value: Some(8000),
@@ -1319,7 +2536,7 @@ mod test {
                             .build()
                     )
                     .build();
-                insert_block(&block, &client).await?;
+                insert_block(&block, None, &client).await?;
                 assert_eq!(0, get_inscriptions_at_block(&client, 800001).await?.len());
                 let locations = get_locations(7000, &client).await;
                 assert_eq!(2, locations.len());
@@ -1343,6 +2560,7 @@ mod test {
                                 .to_string()
                         ),
                         prev_offset: Some(PgNumericU64(0)),
+                        prev_address: Some("324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp".to_string()),
                         value: Some(PgNumericU64(8000)),
                         transfer_type: "transferred".to_string(),
                         timestamp: PgBigIntU32(1712982301)
@@ -1381,11 +2599,48 @@ mod test {
                 );
                 assert_eq!(1, get_type_count("blessed", &client).await);
                 assert_eq!(Some(800001), get_chain_tip_block_height(&client).await?);
+                assert!(
+                    ordinals_pg::is_block_hash_indexed(
+                        "00000000000000000001b322ec2ea8b5b9b0ac413385069ad6b0c84e0331bf23",
+                        &client
+                    )
+                    .await?
+                );
+                // Holdings at the reveal height still show the inscription with its genesis owner.
+                let sender = "324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp".to_string();
+                let receiver = "3DnzPvLPH1jA9EqQzq3Fgo9BMDya4eG1ay".to_string();
+                assert!(
+                    get_inscriptions_held_by_address(&sender, None, 20, 0, &client)
+                        .await?
+                        .is_empty()
+                );
+                assert_eq!(
+                    1,
+                    get_inscriptions_held_by_address(&receiver, None, 20, 0, &client)
+                        .await?
+                        .len()
+                );
+                let held_at_reveal =
+                    get_inscriptions_held_by_address(&sender, Some(800000), 20, 0, &client).await?;
+                assert_eq!(1, held_at_reveal.len());
+                assert_eq!(PgNumericU64(800000), held_at_reveal[0].1.block_height);
+                assert!(
+                    get_inscriptions_held_by_address(&receiver, Some(800000), 20, 0, &client)
+                        .await?
+                        .is_empty()
+                );
             }
 
             // Rollback transfer
             {
                 rollback_block(800001, &client).await?;
+                assert!(
+                    !ordinals_pg::is_block_hash_indexed(
+                        "00000000000000000001b322ec2ea8b5b9b0ac413385069ad6b0c84e0331bf23",
+                        &client
+                    )
+                    .await?
+                );
                 assert_eq!(1, get_locations(7000, &client).await.len());
                 assert_eq!(
                     Some(DbCurrentLocation {
@@ -1455,4 +2710,175 @@ mod test {
         pg_reset_db(&mut pg_client).await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_webhook_delivery_queue() -> Result<(), String> {
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        {
+            let mut ord_client = pg_pool_client(&pg_test_connection_pool()).await?;
+            let client = pg_begin(&mut ord_client).await?;
+            ordinals_pg::insert_webhook_delivery(800000, &"{}".to_string(), &client).await?;
+            ordinals_pg::insert_webhook_delivery(800001, &"{}".to_string(), &client).await?;
+
+            let delivery = ordinals_pg::get_due_webhook_delivery(&client)
+                .await?
+                .unwrap();
+            assert_eq!(PgNumericU64(800000), delivery.block_height);
+
+            // A postponed delivery holds back the ones queued after it.
+            ordinals_pg::postpone_webhook_delivery(
+                delivery.id,
+                &"timeout".to_string(),
+                60.0,
+                &client,
+            )
+            .await?;
+            assert_eq!(None, ordinals_pg::get_due_webhook_delivery(&client).await?);
+
+            ordinals_pg::dead_letter_webhook_delivery(delivery.id, &"timeout".to_string(), &client)
+                .await?;
+            let row = client
+                .query_one(
+                    "SELECT attempts FROM webhook_dead_letters WHERE id = $1",
+                    &[&delivery.id],
+                )
+                .await
+                .unwrap();
+            assert_eq!(2, row.get::<_, i32>("attempts"));
+
+            let next = ordinals_pg::get_due_webhook_delivery(&client)
+                .await?
+                .unwrap();
+            assert_eq!(PgNumericU64(800001), next.block_height);
+            ordinals_pg::delete_webhook_delivery(next.id, &client).await?;
+            assert_eq!(None, ordinals_pg::get_due_webhook_delivery(&client).await?);
+        }
+        pg_reset_db(&mut pg_client).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_provisional_inscriptions_reconciled_by_backfill() -> Result<(), String> {
+        let inscription_id = "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0";
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        {
+            let mut ord_client = pg_pool_client(&pg_test_connection_pool()).await?;
+            let client = pg_begin(&mut ord_client).await?;
+            let block = TestBlockBuilder::new()
+                .height(800000)
+                .add_transaction(TestTransactionBuilder::new_with_operation().build())
+                .build();
+
+            // The tip lane stores the reveal before the backfill reaches its block.
+            ordinals_pg::insert_provisional_block(&block, &client).await?;
+            let provisional =
+                ordinals_pg::get_provisional_inscription_by_id(inscription_id, &client)
+                    .await?
+                    .unwrap();
+            assert_eq!(PgNumericU64(800000), provisional.block_height);
+            assert_eq!(
+                1,
+                ordinals_pg::get_provisional_inscriptions_at_block(800000, &client)
+                    .await?
+                    .len()
+            );
+
+            insert_block(&block, None, &client).await?;
+            assert_eq!(
+                None,
+                ordinals_pg::get_provisional_inscription_by_id(inscription_id, &client).await?
+            );
+            assert!(
+                ordinals_pg::get_provisional_inscriptions_at_block(800000, &client)
+                    .await?
+                    .is_empty()
+            );
+            assert!(get_inscription(inscription_id, &client).await.is_some());
+        }
+        pg_reset_db(&mut pg_client).await?;
+        Ok(())
+    }
+
+    fn reveal_transaction(index: u64) -> BitcoinTransactionData {
+        let tx_id = format!("{index:064x}");
+        let mut tx = TestTransactionBuilder::new_with_operation()
+            .hash(format!("0x{tx_id}"))
+            .build();
+        if let OrdinalOperation::InscriptionRevealed(reveal) =
+            &mut tx.metadata.ordinal_operations[0]
+        {
+            reveal.inscription_id = format!("{tx_id}i0");
+            reveal.inscription_number = OrdinalInscriptionNumber {
+                classic: index as i64,
+                jubilee: index as i64,
+            };
+            reveal.ordinal_number = index;
+            reveal.tx_index = index as usize;
+            reveal.satpoint_post_inscription = format!("{tx_id}:0:0");
+        }
+        tx
+    }
+
+    async fn get_stored_content_count<T: GenericClient>(client: &T) -> i64 {
+        client
+            .query_one("SELECT COUNT(*) AS count FROM inscription_contents", &[])
+            .await
+            .unwrap()
+            .get("count")
+    }
+
+    #[tokio::test]
+    async fn test_identical_contents_stored_once() -> Result<(), String> {
+        let first_id = format!("{:064x}i0", 0);
+        let second_id = format!("{:064x}i0", 1);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        {
+            let mut ord_client = pg_pool_client(&pg_test_connection_pool()).await?;
+            let client = pg_begin(&mut ord_client).await?;
+            let block = TestBlockBuilder::new()
+                .height(800000)
+                .add_transaction(reveal_transaction(0))
+                .add_transaction(reveal_transaction(1))
+                .build();
+
+            insert_block(&block, None, &client).await?;
+            assert_eq!(1, get_stored_content_count(&client).await);
+            for inscription_id in [&first_id, &second_id] {
+                let inscription = get_inscription(inscription_id, &client).await.unwrap();
+                assert_eq!(94, inscription.content.len());
+            }
+
+            // The copy that was not taken down is still served.
+            ordinals_pg::take_down_inscription_content(
+                &DbInscriptionTakedown {
+                    inscription_id: first_id.clone(),
+                    reason: "test".to_string(),
+                },
+                &client,
+            )
+            .await?;
+            assert!(get_inscription(&first_id, &client)
+                .await
+                .unwrap()
+                .content
+                .is_empty());
+            assert_eq!(
+                94,
+                get_inscription(&second_id, &client)
+                    .await
+                    .unwrap()
+                    .content
+                    .len()
+            );
+            assert_eq!(1, get_stored_content_count(&client).await);
+
+            rollback_block(800000, &client).await?;
+            assert_eq!(0, get_stored_content_count(&client).await);
+        }
+        pg_reset_db(&mut pg_client).await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+
+use chainhook_sdk::indexer::bitcoin::BitcoinBlockFullBreakdown;
+use chainhook_sdk::utils::{hex, Context};
+use chainhook_types::{BitcoinBlockData, OrdinalOperation};
+use rocksdb::DB;
+
+use crate::{config::Config, db::blocks::rocks_db_default_options};
+
+/// Witness bytes that open an inscription envelope: `OP_FALSE OP_IF OP_PUSHBYTES_3 "ord"`.
+const INSCRIPTION_ENVELOPE_MARKER: [u8; 6] = [0x00, 0x63, 0x03, b'o', b'r', b'd'];
+
+lazy_static! {
+    /// Read-write handles currently open, by path. RocksDB locks a database for the process that opened it, so the
+    /// download pipeline and the streamed blocks sidecar have to share a single handle.
+    static ref RAW_TRANSACTIONS_DBS_RW: Mutex<HashMap<PathBuf, Weak<DB>>> = Mutex::new(HashMap::new());
+}
+
+fn get_default_raw_transactions_db_path(base_dir: &PathBuf) -> PathBuf {
+    let mut destination_path = base_dir.clone();
+    destination_path.push("raw_transactions.rocksdb");
+    destination_path
+}
+
+/// Opens the optional raw transactions index, which maps the txid of every inscription reveal transaction seen by the
+/// indexer to the height of its block and its offset in that block. The compacted blocks DB drops scripts and
+/// witnesses, so the serialized transaction is then read from bitcoind, which only needs the block to find it.
+pub fn open_raw_transactions_db(
+    readwrite: bool,
+    config: &Config,
+    _ctx: &Context,
+) -> Result<DB, String> {
+    let path = get_default_raw_transactions_db_path(&config.expected_cache_path());
+    let mut opts =
+        rocks_db_default_options(config.resources.ulimit, config.resources.memory_available);
+    if readwrite {
+        DB::open(&opts, path)
+            .map_err(|e| format!("unable to read-write raw_transactions.rocksdb: {e}"))
+    } else {
+        opts.set_disable_auto_compactions(true);
+        opts.set_max_background_jobs(0);
+        DB::open_for_read_only(&opts, path, false)
+            .map_err(|e| format!("unable to read raw_transactions.rocksdb: {e}"))
+    }
+}
+
+/// Returns the read-write handle of the index, opening it if no one in the process holds it yet. It is closed once
+/// every holder has dropped it.
+pub fn shared_raw_transactions_db_rw(config: &Config, ctx: &Context) -> Result<Arc<DB>, String> {
+    let path = get_default_raw_transactions_db_path(&config.expected_cache_path());
+    let mut dbs = RAW_TRANSACTIONS_DBS_RW.lock().unwrap();
+    if let Some(db) = dbs.get(&path).and_then(|db| db.upgrade()) {
+        return Ok(db);
+    }
+    let db = Arc::new(open_raw_transactions_db(true, config, ctx)?);
+    dbs.insert(path, Arc::downgrade(&db));
+    Ok(db)
+}
+
+/// Opens the index as a secondary instance, for readers living alongside the indexer. Unlike a read-only instance, it
+/// can follow the indexer's writes with `try_catch_up_with_primary`.
+fn open_raw_transactions_db_secondary(config: &Config) -> Result<DB, String> {
+    let path = get_default_raw_transactions_db_path(&config.expected_cache_path());
+    let mut secondary_path = config.expected_cache_path();
+    secondary_path.push("raw_transactions.rocksdb.secondary");
+    let mut opts =
+        rocks_db_default_options(config.resources.ulimit, config.resources.memory_available);
+    // Secondary instances keep every file of the primary open.
+    opts.set_max_open_files(-1);
+    DB::open_as_secondary(&opts, &path, &secondary_path)
+        .map_err(|e| format!("unable to read raw_transactions.rocksdb: {e}"))
+}
+
+/// Read access to the index shared by every API request. The index is opened on first use, since the indexer may not
+/// have created it yet, and catches up with the indexer's writes before each lookup.
+#[derive(Default)]
+pub struct RawTransactionsReader {
+    db: Mutex<Option<Arc<DB>>>,
+}
+
+impl RawTransactionsReader {
+    /// Returns the block height and offset in that block of an indexed transaction. RocksDB calls block, so they run on
+    /// the blocking thread pool.
+    pub async fn find(
+        self: &Arc<Self>,
+        txid: &str,
+        config: &Config,
+    ) -> Result<Option<(u32, u32)>, String> {
+        let reader = self.clone();
+        let txid = txid.to_string();
+        let config = config.clone();
+        tokio::task::spawn_blocking(move || reader.find_blocking(&txid, &config))
+            .await
+            .map_err(|e| format!("unable to read raw_transactions.rocksdb: {e}"))?
+    }
+
+    fn find_blocking(&self, txid: &str, config: &Config) -> Result<Option<(u32, u32)>, String> {
+        let db = {
+            let mut db = self.db.lock().unwrap();
+            match db.as_ref() {
+                Some(db) => db.clone(),
+                None => db
+                    .insert(Arc::new(open_raw_transactions_db_secondary(config)?))
+                    .clone(),
+            }
+        };
+        db.try_catch_up_with_primary()
+            .map_err(|e| format!("unable to refresh raw_transactions.rocksdb: {e}"))?;
+        Ok(find_raw_transaction(txid, &db))
+    }
+}
+
+fn txid_key(txid: &str) -> Vec<u8> {
+    hex::decode(txid.trim_start_matches("0x")).unwrap_or_default()
+}
+
+pub fn insert_raw_transaction(
+    txid: &str,
+    block_height: u32,
+    offset: u32,
+    raw_transactions_db_rw: &DB,
+) -> Result<(), String> {
+    let mut value = block_height.to_be_bytes().to_vec();
+    value.extend_from_slice(&offset.to_be_bytes());
+    raw_transactions_db_rw
+        .put(txid_key(txid), value)
+        .map_err(|e| format!("unable to index raw transaction {txid}: {e}"))
+}
+
+/// Returns the block height and offset in that block of an indexed transaction.
+pub fn find_raw_transaction(txid: &str, raw_transactions_db: &DB) -> Option<(u32, u32)> {
+    match raw_transactions_db.get(txid_key(txid)) {
+        Ok(Some(bytes)) if bytes.len() == 8 => Some((
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        )),
+        _ => None,
+    }
+}
+
+fn has_inscription_envelope(witness: &[u8]) -> bool {
+    witness
+        .windows(INSCRIPTION_ENVELOPE_MARKER.len())
+        .any(|window| window == INSCRIPTION_ENVELOPE_MARKER)
+}
+
+/// Returns `(txid, offset)` for every transaction in a downloaded block that carries an inscription envelope in one of
+/// its witnesses.
+pub fn extract_reveal_transaction_offsets(block: &BitcoinBlockFullBreakdown) -> Vec<(String, u32)> {
+    let mut reveals = vec![];
+    for (offset, tx) in block.tx.iter().enumerate() {
+        let is_reveal = tx.vin.iter().any(|input| {
+            input.txinwitness.as_ref().is_some_and(|witness| {
+                witness
+                    .iter()
+                    .any(|w| hex::decode(w).is_ok_and(|bytes| has_inscription_envelope(&bytes)))
+            })
+        });
+        if is_reveal {
+            reveals.push((tx.txid.clone(), offset as u32));
+        }
+    }
+    reveals
+}
+
+/// Indexes the reveal transactions of a standardized block, which are the ones carrying an `InscriptionRevealed`
+/// operation.
+pub fn index_block_reveal_transactions(
+    block: &BitcoinBlockData,
+    raw_transactions_db_rw: &DB,
+) -> Result<(), String> {
+    for (offset, tx) in block.transactions.iter().enumerate() {
+        if tx
+            .metadata
+            .ordinal_operations
+            .iter()
+            .any(|op| matches!(op, OrdinalOperation::InscriptionRevealed(_)))
+        {
+            insert_raw_transaction(
+                tx.transaction_identifier.get_hash_bytes_str(),
+                block.block_identifier.index as u32,
+                offset as u32,
+                raw_transactions_db_rw,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+
+    use crate::{config::Config, db::drop_all_dbs};
+
+    use super::{
+        find_raw_transaction, has_inscription_envelope, insert_raw_transaction,
+        shared_raw_transactions_db_rw, INSCRIPTION_ENVELOPE_MARKER,
+    };
+
+    #[test]
+    fn indexes_and_finds_raw_transactions() {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/raw_transactions".to_string();
+        drop_all_dbs(&config);
+        let db = shared_raw_transactions_db_rw(&config, &ctx).unwrap();
+        let txid = "0xb61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735";
+
+        insert_raw_transaction(txid, 775617, 12, &db).unwrap();
+
+        assert_eq!(find_raw_transaction(&txid[2..], &db), Some((775617, 12)));
+        assert_eq!(find_raw_transaction("00", &db), None);
+        assert!(std::sync::Arc::ptr_eq(
+            &db,
+            &shared_raw_transactions_db_rw(&config, &ctx).unwrap()
+        ));
+        drop(db);
+        drop_all_dbs(&config);
+    }
+
+    #[test]
+    fn finds_envelopes_in_witness_bytes() {
+        let mut witness = vec![0x20; 33];
+        witness.extend_from_slice(&INSCRIPTION_ENVELOPE_MARKER);
+        assert!(has_inscription_envelope(&witness));
+        // The marker's hex text split across a byte boundary is not an envelope.
+        assert!(!has_inscription_envelope(&[
+            0x00, 0x06, 0x30, 0x36, 0xf7, 0x26, 0x40
+        ]));
+        assert!(!has_inscription_envelope(&INSCRIPTION_ENVELOPE_MARKER[..5]));
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use chainhook_postgres::PgError;
+
+/// Step of the block indexing pipeline an error was raised in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexingStage {
+    Traversals,
+    Sequencing,
+    Transfers,
+    OrdinalsWrite,
+    Brc20,
+    Sinks,
+    Commit,
+    Rollback,
+}
+
+impl IndexingStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IndexingStage::Traversals => "traversals",
+            IndexingStage::Sequencing => "sequencing",
+            IndexingStage::Transfers => "transfers",
+            IndexingStage::OrdinalsWrite => "ordinals_write",
+            IndexingStage::Brc20 => "brc20",
+            IndexingStage::Sinks => "sinks",
+            IndexingStage::Commit => "commit",
+            IndexingStage::Rollback => "rollback",
+        }
+    }
+}
+
+impl fmt::Display for IndexingStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error raised by ordhook. Failures of the ordinals and BRC-20 database helpers are `Postgres` errors, classified by
+/// their SQLSTATE. Other errors, including bitcoind RPC failures reported by the SDK, are still strings that end up in
+/// `Other` and are never considered retryable.
+#[derive(Debug, thiserror::Error)]
+pub enum OrdhookError {
+    #[error(transparent)]
+    Postgres(#[from] PgError),
+    #[error("block #{block_height} failed at {stage}: {source}")]
+    Block {
+        block_height: u64,
+        stage: IndexingStage,
+        source: Box<OrdhookError>,
+    },
+    #[error("{0}")]
+    Other(String),
+}
+
+impl OrdhookError {
+    /// Tags the error with the block and pipeline stage it was raised in.
+    pub fn at_block(self, block_height: u64, stage: IndexingStage) -> Self {
+        OrdhookError::Block {
+            block_height,
+            stage,
+            source: Box::new(self),
+        }
+    }
+
+    /// `true` if indexing the same block again may succeed, e.g. once Postgres is reachable again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            OrdhookError::Postgres(e) => e.is_retryable(),
+            OrdhookError::Block { source, .. } => source.is_retryable(),
+            OrdhookError::Other(_) => false,
+        }
+    }
+
+    pub fn block_height(&self) -> Option<u64> {
+        match self {
+            OrdhookError::Block { block_height, .. } => Some(*block_height),
+            _ => None,
+        }
+    }
+
+    pub fn stage(&self) -> Option<IndexingStage> {
+        match self {
+            OrdhookError::Block { stage, .. } => Some(*stage),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for OrdhookError {
+    fn from(e: String) -> Self {
+        OrdhookError::Other(e)
+    }
+}
+
+impl From<OrdhookError> for String {
+    fn from(e: OrdhookError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Adds block and stage context to the error of a result.
+pub trait BlockErrorContext<T> {
+    fn at_block(self, block_height: u64, stage: IndexingStage) -> Result<T, OrdhookError>;
+}
+
+impl<T, E: Into<OrdhookError>> BlockErrorContext<T> for Result<T, E> {
+    fn at_block(self, block_height: u64, stage: IndexingStage) -> Result<T, OrdhookError> {
+        self.map_err(|e| e.into().at_block(block_height, stage))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlockErrorContext, IndexingStage, OrdhookError};
+
+    #[test]
+    fn keeps_block_context() {
+        let result: Result<(), String> = Err("unable to insert block".to_string());
+        let e = result
+            .at_block(840000, IndexingStage::OrdinalsWrite)
+            .unwrap_err();
+        assert_eq!(e.block_height(), Some(840000));
+        assert_eq!(e.stage(), Some(IndexingStage::OrdinalsWrite));
+        assert!(!e.is_retryable());
+        assert_eq!(
+            String::from(e),
+            "block #840000 failed at ordinals_write: unable to insert block"
+        );
+        assert!(OrdhookError::from("oops".to_string())
+            .block_height()
+            .is_none());
+    }
+}
@@ -13,5 +13,6 @@ pub mod config;
 pub mod core;
 pub mod db;
 pub mod download;
+pub mod error;
 pub mod service;
 pub mod utils;
@@ -0,0 +1,314 @@
+use std::{collections::BTreeMap, io::Write, str::FromStr};
+
+use chainhook_postgres::{
+    pg_pool, pg_pool_client,
+    types::{PgNumericU128, PgNumericU64, PgSmallIntU8},
+};
+use chainhook_sdk::utils::Context;
+
+use crate::{config::Config, core::meta_protocols::brc20::u128_amount_to_decimals_str, try_info};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ActivityReportFormat {
+    /// `date,metric,ticker,value` lines, after a header line. `ticker` is only set for BRC-20 metrics.
+    Csv,
+    /// A single `{"from_date": ..., "to_date": ..., "days": [...]}` document.
+    Json,
+}
+
+impl FromStr for ActivityReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ActivityReportFormat::Csv),
+            "json" => Ok(ActivityReportFormat::Json),
+            _ => Err(format!("unknown report format {s}, expected csv or json")),
+        }
+    }
+}
+
+/// BRC-20 activity of a token over a day. Amounts are formatted with the token's decimals, like in the API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Brc20TickerActivity {
+    pub ticker: String,
+    /// Deploys, mints, transfer inscriptions and transfer sends.
+    pub operations: u64,
+    pub minted: String,
+    /// Amount moved by transfer sends.
+    pub transferred: String,
+}
+
+/// Activity of a UTC day.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct DailyActivity {
+    pub date: String,
+    pub inscriptions: u64,
+    /// Fees paid by the reveal transactions of the day's inscriptions, in sats.
+    pub inscription_fees: u64,
+    /// Empty when BRC-20 indexing is not enabled.
+    pub brc20: Vec<Brc20TickerActivity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ActivityReport {
+    pub from_date: String,
+    pub to_date: String,
+    /// Days with activity, in chronological order.
+    pub days: Vec<DailyActivity>,
+}
+
+/// Parses a `YYYY-MM-DD` date into the unix timestamp of its UTC midnight.
+pub fn parse_report_date(date: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid date {date}, expected YYYY-MM-DD");
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return Err(invalid());
+    }
+    let year: i64 = year.parse().map_err(|_| invalid())?;
+    let month: i64 = month.parse().map_err(|_| invalid())?;
+    let day: i64 = day.parse().map_err(|_| invalid())?;
+    let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap_year => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if day < 1 || day > days_in_month {
+        return Err(invalid());
+    }
+    // Days since 1970-01-01 in the proleptic Gregorian calendar, counting years from March so leap days come last.
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Ok((era * 146_097 + day_of_era - 719_468) * SECONDS_PER_DAY)
+}
+
+/// Aggregates inscriptions, inscription fees and BRC-20 activity per UTC day, from `from_date` to `to_date` included.
+/// Days are bucketed by block timestamp. Both indexes are scanned over the whole range, so the report is meant to be
+/// generated against a replica or off-peak.
+pub async fn build_activity_report(
+    config: &Config,
+    from_date: &str,
+    to_date: &str,
+    ctx: &Context,
+) -> Result<ActivityReport, String> {
+    let from_timestamp = parse_report_date(from_date)?;
+    let to_timestamp = parse_report_date(to_date)? + SECONDS_PER_DAY;
+    if from_timestamp >= to_timestamp {
+        return Err(format!("from date {from_date} is after to date {to_date}"));
+    }
+    try_info!(
+        ctx,
+        "Building activity report from {from_date} to {to_date}"
+    );
+    let mut days: BTreeMap<String, DailyActivity> = BTreeMap::new();
+    {
+        let ord_pool = pg_pool(&config.ordinals_db)?;
+        let ord_client = pg_pool_client(&ord_pool).await?;
+        let rows = ord_client
+            .query(
+                "SELECT TO_CHAR(TO_TIMESTAMP(timestamp) AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS date,
+                    COUNT(*) AS inscriptions, COALESCE(SUM(fee), 0) AS fees
+                FROM inscriptions
+                WHERE timestamp >= $1 AND timestamp < $2
+                GROUP BY date",
+                &[&from_timestamp, &to_timestamp],
+            )
+            .await
+            .map_err(|e| format!("build_activity_report: {e}"))?;
+        for row in rows.iter() {
+            let date: String = row.get("date");
+            let inscriptions: i64 = row.get("inscriptions");
+            let fees: PgNumericU64 = row.get("fees");
+            let day = days.entry(date.clone()).or_insert_with(|| DailyActivity {
+                date,
+                ..Default::default()
+            });
+            day.inscriptions = inscriptions as u64;
+            day.inscription_fees = fees.0;
+        }
+    }
+    if let Some(brc20_db) = &config.brc20_db {
+        let brc20_pool = pg_pool(brc20_db)?;
+        let brc20_client = pg_pool_client(&brc20_pool).await?;
+        let rows = brc20_client
+            .query(
+                "SELECT TO_CHAR(TO_TIMESTAMP(o.timestamp) AT TIME ZONE 'UTC', 'YYYY-MM-DD') AS date,
+                    t.display_ticker, t.decimals,
+                    COUNT(*) FILTER (WHERE o.operation <> 'transfer_receive') AS operations,
+                    COALESCE(SUM(o.amount) FILTER (WHERE o.operation = 'mint'), 0) AS minted,
+                    COALESCE(SUM(o.amount) FILTER (WHERE o.operation = 'transfer_send'), 0) AS transferred
+                FROM operations AS o
+                INNER JOIN tokens AS t ON t.ticker = o.ticker
+                WHERE o.timestamp >= $1 AND o.timestamp < $2
+                GROUP BY date, o.ticker, t.display_ticker, t.decimals
+                ORDER BY date, o.ticker",
+                &[&from_timestamp, &to_timestamp],
+            )
+            .await
+            .map_err(|e| format!("build_activity_report: {e}"))?;
+        for row in rows.iter() {
+            let date: String = row.get("date");
+            let operations: i64 = row.get("operations");
+            let decimals: PgSmallIntU8 = row.get("decimals");
+            let minted: PgNumericU128 = row.get("minted");
+            let transferred: PgNumericU128 = row.get("transferred");
+            let day = days.entry(date.clone()).or_insert_with(|| DailyActivity {
+                date,
+                ..Default::default()
+            });
+            day.brc20.push(Brc20TickerActivity {
+                ticker: row.get("display_ticker"),
+                operations: operations as u64,
+                minted: u128_amount_to_decimals_str(minted.0, decimals.0),
+                transferred: u128_amount_to_decimals_str(transferred.0, decimals.0),
+            });
+        }
+    }
+    Ok(ActivityReport {
+        from_date: from_date.to_string(),
+        to_date: to_date.to_string(),
+        days: days.into_values().collect(),
+    })
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("unable to write report: {e}")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn write_activity_report<W: Write>(
+    report: &ActivityReport,
+    format: ActivityReportFormat,
+    writer: &mut W,
+) -> Result<(), String> {
+    match format {
+        ActivityReportFormat::Csv => {
+            writeln!(writer, "date,metric,ticker,value").map_err(write_error)?;
+            for day in report.days.iter() {
+                let date = &day.date;
+                writeln!(writer, "{date},inscriptions,,{}", day.inscriptions)
+                    .map_err(write_error)?;
+                writeln!(writer, "{date},inscription_fees,,{}", day.inscription_fees)
+                    .map_err(write_error)?;
+                for token in day.brc20.iter() {
+                    let ticker = csv_field(&token.ticker);
+                    writeln!(
+                        writer,
+                        "{date},brc20_operations,{ticker},{}",
+                        token.operations
+                    )
+                    .map_err(write_error)?;
+                    writeln!(writer, "{date},brc20_minted,{ticker},{}", token.minted)
+                        .map_err(write_error)?;
+                    writeln!(
+                        writer,
+                        "{date},brc20_transferred,{ticker},{}",
+                        token.transferred
+                    )
+                    .map_err(write_error)?;
+                }
+            }
+        }
+        ActivityReportFormat::Json => {
+            serde_json::to_writer(&mut *writer, report)
+                .map_err(|e| format!("unable to write report: {e}"))?;
+            writeln!(writer).map_err(write_error)?;
+        }
+    }
+    writer.flush().map_err(write_error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_report_date, write_activity_report, ActivityReport, ActivityReportFormat,
+        Brc20TickerActivity, DailyActivity,
+    };
+
+    fn report() -> ActivityReport {
+        ActivityReport {
+            from_date: "2024-02-28".to_string(),
+            to_date: "2024-03-01".to_string(),
+            days: vec![
+                DailyActivity {
+                    date: "2024-02-28".to_string(),
+                    inscriptions: 12,
+                    inscription_fees: 34_000,
+                    brc20: vec![Brc20TickerActivity {
+                        ticker: "a,\"b".to_string(),
+                        operations: 3,
+                        minted: "1000.000000000000000000".to_string(),
+                        transferred: "0.000000000000000000".to_string(),
+                    }],
+                },
+                DailyActivity {
+                    date: "2024-03-01".to_string(),
+                    inscriptions: 1,
+                    inscription_fees: 500,
+                    brc20: vec![],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn parses_report_dates() {
+        assert_eq!(parse_report_date("1970-01-01"), Ok(0));
+        assert_eq!(parse_report_date("2024-02-29"), Ok(1_709_164_800));
+        assert_eq!(parse_report_date("2000-03-01"), Ok(951_868_800));
+        assert!(parse_report_date("2023-02-29").is_err());
+        assert!(parse_report_date("2024-13-01").is_err());
+        assert!(parse_report_date("2024-1-01").is_err());
+        assert!(parse_report_date("20240101").is_err());
+    }
+
+    #[test]
+    fn writes_csv_report() {
+        let mut out = vec![];
+        write_activity_report(&report(), ActivityReportFormat::Csv, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "date,metric,ticker,value\n\
+            2024-02-28,inscriptions,,12\n\
+            2024-02-28,inscription_fees,,34000\n\
+            2024-02-28,brc20_operations,\"a,\"\"b\",3\n\
+            2024-02-28,brc20_minted,\"a,\"\"b\",1000.000000000000000000\n\
+            2024-02-28,brc20_transferred,\"a,\"\"b\",0.000000000000000000\n\
+            2024-03-01,inscriptions,,1\n\
+            2024-03-01,inscription_fees,,500\n"
+        );
+    }
+
+    #[test]
+    fn writes_json_report() {
+        let mut out = vec![];
+        write_activity_report(&report(), ActivityReportFormat::Json, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["from_date"], "2024-02-28");
+        assert_eq!(json["days"].as_array().unwrap().len(), 2);
+        assert_eq!(json["days"][0]["inscription_fees"], 34_000);
+        assert_eq!(json["days"][0]["brc20"][0]["operations"], 3);
+    }
+}
@@ -0,0 +1,531 @@
+use std::{collections::HashSet, sync::Arc};
+
+use chainhook_sdk::utils::Context;
+use chainhook_types::{
+    BitcoinBlockData, BlockIdentifier, Brc20Operation, OrdinalInscriptionTransferDestination,
+    OrdinalOperation,
+};
+#[cfg(feature = "websocket")]
+use futures_util::{SinkExt, StreamExt};
+use hyper::{
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    Body, Response,
+};
+#[cfg(feature = "websocket")]
+use hyper::{
+    header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE},
+    Request,
+};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use tokio::sync::broadcast;
+#[cfg(feature = "websocket")]
+use tokio_tungstenite::{
+    tungstenite::{handshake::derive_accept_key, protocol::Role, Message},
+    WebSocketStream,
+};
+
+use crate::{try_debug, try_warn};
+
+use super::block_events::{serialize_ordinal_operation, CharmsFormat, PayloadFormat};
+
+/// Number of blocks buffered for each streaming client. Clients that fall further behind skip the blocks they missed.
+const ACTIVITY_STREAM_CAPACITY: usize = 100;
+
+/// Ordinal activity of an indexed block, broadcast to every streaming client as soon as the block is indexed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrdinalBlockActivity {
+    pub block_identifier: BlockIdentifier,
+    pub timestamp: u32,
+    pub timestamp_ms: Option<u64>,
+    /// Operations of the block along with the id of the transaction they belong to.
+    pub operations: Vec<(String, OrdinalOperation)>,
+    /// BRC-20 operations of the block along with the id of the transaction they belong to.
+    pub brc20_operations: Vec<(String, Brc20Operation)>,
+    pub charms_format: CharmsFormat,
+}
+
+/// A single ordinal operation, as pushed to WebSocket clients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrdinalActivityEvent {
+    pub block_identifier: BlockIdentifier,
+    pub timestamp: u32,
+    pub timestamp_ms: Option<u64>,
+    pub tx_id: String,
+    pub operation: OrdinalOperation,
+    pub charms_format: CharmsFormat,
+}
+
+impl Serialize for OrdinalActivityEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Operation<'a>(&'a OrdinalOperation, CharmsFormat);
+        impl Serialize for Operation<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serialize_ordinal_operation(self.0, self.1, serializer)
+            }
+        }
+        let mut state = serializer.serialize_struct("OrdinalActivityEvent", 5)?;
+        state.serialize_field("block_identifier", &self.block_identifier)?;
+        state.serialize_field("timestamp", &self.timestamp)?;
+        match self.timestamp_ms {
+            Some(timestamp_ms) => state.serialize_field("timestamp_ms", &timestamp_ms)?,
+            None => state.skip_field("timestamp_ms")?,
+        }
+        state.serialize_field("tx_id", &self.tx_id)?;
+        state.serialize_field("operation", &Operation(&self.operation, self.charms_format))?;
+        state.end()
+    }
+}
+
+/// A single BRC-20 operation, as pushed to Server-Sent Events clients.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Brc20ActivityEvent {
+    pub block_identifier: BlockIdentifier,
+    pub timestamp: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<u64>,
+    pub tx_id: String,
+    pub operation: Brc20Operation,
+}
+
+impl OrdinalBlockActivity {
+    pub fn from_block(block: &BitcoinBlockData, payload_format: PayloadFormat) -> Self {
+        let mut operations = vec![];
+        let mut brc20_operations = vec![];
+        for tx in block.transactions.iter() {
+            for operation in tx.metadata.ordinal_operations.iter() {
+                operations.push((tx.transaction_identifier.hash.clone(), operation.clone()));
+            }
+            if let Some(operation) = &tx.metadata.brc20_operation {
+                brc20_operations.push((tx.transaction_identifier.hash.clone(), operation.clone()));
+            }
+        }
+        OrdinalBlockActivity {
+            block_identifier: block.block_identifier.clone(),
+            timestamp: block.timestamp,
+            timestamp_ms: payload_format.timestamp_ms(block),
+            operations,
+            brc20_operations,
+            charms_format: payload_format.charms,
+        }
+    }
+}
+
+pub type ActivityStreamSender = broadcast::Sender<Arc<OrdinalBlockActivity>>;
+
+pub fn new_activity_stream() -> ActivityStreamSender {
+    broadcast::channel(ACTIVITY_STREAM_CAPACITY).0
+}
+
+/// Pushes the ordinal activity of an indexed block to the connected streaming clients, if any.
+pub fn publish_ordinal_activity(
+    block: &BitcoinBlockData,
+    payload_format: PayloadFormat,
+    activity_stream: &ActivityStreamSender,
+) {
+    if activity_stream.receiver_count() == 0 {
+        return;
+    }
+    let _ = activity_stream.send(Arc::new(OrdinalBlockActivity::from_block(
+        block,
+        payload_format,
+    )));
+}
+
+/// Per-connection filter built from the stream request's query string, e.g.
+/// `?address=bc1p...&inscription_id=...i0&sat_from=0&sat_to=5000000000`. Repeated `address` and `inscription_id`
+/// parameters are OR-ed together, and an empty filter lets every event through.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActivityStreamFilter {
+    pub addresses: HashSet<String>,
+    pub inscription_ids: HashSet<String>,
+    /// Ordinal numbers of the inscriptions in `inscription_ids`, used to match their transfers.
+    pub ordinal_numbers: HashSet<u64>,
+    pub sat_range: Option<(u64, u64)>,
+}
+
+impl ActivityStreamFilter {
+    pub fn from_query(query: Option<&str>) -> Result<Self, String> {
+        let mut filter = ActivityStreamFilter::default();
+        let (mut sat_from, mut sat_to) = (None, None);
+        for pair in query.unwrap_or("").split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or(format!("invalid query parameter {pair}"))?;
+            match key {
+                "address" => {
+                    filter.addresses.insert(value.to_string());
+                }
+                "inscription_id" => {
+                    filter.inscription_ids.insert(value.to_string());
+                }
+                "sat_from" => {
+                    sat_from = Some(value.parse::<u64>().map_err(|_| "invalid sat_from")?);
+                }
+                "sat_to" => {
+                    sat_to = Some(value.parse::<u64>().map_err(|_| "invalid sat_to")?);
+                }
+                _ => return Err(format!("unknown query parameter {key}")),
+            }
+        }
+        filter.sat_range = match (sat_from, sat_to) {
+            (None, None) => None,
+            (from, to) => Some((from.unwrap_or(0), to.unwrap_or(u64::MAX))),
+        };
+        Ok(filter)
+    }
+
+    /// Returns true if the operation should be pushed to this connection. A matching reveal adds its ordinal number to
+    /// the filter so later transfers of that inscription match too.
+    pub fn matches(&mut self, operation: &OrdinalOperation) -> bool {
+        let (ordinal_number, address, inscription_id) = match operation {
+            OrdinalOperation::InscriptionRevealed(reveal) => (
+                reveal.ordinal_number,
+                reveal.inscriber_address.as_ref(),
+                Some(&reveal.inscription_id),
+            ),
+            OrdinalOperation::InscriptionTransferred(transfer) => (
+                transfer.ordinal_number,
+                match &transfer.destination {
+                    OrdinalInscriptionTransferDestination::Transferred(address)
+                    | OrdinalInscriptionTransferDestination::Burnt(address) => Some(address),
+                    OrdinalInscriptionTransferDestination::SpentInFees => None,
+                },
+                None,
+            ),
+        };
+        if let Some((from, to)) = self.sat_range {
+            if ordinal_number < from || ordinal_number > to {
+                return false;
+            }
+        }
+        if self.addresses.is_empty() && self.inscription_ids.is_empty() {
+            return true;
+        }
+        if address.is_some_and(|address| self.addresses.contains(address)) {
+            return true;
+        }
+        if inscription_id.is_some_and(|id| self.inscription_ids.contains(id)) {
+            self.ordinal_numbers.insert(ordinal_number);
+            return true;
+        }
+        self.ordinal_numbers.contains(&ordinal_number)
+    }
+
+    /// Events of a block that should be pushed to this connection, in block order.
+    pub fn matching_events(&mut self, block: &OrdinalBlockActivity) -> Vec<OrdinalActivityEvent> {
+        let mut events = vec![];
+        for (tx_id, operation) in block.operations.iter() {
+            if !self.matches(operation) {
+                continue;
+            }
+            events.push(OrdinalActivityEvent {
+                block_identifier: block.block_identifier.clone(),
+                timestamp: block.timestamp,
+                timestamp_ms: block.timestamp_ms,
+                tx_id: tx_id.clone(),
+                operation: operation.clone(),
+                charms_format: block.charms_format,
+            });
+        }
+        events
+    }
+}
+
+/// Evaluates a stream filter against a sequence of blocks, without a node or a database, and returns the events a
+/// client connected with that filter would have received. Blocks are expected in chain order: transfers of an
+/// inscription only match once its reveal was seen. Handy to test filters against block fixtures, e.g. built with the
+/// `TestBlockBuilder` exposed by the `test-kit` feature.
+pub fn find_filter_occurrences(
+    filter: &ActivityStreamFilter,
+    blocks: &[BitcoinBlockData],
+    payload_format: PayloadFormat,
+) -> Vec<OrdinalActivityEvent> {
+    let mut filter = filter.clone();
+    blocks
+        .iter()
+        .flat_map(|block| {
+            filter.matching_events(&OrdinalBlockActivity::from_block(block, payload_format))
+        })
+        .collect()
+}
+
+/// Upgrades an HTTP request to a WebSocket connection that receives the matching ordinal activity as JSON text frames.
+#[cfg(feature = "websocket")]
+pub fn serve_activity_stream(
+    mut req: Request<Body>,
+    mut filter: ActivityStreamFilter,
+    activity_stream: &ActivityStreamSender,
+    ctx: &Context,
+) -> Response<Body> {
+    let Some(accept_key) = req
+        .headers()
+        .get(SEC_WEBSOCKET_KEY)
+        .map(|key| derive_accept_key(key.as_bytes()))
+    else {
+        return Response::builder()
+            .status(400)
+            .body(Body::from("expected a websocket upgrade request"))
+            .unwrap();
+    };
+    let mut receiver = activity_stream.subscribe();
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        let upgraded = match hyper::upgrade::on(&mut req).await {
+            Ok(upgraded) => upgraded,
+            Err(e) => {
+                try_warn!(ctx, "Activity stream: upgrade failed: {e}");
+                return;
+            }
+        };
+        let mut websocket = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
+        try_debug!(ctx, "Activity stream: client connected");
+        loop {
+            tokio::select! {
+                block = receiver.recv() => {
+                    let block = match block {
+                        Ok(block) => block,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            try_warn!(ctx, "Activity stream: client lagging, {skipped} blocks skipped");
+                            continue;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    let mut disconnected = false;
+                    for event in filter.matching_events(&block) {
+                        let Ok(payload) = serde_json::to_string(&event) else {
+                            continue;
+                        };
+                        if websocket.send(Message::Text(payload)).await.is_err() {
+                            disconnected = true;
+                            break;
+                        }
+                    }
+                    if disconnected {
+                        break;
+                    }
+                }
+                message = websocket.next() => match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                },
+            }
+        }
+        try_debug!(ctx, "Activity stream: client disconnected");
+    });
+    Response::builder()
+        .status(101)
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_ACCEPT, accept_key)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn sse_message<T: serde::Serialize>(event_name: &str, event: &T) -> String {
+    match serde_json::to_string(event) {
+        Ok(payload) => format!("event: {event_name}\ndata: {payload}\n\n"),
+        Err(_) => String::new(),
+    }
+}
+
+/// Formats the activity of a block as Server-Sent Events messages: one `ordinal` event per inscription reveal or
+/// transfer, followed by one `brc20` event per BRC-20 operation.
+pub fn block_activity_sse_messages(block: &OrdinalBlockActivity) -> String {
+    let mut messages = String::new();
+    for (tx_id, operation) in block.operations.iter() {
+        let event = OrdinalActivityEvent {
+            block_identifier: block.block_identifier.clone(),
+            timestamp: block.timestamp,
+            timestamp_ms: block.timestamp_ms,
+            tx_id: tx_id.clone(),
+            operation: operation.clone(),
+            charms_format: block.charms_format,
+        };
+        messages.push_str(&sse_message("ordinal", &event));
+    }
+    for (tx_id, operation) in block.brc20_operations.iter() {
+        let event = Brc20ActivityEvent {
+            block_identifier: block.block_identifier.clone(),
+            timestamp: block.timestamp,
+            timestamp_ms: block.timestamp_ms,
+            tx_id: tx_id.clone(),
+            operation: operation.clone(),
+        };
+        messages.push_str(&sse_message("brc20", &event));
+    }
+    messages
+}
+
+/// Responds with a Server-Sent Events stream that receives all ordinal and BRC-20 activity as blocks get indexed.
+pub fn serve_event_stream(activity_stream: &ActivityStreamSender, ctx: &Context) -> Response<Body> {
+    let mut receiver = activity_stream.subscribe();
+    let (mut sender, body) = Body::channel();
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        try_debug!(ctx, "Event stream: client connected");
+        loop {
+            let block = match receiver.recv().await {
+                Ok(block) => block,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    try_warn!(
+                        ctx,
+                        "Event stream: client lagging, {skipped} blocks skipped"
+                    );
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let messages = block_activity_sse_messages(&block);
+            if messages.is_empty() {
+                continue;
+            }
+            if sender.send_data(messages.into()).await.is_err() {
+                break;
+            }
+        }
+        try_debug!(ctx, "Event stream: client disconnected");
+    });
+    Response::builder()
+        .status(200)
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(body)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_types::{
+        BlockIdentifier, Brc20BalanceData, Brc20Operation, OrdinalInscriptionTransferData,
+        OrdinalInscriptionTransferDestination, OrdinalOperation,
+    };
+
+    use crate::core::{
+        meta_protocols::brc20::test_utils::Brc20RevealBuilder,
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::{
+        block_activity_sse_messages, find_filter_occurrences, ActivityStreamFilter, CharmsFormat,
+        OrdinalBlockActivity, PayloadFormat,
+    };
+
+    fn transfer(ordinal_number: u64, address: &str) -> OrdinalOperation {
+        OrdinalOperation::InscriptionTransferred(OrdinalInscriptionTransferData {
+            ordinal_number,
+            destination: OrdinalInscriptionTransferDestination::Transferred(address.to_string()),
+            from_address: None,
+            satpoint_pre_transfer: "".to_string(),
+            satpoint_post_transfer: "".to_string(),
+            post_transfer_output_value: None,
+            tx_index: 0,
+        })
+    }
+
+    #[test]
+    fn parses_stream_filters() {
+        let filter = ActivityStreamFilter::from_query(Some(
+            "address=bc1pa&address=bc1pb&inscription_id=abci0&sat_from=10",
+        ))
+        .unwrap();
+        assert_eq!(filter.addresses.len(), 2);
+        assert!(filter.inscription_ids.contains("abci0"));
+        assert_eq!(filter.sat_range, Some((10, u64::MAX)));
+        assert!(ActivityStreamFilter::from_query(Some("sat_to=abc")).is_err());
+        assert!(ActivityStreamFilter::from_query(Some("unknown=1")).is_err());
+        assert_eq!(
+            ActivityStreamFilter::from_query(None).unwrap(),
+            ActivityStreamFilter::default()
+        );
+    }
+
+    #[test]
+    fn follows_transfers_of_filtered_inscriptions() {
+        let reveal = Brc20RevealBuilder::new().ordinal_number(700).build();
+        let mut filter = ActivityStreamFilter::from_query(Some(&format!(
+            "inscription_id={}",
+            reveal.inscription_id
+        )))
+        .unwrap();
+        assert!(!filter.matches(&transfer(700, "bc1pa")));
+        assert!(filter.matches(&OrdinalOperation::InscriptionRevealed(reveal)));
+        assert!(filter.matches(&transfer(700, "bc1pa")));
+        assert!(!filter.matches(&transfer(701, "bc1pa")));
+    }
+
+    #[test]
+    fn filters_by_address_and_sat_range() {
+        let mut filter =
+            ActivityStreamFilter::from_query(Some("address=bc1pa&sat_from=100&sat_to=200"))
+                .unwrap();
+        assert!(filter.matches(&transfer(150, "bc1pa")));
+        assert!(!filter.matches(&transfer(150, "bc1pb")));
+        assert!(!filter.matches(&transfer(250, "bc1pa")));
+    }
+
+    #[test]
+    fn finds_filter_occurrences_across_blocks() {
+        let reveal = Brc20RevealBuilder::new().ordinal_number(700).build();
+        let filter = ActivityStreamFilter::from_query(Some(&format!(
+            "inscription_id={}",
+            reveal.inscription_id
+        )))
+        .unwrap();
+        let blocks = vec![
+            TestBlockBuilder::new()
+                .height(840000)
+                .add_transaction(
+                    TestTransactionBuilder::new()
+                        .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(reveal))
+                        .build(),
+                )
+                .build(),
+            TestBlockBuilder::new()
+                .height(840001)
+                .add_transaction(
+                    TestTransactionBuilder::new()
+                        .add_ordinal_operation(transfer(700, "bc1pa"))
+                        .add_ordinal_operation(transfer(701, "bc1pa"))
+                        .build(),
+                )
+                .build(),
+        ];
+
+        let occurrences = find_filter_occurrences(&filter, &blocks, PayloadFormat::default());
+
+        assert_eq!(occurrences.len(), 2);
+        assert_eq!(occurrences[0].block_identifier.index, 840000);
+        assert_eq!(occurrences[1].block_identifier.index, 840001);
+        assert_eq!(occurrences[1].operation, transfer(700, "bc1pa"));
+        assert!(filter.ordinal_numbers.is_empty());
+    }
+
+    #[test]
+    fn formats_sse_messages() {
+        let block = OrdinalBlockActivity {
+            block_identifier: BlockIdentifier {
+                index: 840_000,
+                hash: "0xabcd".to_string(),
+            },
+            timestamp: 1713571767,
+            timestamp_ms: Some(1713571767000),
+            operations: vec![("0x01".to_string(), transfer(700, "bc1pa"))],
+            brc20_operations: vec![(
+                "0x02".to_string(),
+                Brc20Operation::Mint(Brc20BalanceData {
+                    tick: "pepe".to_string(),
+                    amt: "1000".to_string(),
+                    address: "bc1pa".to_string(),
+                    inscription_id: "02i0".to_string(),
+                }),
+            )],
+            charms_format: CharmsFormat::Names,
+        };
+        let messages = block_activity_sse_messages(&block);
+        let events: Vec<&str> = messages.split_terminator("\n\n").collect();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].starts_with("event: ordinal\ndata: {"));
+        assert!(events[1].starts_with("event: brc20\ndata: {"));
+        assert!(events[1].contains("\"mint\""));
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use chainhook_sdk::utils::Context;
+use chainhook_types::{
+    BitcoinBlockData, Brc20Operation, OrdinalInscriptionTransferDestination, OrdinalOperation,
+};
+use deadpool_postgres::Transaction;
+use reqwest::header::CONTENT_TYPE;
+
+use crate::{config::Config, db::ordinals_pg, try_debug, try_warn};
+
+/// Maps addresses to the cluster or entity ids they belong to. Clusterers are asked about every address involved in an
+/// indexed block, and the ids they return are stored in `address_clusters` so holdings can be queried per cluster.
+/// Addresses left out of the returned map keep their previous cluster, if any.
+#[async_trait]
+pub trait AddressClusterer: Send + Sync {
+    /// Short name used in logs and stored as the source of the clusters it returns.
+    fn name(&self) -> &str;
+
+    async fn cluster_addresses(
+        &self,
+        block_height: u64,
+        addresses: &[String],
+        ctx: &Context,
+    ) -> Result<HashMap<String, String>, String>;
+}
+
+#[derive(Debug, Serialize)]
+struct ClusterRequest<'a> {
+    block_height: u64,
+    addresses: &'a [String],
+}
+
+/// Posts `{"block_height": ..., "addresses": [...]}` to a URL and expects a JSON object mapping addresses to cluster ids.
+pub struct HttpAddressClusterer {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl HttpAddressClusterer {
+    pub fn new(url: &str) -> Self {
+        HttpAddressClusterer {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl AddressClusterer for HttpAddressClusterer {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn cluster_addresses(
+        &self,
+        block_height: u64,
+        addresses: &[String],
+        _ctx: &Context,
+    ) -> Result<HashMap<String, String>, String> {
+        let res = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .json(&ClusterRequest {
+                block_height,
+                addresses,
+            })
+            .send()
+            .await
+            .map_err(|e| format!("unable to reach address clusterer: {e}"))?;
+        if !res.status().is_success() {
+            return Err(format!("address clusterer returned {}", res.status()));
+        }
+        res.json()
+            .await
+            .map_err(|e| format!("invalid address clusterer response: {e}"))
+    }
+}
+
+/// Clusterer posting to `address_clustering.url`, followed by the custom ones. Clustering is disabled on dry runs.
+pub fn configured_address_clusterers(config: &Config) -> Vec<Arc<dyn AddressClusterer>> {
+    let mut clusterers: Vec<Arc<dyn AddressClusterer>> = vec![];
+    if config.dry_run {
+        return clusterers;
+    }
+    if let Some(url) = &config.address_clustering.url {
+        clusterers.push(Arc::new(HttpAddressClusterer::new(url)));
+    }
+    clusterers.extend(config.address_clustering.custom.iter().cloned());
+    clusterers
+}
+
+/// Every address that received an inscription or took part in a BRC-20 operation in `block`, sorted and deduplicated.
+pub fn collect_block_addresses(block: &BitcoinBlockData) -> Vec<String> {
+    let mut addresses = BTreeSet::new();
+    for tx in block.transactions.iter() {
+        for op in tx.metadata.ordinal_operations.iter() {
+            match op {
+                OrdinalOperation::InscriptionRevealed(reveal) => {
+                    if let Some(address) = &reveal.inscriber_address {
+                        addresses.insert(address.clone());
+                    }
+                }
+                OrdinalOperation::InscriptionTransferred(transfer) => {
+                    if let OrdinalInscriptionTransferDestination::Transferred(address) =
+                        &transfer.destination
+                    {
+                        addresses.insert(address.clone());
+                    }
+                    if let Some(address) = &transfer.from_address {
+                        addresses.insert(address.clone());
+                    }
+                }
+            }
+        }
+        match &tx.metadata.brc20_operation {
+            Some(Brc20Operation::Deploy(deploy)) => {
+                addresses.insert(deploy.address.clone());
+            }
+            Some(Brc20Operation::Mint(mint)) => {
+                addresses.insert(mint.address.clone());
+            }
+            Some(Brc20Operation::Transfer(transfer)) => {
+                addresses.insert(transfer.address.clone());
+            }
+            Some(Brc20Operation::TransferSend(transfer)) => {
+                addresses.insert(transfer.sender_address.clone());
+                addresses.insert(transfer.receiver_address.clone());
+            }
+            None => {}
+        }
+    }
+    addresses.into_iter().filter(|a| !a.is_empty()).collect()
+}
+
+/// Asks every configured clusterer about the addresses of `block` and stores their answers in `ord_tx`. Clusters are
+/// enrichment data rather than chain data, so a failing clusterer is logged and skipped instead of stopping indexing;
+/// its addresses are asked about again the next time they show up in a block.
+pub async fn cluster_block_addresses(
+    block: &BitcoinBlockData,
+    config: &Config,
+    ord_tx: &Transaction<'_>,
+    ctx: &Context,
+) -> Result<(), String> {
+    let clusterers = configured_address_clusterers(config);
+    if clusterers.is_empty() {
+        return Ok(());
+    }
+    let block_height = block.block_identifier.index;
+    let addresses = collect_block_addresses(block);
+    if addresses.is_empty() {
+        return Ok(());
+    }
+    for clusterer in clusterers.iter() {
+        match clusterer
+            .cluster_addresses(block_height, &addresses, ctx)
+            .await
+        {
+            Ok(clusters) => {
+                try_debug!(
+                    ctx,
+                    "Address clustering: {} returned {} clusters for block #{block_height}",
+                    clusterer.name(),
+                    clusters.len()
+                );
+                ordinals_pg::upsert_address_clusters(
+                    &clusters,
+                    clusterer.name(),
+                    block_height,
+                    ord_tx,
+                )
+                .await?;
+            }
+            Err(e) => {
+                try_warn!(
+                    ctx,
+                    "Address clustering: {} failed for block #{block_height}: {e}",
+                    clusterer.name()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_types::{
+        Brc20Operation, Brc20TransferData, OrdinalInscriptionTransferData,
+        OrdinalInscriptionTransferDestination, OrdinalOperation,
+    };
+
+    use crate::core::{
+        meta_protocols::brc20::test_utils::Brc20RevealBuilder,
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::collect_block_addresses;
+
+    #[test]
+    fn collects_sorted_unique_block_addresses() {
+        let block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(
+                        Brc20RevealBuilder::new()
+                            .inscriber_address(Some("bc1qinscriber".to_string()))
+                            .build(),
+                    ))
+                    .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                        OrdinalInscriptionTransferData {
+                            ordinal_number: 500,
+                            destination: OrdinalInscriptionTransferDestination::SpentInFees,
+                            from_address: Some("bc1qsender".to_string()),
+                            satpoint_pre_transfer: "".to_string(),
+                            satpoint_post_transfer: "".to_string(),
+                            post_transfer_output_value: None,
+                            tx_index: 0,
+                        },
+                    ))
+                    .brc20_operation(Some(Brc20Operation::TransferSend(Brc20TransferData {
+                        tick: "pepe".to_string(),
+                        amt: "10.000000000000000000".to_string(),
+                        sender_address: "bc1qsender".to_string(),
+                        receiver_address: "bc1qreceiver".to_string(),
+                        inscription_id:
+                            "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0"
+                                .to_string(),
+                    })))
+                    .build(),
+            )
+            .build();
+
+        assert_eq!(
+            collect_block_addresses(&block),
+            vec![
+                "bc1qinscriber".to_string(),
+                "bc1qreceiver".to_string(),
+                "bc1qsender".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,803 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    time::Duration,
+};
+
+use chainhook_postgres::pg_pool_client;
+use chainhook_sdk::utils::Context;
+use chainhook_types::{
+    BitcoinBlockData, BlockIdentifier, Brc20Operation, OrdinalInscriptionTransferDestination,
+    OrdinalOperation,
+};
+use deadpool_postgres::Pool;
+use serde::Serialize;
+
+use crate::{
+    config::{AddressWatchConfig, WebhookClientTlsConfig},
+    db::ordinals_pg,
+    try_debug, try_info, try_warn,
+};
+
+const WEBHOOK_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const WEBHOOK_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Pause between two polls of the activity queue when activity is delivered per block.
+const ADDRESS_WATCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressActivityDirection {
+    Received,
+    Sent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressActivityKind {
+    InscriptionRevealed,
+    InscriptionTransferred,
+    Brc20Deploy,
+    Brc20Mint,
+    Brc20Transfer,
+    Brc20TransferSend,
+    ChildInscriptionRevealed,
+    DelegateInscriptionRevealed,
+}
+
+/// A single inscription or BRC-20 movement that touched a watched address.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressActivity {
+    pub address: String,
+    pub direction: AddressActivityDirection,
+    pub kind: AddressActivityKind,
+    pub tx_id: String,
+    pub tx_index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inscription_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ordinal_number: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tick: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    /// Watched parent or delegate inscription id that matched a reveal.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub related_inscription_id: Option<String>,
+}
+
+/// Compact webhook payload sent once per block whenever at least one watched address was involved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressActivityPayload {
+    pub block_identifier: BlockIdentifier,
+    pub timestamp: u32,
+    pub activity: Vec<AddressActivity>,
+}
+
+/// Activity entry of a digest, tagged with the block it happened in.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DigestedAddressActivity {
+    pub block_height: u64,
+    pub timestamp: u32,
+    #[serde(flatten)]
+    pub activity: AddressActivity,
+}
+
+/// Everything that happened to one address over a digest period.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AddressActivitySummary {
+    pub address: String,
+    pub received_count: usize,
+    pub sent_count: usize,
+    pub activity: Vec<DigestedAddressActivity>,
+}
+
+/// Webhook payload posted every `digest_interval_secs` instead of per block payloads, grouped by address.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AddressActivityDigest {
+    pub from_block_height: u64,
+    pub to_block_height: u64,
+    pub addresses: Vec<AddressActivitySummary>,
+}
+
+/// Extracts every inscription and BRC-20 operation in an indexed block that involves one of the given addresses.
+pub fn collect_address_activity(
+    block: &BitcoinBlockData,
+    addresses: &HashSet<String>,
+) -> Vec<AddressActivity> {
+    let mut activity = vec![];
+    if addresses.is_empty() {
+        return activity;
+    }
+    for (tx_index, tx) in block.transactions.iter().enumerate() {
+        let tx_id = tx.transaction_identifier.get_hash_bytes_str().to_string();
+        for op in tx.metadata.ordinal_operations.iter() {
+            match op {
+                OrdinalOperation::InscriptionRevealed(reveal) => {
+                    let Some(address) = &reveal.inscriber_address else {
+                        continue;
+                    };
+                    if addresses.contains(address) {
+                        activity.push(AddressActivity {
+                            address: address.clone(),
+                            direction: AddressActivityDirection::Received,
+                            kind: AddressActivityKind::InscriptionRevealed,
+                            tx_id: tx_id.clone(),
+                            tx_index,
+                            inscription_id: Some(reveal.inscription_id.clone()),
+                            ordinal_number: Some(reveal.ordinal_number),
+                            tick: None,
+                            amount: None,
+                            related_inscription_id: None,
+                        });
+                    }
+                }
+                OrdinalOperation::InscriptionTransferred(transfer) => {
+                    let receiver = match &transfer.destination {
+                        OrdinalInscriptionTransferDestination::Transferred(address) => {
+                            Some(address)
+                        }
+                        _ => None,
+                    };
+                    for (address, direction) in [
+                        (
+                            transfer.from_address.as_ref(),
+                            AddressActivityDirection::Sent,
+                        ),
+                        (receiver, AddressActivityDirection::Received),
+                    ] {
+                        let Some(address) = address.filter(|a| addresses.contains(*a)) else {
+                            continue;
+                        };
+                        activity.push(AddressActivity {
+                            address: address.clone(),
+                            direction,
+                            kind: AddressActivityKind::InscriptionTransferred,
+                            tx_id: tx_id.clone(),
+                            tx_index,
+                            inscription_id: None,
+                            ordinal_number: Some(transfer.ordinal_number),
+                            tick: None,
+                            amount: None,
+                            related_inscription_id: None,
+                        });
+                    }
+                }
+            }
+        }
+        let Some(brc20_operation) = &tx.metadata.brc20_operation else {
+            continue;
+        };
+        let mut push_brc20_activity =
+            |address: &String,
+             direction: AddressActivityDirection,
+             kind: AddressActivityKind,
+             inscription_id: &String,
+             tick: &String,
+             amount: Option<&String>| {
+                if addresses.contains(address) {
+                    activity.push(AddressActivity {
+                        address: address.clone(),
+                        direction,
+                        kind,
+                        tx_id: tx_id.clone(),
+                        tx_index,
+                        inscription_id: Some(inscription_id.clone()),
+                        ordinal_number: None,
+                        tick: Some(tick.clone()),
+                        amount: amount.cloned(),
+                        related_inscription_id: None,
+                    });
+                }
+            };
+        match brc20_operation {
+            Brc20Operation::Deploy(deploy) => push_brc20_activity(
+                &deploy.address,
+                AddressActivityDirection::Received,
+                AddressActivityKind::Brc20Deploy,
+                &deploy.inscription_id,
+                &deploy.tick,
+                None,
+            ),
+            Brc20Operation::Mint(mint) => push_brc20_activity(
+                &mint.address,
+                AddressActivityDirection::Received,
+                AddressActivityKind::Brc20Mint,
+                &mint.inscription_id,
+                &mint.tick,
+                Some(&mint.amt),
+            ),
+            Brc20Operation::Transfer(transfer) => push_brc20_activity(
+                &transfer.address,
+                AddressActivityDirection::Received,
+                AddressActivityKind::Brc20Transfer,
+                &transfer.inscription_id,
+                &transfer.tick,
+                Some(&transfer.amt),
+            ),
+            Brc20Operation::TransferSend(transfer) => {
+                push_brc20_activity(
+                    &transfer.sender_address,
+                    AddressActivityDirection::Sent,
+                    AddressActivityKind::Brc20TransferSend,
+                    &transfer.inscription_id,
+                    &transfer.tick,
+                    Some(&transfer.amt),
+                );
+                push_brc20_activity(
+                    &transfer.receiver_address,
+                    AddressActivityDirection::Received,
+                    AddressActivityKind::Brc20TransferSend,
+                    &transfer.inscription_id,
+                    &transfer.tick,
+                    Some(&transfer.amt),
+                );
+            }
+        }
+    }
+    activity
+}
+
+/// Extracts every inscription revealed in an indexed block that is a child of one of the given parents or delegates to
+/// one of the given inscriptions. The reported address is the inscriber's, or empty when it is unknown.
+pub fn collect_collection_activity(
+    block: &BitcoinBlockData,
+    parents: &HashSet<String>,
+    delegates: &HashSet<String>,
+) -> Vec<AddressActivity> {
+    let mut activity = vec![];
+    if parents.is_empty() && delegates.is_empty() {
+        return activity;
+    }
+    for (tx_index, tx) in block.transactions.iter().enumerate() {
+        let tx_id = tx.transaction_identifier.get_hash_bytes_str().to_string();
+        for op in tx.metadata.ordinal_operations.iter() {
+            let OrdinalOperation::InscriptionRevealed(reveal) = op else {
+                continue;
+            };
+            let matches = reveal
+                .parents
+                .iter()
+                .filter(|parent| parents.contains(*parent))
+                .map(|parent| (AddressActivityKind::ChildInscriptionRevealed, parent))
+                .chain(
+                    reveal
+                        .delegate
+                        .iter()
+                        .filter(|delegate| delegates.contains(*delegate))
+                        .map(|delegate| {
+                            (AddressActivityKind::DelegateInscriptionRevealed, delegate)
+                        }),
+                );
+            for (kind, related_inscription_id) in matches {
+                activity.push(AddressActivity {
+                    address: reveal.inscriber_address.clone().unwrap_or_default(),
+                    direction: AddressActivityDirection::Received,
+                    kind,
+                    tx_id: tx_id.clone(),
+                    tx_index,
+                    inscription_id: Some(reveal.inscription_id.clone()),
+                    ordinal_number: Some(reveal.ordinal_number),
+                    tick: None,
+                    amount: None,
+                    related_inscription_id: Some(related_inscription_id.clone()),
+                });
+            }
+        }
+    }
+    activity
+}
+
+/// Builds the HTTP client used for deliveries. Delivery workers build it once, so certificates rotated on disk are
+/// picked up on restart. Requests time out so an unresponsive endpoint cannot stall a delivery queue.
+pub(crate) fn build_webhook_client(
+    tls: &Option<WebhookClientTlsConfig>,
+) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(WEBHOOK_CONNECT_TIMEOUT)
+        .timeout(WEBHOOK_REQUEST_TIMEOUT);
+    if let Some(tls) = tls {
+        let mut pem = std::fs::read(&tls.client_certificate_path).map_err(|e| {
+            format!(
+                "unable to read client certificate {}: {e}",
+                tls.client_certificate_path
+            )
+        })?;
+        pem.extend(
+            std::fs::read(&tls.client_key_path)
+                .map_err(|e| format!("unable to read client key {}: {e}", tls.client_key_path))?,
+        );
+        let identity = reqwest::Identity::from_pem(&pem)
+            .map_err(|e| format!("invalid client certificate or key: {e}"))?;
+        builder = builder.identity(identity);
+        if let Some(ca_certificate_path) = &tls.ca_certificate_path {
+            let ca = std::fs::read(ca_certificate_path)
+                .map_err(|e| format!("unable to read CA certificate {ca_certificate_path}: {e}"))?;
+            let ca = reqwest::Certificate::from_pem(&ca)
+                .map_err(|e| format!("invalid CA certificate {ca_certificate_path}: {e}"))?;
+            builder = builder.add_root_certificate(ca);
+        }
+    }
+    builder
+        .build()
+        .map_err(|e| format!("unable to build webhook client: {e}"))
+}
+
+/// Summarizes the per block payloads queued over a digest period, by address. Returns `None` when there is nothing to
+/// report.
+pub fn build_address_activity_digest(
+    payloads: &[AddressActivityPayload],
+) -> Option<AddressActivityDigest> {
+    let from_block_height = payloads.iter().map(|p| p.block_identifier.index).min()?;
+    let to_block_height = payloads.iter().map(|p| p.block_identifier.index).max()?;
+    let mut addresses: BTreeMap<String, AddressActivitySummary> = BTreeMap::new();
+    for payload in payloads.iter() {
+        for activity in payload.activity.iter() {
+            let summary = addresses
+                .entry(activity.address.clone())
+                .or_insert_with(|| AddressActivitySummary {
+                    address: activity.address.clone(),
+                    received_count: 0,
+                    sent_count: 0,
+                    activity: vec![],
+                });
+            match activity.direction {
+                AddressActivityDirection::Received => summary.received_count += 1,
+                AddressActivityDirection::Sent => summary.sent_count += 1,
+            }
+            summary.activity.push(DigestedAddressActivity {
+                block_height: payload.block_identifier.index,
+                timestamp: payload.timestamp,
+                activity: activity.clone(),
+            });
+        }
+    }
+    for summary in addresses.values_mut() {
+        summary
+            .activity
+            .sort_by_key(|entry| (entry.block_height, entry.activity.tx_index));
+    }
+    Some(AddressActivityDigest {
+        from_block_height,
+        to_block_height,
+        addresses: addresses.into_values().collect(),
+    })
+}
+
+async fn post_address_watch_payload<T: Serialize>(
+    payload: &T,
+    address_watch: &AddressWatchConfig,
+    http_client: &reqwest::Client,
+) -> Result<(), String> {
+    let mut request = http_client.post(&address_watch.url).json(payload);
+    if let Some(authorization) = &address_watch.authorization {
+        request = request.header(
+            reqwest::header::AUTHORIZATION,
+            authorization.resolve().await?,
+        );
+    }
+    let res = request
+        .send()
+        .await
+        .map_err(|e| format!("unable to deliver activity: {e}"))?;
+    if !res.status().is_success() {
+        return Err(format!("webhook returned {}", res.status()));
+    }
+    Ok(())
+}
+
+/// Queues watched address activity of an indexed block, to be posted by [start_address_watch_deliveries] on its own or
+/// in the next digest. Blocks with no matching activity are skipped. Failures are logged and never interrupt indexing.
+pub async fn notify_address_activity(
+    block: &BitcoinBlockData,
+    address_watch: &AddressWatchConfig,
+    ordinals_pool: &Pool,
+    ctx: &Context,
+) {
+    let block_height = block.block_identifier.index;
+    let mut activity = collect_address_activity(block, &address_watch.addresses);
+    activity.extend(collect_collection_activity(
+        block,
+        &address_watch.parents,
+        &address_watch.delegates,
+    ));
+    if activity.is_empty() {
+        return;
+    }
+    let payload = AddressActivityPayload {
+        block_identifier: block.block_identifier.clone(),
+        timestamp: block.timestamp,
+        activity,
+    };
+    if let Err(e) = queue_address_activity(&payload, ordinals_pool).await {
+        try_warn!(
+            ctx,
+            "Address watch: unable to queue activity of block #{block_height}: {e}"
+        );
+    }
+}
+
+async fn queue_address_activity(
+    payload: &AddressActivityPayload,
+    ordinals_pool: &Pool,
+) -> Result<(), String> {
+    let serialized =
+        serde_json::to_string(payload).map_err(|e| format!("unable to serialize activity: {e}"))?;
+    let client = pg_pool_client(ordinals_pool).await?;
+    ordinals_pg::insert_address_watch_digest_entry(
+        payload.block_identifier.index,
+        payload.block_identifier.get_hash_bytes_str(),
+        &serialized,
+        &client,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Posts the queued activity whose block is still part of the indexed chain, one payload per block and oldest first.
+/// Entries from reorged out blocks are removed, entries from blocks that are not indexed yet wait for the next poll. A
+/// failed delivery stops at its entry so it is retried first.
+async fn deliver_queued_address_activity(
+    address_watch: &AddressWatchConfig,
+    http_client: &reqwest::Client,
+    ordinals_pool: &Pool,
+    ctx: &Context,
+) -> Result<(), String> {
+    let client = pg_pool_client(ordinals_pool).await?;
+    let chain_tip = ordinals_pg::get_chain_tip_block_height(&client)
+        .await?
+        .unwrap_or(0);
+    let mut result = Ok(());
+    let mut processed_ids = vec![];
+    for (id, block_height, canonical, payload) in
+        ordinals_pg::get_address_watch_digest_entries(&client).await?
+    {
+        if !canonical {
+            if block_height <= chain_tip {
+                processed_ids.push(id);
+            }
+            continue;
+        }
+        let payload: AddressActivityPayload = serde_json::from_str(&payload)
+            .map_err(|e| format!("unable to parse queued activity {id}: {e}"))?;
+        if let Err(e) = post_address_watch_payload(&payload, address_watch, http_client).await {
+            result = Err(format!(
+                "unable to deliver activity for block #{block_height}: {e}"
+            ));
+            break;
+        }
+        try_debug!(
+            ctx,
+            "Address watch: delivered {} activity entries for block #{block_height}",
+            payload.activity.len()
+        );
+        processed_ids.push(id);
+    }
+    if !processed_ids.is_empty() {
+        ordinals_pg::delete_address_watch_digest_entries(&processed_ids, &client).await?;
+    }
+    result
+}
+
+/// Builds and posts a digest of the queued activity whose block is still part of the indexed chain. Delivered entries
+/// and entries from reorged out blocks are removed, entries from blocks that are not indexed yet wait for the next one.
+async fn deliver_address_activity_digest(
+    address_watch: &AddressWatchConfig,
+    http_client: &reqwest::Client,
+    ordinals_pool: &Pool,
+    ctx: &Context,
+) -> Result<(), String> {
+    let client = pg_pool_client(ordinals_pool).await?;
+    let chain_tip = ordinals_pg::get_chain_tip_block_height(&client)
+        .await?
+        .unwrap_or(0);
+    let mut processed_ids = vec![];
+    let mut payloads = vec![];
+    for (id, block_height, canonical, payload) in
+        ordinals_pg::get_address_watch_digest_entries(&client).await?
+    {
+        if canonical {
+            let payload: AddressActivityPayload = serde_json::from_str(&payload)
+                .map_err(|e| format!("unable to parse queued activity {id}: {e}"))?;
+            payloads.push(payload);
+            processed_ids.push(id);
+        } else if block_height <= chain_tip {
+            processed_ids.push(id);
+        }
+    }
+    if let Some(digest) = build_address_activity_digest(&payloads) {
+        post_address_watch_payload(&digest, address_watch, http_client).await?;
+        try_debug!(
+            ctx,
+            "Address watch: delivered digest for {} addresses from block #{} to #{}",
+            digest.addresses.len(),
+            digest.from_block_height,
+            digest.to_block_height
+        );
+    }
+    if !processed_ids.is_empty() {
+        ordinals_pg::delete_address_watch_digest_entries(&processed_ids, &client).await?;
+    }
+    Ok(())
+}
+
+/// Continuously posts queued watched address activity, block by block, or as a digest every `digest_interval_secs`
+/// when digests are enabled. Activity that fails to be delivered stays queued and is retried.
+pub async fn start_address_watch_deliveries(
+    address_watch: AddressWatchConfig,
+    ordinals_pool: Pool,
+    ctx: Context,
+) {
+    let http_client = match build_webhook_client(&address_watch.tls) {
+        Ok(http_client) => http_client,
+        Err(e) => {
+            try_warn!(ctx, "Address watch: deliveries disabled: {e}");
+            return;
+        }
+    };
+    match address_watch.digest_interval_secs {
+        Some(interval_secs) => {
+            try_info!(
+                ctx,
+                "Address watch: posting activity digests to {} every {interval_secs}s",
+                address_watch.url
+            );
+            loop {
+                tokio::time::sleep(Duration::from_secs(interval_secs.max(1))).await;
+                if let Err(e) = deliver_address_activity_digest(
+                    &address_watch,
+                    &http_client,
+                    &ordinals_pool,
+                    &ctx,
+                )
+                .await
+                {
+                    try_warn!(ctx, "Address watch: unable to deliver digest: {e}");
+                }
+            }
+        }
+        None => {
+            try_info!(
+                ctx,
+                "Address watch: posting activity to {}",
+                address_watch.url
+            );
+            loop {
+                if let Err(e) = deliver_queued_address_activity(
+                    &address_watch,
+                    &http_client,
+                    &ordinals_pool,
+                    &ctx,
+                )
+                .await
+                {
+                    try_warn!(ctx, "Address watch: {e}");
+                }
+                tokio::time::sleep(ADDRESS_WATCH_POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use chainhook_types::{
+        BlockIdentifier, Brc20Operation, Brc20TransferData, OrdinalInscriptionTransferData,
+        OrdinalInscriptionTransferDestination, OrdinalOperation,
+    };
+
+    use crate::core::{
+        meta_protocols::brc20::test_utils::Brc20RevealBuilder,
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::{
+        build_address_activity_digest, collect_address_activity, collect_collection_activity,
+        AddressActivity, AddressActivityDirection, AddressActivityKind, AddressActivityPayload,
+    };
+
+    #[test]
+    fn collects_activity_for_watched_addresses_only() {
+        let watched = "bc1pd99n363yjz8gd2zhy7gstsmk4qkdz4t029j44wewhmee3dta429sm5xqrd".to_string();
+        let block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                        OrdinalInscriptionTransferData {
+                            ordinal_number: 500,
+                            destination: OrdinalInscriptionTransferDestination::Transferred(
+                                watched.clone(),
+                            ),
+                            from_address: None,
+                            satpoint_pre_transfer: "".to_string(),
+                            satpoint_post_transfer: "".to_string(),
+                            post_transfer_output_value: Some(546),
+                            tx_index: 0,
+                        },
+                    ))
+                    .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                        OrdinalInscriptionTransferData {
+                            ordinal_number: 600,
+                            destination: OrdinalInscriptionTransferDestination::Transferred(
+                                "bc1qunwatched".to_string(),
+                            ),
+                            from_address: None,
+                            satpoint_pre_transfer: "".to_string(),
+                            satpoint_post_transfer: "".to_string(),
+                            post_transfer_output_value: Some(546),
+                            tx_index: 0,
+                        },
+                    ))
+                    .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                        OrdinalInscriptionTransferData {
+                            ordinal_number: 700,
+                            destination: OrdinalInscriptionTransferDestination::Transferred(
+                                "bc1qunwatched".to_string(),
+                            ),
+                            from_address: Some(watched.clone()),
+                            satpoint_pre_transfer: "".to_string(),
+                            satpoint_post_transfer: "".to_string(),
+                            post_transfer_output_value: Some(546),
+                            tx_index: 0,
+                        },
+                    ))
+                    .brc20_operation(Some(Brc20Operation::TransferSend(Brc20TransferData {
+                        tick: "pepe".to_string(),
+                        amt: "10.000000000000000000".to_string(),
+                        sender_address: watched.clone(),
+                        receiver_address: "bc1qunwatched".to_string(),
+                        inscription_id:
+                            "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0"
+                                .to_string(),
+                    })))
+                    .build(),
+            )
+            .build();
+
+        let activity = collect_address_activity(&block, &HashSet::from([watched.clone()]));
+
+        assert_eq!(activity.len(), 3);
+        assert_eq!(
+            activity[0].kind,
+            AddressActivityKind::InscriptionTransferred
+        );
+        assert_eq!(activity[0].ordinal_number, Some(500));
+        assert_eq!(activity[0].direction, AddressActivityDirection::Received);
+        assert_eq!(
+            activity[1].kind,
+            AddressActivityKind::InscriptionTransferred
+        );
+        assert_eq!(activity[1].ordinal_number, Some(700));
+        assert_eq!(activity[1].direction, AddressActivityDirection::Sent);
+        assert_eq!(activity[2].kind, AddressActivityKind::Brc20TransferSend);
+        assert_eq!(activity[2].direction, AddressActivityDirection::Sent);
+        assert_eq!(
+            activity[2].amount,
+            Some("10.000000000000000000".to_string())
+        );
+    }
+
+    #[test]
+    fn collects_children_and_delegates_of_watched_inscriptions() {
+        let parent =
+            "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0".to_string();
+        let child = Brc20RevealBuilder::new()
+            .inscription_id("9bb2314d666ae0b1db8161cb373fcc1381681f71445c4e0335aa80ea9c37fcddi0")
+            .parents(vec![parent.clone()])
+            .build();
+        let mut copy = Brc20RevealBuilder::new()
+            .inscription_id("9bb2314d666ae0b1db8161cb373fcc1381681f71445c4e0335aa80ea9c37fcddi1")
+            .build();
+        copy.delegate = Some(parent.clone());
+        let unrelated = Brc20RevealBuilder::new()
+            .inscription_id("9bb2314d666ae0b1db8161cb373fcc1381681f71445c4e0335aa80ea9c37fcddi2")
+            .parents(vec!["unwatchedi0".to_string()])
+            .build();
+        let block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(child))
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(copy))
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(unrelated))
+                    .build(),
+            )
+            .build();
+
+        let activity = collect_collection_activity(
+            &block,
+            &HashSet::from([parent.clone()]),
+            &HashSet::from([parent.clone()]),
+        );
+
+        assert_eq!(activity.len(), 2);
+        assert_eq!(
+            activity[0].kind,
+            AddressActivityKind::ChildInscriptionRevealed
+        );
+        assert_eq!(
+            activity[0].inscription_id,
+            Some("9bb2314d666ae0b1db8161cb373fcc1381681f71445c4e0335aa80ea9c37fcddi0".to_string())
+        );
+        assert_eq!(activity[0].related_inscription_id, Some(parent.clone()));
+        assert_eq!(
+            activity[1].kind,
+            AddressActivityKind::DelegateInscriptionRevealed
+        );
+        assert_eq!(activity[1].address, "324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp");
+    }
+
+    fn transfer_activity(address: &str, direction: AddressActivityDirection) -> AddressActivity {
+        AddressActivity {
+            address: address.to_string(),
+            direction,
+            kind: AddressActivityKind::InscriptionTransferred,
+            tx_id: "0x0c0a8f6a3d9b0d7ddb4cb08bf6e8ba1c2c6e1ee4ac1d3c4c8d8b5f3c3b7c2e6e".to_string(),
+            tx_index: 1,
+            inscription_id: None,
+            ordinal_number: Some(500),
+            tick: None,
+            amount: None,
+            related_inscription_id: None,
+        }
+    }
+
+    fn activity_payload(
+        block_height: u64,
+        activity: Vec<AddressActivity>,
+    ) -> AddressActivityPayload {
+        AddressActivityPayload {
+            block_identifier: BlockIdentifier {
+                index: block_height,
+                hash: format!("0x{block_height:064x}"),
+            },
+            timestamp: block_height as u32,
+            activity,
+        }
+    }
+
+    #[test]
+    fn builds_digest_grouped_by_address() {
+        assert!(build_address_activity_digest(&[]).is_none());
+
+        let payloads = vec![
+            activity_payload(
+                840_002,
+                vec![transfer_activity(
+                    "bc1qalice",
+                    AddressActivityDirection::Sent,
+                )],
+            ),
+            activity_payload(
+                840_000,
+                vec![
+                    transfer_activity("bc1qbob", AddressActivityDirection::Received),
+                    transfer_activity("bc1qalice", AddressActivityDirection::Received),
+                ],
+            ),
+        ];
+        let queued: Vec<AddressActivityPayload> = payloads
+            .iter()
+            .map(|payload| serde_json::from_str(&serde_json::to_string(payload).unwrap()).unwrap())
+            .collect();
+        assert_eq!(queued, payloads);
+
+        let digest = build_address_activity_digest(&queued).unwrap();
+        assert_eq!(digest.from_block_height, 840_000);
+        assert_eq!(digest.to_block_height, 840_002);
+        assert_eq!(digest.addresses.len(), 2);
+        assert_eq!(digest.addresses[0].address, "bc1qalice");
+        assert_eq!(digest.addresses[0].received_count, 1);
+        assert_eq!(digest.addresses[0].sent_count, 1);
+        assert_eq!(
+            digest.addresses[0]
+                .activity
+                .iter()
+                .map(|entry| entry.block_height)
+                .collect::<Vec<_>>(),
+            vec![840_000, 840_002]
+        );
+        assert_eq!(digest.addresses[1].address, "bc1qbob");
+        assert_eq!(digest.addresses[1].received_count, 1);
+    }
+}
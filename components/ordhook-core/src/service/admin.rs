@@ -0,0 +1,202 @@
+use std::{sync::Arc, time::Duration};
+
+use chainhook_sdk::utils::Context;
+use hyper::{body::HttpBody, Body, Method, Request, Response};
+
+use crate::{config::AdminConfig, try_info, try_warn, utils::http::serve_http};
+
+use super::{
+    api::{bad_request, json_response, not_found},
+    write_throttle::WriteThrottle,
+};
+
+/// Largest request body accepted by the admin API, in bytes.
+const MAX_ADMIN_BODY_SIZE: usize = 64 * 1024;
+
+/// Body of a `PUT /throttle` request. Both fields are optional: the rate defaults to the configured one, and throttling
+/// lasts until `DELETE /throttle` when no duration is given.
+#[derive(Debug, Default, Deserialize)]
+struct ThrottleRequest {
+    max_wal_bytes_per_sec: Option<u64>,
+    duration_secs: Option<u64>,
+}
+
+async fn read_throttle_request(req: Request<Body>) -> Result<ThrottleRequest, String> {
+    let mut body = req.into_body();
+    let mut bytes = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| format!("unable to read request body: {e}"))?;
+        if bytes.len() + chunk.len() > MAX_ADMIN_BODY_SIZE {
+            return Err("request body is too large".to_string());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    if bytes.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(ThrottleRequest::default());
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("invalid request body: {e}"))
+}
+
+async fn serve_admin_req(
+    req: Request<Body>,
+    admin: AdminConfig,
+    write_throttle: Arc<WriteThrottle>,
+    ctx: Context,
+) -> Result<Response<Body>, hyper::Error> {
+    if req.uri().path().trim_matches('/') != "throttle" {
+        return Ok(not_found());
+    }
+    match req.method() {
+        &Method::GET => {}
+        &Method::PUT => {
+            let request = match read_throttle_request(req).await {
+                Ok(request) => request,
+                Err(e) => return Ok(bad_request(&e)),
+            };
+            let max_wal_bytes_per_sec = request
+                .max_wal_bytes_per_sec
+                .unwrap_or(admin.default_max_wal_bytes_per_sec);
+            if max_wal_bytes_per_sec == 0 {
+                return Ok(bad_request("max_wal_bytes_per_sec must be positive"));
+            }
+            write_throttle.enable(
+                max_wal_bytes_per_sec,
+                request.duration_secs.map(Duration::from_secs),
+            );
+            try_info!(
+                ctx,
+                "Admin: throttling writes to {max_wal_bytes_per_sec} WAL bytes per second"
+            );
+        }
+        &Method::DELETE => {
+            write_throttle.disable();
+            try_info!(ctx, "Admin: write throttle disabled, resuming full speed");
+        }
+        _ => return Ok(not_found()),
+    }
+    Ok(json_response(&write_throttle.status()))
+}
+
+/// Serves the admin API until the server fails:
+///   GET /throttle returns the state of the write throttle.
+///   PUT /throttle starts throttling, see `ThrottleRequest`.
+///   DELETE /throttle stops throttling.
+pub async fn start_serving_admin_api(
+    admin: AdminConfig,
+    write_throttle: Arc<WriteThrottle>,
+    ctx: Context,
+) {
+    try_info!(ctx, "Admin API: listening on {}", admin.listen_address);
+    let ctx_clone = ctx.clone();
+    let listen_address = admin.listen_address.clone();
+    let serve_future = serve_http(&listen_address, move |r| {
+        serve_admin_req(r, admin.clone(), write_throttle.clone(), ctx_clone.clone())
+    });
+    if let Err(err) = serve_future.await {
+        try_warn!(ctx, "Admin API: server error: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use chainhook_sdk::utils::Context;
+    use hyper::{Body, Request};
+
+    use crate::{
+        config::{AdminConfig, ListenAddress},
+        service::write_throttle::WriteThrottle,
+    };
+
+    use super::{read_throttle_request, serve_admin_req, MAX_ADMIN_BODY_SIZE};
+
+    fn admin_config() -> AdminConfig {
+        AdminConfig {
+            listen_address: ListenAddress::Tcp(([127, 0, 0, 1], 0).into()),
+            default_max_wal_bytes_per_sec: 1024,
+        }
+    }
+
+    fn request(method: &str, uri: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn serve(
+        req: Request<Body>,
+        write_throttle: &Arc<WriteThrottle>,
+    ) -> (u16, serde_json::Value) {
+        let response = serve_admin_req(
+            req,
+            admin_config(),
+            write_throttle.clone(),
+            Context::empty(),
+        )
+        .await
+        .unwrap();
+        let status = response.status().as_u16();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (
+            status,
+            serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn reads_throttle_requests() {
+        let request_body = |body: &str| request("PUT", "/throttle", body);
+        let empty = read_throttle_request(request_body(" \n")).await.unwrap();
+        assert_eq!(empty.max_wal_bytes_per_sec, None);
+        assert_eq!(empty.duration_secs, None);
+        let full = read_throttle_request(request_body(
+            r#"{"max_wal_bytes_per_sec": 2048, "duration_secs": 60}"#,
+        ))
+        .await
+        .unwrap();
+        assert_eq!(full.max_wal_bytes_per_sec, Some(2048));
+        assert_eq!(full.duration_secs, Some(60));
+        assert!(read_throttle_request(request_body("{")).await.is_err());
+        assert!(
+            read_throttle_request(request_body(&" ".repeat(MAX_ADMIN_BODY_SIZE + 1)))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_throttle_routes() {
+        let write_throttle = Arc::new(WriteThrottle::default());
+
+        let (status, body) = serve(request("GET", "/throttle", ""), &write_throttle).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["enabled"], false);
+
+        let (status, body) = serve(request("PUT", "/throttle", ""), &write_throttle).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["enabled"], true);
+        assert_eq!(body["max_wal_bytes_per_sec"], 1024);
+
+        let (status, _) = serve(
+            request("PUT", "/throttle", r#"{"max_wal_bytes_per_sec": 0}"#),
+            &write_throttle,
+        )
+        .await;
+        assert_eq!(status, 400);
+        let (status, _) = serve(request("PUT", "/throttle", "not json"), &write_throttle).await;
+        assert_eq!(status, 400);
+        assert!(write_throttle.status().enabled);
+
+        let (status, body) = serve(request("DELETE", "/throttle", ""), &write_throttle).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["enabled"], false);
+
+        let (status, _) = serve(request("POST", "/throttle", ""), &write_throttle).await;
+        assert_eq!(status, 404);
+        let (status, _) = serve(request("GET", "/status", ""), &write_throttle).await;
+        assert_eq!(status, 404);
+    }
+}
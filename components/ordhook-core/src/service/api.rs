@@ -0,0 +1,1269 @@
+use std::{collections::HashMap, sync::Arc};
+
+use chainhook_postgres::{pg_begin_read_snapshot, pg_pool_client};
+use chainhook_sdk::{
+    indexer::bitcoin::{build_http_client, retrieve_block_hash, retrieve_raw_transaction},
+    utils::Context,
+};
+use deadpool_postgres::{GenericClient, Pool};
+use hyper::{
+    body::HttpBody,
+    header::{HeaderValue, CONTENT_TYPE},
+    Body, Method, Request, Response,
+};
+
+use crate::{
+    config::{ApiConfig, Config},
+    core::meta_protocols::brc20::{
+        brc20_pg,
+        models::{DbBalance, DbOperation, DbToken},
+        u128_amount_to_decimals_str,
+    },
+    db::{
+        models::{DbCurrentLocation, DbInscription, DbProvisionalInscription},
+        ordinals_pg,
+        raw_transactions::RawTransactionsReader,
+    },
+    try_debug, try_info, try_warn,
+    utils::http::serve_http,
+};
+
+#[cfg(feature = "websocket")]
+use super::activity_stream::{serve_activity_stream, ActivityStreamFilter};
+use super::{
+    activity_stream::{serve_event_stream, ActivityStreamSender},
+    api_cache::ApiResponseCache,
+    mempool_brc20::MempoolBrc20Operations,
+    mempool_reveals::{start_watching_mempool_reveals, MempoolReveals},
+    psbt_check::{evaluate_psbt_inputs, parse_psbt_check_request},
+    PgConnectionPools,
+};
+
+/// Largest `POST /psbt/check` body accepted, in bytes.
+const MAX_PSBT_CHECK_BODY_SIZE: usize = 4 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiInscriptionLocation {
+    pub block_height: u64,
+    pub tx_id: String,
+    pub output: String,
+    pub offset: Option<u64>,
+    pub address: Option<String>,
+}
+
+/// Inscription as returned by the API. Content bytes are left out, only their type and length are reported.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiInscription {
+    pub id: String,
+    pub number: i64,
+    pub classic_number: i64,
+    pub ordinal_number: u64,
+    pub block_height: u64,
+    pub block_hash: String,
+    pub tx_id: String,
+    pub tx_index: u32,
+    pub genesis_address: Option<String>,
+    pub mime_type: String,
+    pub content_type: String,
+    pub content_length: u32,
+    pub fee: u64,
+    pub curse_type: Option<String>,
+    pub recursive: bool,
+    pub metadata: Option<String>,
+    pub metaprotocol: Option<String>,
+    pub delegate: Option<String>,
+    pub timestamp: u32,
+    pub charms: u32,
+    pub location: Option<ApiInscriptionLocation>,
+}
+
+impl ApiInscription {
+    fn from_db(inscription: DbInscription, location: Option<&DbCurrentLocation>) -> Self {
+        ApiInscription {
+            id: inscription.inscription_id,
+            number: inscription.number,
+            classic_number: inscription.classic_number,
+            ordinal_number: inscription.ordinal_number.0,
+            block_height: inscription.block_height.0,
+            block_hash: inscription.block_hash,
+            tx_id: inscription.tx_id,
+            tx_index: inscription.tx_index.0,
+            genesis_address: inscription.address,
+            mime_type: inscription.mime_type,
+            content_type: inscription.content_type,
+            content_length: inscription.content_length.0,
+            fee: inscription.fee.0,
+            curse_type: inscription.curse_type,
+            recursive: inscription.recursive,
+            metadata: inscription.metadata,
+            metaprotocol: inscription.metaprotocol,
+            delegate: inscription.delegate,
+            timestamp: inscription.timestamp.0,
+            charms: inscription.charms.0,
+            location: location.map(|location| ApiInscriptionLocation {
+                block_height: location.block_height.0,
+                tx_id: location.tx_id.clone(),
+                output: location.output.clone(),
+                offset: location.offset.map(|offset| offset.0),
+                address: location.address.clone(),
+            }),
+        }
+    }
+}
+
+/// Inscription revealed in a tip block that is not indexed yet, returned with `?include_provisional=true`. Numbers, sats
+/// and fees are only known once the block is indexed, at which point the inscription is served as an [ApiInscription].
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiProvisionalInscription {
+    pub id: String,
+    pub block_height: u64,
+    pub block_hash: String,
+    pub tx_id: String,
+    pub tx_index: u32,
+    pub mime_type: String,
+    pub content_type: String,
+    pub content_length: u32,
+    pub curse_type: Option<String>,
+    pub metaprotocol: Option<String>,
+    pub delegate: Option<String>,
+    pub timestamp: u32,
+    /// Always true, tells provisional entries apart in responses that mix them with indexed inscriptions.
+    pub provisional: bool,
+}
+
+impl ApiProvisionalInscription {
+    fn from_db(inscription: DbProvisionalInscription) -> Self {
+        ApiProvisionalInscription {
+            id: inscription.inscription_id,
+            block_height: inscription.block_height.0,
+            block_hash: inscription.block_hash,
+            tx_id: inscription.tx_id,
+            tx_index: inscription.tx_index.0,
+            mime_type: inscription.mime_type,
+            content_type: inscription.content_type,
+            content_length: inscription.content_length.0,
+            curse_type: inscription.curse_type,
+            metaprotocol: inscription.metaprotocol,
+            delegate: inscription.delegate,
+            timestamp: inscription.timestamp.0,
+            provisional: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ApiInscriptionEntry {
+    Indexed(ApiInscription),
+    Provisional(ApiProvisionalInscription),
+}
+
+/// BRC-20 token as returned by the API. Amounts are formatted with the token's decimals.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiBrc20Token {
+    pub ticker: String,
+    pub inscription_id: String,
+    pub inscription_number: i64,
+    pub block_height: u64,
+    pub tx_id: String,
+    pub address: String,
+    pub max_supply: String,
+    pub mint_limit: String,
+    pub decimals: u8,
+    pub self_mint: bool,
+    pub minted_supply: String,
+    pub tx_count: i32,
+    pub holders: i32,
+    pub deploy_timestamp: u32,
+}
+
+impl ApiBrc20Token {
+    fn from_db(token: DbToken) -> Self {
+        let decimals = token.decimals.0;
+        ApiBrc20Token {
+            ticker: token.display_ticker,
+            inscription_id: token.inscription_id,
+            inscription_number: token.inscription_number,
+            block_height: token.block_height.0,
+            tx_id: token.tx_id,
+            address: token.address,
+            max_supply: u128_amount_to_decimals_str(token.max.0, decimals),
+            mint_limit: u128_amount_to_decimals_str(token.limit.0, decimals),
+            decimals,
+            self_mint: token.self_mint,
+            minted_supply: u128_amount_to_decimals_str(token.minted_supply.0, decimals),
+            tx_count: token.tx_count,
+            holders: token.holders,
+            deploy_timestamp: token.timestamp.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiBrc20Balance {
+    pub ticker: String,
+    pub available_balance: String,
+    pub transferrable_balance: String,
+    pub overall_balance: String,
+}
+
+impl ApiBrc20Balance {
+    fn from_db(balance: DbBalance, token: &DbToken) -> Self {
+        let decimals = token.decimals.0;
+        ApiBrc20Balance {
+            ticker: token.display_ticker.clone(),
+            available_balance: u128_amount_to_decimals_str(balance.avail_balance.0, decimals),
+            transferrable_balance: u128_amount_to_decimals_str(balance.trans_balance.0, decimals),
+            overall_balance: u128_amount_to_decimals_str(balance.total_balance.0, decimals),
+        }
+    }
+}
+
+/// BRC-20 operation that changed the balances of an address.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiBrc20Activity {
+    pub ticker: String,
+    pub operation: String,
+    pub inscription_id: String,
+    pub inscription_number: i64,
+    pub block_height: u64,
+    pub block_hash: String,
+    pub tx_id: String,
+    pub tx_index: u64,
+    pub address: String,
+    pub to_address: Option<String>,
+    pub amount: String,
+    pub timestamp: u32,
+}
+
+impl ApiBrc20Activity {
+    fn from_db(operation: DbOperation, token: &DbToken) -> Self {
+        ApiBrc20Activity {
+            ticker: token.display_ticker.clone(),
+            operation: operation.operation,
+            inscription_id: operation.inscription_id,
+            inscription_number: operation.inscription_number,
+            block_height: operation.block_height.0,
+            block_hash: operation.block_hash,
+            tx_id: operation.tx_id,
+            tx_index: operation.tx_index.0,
+            address: operation.address,
+            to_address: operation.to_address,
+            amount: u128_amount_to_decimals_str(operation.amount.0, token.decimals.0),
+            timestamp: operation.timestamp.0,
+        }
+    }
+}
+
+/// Serves the read-only HTTP API until the server fails.
+pub async fn start_serving_api(
+    api: ApiConfig,
+    config: Config,
+    pg_pools: PgConnectionPools,
+    activity_stream: ActivityStreamSender,
+    mempool_brc20: Option<Arc<MempoolBrc20Operations>>,
+    ctx: Context,
+) {
+    let ctx_clone = ctx.clone();
+    try_info!(ctx, "API: listening on {}", api.listen_address);
+    let response_cache = Arc::new(ApiResponseCache::new(api.response_cache_size));
+    let raw_transactions = config
+        .storage
+        .raw_transactions_index
+        .then(|| Arc::new(RawTransactionsReader::default()));
+    let mempool_reveals = if api.mempool_reveals {
+        let mempool_reveals = Arc::new(MempoolReveals::default());
+        tokio::spawn(start_watching_mempool_reveals(
+            config.clone(),
+            pg_pools.ordinals.clone(),
+            mempool_reveals.clone(),
+            ctx.clone(),
+        ));
+        Some(mempool_reveals)
+    } else {
+        None
+    };
+    let serve_future = serve_http(&api.listen_address, move |r| {
+        serve_req(
+            r,
+            config.clone(),
+            pg_pools.clone(),
+            response_cache.clone(),
+            raw_transactions.clone(),
+            activity_stream.clone(),
+            mempool_reveals.clone(),
+            mempool_brc20.clone(),
+            ctx_clone.clone(),
+        )
+    });
+    if let Err(err) = serve_future.await {
+        try_warn!(ctx, "API: server error: {}", err);
+    }
+}
+
+pub(super) fn not_found() -> Response<Body> {
+    Response::builder().status(404).body(Body::empty()).unwrap()
+}
+
+pub(super) fn bad_request(message: &str) -> Response<Body> {
+    Response::builder()
+        .status(400)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(Body::from(message.to_string()))
+        .unwrap()
+}
+
+fn conflict(message: &str) -> Response<Body> {
+    Response::builder()
+        .status(409)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(Body::from(message.to_string()))
+        .unwrap()
+}
+
+fn internal_error(error: String, ctx: &Context) -> Response<Body> {
+    try_warn!(ctx, "API: {error}");
+    Response::builder().status(500).body(Body::empty()).unwrap()
+}
+
+pub(super) fn json_response<T: serde::Serialize>(value: &T) -> Response<Body> {
+    Response::builder()
+        .status(200)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(value).unwrap_or_default()))
+        .unwrap()
+}
+
+/// Returns the value of `name` in a `key=value&...` query string.
+fn query_param<'a>(query: Option<&'a str>, name: &str) -> Option<&'a str> {
+    query?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then_some(value)
+    })
+}
+
+/// Parses the `at_height` query parameter of point-in-time queries. Heights past the indexed chain tip are rejected since
+/// their state is not known yet.
+fn at_height_param(
+    query: Option<&str>,
+    chain_tip: Option<u64>,
+) -> Result<Option<u64>, Response<Body>> {
+    let Some(at_height) = query_param(query, "at_height") else {
+        return Ok(None);
+    };
+    let Ok(at_height) = at_height.parse::<u64>() else {
+        return Err(bad_request("invalid at_height query parameter"));
+    };
+    if !chain_tip.is_some_and(|chain_tip| at_height <= chain_tip) {
+        return Err(bad_request("at_height is past the indexed chain tip"));
+    }
+    Ok(Some(at_height))
+}
+
+/// Whether `?include_provisional=true` asks for inscriptions of tip blocks that are not indexed yet.
+fn include_provisional_param(query: Option<&str>) -> bool {
+    query_param(query, "include_provisional") == Some("true")
+}
+
+/// Decodes a percent-encoded query string value, where `+` stands for a space.
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value.get(i + 1..i + 3)?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Serializes inscriptions along with their current location.
+async fn inscriptions_response<T: GenericClient>(
+    inscriptions: Vec<DbInscription>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let ordinal_numbers = inscriptions.iter().map(|i| i.ordinal_number.0).collect();
+    let locations = ordinals_pg::get_current_locations(&ordinal_numbers, client).await?;
+    let results: Vec<ApiInscription> = inscriptions
+        .into_iter()
+        .map(|inscription| {
+            let location = locations.get(&inscription.ordinal_number.0);
+            ApiInscription::from_db(inscription, location)
+        })
+        .collect();
+    Ok(json_response(&results))
+}
+
+async fn get_inscription<T: GenericClient>(
+    inscription_id: &str,
+    query: Option<&str>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let Some(inscription) = ordinals_pg::get_inscription_by_id(inscription_id, client).await?
+    else {
+        if include_provisional_param(query) {
+            if let Some(inscription) =
+                ordinals_pg::get_provisional_inscription_by_id(inscription_id, client).await?
+            {
+                return Ok(json_response(&ApiProvisionalInscription::from_db(
+                    inscription,
+                )));
+            }
+        }
+        return Ok(not_found());
+    };
+    let locations =
+        ordinals_pg::get_current_locations(&vec![inscription.ordinal_number.0], client).await?;
+    let location = locations.get(&inscription.ordinal_number.0);
+    Ok(json_response(&ApiInscription::from_db(
+        inscription,
+        location,
+    )))
+}
+
+/// 451 response for inscriptions whose content was taken down or filtered by a content policy or scanner. Both can
+/// happen at any time without the chain tip moving, so this is checked before the response cache.
+async fn get_withdrawn_content<T: GenericClient>(
+    inscription_id: &str,
+    client: &T,
+) -> Result<Option<Response<Body>>, String> {
+    if ordinals_pg::get_inscription_takedown(inscription_id, client)
+        .await?
+        .is_some()
+    {
+        return Ok(Some(
+            Response::builder()
+                .status(451)
+                .header(CONTENT_TYPE, "text/plain")
+                .body(Body::from("content taken down"))
+                .unwrap(),
+        ));
+    }
+    if let Some(filtered) = ordinals_pg::get_inscription_filter(inscription_id, client).await? {
+        return Ok(Some(
+            Response::builder()
+                .status(451)
+                .header(CONTENT_TYPE, "text/plain")
+                .body(Body::from(format!(
+                    "content filtered by the {} policy",
+                    filtered.policy
+                )))
+                .unwrap(),
+        ));
+    }
+    Ok(None)
+}
+
+/// Raw content of an inscription, served with its content type. Inscriptions whose content was not stored respond with
+/// a 404, and withdrawn ones are answered by `get_withdrawn_content` beforehand. Provisional inscriptions are served with
+/// `?include_provisional=true`.
+async fn get_inscription_content<T: GenericClient>(
+    inscription_id: &str,
+    query: Option<&str>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let (content_type, content, content_omitted) =
+        match ordinals_pg::get_inscription_by_id(inscription_id, client).await? {
+            Some(inscription) => (
+                inscription.content_type,
+                inscription.content,
+                inscription.content_omitted,
+            ),
+            None if include_provisional_param(query) => {
+                match ordinals_pg::get_provisional_inscription_by_id(inscription_id, client).await?
+                {
+                    Some(inscription) => (inscription.content_type, inscription.content, false),
+                    None => return Ok(not_found()),
+                }
+            }
+            None => return Ok(not_found()),
+        };
+    if content_omitted {
+        return Ok(not_found());
+    }
+    // Content types come straight from the chain and may not be valid header values.
+    let content_type = HeaderValue::from_str(&content_type)
+        .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+    Ok(Response::builder()
+        .status(200)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(content))
+        .unwrap())
+}
+
+/// Inscriptions revealed in a block, in reveal order. Paginated with `limit` (max 60) and `offset`.
+async fn get_inscriptions_at_block<T: GenericClient>(
+    query: Option<&str>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let Some(block_height) = query_param(query, "block") else {
+        return Ok(bad_request("missing block query parameter"));
+    };
+    let Ok(block_height) = block_height.parse::<u64>() else {
+        return Ok(bad_request("invalid block query parameter"));
+    };
+    let Ok(limit) = query_param(query, "limit").unwrap_or("20").parse::<i64>() else {
+        return Ok(bad_request("invalid limit query parameter"));
+    };
+    let Ok(offset) = query_param(query, "offset").unwrap_or("0").parse::<i64>() else {
+        return Ok(bad_request("invalid offset query parameter"));
+    };
+    let (limit, offset) = (limit.clamp(1, 60), offset.max(0));
+    let inscriptions =
+        ordinals_pg::get_inscriptions_revealed_at_block(block_height, Some(limit), offset, client)
+            .await?;
+    if !include_provisional_param(query) {
+        return inscriptions_response(inscriptions, client).await;
+    }
+    // Provisional rows are dropped as soon as their block is indexed, so a block never has both.
+    let ordinal_numbers = inscriptions.iter().map(|i| i.ordinal_number.0).collect();
+    let locations = ordinals_pg::get_current_locations(&ordinal_numbers, client).await?;
+    let mut results: Vec<ApiInscriptionEntry> = inscriptions
+        .into_iter()
+        .map(|inscription| {
+            let location = locations.get(&inscription.ordinal_number.0);
+            ApiInscriptionEntry::Indexed(ApiInscription::from_db(inscription, location))
+        })
+        .collect();
+    results.extend(
+        ordinals_pg::get_provisional_inscriptions_at_block(block_height, client)
+            .await?
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|inscription| {
+                ApiInscriptionEntry::Provisional(ApiProvisionalInscription::from_db(inscription))
+            }),
+    );
+    Ok(json_response(&results))
+}
+
+/// Inscriptions held by an address, newest first, or those it held at the end of block `at_height`. Paginated with
+/// `limit` (max 60) and `offset`.
+async fn get_address_inscriptions<T: GenericClient>(
+    address: &str,
+    query: Option<&str>,
+    chain_tip: Option<u64>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let at_height = match at_height_param(query, chain_tip) {
+        Ok(at_height) => at_height,
+        Err(response) => return Ok(response),
+    };
+    let Ok(limit) = query_param(query, "limit").unwrap_or("20").parse::<i64>() else {
+        return Ok(bad_request("invalid limit query parameter"));
+    };
+    let Ok(offset) = query_param(query, "offset").unwrap_or("0").parse::<i64>() else {
+        return Ok(bad_request("invalid offset query parameter"));
+    };
+    let holdings = ordinals_pg::get_inscriptions_held_by_address(
+        &address.to_string(),
+        at_height,
+        limit.clamp(1, 60),
+        offset.max(0),
+        client,
+    )
+    .await?;
+    let results: Vec<ApiInscription> = holdings
+        .into_iter()
+        .map(|(inscription, location)| ApiInscription::from_db(inscription, Some(&location)))
+        .collect();
+    Ok(json_response(&results))
+}
+
+/// Addresses an address clusterer assigned to `cluster_id`, paginated with `limit` (max 60) and `offset`.
+async fn get_cluster_addresses<T: GenericClient>(
+    cluster_id: &str,
+    query: Option<&str>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let Ok(limit) = query_param(query, "limit").unwrap_or("20").parse::<i64>() else {
+        return Ok(bad_request("invalid limit query parameter"));
+    };
+    let Ok(offset) = query_param(query, "offset").unwrap_or("0").parse::<i64>() else {
+        return Ok(bad_request("invalid offset query parameter"));
+    };
+    let addresses = ordinals_pg::get_cluster_addresses(
+        &cluster_id.to_string(),
+        limit.clamp(1, 60),
+        offset.max(0),
+        client,
+    )
+    .await?;
+    Ok(json_response(&addresses))
+}
+
+/// Inscriptions currently held by any address of a cluster, paginated with `limit` (max 60) and `offset`.
+async fn get_cluster_inscriptions<T: GenericClient>(
+    cluster_id: &str,
+    query: Option<&str>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let Ok(limit) = query_param(query, "limit").unwrap_or("20").parse::<i64>() else {
+        return Ok(bad_request("invalid limit query parameter"));
+    };
+    let Ok(offset) = query_param(query, "offset").unwrap_or("0").parse::<i64>() else {
+        return Ok(bad_request("invalid offset query parameter"));
+    };
+    let holdings = ordinals_pg::get_inscriptions_held_by_cluster(
+        &cluster_id.to_string(),
+        limit.clamp(1, 60),
+        offset.max(0),
+        client,
+    )
+    .await?;
+    let results: Vec<ApiInscription> = holdings
+        .into_iter()
+        .map(|(inscription, location)| ApiInscription::from_db(inscription, Some(&location)))
+        .collect();
+    Ok(json_response(&results))
+}
+
+/// Block holding the operation with a given sequence number, so consumers can resume from a stored sequence cursor.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiOperationSequence {
+    pub sequence: u64,
+    pub block_height: u64,
+    pub block_hash: String,
+    pub first_operation_sequence: u64,
+}
+
+async fn get_operation_sequence<T: GenericClient>(
+    sequence: &str,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let Ok(sequence) = sequence.parse::<u64>() else {
+        return Ok(bad_request("invalid sequence"));
+    };
+    match ordinals_pg::get_block_by_operation_sequence(sequence, client).await? {
+        Some((block_height, block_hash, first_operation_sequence)) => {
+            Ok(json_response(&ApiOperationSequence {
+                sequence,
+                block_height,
+                block_hash,
+                first_operation_sequence,
+            }))
+        }
+        None => Ok(not_found()),
+    }
+}
+
+/// Full-text search over text and JSON inscriptions, paginated with `limit` (max 60) and `offset`.
+async fn search_inscriptions<T: GenericClient>(
+    query: Option<&str>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let Some(search) = query_param(query, "q").and_then(percent_decode) else {
+        return Ok(bad_request("missing q query parameter"));
+    };
+    if search.trim().is_empty() {
+        return Ok(bad_request("missing q query parameter"));
+    }
+    let Ok(limit) = query_param(query, "limit").unwrap_or("20").parse::<i64>() else {
+        return Ok(bad_request("invalid limit query parameter"));
+    };
+    let Ok(offset) = query_param(query, "offset").unwrap_or("0").parse::<i64>() else {
+        return Ok(bad_request("invalid offset query parameter"));
+    };
+    let inscriptions =
+        ordinals_pg::search_inscriptions(&search, limit.clamp(1, 60), offset.max(0), client)
+            .await?;
+    inscriptions_response(inscriptions, client).await
+}
+
+async fn get_brc20_token<T: GenericClient>(
+    ticker: &str,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    match brc20_pg::get_token(&ticker.to_lowercase(), client).await? {
+        Some(token) => Ok(json_response(&ApiBrc20Token::from_db(token))),
+        None => Ok(not_found()),
+    }
+}
+
+/// Balances of an address, or its balances at the end of block `at_height`.
+async fn get_brc20_balances<T: GenericClient>(
+    address: &str,
+    query: Option<&str>,
+    chain_tip: Option<u64>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let balances = match at_height_param(query, chain_tip) {
+        Ok(Some(at_height)) => {
+            brc20_pg::get_balances_for_address_at_height(&address.to_string(), at_height, client)
+                .await?
+        }
+        Ok(None) => brc20_pg::get_balances_for_address(&address.to_string(), client).await?,
+        Err(response) => return Ok(response),
+    };
+    let tickers = balances.iter().map(|b| b.ticker.clone()).collect();
+    let tokens: HashMap<String, DbToken> = brc20_pg::get_tokens(&tickers, client)
+        .await?
+        .into_iter()
+        .map(|token| (token.ticker.clone(), token))
+        .collect();
+    let results: Vec<ApiBrc20Balance> = balances
+        .into_iter()
+        .filter_map(|balance| {
+            let token = tokens.get(&balance.ticker)?;
+            Some(ApiBrc20Balance::from_db(balance, token))
+        })
+        .collect();
+    Ok(json_response(&results))
+}
+
+/// Operations that changed the balances of an address, newest first. Can be narrowed down to one `operation` and is
+/// paginated with `limit` (max 60) and `offset`.
+async fn get_brc20_activity<T: GenericClient>(
+    address: &str,
+    query: Option<&str>,
+    client: &T,
+) -> Result<Response<Body>, String> {
+    let operation = query_param(query, "operation");
+    if !matches!(
+        operation,
+        None | Some("deploy" | "mint" | "transfer" | "transfer_send" | "transfer_receive")
+    ) {
+        return Ok(bad_request("invalid operation query parameter"));
+    }
+    let Ok(limit) = query_param(query, "limit").unwrap_or("20").parse::<i64>() else {
+        return Ok(bad_request("invalid limit query parameter"));
+    };
+    let Ok(offset) = query_param(query, "offset").unwrap_or("0").parse::<i64>() else {
+        return Ok(bad_request("invalid offset query parameter"));
+    };
+    let operations = brc20_pg::get_address_operations(
+        &address.to_string(),
+        operation,
+        limit.clamp(1, 60),
+        offset.max(0),
+        client,
+    )
+    .await?;
+    let mut tickers: Vec<String> = operations.iter().map(|op| op.ticker.clone()).collect();
+    tickers.sort();
+    tickers.dedup();
+    let tokens: HashMap<String, DbToken> = brc20_pg::get_tokens(&tickers, client)
+        .await?
+        .into_iter()
+        .map(|token| (token.ticker.clone(), token))
+        .collect();
+    let results: Vec<ApiBrc20Activity> = operations
+        .into_iter()
+        .filter_map(|operation| {
+            let token = tokens.get(&operation.ticker)?;
+            Some(ApiBrc20Activity::from_db(operation, token))
+        })
+        .collect();
+    Ok(json_response(&results))
+}
+
+/// Serialized bytes of an indexed transaction, hex encoded. Only inscription reveal transactions are indexed, so
+/// transfers and any other transaction respond with a 404, and reveals of taken down inscriptions with a 451. The index
+/// only locates the block, bitcoind serves the bytes.
+async fn get_raw_transaction<T: GenericClient>(
+    txid: &str,
+    raw_transactions: Option<&Arc<RawTransactionsReader>>,
+    config: &Config,
+    client: &T,
+    ctx: &Context,
+) -> Result<Response<Body>, String> {
+    let Some(raw_transactions) = raw_transactions else {
+        return Ok(not_found());
+    };
+    let txid = txid.trim_start_matches("0x").to_lowercase();
+    let Some((block_height, _)) = raw_transactions.find(&txid, config).await? else {
+        return Ok(not_found());
+    };
+    if ordinals_pg::is_transaction_taken_down(&txid, client).await? {
+        return Ok(Response::builder()
+            .status(451)
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from("content taken down"))
+            .unwrap());
+    }
+    let http_client = build_http_client();
+    let bitcoin_config = config.get_event_observer_config().get_bitcoin_config();
+    let block_hash =
+        retrieve_block_hash(&http_client, &(block_height as u64), &bitcoin_config, ctx).await?;
+    let raw_transaction =
+        retrieve_raw_transaction(&http_client, &txid, &block_hash, &bitcoin_config, ctx).await?;
+    Ok(Response::builder()
+        .status(200)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(Body::from(raw_transaction))
+        .unwrap())
+}
+
+/// Opens a WebSocket stream of ordinal activity. Filtered inscriptions that were revealed before the connection are
+/// looked up so their transfers match from the start.
+#[cfg(feature = "websocket")]
+async fn open_activity_stream(
+    req: Request<Body>,
+    ordinals_pool: &Pool,
+    activity_stream: &ActivityStreamSender,
+    ctx: &Context,
+) -> Result<Response<Body>, String> {
+    let mut filter = match ActivityStreamFilter::from_query(req.uri().query()) {
+        Ok(filter) => filter,
+        Err(e) => return Ok(bad_request(&e)),
+    };
+    if !filter.inscription_ids.is_empty() {
+        let client = pg_pool_client(ordinals_pool).await?;
+        for inscription_id in filter.inscription_ids.iter() {
+            if let Some(inscription) =
+                ordinals_pg::get_inscription_by_id(inscription_id, &client).await?
+            {
+                filter.ordinal_numbers.insert(inscription.ordinal_number.0);
+            }
+        }
+    }
+    Ok(serve_activity_stream(req, filter, activity_stream, ctx))
+}
+
+/// Reports the inscriptions and rare sats spent by the inputs of a PSBT, so wallets can warn users before they sign.
+async fn check_psbt(req: Request<Body>, ordinals_pool: &Pool) -> Result<Response<Body>, String> {
+    let mut body = req.into_body();
+    let mut bytes = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| format!("unable to read request body: {e}"))?;
+        if bytes.len() + chunk.len() > MAX_PSBT_CHECK_BODY_SIZE {
+            return Ok(bad_request("request body is too large"));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    let outputs = match parse_psbt_check_request(&bytes) {
+        Ok(outputs) => outputs,
+        Err(e) => return Ok(bad_request(&e)),
+    };
+    let client = pg_pool_client(ordinals_pool).await?;
+    let sats = ordinals_pg::get_inscribed_sats_at_outputs(&outputs, &client).await?;
+    Ok(json_response(&evaluate_psbt_inputs(&outputs, &sats)))
+}
+
+/// Checks a `?at_block_hash=` pin against the blocks we have indexed. Responds with a conflict when the block is unknown
+/// or was reorged out, so clients know the data they are reading is not on the chain they expect. On indexes upgraded
+/// from a version without `indexed_blocks`, blocks without inscriptions are only known once the startup backfill has
+/// recorded their hashes.
+async fn check_pinned_block_hash<T: GenericClient>(
+    at_block_hash: Option<&String>,
+    client: &T,
+) -> Result<Option<Response<Body>>, String> {
+    let Some(block_hash) = at_block_hash else {
+        return Ok(None);
+    };
+    if ordinals_pg::is_block_hash_indexed(block_hash, client).await? {
+        return Ok(None);
+    }
+    Ok(Some(conflict(&format!(
+        "block {block_hash} is not part of the indexed canonical chain"
+    ))))
+}
+
+async fn route_req(
+    req: &Request<Body>,
+    config: &Config,
+    pg_pools: &PgConnectionPools,
+    response_cache: &ApiResponseCache,
+    raw_transactions: Option<&Arc<RawTransactionsReader>>,
+    ctx: &Context,
+) -> Result<Response<Body>, String> {
+    let query = req.uri().query();
+    let segments: Vec<&str> = req.uri().path().trim_matches('/').split('/').collect();
+    let at_block_hash = query_param(query, "at_block_hash")
+        .map(|hash| hash.trim_start_matches("0x").to_lowercase());
+
+    // Ordinals reads and the pin check share one snapshot, so a reorg committed mid-request can't leak into the response.
+    let mut ord_client = pg_pool_client(&pg_pools.ordinals).await?;
+    let ord_tx = pg_begin_read_snapshot(&mut ord_client).await?;
+    if let Some(response) = check_pinned_block_hash(at_block_hash.as_ref(), &ord_tx).await? {
+        return Ok(response);
+    }
+    // Raw transactions are not cached, they are already read from bitcoind's own cache.
+    if let (&Method::GET, ["tx", txid, "raw"]) = (req.method(), segments.as_slice()) {
+        return get_raw_transaction(txid, raw_transactions, config, &ord_tx, ctx).await;
+    }
+    if let (&Method::GET, ["inscriptions", inscription_id, "content"]) =
+        (req.method(), segments.as_slice())
+    {
+        if let Some(response) = get_withdrawn_content(inscription_id, &ord_tx).await? {
+            return Ok(response);
+        }
+    }
+    let cache_key = req.uri().to_string();
+    let chain_tip = ordinals_pg::get_chain_tip_block_height(&ord_tx).await?;
+    // Provisional inscriptions change as tip blocks arrive, not with the indexed chain tip, so they bypass the cache.
+    let cacheable = !include_provisional_param(query);
+    if cacheable {
+        if let Some(response) = response_cache.get(&cache_key, chain_tip) {
+            return Ok(response);
+        }
+    }
+    let response = match (req.method(), segments.as_slice()) {
+        (&Method::GET, ["inscriptions", inscription_id]) => {
+            get_inscription(inscription_id, query, &ord_tx).await?
+        }
+        (&Method::GET, ["inscriptions", inscription_id, "content"]) => {
+            get_inscription_content(inscription_id, query, &ord_tx).await?
+        }
+        (&Method::GET, ["inscriptions"]) => get_inscriptions_at_block(query, &ord_tx).await?,
+        (&Method::GET, ["addresses", address, "inscriptions"]) => {
+            get_address_inscriptions(address, query, chain_tip, &ord_tx).await?
+        }
+        (&Method::GET, ["clusters", cluster_id, "addresses"]) => {
+            get_cluster_addresses(cluster_id, query, &ord_tx).await?
+        }
+        (&Method::GET, ["clusters", cluster_id, "inscriptions"]) => {
+            get_cluster_inscriptions(cluster_id, query, &ord_tx).await?
+        }
+        (&Method::GET, ["sequences", sequence]) => {
+            get_operation_sequence(sequence, &ord_tx).await?
+        }
+        (&Method::GET, ["search"]) if config.storage.text_search_index => {
+            search_inscriptions(query, &ord_tx).await?
+        }
+        (&Method::GET, ["brc20", "tokens", ticker]) => match &pg_pools.brc20 {
+            Some(brc20_pool) => {
+                let mut brc20_client = pg_pool_client(brc20_pool).await?;
+                let brc20_tx = pg_begin_read_snapshot(&mut brc20_client).await?;
+                get_brc20_token(ticker, &brc20_tx).await?
+            }
+            None => not_found(),
+        },
+        (&Method::GET, ["brc20", "balances", address]) => match &pg_pools.brc20 {
+            Some(brc20_pool) => {
+                let mut brc20_client = pg_pool_client(brc20_pool).await?;
+                let brc20_tx = pg_begin_read_snapshot(&mut brc20_client).await?;
+                get_brc20_balances(address, query, chain_tip, &brc20_tx).await?
+            }
+            None => not_found(),
+        },
+        (&Method::GET, ["brc20", "activity", address]) => match &pg_pools.brc20 {
+            Some(brc20_pool) => {
+                let mut brc20_client = pg_pool_client(brc20_pool).await?;
+                let brc20_tx = pg_begin_read_snapshot(&mut brc20_client).await?;
+                get_brc20_activity(address, query, &brc20_tx).await?
+            }
+            None => not_found(),
+        },
+        (_, _) => {
+            try_debug!(
+                ctx,
+                "API: received request with invalid method/route: {}/{}",
+                req.method(),
+                req.uri().path()
+            );
+            return Ok(not_found());
+        }
+    };
+    // BRC-20 data lives in its own database and can't be read from the ordinals snapshot, so a pin is checked again
+    // once it has been read, outside of the snapshot so that blocks committed since then are visible.
+    if at_block_hash.is_some() && segments.first() == Some(&"brc20") {
+        ord_tx
+            .rollback()
+            .await
+            .map_err(|e| format!("unable to close ordinals snapshot: {e}"))?;
+        if let Some(response) = check_pinned_block_hash(at_block_hash.as_ref(), &ord_client).await?
+        {
+            return Ok(response);
+        }
+    }
+    if response.status() != 200 || !cacheable {
+        return Ok(response);
+    }
+    let (parts, body) = response.into_parts();
+    let body = hyper::body::to_bytes(body)
+        .await
+        .map_err(|e| format!("unable to read response body: {e}"))?;
+    let content_type = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    response_cache.put(cache_key, chain_tip, content_type, body.clone());
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+async fn serve_req(
+    req: Request<Body>,
+    config: Config,
+    pg_pools: PgConnectionPools,
+    response_cache: Arc<ApiResponseCache>,
+    raw_transactions: Option<Arc<RawTransactionsReader>>,
+    activity_stream: ActivityStreamSender,
+    mempool_reveals: Option<Arc<MempoolReveals>>,
+    mempool_brc20: Option<Arc<MempoolBrc20Operations>>,
+    ctx: Context,
+) -> Result<Response<Body>, hyper::Error> {
+    // The stream takes ownership of the request to upgrade its connection.
+    #[cfg(feature = "websocket")]
+    if req.method() == Method::GET && req.uri().path().trim_matches('/') == "stream/ordinals" {
+        return Ok(
+            open_activity_stream(req, &pg_pools.ordinals, &activity_stream, &ctx)
+                .await
+                .unwrap_or_else(|e| internal_error(e, &ctx)),
+        );
+    }
+    if req.method() == Method::GET && req.uri().path().trim_matches('/') == "stream/events" {
+        return Ok(serve_event_stream(&activity_stream, &ctx));
+    }
+    // Pending reveals change with the mempool, not with the chain tip, so they bypass the response cache.
+    if req.method() == Method::GET && req.uri().path().trim_matches('/') == "mempool/inscriptions" {
+        return Ok(match &mempool_reveals {
+            Some(mempool_reveals) => json_response(&mempool_reveals.snapshot()),
+            None => not_found(),
+        });
+    }
+    if req.method() == Method::GET && req.uri().path().trim_matches('/') == "mempool/brc20" {
+        return Ok(match &mempool_brc20 {
+            Some(mempool_brc20) => json_response(&mempool_brc20.snapshot()),
+            None => not_found(),
+        });
+    }
+    // Checking a PSBT reads the request body, so it also needs to own the request.
+    if req.method() == Method::POST && req.uri().path().trim_matches('/') == "psbt/check" {
+        return Ok(check_psbt(req, &pg_pools.ordinals)
+            .await
+            .unwrap_or_else(|e| internal_error(e, &ctx)));
+    }
+    Ok(route_req(
+        &req,
+        &config,
+        &pg_pools,
+        &response_cache,
+        raw_transactions.as_ref(),
+        &ctx,
+    )
+    .await
+    .unwrap_or_else(|e| internal_error(e, &ctx)))
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+    use hyper::{Body, Request};
+
+    use crate::{
+        config::Config,
+        core::block_fixtures::index_committed_fixture,
+        db::{
+            drop_all_dbs,
+            models::{DbFilteredInscription, DbInscriptionTakedown},
+            ordinals_pg, pg_reset_db, pg_test_config, pg_test_connection,
+        },
+        service::{api_cache::ApiResponseCache, PgConnectionPools, Service},
+    };
+
+    use super::{at_height_param, percent_decode, query_param, route_req};
+
+    const INSCRIPTION_ID: &str =
+        "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0";
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    /// Routes a request past the response cache and returns its status and body.
+    async fn route_uncached(
+        req: Request<Body>,
+        config: &Config,
+        pg_pools: &PgConnectionPools,
+    ) -> Result<(u16, String), String> {
+        let response = route_req(
+            &req,
+            config,
+            pg_pools,
+            &ApiResponseCache::new(10),
+            None,
+            &Context::empty(),
+        )
+        .await?;
+        let status = response.status().as_u16();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|e| format!("unable to read response body: {e}"))?;
+        Ok((status, String::from_utf8_lossy(&body).to_string()))
+    }
+
+    #[test]
+    fn parses_query_parameters() {
+        assert_eq!(
+            query_param(Some("block=850000&limit=5"), "limit"),
+            Some("5")
+        );
+        assert_eq!(query_param(Some("block=850000&limit"), "limit"), None);
+        assert_eq!(query_param(Some("blocks=850000"), "block"), None);
+        assert_eq!(query_param(None, "block"), None);
+        assert_eq!(
+            percent_decode("ordi+deploy%21"),
+            Some("ordi deploy!".to_string())
+        );
+        assert_eq!(percent_decode("%E2%9C%93"), Some("\u{2713}".to_string()));
+        assert_eq!(percent_decode("%2"), None);
+        assert_eq!(percent_decode("%zz"), None);
+        assert_eq!(percent_decode("%ff"), None);
+    }
+
+    #[test]
+    fn rejects_heights_past_the_chain_tip() {
+        assert_eq!(at_height_param(Some("limit=5"), None).unwrap(), None);
+        assert_eq!(
+            at_height_param(Some("at_height=850000"), Some(850000)).unwrap(),
+            Some(850000)
+        );
+        for (query, chain_tip) in [
+            ("at_height=850001", Some(850000)),
+            ("at_height=850000", None),
+            ("at_height=tip", Some(850000)),
+        ] {
+            assert_eq!(
+                at_height_param(Some(query), chain_tip)
+                    .unwrap_err()
+                    .status(),
+                400
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn routes_inscription_requests() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/api_routes".to_string();
+        config.ordinals_db = pg_test_config();
+        drop_all_dbs(&config);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        let block = index_committed_fixture("inscription_reveal.jsonl.gz", &config, &ctx).await?;
+        let pg_pools = Service::new(&config, &ctx).pg_pools;
+        let block_hash = block.block_identifier.get_hash_bytes_str().to_string();
+
+        let (status, body) = route_uncached(
+            get(&format!("/inscriptions/{INSCRIPTION_ID}")),
+            &config,
+            &pg_pools,
+        )
+        .await?;
+        assert_eq!(status, 200);
+        assert!(body.contains(INSCRIPTION_ID));
+        let (status, body) = route_uncached(
+            get("/inscriptions?block=850000&limit=60"),
+            &config,
+            &pg_pools,
+        )
+        .await?;
+        assert_eq!(status, 200);
+        assert!(body.contains(INSCRIPTION_ID));
+        let (status, body) = route_uncached(
+            get("/inscriptions?block=850000&offset=1000"),
+            &config,
+            &pg_pools,
+        )
+        .await?;
+        assert_eq!((status, body.as_str()), (200, "[]"));
+        let (status, _) = route_uncached(
+            get(&format!(
+                "/inscriptions?block=850000&at_block_hash={block_hash}"
+            )),
+            &config,
+            &pg_pools,
+        )
+        .await?;
+        assert_eq!(status, 200);
+
+        for (uri, expected_status) in [
+            ("/inscriptions", 400),
+            ("/inscriptions?block=tip", 400),
+            ("/inscriptions?block=850000&limit=all", 400),
+            ("/inscriptions?block=850000&offset=-", 400),
+            ("/addresses/bc1q/inscriptions?at_height=850001", 400),
+            (
+                "/inscriptions/0000000000000000000000000000000000000000000000000000000000000000i0",
+                404,
+            ),
+            ("/unknown", 404),
+            (
+                "/inscriptions?block=850000&at_block_hash=ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                409,
+            ),
+        ] {
+            let (status, _) = route_uncached(get(uri), &config, &pg_pools).await?;
+            assert_eq!(status, expected_status, "GET {uri}");
+        }
+        let post = Request::post("/inscriptions?block=850000")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(route_uncached(post, &config, &pg_pools).await?.0, 404);
+
+        ordinals_pg::insert_filtered_inscriptions(
+            &vec![DbFilteredInscription {
+                inscription_id: INSCRIPTION_ID.to_string(),
+                policy: "denied_mime_types".to_string(),
+                reason: "mime type text/plain is denied".to_string(),
+            }],
+            &pg_client,
+        )
+        .await?;
+        let (status, body) = route_uncached(
+            get(&format!("/inscriptions/{INSCRIPTION_ID}/content")),
+            &config,
+            &pg_pools,
+        )
+        .await?;
+        assert_eq!(status, 451);
+        assert_eq!(body, "content filtered by the denied_mime_types policy");
+
+        pg_reset_db(&mut pg_client).await?;
+        drop_all_dbs(&config);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn taken_down_content_is_not_served_from_cache() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/api".to_string();
+        config.ordinals_db = pg_test_config();
+        drop_all_dbs(&config);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        index_committed_fixture("inscription_reveal.jsonl.gz", &config, &ctx).await?;
+        let pg_pools = Service::new(&config, &ctx).pg_pools;
+        let response_cache = ApiResponseCache::new(10);
+        let uri = format!("/inscriptions/{INSCRIPTION_ID}/content");
+
+        let response =
+            route_req(&get(&uri), &config, &pg_pools, &response_cache, None, &ctx).await?;
+        assert_eq!(response.status(), 200);
+        assert!(response_cache.get(&uri, Some(850000)).is_some());
+
+        ordinals_pg::take_down_inscription_content(
+            &DbInscriptionTakedown {
+                inscription_id: INSCRIPTION_ID.to_string(),
+                reason: "test".to_string(),
+            },
+            &pg_client,
+        )
+        .await?;
+        let response =
+            route_req(&get(&uri), &config, &pg_pools, &response_cache, None, &ctx).await?;
+        assert_eq!(response.status(), 451);
+
+        pg_reset_db(&mut pg_client).await?;
+        drop_all_dbs(&config);
+        Ok(())
+    }
+}
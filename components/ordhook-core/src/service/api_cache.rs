@@ -0,0 +1,93 @@
+use std::{num::NonZeroUsize, sync::Mutex};
+
+use hyper::{body::Bytes, header::CONTENT_TYPE, Body, Response};
+use lru::LruCache;
+
+struct CachedResponse {
+    chain_tip: Option<u64>,
+    content_type: Option<String>,
+    body: Bytes,
+}
+
+/// In-memory LRU cache of successful API responses, keyed by request path and query. Any response may change once a
+/// block is indexed or rolled back, so an entry is only served while the chain tip is the one it was computed at.
+pub struct ApiResponseCache {
+    responses: Option<Mutex<LruCache<String, CachedResponse>>>,
+}
+
+impl ApiResponseCache {
+    /// Creates a cache holding up to `size` responses. A size of `0` disables caching.
+    pub fn new(size: usize) -> Self {
+        ApiResponseCache {
+            responses: NonZeroUsize::new(size).map(|size| Mutex::new(LruCache::new(size))),
+        }
+    }
+
+    pub fn get(&self, key: &str, chain_tip: Option<u64>) -> Option<Response<Body>> {
+        let mut responses = self.responses.as_ref()?.lock().ok()?;
+        let cached = responses.get(key)?;
+        if cached.chain_tip != chain_tip {
+            responses.pop(key);
+            return None;
+        }
+        let mut response = Response::builder().status(200);
+        if let Some(content_type) = &cached.content_type {
+            response = response.header(CONTENT_TYPE, content_type);
+        }
+        response.body(Body::from(cached.body.clone())).ok()
+    }
+
+    pub fn put(
+        &self,
+        key: String,
+        chain_tip: Option<u64>,
+        content_type: Option<String>,
+        body: Bytes,
+    ) {
+        let Some(Ok(mut responses)) = self.responses.as_ref().map(|r| r.lock()) else {
+            return;
+        };
+        responses.put(
+            key,
+            CachedResponse {
+                chain_tip,
+                content_type,
+                body,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::body::Bytes;
+
+    use super::ApiResponseCache;
+
+    #[test]
+    fn invalidates_responses_on_new_chain_tip() {
+        let cache = ApiResponseCache::new(10);
+        cache.put(
+            "/inscriptions/abci0".to_string(),
+            Some(840_000),
+            Some("application/json".to_string()),
+            Bytes::from_static(b"{}"),
+        );
+        assert!(cache.get("/inscriptions/abci0", Some(840_000)).is_some());
+        assert!(cache.get("/inscriptions/abci1", Some(840_000)).is_none());
+        assert!(cache.get("/inscriptions/abci0", Some(840_001)).is_none());
+        assert!(cache.get("/inscriptions/abci0", Some(840_000)).is_none());
+    }
+
+    #[test]
+    fn disabled_with_zero_size() {
+        let cache = ApiResponseCache::new(0);
+        cache.put(
+            "/inscriptions/abci0".to_string(),
+            Some(840_000),
+            None,
+            Bytes::from_static(b"{}"),
+        );
+        assert!(cache.get("/inscriptions/abci0", Some(840_000)).is_none());
+    }
+}
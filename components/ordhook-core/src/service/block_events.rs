@@ -0,0 +1,306 @@
+use chainhook_types::{BitcoinBlockData, BlockIdentifier, Brc20Operation, OrdinalOperation};
+use ord::charm::Charm;
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+
+/// How the charms of revealed inscriptions are written in outgoing payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CharmsFormat {
+    /// List of charm names, e.g. `["cursed", "vindicated"]`.
+    #[default]
+    Names,
+    /// The `ord` charms bitfield as an integer.
+    Bitfield,
+}
+
+/// How outgoing payloads are written, see the `[sinks]` config.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PayloadFormat {
+    pub charms: CharmsFormat,
+    /// Leaves `timestamp_ms` out of payloads so they keep the shape they had before it was added.
+    pub legacy_timestamps: bool,
+}
+
+impl PayloadFormat {
+    /// Millisecond timestamp written next to a block's `timestamp`, which is in seconds.
+    pub fn timestamp_ms(&self, block: &BitcoinBlockData) -> Option<u64> {
+        if self.legacy_timestamps {
+            None
+        } else {
+            Some(block.timestamp_ms())
+        }
+    }
+}
+
+/// Serializes an ordinal operation for an outgoing payload, with the charms of a reveal in the given format.
+pub fn serialize_ordinal_operation<S: Serializer>(
+    operation: &OrdinalOperation,
+    charms_format: CharmsFormat,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match (operation, charms_format) {
+        (OrdinalOperation::InscriptionRevealed(reveal), CharmsFormat::Names) => {
+            let mut value = serde_json::to_value(operation).map_err(serde::ser::Error::custom)?;
+            value["inscription_revealed"]["charms"] = json!(Charm::names(reveal.charms));
+            value.serialize(serializer)
+        }
+        _ => operation.serialize(serializer),
+    }
+}
+
+/// Kind of change a block event describes. Consumers must undo the operations of a rolled back block before applying
+/// its replacement.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockEventKind {
+    Apply,
+    Rollback,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockEventOperation {
+    /// Position of the operation in the total order of operations, see `BlockEvent::first_sequence`.
+    pub sequence: Option<u64>,
+    pub tx_id: String,
+    pub operation: OrdinalOperation,
+    pub charms_format: CharmsFormat,
+}
+
+impl Serialize for BlockEventOperation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Operation<'a>(&'a OrdinalOperation, CharmsFormat);
+        impl Serialize for Operation<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serialize_ordinal_operation(self.0, self.1, serializer)
+            }
+        }
+        let mut state = serializer.serialize_struct("BlockEventOperation", 3)?;
+        state.serialize_field("sequence", &self.sequence)?;
+        state.serialize_field("tx_id", &self.tx_id)?;
+        state.serialize_field("operation", &Operation(&self.operation, self.charms_format))?;
+        state.end()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockEventBrc20Operation {
+    pub sequence: Option<u64>,
+    pub tx_id: String,
+    pub operation: Brc20Operation,
+}
+
+/// Event delivered to external consumers for every block applied to or rolled back from the index.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockEvent {
+    pub kind: BlockEventKind,
+    pub block_height: u64,
+    pub block_identifier: Option<BlockIdentifier>,
+    pub parent_block_identifier: Option<BlockIdentifier>,
+    /// Block timestamp in seconds since the Unix epoch (UTC).
+    pub timestamp: Option<u32>,
+    /// Block timestamp in milliseconds since the Unix epoch (UTC).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp_ms: Option<u64>,
+    /// Sequence number of the first operation of the block. Operations are numbered without gaps along the canonical
+    /// chain, in transaction order with the BRC-20 operation of a transaction after its ordinal operations. Blocks that
+    /// replace rolled back ones reuse the numbers of the blocks they replace.
+    pub first_sequence: Option<u64>,
+    pub operations: Vec<BlockEventOperation>,
+    pub brc20_operations: Vec<BlockEventBrc20Operation>,
+}
+
+impl BlockEvent {
+    pub fn apply(block: &BitcoinBlockData, payload_format: PayloadFormat) -> Self {
+        let first_sequence = block.metadata.first_operation_sequence;
+        let mut operations = vec![];
+        let mut brc20_operations = vec![];
+        let mut offset = 0;
+        let mut next_sequence = || {
+            let sequence = first_sequence.map(|first| first + offset);
+            offset += 1;
+            sequence
+        };
+        for tx in block.transactions.iter() {
+            for operation in tx.metadata.ordinal_operations.iter() {
+                operations.push(BlockEventOperation {
+                    sequence: next_sequence(),
+                    tx_id: tx.transaction_identifier.hash.clone(),
+                    operation: operation.clone(),
+                    charms_format: payload_format.charms,
+                });
+            }
+            if let Some(operation) = &tx.metadata.brc20_operation {
+                brc20_operations.push(BlockEventBrc20Operation {
+                    sequence: next_sequence(),
+                    tx_id: tx.transaction_identifier.hash.clone(),
+                    operation: operation.clone(),
+                });
+            }
+        }
+        BlockEvent {
+            kind: BlockEventKind::Apply,
+            block_height: block.block_identifier.index,
+            block_identifier: Some(block.block_identifier.clone()),
+            parent_block_identifier: Some(block.parent_block_identifier.clone()),
+            timestamp: Some(block.timestamp),
+            timestamp_ms: payload_format.timestamp_ms(block),
+            first_sequence,
+            operations,
+            brc20_operations,
+        }
+    }
+
+    pub fn rollback(block_height: u64) -> Self {
+        BlockEvent {
+            kind: BlockEventKind::Rollback,
+            block_height,
+            block_identifier: None,
+            parent_block_identifier: None,
+            timestamp: None,
+            timestamp_ms: None,
+            first_sequence: None,
+            operations: vec![],
+            brc20_operations: vec![],
+        }
+    }
+}
+
+/// Number of operations a block takes in the sequence: its ordinal operations and BRC-20 operations.
+pub fn count_sequenced_operations(block: &BitcoinBlockData) -> u64 {
+    block
+        .transactions
+        .iter()
+        .map(|tx| {
+            tx.metadata.ordinal_operations.len() as u64
+                + tx.metadata.brc20_operation.is_some() as u64
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_types::{Brc20Operation, Brc20TokenDeployData, OrdinalOperation};
+
+    use crate::core::{
+        meta_protocols::brc20::test_utils::Brc20RevealBuilder,
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::{
+        count_sequenced_operations, BlockEvent, BlockEventKind, CharmsFormat, PayloadFormat,
+    };
+
+    #[test]
+    fn collects_block_operations() {
+        let block = TestBlockBuilder::new()
+            .height(840_000)
+            .add_transaction(TestTransactionBuilder::new().build())
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(
+                        Brc20RevealBuilder::new().build(),
+                    ))
+                    .build(),
+            )
+            .build();
+        let event = BlockEvent::apply(&block, PayloadFormat::default());
+        assert_eq!(event.kind, BlockEventKind::Apply);
+        assert_eq!(event.block_height, 840_000);
+        assert_eq!(event.operations.len(), 1);
+        assert!(BlockEvent::rollback(840_000).operations.is_empty());
+    }
+
+    #[test]
+    fn numbers_operations_in_transaction_order() {
+        let mut block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(
+                        Brc20RevealBuilder::new().build(),
+                    ))
+                    .brc20_operation(Some(Brc20Operation::Deploy(Brc20TokenDeployData {
+                        tick: "pepe".to_string(),
+                        max: "21000000".to_string(),
+                        lim: "1000".to_string(),
+                        dec: "18".to_string(),
+                        address: "bc1qdeployer".to_string(),
+                        inscription_id:
+                            "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0"
+                                .to_string(),
+                        self_mint: false,
+                    })))
+                    .build(),
+            )
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(
+                        Brc20RevealBuilder::new().build(),
+                    ))
+                    .build(),
+            )
+            .build();
+        assert_eq!(count_sequenced_operations(&block), 3);
+        assert_eq!(
+            BlockEvent::apply(&block, PayloadFormat::default()).operations[0].sequence,
+            None
+        );
+
+        block.metadata.first_operation_sequence = Some(100);
+        let event = BlockEvent::apply(&block, PayloadFormat::default());
+        assert_eq!(event.first_sequence, Some(100));
+        assert_eq!(
+            event
+                .operations
+                .iter()
+                .map(|operation| operation.sequence)
+                .collect::<Vec<_>>(),
+            vec![Some(100), Some(102)]
+        );
+        assert_eq!(event.brc20_operations[0].sequence, Some(101));
+    }
+
+    #[test]
+    fn serializes_charms_in_configured_format() {
+        let mut reveal = Brc20RevealBuilder::new().build();
+        reveal.charms = 0b10 | 0b100_0000_0000;
+        let block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(reveal))
+                    .build(),
+            )
+            .build();
+
+        let event =
+            serde_json::to_value(BlockEvent::apply(&block, PayloadFormat::default())).unwrap();
+        assert_eq!(
+            event["operations"][0]["operation"]["inscription_revealed"]["charms"],
+            serde_json::json!(["cursed", "vindicated"])
+        );
+        let payload_format = PayloadFormat {
+            charms: CharmsFormat::Bitfield,
+            ..Default::default()
+        };
+        let event = serde_json::to_value(BlockEvent::apply(&block, payload_format)).unwrap();
+        assert_eq!(
+            event["operations"][0]["operation"]["inscription_revealed"]["charms"],
+            serde_json::json!(1026)
+        );
+    }
+
+    #[test]
+    fn writes_timestamps_in_seconds_and_milliseconds() {
+        let block = TestBlockBuilder::new().build();
+        let event =
+            serde_json::to_value(BlockEvent::apply(&block, PayloadFormat::default())).unwrap();
+        assert_eq!(event["timestamp"], serde_json::json!(1712982301));
+        assert_eq!(event["timestamp_ms"], serde_json::json!(1712982301000u64));
+
+        let payload_format = PayloadFormat {
+            legacy_timestamps: true,
+            ..Default::default()
+        };
+        let event = serde_json::to_value(BlockEvent::apply(&block, payload_format)).unwrap();
+        assert_eq!(event["timestamp"], serde_json::json!(1712982301));
+        assert!(event.get("timestamp_ms").is_none());
+    }
+}
@@ -0,0 +1,433 @@
+use std::collections::HashMap;
+
+use chainhook_postgres::{pg_begin, pg_pool, pg_pool_client, types::PgNumericU64};
+use chainhook_sdk::utils::Context;
+use chainhook_types::{
+    BitcoinBlockData, BitcoinBlockMetadata, BitcoinNetwork, BitcoinTransactionData,
+    BitcoinTransactionMetadata, BlockIdentifier, OrdinalInscriptionNumber,
+    OrdinalInscriptionRevealData, OrdinalInscriptionTransferData,
+    OrdinalInscriptionTransferDestination, OrdinalOperation, TransactionIdentifier,
+};
+use ord::inscription::Inscription;
+
+use crate::{
+    config::Config,
+    core::meta_protocols::brc20::{
+        brc20_activation_height, brc20_pg,
+        cache::Brc20MemoryCache,
+        index::index_block_and_insert_brc20_operations,
+        parser::{parse_brc20_operation, ParsedBrc20Operation},
+    },
+    db::{
+        models::{DbInscription, DbLocation},
+        ordinals_pg, pg_commit_unless_dry_run,
+    },
+    try_info, try_warn,
+};
+
+fn format_satpoint(output: &String, offset: &Option<PgNumericU64>) -> String {
+    match offset {
+        Some(offset) => format!("{output}:{}", offset.0),
+        None => output.clone(),
+    }
+}
+
+fn inscription_index_in_tx(inscription_id: &str) -> u64 {
+    inscription_id
+        .rsplit_once('i')
+        .and_then(|(_, index)| index.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Rebuilds the ordinal operations of a block from the rows the ordinals index stored for it, in the order the indexer
+/// produced them: reveals of a transaction first, by envelope position, then its transfers. Transactions without ordinal
+/// activity are left empty but kept so operations keep their transaction index. Returns `None` if the block had no
+/// ordinal activity, along with the BRC-20 operations found in the revealed contents.
+pub fn build_block_from_ordinals_rows(
+    block_height: u64,
+    network: &BitcoinNetwork,
+    mut inscriptions: Vec<DbInscription>,
+    locations: Vec<DbLocation>,
+    mut parents: HashMap<String, Vec<String>>,
+    ctx: &Context,
+) -> Result<Option<(BitcoinBlockData, HashMap<String, ParsedBrc20Operation>)>, String> {
+    let (block_hash, timestamp) = match (inscriptions.first(), locations.first()) {
+        (Some(inscription), _) => (inscription.block_hash.clone(), inscription.timestamp.0),
+        (None, Some(location)) => (location.block_hash.clone(), location.timestamp.0),
+        (None, None) => return Ok(None),
+    };
+    let tx_count = inscriptions
+        .iter()
+        .map(|i| i.tx_index.0)
+        .chain(locations.iter().map(|l| l.tx_index.0))
+        .max()
+        .unwrap_or(0) as usize
+        + 1;
+    let mut transactions: Vec<BitcoinTransactionData> = (0..tx_count)
+        .map(|tx_index| BitcoinTransactionData {
+            transaction_identifier: TransactionIdentifier {
+                hash: String::new(),
+            },
+            operations: vec![],
+            metadata: BitcoinTransactionMetadata {
+                inputs: vec![],
+                outputs: vec![],
+                ordinal_operations: vec![],
+                brc20_operation: None,
+                proof: None,
+                fee: 0,
+                index: tx_index as u32,
+            },
+        })
+        .collect();
+    let post_locations: HashMap<(u64, u32), &DbLocation> = locations
+        .iter()
+        .map(|l| ((l.ordinal_number.0, l.tx_index.0), l))
+        .collect();
+
+    let mut brc20_operation_map = HashMap::new();
+    inscriptions.sort_by(|a, b| {
+        (a.tx_index.0, inscription_index_in_tx(&a.inscription_id))
+            .cmp(&(b.tx_index.0, inscription_index_in_tx(&b.inscription_id)))
+    });
+    for inscription in inscriptions.into_iter() {
+        let Some(location) =
+            post_locations.get(&(inscription.ordinal_number.0, inscription.tx_index.0))
+        else {
+            return Err(format!(
+                "no location recorded for inscription {} at block #{block_height}",
+                inscription.inscription_id
+            ));
+        };
+        if inscription.content_omitted {
+            try_warn!(
+                ctx,
+                "Content of inscription {} was not stored, it can't be checked for a BRC-20 operation",
+                inscription.inscription_id
+            );
+        } else {
+            let envelope = Inscription {
+                body: Some(inscription.content.clone()),
+                content_type: Some(inscription.content_type.clone().into_bytes()),
+                ..Default::default()
+            };
+            match parse_brc20_operation(&envelope) {
+                Ok(Some(op)) => {
+                    brc20_operation_map.insert(inscription.inscription_id.clone(), op);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    try_warn!(ctx, "Error parsing BRC-20 operation: {}", e);
+                }
+            };
+        }
+        let tx = &mut transactions[inscription.tx_index.0 as usize];
+        tx.transaction_identifier.hash = format!("0x{}", inscription.tx_id);
+        tx.metadata
+            .ordinal_operations
+            .push(OrdinalOperation::InscriptionRevealed(
+                OrdinalInscriptionRevealData {
+                    content_bytes: String::new(),
+                    content_type: inscription.content_type,
+                    content_length: inscription.content_length.0 as usize,
+                    inscription_number: OrdinalInscriptionNumber {
+                        classic: inscription.classic_number,
+                        jubilee: inscription.number,
+                    },
+                    inscription_fee: inscription.fee.0,
+                    inscription_output_value: location.value.as_ref().map(|v| v.0).unwrap_or(0),
+                    parents: parents
+                        .remove(&inscription.inscription_id)
+                        .unwrap_or_default(),
+                    inscription_id: inscription.inscription_id,
+                    inscription_input_index: inscription.input_index.0 as usize,
+                    inscription_pointer: inscription.pointer.map(|p| p.0),
+                    inscriber_address: inscription.address,
+                    delegate: inscription.delegate,
+                    metaprotocol: inscription.metaprotocol,
+                    metadata: None,
+                    ordinal_number: inscription.ordinal_number.0,
+                    ordinal_block_height: 0,
+                    ordinal_offset: 0,
+                    tx_index: inscription.tx_index.0 as usize,
+                    transfers_pre_inscription: 0,
+                    satpoint_post_inscription: format_satpoint(&location.output, &location.offset),
+                    curse_type: None,
+                    charms: inscription.charms.0 as u16,
+                    unbound_sequence: inscription.unbound_sequence,
+                },
+            ));
+    }
+    for location in locations.iter() {
+        let Some(prev_output) = &location.prev_output else {
+            // Reveal locations were covered above.
+            continue;
+        };
+        let destination = match location.transfer_type.as_str() {
+            "transferred" => OrdinalInscriptionTransferDestination::Transferred(
+                location.address.clone().unwrap_or_default(),
+            ),
+            "spent_in_fees" => OrdinalInscriptionTransferDestination::SpentInFees,
+            _ => OrdinalInscriptionTransferDestination::Burnt(String::new()),
+        };
+        let tx = &mut transactions[location.tx_index.0 as usize];
+        tx.transaction_identifier.hash = format!("0x{}", location.tx_id);
+        tx.metadata
+            .ordinal_operations
+            .push(OrdinalOperation::InscriptionTransferred(
+                OrdinalInscriptionTransferData {
+                    ordinal_number: location.ordinal_number.0,
+                    destination,
+                    from_address: location.prev_address.clone(),
+                    satpoint_pre_transfer: format_satpoint(prev_output, &location.prev_offset),
+                    satpoint_post_transfer: format_satpoint(&location.output, &location.offset),
+                    post_transfer_output_value: location.value.as_ref().map(|v| v.0),
+                    tx_index: location.tx_index.0 as usize,
+                },
+            ));
+    }
+
+    let block = BitcoinBlockData {
+        block_identifier: BlockIdentifier {
+            index: block_height,
+            hash: format!("0x{block_hash}"),
+        },
+        parent_block_identifier: BlockIdentifier {
+            index: block_height.saturating_sub(1),
+            hash: String::new(),
+        },
+        timestamp,
+        transactions,
+        metadata: BitcoinBlockMetadata {
+            network: network.clone(),
+            first_operation_sequence: None,
+        },
+    };
+    Ok(Some((block, brc20_operation_map)))
+}
+
+/// Builds the BRC-20 index from the inscriptions and transfers already stored in the ordinals database, so BRC-20 can
+/// be enabled on an existing deployment without re-downloading blocks. Picks up after the last block that produced a
+/// BRC-20 operation, so an interrupted backfill can simply be started again. The service must not be running while this
+/// runs, and BRC-20 modules and event sinks are not notified of backfilled blocks. Returns the number of blocks
+/// backfilled.
+pub async fn backfill_brc20_from_ordinals_index(
+    config: &Config,
+    start_block: Option<u64>,
+    end_block: Option<u64>,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let (true, Some(brc20_db)) = (config.meta_protocols.brc20, &config.brc20_db) else {
+        return Err("BRC-20 indexing is not enabled in this config".to_string());
+    };
+    let network = &config.network.bitcoin_network;
+    let ord_pool = pg_pool(&config.ordinals_db)?;
+    let ord_client = pg_pool_client(&ord_pool).await?;
+    let brc20_pool = pg_pool(brc20_db)?;
+    let mut brc20_client = pg_pool_client(&brc20_pool).await?;
+
+    let Some(chain_tip) = ordinals_pg::get_chain_tip_block_height(&ord_client).await? else {
+        return Err("the ordinals index is empty".to_string());
+    };
+    let end_block = end_block.unwrap_or(chain_tip).min(chain_tip);
+    let mut start_block = start_block
+        .unwrap_or(0)
+        .max(brc20_activation_height(network));
+    if let Some(last_block) = brc20_pg::get_highest_operation_block_height(&brc20_client).await? {
+        if last_block >= start_block {
+            try_info!(
+                ctx,
+                "BRC-20 operations already indexed up to block #{last_block}, resuming after it"
+            );
+            start_block = last_block + 1;
+        }
+    }
+    if start_block > end_block {
+        try_info!(
+            ctx,
+            "BRC-20 index is already backfilled up to block #{end_block}"
+        );
+        return Ok(0);
+    }
+    try_info!(
+        ctx,
+        "Backfilling BRC-20 from the ordinals index, blocks #{start_block} to #{end_block}"
+    );
+
+    let self_mint_activation_height = config
+        .meta_protocols
+        .brc20_self_mint_activation_height(network);
+    let mut brc20_cache = Brc20MemoryCache::new(config.resources.brc20_lru_cache_size);
+    let mut count = 0;
+    for block_height in start_block..=end_block {
+        let inscriptions =
+            ordinals_pg::get_inscriptions_revealed_at_block(block_height, None, 0, &ord_client)
+                .await?;
+        let locations = ordinals_pg::get_locations_at_block(block_height, &ord_client).await?;
+        let parents =
+            ordinals_pg::get_inscription_parents_at_block(block_height, &ord_client).await?;
+        let Some((mut block, mut brc20_operation_map)) = build_block_from_ordinals_rows(
+            block_height,
+            network,
+            inscriptions,
+            locations,
+            parents,
+            ctx,
+        )?
+        else {
+            continue;
+        };
+        let brc20_tx = pg_begin(&mut brc20_client).await?;
+        index_block_and_insert_brc20_operations(
+            &mut block,
+            &mut brc20_operation_map,
+            self_mint_activation_height,
+            config.meta_protocols.brc20_strictness,
+            &mut brc20_cache,
+            &brc20_tx,
+            ctx,
+        )
+        .await?;
+        pg_commit_unless_dry_run(brc20_tx, config, "brc20").await?;
+        count += 1;
+        if block_height % 1000 == 0 {
+            try_info!(ctx, "BRC-20 backfilled up to block #{block_height}");
+        }
+    }
+    try_info!(ctx, "BRC-20 backfill complete at block #{end_block}");
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use bitcoin::hashes::{sha256, Hash};
+    use chainhook_postgres::types::{PgBigIntU32, PgNumericU64};
+    use chainhook_types::{
+        BitcoinNetwork, OrdinalInscriptionTransferDestination, OrdinalOperation,
+    };
+
+    use crate::{
+        core::meta_protocols::brc20::{
+            parser::{ParsedBrc20Operation, ParsedBrc20TokenDeployData},
+            test_utils::get_test_ctx,
+        },
+        db::models::{DbInscription, DbLocation},
+    };
+
+    use super::build_block_from_ordinals_rows;
+
+    fn location(tx_index: u32, ordinal_number: u64, prev_output: Option<&str>) -> DbLocation {
+        DbLocation {
+            ordinal_number: PgNumericU64(ordinal_number),
+            block_height: PgNumericU64(780000),
+            tx_index: PgBigIntU32(tx_index),
+            tx_id: format!("{tx_index:064}"),
+            block_hash: "00000000000000000002b14f0c5dde0b2fc74d022e860696bd64f1f652756674"
+                .to_string(),
+            address: Some("bc1pa".to_string()),
+            output: format!("{tx_index:064}:0"),
+            offset: Some(PgNumericU64(0)),
+            prev_output: prev_output.map(|o| o.to_string()),
+            prev_offset: prev_output.map(|_| PgNumericU64(0)),
+            prev_address: prev_output.map(|_| "bc1pb".to_string()),
+            value: Some(PgNumericU64(546)),
+            transfer_type: "transferred".to_string(),
+            timestamp: PgBigIntU32(1677731361),
+        }
+    }
+
+    fn inscription(tx_index: u32, ordinal_number: u64, content: &str) -> DbInscription {
+        DbInscription {
+            inscription_id: format!("{tx_index:064}i0"),
+            ordinal_number: PgNumericU64(ordinal_number),
+            number: tx_index as i64,
+            classic_number: tx_index as i64,
+            block_height: PgNumericU64(780000),
+            block_hash: "00000000000000000002b14f0c5dde0b2fc74d022e860696bd64f1f652756674"
+                .to_string(),
+            tx_id: format!("{tx_index:064}"),
+            tx_index: PgBigIntU32(tx_index),
+            address: Some("bc1pa".to_string()),
+            mime_type: "text/plain".to_string(),
+            content_type: "text/plain;charset=utf-8".to_string(),
+            content_length: PgBigIntU32(content.len() as u32),
+            content: content.as_bytes().to_vec(),
+            content_omitted: false,
+            content_hash: sha256::Hash::hash(content.as_bytes()).to_string(),
+            fee: PgNumericU64(1000),
+            curse_type: None,
+            recursive: false,
+            input_index: PgBigIntU32(0),
+            pointer: None,
+            metadata: None,
+            metaprotocol: None,
+            delegate: None,
+            timestamp: PgBigIntU32(1677731361),
+            charms: PgBigIntU32(0),
+            unbound_sequence: None,
+        }
+    }
+
+    #[test]
+    fn rebuilds_ordinal_operations_at_their_tx_index() {
+        let inscriptions = vec![
+            inscription(
+                3,
+                200,
+                r#"{"p":"brc-20","op":"deploy","tick":"pepe","max":"21000000","lim":"1000"}"#,
+            ),
+            inscription(1, 100, "hello"),
+        ];
+        let locations = vec![
+            location(1, 100, None),
+            location(3, 200, None),
+            location(4, 300, Some("aa:0")),
+        ];
+        let mut parents = HashMap::new();
+        parents.insert(format!("{:064}i0", 1), vec!["parenti0".to_string()]);
+        let (block, operations) = build_block_from_ordinals_rows(
+            780000,
+            &BitcoinNetwork::Mainnet,
+            inscriptions,
+            locations,
+            parents,
+            &get_test_ctx(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(block.transactions.len(), 5);
+        assert!(block.transactions[0].metadata.ordinal_operations.is_empty());
+        let OrdinalOperation::InscriptionRevealed(reveal) =
+            &block.transactions[1].metadata.ordinal_operations[0]
+        else {
+            panic!("expected a reveal");
+        };
+        assert_eq!(reveal.parents, vec!["parenti0".to_string()]);
+        assert_eq!(reveal.satpoint_post_inscription, format!("{:064}:0:0", 1));
+        let OrdinalOperation::InscriptionTransferred(transfer) =
+            &block.transactions[4].metadata.ordinal_operations[0]
+        else {
+            panic!("expected a transfer");
+        };
+        assert_eq!(transfer.satpoint_pre_transfer, "aa:0:0".to_string());
+        assert_eq!(
+            transfer.destination,
+            OrdinalInscriptionTransferDestination::Transferred("bc1pa".to_string())
+        );
+        assert_eq!(operations.len(), 1);
+        assert_eq!(
+            operations.get(&format!("{:064}i0", 3)),
+            Some(&ParsedBrc20Operation::Deploy(ParsedBrc20TokenDeployData {
+                tick: "pepe".to_string(),
+                display_tick: "pepe".to_string(),
+                max: "21000000".to_string(),
+                lim: "1000".to_string(),
+                dec: "18".to_string(),
+                self_mint: false,
+            }))
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::{io::Write, str::FromStr};
+
+use chainhook_postgres::{
+    pg_begin_read_snapshot, pg_pool, pg_pool_client,
+    types::{PgNumericU128, PgNumericU64, PgSmallIntU8},
+};
+use chainhook_sdk::utils::Context;
+use futures::TryStreamExt;
+use tokio_postgres::types::ToSql;
+
+use crate::{
+    config::Config, core::meta_protocols::brc20::u128_amount_to_decimals_str, db::ordinals_pg,
+    try_info,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Brc20BalanceExportFormat {
+    /// `ticker,address,available_balance,transferrable_balance,overall_balance` lines, after a header line.
+    Csv,
+    /// A single `{"block_height": N, "balances": [...]}` document.
+    Json,
+}
+
+impl FromStr for Brc20BalanceExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Brc20BalanceExportFormat::Csv),
+            "json" => Ok(Brc20BalanceExportFormat::Json),
+            _ => Err(format!("unknown export format {s}, expected csv or json")),
+        }
+    }
+}
+
+/// Balance of a holder at the export height. Amounts are formatted with the token's decimals, like in the API.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Brc20BalanceSnapshotEntry {
+    pub ticker: String,
+    pub address: String,
+    pub available_balance: String,
+    pub transferrable_balance: String,
+    pub overall_balance: String,
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("unable to write export: {e}")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn write_balance_export_header<W: Write>(
+    format: Brc20BalanceExportFormat,
+    block_height: u64,
+    writer: &mut W,
+) -> Result<(), String> {
+    match format {
+        Brc20BalanceExportFormat::Csv => writeln!(
+            writer,
+            "ticker,address,available_balance,transferrable_balance,overall_balance"
+        ),
+        Brc20BalanceExportFormat::Json => {
+            write!(writer, "{{\"block_height\":{block_height},\"balances\":[")
+        }
+    }
+    .map_err(write_error)
+}
+
+/// Writes one balance, `index` being its position in the export.
+pub fn write_balance_export_entry<W: Write>(
+    format: Brc20BalanceExportFormat,
+    index: u64,
+    entry: &Brc20BalanceSnapshotEntry,
+    writer: &mut W,
+) -> Result<(), String> {
+    match format {
+        Brc20BalanceExportFormat::Csv => writeln!(
+            writer,
+            "{},{},{},{},{}",
+            csv_field(&entry.ticker),
+            csv_field(&entry.address),
+            entry.available_balance,
+            entry.transferrable_balance,
+            entry.overall_balance
+        )
+        .map_err(write_error),
+        Brc20BalanceExportFormat::Json => {
+            if index > 0 {
+                writer.write_all(b",").map_err(write_error)?;
+            }
+            serde_json::to_writer(&mut *writer, entry)
+                .map_err(|e| format!("unable to write export: {e}"))
+        }
+    }
+}
+
+pub fn write_balance_export_footer<W: Write>(
+    format: Brc20BalanceExportFormat,
+    writer: &mut W,
+) -> Result<(), String> {
+    match format {
+        Brc20BalanceExportFormat::Csv => Ok(()),
+        Brc20BalanceExportFormat::Json => writeln!(writer, "]}}").map_err(write_error),
+    }
+}
+
+/// Writes the BRC-20 balance of every holder as of the end of `block_height`, sorted by ticker and address. Holders
+/// whose overall balance is zero at that height are left out. Returns the number of balances written.
+pub async fn export_brc20_balances<W: Write>(
+    config: &Config,
+    block_height: u64,
+    format: Brc20BalanceExportFormat,
+    writer: &mut W,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let Some(brc20_db) = &config.brc20_db else {
+        return Err("BRC-20 indexing is not enabled in this config".to_string());
+    };
+    {
+        let ord_pool = pg_pool(&config.ordinals_db)?;
+        let ord_client = pg_pool_client(&ord_pool).await?;
+        let chain_tip = ordinals_pg::get_chain_tip_block_height(&ord_client).await?;
+        if !chain_tip.is_some_and(|chain_tip| block_height <= chain_tip) {
+            return Err(format!(
+                "block #{block_height} has not been indexed yet, chain tip is {chain_tip:?}"
+            ));
+        }
+    }
+    let brc20_pool = pg_pool(brc20_db)?;
+    let mut brc20_client = pg_pool_client(&brc20_pool).await?;
+    let brc20_tx = pg_begin_read_snapshot(&mut brc20_client).await?;
+    try_info!(ctx, "Exporting BRC-20 balances at block #{block_height}");
+    write_balance_export_header(format, block_height, writer)?;
+
+    let height = PgNumericU64(block_height);
+    let rows = brc20_tx
+        .query_raw(
+            "WITH balances_at_height AS (
+                SELECT DISTINCT ON (ticker, address) ticker, address, avail_balance, trans_balance, total_balance
+                FROM balances_history
+                WHERE block_height <= $1
+                ORDER BY ticker, address, block_height DESC
+            )
+            SELECT t.display_ticker, t.decimals, b.address, b.avail_balance, b.trans_balance, b.total_balance
+            FROM balances_at_height AS b
+            INNER JOIN tokens AS t ON t.ticker = b.ticker
+            WHERE b.total_balance > 0
+            ORDER BY b.ticker, b.address",
+            [&height as &dyn ToSql],
+        )
+        .await
+        .map_err(|e| format!("export_brc20_balances: {e}"))?;
+    futures::pin_mut!(rows);
+    let mut count = 0;
+    while let Some(row) = rows
+        .try_next()
+        .await
+        .map_err(|e| format!("export_brc20_balances: {e}"))?
+    {
+        let decimals: PgSmallIntU8 = row.get("decimals");
+        let avail_balance: PgNumericU128 = row.get("avail_balance");
+        let trans_balance: PgNumericU128 = row.get("trans_balance");
+        let total_balance: PgNumericU128 = row.get("total_balance");
+        let entry = Brc20BalanceSnapshotEntry {
+            ticker: row.get("display_ticker"),
+            address: row.get("address"),
+            available_balance: u128_amount_to_decimals_str(avail_balance.0, decimals.0),
+            transferrable_balance: u128_amount_to_decimals_str(trans_balance.0, decimals.0),
+            overall_balance: u128_amount_to_decimals_str(total_balance.0, decimals.0),
+        };
+        write_balance_export_entry(format, count, &entry, writer)?;
+        count += 1;
+    }
+    write_balance_export_footer(format, writer)?;
+    writer.flush().map_err(write_error)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        write_balance_export_entry, write_balance_export_footer, write_balance_export_header,
+        Brc20BalanceExportFormat, Brc20BalanceSnapshotEntry,
+    };
+
+    fn export(format: Brc20BalanceExportFormat) -> String {
+        let entries = [
+            Brc20BalanceSnapshotEntry {
+                ticker: "pepe".to_string(),
+                address: "bc1pa".to_string(),
+                available_balance: "1000.000000000000000000".to_string(),
+                transferrable_balance: "0.000000000000000000".to_string(),
+                overall_balance: "1000.000000000000000000".to_string(),
+            },
+            Brc20BalanceSnapshotEntry {
+                ticker: "a,\"b".to_string(),
+                address: "bc1pb".to_string(),
+                available_balance: "1".to_string(),
+                transferrable_balance: "2".to_string(),
+                overall_balance: "3".to_string(),
+            },
+        ];
+        let mut out = vec![];
+        write_balance_export_header(format, 840_000, &mut out).unwrap();
+        for (i, entry) in entries.iter().enumerate() {
+            write_balance_export_entry(format, i as u64, entry, &mut out).unwrap();
+        }
+        write_balance_export_footer(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_csv_export() {
+        assert_eq!(
+            export(Brc20BalanceExportFormat::Csv),
+            "ticker,address,available_balance,transferrable_balance,overall_balance\n\
+            pepe,bc1pa,1000.000000000000000000,0.000000000000000000,1000.000000000000000000\n\
+            \"a,\"\"b\",bc1pb,1,2,3\n"
+        );
+    }
+
+    #[test]
+    fn writes_json_export() {
+        let json: serde_json::Value =
+            serde_json::from_str(&export(Brc20BalanceExportFormat::Json)).unwrap();
+        assert_eq!(json["block_height"], 840_000);
+        assert_eq!(json["balances"].as_array().unwrap().len(), 2);
+        assert_eq!(json["balances"][1]["ticker"], "a,\"b");
+        assert_eq!(
+            json["balances"][0]["overall_balance"],
+            "1000.000000000000000000"
+        );
+    }
+}
@@ -0,0 +1,488 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::Write,
+};
+
+use chainhook_postgres::{
+    pg_begin_read_snapshot, pg_pool, pg_pool_client,
+    types::{PgNumericU128, PgSmallIntU8},
+};
+use chainhook_sdk::utils::Context;
+use deadpool_postgres::GenericClient;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    config::Config,
+    core::meta_protocols::brc20::{decimals_str_amount_to_u128, u128_amount_to_decimals_str},
+    db::ordinals_pg,
+    try_info, try_warn,
+};
+
+/// Largest page size accepted by the reference API.
+const REFERENCE_PAGE_SIZE: usize = 60;
+
+/// Token state compared between the local database and the reference indexer. Amounts are in the token's base unit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Brc20TokenState {
+    pub max: u128,
+    pub decimals: u8,
+    pub minted_supply: u128,
+}
+
+/// Difference found between the local database and the reference indexer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Brc20Mismatch {
+    /// Token known to the reference indexer only.
+    MissingToken { ticker: String },
+    /// Token known to the local database only.
+    UnexpectedToken { ticker: String },
+    TokenField {
+        ticker: String,
+        field: &'static str,
+        local: String,
+        reference: String,
+    },
+    /// Overall balance of an address, `None` when the address holds none of the token.
+    Balance {
+        ticker: String,
+        address: String,
+        local: Option<String>,
+        reference: Option<String>,
+    },
+}
+
+impl fmt::Display for Brc20Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Brc20Mismatch::MissingToken { ticker } => {
+                write!(f, "{ticker}: token missing from local database")
+            }
+            Brc20Mismatch::UnexpectedToken { ticker } => {
+                write!(f, "{ticker}: token missing from reference")
+            }
+            Brc20Mismatch::TokenField {
+                ticker,
+                field,
+                local,
+                reference,
+            } => write!(f, "{ticker}: {field} is {local}, reference has {reference}"),
+            Brc20Mismatch::Balance {
+                ticker,
+                address,
+                local,
+                reference,
+            } => write!(
+                f,
+                "{ticker} {address}: balance is {}, reference has {}",
+                local.as_deref().unwrap_or("none"),
+                reference.as_deref().unwrap_or("none")
+            ),
+        }
+    }
+}
+
+/// Compares the tokens of both indexers, keyed by lowercase ticker.
+pub fn diff_brc20_tokens(
+    local: &BTreeMap<String, Brc20TokenState>,
+    reference: &BTreeMap<String, Brc20TokenState>,
+) -> Vec<Brc20Mismatch> {
+    let mut mismatches = vec![];
+    for (ticker, reference_token) in reference.iter() {
+        let Some(local_token) = local.get(ticker) else {
+            mismatches.push(Brc20Mismatch::MissingToken {
+                ticker: ticker.clone(),
+            });
+            continue;
+        };
+        if local_token.decimals != reference_token.decimals {
+            mismatches.push(Brc20Mismatch::TokenField {
+                ticker: ticker.clone(),
+                field: "decimals",
+                local: local_token.decimals.to_string(),
+                reference: reference_token.decimals.to_string(),
+            });
+            // Amounts can't be compared when they are not expressed in the same unit.
+            continue;
+        }
+        for (field, local_amount, reference_amount) in [
+            ("max_supply", local_token.max, reference_token.max),
+            (
+                "minted_supply",
+                local_token.minted_supply,
+                reference_token.minted_supply,
+            ),
+        ] {
+            if local_amount != reference_amount {
+                mismatches.push(Brc20Mismatch::TokenField {
+                    ticker: ticker.clone(),
+                    field,
+                    local: u128_amount_to_decimals_str(local_amount, local_token.decimals),
+                    reference: u128_amount_to_decimals_str(
+                        reference_amount,
+                        reference_token.decimals,
+                    ),
+                });
+            }
+        }
+    }
+    for ticker in local.keys() {
+        if !reference.contains_key(ticker) {
+            mismatches.push(Brc20Mismatch::UnexpectedToken {
+                ticker: ticker.clone(),
+            });
+        }
+    }
+    mismatches
+}
+
+/// Compares the positive overall balances of a token, keyed by address.
+pub fn diff_brc20_balances(
+    ticker: &str,
+    decimals: u8,
+    local: &BTreeMap<String, u128>,
+    reference: &BTreeMap<String, u128>,
+) -> Vec<Brc20Mismatch> {
+    let mut mismatches = vec![];
+    let addresses: BTreeSet<&String> = local.keys().chain(reference.keys()).collect();
+    for address in addresses {
+        let local_balance = local.get(address).filter(|balance| **balance > 0);
+        let reference_balance = reference.get(address).filter(|balance| **balance > 0);
+        if local_balance != reference_balance {
+            mismatches.push(Brc20Mismatch::Balance {
+                ticker: ticker.to_string(),
+                address: address.clone(),
+                local: local_balance.map(|b| u128_amount_to_decimals_str(*b, decimals)),
+                reference: reference_balance.map(|b| u128_amount_to_decimals_str(*b, decimals)),
+            });
+        }
+    }
+    mismatches
+}
+
+#[derive(Debug, Deserialize)]
+struct ReferencePage<T> {
+    total: usize,
+    results: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReferenceStatus {
+    block_height: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReferenceToken {
+    ticker: String,
+    max_supply: String,
+    decimals: u8,
+    minted_supply: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReferenceHolder {
+    address: String,
+    overall_balance: String,
+}
+
+/// Client for an ordinals API serving BRC-20 state, such as `https://api.hiro.so/ordinals/v1`.
+struct ReferenceApi {
+    url: Url,
+    client: reqwest::Client,
+}
+
+impl ReferenceApi {
+    fn new(url: &str) -> Result<Self, String> {
+        let url = Url::parse(url.trim_end_matches('/'))
+            .map_err(|e| format!("invalid reference url {url}: {e}"))?;
+        if url.cannot_be_a_base() {
+            return Err(format!("invalid reference url {url}"));
+        }
+        Ok(ReferenceApi {
+            url,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn endpoint(&self, path: &[&str]) -> Url {
+        let mut url = self.url.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().extend(path);
+        }
+        url
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url) -> Result<T, String> {
+        let res = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|e| format!("unable to reach {url}: {e}"))?;
+        if !res.status().is_success() {
+            return Err(format!("{url} returned {}", res.status()));
+        }
+        res.json()
+            .await
+            .map_err(|e| format!("unable to parse response from {url}: {e}"))
+    }
+
+    async fn get_all<T: DeserializeOwned>(&self, path: &[&str]) -> Result<Vec<T>, String> {
+        let mut results = vec![];
+        loop {
+            let mut url = self.endpoint(path);
+            url.query_pairs_mut()
+                .append_pair("limit", &REFERENCE_PAGE_SIZE.to_string())
+                .append_pair("offset", &results.len().to_string());
+            let page: ReferencePage<T> = self.get(url).await?;
+            let page_len = page.results.len();
+            results.extend(page.results);
+            if page_len == 0 || results.len() >= page.total {
+                return Ok(results);
+            }
+        }
+    }
+
+    async fn get_block_height(&self) -> Result<Option<u64>, String> {
+        let status: ReferenceStatus = self.get(self.endpoint(&[])).await?;
+        Ok(status.block_height)
+    }
+
+    async fn get_tokens(&self) -> Result<BTreeMap<String, Brc20TokenState>, String> {
+        let mut tokens = BTreeMap::new();
+        for token in self
+            .get_all::<ReferenceToken>(&["brc-20", "tokens"])
+            .await?
+        {
+            let state = Brc20TokenState {
+                max: decimals_str_amount_to_u128(&token.max_supply, token.decimals)?,
+                decimals: token.decimals,
+                minted_supply: decimals_str_amount_to_u128(&token.minted_supply, token.decimals)?,
+            };
+            tokens.insert(token.ticker.to_lowercase(), state);
+        }
+        Ok(tokens)
+    }
+
+    async fn get_holders(
+        &self,
+        ticker: &str,
+        decimals: u8,
+    ) -> Result<BTreeMap<String, u128>, String> {
+        let mut holders = BTreeMap::new();
+        for holder in self
+            .get_all::<ReferenceHolder>(&["brc-20", "tokens", ticker, "holders"])
+            .await?
+        {
+            let balance = decimals_str_amount_to_u128(&holder.overall_balance, decimals)?;
+            holders.insert(holder.address, balance);
+        }
+        Ok(holders)
+    }
+}
+
+async fn get_local_tokens<T: GenericClient>(
+    client: &T,
+) -> Result<BTreeMap<String, Brc20TokenState>, String> {
+    let rows = client
+        .query(
+            "SELECT ticker, max, decimals, minted_supply FROM tokens",
+            &[],
+        )
+        .await
+        .map_err(|e| format!("get_local_tokens: {e}"))?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let max: PgNumericU128 = row.get("max");
+            let decimals: PgSmallIntU8 = row.get("decimals");
+            let minted_supply: PgNumericU128 = row.get("minted_supply");
+            (
+                row.get("ticker"),
+                Brc20TokenState {
+                    max: max.0,
+                    decimals: decimals.0,
+                    minted_supply: minted_supply.0,
+                },
+            )
+        })
+        .collect())
+}
+
+async fn get_local_holders<T: GenericClient>(
+    ticker: &String,
+    client: &T,
+) -> Result<BTreeMap<String, u128>, String> {
+    let rows = client
+        .query(
+            "SELECT address, total_balance FROM balances WHERE ticker = $1 AND total_balance > 0",
+            &[&ticker],
+        )
+        .await
+        .map_err(|e| format!("get_local_holders: {e}"))?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let balance: PgNumericU128 = row.get("total_balance");
+            (row.get("address"), balance.0)
+        })
+        .collect())
+}
+
+/// Compares the local BRC-20 tokens and holder balances against the reference API at `url`, writing one line per
+/// mismatch. Only the given tickers are compared when `tickers` is not empty. Both indexers should be at the same block
+/// height for the results to be meaningful. Returns the number of mismatches found.
+pub async fn verify_brc20_state<W: Write>(
+    config: &Config,
+    url: &str,
+    tickers: &[String],
+    writer: &mut W,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let Some(brc20_db) = &config.brc20_db else {
+        return Err("BRC-20 indexing is not enabled in this config".to_string());
+    };
+    let reference = ReferenceApi::new(url)?;
+    let local_height = {
+        let ord_pool = pg_pool(&config.ordinals_db)?;
+        let ord_client = pg_pool_client(&ord_pool).await?;
+        ordinals_pg::get_chain_tip_block_height(&ord_client).await?
+    };
+    let reference_height = reference.get_block_height().await?;
+    if local_height != reference_height {
+        try_warn!(
+            ctx,
+            "Local chain tip is {local_height:?} but reference is at {reference_height:?}, expect spurious mismatches"
+        );
+    }
+
+    let brc20_pool = pg_pool(brc20_db)?;
+    let mut brc20_client = pg_pool_client(&brc20_pool).await?;
+    let brc20_tx = pg_begin_read_snapshot(&mut brc20_client).await?;
+    let tickers: Vec<String> = tickers.iter().map(|t| t.to_lowercase()).collect();
+    let keep = |ticker: &String| tickers.is_empty() || tickers.contains(ticker);
+
+    let mut local_tokens = get_local_tokens(&brc20_tx).await?;
+    local_tokens.retain(|ticker, _| keep(ticker));
+    try_info!(ctx, "Fetching BRC-20 tokens from {url}");
+    let mut reference_tokens = reference.get_tokens().await?;
+    reference_tokens.retain(|ticker, _| keep(ticker));
+
+    let mut count = 0;
+    let mut report = |mismatch: &Brc20Mismatch| -> Result<(), String> {
+        count += 1;
+        writeln!(writer, "{mismatch}").map_err(|e| format!("unable to write report: {e}"))
+    };
+    for mismatch in diff_brc20_tokens(&local_tokens, &reference_tokens).iter() {
+        report(mismatch)?;
+    }
+    for (ticker, local_token) in local_tokens.iter() {
+        if reference_tokens
+            .get(ticker)
+            .map_or(true, |t| t.decimals != local_token.decimals)
+        {
+            continue;
+        }
+        try_info!(ctx, "Comparing {ticker} holders");
+        let local_holders = get_local_holders(ticker, &brc20_tx).await?;
+        let reference_holders = reference.get_holders(ticker, local_token.decimals).await?;
+        for mismatch in diff_brc20_balances(
+            ticker,
+            local_token.decimals,
+            &local_holders,
+            &reference_holders,
+        )
+        .iter()
+        {
+            report(mismatch)?;
+        }
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("unable to write report: {e}"))?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::{diff_brc20_balances, diff_brc20_tokens, Brc20Mismatch, Brc20TokenState};
+
+    fn token(max: u128, decimals: u8, minted_supply: u128) -> Brc20TokenState {
+        Brc20TokenState {
+            max,
+            decimals,
+            minted_supply,
+        }
+    }
+
+    #[test]
+    fn diffs_tokens() {
+        let local = BTreeMap::from([
+            ("ordi".to_string(), token(21_000_000, 0, 21_000_000)),
+            ("pepe".to_string(), token(1_000, 2, 500)),
+            ("sats".to_string(), token(100, 0, 100)),
+        ]);
+        let reference = BTreeMap::from([
+            ("ordi".to_string(), token(21_000_000, 0, 21_000_000)),
+            ("pepe".to_string(), token(1_000, 2, 400)),
+            ("rats".to_string(), token(100, 0, 100)),
+        ]);
+        let mismatches = diff_brc20_tokens(&local, &reference);
+        assert_eq!(
+            mismatches,
+            vec![
+                Brc20Mismatch::TokenField {
+                    ticker: "pepe".to_string(),
+                    field: "minted_supply",
+                    local: "5.00".to_string(),
+                    reference: "4.00".to_string(),
+                },
+                Brc20Mismatch::MissingToken {
+                    ticker: "rats".to_string()
+                },
+                Brc20Mismatch::UnexpectedToken {
+                    ticker: "sats".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            mismatches[0].to_string(),
+            "pepe: minted_supply is 5.00, reference has 4.00"
+        );
+    }
+
+    #[test]
+    fn diffs_balances_ignoring_empty_ones() {
+        let local = BTreeMap::from([
+            ("bc1pa".to_string(), 100),
+            ("bc1pb".to_string(), 50),
+            ("bc1pc".to_string(), 0),
+        ]);
+        let reference = BTreeMap::from([
+            ("bc1pa".to_string(), 100),
+            ("bc1pb".to_string(), 40),
+            ("bc1pd".to_string(), 10),
+        ]);
+        let mismatches = diff_brc20_balances("pepe", 1, &local, &reference);
+        assert_eq!(
+            mismatches,
+            vec![
+                Brc20Mismatch::Balance {
+                    ticker: "pepe".to_string(),
+                    address: "bc1pb".to_string(),
+                    local: Some("5.0".to_string()),
+                    reference: Some("4.0".to_string()),
+                },
+                Brc20Mismatch::Balance {
+                    ticker: "pepe".to_string(),
+                    address: "bc1pd".to_string(),
+                    local: None,
+                    reference: Some("1.0".to_string()),
+                },
+            ]
+        );
+    }
+}
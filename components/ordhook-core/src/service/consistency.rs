@@ -0,0 +1,189 @@
+use chainhook_postgres::{pg_pool, pg_pool_client};
+use chainhook_sdk::utils::Context;
+
+use crate::{
+    config::Config,
+    core::{first_inscription_height, meta_protocols::brc20::brc20_pg},
+    db::{
+        blocks::{
+            find_block_hash_at_block_height, find_last_block_inserted, open_readonly_blocks_db,
+        },
+        ordinals_pg,
+    },
+    try_info,
+};
+
+use super::Service;
+
+/// A block whose hash recorded in a Postgres database differs from the one stored in the blocks DB.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockHashDivergence {
+    pub block_height: u64,
+    /// Database the hash was read from, `ordinals` or `brc20`.
+    pub store: String,
+    pub store_block_hash: String,
+    pub blocks_db_block_hash: String,
+}
+
+/// Result of cross-checking the blocks DB against the ordinals and BRC-20 databases.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConsistencyReport {
+    pub from: u64,
+    pub to: u64,
+    pub blocks_db_block_height: u64,
+    pub ordinals_block_height: Option<u64>,
+    pub brc20_last_operation_block_height: Option<u64>,
+    /// Chain tips that cannot be explained by the order stores are written in, e.g. an ordinals index ahead of the
+    /// blocks DB.
+    pub height_divergences: Vec<String>,
+    pub hash_divergences: Vec<BlockHashDivergence>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.height_divergences.is_empty() && self.hash_divergences.is_empty()
+    }
+
+    /// Lowest block the indexes have to be rolled back to before they can be re-indexed.
+    pub fn first_divergent_block_height(&self) -> Option<u64> {
+        self.hash_divergences.iter().map(|d| d.block_height).min()
+    }
+}
+
+/// Compares the block hashes recorded in a database with the blocks DB. Blocks archived before hashes were recorded are
+/// not reported, there is nothing to compare them with.
+fn find_block_hash_divergences<F>(
+    store: &str,
+    store_block_hashes: &[(u64, String)],
+    blocks_db_block_hash: F,
+) -> Vec<BlockHashDivergence>
+where
+    F: Fn(u64) -> Option<String>,
+{
+    let mut divergences = vec![];
+    for (block_height, store_block_hash) in store_block_hashes.iter() {
+        let Some(blocks_db_block_hash) = blocks_db_block_hash(*block_height) else {
+            continue;
+        };
+        if blocks_db_block_hash != *store_block_hash {
+            divergences.push(BlockHashDivergence {
+                block_height: *block_height,
+                store: store.to_string(),
+                store_block_hash: store_block_hash.clone(),
+                blocks_db_block_hash,
+            });
+        }
+    }
+    divergences
+}
+
+/// Cross-checks the blocks DB against the chain tips and block hashes recorded in the ordinals database and, when
+/// enabled, the BRC-20 database. Hashes are compared for blocks `from..=to`, which default to the first inscription
+/// height and the ordinals chain tip.
+pub async fn verify_index_consistency(
+    config: &Config,
+    from: Option<u64>,
+    to: Option<u64>,
+    ctx: &Context,
+) -> Result<ConsistencyReport, String> {
+    let blocks_db = open_readonly_blocks_db(config, ctx)?;
+    let ord_pool = pg_pool(&config.ordinals_db)?;
+    let ord_client = pg_pool_client(&ord_pool).await?;
+
+    let mut report = ConsistencyReport {
+        blocks_db_block_height: find_last_block_inserted(&blocks_db) as u64,
+        ordinals_block_height: ordinals_pg::get_chain_tip_block_height(&ord_client).await?,
+        ..Default::default()
+    };
+    report.from = from.unwrap_or(first_inscription_height(config));
+    report.to = to.unwrap_or(report.ordinals_block_height.unwrap_or(0));
+
+    if let Some(ordinals_block_height) = report.ordinals_block_height {
+        if ordinals_block_height > report.blocks_db_block_height {
+            report.height_divergences.push(format!(
+                "ordinals db is at #{ordinals_block_height} but blocks db is at #{}",
+                report.blocks_db_block_height
+            ));
+        }
+    }
+    let blocks_db_block_hash =
+        |block_height: u64| find_block_hash_at_block_height(block_height as u32, &blocks_db);
+    let ordinals_block_hashes =
+        ordinals_pg::get_indexed_block_hashes(report.from, report.to, &ord_client).await?;
+    report.hash_divergences.extend(find_block_hash_divergences(
+        "ordinals",
+        &ordinals_block_hashes,
+        blocks_db_block_hash,
+    ));
+
+    if let (true, Some(brc20_db)) = (config.meta_protocols.brc20, &config.brc20_db) {
+        let brc20_pool = pg_pool(brc20_db)?;
+        let brc20_client = pg_pool_client(&brc20_pool).await?;
+        report.brc20_last_operation_block_height =
+            brc20_pg::get_highest_operation_block_height(&brc20_client).await?;
+        if let Some(brc20_block_height) = report.brc20_last_operation_block_height {
+            if brc20_block_height > report.ordinals_block_height.unwrap_or(0) {
+                report.height_divergences.push(format!(
+                    "brc20 db has operations at #{brc20_block_height} but ordinals db is at #{}",
+                    report.ordinals_block_height.unwrap_or(0)
+                ));
+            }
+        }
+        let brc20_block_hashes =
+            brc20_pg::get_operation_block_hashes(report.from, report.to, &brc20_client).await?;
+        report.hash_divergences.extend(find_block_hash_divergences(
+            "brc20",
+            &brc20_block_hashes,
+            blocks_db_block_hash,
+        ));
+    }
+    report
+        .hash_divergences
+        .sort_by_key(|divergence| divergence.block_height);
+    Ok(report)
+}
+
+/// Rolls the ordinals and BRC-20 indexes back to the block before `from` and indexes again up to bitcoind's chain tip,
+/// re-archiving those blocks in the blocks DB on the way.
+pub async fn reindex_from_block(config: &Config, from: u64, ctx: &Context) -> Result<(), String> {
+    let service = Service::new(config, ctx);
+    let chain_tip = service.get_index_chain_tip().await?;
+    if from <= chain_tip {
+        try_info!(
+            ctx,
+            "Rolling back blocks #{from} to #{chain_tip} before re-indexing them"
+        );
+        let block_heights: Vec<u64> = (from..=chain_tip).rev().collect();
+        service.rollback(&block_heights).await?;
+    }
+    service.catch_up_to_bitcoin_chain_tip().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{find_block_hash_divergences, BlockHashDivergence};
+
+    #[test]
+    fn finds_block_hash_divergences() {
+        let store_block_hashes = vec![
+            (840000, "aa".to_string()),
+            (840001, "bb".to_string()),
+            (840002, "cc".to_string()),
+        ];
+        let divergences =
+            find_block_hash_divergences("ordinals", &store_block_hashes, |height| match height {
+                840000 => Some("aa".to_string()),
+                840001 => Some("ff".to_string()),
+                _ => None,
+            });
+        assert_eq!(
+            divergences,
+            vec![BlockHashDivergence {
+                block_height: 840001,
+                store: "ordinals".to_string(),
+                store_block_hash: "bb".to_string(),
+                blocks_db_block_hash: "ff".to_string(),
+            }]
+        );
+    }
+}
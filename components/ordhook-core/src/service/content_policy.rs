@@ -0,0 +1,241 @@
+use std::{collections::HashSet, sync::Arc};
+
+use bitcoin::hashes::{sha256, Hash};
+use chainhook_sdk::utils::{hex, Context};
+use chainhook_types::{BitcoinBlockData, OrdinalOperation};
+use deadpool_postgres::Transaction;
+
+use crate::{
+    config::Config,
+    db::{models::DbFilteredInscription, ordinals_pg},
+    error::OrdhookError,
+    try_debug,
+};
+
+/// Content of a revealed inscription, as seen by content policies.
+pub struct InscriptionContent<'a> {
+    pub inscription_id: &'a str,
+    /// Lowercase MIME type, without parameters.
+    pub mime_type: &'a str,
+    pub content: &'a [u8],
+}
+
+/// Operator rule deciding which inscriptions must not have their content served. Filtered inscriptions keep every
+/// consensus field (numbers, locations, BRC-20 operations), they are only marked in `filtered_inscriptions` so their
+/// content is withheld by the API and left out of search results.
+pub trait ContentPolicy: Send + Sync {
+    /// Short name stored with every inscription the policy filters.
+    fn name(&self) -> &str;
+
+    /// Returns why the inscription is filtered, or `None` to let it through.
+    fn check(&self, inscription: &InscriptionContent) -> Option<String>;
+}
+
+/// Filters contents larger than a number of bytes.
+pub struct MaxContentSizePolicy(pub u64);
+
+impl ContentPolicy for MaxContentSizePolicy {
+    fn name(&self) -> &str {
+        "max_content_bytes"
+    }
+
+    fn check(&self, inscription: &InscriptionContent) -> Option<String> {
+        let length = inscription.content.len() as u64;
+        (length > self.0).then(|| format!("content is {length} bytes, limit is {}", self.0))
+    }
+}
+
+/// Filters MIME types, either exactly (`image/svg+xml`) or by top-level type (`video/*`).
+pub struct MimeTypeDenyListPolicy(pub HashSet<String>);
+
+impl ContentPolicy for MimeTypeDenyListPolicy {
+    fn name(&self) -> &str {
+        "denied_mime_types"
+    }
+
+    fn check(&self, inscription: &InscriptionContent) -> Option<String> {
+        let top_level = inscription.mime_type.split('/').next().unwrap_or("");
+        if self.0.contains(inscription.mime_type) || self.0.contains(&format!("{top_level}/*")) {
+            Some(format!("mime type {} is denied", inscription.mime_type))
+        } else {
+            None
+        }
+    }
+}
+
+/// Filters contents whose hex encoded SHA-256 is in a blocklist.
+pub struct ContentHashBlocklistPolicy(pub HashSet<String>);
+
+impl ContentPolicy for ContentHashBlocklistPolicy {
+    fn name(&self) -> &str {
+        "blocked_content_hashes"
+    }
+
+    fn check(&self, inscription: &InscriptionContent) -> Option<String> {
+        let hash = sha256::Hash::hash(inscription.content).to_string();
+        self.0
+            .contains(&hash)
+            .then(|| format!("content hash {hash} is blocked"))
+    }
+}
+
+/// Built-in policies set in `content_policy`, followed by the custom ones.
+pub fn configured_content_policies(config: &Config) -> Vec<Arc<dyn ContentPolicy>> {
+    let content_policy = &config.content_policy;
+    let mut policies: Vec<Arc<dyn ContentPolicy>> = vec![];
+    if let Some(max_bytes) = content_policy.max_content_bytes {
+        policies.push(Arc::new(MaxContentSizePolicy(max_bytes)));
+    }
+    if !content_policy.denied_mime_types.is_empty() {
+        policies.push(Arc::new(MimeTypeDenyListPolicy(
+            content_policy
+                .denied_mime_types
+                .iter()
+                .map(|mime_type| mime_type.to_lowercase())
+                .collect(),
+        )));
+    }
+    if !content_policy.blocked_content_hashes.is_empty() {
+        policies.push(Arc::new(ContentHashBlocklistPolicy(
+            content_policy
+                .blocked_content_hashes
+                .iter()
+                .map(|hash| hash.to_lowercase())
+                .collect(),
+        )));
+    }
+    policies.extend(content_policy.custom.iter().cloned());
+    policies
+}
+
+/// Runs `policies` against every inscription revealed in `block`. The first policy that flags an inscription wins.
+pub fn filter_block_inscriptions(
+    block: &BitcoinBlockData,
+    policies: &[Arc<dyn ContentPolicy>],
+) -> Vec<DbFilteredInscription> {
+    let mut filtered = vec![];
+    if policies.is_empty() {
+        return filtered;
+    }
+    for tx in block.transactions.iter() {
+        for operation in tx.metadata.ordinal_operations.iter() {
+            let OrdinalOperation::InscriptionRevealed(reveal) = operation else {
+                continue;
+            };
+            let content = reveal
+                .content_bytes
+                .get(2..)
+                .and_then(|bytes| hex::decode(bytes).ok())
+                .unwrap_or_default();
+            let mime_type = reveal
+                .content_type
+                .split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_lowercase();
+            let inscription = InscriptionContent {
+                inscription_id: &reveal.inscription_id,
+                mime_type: &mime_type,
+                content: &content,
+            };
+            for policy in policies.iter() {
+                if let Some(reason) = policy.check(&inscription) {
+                    filtered.push(DbFilteredInscription {
+                        inscription_id: reveal.inscription_id.clone(),
+                        policy: policy.name().to_string(),
+                        reason,
+                    });
+                    break;
+                }
+            }
+        }
+    }
+    filtered
+}
+
+/// Marks the inscriptions of `block` flagged by the configured content policies as filtered, in `ord_tx`.
+pub async fn apply_content_policies(
+    block: &BitcoinBlockData,
+    config: &Config,
+    ord_tx: &Transaction<'_>,
+    ctx: &Context,
+) -> Result<(), OrdhookError> {
+    let filtered = filter_block_inscriptions(block, &configured_content_policies(config));
+    if filtered.is_empty() {
+        return Ok(());
+    }
+    try_debug!(
+        ctx,
+        "Content policy: filtered {} inscriptions in block #{}",
+        filtered.len(),
+        block.block_identifier.index
+    );
+    Ok(ordinals_pg::insert_filtered_inscriptions(&filtered, ord_tx).await?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashSet, sync::Arc};
+
+    use chainhook_types::OrdinalOperation;
+
+    use crate::core::{
+        meta_protocols::brc20::test_utils::Brc20RevealBuilder,
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::{
+        filter_block_inscriptions, ContentHashBlocklistPolicy, ContentPolicy, MaxContentSizePolicy,
+        MimeTypeDenyListPolicy,
+    };
+
+    fn reveal(inscription_id: &str, content_type: &str, content_bytes: &str) -> OrdinalOperation {
+        let mut reveal = Brc20RevealBuilder::new()
+            .inscription_id(inscription_id)
+            .build();
+        reveal.content_type = content_type.to_string();
+        reveal.content_bytes = content_bytes.to_string();
+        OrdinalOperation::InscriptionRevealed(reveal)
+    }
+
+    #[test]
+    fn filters_with_the_first_matching_policy() {
+        let block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    // "hello"
+                    .add_ordinal_operation(reveal("a", "text/plain;charset=utf-8", "0x68656c6c6f"))
+                    .add_ordinal_operation(reveal("b", "Video/MP4", "0x00"))
+                    .add_ordinal_operation(reveal("c", "image/svg+xml", "0x0000000000"))
+                    .add_ordinal_operation(reveal("d", "image/png", "0x00"))
+                    .build(),
+            )
+            .build();
+        let policies: Vec<Arc<dyn ContentPolicy>> = vec![
+            Arc::new(ContentHashBlocklistPolicy(HashSet::from([
+                "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".to_string(),
+            ]))),
+            Arc::new(MimeTypeDenyListPolicy(HashSet::from([
+                "video/*".to_string(),
+                "image/svg+xml".to_string(),
+            ]))),
+            Arc::new(MaxContentSizePolicy(4)),
+        ];
+
+        let filtered = filter_block_inscriptions(&block, &policies);
+
+        assert_eq!(
+            filtered
+                .iter()
+                .map(|f| (f.inscription_id.as_str(), f.policy.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a", "blocked_content_hashes"),
+                ("b", "denied_mime_types"),
+                ("c", "denied_mime_types"),
+            ]
+        );
+        assert_eq!(filtered[1].reason, "mime type video/mp4 is denied");
+    }
+}
@@ -0,0 +1,408 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chainhook_postgres::{pg_begin, pg_pool_client};
+use chainhook_sdk::utils::Context;
+use deadpool_postgres::Pool;
+use reqwest::header::CONTENT_TYPE;
+
+use crate::{
+    config::{Config, DEFAULT_CONTENT_SCANNER_TIMEOUT},
+    db::{
+        models::{DbContentScan, DbFilteredInscription},
+        ordinals_pg,
+    },
+    try_debug, try_info, try_warn,
+};
+
+use super::content_policy::InscriptionContent;
+
+/// Inscriptions handed to each scanner per poll.
+const CONTENT_SCAN_BATCH_SIZE: i64 = 100;
+
+/// Pause between two polls once every scanner has caught up with the index.
+const CONTENT_SCAN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Verdict of a content scanner on an inscription.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ContentScanVerdict {
+    pub flagged: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Automated moderation of indexed inscription content, e.g. a malware or NSFW classifier. Scanners run in the
+/// background after blocks are indexed, each inscription is scanned once per scanner. Verdicts are stored in
+/// `content_scans`, and flagged inscriptions are filtered like the ones a `ContentPolicy` flags, so their content is no
+/// longer served.
+#[async_trait]
+pub trait ContentScanner: Send + Sync {
+    /// Short name used in logs, stored with its verdicts and as the policy of the inscriptions it flags.
+    fn name(&self) -> &str;
+
+    async fn scan(
+        &self,
+        inscription: &InscriptionContent<'_>,
+        ctx: &Context,
+    ) -> Result<ContentScanVerdict, String>;
+}
+
+/// Posts the raw content of each inscription to a URL, with its MIME type as `Content-Type` and its id in the
+/// `X-Inscription-Id` header, and expects `{"flagged": bool, "reason": "..."}` back.
+pub struct HttpContentScanner {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl HttpContentScanner {
+    /// Requests that get no answer within `timeout` count as a scanner error.
+    pub fn new(url: &str, timeout: Duration) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|e| format!("unable to build content scanner client: {e}"))?;
+        Ok(HttpContentScanner {
+            url: url.to_string(),
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl ContentScanner for HttpContentScanner {
+    fn name(&self) -> &str {
+        "scanner_url"
+    }
+
+    async fn scan(
+        &self,
+        inscription: &InscriptionContent<'_>,
+        _ctx: &Context,
+    ) -> Result<ContentScanVerdict, String> {
+        let res = self
+            .client
+            .post(&self.url)
+            .header(CONTENT_TYPE, inscription.mime_type)
+            .header("X-Inscription-Id", inscription.inscription_id)
+            .body(inscription.content.to_vec())
+            .send()
+            .await
+            .map_err(|e| format!("unable to reach content scanner: {e}"))?;
+        if !res.status().is_success() {
+            return Err(format!("content scanner returned {}", res.status()));
+        }
+        res.json()
+            .await
+            .map_err(|e| format!("invalid content scanner response: {e}"))
+    }
+}
+
+/// Scanner posting to `content_policy.scanner_url`, followed by the custom ones. Scanning is disabled on dry runs.
+pub fn configured_content_scanners(
+    config: &Config,
+) -> Result<Vec<Arc<dyn ContentScanner>>, String> {
+    let mut scanners: Vec<Arc<dyn ContentScanner>> = vec![];
+    if config.dry_run {
+        return Ok(scanners);
+    }
+    if let Some(url) = &config.content_policy.scanner_url {
+        let timeout = config
+            .content_policy
+            .scanner_timeout
+            .unwrap_or(DEFAULT_CONTENT_SCANNER_TIMEOUT);
+        scanners.push(Arc::new(HttpContentScanner::new(
+            url,
+            Duration::from_secs(timeout),
+        )?));
+    }
+    scanners.extend(config.content_policy.scanners.iter().cloned());
+    Ok(scanners)
+}
+
+/// Hands the next batch of inscriptions `scanner` has not seen to it and stores its verdicts. A scanner error is recorded
+/// as a failed attempt and the batch moves on, the inscription is retried once its backoff expires. Returns the number
+/// of inscriptions handed to the scanner.
+pub async fn scan_next_inscriptions(
+    scanner: &dyn ContentScanner,
+    from_block_height: u64,
+    ordinals_pool: &Pool,
+    ctx: &Context,
+) -> Result<usize, String> {
+    let mut ord_client = pg_pool_client(ordinals_pool).await?;
+    let inscriptions = ordinals_pg::get_unscanned_inscriptions(
+        scanner.name(),
+        from_block_height,
+        CONTENT_SCAN_BATCH_SIZE,
+        &ord_client,
+    )
+    .await?;
+    let mut scanned = 0;
+    for inscription in inscriptions.iter() {
+        scanned += 1;
+        let verdict = match scanner
+            .scan(
+                &InscriptionContent {
+                    inscription_id: &inscription.inscription_id,
+                    mime_type: &inscription.mime_type,
+                    content: &inscription.content,
+                },
+                ctx,
+            )
+            .await
+        {
+            Ok(verdict) => verdict,
+            Err(e) => {
+                let attempts = ordinals_pg::insert_content_scan_failure(
+                    &inscription.inscription_id,
+                    scanner.name(),
+                    &e,
+                    &ord_client,
+                )
+                .await?;
+                try_warn!(
+                    ctx,
+                    "Content scanner {}: unable to scan inscription {} (attempt {attempts}): {e}",
+                    scanner.name(),
+                    inscription.inscription_id
+                );
+                continue;
+            }
+        };
+        let ord_tx = pg_begin(&mut ord_client).await?;
+        ordinals_pg::insert_content_scan(
+            &DbContentScan {
+                inscription_id: inscription.inscription_id.clone(),
+                scanner: scanner.name().to_string(),
+                flagged: verdict.flagged,
+                reason: verdict.reason.clone(),
+            },
+            &ord_tx,
+        )
+        .await?;
+        ordinals_pg::delete_content_scan_failure(
+            &inscription.inscription_id,
+            scanner.name(),
+            &ord_tx,
+        )
+        .await?;
+        if verdict.flagged {
+            let reason = verdict
+                .reason
+                .unwrap_or_else(|| "flagged by content scanner".to_string());
+            try_debug!(
+                ctx,
+                "Content scanner {}: filtered inscription {} ({reason})",
+                scanner.name(),
+                inscription.inscription_id
+            );
+            ordinals_pg::insert_filtered_inscriptions(
+                &vec![DbFilteredInscription {
+                    inscription_id: inscription.inscription_id.clone(),
+                    policy: scanner.name().to_string(),
+                    reason,
+                }],
+                &ord_tx,
+            )
+            .await?;
+        }
+        ord_tx
+            .commit()
+            .await
+            .map_err(|e| format!("unable to commit content scan: {e}"))?;
+    }
+    Ok(scanned)
+}
+
+/// Continuously scans newly indexed inscriptions with every configured scanner.
+pub async fn start_content_scanning(
+    scanners: Vec<Arc<dyn ContentScanner>>,
+    from_block_height: u64,
+    ordinals_pool: Pool,
+    ctx: Context,
+) {
+    for scanner in scanners.iter() {
+        try_info!(
+            ctx,
+            "Content scanner {}: scanning inscriptions from block #{from_block_height}",
+            scanner.name()
+        );
+    }
+    loop {
+        let mut caught_up = true;
+        for scanner in scanners.iter() {
+            match scan_next_inscriptions(scanner.as_ref(), from_block_height, &ordinals_pool, &ctx)
+                .await
+            {
+                Ok(scanned) => caught_up &= scanned < CONTENT_SCAN_BATCH_SIZE as usize,
+                Err(e) => try_warn!(ctx, "Content scanner {}: {e}", scanner.name()),
+            }
+        }
+        if caught_up {
+            tokio::time::sleep(CONTENT_SCAN_POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use async_trait::async_trait;
+    use chainhook_postgres::{pg_begin, pg_pool_client};
+    use chainhook_sdk::utils::Context;
+
+    use crate::{
+        core::test_builders::{TestBlockBuilder, TestTransactionBuilder},
+        db::{ordinals_pg, pg_reset_db, pg_test_connection, pg_test_connection_pool},
+        service::content_policy::InscriptionContent,
+    };
+
+    use super::{scan_next_inscriptions, ContentScanVerdict, ContentScanner};
+
+    const INSCRIPTION_ID: &str =
+        "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0";
+
+    struct TextScanner;
+
+    #[async_trait]
+    impl ContentScanner for TextScanner {
+        fn name(&self) -> &str {
+            "text_scanner"
+        }
+
+        async fn scan(
+            &self,
+            inscription: &InscriptionContent<'_>,
+            _ctx: &Context,
+        ) -> Result<ContentScanVerdict, String> {
+            Ok(ContentScanVerdict {
+                flagged: inscription.mime_type == "text/plain",
+                reason: Some("text".to_string()),
+            })
+        }
+    }
+
+    struct FailingScanner;
+
+    #[async_trait]
+    impl ContentScanner for FailingScanner {
+        fn name(&self) -> &str {
+            "failing_scanner"
+        }
+
+        async fn scan(
+            &self,
+            _inscription: &InscriptionContent<'_>,
+            _ctx: &Context,
+        ) -> Result<ContentScanVerdict, String> {
+            Err("scanner unavailable".to_string())
+        }
+    }
+
+    #[test]
+    fn parses_verdict_without_reason() {
+        assert_eq!(
+            serde_json::from_str::<ContentScanVerdict>(r#"{"flagged":false}"#).unwrap(),
+            ContentScanVerdict {
+                flagged: false,
+                reason: None
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn filters_flagged_inscriptions_once() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        let pool = pg_test_connection_pool();
+        {
+            let mut ord_client = pg_pool_client(&pool).await?;
+            let client = pg_begin(&mut ord_client).await?;
+            let block = TestBlockBuilder::new()
+                .height(800000)
+                .add_transaction(TestTransactionBuilder::new_with_operation().build())
+                .build();
+            ordinals_pg::insert_block(&block, None, &client).await?;
+            client.commit().await.unwrap();
+        }
+
+        assert_eq!(
+            scan_next_inscriptions(&TextScanner, 0, &pool, &ctx).await?,
+            1
+        );
+        assert_eq!(
+            scan_next_inscriptions(&TextScanner, 0, &pool, &ctx).await?,
+            0
+        );
+        {
+            let ord_client = pg_pool_client(&pool).await?;
+            let scans = ordinals_pg::get_content_scans(INSCRIPTION_ID, &ord_client).await?;
+            assert_eq!(scans.len(), 1);
+            assert!(scans[0].flagged);
+            let filter = ordinals_pg::get_inscription_filter(INSCRIPTION_ID, &ord_client)
+                .await?
+                .unwrap();
+            assert_eq!(filter.policy, "text_scanner");
+            assert_eq!(filter.reason, "text");
+        }
+        pg_reset_db(&mut pg_client).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn backs_off_inscriptions_the_scanner_fails_on() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        let pool = pg_test_connection_pool();
+        {
+            let mut ord_client = pg_pool_client(&pool).await?;
+            let client = pg_begin(&mut ord_client).await?;
+            let block = TestBlockBuilder::new()
+                .height(800000)
+                .add_transaction(TestTransactionBuilder::new_with_operation().build())
+                .build();
+            ordinals_pg::insert_block(&block, None, &client).await?;
+            client.commit().await.unwrap();
+        }
+
+        // The failure is recorded and the inscription is not handed again until its backoff expires.
+        assert_eq!(
+            scan_next_inscriptions(&FailingScanner, 0, &pool, &ctx).await?,
+            1
+        );
+        assert_eq!(
+            scan_next_inscriptions(&FailingScanner, 0, &pool, &ctx).await?,
+            0
+        );
+        {
+            let ord_client = pg_pool_client(&pool).await?;
+            // Once the backoff expires, the inscription is handed again.
+            ord_client
+                .execute(
+                    "UPDATE content_scan_failures SET last_attempt_at = NOW() - INTERVAL '1 hour'",
+                    &[],
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            scan_next_inscriptions(&FailingScanner, 0, &pool, &ctx).await?,
+            1
+        );
+        {
+            let ord_client = pg_pool_client(&pool).await?;
+            assert_eq!(
+                ordinals_pg::insert_content_scan_failure(
+                    INSCRIPTION_ID,
+                    "failing_scanner",
+                    "scanner unavailable",
+                    &ord_client
+                )
+                .await?,
+                3
+            );
+        }
+        pg_reset_db(&mut pg_client).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use chainhook_postgres::{pg_connect, PgConnectionConfig};
+use chainhook_sdk::utils::Context;
+use tokio_postgres::Client;
+
+use crate::{config::Config, db::migrate_dbs, try_info, try_warn};
+
+use super::replay::{drop_schema, live_schema, prepare_scratch_schema, validate_schema_name};
+
+/// Schema holding the BRC-20 tables of an experiment. Ordinals and BRC-20 may share a database, so they can't share the
+/// experiment schema too.
+pub fn brc20_experiment_schema(schema: &str) -> String {
+    format!("{schema}_brc20")
+}
+
+/// Points the ordinals DB to `schema` and the BRC-20 DB to its paired schema, overriding the configured search paths.
+pub fn use_experiment_schema(config: &mut Config, schema: &str) -> Result<(), String> {
+    validate_schema_name(schema)?;
+    config.ordinals_db.search_path = Some(schema.to_string());
+    if let Some(brc20_db) = config.brc20_db.as_mut() {
+        brc20_db.search_path = Some(brc20_experiment_schema(schema));
+    }
+    Ok(())
+}
+
+fn experiment_brc20_db(config: &Config) -> Option<&PgConnectionConfig> {
+    match (&config.brc20_db, config.meta_protocols.brc20) {
+        (Some(brc20_db), true) => Some(brc20_db),
+        _ => None,
+    }
+}
+
+async fn schema_exists(client: &Client, schema: &str) -> Result<bool, String> {
+    let row = client
+        .query_opt("SELECT 1 FROM pg_namespace WHERE nspname = $1", &[&schema])
+        .await
+        .map_err(|e| format!("unable to look up schema {schema}: {e}"))?;
+    Ok(row.is_some())
+}
+
+async fn create_schema(
+    db: &PgConnectionConfig,
+    schema: &str,
+    copy_live: bool,
+    is_brc20: bool,
+) -> Result<(), String> {
+    let mut client = pg_connect(db).await?;
+    if schema_exists(&client, schema).await? {
+        return Err(format!("schema {schema} already exists, drop it first"));
+    }
+    if copy_live {
+        prepare_scratch_schema(&mut client, db, schema, is_brc20).await
+    } else {
+        client
+            .batch_execute(&format!("CREATE SCHEMA \"{schema}\""))
+            .await
+            .map_err(|e| format!("unable to create schema {schema}: {e}"))
+    }
+}
+
+/// Creates and migrates the schemas of an experiment index. With `copy_live` they start as a copy of the configured
+/// schemas so the experiment can resume from the current chain tip instead of indexing from scratch.
+pub async fn create_experiment_schemas(
+    config: &Config,
+    schema: &str,
+    copy_live: bool,
+    ctx: &Context,
+) -> Result<(), String> {
+    validate_schema_name(schema)?;
+    try_info!(ctx, "Creating ordinals schema {schema}");
+    create_schema(&config.ordinals_db, schema, copy_live, false).await?;
+    if let Some(brc20_db) = experiment_brc20_db(config) {
+        let brc20_schema = brc20_experiment_schema(schema);
+        try_info!(ctx, "Creating brc20 schema {brc20_schema}");
+        create_schema(brc20_db, &brc20_schema, copy_live, true).await?;
+    }
+    let mut experiment_config = config.clone();
+    use_experiment_schema(&mut experiment_config, schema)?;
+    Ok(migrate_dbs(&experiment_config, ctx).await?)
+}
+
+/// Drops the schemas of an experiment index. Refuses to drop the configured schemas.
+pub async fn drop_experiment_schemas(
+    config: &Config,
+    schema: &str,
+    ctx: &Context,
+) -> Result<(), String> {
+    validate_schema_name(schema)?;
+    if schema == live_schema(&config.ordinals_db) {
+        return Err(format!(
+            "schema {schema} is the configured ordinals schema, use `database reset` instead"
+        ));
+    }
+    try_warn!(ctx, "Dropping ordinals schema {schema}");
+    drop_schema(&pg_connect(&config.ordinals_db).await?, schema).await?;
+    if let Some(brc20_db) = experiment_brc20_db(config) {
+        let brc20_schema = brc20_experiment_schema(schema);
+        if brc20_schema == live_schema(brc20_db) {
+            return Err(format!(
+                "schema {brc20_schema} is the configured brc20 schema, use `database reset` instead"
+            ));
+        }
+        try_warn!(ctx, "Dropping brc20 schema {brc20_schema}");
+        drop_schema(&pg_connect(brc20_db).await?, &brc20_schema).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+
+    use super::use_experiment_schema;
+
+    #[test]
+    fn points_config_to_experiment_schemas() {
+        let mut config = Config::devnet_default();
+        config.brc20_db = Some(config.ordinals_db.clone());
+        use_experiment_schema(&mut config, "fix_1234").unwrap();
+        assert_eq!(config.ordinals_db.search_path, Some("fix_1234".to_string()));
+        assert_eq!(
+            config.brc20_db.unwrap().search_path,
+            Some("fix_1234_brc20".to_string())
+        );
+        assert!(use_experiment_schema(&mut Config::devnet_default(), "Fix;DROP").is_err());
+    }
+}
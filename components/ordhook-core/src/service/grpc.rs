@@ -0,0 +1,284 @@
+use std::pin::Pin;
+
+use chainhook_postgres::pg_pool_client;
+use chainhook_sdk::utils::Context;
+use chainhook_types::{OrdinalInscriptionTransferDestination, OrdinalOperation};
+use futures::Stream;
+use tokio::{net::UnixListener, sync::broadcast};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{
+    config::ListenAddress,
+    db::{
+        models::{DbInscription, DbLocation},
+        ordinals_pg,
+    },
+    try_info, try_warn,
+};
+
+use super::{
+    activity_stream::{ActivityStreamSender, OrdinalBlockActivity},
+    PgConnectionPools,
+};
+
+pub mod proto {
+    tonic::include_proto!("ordhook");
+}
+
+use proto::{
+    ordhook_server::{Ordhook, OrdhookServer},
+    ordinal_operation, BlockActivity, GetInscriptionRequest, GetTransfersForSatRequest,
+    GetTransfersForSatResponse, Inscription, InscriptionReveal, InscriptionTransfer,
+    StreamBlocksRequest, Transfer,
+};
+
+impl From<&OrdinalBlockActivity> for BlockActivity {
+    fn from(block: &OrdinalBlockActivity) -> Self {
+        BlockActivity {
+            block_height: block.block_identifier.index,
+            block_hash: block
+                .block_identifier
+                .hash
+                .trim_start_matches("0x")
+                .to_string(),
+            timestamp: block.timestamp,
+            timestamp_ms: block.timestamp as u64 * 1000,
+            operations: block
+                .operations
+                .iter()
+                .map(|(tx_id, operation)| proto::OrdinalOperation {
+                    tx_id: tx_id.trim_start_matches("0x").to_string(),
+                    operation: Some(match operation {
+                        OrdinalOperation::InscriptionRevealed(reveal) => {
+                            ordinal_operation::Operation::Reveal(InscriptionReveal {
+                                inscription_id: reveal.inscription_id.clone(),
+                                number: reveal.inscription_number.jubilee,
+                                ordinal_number: reveal.ordinal_number,
+                                address: reveal.inscriber_address.clone(),
+                                content_type: reveal.content_type.clone(),
+                                content_length: reveal.content_length as u64,
+                                fee: reveal.inscription_fee,
+                                satpoint: reveal.satpoint_post_inscription.clone(),
+                            })
+                        }
+                        OrdinalOperation::InscriptionTransferred(transfer) => {
+                            let (transfer_type, address) = match &transfer.destination {
+                                OrdinalInscriptionTransferDestination::Transferred(address) => {
+                                    ("transferred", Some(address.clone()))
+                                }
+                                OrdinalInscriptionTransferDestination::SpentInFees => {
+                                    ("spent_in_fees", None)
+                                }
+                                OrdinalInscriptionTransferDestination::Burnt(address) => {
+                                    ("burnt", Some(address.clone()))
+                                }
+                            };
+                            ordinal_operation::Operation::Transfer(InscriptionTransfer {
+                                ordinal_number: transfer.ordinal_number,
+                                transfer_type: transfer_type.to_string(),
+                                address,
+                                satpoint_pre_transfer: transfer.satpoint_pre_transfer.clone(),
+                                satpoint_post_transfer: transfer.satpoint_post_transfer.clone(),
+                                from_address: transfer.from_address.clone(),
+                            })
+                        }
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<DbInscription> for Inscription {
+    fn from(inscription: DbInscription) -> Self {
+        Inscription {
+            inscription_id: inscription.inscription_id,
+            number: inscription.number,
+            ordinal_number: inscription.ordinal_number.0,
+            block_height: inscription.block_height.0,
+            tx_id: inscription.tx_id,
+            address: inscription.address,
+            content_type: inscription.content_type,
+            content_length: inscription.content_length.0,
+            fee: inscription.fee.0,
+            timestamp: inscription.timestamp.0,
+            timestamp_ms: inscription.timestamp.0 as u64 * 1000,
+        }
+    }
+}
+
+impl From<DbLocation> for Transfer {
+    fn from(location: DbLocation) -> Self {
+        Transfer {
+            block_height: location.block_height.0,
+            tx_id: location.tx_id,
+            tx_index: location.tx_index.0,
+            address: location.address,
+            output: location.output,
+            offset: location.offset.map(|offset| offset.0),
+            transfer_type: location.transfer_type,
+            timestamp: location.timestamp.0,
+            from_address: location.prev_address,
+            timestamp_ms: location.timestamp.0 as u64 * 1000,
+        }
+    }
+}
+
+struct OrdhookGrpcService {
+    pg_pools: PgConnectionPools,
+    activity_stream: ActivityStreamSender,
+}
+
+#[tonic::async_trait]
+impl Ordhook for OrdhookGrpcService {
+    type StreamBlocksStream = Pin<Box<dyn Stream<Item = Result<BlockActivity, Status>> + Send>>;
+
+    async fn stream_blocks(
+        &self,
+        _request: Request<StreamBlocksRequest>,
+    ) -> Result<Response<Self::StreamBlocksStream>, Status> {
+        let receiver = self.activity_stream.subscribe();
+        // A lagging client gets a `DATA_LOSS` status rather than a stream with silent gaps.
+        let stream = futures::stream::unfold(Some(receiver), |receiver| async move {
+            let mut receiver = receiver?;
+            match receiver.recv().await {
+                Ok(block) => Some((Ok(BlockActivity::from(block.as_ref())), Some(receiver))),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => Some((
+                    Err(Status::data_loss(format!(
+                        "client lagging, {skipped} blocks skipped"
+                    ))),
+                    None,
+                )),
+                Err(broadcast::error::RecvError::Closed) => None,
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_inscription(
+        &self,
+        request: Request<GetInscriptionRequest>,
+    ) -> Result<Response<Inscription>, Status> {
+        let client = pg_pool_client(&self.pg_pools.ordinals)
+            .await
+            .map_err(Status::internal)?;
+        let inscription =
+            ordinals_pg::get_inscription_by_id(&request.get_ref().inscription_id, &client)
+                .await
+                .map_err(Status::internal)?
+                .ok_or(Status::not_found("inscription not found"))?;
+        Ok(Response::new(inscription.into()))
+    }
+
+    async fn get_transfers_for_sat(
+        &self,
+        request: Request<GetTransfersForSatRequest>,
+    ) -> Result<Response<GetTransfersForSatResponse>, Status> {
+        let client = pg_pool_client(&self.pg_pools.ordinals)
+            .await
+            .map_err(Status::internal)?;
+        let locations = ordinals_pg::get_locations_for_ordinal_number(
+            request.get_ref().ordinal_number,
+            &client,
+        )
+        .await
+        .map_err(Status::internal)?;
+        Ok(Response::new(GetTransfersForSatResponse {
+            transfers: locations.into_iter().map(Transfer::from).collect(),
+        }))
+    }
+}
+
+/// Serves the gRPC API on a TCP or unix domain socket until the server fails. Blocks are streamed as they get indexed at
+/// the chain tip. A stale socket file left by a previous run is removed before binding.
+pub async fn start_serving_grpc(
+    listen_address: ListenAddress,
+    pg_pools: PgConnectionPools,
+    activity_stream: ActivityStreamSender,
+    ctx: Context,
+) {
+    try_info!(ctx, "gRPC: listening on {listen_address}");
+    let service = OrdhookGrpcService {
+        pg_pools,
+        activity_stream,
+    };
+    let router = Server::builder().add_service(OrdhookServer::new(service));
+    let result = match &listen_address {
+        ListenAddress::Tcp(addr) => router.serve(*addr).await.map_err(|e| e.to_string()),
+        ListenAddress::Unix(path) => {
+            let _ = std::fs::remove_file(path);
+            match UnixListener::bind(path) {
+                Ok(listener) => {
+                    let incoming = futures::stream::unfold(listener, |listener| async move {
+                        let stream = listener.accept().await.map(|(stream, _)| stream);
+                        Some((stream, listener))
+                    });
+                    router
+                        .serve_with_incoming(incoming)
+                        .await
+                        .map_err(|e| e.to_string())
+                }
+                Err(e) => Err(format!("unable to bind {listen_address}: {e}")),
+            }
+        }
+    };
+    if let Err(e) = result {
+        try_warn!(ctx, "gRPC: server error: {e}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_types::{
+        BlockIdentifier, OrdinalInscriptionTransferData, OrdinalInscriptionTransferDestination,
+        OrdinalOperation,
+    };
+
+    use crate::{
+        core::meta_protocols::brc20::test_utils::Brc20RevealBuilder,
+        service::{activity_stream::OrdinalBlockActivity, block_events::CharmsFormat},
+    };
+
+    use super::proto::{ordinal_operation::Operation, BlockActivity};
+
+    #[test]
+    fn converts_block_activity() {
+        let block = OrdinalBlockActivity {
+            block_identifier: BlockIdentifier {
+                index: 840_000,
+                hash: "0xabcd".to_string(),
+            },
+            timestamp: 1713571767,
+            timestamp_ms: Some(1713571767000),
+            operations: vec![
+                (
+                    "0x01".to_string(),
+                    OrdinalOperation::InscriptionRevealed(Brc20RevealBuilder::new().build()),
+                ),
+                (
+                    "0x02".to_string(),
+                    OrdinalOperation::InscriptionTransferred(OrdinalInscriptionTransferData {
+                        ordinal_number: 700,
+                        destination: OrdinalInscriptionTransferDestination::SpentInFees,
+                        from_address: None,
+                        satpoint_pre_transfer: "".to_string(),
+                        satpoint_post_transfer: "".to_string(),
+                        post_transfer_output_value: None,
+                        tx_index: 1,
+                    }),
+                ),
+            ],
+            brc20_operations: vec![],
+            charms_format: CharmsFormat::Names,
+        };
+        let activity = BlockActivity::from(&block);
+        assert_eq!(activity.block_hash, "abcd");
+        assert_eq!(activity.operations.len(), 2);
+        assert_eq!(activity.operations[1].tx_id, "02");
+        let Some(Operation::Transfer(transfer)) = &activity.operations[1].operation else {
+            panic!("expected a transfer");
+        };
+        assert_eq!(transfer.transfer_type, "spent_in_fees");
+        assert_eq!(transfer.address, None);
+    }
+}
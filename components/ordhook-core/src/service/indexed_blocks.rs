@@ -0,0 +1,115 @@
+use chainhook_postgres::{pg_begin, pg_pool_client};
+use chainhook_sdk::{
+    indexer::bitcoin::{build_http_client, retrieve_block_hashes_with_retry},
+    utils::Context,
+};
+use deadpool_postgres::Pool;
+
+use crate::{config::Config, core::first_inscription_height, db::ordinals_pg, try_info};
+
+/// Heights whose hashes are requested from bitcoind in a single batch.
+const INDEXED_BLOCKS_BACKFILL_BATCH_SIZE: i64 = 500;
+
+/// Records the hash of every indexed block that has no `indexed_blocks` row, from the first inscription height up to
+/// the chain tip. Indexes upgraded from a version without `indexed_blocks` only have rows for the blocks that revealed
+/// inscriptions, so pinning a read to any other indexed block would be rejected until its hash is recorded. Returns the
+/// number of blocks recorded.
+pub async fn backfill_indexed_block_hashes(
+    config: &Config,
+    ordinals_pool: &Pool,
+    ctx: &Context,
+) -> Result<u64, String> {
+    let mut ord_client = pg_pool_client(ordinals_pool).await?;
+    let Some(chain_tip) = ordinals_pg::get_chain_tip_block_height(&ord_client).await? else {
+        return Ok(0);
+    };
+    let http_client = build_http_client();
+    let bitcoin_config = config.get_event_observer_config().get_bitcoin_config();
+    let mut from = first_inscription_height(config);
+    let mut recorded = 0;
+    loop {
+        let block_heights = ordinals_pg::get_unrecorded_indexed_block_heights(
+            from,
+            chain_tip,
+            INDEXED_BLOCKS_BACKFILL_BATCH_SIZE,
+            &ord_client,
+        )
+        .await?;
+        let Some(last_block_height) = block_heights.last().copied() else {
+            break;
+        };
+        let block_hashes =
+            retrieve_block_hashes_with_retry(&http_client, &block_heights, &bitcoin_config, ctx)
+                .await?;
+        let ord_tx = pg_begin(&mut ord_client).await?;
+        for (block_height, block_hash) in block_heights.iter().zip(block_hashes.iter()) {
+            ordinals_pg::insert_backfilled_indexed_block(*block_height, block_hash, &ord_tx)
+                .await?;
+        }
+        ord_tx
+            .commit()
+            .await
+            .map_err(|e| format!("unable to commit indexed block hashes: {e}"))?;
+        recorded += block_heights.len() as u64;
+        try_info!(
+            ctx,
+            "Indexed blocks: recorded hashes up to block #{last_block_height}"
+        );
+        from = last_block_height + 1;
+    }
+    Ok(recorded)
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_postgres::{pg_begin, pg_pool_client};
+
+    use crate::{
+        core::test_builders::{TestBlockBuilder, TestTransactionBuilder},
+        db::{ordinals_pg, pg_reset_db, pg_test_connection, pg_test_connection_pool},
+    };
+
+    #[tokio::test]
+    async fn finds_and_records_unrecorded_heights() -> Result<(), String> {
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        {
+            let mut ord_client = pg_pool_client(&pg_test_connection_pool()).await?;
+            let client = pg_begin(&mut ord_client).await?;
+            for block_height in [800000, 800003] {
+                let block = TestBlockBuilder::new()
+                    .height(block_height)
+                    .hash(format!("0x{block_height:064x}"))
+                    .add_transaction(TestTransactionBuilder::new().build())
+                    .build();
+                ordinals_pg::insert_block(&block, None, &client).await?;
+            }
+
+            assert_eq!(
+                ordinals_pg::get_unrecorded_indexed_block_heights(800000, 800003, 500, &client)
+                    .await?,
+                vec![800001, 800002]
+            );
+            assert_eq!(
+                ordinals_pg::get_unrecorded_indexed_block_heights(800000, 800003, 1, &client)
+                    .await?,
+                vec![800001]
+            );
+            let block_hash = format!("{:064x}", 800001);
+            ordinals_pg::insert_backfilled_indexed_block(800001, &block_hash, &client).await?;
+            // A hash recorded by the indexer is never overwritten.
+            ordinals_pg::insert_backfilled_indexed_block(800003, &"ff".repeat(32), &client).await?;
+            assert_eq!(
+                ordinals_pg::get_unrecorded_indexed_block_heights(800000, 800003, 500, &client)
+                    .await?,
+                vec![800002]
+            );
+            assert!(ordinals_pg::is_block_hash_indexed(&block_hash, &client).await?);
+            assert!(
+                ordinals_pg::is_block_hash_indexed(&format!("{:064x}", 800003), &client).await?
+            );
+        }
+        pg_reset_db(&mut pg_client).await?;
+        Ok(())
+    }
+}
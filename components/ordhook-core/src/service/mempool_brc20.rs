@@ -0,0 +1,472 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{atomic::AtomicBool, Arc, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bitcoin::{consensus::deserialize, Address, Transaction};
+use chainhook_postgres::pg_pool_client;
+use chainhook_sdk::{
+    indexer::bitcoin::build_http_client, observer::start_zeromq_raw_tx_listener, utils::Context,
+};
+use crossbeam_channel::RecvTimeoutError;
+use deadpool_postgres::{GenericClient, Pool};
+
+use crate::{
+    config::Config,
+    core::{
+        meta_protocols::brc20::{
+            brc20_activation_height, brc20_pg, decimals_str_amount_to_u128,
+            models::DbToken,
+            parser::{parse_brc20_operation, ParsedBrc20Operation},
+        },
+        protocol::{
+            inscription_parsing::parse_inscriptions_from_witness,
+            inscription_sequencing::get_bitcoin_network,
+        },
+    },
+    db::ordinals_pg,
+    try_debug, try_info, try_warn,
+};
+
+use super::{mempool_reveals::bitcoind_rpc, sinks::configured_event_sinks};
+
+/// How often pending operations whose transaction left the mempool are dropped.
+const MEMPOOL_PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// BRC-20 operation inscribed by a transaction that is still in the mempool.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PendingBrc20Operation {
+    pub inscription_id: String,
+    pub tx_id: String,
+    /// Address the inscription is sent to by the first output of its transaction, when it can be decoded.
+    pub address: Option<String>,
+    pub parents: Vec<String>,
+    /// Unix time at which the transaction was first seen.
+    pub first_seen: u64,
+    pub operation: ParsedBrc20Operation,
+    /// Always `true`: the transaction may never be mined, and the operation may be invalid once it is.
+    pub unconfirmed: bool,
+}
+
+/// Pending BRC-20 operations of the transactions currently in the mempool, by txid.
+#[derive(Default)]
+pub struct MempoolBrc20Operations {
+    operations: RwLock<HashMap<String, Vec<PendingBrc20Operation>>>,
+}
+
+impl MempoolBrc20Operations {
+    /// Pending operations in the order their transactions were first seen.
+    pub fn snapshot(&self) -> Vec<PendingBrc20Operation> {
+        let mut operations: Vec<PendingBrc20Operation> = self
+            .operations
+            .read()
+            .map(|operations| operations.values().flatten().cloned().collect())
+            .unwrap_or_default();
+        operations.sort_by(|a, b| {
+            (a.first_seen, &a.tx_id, &a.inscription_id).cmp(&(
+                b.first_seen,
+                &b.tx_id,
+                &b.inscription_id,
+            ))
+        });
+        operations
+    }
+
+    pub fn contains(&self, txid: &str) -> bool {
+        self.operations
+            .read()
+            .is_ok_and(|operations| operations.contains_key(txid))
+    }
+
+    fn insert(&self, txid: String, pending: Vec<PendingBrc20Operation>) {
+        if let Ok(mut operations) = self.operations.write() {
+            operations.insert(txid, pending);
+        }
+    }
+
+    /// Keeps the operations of the transactions for which `keep` returns `true`.
+    pub fn retain<F: Fn(&str) -> bool>(&self, keep: F) {
+        if let Ok(mut operations) = self.operations.write() {
+            operations.retain(|txid, _| keep(txid));
+        }
+    }
+}
+
+/// Parses the BRC-20 operations inscribed by a serialized transaction. Cursed inscriptions are left out since their
+/// eligibility depends on the number they get once mined.
+pub fn parse_pending_brc20_operations(
+    raw_tx: &[u8],
+    config: &Config,
+    first_seen: u64,
+) -> Option<(String, Vec<PendingBrc20Operation>)> {
+    let tx = deserialize::<Transaction>(raw_tx).ok()?;
+    let txid = tx.txid().to_string();
+    let address = tx.output.first().and_then(|output| {
+        Address::from_script(
+            &output.script_pubkey,
+            get_bitcoin_network(&config.network.bitcoin_network),
+        )
+        .ok()
+        .map(|address| address.to_string())
+    });
+    let mut pending = vec![];
+    for (input_index, input) in tx.input.iter().enumerate() {
+        let Some(inscriptions) =
+            parse_inscriptions_from_witness(input_index, input.witness.to_vec(), &txid)
+        else {
+            continue;
+        };
+        for (reveal, inscription) in inscriptions {
+            if reveal.curse_type.is_some() {
+                continue;
+            }
+            let Ok(Some(operation)) = parse_brc20_operation(&inscription) else {
+                continue;
+            };
+            pending.push(PendingBrc20Operation {
+                inscription_id: reveal.inscription_id,
+                tx_id: txid.clone(),
+                address: address.clone(),
+                parents: reveal.parents,
+                first_seen,
+                operation,
+                unconfirmed: true,
+            });
+        }
+    }
+    Some((txid, pending))
+}
+
+/// Checks a pending operation against the BRC-20 state at the chain tip: deploys must use a free ticker, mints must fit
+/// in the token's limit and remaining supply, and transfers must be covered by the `available_balance` of the sender.
+/// Other unconfirmed operations are not taken into account, so an operation passing these checks may still be invalid
+/// once mined.
+pub fn prevalidate_pending_brc20_operation(
+    pending: &PendingBrc20Operation,
+    token: Option<&DbToken>,
+    available_balance: Option<u128>,
+) -> bool {
+    match (&pending.operation, token) {
+        (ParsedBrc20Operation::Deploy(_), token) => token.is_none(),
+        (ParsedBrc20Operation::Mint(data), Some(token)) => {
+            if token.self_mint && !pending.parents.contains(&token.inscription_id) {
+                return false;
+            }
+            let Ok(amount) = decimals_str_amount_to_u128(&data.amt, token.decimals.0) else {
+                return false;
+            };
+            amount <= token.limit.0 && token.minted_supply.0 < token.max.0
+        }
+        (ParsedBrc20Operation::Transfer(data), Some(token)) => {
+            let Ok(amount) = decimals_str_amount_to_u128(&data.amt, token.decimals.0) else {
+                return false;
+            };
+            available_balance.is_some_and(|balance| amount <= balance)
+        }
+        (_, None) => false,
+    }
+}
+
+async fn prevalidate_pending_brc20_operations<T: GenericClient>(
+    pending: Vec<PendingBrc20Operation>,
+    client: &T,
+) -> Result<Vec<PendingBrc20Operation>, String> {
+    let mut valid = vec![];
+    for operation in pending.into_iter() {
+        let tick = match &operation.operation {
+            ParsedBrc20Operation::Deploy(data) => &data.tick,
+            ParsedBrc20Operation::Mint(data) | ParsedBrc20Operation::Transfer(data) => &data.tick,
+        };
+        let token = brc20_pg::get_token(tick, client).await?;
+        let available_balance = match (&operation.operation, &operation.address) {
+            (ParsedBrc20Operation::Transfer(_), Some(address)) => {
+                brc20_pg::get_token_available_balance_for_address(tick, address, client).await?
+            }
+            _ => None,
+        };
+        if prevalidate_pending_brc20_operation(&operation, token.as_ref(), available_balance) {
+            valid.push(operation);
+        }
+    }
+    Ok(valid)
+}
+
+async fn process_mempool_transaction(
+    raw_tx: &[u8],
+    config: &Config,
+    http_client: &reqwest::Client,
+    ordinals_pool: &Pool,
+    brc20_pool: &Pool,
+    mempool_brc20: &MempoolBrc20Operations,
+    ctx: &Context,
+) -> Result<(), String> {
+    let first_seen = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let Some((txid, pending)) = parse_pending_brc20_operations(raw_tx, config, first_seen) else {
+        return Ok(());
+    };
+    if pending.is_empty() || mempool_brc20.contains(&txid) {
+        return Ok(());
+    }
+    // bitcoind publishes transactions again when they are mined, once they have left its mempool.
+    if bitcoind_rpc::<serde_json::Value>(http_client, config, "getmempoolentry", json!([txid]), ctx)
+        .await
+        .is_err()
+    {
+        return Ok(());
+    }
+    // Operations are only pending once BRC-20 is active at the next block.
+    let chain_tip = {
+        let ord_client = pg_pool_client(ordinals_pool).await?;
+        ordinals_pg::get_chain_tip_block_height(&ord_client)
+            .await?
+            .unwrap_or(0)
+    };
+    if chain_tip + 1 < brc20_activation_height(&config.network.bitcoin_network) {
+        return Ok(());
+    }
+    let client = pg_pool_client(brc20_pool).await?;
+    let pending = prevalidate_pending_brc20_operations(pending, &client).await?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+    try_debug!(
+        ctx,
+        "Mempool: {} pending BRC-20 operations in transaction {txid}",
+        pending.len()
+    );
+    for sink in configured_event_sinks(config) {
+        if let Err(e) = sink.apply_pending_brc20_operations(&pending, ctx).await {
+            try_warn!(ctx, "Mempool: {} sink: {e}", sink.name());
+        }
+    }
+    mempool_brc20.insert(txid, pending);
+    Ok(())
+}
+
+/// Drops the operations of transactions that were mined or evicted, and the mined transactions published again by
+/// bitcoind.
+async fn prune_mempool_brc20_operations(
+    config: &Config,
+    http_client: &reqwest::Client,
+    mempool_brc20: &MempoolBrc20Operations,
+    ctx: &Context,
+) -> Result<(), String> {
+    let mempool: HashSet<String> =
+        bitcoind_rpc(http_client, config, "getrawmempool", json!([false]), ctx).await?;
+    mempool_brc20.retain(|txid| mempool.contains(txid));
+    Ok(())
+}
+
+/// Listens to bitcoind's `rawtx` ZMQ notifications on `bitcoind_zmq_url` and keeps `mempool_brc20` up to date with the
+/// BRC-20 operations of mempool transactions that pass `prevalidate_pending_brc20_operation`. New pending operations are
+/// also passed to the configured sinks. Returns if the ZMQ listener stops.
+pub fn start_watching_mempool_brc20(
+    config: Config,
+    bitcoind_zmq_url: String,
+    ordinals_pool: Pool,
+    brc20_pool: Pool,
+    mempool_brc20: Arc<MempoolBrc20Operations>,
+    ctx: Context,
+) {
+    let (raw_tx_tx, raw_tx_rx) = crossbeam_channel::unbounded();
+    let moved_ctx = ctx.clone();
+    let _ = hiro_system_kit::thread_named("Mempool BRC-20 listener").spawn(move || {
+        start_zeromq_raw_tx_listener(
+            &bitcoind_zmq_url,
+            raw_tx_tx,
+            Arc::new(AtomicBool::new(false)),
+            &moved_ctx,
+        );
+    });
+    try_info!(ctx, "Mempool: watching for pending BRC-20 operations");
+    let http_client = build_http_client();
+    hiro_system_kit::nestable_block_on(async move {
+        let mut last_prune = Instant::now();
+        loop {
+            match raw_tx_rx.recv_timeout(MEMPOOL_PRUNE_INTERVAL) {
+                Ok(raw_tx) => {
+                    if let Err(e) = process_mempool_transaction(
+                        &raw_tx,
+                        &config,
+                        &http_client,
+                        &ordinals_pool,
+                        &brc20_pool,
+                        &mempool_brc20,
+                        &ctx,
+                    )
+                    .await
+                    {
+                        try_warn!(ctx, "Mempool: unable to process transaction: {e}");
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if last_prune.elapsed() >= MEMPOOL_PRUNE_INTERVAL {
+                if let Err(e) =
+                    prune_mempool_brc20_operations(&config, &http_client, &mempool_brc20, &ctx)
+                        .await
+                {
+                    try_warn!(
+                        ctx,
+                        "Mempool: unable to prune pending BRC-20 operations: {e}"
+                    );
+                }
+                last_prune = Instant::now();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_postgres::types::{PgBigIntU32, PgNumericU128, PgNumericU64, PgSmallIntU8};
+
+    use crate::core::meta_protocols::brc20::{
+        models::DbToken,
+        parser::{ParsedBrc20BalanceData, ParsedBrc20Operation, ParsedBrc20TokenDeployData},
+    };
+
+    use super::{
+        prevalidate_pending_brc20_operation, MempoolBrc20Operations, PendingBrc20Operation,
+    };
+
+    fn pending(
+        tx_id: &str,
+        first_seen: u64,
+        operation: ParsedBrc20Operation,
+    ) -> PendingBrc20Operation {
+        PendingBrc20Operation {
+            inscription_id: format!("{tx_id}i0"),
+            tx_id: tx_id.to_string(),
+            address: Some("bc1pa".to_string()),
+            parents: vec![],
+            first_seen,
+            operation,
+            unconfirmed: true,
+        }
+    }
+
+    fn mint(amt: &str) -> ParsedBrc20Operation {
+        ParsedBrc20Operation::Mint(ParsedBrc20BalanceData {
+            tick: "pepe".to_string(),
+            amt: amt.to_string(),
+        })
+    }
+
+    fn token(minted_supply: u128) -> DbToken {
+        DbToken {
+            ticker: "pepe".to_string(),
+            display_ticker: "pepe".to_string(),
+            inscription_id: "aai0".to_string(),
+            inscription_number: 0,
+            block_height: PgNumericU64(840_000),
+            block_hash: "00".to_string(),
+            tx_id: "aa".to_string(),
+            tx_index: PgNumericU64(0),
+            address: "bc1pa".to_string(),
+            max: PgNumericU128(21_000_000),
+            limit: PgNumericU128(1_000),
+            decimals: PgSmallIntU8(0),
+            self_mint: false,
+            minted_supply: PgNumericU128(minted_supply),
+            tx_count: 0,
+            holders: 0,
+            timestamp: PgBigIntU32(0),
+        }
+    }
+
+    #[test]
+    fn prevalidates_operations_against_tip_state() {
+        let deploy = pending(
+            "aa",
+            0,
+            ParsedBrc20Operation::Deploy(ParsedBrc20TokenDeployData {
+                tick: "pepe".to_string(),
+                display_tick: "pepe".to_string(),
+                max: "21000000".to_string(),
+                lim: "1000".to_string(),
+                dec: "0".to_string(),
+                self_mint: false,
+            }),
+        );
+        assert!(prevalidate_pending_brc20_operation(&deploy, None, None));
+        assert!(!prevalidate_pending_brc20_operation(
+            &deploy,
+            Some(&token(0)),
+            None
+        ));
+
+        let valid_mint = pending("bb", 0, mint("1000"));
+        assert!(prevalidate_pending_brc20_operation(
+            &valid_mint,
+            Some(&token(0)),
+            None
+        ));
+        assert!(!prevalidate_pending_brc20_operation(
+            &valid_mint,
+            None,
+            None
+        ));
+        assert!(!prevalidate_pending_brc20_operation(
+            &valid_mint,
+            Some(&token(21_000_000)),
+            None
+        ));
+        let over_limit = pending("cc", 0, mint("1001"));
+        assert!(!prevalidate_pending_brc20_operation(
+            &over_limit,
+            Some(&token(0)),
+            None
+        ));
+
+        let transfer = pending(
+            "dd",
+            0,
+            ParsedBrc20Operation::Transfer(ParsedBrc20BalanceData {
+                tick: "pepe".to_string(),
+                amt: "50".to_string(),
+            }),
+        );
+        assert!(prevalidate_pending_brc20_operation(
+            &transfer,
+            Some(&token(0)),
+            Some(50)
+        ));
+        assert!(!prevalidate_pending_brc20_operation(
+            &transfer,
+            Some(&token(0)),
+            Some(49)
+        ));
+        assert!(!prevalidate_pending_brc20_operation(
+            &transfer,
+            Some(&token(0)),
+            None
+        ));
+    }
+
+    #[test]
+    fn keeps_pending_operations_in_mempool_order() {
+        let mempool_brc20 = MempoolBrc20Operations::default();
+        mempool_brc20.insert("bb".to_string(), vec![pending("bb", 20, mint("1"))]);
+        mempool_brc20.insert("aa".to_string(), vec![pending("aa", 10, mint("1"))]);
+        mempool_brc20.insert("cc".to_string(), vec![pending("cc", 30, mint("1"))]);
+        let tx_ids: Vec<String> = mempool_brc20
+            .snapshot()
+            .into_iter()
+            .map(|operation| operation.tx_id)
+            .collect();
+        assert_eq!(tx_ids, vec!["aa", "bb", "cc"]);
+
+        mempool_brc20.retain(|txid| txid != "bb");
+        assert!(!mempool_brc20.contains("bb"));
+        assert_eq!(mempool_brc20.snapshot().len(), 2);
+        let json = serde_json::to_value(&mempool_brc20.snapshot()[0]).unwrap();
+        assert_eq!(json["operation"]["op"], "mint");
+        assert_eq!(json["unconfirmed"], true);
+    }
+}
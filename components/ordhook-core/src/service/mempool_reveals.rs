@@ -0,0 +1,266 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use bitcoin::{consensus::deserialize, Transaction};
+use chainhook_postgres::pg_pool_client;
+use chainhook_sdk::{
+    bitcoincore_rpc,
+    indexer::bitcoin::{build_http_client, send_bitcoind_rpc_request},
+    utils::{hex, Context},
+};
+use chainhook_types::OrdinalInscriptionCurseType;
+use deadpool_postgres::Pool;
+use reqwest::Client as HttpClient;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    config::Config,
+    core::protocol::{
+        inscription_parsing::parse_inscriptions_from_witness,
+        inscription_sequencing::get_bitcoin_network, sequence_cursor::SequenceCursor,
+    },
+    db::ordinals_pg,
+    try_debug, try_warn,
+};
+
+const MEMPOOL_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Inscription revealed by a transaction that is still in the mempool.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PendingReveal {
+    pub inscription_id: String,
+    pub tx_id: String,
+    pub content_type: String,
+    pub curse_type: Option<OrdinalInscriptionCurseType>,
+    /// Unix time at which bitcoind first saw the transaction.
+    pub first_seen: u64,
+    /// Number the inscription would get if pending reveals were mined in the order they entered the mempool. Miners
+    /// order transactions by fee, so this is only an estimate.
+    pub projected_number: i64,
+    /// Always `true`: nothing about this inscription is final until its transaction is mined.
+    pub provisional: bool,
+}
+
+/// Latest pending reveals found in the mempool, shared with the API.
+#[derive(Default)]
+pub struct MempoolReveals {
+    reveals: RwLock<Vec<PendingReveal>>,
+}
+
+impl MempoolReveals {
+    pub fn snapshot(&self) -> Vec<PendingReveal> {
+        self.reveals
+            .read()
+            .map(|reveals| reveals.clone())
+            .unwrap_or_default()
+    }
+
+    fn replace(&self, reveals: Vec<PendingReveal>) {
+        if let Ok(mut current) = self.reveals.write() {
+            *current = reveals;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MempoolEntry {
+    time: u64,
+}
+
+pub(super) async fn bitcoind_rpc<T: DeserializeOwned>(
+    http_client: &HttpClient,
+    config: &Config,
+    method: &str,
+    params: serde_json::Value,
+    ctx: &Context,
+) -> Result<T, String> {
+    let body = json!({
+        "jsonrpc": "1.0",
+        "id": "ordhook",
+        "method": method,
+        "params": params
+    });
+    let endpoints = &config.network.bitcoind_rpc_endpoints;
+    send_bitcoind_rpc_request(
+        http_client,
+        endpoints.active_url(),
+        &config.network.bitcoind_rpc_credentials()?,
+        &body,
+        endpoints.rate_limiter(),
+        ctx,
+    )
+    .await
+    .map_err(|e| format!("{method}: {e}"))?
+    .json::<bitcoincore_rpc::jsonrpc::Response>()
+    .await
+    .map_err(|e| format!("unable to parse {method} response ({e})"))?
+    .result::<T>()
+    .map_err(|e| format!("unable to parse {method} response ({e})"))
+}
+
+/// Parses the inscriptions revealed by a hex encoded mempool transaction.
+pub fn parse_pending_reveals(txid: &str, raw_tx: &str, first_seen: u64) -> Vec<PendingReveal> {
+    let Ok(bytes) = hex::decode(raw_tx) else {
+        return vec![];
+    };
+    let Ok(tx) = deserialize::<Transaction>(&bytes) else {
+        return vec![];
+    };
+    let mut reveals = vec![];
+    for (input_index, input) in tx.input.iter().enumerate() {
+        let Some(inscriptions) =
+            parse_inscriptions_from_witness(input_index, input.witness.to_vec(), txid)
+        else {
+            continue;
+        };
+        for (reveal, _) in inscriptions {
+            reveals.push(PendingReveal {
+                inscription_id: reveal.inscription_id,
+                tx_id: txid.to_string(),
+                content_type: reveal.content_type,
+                curse_type: reveal.curse_type,
+                first_seen,
+                projected_number: 0,
+                provisional: true,
+            });
+        }
+    }
+    reveals
+}
+
+/// Orders reveals by the time their transaction entered the mempool and numbers them starting at `next_number`.
+/// Reveals of the same transaction keep their input order.
+pub fn project_inscription_numbers(reveals: &mut Vec<PendingReveal>, next_number: i64) {
+    reveals.sort_by(|a, b| (a.first_seen, &a.tx_id).cmp(&(b.first_seen, &b.tx_id)));
+    for (i, reveal) in reveals.iter_mut().enumerate() {
+        reveal.projected_number = next_number + i as i64;
+    }
+}
+
+async fn refresh_mempool_reveals(
+    config: &Config,
+    ordinals_pool: &Pool,
+    http_client: &HttpClient,
+    parsed_txs: &mut HashMap<String, Vec<PendingReveal>>,
+    mempool_reveals: &MempoolReveals,
+    ctx: &Context,
+) -> Result<(), String> {
+    let mempool: HashMap<String, MempoolEntry> =
+        bitcoind_rpc(http_client, config, "getrawmempool", json!([true]), ctx).await?;
+    parsed_txs.retain(|txid, _| mempool.contains_key(txid));
+    for (txid, entry) in mempool.iter() {
+        if parsed_txs.contains_key(txid) {
+            continue;
+        }
+        // The transaction may have been mined or evicted since the mempool was listed.
+        let Ok(raw_tx) = bitcoind_rpc::<String>(
+            http_client,
+            config,
+            "getrawtransaction",
+            json!([txid, false]),
+            ctx,
+        )
+        .await
+        else {
+            continue;
+        };
+        parsed_txs.insert(
+            txid.clone(),
+            parse_pending_reveals(txid, &raw_tx, entry.time),
+        );
+    }
+
+    let client = pg_pool_client(ordinals_pool).await?;
+    let chain_tip = ordinals_pg::get_chain_tip_block_height(&client)
+        .await?
+        .unwrap_or(0);
+    let next_number = SequenceCursor::new()
+        .pick_next(
+            false,
+            chain_tip + 1,
+            &get_bitcoin_network(&config.network.bitcoin_network),
+            &client,
+        )
+        .await?
+        .jubilee;
+    let mut reveals: Vec<PendingReveal> = parsed_txs.values().flatten().cloned().collect();
+    project_inscription_numbers(&mut reveals, next_number);
+    try_debug!(
+        ctx,
+        "Mempool: {} pending inscription reveals",
+        reveals.len()
+    );
+    mempool_reveals.replace(reveals);
+    Ok(())
+}
+
+/// Polls the bitcoind mempool for inscription reveals and keeps `mempool_reveals` up to date with their projected
+/// inscription numbers. Never returns.
+pub async fn start_watching_mempool_reveals(
+    config: Config,
+    ordinals_pool: Pool,
+    mempool_reveals: Arc<MempoolReveals>,
+    ctx: Context,
+) {
+    let http_client = build_http_client();
+    let mut parsed_txs = HashMap::new();
+    loop {
+        if let Err(e) = refresh_mempool_reveals(
+            &config,
+            &ordinals_pool,
+            &http_client,
+            &mut parsed_txs,
+            &mempool_reveals,
+            &ctx,
+        )
+        .await
+        {
+            try_warn!(ctx, "Mempool: unable to refresh pending reveals: {e}");
+        }
+        tokio::time::sleep(MEMPOOL_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{project_inscription_numbers, PendingReveal};
+
+    fn reveal(tx_id: &str, index: usize, first_seen: u64) -> PendingReveal {
+        PendingReveal {
+            inscription_id: format!("{tx_id}i{index}"),
+            tx_id: tx_id.to_string(),
+            content_type: "text/plain".to_string(),
+            curse_type: None,
+            first_seen,
+            projected_number: 0,
+            provisional: true,
+        }
+    }
+
+    #[test]
+    fn projects_numbers_in_mempool_order() {
+        let mut reveals = vec![
+            reveal("bb", 0, 20),
+            reveal("aa", 0, 10),
+            reveal("cc", 0, 20),
+            reveal("cc", 1, 20),
+        ];
+        project_inscription_numbers(&mut reveals, 70_000_000);
+        let projected: Vec<(String, i64)> = reveals
+            .into_iter()
+            .map(|r| (r.inscription_id, r.projected_number))
+            .collect();
+        assert_eq!(
+            projected,
+            vec![
+                ("aai0".to_string(), 70_000_000),
+                ("bbi0".to_string(), 70_000_001),
+                ("cci0".to_string(), 70_000_002),
+                ("cci1".to_string(), 70_000_003),
+            ]
+        );
+    }
+}
@@ -1,43 +1,106 @@
+pub mod activity_report;
+pub mod activity_stream;
+pub mod address_clusters;
+pub mod address_watch;
+pub mod admin;
+pub mod api;
+pub mod api_cache;
+pub mod block_events;
+pub mod brc20_backfill;
+pub mod brc20_export;
+pub mod brc20_verify;
+pub mod consistency;
+pub mod content_policy;
+pub mod content_scanner;
+pub mod experiment_schemas;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod indexed_blocks;
+pub mod mempool_brc20;
+pub mod mempool_reveals;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod observer_state;
+pub mod psbt_check;
+#[cfg(feature = "redis")]
+pub mod redis;
+pub mod replay;
+pub mod shadow;
+pub mod sinks;
+pub mod status;
+pub mod takedown;
+pub mod utxo_export;
+pub mod webhook;
+pub mod write_throttle;
+
 use crate::config::Config;
 use crate::core::meta_protocols::brc20::cache::{brc20_new_cache, Brc20MemoryCache};
 use crate::core::pipeline::bitcoind_download_blocks;
 use crate::core::pipeline::processors::block_archiving::start_block_archiving_processor;
 use crate::core::pipeline::processors::inscription_indexing::{
-    index_block, rollback_block, start_inscription_indexing_processor,
+    index_block, report_indexing_error, resume_from_ordinals_chain_tip, rollback_block,
+    start_inscription_indexing_processor,
 };
+use crate::core::pipeline::tip_lane::TipPriorityLane;
 use crate::core::protocol::sequence_cursor::SequenceCursor;
 use crate::core::{
     first_inscription_height, new_traversals_lazy_cache, should_sync_ordinals_db,
     should_sync_rocks_db,
 };
 use crate::db::blocks::{
-    self, find_missing_blocks, open_blocks_db_with_retry, run_compaction,
+    self, find_missing_blocks, find_stale_blocks, open_blocks_db_with_retry, run_compaction,
 };
 use crate::db::cursor::{BlockBytesCursor, TransactionBytesCursor};
-use crate::db::ordinals_pg;
+use crate::db::raw_transactions::{index_block_reveal_transactions, shared_raw_transactions_db_rw};
+use crate::db::{ordinals_pg, pg_commit_unless_dry_run};
+use crate::error::OrdhookError;
+use crate::service::activity_stream::{
+    new_activity_stream, publish_ordinal_activity, ActivityStreamSender,
+};
+use crate::service::address_watch::{notify_address_activity, start_address_watch_deliveries};
+use crate::service::admin::start_serving_admin_api;
+use crate::service::api::start_serving_api;
+use crate::service::content_scanner::{configured_content_scanners, start_content_scanning};
+#[cfg(feature = "grpc")]
+use crate::service::grpc::start_serving_grpc;
+use crate::service::indexed_blocks::backfill_indexed_block_hashes;
+use crate::service::mempool_brc20::{start_watching_mempool_brc20, MempoolBrc20Operations};
+use crate::service::observer_state::chain_event_cursor_store;
+use crate::service::shadow::start_shadow_comparisons;
+use crate::service::webhook::start_webhook_deliveries;
+use crate::service::write_throttle::WriteThrottle;
 use crate::utils::monitoring::{start_serving_prometheus_metrics, PrometheusMonitoring};
-use crate::{try_crit, try_error, try_info};
+use crate::utils::systemd::SystemdNotifier;
+use crate::{try_error, try_info, try_warn};
 use chainhook_postgres::{pg_begin, pg_pool, pg_pool_client};
+use chainhook_sdk::indexer::bitcoin::{build_http_client, retrieve_block_hashes_with_retry};
 use chainhook_sdk::observer::{
-    start_event_observer, BitcoinBlockDataCached, ObserverEvent, ObserverSidecar,
+    start_event_observer, BitcoinBlockDataCached, HandleBlock, ObserverEvent, ObserverSidecar,
 };
-use chainhook_sdk::utils::bitcoind::bitcoind_wait_for_chain_tip;
+use chainhook_sdk::utils::bitcoind::{bitcoind_probe_inline_prevouts, bitcoind_wait_for_chain_tip};
 use chainhook_sdk::utils::{BlockHeights, Context};
 use chainhook_types::BlockIdentifier;
 use crossbeam_channel::select;
 use dashmap::DashMap;
-use deadpool_postgres::Pool;
+use deadpool_postgres::{GenericClient, Pool};
 use fxhash::FxHasher;
+use rocksdb::DB;
 
 use std::collections::BTreeMap;
 use std::hash::BuildHasherDefault;
 use std::sync::mpsc::channel;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Number of blocks below the index chain tip whose stored hash is compared against bitcoind on integrity checks.
+const STALE_BLOCKS_CHECK_DEPTH: u64 = 100;
 
 #[derive(Debug, Clone)]
 pub struct PgConnectionPools {
     pub ordinals: Pool,
     pub brc20: Option<Pool>,
+    /// Paces block writes while a database backup runs.
+    pub write_throttle: Arc<WriteThrottle>,
 }
 
 pub struct Service {
@@ -45,13 +108,18 @@ pub struct Service {
     pub config: Config,
     pub ctx: Context,
     pub pg_pools: PgConnectionPools,
+    pub activity_stream: ActivityStreamSender,
 }
 
 impl Service {
     pub fn new(config: &Config, ctx: &Context) -> Self {
         Self {
             prometheus: PrometheusMonitoring::new(),
-            config: config.clone(),
+            // A shadow's blocks are already announced by the primary it is compared with.
+            config: match config.shadow {
+                Some(_) => config.without_outbound_side_effects(),
+                None => config.clone(),
+            },
             ctx: ctx.clone(),
             pg_pools: PgConnectionPools {
                 ordinals: pg_pool(&config.ordinals_db).unwrap(),
@@ -59,7 +127,9 @@ impl Service {
                     (true, Some(brc20_db)) => Some(pg_pool(&brc20_db).unwrap()),
                     _ => None,
                 },
+                write_throttle: Arc::new(WriteThrottle::default()),
             },
+            activity_stream: new_activity_stream(),
         }
     }
 
@@ -76,22 +146,164 @@ impl Service {
             .max(first_inscription_height(&self.config) - 1);
         ordinals_pg::update_chain_tip(db_height, &ord_tx).await?;
 
-        ord_tx
-            .commit()
-            .await
-            .map_err(|e| format!("unable to commit get_index_chain_tip transaction: {e}"))?;
+        pg_commit_unless_dry_run(ord_tx, &self.config, "ordinals").await?;
         Ok(db_height)
     }
 
     pub async fn run(&mut self, check_blocks_integrity: bool) -> Result<(), String> {
         // 1: Initialize Prometheus monitoring server.
-        if let Some(port) = self.config.network.prometheus_monitoring_port {
+        if let Some(listen_address) = &self.config.prometheus_listen_address {
+            let listen_address = listen_address.clone();
             let registry_moved = self.prometheus.registry.clone();
+            let cpu_profiling = self.config.cpu_profiling;
             let ctx_cloned = self.ctx.clone();
             let _ = std::thread::spawn(move || {
                 let _ = hiro_system_kit::nestable_block_on(start_serving_prometheus_metrics(
-                    port,
+                    listen_address,
                     registry_moved,
+                    cpu_profiling,
+                    ctx_cloned,
+                ));
+            });
+        }
+        let mempool_brc20 = match (
+            &self.config.meta_protocols.brc20_mempool_zmq_url,
+            &self.pg_pools.brc20,
+        ) {
+            (Some(bitcoind_zmq_url), Some(brc20_pool)) => {
+                let mempool_brc20 = Arc::new(MempoolBrc20Operations::default());
+                let config_moved = self.config.clone();
+                let bitcoind_zmq_url = bitcoind_zmq_url.clone();
+                let ordinals_pool = self.pg_pools.ordinals.clone();
+                let brc20_pool = brc20_pool.clone();
+                let mempool_brc20_moved = mempool_brc20.clone();
+                let ctx_cloned = self.ctx.clone();
+                let _ = std::thread::spawn(move || {
+                    start_watching_mempool_brc20(
+                        config_moved,
+                        bitcoind_zmq_url,
+                        ordinals_pool,
+                        brc20_pool,
+                        mempool_brc20_moved,
+                        ctx_cloned,
+                    );
+                });
+                Some(mempool_brc20)
+            }
+            _ => None,
+        };
+        if let Some(api) = &self.config.api {
+            let api = api.clone();
+            let config_moved = self.config.clone();
+            let pg_pools = self.pg_pools.clone();
+            let activity_stream = self.activity_stream.clone();
+            let ctx_cloned = self.ctx.clone();
+            let _ = std::thread::spawn(move || {
+                let _ = hiro_system_kit::nestable_block_on(start_serving_api(
+                    api,
+                    config_moved,
+                    pg_pools,
+                    activity_stream,
+                    mempool_brc20,
+                    ctx_cloned,
+                ));
+            });
+        }
+        if let Some(admin) = &self.config.admin {
+            let admin = admin.clone();
+            let write_throttle = self.pg_pools.write_throttle.clone();
+            let ctx_cloned = self.ctx.clone();
+            let _ = std::thread::spawn(move || {
+                let _ = hiro_system_kit::nestable_block_on(start_serving_admin_api(
+                    admin,
+                    write_throttle,
+                    ctx_cloned,
+                ));
+            });
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(grpc) = &self.config.grpc {
+            let listen_address = grpc.listen_address.clone();
+            let pg_pools = self.pg_pools.clone();
+            let activity_stream = self.activity_stream.clone();
+            let ctx_cloned = self.ctx.clone();
+            let _ = std::thread::spawn(move || {
+                let _ = hiro_system_kit::nestable_block_on(start_serving_grpc(
+                    listen_address,
+                    pg_pools,
+                    activity_stream,
+                    ctx_cloned,
+                ));
+            });
+        }
+        if let (Some(webhook), false) = (&self.config.webhook, self.config.dry_run) {
+            let webhook_moved = webhook.clone();
+            let pg_pools = self.pg_pools.clone();
+            let ctx_cloned = self.ctx.clone();
+            let _ = std::thread::spawn(move || {
+                let _ = hiro_system_kit::nestable_block_on(start_webhook_deliveries(
+                    webhook_moved,
+                    pg_pools,
+                    ctx_cloned,
+                ));
+            });
+        }
+        if let (Some(address_watch), false) = (&self.config.address_watch, self.config.dry_run) {
+            let address_watch_moved = address_watch.clone();
+            let ordinals_pool = self.pg_pools.ordinals.clone();
+            let ctx_cloned = self.ctx.clone();
+            let _ = std::thread::spawn(move || {
+                hiro_system_kit::nestable_block_on(start_address_watch_deliveries(
+                    address_watch_moved,
+                    ordinals_pool,
+                    ctx_cloned,
+                ));
+            });
+        }
+        if !self.config.dry_run {
+            let config_moved = self.config.clone();
+            let ordinals_pool = self.pg_pools.ordinals.clone();
+            let ctx_cloned = self.ctx.clone();
+            let _ =
+                std::thread::spawn(move || {
+                    if let Err(e) = hiro_system_kit::nestable_block_on(
+                        backfill_indexed_block_hashes(&config_moved, &ordinals_pool, &ctx_cloned),
+                    ) {
+                        try_warn!(
+                            ctx_cloned,
+                            "Indexed blocks: unable to record block hashes: {e}"
+                        );
+                    }
+                });
+        }
+        let content_scanners = configured_content_scanners(&self.config)?;
+        if !content_scanners.is_empty() {
+            let from_block_height = self
+                .config
+                .content_policy
+                .scan_from_block_height
+                .unwrap_or(0);
+            let ordinals_pool = self.pg_pools.ordinals.clone();
+            let ctx_cloned = self.ctx.clone();
+            let _ = std::thread::spawn(move || {
+                hiro_system_kit::nestable_block_on(start_content_scanning(
+                    content_scanners,
+                    from_block_height,
+                    ordinals_pool,
+                    ctx_cloned,
+                ));
+            });
+        }
+        if let Some(shadow) = &self.config.shadow {
+            let shadow_moved = shadow.clone();
+            let config_moved = self.config.clone();
+            let prometheus_moved = self.prometheus.clone();
+            let ctx_cloned = self.ctx.clone();
+            let _ = std::thread::spawn(move || {
+                hiro_system_kit::nestable_block_on(start_shadow_comparisons(
+                    shadow_moved,
+                    config_moved,
+                    prometheus_moved,
                     ctx_cloned,
                 ));
             });
@@ -111,7 +323,11 @@ impl Service {
         self.prometheus
             .initialize(0, max_inscription_number as u64, chain_tip);
 
-        // 2: Catch-up the ordinals index to Bitcoin chain tip.
+        // 2: Catch-up the ordinals index to Bitcoin chain tip. Systemd only sees the service as ready once it streams blocks.
+        let systemd = SystemdNotifier::from_env();
+        if let Err(e) = systemd.status("Catching up to the bitcoin chain tip") {
+            try_warn!(self.ctx, "Service: {e}");
+        }
         if check_blocks_integrity {
             self.check_blocks_db_integrity().await?;
         }
@@ -119,7 +335,7 @@ impl Service {
         try_info!(self.ctx, "Service: Streaming blocks start");
 
         // 3: Set up the real-time ZMQ Bitcoin block streaming channels and start listening.
-        let zmq_observer_sidecar = self.set_up_bitcoin_zmq_observer_sidecar()?;
+        let zmq_observer_sidecar = self.set_up_bitcoin_zmq_observer_sidecar(systemd.clone())?;
         let (observer_command_tx, observer_command_rx) = channel();
         let (observer_event_tx, observer_event_rx) = crossbeam_channel::unbounded();
         let inner_ctx = if self.config.logs.chainhook_internals {
@@ -128,7 +344,11 @@ impl Service {
             Context::empty()
         };
 
-        let event_observer_config = self.config.get_event_observer_config();
+        let mut event_observer_config = self.config.get_event_observer_config();
+        event_observer_config.chain_event_cursor_store = Some(chain_event_cursor_store(
+            &self.config,
+            &self.pg_pools.ordinals,
+        )?);
         let _ = start_event_observer(
             event_observer_config,
             observer_command_tx.clone(),
@@ -137,6 +357,12 @@ impl Service {
             Some(zmq_observer_sidecar),
             inner_ctx,
         );
+        if let Err(e) = systemd
+            .ready()
+            .and_then(|_| systemd.status("Streaming blocks"))
+        {
+            try_warn!(self.ctx, "Service: {e}");
+        }
 
         // 4: Block the main thread.
         loop {
@@ -148,8 +374,18 @@ impl Service {
                 }
             };
             match event {
+                ObserverEvent::BitcoinBlocksMissed { last_block_height } => {
+                    try_warn!(
+                        self.ctx,
+                        "Service: Bitcoin block notifications were missed after block {}, catching up with the next streamed block",
+                        last_block_height
+                            .map(|height| format!("#{height}"))
+                            .unwrap_or("unknown".to_string())
+                    );
+                }
                 ObserverEvent::Terminate => {
                     try_info!(&self.ctx, "Terminating runloop");
+                    let _ = systemd.stopping();
                     break;
                 }
                 _ => {}
@@ -166,7 +402,12 @@ impl Service {
         Ok(())
     }
 
-    fn set_up_bitcoin_zmq_observer_sidecar(&self) -> Result<ObserverSidecar, String> {
+    /// The sidecar runloop indexes every streamed block, so it is the one pinging the systemd watchdog: a block that hangs
+    /// while being indexed stops the pings and gets the service restarted.
+    fn set_up_bitcoin_zmq_observer_sidecar(
+        &self,
+        mut systemd: SystemdNotifier,
+    ) -> Result<ObserverSidecar, String> {
         let (block_mutator_in_tx, block_mutator_in_rx) = crossbeam_channel::unbounded();
         let (block_mutator_out_tx, block_mutator_out_rx) = crossbeam_channel::unbounded();
         let (chain_event_notifier_tx, chain_event_notifier_rx) = crossbeam_channel::unbounded();
@@ -177,10 +418,26 @@ impl Service {
         // TODO(rafaelcr): Move these outside so they can be used across blocks.
         let cache_l2 = Arc::new(new_traversals_lazy_cache(100_000));
         let mut brc20_cache = brc20_new_cache(&self.config);
+        let raw_transactions_db = if self.config.storage.raw_transactions_index {
+            Some(shared_raw_transactions_db_rw(&self.config, &self.ctx)?)
+        } else {
+            None
+        };
         let ctx = self.ctx.clone();
         let config = self.config.clone();
         let pg_pools = self.pg_pools.clone();
         let prometheus = self.prometheus.clone();
+        let activity_stream = self.activity_stream.clone();
+        let catch_up_service = Service {
+            prometheus: self.prometheus.clone(),
+            config: self.config.clone(),
+            ctx: self.ctx.clone(),
+            pg_pools: self.pg_pools.clone(),
+            activity_stream: self.activity_stream.clone(),
+        };
+        let watchdog_ping_interval = systemd
+            .watchdog_ping_interval()
+            .unwrap_or(Duration::from_secs(60));
 
         hiro_system_kit::thread_named("Observer Sidecar Runloop")
             .spawn(move || {
@@ -191,12 +448,25 @@ impl Service {
                             // data to DB.
                             recv(block_mutator_in_rx) -> msg => {
                                 if let Ok((mut blocks_to_mutate, blocks_ids_to_rollback)) = msg {
+                                    if let Err(e) = catch_up_service
+                                        .catch_up_missed_blocks(
+                                            &mut blocks_to_mutate,
+                                            &blocks_ids_to_rollback,
+                                            &mut brc20_cache,
+                                        )
+                                        .await
+                                    {
+                                        report_indexing_error(&OrdhookError::from(e), &prometheus, &ctx);
+                                        std::process::exit(1);
+                                    }
                                     match chainhook_sidecar_mutate_blocks(
                                         &mut blocks_to_mutate,
                                         &blocks_ids_to_rollback,
                                         &cache_l2,
                                         &mut brc20_cache,
                                         &prometheus,
+                                        &activity_stream,
+                                        raw_transactions_db.as_deref(),
                                         &config,
                                         &pg_pools,
                                         &ctx,
@@ -205,15 +475,27 @@ impl Service {
                                             let _ = block_mutator_out_tx.send(blocks_to_mutate);
                                         },
                                         Err(e) => {
-                                            try_crit!(ctx, "Error indexing streamed block: {e}");
+                                            report_indexing_error(&e, &prometheus, &ctx);
                                             std::process::exit(1);
                                         },
                                     };
                                 }
                             }
-                            recv(chain_event_notifier_rx) -> _msg => {
-                                // No action required.
+                            recv(chain_event_notifier_rx) -> msg => {
+                                // Replayed blocks were already indexed and announced before the restart, the sidecar
+                                // skipped them since they are in `indexed_blocks`.
+                                if let Ok(HandleBlock::ReplayBlock(block)) = msg {
+                                    try_info!(
+                                        ctx,
+                                        "Service: Block {} was already delivered before the restart, skipped",
+                                        block.block_identifier
+                                    );
+                                }
                             }
+                            default(watchdog_ping_interval) => {}
+                        }
+                        if let Err(e) = systemd.ping_watchdog_if_due() {
+                            try_warn!(ctx, "Service: {e}");
                         }
                     }
                 })
@@ -224,7 +506,8 @@ impl Service {
     }
 
     pub async fn check_blocks_db_integrity(&mut self) -> Result<(), String> {
-        bitcoind_wait_for_chain_tip(&self.config.network, &self.ctx);
+        bitcoind_wait_for_chain_tip(&self.config.network, &self.ctx).await;
+        bitcoind_probe_inline_prevouts(&self.config.network, &self.ctx).await;
         let (tip, missing_blocks) = {
             let blocks_db = open_blocks_db_with_retry(false, &self.config, &self.ctx);
             let ord_client = pg_pool_client(&self.pg_pools.ordinals).await?;
@@ -232,7 +515,35 @@ impl Service {
             let tip = ordinals_pg::get_chain_tip_block_height(&ord_client)
                 .await?
                 .unwrap_or(0);
-            let missing_blocks = find_missing_blocks(&blocks_db, 0, tip as u32, &self.ctx);
+            let mut missing_blocks = find_missing_blocks(&blocks_db, 0, tip as u32, &self.ctx);
+
+            // Blocks near the tip may have been stored from a fork that was later re-orged out.
+            let http_client = build_http_client();
+            let bitcoin_config = self.config.get_event_observer_config().get_bitcoin_config();
+            let block_heights: Vec<u64> =
+                (tip.saturating_sub(STALE_BLOCKS_CHECK_DEPTH)..=tip).collect();
+            let block_hashes = retrieve_block_hashes_with_retry(
+                &http_client,
+                &block_heights,
+                &bitcoin_config,
+                &self.ctx,
+            )
+            .await?;
+            let canonical_block_hashes: Vec<(u32, String)> = block_heights
+                .into_iter()
+                .map(|block_height| block_height as u32)
+                .zip(block_hashes)
+                .collect();
+            for block_height in find_stale_blocks(&blocks_db, &canonical_block_hashes) {
+                try_warn!(
+                    self.ctx,
+                    "Block #{block_height} in blocks DB belongs to a stale fork, will re-fetch"
+                );
+                if !missing_blocks.contains(&block_height) {
+                    missing_blocks.push(block_height);
+                }
+            }
+            missing_blocks.sort();
 
             (tip, missing_blocks)
         };
@@ -250,6 +561,7 @@ impl Service {
                 tip.into(),
                 &block_ingestion_processor,
                 10_000,
+                None,
                 &self.ctx,
             )
             .await?;
@@ -260,10 +572,58 @@ impl Service {
         Ok(())
     }
 
+    /// Runs [Service::catch_up_to_bitcoin_chain_tip] when streamed blocks don't build on the indexed chain tip, which
+    /// happens when the observer missed block notifications. Streamed blocks indexed by the catch-up are then flagged as
+    /// processed so the sidecar skips them.
+    ///
+    /// The catch-up indexes BRC-20 operations with its own cache, so the sidecar's `brc20_cache` is rebuilt afterwards.
+    /// Otherwise the next streamed block would be validated against the supplies and balances it held before the missed
+    /// blocks.
+    ///
+    /// Does nothing in dry-run mode: indexed blocks are never committed there, so the chain tip read from Postgres stays
+    /// put and every streamed block would look like it missed the ones before it.
+    pub async fn catch_up_missed_blocks(
+        &self,
+        blocks_to_mutate: &mut Vec<BitcoinBlockDataCached>,
+        block_ids_to_rollback: &Vec<BlockIdentifier>,
+        brc20_cache: &mut Option<Brc20MemoryCache>,
+    ) -> Result<(), String> {
+        if self.config.dry_run || !block_ids_to_rollback.is_empty() {
+            return Ok(());
+        }
+        let Some(first_block_height) = blocks_to_mutate
+            .iter()
+            .filter(|cached| !cached.processed_by_sidecar)
+            .map(|cached| cached.block.block_identifier.index)
+            .min()
+        else {
+            return Ok(());
+        };
+        let chain_tip = {
+            let ord_client = pg_pool_client(&self.pg_pools.ordinals).await?;
+            ordinals_pg::get_chain_tip_block_height(&ord_client)
+                .await?
+                .unwrap_or(0)
+        };
+        if first_block_height <= chain_tip + 1 {
+            return Ok(());
+        }
+        try_warn!(
+            self.ctx,
+            "Service: Streamed block #{first_block_height} does not build on indexed block #{chain_tip}, catching up to the bitcoin chain tip"
+        );
+        self.catch_up_to_bitcoin_chain_tip().await?;
+        *brc20_cache = brc20_new_cache(&self.config);
+        let ord_client = pg_pool_client(&self.pg_pools.ordinals).await?;
+        mark_indexed_blocks_as_processed(blocks_to_mutate, &ord_client).await?;
+        Ok(())
+    }
+
     /// Synchronizes and indexes all databases until their block height matches bitcoind's block height.
     pub async fn catch_up_to_bitcoin_chain_tip(&self) -> Result<(), String> {
-        // 0: Make sure bitcoind is synchronized.
-        bitcoind_wait_for_chain_tip(&self.config.network, &self.ctx);
+        // 0: Make sure bitcoind is synchronized, and check whether it returns prevouts with blocks.
+        bitcoind_wait_for_chain_tip(&self.config.network, &self.ctx).await;
+        bitcoind_probe_inline_prevouts(&self.config.network, &self.ctx).await;
 
         // 1: Catch up blocks DB so it is at least at the same height as the ordinals DB.
         if let Some((start_block, end_block)) =
@@ -284,13 +644,25 @@ impl Service {
                 first_inscription_height(&self.config),
                 &blocks_post_processor,
                 10_000,
+                None,
                 &self.ctx,
             )
             .await?;
         }
 
         // 2: Catch up ordinals DB until it reaches bitcoind block height. This will also advance blocks DB and BRC-20 DB if
-        // enabled.
+        // enabled. Blocks mined in the meantime are indexed provisionally by the tip lane so they don't wait behind
+        // history.
+        let tip_lane = if self.config.resources.tip_priority_lane {
+            Some(TipPriorityLane::start(
+                &self.config,
+                &self.pg_pools,
+                &self.ctx,
+            ))
+        } else {
+            None
+        };
+        resume_from_ordinals_chain_tip(&self.config, &self.pg_pools, &self.ctx).await?;
         let mut last_block_processed = 0;
         while let Some((start_block, end_block, speed)) =
             should_sync_ordinals_db(&self.config, &self.pg_pools, &self.ctx).await?
@@ -317,27 +689,52 @@ impl Service {
                 first_inscription_height(&self.config),
                 &blocks_post_processor,
                 speed,
+                tip_lane.as_ref(),
                 &self.ctx,
             )
             .await?;
             last_block_processed = end_block;
         }
+        if let Some(tip_lane) = tip_lane {
+            tip_lane.terminate(&self.ctx);
+        }
 
         try_info!(self.ctx, "Index has reached bitcoin chain tip");
         Ok(())
     }
 }
 
+/// Flags the blocks that are already part of the indexed chain as processed, so the sidecar doesn't index them again.
+async fn mark_indexed_blocks_as_processed<T: GenericClient>(
+    blocks: &mut Vec<BitcoinBlockDataCached>,
+    client: &T,
+) -> Result<(), String> {
+    for cached_block in blocks.iter_mut() {
+        if !cached_block.processed_by_sidecar
+            && ordinals_pg::is_block_hash_indexed(
+                cached_block.block.block_identifier.get_hash_bytes_str(),
+                client,
+            )
+            .await?
+        {
+            cached_block.processed_by_sidecar = true;
+        }
+    }
+    Ok(())
+}
+
 pub async fn chainhook_sidecar_mutate_blocks(
     blocks_to_mutate: &mut Vec<BitcoinBlockDataCached>,
     block_ids_to_rollback: &Vec<BlockIdentifier>,
     cache_l2: &Arc<DashMap<(u32, [u8; 8]), TransactionBytesCursor, BuildHasherDefault<FxHasher>>>,
     brc20_cache: &mut Option<Brc20MemoryCache>,
     prometheus: &PrometheusMonitoring,
+    activity_stream: &ActivityStreamSender,
+    raw_transactions_db: Option<&DB>,
     config: &Config,
     pg_pools: &PgConnectionPools,
     ctx: &Context,
-) -> Result<(), String> {
+) -> Result<(), OrdhookError> {
     if block_ids_to_rollback.len() > 0 {
         let blocks_db_rw = open_blocks_db_with_retry(true, &config, ctx);
         for block_id in block_ids_to_rollback.iter() {
@@ -354,6 +751,13 @@ pub async fn chainhook_sidecar_mutate_blocks(
             .map_err(|e| format!("error dropping rollback blocks from rocksdb: {e}"))?;
     }
 
+    // After a restart the observer resumes from the last block it delivered, so it may stream blocks that catch-up
+    // already indexed.
+    {
+        let ord_client = pg_pool_client(&pg_pools.ordinals).await?;
+        mark_indexed_blocks_as_processed(blocks_to_mutate, &ord_client).await?;
+    }
+
     for cached_block in blocks_to_mutate.iter_mut() {
         if cached_block.processed_by_sidecar {
             continue;
@@ -364,13 +768,15 @@ pub async fn chainhook_sidecar_mutate_blocks(
                 return Err(format!(
                     "Unable to compress block #{}: #{e}",
                     cached_block.block.block_identifier.index
-                ));
+                )
+                .into());
             }
         };
         {
             let blocks_db_rw = open_blocks_db_with_retry(true, &config, ctx);
             blocks::insert_entry_in_blocks(
                 cached_block.block.block_identifier.index as u32,
+                &cached_block.block.block_identifier.hash,
                 &block_bytes,
                 true,
                 &blocks_db_rw,
@@ -395,7 +801,170 @@ pub async fn chainhook_sidecar_mutate_blocks(
             &ctx,
         )
         .await?;
+        if let Some(raw_transactions_db) = raw_transactions_db {
+            if let Err(e) =
+                index_block_reveal_transactions(&cached_block.block, raw_transactions_db)
+            {
+                try_warn!(
+                    ctx,
+                    "Unable to index reveal transactions of block #{}: {e}",
+                    cached_block.block.block_identifier.index
+                );
+            }
+        }
+        if let (Some(address_watch), false) = (&config.address_watch, config.dry_run) {
+            notify_address_activity(&cached_block.block, address_watch, &pg_pools.ordinals, ctx)
+                .await;
+        }
+        if !config.dry_run {
+            publish_ordinal_activity(
+                &cached_block.block,
+                config.sinks.payload_format(),
+                activity_stream,
+            );
+        }
         cached_block.processed_by_sidecar = true;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use chainhook_postgres::{pg_connect, PgConnectionConfig};
+    use chainhook_sdk::{
+        observer::{BitcoinBlockDataCached, BlockIndexedCounts},
+        utils::Context,
+    };
+
+    use crate::{
+        config::Config,
+        core::{
+            block_fixtures::index_committed_fixture, meta_protocols::brc20::brc20_pg,
+            test_builders::TestBlockBuilder,
+        },
+        db::{drop_all_dbs, ordinals_pg, pg_reset_db, pg_test_config, pg_test_connection},
+    };
+
+    use super::{mark_indexed_blocks_as_processed, resume_from_ordinals_chain_tip, Service};
+
+    #[tokio::test]
+    async fn dry_run_never_catches_up_missed_blocks() -> Result<(), String> {
+        let mut config = Config::test_default();
+        config.dry_run = true;
+        // Unreachable database: reading the chain tip would fail.
+        config.ordinals_db.port = 1;
+        let service = Service::new(&config, &Context::empty());
+        let mut blocks = vec![BitcoinBlockDataCached {
+            block: TestBlockBuilder::new().height(900000).build(),
+            processed_by_sidecar: false,
+        }];
+        service
+            .catch_up_missed_blocks(&mut blocks, &vec![], &mut None)
+            .await?;
+        assert!(!blocks[0].processed_by_sidecar);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn marks_blocks_indexed_before_a_restart_as_processed() -> Result<(), String> {
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        let indexed_hash = format!("{:064x}", 800000);
+        ordinals_pg::insert_backfilled_indexed_block(800000, &indexed_hash, &pg_client).await?;
+        let mut blocks: Vec<BitcoinBlockDataCached> = [800000, 800001]
+            .into_iter()
+            .map(|height| BitcoinBlockDataCached {
+                block: TestBlockBuilder::new()
+                    .height(height)
+                    .hash(format!("0x{:064x}", height))
+                    .build(),
+                processed_by_sidecar: false,
+            })
+            .collect();
+
+        mark_indexed_blocks_as_processed(&mut blocks, &pg_client).await?;
+
+        assert!(blocks[0].processed_by_sidecar);
+        assert!(!blocks[1].processed_by_sidecar);
+        pg_reset_db(&mut pg_client).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn counts_the_activity_of_indexed_blocks() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/block_indexed_counts".to_string();
+        config.ordinals_db = pg_test_config();
+        drop_all_dbs(&config);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+
+        let block = index_committed_fixture("inscription_reveal.jsonl.gz", &config, &ctx).await?;
+
+        assert_eq!(
+            BlockIndexedCounts::from_block(&block),
+            BlockIndexedCounts {
+                transactions: 2,
+                inscription_reveals: 1,
+                inscription_transfers: 0,
+                brc20_operations: 0,
+            }
+        );
+        pg_reset_db(&mut pg_client).await?;
+        drop_all_dbs(&config);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rolls_back_brc20_operations_ahead_of_the_ordinals_chain_tip() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/resume_from_ordinals_chain_tip".to_string();
+        config.ordinals_db = pg_test_config();
+        config.meta_protocols.brc20 = true;
+        // Both schemas track their migrations in a table of the same name, so BRC-20 gets its own schema.
+        let brc20_db = PgConnectionConfig {
+            search_path: Some("brc20_resume_test".to_string()),
+            ..pg_test_config()
+        };
+        config.brc20_db = Some(brc20_db.clone());
+        drop_all_dbs(&config);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        pg_client
+            .batch_execute("CREATE SCHEMA IF NOT EXISTS brc20_resume_test")
+            .await
+            .map_err(|e| format!("unable to create brc20 schema: {e}"))?;
+        let mut brc20_client = pg_connect(&brc20_db).await?;
+        brc20_pg::migrate(&mut brc20_client).await?;
+        let block = index_committed_fixture("inscription_reveal.jsonl.gz", &config, &ctx).await?;
+        assert_eq!(BlockIndexedCounts::from_block(&block).brc20_operations, 1);
+        let pg_pools = Service::new(&config, &ctx).pg_pools;
+
+        resume_from_ordinals_chain_tip(&config, &pg_pools, &ctx).await?;
+        assert_eq!(
+            brc20_pg::get_highest_operation_block_height(&brc20_client).await?,
+            Some(850000)
+        );
+
+        // A crash after the BRC-20 commit of block 850000 but before its ordinals commit.
+        ordinals_pg::update_chain_tip(849999, &pg_client).await?;
+        resume_from_ordinals_chain_tip(&config, &pg_pools, &ctx).await?;
+        assert_eq!(
+            brc20_pg::get_highest_operation_block_height(&brc20_client).await?,
+            None
+        );
+        assert!(brc20_pg::get_token(&"ordi".to_string(), &brc20_client)
+            .await?
+            .is_none());
+
+        brc20_client
+            .batch_execute("DROP SCHEMA brc20_resume_test CASCADE")
+            .await
+            .map_err(|e| format!("unable to drop brc20 schema: {e}"))?;
+        pg_reset_db(&mut pg_client).await?;
+        drop_all_dbs(&config);
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use async_nats::{
+    header::NATS_MESSAGE_ID,
+    jetstream::{self, stream, Context as JetStreamContext},
+    HeaderMap,
+};
+use chainhook_sdk::utils::Context;
+
+use crate::{config::NatsConfig, try_debug};
+
+use super::block_events::{BlockEvent, BlockEventKind};
+
+/// Subject a block event is published on. Each block height has its own subject so consumers can look up the stream
+/// sequence of a height and replay from there.
+pub fn block_event_subject(event: &BlockEvent, subject_prefix: &str) -> String {
+    match event.kind {
+        BlockEventKind::Apply => format!("{subject_prefix}.blocks.{}", event.block_height),
+        BlockEventKind::Rollback => format!("{subject_prefix}.rollbacks.{}", event.block_height),
+    }
+}
+
+async fn get_jetstream(nats: &NatsConfig) -> Result<JetStreamContext, String> {
+    let client = async_nats::connect(&nats.url)
+        .await
+        .map_err(|e| format!("unable to connect to NATS server {}: {e}", nats.url))?;
+    let jetstream = jetstream::new(client);
+    jetstream
+        .get_or_create_stream(stream::Config {
+            name: nats.stream_name.clone(),
+            subjects: vec![format!("{}.>", nats.subject_prefix)],
+            storage: stream::StorageType::File,
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| format!("unable to get NATS stream {}: {e}", nats.stream_name))?;
+    Ok(jetstream)
+}
+
+/// Publishes a block event and waits until JetStream has persisted it. Applied blocks carry their hash as message id,
+/// so publishing the same block again after a restart is deduplicated by the server.
+pub async fn publish_block_event(
+    event: &BlockEvent,
+    nats: &NatsConfig,
+    ctx: &Context,
+) -> Result<(), String> {
+    let jetstream = get_jetstream(nats).await?;
+    let subject = block_event_subject(event, &nats.subject_prefix);
+    let payload =
+        serde_json::to_vec(event).map_err(|e| format!("unable to serialize NATS message: {e}"))?;
+    let mut headers = HeaderMap::new();
+    if let Some(block_identifier) = &event.block_identifier {
+        headers.insert(NATS_MESSAGE_ID, block_identifier.hash.as_str());
+    }
+    jetstream
+        .publish_with_headers(subject.clone(), headers, payload.into())
+        .await
+        .map_err(|e| format!("unable to publish NATS message on {subject}: {e}"))?
+        .await
+        .map_err(|e| format!("NATS message on {subject} was not acknowledged: {e}"))?;
+    try_debug!(ctx, "NATS: published {subject}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::service::block_events::BlockEvent;
+
+    use super::block_event_subject;
+
+    #[test]
+    fn keys_subjects_by_height() {
+        assert_eq!(
+            block_event_subject(&BlockEvent::rollback(840_000), "ordhook"),
+            "ordhook.rollbacks.840000"
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chainhook_sdk::observer::chain_event_cursor::{
+    ChainEventCursor, ChainEventCursorStore, FileChainEventCursorStore,
+};
+use deadpool_postgres::Pool;
+#[cfg(feature = "s3")]
+use s3::creds::Credentials;
+#[cfg(feature = "s3")]
+use s3::{Bucket, Region};
+
+#[cfg(feature = "s3")]
+use crate::config::S3StateConfig;
+use crate::config::{Config, ObserversStateConfig};
+use crate::db::ordinals_pg;
+
+const CHAIN_EVENT_CURSOR_KEY: &str = "chain_event_cursor.json";
+
+/// Builds the store the observer persists its chain event cursor to, according to `storage.observers_state`.
+pub fn chain_event_cursor_store(
+    config: &Config,
+    ordinals_pool: &Pool,
+) -> Result<Arc<dyn ChainEventCursorStore>, String> {
+    let store: Arc<dyn ChainEventCursorStore> = match &config.storage.observers_state {
+        ObserversStateConfig::Local => Arc::new(FileChainEventCursorStore {
+            path: config
+                .expected_observers_cache_path()
+                .join(CHAIN_EVENT_CURSOR_KEY),
+        }),
+        ObserversStateConfig::Postgres => Arc::new(PostgresChainEventCursorStore {
+            pool: ordinals_pool.clone(),
+        }),
+        #[cfg(feature = "s3")]
+        ObserversStateConfig::S3(s3_config) => Arc::new(S3ChainEventCursorStore::new(s3_config)?),
+        #[cfg(not(feature = "s3"))]
+        ObserversStateConfig::S3(_) => {
+            return Err(
+                "s3 observers_state requires ordhook to be built with the s3 feature".into(),
+            )
+        }
+    };
+    Ok(store)
+}
+
+fn parse_cursor(bytes: &[u8], location: &str) -> Result<ChainEventCursor, String> {
+    serde_json::from_slice(bytes)
+        .map_err(|e| format!("unable to parse chain event cursor {location}: {e}"))
+}
+
+fn serialize_cursor(cursor: &ChainEventCursor) -> Result<String, String> {
+    serde_json::to_string(cursor)
+        .map_err(|e| format!("unable to serialize chain event cursor: {e}"))
+}
+
+/// Keeps the cursor in the `observer_state` table of the ordinals database.
+#[derive(Debug)]
+pub struct PostgresChainEventCursorStore {
+    pool: Pool,
+}
+
+#[async_trait]
+impl ChainEventCursorStore for PostgresChainEventCursorStore {
+    async fn load(&self) -> Result<Option<ChainEventCursor>, String> {
+        let client = self
+            .pool
+            .get()
+            .await
+            .map_err(|e| format!("unable to get pg client: {e}"))?;
+        match ordinals_pg::get_observer_state(CHAIN_EVENT_CURSOR_KEY, &client).await? {
+            Some(value) => parse_cursor(value.as_bytes(), &self.location()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    async fn save(&self, cursor: &ChainEventCursor) -> Result<(), String> {
+        let value = serialize_cursor(cursor)?;
+        let client = self
+            .pool
+            .get()
+            .await
+            .map_err(|e| format!("unable to get pg client: {e}"))?;
+        Ok(ordinals_pg::upsert_observer_state(CHAIN_EVENT_CURSOR_KEY, &value, &client).await?)
+    }
+
+    fn location(&self) -> String {
+        format!("postgres observer_state/{CHAIN_EVENT_CURSOR_KEY}")
+    }
+}
+
+/// Keeps the cursor as an object in an S3 compatible bucket. Credentials are read from the standard AWS environment
+/// variables, profile or instance metadata.
+#[cfg(feature = "s3")]
+#[derive(Debug)]
+pub struct S3ChainEventCursorStore {
+    bucket: Bucket,
+    key: String,
+}
+
+#[cfg(feature = "s3")]
+impl S3ChainEventCursorStore {
+    pub fn new(config: &S3StateConfig) -> Result<S3ChainEventCursorStore, String> {
+        let region = match &config.endpoint {
+            Some(endpoint) => Region::Custom {
+                region: config.region.clone(),
+                endpoint: endpoint.clone(),
+            },
+            None => config
+                .region
+                .parse()
+                .map_err(|e| format!("invalid S3 region {}: {e}", config.region))?,
+        };
+        let credentials =
+            Credentials::default().map_err(|e| format!("unable to load S3 credentials: {e}"))?;
+        let mut bucket = Bucket::new(&config.bucket, region, credentials)
+            .map_err(|e| format!("unable to configure S3 bucket {}: {e}", config.bucket))?;
+        if config.endpoint.is_some() {
+            bucket = bucket.with_path_style();
+        }
+        Ok(S3ChainEventCursorStore {
+            bucket,
+            key: s3_object_key(&config.prefix, CHAIN_EVENT_CURSOR_KEY),
+        })
+    }
+}
+
+#[cfg(feature = "s3")]
+#[async_trait]
+impl ChainEventCursorStore for S3ChainEventCursorStore {
+    async fn load(&self) -> Result<Option<ChainEventCursor>, String> {
+        let response = self
+            .bucket
+            .get_object(&self.key)
+            .await
+            .map_err(|e| format!("unable to fetch {}: {e}", self.location()))?;
+        match response.status_code() {
+            200 => parse_cursor(response.bytes(), &self.location()).map(Some),
+            404 => Ok(None),
+            status => Err(format!(
+                "unable to fetch {}: status {status}",
+                self.location()
+            )),
+        }
+    }
+
+    async fn save(&self, cursor: &ChainEventCursor) -> Result<(), String> {
+        let value = serialize_cursor(cursor)?;
+        let response = self
+            .bucket
+            .put_object(&self.key, value.as_bytes())
+            .await
+            .map_err(|e| format!("unable to upload {}: {e}", self.location()))?;
+        match response.status_code() {
+            200..=299 => Ok(()),
+            status => Err(format!(
+                "unable to upload {}: status {status}",
+                self.location()
+            )),
+        }
+    }
+
+    fn location(&self) -> String {
+        format!("s3://{}/{}", self.bucket.name, self.key)
+    }
+}
+
+#[cfg(feature = "s3")]
+fn s3_object_key(prefix: &str, name: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{prefix}/{name}")
+    }
+}
+
+#[cfg(all(test, feature = "s3"))]
+mod test {
+    use test_case::test_case;
+
+    use super::s3_object_key;
+
+    #[test_case("" => "chain_event_cursor.json".to_string(); "without prefix")]
+    #[test_case("ordhook/mainnet" => "ordhook/mainnet/chain_event_cursor.json".to_string(); "with prefix")]
+    #[test_case("/ordhook/" => "ordhook/chain_event_cursor.json".to_string(); "with slashes")]
+    fn builds_s3_object_keys(prefix: &str) -> String {
+        s3_object_key(prefix, "chain_event_cursor.json")
+    }
+}
@@ -0,0 +1,183 @@
+use std::{collections::HashSet, str::FromStr};
+
+use bitcoin::{psbt::Psbt, OutPoint};
+
+use crate::db::models::DbInscribedSat;
+
+/// Body of a `POST /psbt/check` request. Either a base64 encoded PSBT or a list of `txid:vout` outpoints.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PsbtCheckRequest {
+    pub psbt: Option<String>,
+    pub inputs: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsbtInputInscription {
+    pub id: String,
+    pub number: i64,
+    pub ordinal_number: u64,
+    pub offset: Option<u64>,
+    pub rarity: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsbtInputRareSat {
+    pub ordinal_number: u64,
+    pub offset: Option<u64>,
+    pub rarity: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsbtInputCheck {
+    pub vin: usize,
+    pub output: String,
+    pub inscriptions: Vec<PsbtInputInscription>,
+    pub rare_sats: Vec<PsbtInputRareSat>,
+}
+
+/// What the inputs of a PSBT carry. Rarity is only known for sats that hold inscriptions, since those are the only sats
+/// the index keeps track of.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PsbtCheckReport {
+    /// `true` if signing would spend at least one inscription or rare sat.
+    pub warning: bool,
+    pub inputs: Vec<PsbtInputCheck>,
+}
+
+/// Returns the outpoints spent by the request, as `txid:vout`, in input order.
+pub fn parse_psbt_check_request(body: &[u8]) -> Result<Vec<String>, String> {
+    let request: PsbtCheckRequest =
+        serde_json::from_slice(body).map_err(|e| format!("invalid request body: {e}"))?;
+    match (request.psbt, request.inputs) {
+        (Some(psbt), None) => {
+            let psbt = Psbt::from_str(psbt.trim()).map_err(|e| format!("invalid psbt: {e}"))?;
+            Ok(psbt
+                .unsigned_tx
+                .input
+                .iter()
+                .map(|input| input.previous_output.to_string())
+                .collect())
+        }
+        (None, Some(inputs)) => inputs
+            .iter()
+            .map(|input| {
+                OutPoint::from_str(input)
+                    .map(|outpoint| outpoint.to_string())
+                    .map_err(|_| format!("invalid input {input}"))
+            })
+            .collect(),
+        _ => Err("expected either a psbt or a list of inputs".to_string()),
+    }
+}
+
+/// Groups the inscribed sats found at `outputs` by input.
+pub fn evaluate_psbt_inputs(outputs: &[String], sats: &[DbInscribedSat]) -> PsbtCheckReport {
+    let mut inputs = vec![];
+    for (vin, output) in outputs.iter().enumerate() {
+        let mut inscriptions = vec![];
+        let mut rare_sats = vec![];
+        let mut seen_sats = HashSet::new();
+        for sat in sats.iter().filter(|sat| &sat.output == output) {
+            let offset = sat.offset.map(|offset| offset.0);
+            inscriptions.push(PsbtInputInscription {
+                id: sat.inscription_id.clone(),
+                number: sat.number,
+                ordinal_number: sat.ordinal_number.0,
+                offset,
+                rarity: sat.rarity.clone(),
+            });
+            if sat.rarity != "common" && seen_sats.insert(sat.ordinal_number.0) {
+                rare_sats.push(PsbtInputRareSat {
+                    ordinal_number: sat.ordinal_number.0,
+                    offset,
+                    rarity: sat.rarity.clone(),
+                });
+            }
+        }
+        inputs.push(PsbtInputCheck {
+            vin,
+            output: output.clone(),
+            inscriptions,
+            rare_sats,
+        });
+    }
+    PsbtCheckReport {
+        warning: inputs
+            .iter()
+            .any(|input| !input.inscriptions.is_empty() || !input.rare_sats.is_empty()),
+        inputs,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        absolute::LockTime, psbt::Psbt, transaction::Version, OutPoint, Transaction, TxIn,
+    };
+    use chainhook_postgres::types::PgNumericU64;
+
+    use crate::db::models::DbInscribedSat;
+
+    use super::{evaluate_psbt_inputs, parse_psbt_check_request};
+
+    const TXID: &str = "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735";
+
+    #[test]
+    fn parses_psbt_inputs() {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![
+                TxIn {
+                    previous_output: OutPoint::from_str(&format!("{TXID}:0")).unwrap(),
+                    ..Default::default()
+                },
+                TxIn {
+                    previous_output: OutPoint::from_str(&format!("{TXID}:3")).unwrap(),
+                    ..Default::default()
+                },
+            ],
+            output: vec![],
+        };
+        let psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        let body = json!({ "psbt": psbt.to_string() }).to_string();
+        assert_eq!(
+            parse_psbt_check_request(body.as_bytes()).unwrap(),
+            vec![format!("{TXID}:0"), format!("{TXID}:3")]
+        );
+        let body = json!({ "inputs": [format!("{TXID}:1")] }).to_string();
+        assert_eq!(
+            parse_psbt_check_request(body.as_bytes()).unwrap(),
+            vec![format!("{TXID}:1")]
+        );
+        assert!(parse_psbt_check_request(br#"{"inputs": ["nope"]}"#).is_err());
+        assert!(parse_psbt_check_request(b"{}").is_err());
+    }
+
+    #[test]
+    fn flags_inscriptions_and_rare_sats() {
+        let inscribed =
+            |offset: u64, ordinal_number: u64, rarity: &str, number: i64| DbInscribedSat {
+                output: format!("{TXID}:0"),
+                offset: Some(PgNumericU64(offset)),
+                ordinal_number: PgNumericU64(ordinal_number),
+                rarity: rarity.to_string(),
+                inscription_id: format!("{TXID}i{number}"),
+                number,
+            };
+        let sats = vec![
+            inscribed(0, 5_000_000_000, "uncommon", 0),
+            inscribed(0, 5_000_000_000, "uncommon", 1),
+            inscribed(546, 5_000_000_546, "common", 2),
+        ];
+        let report = evaluate_psbt_inputs(&[format!("{TXID}:0"), format!("{TXID}:1")], &sats);
+        assert!(report.warning);
+        assert_eq!(report.inputs[0].inscriptions.len(), 3);
+        assert_eq!(report.inputs[0].rare_sats.len(), 1);
+        assert_eq!(report.inputs[0].rare_sats[0].rarity, "uncommon");
+        assert!(report.inputs[1].inscriptions.is_empty());
+        assert!(!evaluate_psbt_inputs(&[format!("{TXID}:1")], &sats).warning);
+    }
+}
@@ -0,0 +1,186 @@
+use chainhook_sdk::utils::Context;
+use chainhook_types::{BitcoinBlockData, OrdinalOperation};
+use redis::AsyncCommands;
+
+use crate::{config::RedisConfig, try_debug, try_warn};
+
+use super::{
+    activity_stream::{Brc20ActivityEvent, OrdinalActivityEvent},
+    block_events::PayloadFormat,
+    mempool_brc20::PendingBrc20Operation,
+};
+
+/// Messages published for an indexed block as `(channel, payload)` pairs, in block order: reveals and transfers first,
+/// then BRC-20 operations.
+pub fn block_redis_messages(
+    block: &BitcoinBlockData,
+    channel_prefix: &str,
+    payload_format: PayloadFormat,
+) -> Vec<(String, String)> {
+    let timestamp_ms = payload_format.timestamp_ms(block);
+    let mut messages = vec![];
+    let mut brc20_messages = vec![];
+    for tx in block.transactions.iter() {
+        for operation in tx.metadata.ordinal_operations.iter() {
+            let channel = match operation {
+                OrdinalOperation::InscriptionRevealed(_) => format!("{channel_prefix}:reveals"),
+                OrdinalOperation::InscriptionTransferred(_) => {
+                    format!("{channel_prefix}:transfers")
+                }
+            };
+            let event = OrdinalActivityEvent {
+                block_identifier: block.block_identifier.clone(),
+                timestamp: block.timestamp,
+                timestamp_ms,
+                tx_id: tx.transaction_identifier.hash.clone(),
+                operation: operation.clone(),
+                charms_format: payload_format.charms,
+            };
+            if let Ok(payload) = serde_json::to_string(&event) {
+                messages.push((channel, payload));
+            }
+        }
+        if let Some(operation) = &tx.metadata.brc20_operation {
+            let event = Brc20ActivityEvent {
+                block_identifier: block.block_identifier.clone(),
+                timestamp: block.timestamp,
+                timestamp_ms,
+                tx_id: tx.transaction_identifier.hash.clone(),
+                operation: operation.clone(),
+            };
+            if let Ok(payload) = serde_json::to_string(&event) {
+                brc20_messages.push((format!("{channel_prefix}:brc20"), payload));
+            }
+        }
+    }
+    messages.append(&mut brc20_messages);
+    messages
+}
+
+async fn publish_redis_messages(
+    messages: Vec<(String, String)>,
+    redis: &RedisConfig,
+) -> Result<(), String> {
+    let client = redis::Client::open(redis.url.as_str())
+        .map_err(|e| format!("invalid Redis url {}: {e}", redis.url))?;
+    let mut connection = client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|e| format!("unable to connect to Redis server {}: {e}", redis.url))?;
+    for (channel, payload) in messages {
+        let _: i64 = connection
+            .publish(&channel, payload)
+            .await
+            .map_err(|e| format!("unable to publish Redis message on {channel}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Publishes the activity of an indexed block. Failures are logged and never interrupt indexing.
+pub async fn publish_block_to_redis(
+    block: &BitcoinBlockData,
+    redis: &RedisConfig,
+    payload_format: PayloadFormat,
+    ctx: &Context,
+) {
+    let messages = block_redis_messages(block, &redis.channel_prefix, payload_format);
+    if messages.is_empty() {
+        return;
+    }
+    let count = messages.len();
+    match publish_redis_messages(messages, redis).await {
+        Ok(()) => try_debug!(
+            ctx,
+            "Redis: published {count} messages for block #{}",
+            block.block_identifier.index
+        ),
+        Err(e) => try_warn!(ctx, "Redis: {e}"),
+    }
+}
+
+/// Publishes the height of a rolled back block on `<channel_prefix>:rollbacks`.
+pub async fn publish_rollback_to_redis(block_height: u64, redis: &RedisConfig, ctx: &Context) {
+    let message = (
+        format!("{}:rollbacks", redis.channel_prefix),
+        json!({ "block_height": block_height }).to_string(),
+    );
+    if let Err(e) = publish_redis_messages(vec![message], redis).await {
+        try_warn!(ctx, "Redis: {e}");
+    }
+}
+
+/// Publishes BRC-20 operations found in the mempool on `<channel_prefix>:brc20:pending`, one message per operation.
+pub async fn publish_pending_brc20_to_redis(
+    operations: &[PendingBrc20Operation],
+    redis: &RedisConfig,
+    ctx: &Context,
+) {
+    let channel = format!("{}:brc20:pending", redis.channel_prefix);
+    let messages = operations
+        .iter()
+        .filter_map(|operation| serde_json::to_string(operation).ok())
+        .map(|payload| (channel.clone(), payload))
+        .collect();
+    if let Err(e) = publish_redis_messages(messages, redis).await {
+        try_warn!(ctx, "Redis: {e}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_types::{
+        Brc20BalanceData, Brc20Operation, OrdinalInscriptionTransferData,
+        OrdinalInscriptionTransferDestination, OrdinalOperation,
+    };
+
+    use crate::core::{
+        meta_protocols::brc20::test_utils::Brc20RevealBuilder,
+        test_builders::{TestBlockBuilder, TestTransactionBuilder},
+    };
+
+    use super::{block_redis_messages, PayloadFormat};
+
+    #[test]
+    fn routes_operations_to_channels() {
+        let block = TestBlockBuilder::new()
+            .height(840_000)
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(
+                        Brc20RevealBuilder::new().build(),
+                    ))
+                    .brc20_operation(Some(Brc20Operation::Mint(Brc20BalanceData {
+                        tick: "pepe".to_string(),
+                        amt: "1000".to_string(),
+                        address: "bc1pa".to_string(),
+                        inscription_id: "01i0".to_string(),
+                    })))
+                    .build(),
+            )
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                        OrdinalInscriptionTransferData {
+                            ordinal_number: 700,
+                            destination: OrdinalInscriptionTransferDestination::SpentInFees,
+                            from_address: None,
+                            satpoint_pre_transfer: "".to_string(),
+                            satpoint_post_transfer: "".to_string(),
+                            post_transfer_output_value: None,
+                            tx_index: 1,
+                        },
+                    ))
+                    .build(),
+            )
+            .build();
+        let channels: Vec<String> =
+            block_redis_messages(&block, "ordhook", PayloadFormat::default())
+                .into_iter()
+                .map(|(channel, _)| channel)
+                .collect();
+        assert_eq!(
+            channels,
+            vec!["ordhook:reveals", "ordhook:transfers", "ordhook:brc20"]
+        );
+    }
+}
@@ -0,0 +1,563 @@
+use chainhook_postgres::{pg_connect, PgConnectionConfig};
+use chainhook_sdk::utils::{BlockHeights, Context};
+use tokio_postgres::Client;
+
+use crate::{
+    config::Config,
+    core::{
+        first_inscription_height,
+        meta_protocols::brc20::brc20_pg,
+        pipeline::{
+            bitcoind_download_blocks,
+            processors::inscription_indexing::{
+                rollback_block, start_inscription_indexing_processor,
+            },
+        },
+    },
+    db::ordinals_pg,
+    try_info,
+    utils::monitoring::PrometheusMonitoring,
+};
+
+use super::Service;
+
+/// Tables whose rows are tied to the block that created them. They are compared only inside the replayed range.
+pub(crate) const ORDINALS_HISTORY_TABLES: [&str; 5] = [
+    "inscriptions",
+    "locations",
+    "inscription_transfers",
+    "counts_by_block",
+    "indexed_blocks",
+];
+pub(crate) const BRC20_HISTORY_TABLES: [&str; 3] =
+    ["operations", "address_operations", "balances_history"];
+
+/// Tables that are never copied nor compared: bookkeeping, and state written outside of indexing by background services
+/// (clusterers, webhooks, address watch, content scanners). Tables added by a migration must be listed here unless
+/// re-indexing a block rebuilds their rows exactly.
+const IGNORED_TABLES: [&str; 11] = [
+    "pgmigrations",
+    "ordhook_version",
+    "observer_state",
+    "address_clusters",
+    "filtered_inscriptions",
+    "provisional_inscriptions",
+    "webhook_deliveries",
+    "webhook_dead_letters",
+    "address_watch_digest_entries",
+    "content_scans",
+    "content_scan_failures",
+];
+
+/// Difference found between the live schema and the replayed scratch schema for a single table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayTableDiff {
+    pub table: String,
+    /// Live rows that the replay did not produce.
+    pub missing_rows: i64,
+    /// Replayed rows that are not present in the live schema.
+    pub unexpected_rows: i64,
+}
+
+/// Result of a replay: per-table differences, plus the tables that could not be compared, either because the live index
+/// has already moved past the end of the replayed range or because only one of the schemas has them. Rows of inscriptions flagged by a content scanner or taken down
+/// are left out of the comparison, since withdrawing them is not part of indexing.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    pub diffs: Vec<ReplayTableDiff>,
+    pub skipped_tables: Vec<String>,
+}
+
+/// Re-runs the protocol pipeline for blocks `from..=to` into a scratch schema and diffs the result against the live
+/// schema.
+///
+/// The scratch schema starts as a copy of the live schema rolled back to block `from - 1`, so inscription numbers and
+/// counters carry over exactly as they were when the live index reached that height. Traversals are served from the
+/// local blocks DB; block bodies are fetched from bitcoind because the compacted archive does not keep witnesses or
+/// output scripts.
+pub async fn replay_blocks(
+    config: &Config,
+    from: u64,
+    to: u64,
+    scratch_schema: &str,
+    keep_scratch_schema: bool,
+    ctx: &Context,
+) -> Result<ReplayReport, String> {
+    if from > to {
+        return Err(format!("invalid replay range #{from} to #{to}"));
+    }
+    if from < first_inscription_height(config) {
+        return Err(format!(
+            "replay must start at or after the first inscription height #{}",
+            first_inscription_height(config)
+        ));
+    }
+    validate_schema_name(scratch_schema)?;
+    let live_chain_tip = Service::new(config, ctx).get_index_chain_tip().await?;
+    if to > live_chain_tip {
+        return Err(format!(
+            "replay range ends at #{to} but the live index chain tip is #{live_chain_tip}"
+        ));
+    }
+
+    let scratch =
+        prepare_scratch_schemas(config, from, live_chain_tip, scratch_schema, ctx).await?;
+
+    // 3: Replay the range.
+    try_info!(ctx, "Replay: indexing blocks #{from} to #{to}");
+    let scratch_service = Service::new(&scratch.config, ctx);
+    let blocks_post_processor = start_inscription_indexing_processor(
+        &scratch.config,
+        &scratch_service.pg_pools,
+        ctx,
+        &PrometheusMonitoring::new(),
+    );
+    let blocks = BlockHeights::BlockRange(from, to)
+        .get_sorted_entries()
+        .map_err(|_e| format!("Block start / end block spec invalid"))?;
+    bitcoind_download_blocks(
+        &scratch.config,
+        blocks.into(),
+        first_inscription_height(&scratch.config),
+        &blocks_post_processor,
+        10_000,
+        None,
+        ctx,
+    )
+    .await?;
+
+    // 4: Diff. State tables only make sense to compare if the live index stopped where the replay did.
+    let report = diff_scratch_schemas(config, &scratch, (from, to), to == live_chain_tip).await?;
+    if !keep_scratch_schema {
+        drop_scratch_schemas(&scratch).await?;
+    }
+    Ok(report)
+}
+
+/// Scratch schemas of a replay, along with the connections used to build and diff them.
+pub(crate) struct ScratchSchemas {
+    /// Config that indexes into the scratch schemas, see `replay_scratch_config`.
+    pub config: Config,
+    ord_client: Client,
+    ord_schema: String,
+    brc20: Option<(Client, PgConnectionConfig, String)>,
+}
+
+/// Steps 1 and 2 of a replay: copies the live schemas into scratch schemas and rolls them back from `live_chain_tip` to
+/// block `from - 1`.
+pub(crate) async fn prepare_scratch_schemas(
+    config: &Config,
+    from: u64,
+    live_chain_tip: u64,
+    scratch_schema: &str,
+    ctx: &Context,
+) -> Result<ScratchSchemas, String> {
+    // 1: Build scratch schemas as copies of the live ones.
+    let mut scratch_config = replay_scratch_config(config, scratch_schema);
+    try_info!(ctx, "Replay: copying ordinals schema into {scratch_schema}");
+    let mut ord_client = pg_connect(&config.ordinals_db).await?;
+    prepare_scratch_schema(&mut ord_client, &config.ordinals_db, scratch_schema, false).await?;
+    let brc20 = match (&config.brc20_db, config.meta_protocols.brc20) {
+        (Some(brc20_db), true) => {
+            let brc20_scratch_schema = format!("{scratch_schema}_brc20");
+            try_info!(
+                ctx,
+                "Replay: copying brc20 schema into {brc20_scratch_schema}"
+            );
+            let mut brc20_client = pg_connect(brc20_db).await?;
+            prepare_scratch_schema(&mut brc20_client, brc20_db, &brc20_scratch_schema, true)
+                .await?;
+            if let Some(scratch_brc20_db) = scratch_config.brc20_db.as_mut() {
+                scratch_brc20_db.search_path = Some(brc20_scratch_schema.clone());
+            }
+            Some((brc20_client, brc20_db.clone(), brc20_scratch_schema))
+        }
+        _ => None,
+    };
+
+    // 2: Roll the scratch schemas back to the start of the range.
+    let scratch_service = Service::new(&scratch_config, ctx);
+    try_info!(
+        ctx,
+        "Replay: rolling back scratch schema from #{live_chain_tip} to #{}",
+        from - 1
+    );
+    for block_height in (from..=live_chain_tip).rev() {
+        rollback_block(
+            block_height,
+            &scratch_config,
+            &scratch_service.pg_pools,
+            ctx,
+        )
+        .await?;
+    }
+    Ok(ScratchSchemas {
+        config: scratch_config,
+        ord_client,
+        ord_schema: scratch_schema.to_string(),
+        brc20,
+    })
+}
+
+/// Diffs the scratch schemas of a replay over `range` against the live ones.
+pub(crate) async fn diff_scratch_schemas(
+    config: &Config,
+    scratch: &ScratchSchemas,
+    range: (u64, u64),
+    compare_state_tables: bool,
+) -> Result<ReplayReport, String> {
+    let mut report = ReplayReport::default();
+    diff_schemas(
+        &scratch.ord_client,
+        &live_schema(&config.ordinals_db),
+        &scratch.ord_schema,
+        &ORDINALS_HISTORY_TABLES,
+        range,
+        compare_state_tables,
+        true,
+        &mut report,
+    )
+    .await?;
+    if let Some((brc20_client, brc20_db, brc20_scratch_schema)) = &scratch.brc20 {
+        diff_schemas(
+            brc20_client,
+            &live_schema(brc20_db),
+            brc20_scratch_schema,
+            &BRC20_HISTORY_TABLES,
+            range,
+            compare_state_tables,
+            false,
+            &mut report,
+        )
+        .await?;
+    }
+    Ok(report)
+}
+
+pub(crate) async fn drop_scratch_schemas(scratch: &ScratchSchemas) -> Result<(), String> {
+    drop_schema(&scratch.ord_client, &scratch.ord_schema).await?;
+    if let Some((brc20_client, _, brc20_scratch_schema)) = &scratch.brc20 {
+        drop_schema(brc20_client, brc20_scratch_schema).await?;
+    }
+    Ok(())
+}
+
+/// Copy of `config` that indexes into `scratch_schema`, so that rolling back and re-indexing the range is invisible to
+/// anything outside of the database.
+pub(crate) fn replay_scratch_config(config: &Config, scratch_schema: &str) -> Config {
+    let mut scratch_config = config.without_outbound_side_effects();
+    scratch_config.ordinals_db.search_path = Some(scratch_schema.to_string());
+    scratch_config
+}
+
+/// Returns the schema the live index lives in, which is the first entry of its configured search path.
+pub(crate) fn live_schema(db: &PgConnectionConfig) -> String {
+    db.search_path
+        .as_ref()
+        .and_then(|path| path.split(',').next())
+        .map(|schema| schema.trim().to_string())
+        .unwrap_or("public".to_string())
+}
+
+async fn list_all_tables(client: &Client, schema: &str) -> Result<Vec<String>, String> {
+    let rows = client
+        .query(
+            "SELECT tablename FROM pg_tables WHERE schemaname = $1 ORDER BY tablename",
+            &[&schema],
+        )
+        .await
+        .map_err(|e| format!("unable to list tables in schema {schema}: {e}"))?;
+    Ok(rows
+        .iter()
+        .map(|row| row.get::<_, String>("tablename"))
+        .collect())
+}
+
+async fn list_tables(client: &Client, schema: &str) -> Result<Vec<String>, String> {
+    Ok(list_all_tables(client, schema)
+        .await?
+        .into_iter()
+        .filter(|table| !IGNORED_TABLES.contains(&table.as_str()))
+        .collect())
+}
+
+/// Lists the columns `table` has in both schemas, in the order of `left_schema`. Schemas migrated by different versions
+/// may not have the same columns, e.g. when a shadow runs a newer release than its primary.
+async fn list_common_columns(
+    client: &Client,
+    left_schema: &str,
+    right_schema: &str,
+    table: &str,
+) -> Result<Vec<String>, String> {
+    let rows = client
+        .query(
+            "SELECT l.column_name::TEXT
+            FROM information_schema.columns AS l
+            INNER JOIN information_schema.columns AS r
+                ON r.table_schema = $2 AND r.table_name = l.table_name AND r.column_name = l.column_name
+            WHERE l.table_schema = $1 AND l.table_name = $3
+            ORDER BY l.ordinal_position",
+            &[&left_schema, &right_schema, &table],
+        )
+        .await
+        .map_err(|e| format!("unable to list columns of table {table}: {e}"))?;
+    Ok(rows
+        .iter()
+        .map(|row| row.get::<_, String>("column_name"))
+        .collect())
+}
+
+/// Lists the tables of `schema` that have an `inscription_id` column.
+async fn list_tables_with_inscription_id(
+    client: &Client,
+    schema: &str,
+) -> Result<Vec<String>, String> {
+    let rows = client
+        .query(
+            "SELECT table_name::TEXT FROM information_schema.columns
+            WHERE table_schema = $1 AND column_name = 'inscription_id'",
+            &[&schema],
+        )
+        .await
+        .map_err(|e| format!("unable to list tables in schema {schema}: {e}"))?;
+    Ok(rows
+        .iter()
+        .map(|row| row.get::<_, String>("table_name"))
+        .collect())
+}
+
+/// Query returning the inscriptions a content scanner flagged or that were taken down in any of `schemas`, or `None` if
+/// none of them can withdraw inscriptions yet. Their content is withdrawn outside of indexing, at a time that depends on
+/// when each index ran its scanners or received the takedown.
+async fn withdrawn_inscriptions_query(
+    client: &Client,
+    schemas: &[&str],
+) -> Result<Option<String>, String> {
+    let mut queries = vec![];
+    for schema in schemas.iter() {
+        let tables = list_all_tables(client, schema).await?;
+        if tables.iter().any(|table| table == "content_scans") {
+            queries.push(format!(
+                "SELECT inscription_id FROM \"{schema}\".content_scans WHERE flagged"
+            ));
+        }
+        if tables.iter().any(|table| table == "inscription_takedowns") {
+            queries.push(format!(
+                "SELECT inscription_id FROM \"{schema}\".inscription_takedowns"
+            ));
+        }
+    }
+    if queries.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(queries.join(" UNION ")))
+}
+
+/// Only accepts names that can be quoted into SQL as they are.
+pub(crate) fn validate_schema_name(schema: &str) -> Result<(), String> {
+    if schema.is_empty()
+        || !schema
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(format!(
+            "invalid schema name {schema}, only lowercase letters, digits and underscores are allowed"
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) async fn drop_schema(client: &Client, schema: &str) -> Result<(), String> {
+    client
+        .batch_execute(&format!("DROP SCHEMA IF EXISTS \"{schema}\" CASCADE"))
+        .await
+        .map_err(|e| format!("unable to drop schema {schema}: {e}"))
+}
+
+/// Recreates `scratch_schema` with the same migrations as the live schema and copies every live row into it.
+pub(crate) async fn prepare_scratch_schema(
+    client: &mut Client,
+    live_db: &PgConnectionConfig,
+    scratch_schema: &str,
+    is_brc20: bool,
+) -> Result<(), String> {
+    let live_schema = live_schema(live_db);
+    drop_schema(client, scratch_schema).await?;
+    client
+        .batch_execute(&format!(
+            "CREATE SCHEMA \"{scratch_schema}\"; SET search_path TO \"{scratch_schema}\""
+        ))
+        .await
+        .map_err(|e| format!("unable to create schema {scratch_schema}: {e}"))?;
+    if is_brc20 {
+        brc20_pg::migrate(client).await?;
+    } else {
+        ordinals_pg::migrate(client).await?;
+    }
+    for table in list_tables(client, &live_schema).await?.iter() {
+        client
+            .batch_execute(&format!(
+                "INSERT INTO \"{scratch_schema}\".\"{table}\" SELECT * FROM \"{live_schema}\".\"{table}\""
+            ))
+            .await
+            .map_err(|e| format!("unable to copy table {table} into {scratch_schema}: {e}"))?;
+    }
+    client
+        .batch_execute(&format!("SET search_path TO \"{live_schema}\""))
+        .await
+        .map_err(|e| format!("unable to reset search path: {e}"))?;
+    Ok(())
+}
+
+async fn count_rows_in_difference(client: &Client, left: &str, right: &str) -> Result<i64, String> {
+    let row = client
+        .query_one(
+            &format!("SELECT COUNT(*) AS count FROM ({left} EXCEPT ALL {right}) AS diff"),
+            &[],
+        )
+        .await
+        .map_err(|e| format!("unable to diff tables: {e}"))?;
+    Ok(row.get("count"))
+}
+
+/// Compares every table of `live_schema` with the same table in `scratch_schema`, on the columns both schemas have.
+pub(crate) async fn diff_schemas(
+    client: &Client,
+    live_schema: &str,
+    scratch_schema: &str,
+    history_tables: &[&str],
+    (from, to): (u64, u64),
+    compare_state_tables: bool,
+    exclude_withdrawn_inscriptions: bool,
+    report: &mut ReplayReport,
+) -> Result<(), String> {
+    let (withdrawn_inscription_tables, withdrawn_inscriptions) = if exclude_withdrawn_inscriptions {
+        (
+            list_tables_with_inscription_id(client, live_schema).await?,
+            withdrawn_inscriptions_query(client, &[live_schema, scratch_schema]).await?,
+        )
+    } else {
+        (vec![], None)
+    };
+    let scratch_tables = list_tables(client, scratch_schema).await?;
+    for table in list_tables(client, live_schema).await?.iter() {
+        if !scratch_tables.contains(table) {
+            report.skipped_tables.push(table.clone());
+            continue;
+        }
+        let mut conditions = vec![];
+        if history_tables.contains(&table.as_str()) {
+            conditions.push(format!("block_height BETWEEN {from} AND {to}"));
+        } else if !compare_state_tables {
+            report.skipped_tables.push(table.clone());
+            continue;
+        }
+        if let (true, Some(withdrawn_inscriptions)) = (
+            withdrawn_inscription_tables.contains(table),
+            &withdrawn_inscriptions,
+        ) {
+            conditions.push(format!("inscription_id NOT IN ({withdrawn_inscriptions})"));
+        }
+        let filter = if conditions.is_empty() {
+            "".to_string()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let columns = list_common_columns(client, live_schema, scratch_schema, table)
+            .await?
+            .iter()
+            .map(|column| format!("\"{column}\""))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let live_rows = format!("SELECT {columns} FROM \"{live_schema}\".\"{table}\" {filter}");
+        let scratch_rows =
+            format!("SELECT {columns} FROM \"{scratch_schema}\".\"{table}\" {filter}");
+        let missing_rows = count_rows_in_difference(client, &live_rows, &scratch_rows).await?;
+        let unexpected_rows = count_rows_in_difference(client, &scratch_rows, &live_rows).await?;
+        if missing_rows > 0 || unexpected_rows > 0 {
+            report.diffs.push(ReplayTableDiff {
+                table: table.clone(),
+                missing_rows,
+                unexpected_rows,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+
+    use crate::{
+        config::{Brc20ModulesConfig, Config, RedisConfig, WebhookConfig},
+        core::{
+            block_fixtures::index_committed_fixture,
+            meta_protocols::brc20::modules::configured_brc20_modules,
+        },
+        db::{drop_all_dbs, ordinals_pg, pg_reset_db, pg_test_config, pg_test_connection},
+        service::sinks::configured_event_sinks,
+    };
+
+    use super::{
+        diff_scratch_schemas, drop_scratch_schemas, prepare_scratch_schemas, replay_scratch_config,
+    };
+
+    #[tokio::test]
+    async fn replaying_an_indexed_block_reports_no_differences() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/replay".to_string();
+        config.ordinals_db = pg_test_config();
+
+        drop_all_dbs(&config);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        index_committed_fixture("inscription_reveal.jsonl.gz", &config, &ctx).await?;
+
+        let scratch =
+            prepare_scratch_schemas(&config, 850000, 850000, "replay_test_scratch", &ctx).await?;
+        index_committed_fixture("inscription_reveal.jsonl.gz", &scratch.config, &ctx).await?;
+        let report = diff_scratch_schemas(&config, &scratch, (850000, 850000), true).await?;
+        drop_scratch_schemas(&scratch).await?;
+        assert_eq!(report.diffs, vec![]);
+        assert!(report.skipped_tables.is_empty());
+
+        pg_reset_db(&mut pg_client).await?;
+        drop_all_dbs(&config);
+        Ok(())
+    }
+
+    #[test]
+    fn scratch_config_has_no_outbound_side_effects() {
+        let mut config = Config::test_default();
+        config.redis = Some(RedisConfig {
+            url: "redis://localhost:6379".to_string(),
+            channel_prefix: "ordhook".to_string(),
+        });
+        config.webhook = Some(WebhookConfig {
+            url: "http://localhost:3000/events".to_string(),
+            authorization: None,
+            tls: None,
+            max_attempts: 1,
+        });
+        config.sinks.stdout_jsonl = true;
+        config.address_clustering.url = Some("http://localhost:3000/clusters".to_string());
+        config.meta_protocols.brc20_modules = Brc20ModulesConfig {
+            url: Some("http://localhost:3000/brc20".to_string()),
+            custom: vec![],
+        };
+        assert!(!configured_event_sinks(&config).is_empty());
+        assert!(!configured_brc20_modules(&config).is_empty());
+
+        let scratch_config = replay_scratch_config(&config, "replay_scratch");
+        assert_eq!(
+            scratch_config.ordinals_db.search_path,
+            Some("replay_scratch".to_string())
+        );
+        assert!(configured_event_sinks(&scratch_config).is_empty());
+        assert!(configured_brc20_modules(&scratch_config).is_empty());
+        assert!(scratch_config.address_clustering.url.is_none());
+        assert!(scratch_config.address_watch.is_none());
+    }
+}
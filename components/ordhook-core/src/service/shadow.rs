@@ -0,0 +1,220 @@
+use std::time::Duration;
+
+use chainhook_postgres::{pg_connect, types::PgNumericU64};
+use chainhook_sdk::utils::Context;
+use tokio_postgres::Client;
+
+use crate::{
+    config::{Config, ShadowConfig},
+    try_info, try_warn,
+    utils::monitoring::PrometheusMonitoring,
+};
+
+use super::replay::{
+    diff_schemas, live_schema, ReplayReport, BRC20_HISTORY_TABLES, ORDINALS_HISTORY_TABLES,
+};
+
+/// Pause between two comparison rounds against the primary index.
+const SHADOW_COMPARISON_INTERVAL: Duration = Duration::from_secs(10);
+
+async fn get_schema_chain_tip(client: &Client, schema: &str) -> Result<Option<u64>, String> {
+    let row = client
+        .query_opt(
+            &format!("SELECT block_height FROM \"{schema}\".chain_tip"),
+            &[],
+        )
+        .await
+        .map_err(|e| format!("unable to read chain tip of schema {schema}: {e}"))?;
+    Ok(row.map(|row| row.get::<_, PgNumericU64>("block_height").0))
+}
+
+/// Compares every block indexed by both this service and the primary since `last_compared_block`, and returns the last
+/// block compared.
+async fn compare_with_primary(
+    shadow: &ShadowConfig,
+    last_compared_block: Option<u64>,
+    config: &Config,
+    prometheus: &PrometheusMonitoring,
+    ctx: &Context,
+) -> Result<Option<u64>, String> {
+    let ord_client = pg_connect(&config.ordinals_db).await?;
+    let shadow_schema = live_schema(&config.ordinals_db);
+    let (Some(shadow_tip), Some(primary_tip)) = (
+        get_schema_chain_tip(&ord_client, &shadow_schema).await?,
+        get_schema_chain_tip(&ord_client, &shadow.primary_ordinals_schema).await?,
+    ) else {
+        return Ok(last_compared_block);
+    };
+    let comparable_tip = shadow_tip.min(primary_tip);
+    // History before the shadow was started is not compared, the replay command is meant for that.
+    let start = last_compared_block.map_or(comparable_tip, |block| block + 1);
+    if start > comparable_tip {
+        return Ok(last_compared_block);
+    }
+    let brc20 = match (
+        &config.brc20_db,
+        &shadow.primary_brc20_schema,
+        config.meta_protocols.brc20,
+    ) {
+        (Some(brc20_db), Some(primary_brc20_schema), true) => Some((
+            pg_connect(brc20_db).await?,
+            live_schema(brc20_db),
+            primary_brc20_schema,
+        )),
+        _ => None,
+    };
+
+    for block_height in start..=comparable_tip {
+        let mut report = ReplayReport::default();
+        diff_schemas(
+            &ord_client,
+            &shadow.primary_ordinals_schema,
+            &shadow_schema,
+            &ORDINALS_HISTORY_TABLES,
+            (block_height, block_height),
+            false,
+            true,
+            &mut report,
+        )
+        .await?;
+        if let Some((brc20_client, brc20_schema, primary_brc20_schema)) = &brc20 {
+            diff_schemas(
+                brc20_client,
+                primary_brc20_schema,
+                brc20_schema,
+                &BRC20_HISTORY_TABLES,
+                (block_height, block_height),
+                false,
+                false,
+                &mut report,
+            )
+            .await?;
+        }
+        for diff in report.diffs.iter() {
+            try_warn!(
+                ctx,
+                "Shadow: block #{block_height} diverges from primary in {}: {} primary rows missing, {} unexpected rows",
+                diff.table,
+                diff.missing_rows,
+                diff.unexpected_rows
+            );
+        }
+        prometheus.metrics_shadow_block_compared(block_height, !report.diffs.is_empty());
+    }
+    Ok(Some(comparable_tip))
+}
+
+/// Continuously compares the data indexed by this service with the primary index configured in `[shadow]`.
+pub async fn start_shadow_comparisons(
+    shadow: ShadowConfig,
+    config: Config,
+    prometheus: PrometheusMonitoring,
+    ctx: Context,
+) {
+    try_info!(
+        ctx,
+        "Shadow: comparing indexed blocks with primary schema {}",
+        shadow.primary_ordinals_schema
+    );
+    let mut last_compared_block = None;
+    loop {
+        match compare_with_primary(&shadow, last_compared_block, &config, &prometheus, &ctx).await {
+            Ok(block) => last_compared_block = block,
+            Err(e) => try_warn!(ctx, "Shadow: unable to compare with primary: {e}"),
+        }
+        tokio::time::sleep(SHADOW_COMPARISON_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_sdk::utils::Context;
+
+    use crate::{
+        config::{Config, ShadowConfig, WebhookConfig},
+        core::block_fixtures::index_committed_fixture,
+        db::{drop_all_dbs, ordinals_pg, pg_reset_db, pg_test_config, pg_test_connection},
+        service::{
+            replay::{drop_schema, prepare_scratch_schema},
+            sinks::configured_event_sinks,
+            Service,
+        },
+        utils::monitoring::PrometheusMonitoring,
+    };
+
+    use super::compare_with_primary;
+
+    fn shadow_config() -> ShadowConfig {
+        ShadowConfig {
+            primary_ordinals_schema: "shadow_test_primary".to_string(),
+            primary_brc20_schema: None,
+        }
+    }
+
+    #[test]
+    fn shadow_service_has_no_outbound_side_effects() {
+        let mut config = Config::test_default();
+        config.shadow = Some(shadow_config());
+        config.sinks.stdout_jsonl = true;
+        config.webhook = Some(WebhookConfig {
+            url: "http://localhost:3000/events".to_string(),
+            authorization: None,
+            tls: None,
+            max_attempts: 1,
+        });
+        let service = Service::new(&config, &Context::empty());
+        assert!(configured_event_sinks(&service.config).is_empty());
+        assert!(service.config.webhook.is_none());
+    }
+
+    #[tokio::test]
+    async fn compares_with_a_primary_migrated_by_an_older_release() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/shadow".to_string();
+        config.ordinals_db = pg_test_config();
+        let shadow = shadow_config();
+
+        drop_all_dbs(&config);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        index_committed_fixture("inscription_reveal.jsonl.gz", &config, &ctx).await?;
+        // The primary indexed the same block, but lacks a column and the tables added since its release.
+        prepare_scratch_schema(
+            &mut pg_client,
+            &config.ordinals_db,
+            &shadow.primary_ordinals_schema,
+            false,
+        )
+        .await?;
+        pg_client
+            .batch_execute(
+                "ALTER TABLE shadow_test_primary.inscriptions DROP COLUMN content_hash;
+                DROP TABLE shadow_test_primary.inscription_takedowns;
+                DROP TABLE shadow_test_primary.content_scan_failures;
+                DROP TABLE shadow_test_primary.content_scans;",
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let prometheus = PrometheusMonitoring::new();
+        assert_eq!(
+            compare_with_primary(&shadow, None, &config, &prometheus, &ctx).await?,
+            Some(850000)
+        );
+        assert_eq!(prometheus.shadow_last_compared_block_height.get(), 850000);
+        assert_eq!(prometheus.shadow_divergent_blocks.get(), 0);
+
+        pg_client
+            .batch_execute("UPDATE shadow_test_primary.inscriptions SET fee = fee + 1")
+            .await
+            .map_err(|e| e.to_string())?;
+        compare_with_primary(&shadow, Some(849999), &config, &prometheus, &ctx).await?;
+        assert_eq!(prometheus.shadow_divergent_blocks.get(), 1);
+
+        drop_schema(&pg_client, &shadow.primary_ordinals_schema).await?;
+        pg_reset_db(&mut pg_client).await?;
+        drop_all_dbs(&config);
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    io::{self, Write},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use chainhook_sdk::utils::Context;
+use chainhook_types::BitcoinBlockData;
+use deadpool_postgres::Transaction;
+
+use crate::config::Config;
+#[cfg(feature = "nats")]
+use crate::config::NatsConfig;
+#[cfg(feature = "redis")]
+use crate::config::RedisConfig;
+
+#[cfg(feature = "nats")]
+use super::nats::publish_block_event;
+#[cfg(feature = "redis")]
+use super::redis::{
+    publish_block_to_redis, publish_pending_brc20_to_redis, publish_rollback_to_redis,
+};
+use super::{
+    block_events::{BlockEvent, PayloadFormat},
+    mempool_brc20::PendingBrc20Operation,
+    webhook::enqueue_webhook_delivery,
+};
+
+/// Destination for the output of every block applied to or rolled back from the index. Transactional sinks run in the
+/// ordinals transaction of the block, so their writes are committed atomically with it and an error aborts the block.
+/// Other sinks run in order once the block is committed, with an ordinals transaction of their own: an error stops
+/// indexing, but the block is not sent again after a restart.
+#[async_trait]
+pub trait EventSink: Send + Sync {
+    /// Short name used in logs.
+    fn name(&self) -> &str;
+
+    /// Whether the sink only writes through `ord_tx`.
+    fn is_transactional(&self) -> bool {
+        false
+    }
+
+    async fn apply_block(
+        &self,
+        block: &BitcoinBlockData,
+        ord_tx: &Transaction<'_>,
+        ctx: &Context,
+    ) -> Result<(), String>;
+
+    async fn rollback_block(
+        &self,
+        block_height: u64,
+        ord_tx: &Transaction<'_>,
+        ctx: &Context,
+    ) -> Result<(), String>;
+
+    /// Called with the BRC-20 operations of a transaction that entered the mempool, when mempool BRC-20 watching is
+    /// enabled. They are unconfirmed and may never be mined. Errors are logged and ignored.
+    async fn apply_pending_brc20_operations(
+        &self,
+        _operations: &[PendingBrc20Operation],
+        _ctx: &Context,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Queues block events in Postgres for the webhook delivery loop.
+pub struct WebhookQueueSink(pub PayloadFormat);
+
+#[async_trait]
+impl EventSink for WebhookQueueSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn is_transactional(&self) -> bool {
+        true
+    }
+
+    async fn apply_block(
+        &self,
+        block: &BitcoinBlockData,
+        ord_tx: &Transaction<'_>,
+        _ctx: &Context,
+    ) -> Result<(), String> {
+        enqueue_webhook_delivery(&BlockEvent::apply(block, self.0), ord_tx).await
+    }
+
+    async fn rollback_block(
+        &self,
+        block_height: u64,
+        ord_tx: &Transaction<'_>,
+        _ctx: &Context,
+    ) -> Result<(), String> {
+        enqueue_webhook_delivery(&BlockEvent::rollback(block_height), ord_tx).await
+    }
+}
+
+/// Publishes block events to NATS JetStream. Messages go out once the block is committed, so consumers never see a block
+/// rolled back along with a failed batch.
+#[cfg(feature = "nats")]
+pub struct NatsSink(pub NatsConfig, pub PayloadFormat);
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl EventSink for NatsSink {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    async fn apply_block(
+        &self,
+        block: &BitcoinBlockData,
+        _ord_tx: &Transaction<'_>,
+        ctx: &Context,
+    ) -> Result<(), String> {
+        publish_block_event(&BlockEvent::apply(block, self.1), &self.0, ctx).await
+    }
+
+    async fn rollback_block(
+        &self,
+        block_height: u64,
+        _ord_tx: &Transaction<'_>,
+        ctx: &Context,
+    ) -> Result<(), String> {
+        publish_block_event(&BlockEvent::rollback(block_height), &self.0, ctx).await
+    }
+}
+
+/// Publishes block activity on Redis pub/sub channels. Delivery is best effort and never fails a block.
+#[cfg(feature = "redis")]
+pub struct RedisSink(pub RedisConfig, pub PayloadFormat);
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl EventSink for RedisSink {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    async fn apply_block(
+        &self,
+        block: &BitcoinBlockData,
+        _ord_tx: &Transaction<'_>,
+        ctx: &Context,
+    ) -> Result<(), String> {
+        publish_block_to_redis(block, &self.0, self.1, ctx).await;
+        Ok(())
+    }
+
+    async fn rollback_block(
+        &self,
+        block_height: u64,
+        _ord_tx: &Transaction<'_>,
+        ctx: &Context,
+    ) -> Result<(), String> {
+        publish_rollback_to_redis(block_height, &self.0, ctx).await;
+        Ok(())
+    }
+
+    async fn apply_pending_brc20_operations(
+        &self,
+        operations: &[PendingBrc20Operation],
+        ctx: &Context,
+    ) -> Result<(), String> {
+        publish_pending_brc20_to_redis(operations, &self.0, ctx).await;
+        Ok(())
+    }
+}
+
+/// Writes every block event to stdout as a single JSON line.
+pub struct StdoutJsonlSink(pub PayloadFormat);
+
+impl StdoutJsonlSink {
+    fn write_event(&self, event: &BlockEvent) -> Result<(), String> {
+        let line = serde_json::to_string(event)
+            .map_err(|e| format!("unable to serialize block event: {e}"))?;
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{line}").map_err(|e| format!("unable to write to stdout: {e}"))?;
+        stdout
+            .flush()
+            .map_err(|e| format!("unable to write to stdout: {e}"))
+    }
+}
+
+#[async_trait]
+impl EventSink for StdoutJsonlSink {
+    fn name(&self) -> &str {
+        "stdout"
+    }
+
+    async fn apply_block(
+        &self,
+        block: &BitcoinBlockData,
+        _ord_tx: &Transaction<'_>,
+        _ctx: &Context,
+    ) -> Result<(), String> {
+        self.write_event(&BlockEvent::apply(block, self.0))
+    }
+
+    async fn rollback_block(
+        &self,
+        block_height: u64,
+        _ord_tx: &Transaction<'_>,
+        _ctx: &Context,
+    ) -> Result<(), String> {
+        self.write_event(&BlockEvent::rollback(block_height))
+    }
+}
+
+/// Sinks enabled by the config, followed by the custom ones registered in `config.sinks`. Only the webhook queue runs
+/// on dry runs since it writes nothing outside of the ordinals transaction. `[nats]` and `[redis]` are ignored unless
+/// ordhook is built with the matching feature.
+pub fn configured_event_sinks(config: &Config) -> Vec<Arc<dyn EventSink>> {
+    let payload_format = config.sinks.payload_format();
+    let mut sinks: Vec<Arc<dyn EventSink>> = vec![];
+    if config.webhook.is_some() {
+        sinks.push(Arc::new(WebhookQueueSink(payload_format)));
+    }
+    if config.dry_run {
+        return sinks;
+    }
+    #[cfg(feature = "nats")]
+    if let Some(nats) = &config.nats {
+        sinks.push(Arc::new(NatsSink(nats.clone(), payload_format)));
+    }
+    #[cfg(feature = "redis")]
+    if let Some(redis) = &config.redis {
+        sinks.push(Arc::new(RedisSink(redis.clone(), payload_format)));
+    }
+    if config.sinks.stdout_jsonl {
+        sinks.push(Arc::new(StdoutJsonlSink(payload_format)));
+    }
+    sinks.extend(config.sinks.custom.iter().cloned());
+    sinks
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    #[cfg(feature = "redis")]
+    use crate::config::RedisConfig;
+
+    use super::configured_event_sinks;
+
+    fn sink_names(config: &Config) -> Vec<String> {
+        configured_event_sinks(config)
+            .iter()
+            .map(|sink| sink.name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn builds_sinks_from_config() {
+        let mut config = Config::devnet_default();
+        assert!(configured_event_sinks(&config).is_empty());
+        config.sinks.stdout_jsonl = true;
+        assert_eq!(sink_names(&config), vec!["stdout"]);
+        config.dry_run = true;
+        assert!(configured_event_sinks(&config).is_empty());
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn builds_redis_sink_from_config() {
+        let mut config = Config::devnet_default();
+        config.redis = Some(RedisConfig {
+            url: "redis://localhost:6379".to_string(),
+            channel_prefix: "ordhook".to_string(),
+        });
+        config.sinks.stdout_jsonl = true;
+        assert_eq!(sink_names(&config), vec!["redis", "stdout"]);
+    }
+}
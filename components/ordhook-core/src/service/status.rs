@@ -0,0 +1,99 @@
+use chainhook_postgres::{pg_pool, pg_pool_client};
+use chainhook_sdk::utils::{bitcoind::bitcoind_try_get_block_height, Context};
+
+use crate::{
+    config::Config,
+    core::meta_protocols::brc20::brc20_pg,
+    db::{
+        blocks::{find_last_block_inserted, open_readonly_blocks_db},
+        ordinals_pg,
+    },
+};
+
+/// Chain tips of the local stores next to bitcoind's, so sync lag can be checked without querying each store. A store
+/// that could not be read has no height and an entry in `errors`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct IndexStatus {
+    pub bitcoind_block_height: Option<u64>,
+    pub blocks_db_block_height: Option<u64>,
+    pub ordinals_block_height: Option<u64>,
+    /// Last block that produced a BRC-20 operation. The BRC-20 database does not keep a chain tip of its own, it is
+    /// written in the same pipeline as the ordinals database.
+    pub brc20_last_operation_block_height: Option<u64>,
+    pub errors: Vec<String>,
+}
+
+impl IndexStatus {
+    /// Number of blocks the ordinals index is behind bitcoind.
+    pub fn ordinals_blocks_behind(&self) -> Option<u64> {
+        blocks_behind(self.bitcoind_block_height, self.ordinals_block_height)
+    }
+
+    /// Number of blocks the blocks DB is behind bitcoind.
+    pub fn blocks_db_blocks_behind(&self) -> Option<u64> {
+        blocks_behind(self.bitcoind_block_height, self.blocks_db_block_height)
+    }
+}
+
+fn blocks_behind(bitcoind_block_height: Option<u64>, block_height: Option<u64>) -> Option<u64> {
+    Some(bitcoind_block_height?.saturating_sub(block_height?))
+}
+
+async fn get_ordinals_block_height(config: &Config) -> Result<Option<u64>, String> {
+    let pool = pg_pool(&config.ordinals_db)?;
+    let client = pg_pool_client(&pool).await?;
+    Ok(ordinals_pg::get_chain_tip_block_height(&client).await?)
+}
+
+async fn get_brc20_last_operation_block_height(config: &Config) -> Result<Option<u64>, String> {
+    let Some(brc20_db) = &config.brc20_db else {
+        return Ok(None);
+    };
+    let pool = pg_pool(brc20_db)?;
+    let client = pg_pool_client(&pool).await?;
+    Ok(brc20_pg::get_highest_operation_block_height(&client).await?)
+}
+
+/// Reads the chain tip of bitcoind, the blocks DB, the ordinals database and, when enabled, the BRC-20 database.
+pub async fn get_index_status(config: &Config, ctx: &Context) -> IndexStatus {
+    let mut status = IndexStatus::default();
+    match bitcoind_try_get_block_height(&config.network, ctx).await {
+        Ok(block_height) => status.bitcoind_block_height = Some(block_height),
+        Err(e) => status.errors.push(format!("bitcoind: {e}")),
+    }
+    match open_readonly_blocks_db(config, ctx) {
+        Ok(blocks_db) => {
+            status.blocks_db_block_height = Some(find_last_block_inserted(&blocks_db) as u64)
+        }
+        Err(e) => status.errors.push(format!("blocks db: {e}")),
+    }
+    match get_ordinals_block_height(config).await {
+        Ok(block_height) => status.ordinals_block_height = block_height,
+        Err(e) => status.errors.push(format!("ordinals db: {e}")),
+    }
+    if config.meta_protocols.brc20 {
+        match get_brc20_last_operation_block_height(config).await {
+            Ok(block_height) => status.brc20_last_operation_block_height = block_height,
+            Err(e) => status.errors.push(format!("brc20 db: {e}")),
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod test {
+    use super::IndexStatus;
+
+    #[test]
+    fn computes_blocks_behind() {
+        let status = IndexStatus {
+            bitcoind_block_height: Some(840_010),
+            blocks_db_block_height: Some(840_010),
+            ordinals_block_height: Some(840_000),
+            ..Default::default()
+        };
+        assert_eq!(status.ordinals_blocks_behind(), Some(10));
+        assert_eq!(status.blocks_db_blocks_behind(), Some(0));
+        assert_eq!(IndexStatus::default().ordinals_blocks_behind(), None);
+    }
+}
@@ -0,0 +1,129 @@
+use chainhook_postgres::{pg_begin, pg_pool, pg_pool_client};
+use chainhook_sdk::utils::Context;
+use serde::Serialize;
+
+use crate::{
+    config::Config,
+    db::{models::DbInscriptionTakedown, ordinals_pg, pg_commit_unless_dry_run},
+    try_info,
+};
+
+/// Outcome of a content takedown.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InscriptionTakedownReport {
+    pub inscription_id: String,
+    /// Whether the inscription was indexed. A tombstone is recorded either way, so content indexed later is blanked.
+    pub indexed: bool,
+}
+
+/// Blanks the stored content of an inscription and records a tombstone, for operators handling legal takedown requests.
+/// Consensus data (numbers, sats, locations, BRC-20 operations) is not touched. The API also stops serving the whole
+/// reveal transaction as raw bytes, including any other inscription revealed in it.
+pub async fn take_down_inscription_content(
+    config: &Config,
+    inscription_id: &str,
+    reason: &str,
+    ctx: &Context,
+) -> Result<InscriptionTakedownReport, String> {
+    let pool = pg_pool(&config.ordinals_db)?;
+    let mut ord_client = pg_pool_client(&pool).await?;
+    let ord_tx = pg_begin(&mut ord_client).await?;
+    let reveal_tx_id = ordinals_pg::take_down_inscription_content(
+        &DbInscriptionTakedown {
+            inscription_id: inscription_id.to_string(),
+            reason: reason.to_string(),
+        },
+        &ord_tx,
+    )
+    .await?;
+    pg_commit_unless_dry_run(ord_tx, config, "ordinals").await?;
+    try_info!(
+        ctx,
+        "Takedown: content of inscription {inscription_id} blanked"
+    );
+    Ok(InscriptionTakedownReport {
+        inscription_id: inscription_id.to_string(),
+        indexed: reveal_tx_id.is_some(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use chainhook_postgres::{pg_begin, pg_pool_client};
+    use chainhook_sdk::utils::Context;
+
+    use crate::{
+        config::Config,
+        core::test_builders::{TestBlockBuilder, TestTransactionBuilder},
+        db::{drop_all_dbs, ordinals_pg, pg_reset_db, pg_test_connection, pg_test_connection_pool},
+    };
+
+    use super::take_down_inscription_content;
+
+    const INSCRIPTION_ID: &str =
+        "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0";
+
+    #[tokio::test]
+    async fn blanks_content_across_rollbacks() -> Result<(), String> {
+        let ctx = Context::empty();
+        let mut config = Config::test_default();
+        config.storage.working_dir = "tmp/takedown".to_string();
+        drop_all_dbs(&config);
+        let mut pg_client = pg_test_connection().await;
+        ordinals_pg::migrate(&mut pg_client).await?;
+        let block = TestBlockBuilder::new()
+            .height(800000)
+            .add_transaction(TestTransactionBuilder::new_with_operation().build())
+            .build();
+        let tx_id = block.transactions[0]
+            .transaction_identifier
+            .get_hash_bytes_str();
+        {
+            let mut ord_client = pg_pool_client(&pg_test_connection_pool()).await?;
+            let client = pg_begin(&mut ord_client).await?;
+            ordinals_pg::insert_block(&block, None, &client).await?;
+            ordinals_pg::insert_inscription_texts(&block, &client).await?;
+            client.commit().await.unwrap();
+        }
+
+        let report =
+            take_down_inscription_content(&config, INSCRIPTION_ID, "court order", &ctx).await?;
+        assert!(report.indexed);
+        assert!(ordinals_pg::is_transaction_taken_down(tx_id, &pg_client).await?);
+
+        let mut ord_client = pg_pool_client(&pg_test_connection_pool()).await?;
+        let client = pg_begin(&mut ord_client).await?;
+        let inscription = ordinals_pg::get_inscription_by_id(INSCRIPTION_ID, &client)
+            .await?
+            .unwrap();
+        assert!(inscription.content.is_empty());
+        assert!(inscription.content_omitted);
+        assert_eq!(inscription.content_length.0, 94);
+        let content_hash = inscription.content_hash;
+        assert!(
+            ordinals_pg::search_inscriptions("ordi deploy", 20, 0, &client)
+                .await?
+                .is_empty()
+        );
+
+        // The tombstone outlives a rollback, so the content is blanked again when the block is indexed again.
+        ordinals_pg::rollback_block(800000, &client).await?;
+        ordinals_pg::insert_block(&block, None, &client).await?;
+        ordinals_pg::insert_inscription_texts(&block, &client).await?;
+        let inscription = ordinals_pg::get_inscription_by_id(INSCRIPTION_ID, &client)
+            .await?
+            .unwrap();
+        assert!(inscription.content.is_empty());
+        assert_eq!(inscription.content_hash, content_hash);
+        assert_eq!(
+            Some("court order".to_string()),
+            ordinals_pg::get_inscription_takedown(INSCRIPTION_ID, &client)
+                .await?
+                .map(|takedown| takedown.reason)
+        );
+        drop(client);
+        pg_reset_db(&mut pg_client).await?;
+        drop_all_dbs(&config);
+        Ok(())
+    }
+}
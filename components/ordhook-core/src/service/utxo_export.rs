@@ -0,0 +1,204 @@
+use std::{io::Write, str::FromStr};
+
+use chainhook_postgres::{pg_begin_read_snapshot, pg_pool, pg_pool_client, types::PgNumericU64};
+use chainhook_sdk::utils::Context;
+use futures::TryStreamExt;
+use tokio_postgres::types::ToSql;
+
+use crate::{config::Config, db::ordinals_pg, try_info};
+
+/// Magic bytes opening a binary export, followed by the chain tip height the export was taken at.
+const BINARY_EXPORT_MAGIC: &[u8; 8] = b"ORDUTXO1";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UtxoExportFormat {
+    /// `output,offset,ordinal_number,inscription_id` lines, after a header line.
+    Csv,
+    /// `ORDUTXO1` magic and the chain tip as a little endian `u64`, then one 88 byte record per inscription: outpoint
+    /// txid (32 bytes), vout (`u32`), offset (`u64`, `u64::MAX` if unknown), ordinal number (`u64`), inscription
+    /// reveal txid (32 bytes) and inscription index (`u32`). Txids keep their displayed byte order and integers are
+    /// little endian.
+    Binary,
+}
+
+impl FromStr for UtxoExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(UtxoExportFormat::Csv),
+            "binary" => Ok(UtxoExportFormat::Binary),
+            _ => Err(format!("unknown export format {s}, expected csv or binary")),
+        }
+    }
+}
+
+/// Current location of an inscription, as exported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InscribedOutput {
+    pub output: String,
+    pub offset: Option<u64>,
+    pub ordinal_number: u64,
+    pub inscription_id: String,
+}
+
+fn decode_txid(txid: &str) -> Result<[u8; 32], String> {
+    hex::decode(txid)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(format!("invalid txid {txid}"))
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("unable to write export: {e}")
+}
+
+pub fn write_export_header<W: Write>(
+    format: UtxoExportFormat,
+    chain_tip: u64,
+    writer: &mut W,
+) -> Result<(), String> {
+    match format {
+        UtxoExportFormat::Csv => writeln!(writer, "output,offset,ordinal_number,inscription_id"),
+        UtxoExportFormat::Binary => writer
+            .write_all(BINARY_EXPORT_MAGIC)
+            .and_then(|_| writer.write_all(&chain_tip.to_le_bytes())),
+    }
+    .map_err(write_error)
+}
+
+pub fn write_export_record<W: Write>(
+    format: UtxoExportFormat,
+    record: &InscribedOutput,
+    writer: &mut W,
+) -> Result<(), String> {
+    match format {
+        UtxoExportFormat::Csv => {
+            let offset = record.offset.map(|o| o.to_string()).unwrap_or_default();
+            writeln!(
+                writer,
+                "{},{offset},{},{}",
+                record.output, record.ordinal_number, record.inscription_id
+            )
+            .map_err(write_error)
+        }
+        UtxoExportFormat::Binary => {
+            let (txid, vout) = record
+                .output
+                .split_once(':')
+                .ok_or(format!("invalid output {}", record.output))?;
+            let vout = vout
+                .parse::<u32>()
+                .map_err(|_| format!("invalid output {}", record.output))?;
+            let (reveal_txid, index) = record
+                .inscription_id
+                .split_once('i')
+                .ok_or(format!("invalid inscription id {}", record.inscription_id))?;
+            let index = index
+                .parse::<u32>()
+                .map_err(|_| format!("invalid inscription id {}", record.inscription_id))?;
+            let mut bytes = Vec::with_capacity(88);
+            bytes.extend_from_slice(&decode_txid(txid)?);
+            bytes.extend_from_slice(&vout.to_le_bytes());
+            bytes.extend_from_slice(&record.offset.unwrap_or(u64::MAX).to_le_bytes());
+            bytes.extend_from_slice(&record.ordinal_number.to_le_bytes());
+            bytes.extend_from_slice(&decode_txid(reveal_txid)?);
+            bytes.extend_from_slice(&index.to_le_bytes());
+            writer.write_all(&bytes).map_err(write_error)
+        }
+    }
+}
+
+/// Writes the current location of every inscription, sorted by output, from a single database snapshot. Returns the
+/// chain tip the export reflects and the number of records written.
+pub async fn export_inscribed_utxos<W: Write>(
+    config: &Config,
+    format: UtxoExportFormat,
+    writer: &mut W,
+    ctx: &Context,
+) -> Result<(u64, u64), String> {
+    let pool = pg_pool(&config.ordinals_db)?;
+    let mut ord_client = pg_pool_client(&pool).await?;
+    let ord_tx = pg_begin_read_snapshot(&mut ord_client).await?;
+    let chain_tip = ordinals_pg::get_chain_tip_block_height(&ord_tx)
+        .await?
+        .unwrap_or(0);
+    try_info!(ctx, "Exporting inscribed outputs at block #{chain_tip}");
+    write_export_header(format, chain_tip, writer)?;
+
+    let rows = ord_tx
+        .query_raw(
+            "SELECT c.output, c.\"offset\", c.ordinal_number, i.inscription_id
+            FROM current_locations AS c
+            INNER JOIN inscriptions AS i ON i.ordinal_number = c.ordinal_number
+            ORDER BY c.output, c.\"offset\", i.inscription_id",
+            Vec::<&dyn ToSql>::new(),
+        )
+        .await
+        .map_err(|e| format!("export_inscribed_utxos: {e}"))?;
+    futures::pin_mut!(rows);
+    let mut count = 0;
+    while let Some(row) = rows
+        .try_next()
+        .await
+        .map_err(|e| format!("export_inscribed_utxos: {e}"))?
+    {
+        let offset: Option<PgNumericU64> = row.get("offset");
+        let ordinal_number: PgNumericU64 = row.get("ordinal_number");
+        let record = InscribedOutput {
+            output: row.get("output"),
+            offset: offset.map(|offset| offset.0),
+            ordinal_number: ordinal_number.0,
+            inscription_id: row.get("inscription_id"),
+        };
+        write_export_record(format, &record, writer)?;
+        count += 1;
+        if count % 1_000_000 == 0 {
+            try_info!(ctx, "Exported {count} inscriptions");
+        }
+    }
+    writer.flush().map_err(write_error)?;
+    Ok((chain_tip, count))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{write_export_header, write_export_record, InscribedOutput, UtxoExportFormat};
+
+    fn record() -> InscribedOutput {
+        InscribedOutput {
+            output: "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735:1"
+                .to_string(),
+            offset: Some(0),
+            ordinal_number: 7000,
+            inscription_id: "b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0"
+                .to_string(),
+        }
+    }
+
+    #[test]
+    fn writes_csv_export() {
+        let mut out = vec![];
+        write_export_header(UtxoExportFormat::Csv, 840_000, &mut out).unwrap();
+        write_export_record(UtxoExportFormat::Csv, &record(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "output,offset,ordinal_number,inscription_id\n\
+            b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735:1,0,7000,\
+            b61b0172d95e266c18aea0c624db987e971a5d6d4ebc2aaed85da4642d635735i0\n"
+        );
+    }
+
+    #[test]
+    fn writes_binary_export() {
+        let mut out = vec![];
+        write_export_header(UtxoExportFormat::Binary, 840_000, &mut out).unwrap();
+        write_export_record(UtxoExportFormat::Binary, &record(), &mut out).unwrap();
+        assert_eq!(out.len(), 16 + 88);
+        assert_eq!(&out[0..8], b"ORDUTXO1");
+        assert_eq!(out[8..16], 840_000u64.to_le_bytes());
+        assert_eq!(out[16], 0xb6);
+        assert_eq!(out[48..52], 1u32.to_le_bytes());
+        assert_eq!(out[60..68], 7000u64.to_le_bytes());
+    }
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use chainhook_postgres::pg_pool_client;
+use chainhook_sdk::utils::Context;
+use deadpool_postgres::GenericClient;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+
+use crate::{config::WebhookConfig, db::ordinals_pg, try_debug, try_info, try_warn};
+
+use super::{address_watch::build_webhook_client, block_events::BlockEvent, PgConnectionPools};
+
+/// Longest pause between two attempts of the same delivery.
+const MAX_WEBHOOK_RETRY_DELAY_SECS: u64 = 3600;
+
+/// Pause between two polls of an empty or not yet due delivery queue.
+const WEBHOOK_QUEUE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delay before retrying a delivery that already failed `attempts` times: 2s, 4s, 8s... capped at an hour.
+pub fn webhook_retry_delay(attempts: u32) -> Duration {
+    Duration::from_secs(
+        2u64.saturating_pow(attempts)
+            .min(MAX_WEBHOOK_RETRY_DELAY_SECS),
+    )
+}
+
+/// Queues a block event for delivery. Callers pass the transaction that indexes the block, so an event is queued if and
+/// only if its block is committed.
+pub async fn enqueue_webhook_delivery<T: GenericClient>(
+    event: &BlockEvent,
+    client: &T,
+) -> Result<(), String> {
+    let payload = serde_json::to_string(event)
+        .map_err(|e| format!("unable to serialize webhook payload: {e}"))?;
+    Ok(ordinals_pg::insert_webhook_delivery(event.block_height, &payload, client).await?)
+}
+
+async fn post_webhook_payload(
+    payload: &String,
+    webhook: &WebhookConfig,
+    http_client: &reqwest::Client,
+) -> Result<(), String> {
+    let mut request = http_client
+        .post(&webhook.url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(payload.clone());
+    if let Some(authorization) = &webhook.authorization {
+        request = request.header(AUTHORIZATION, authorization.resolve().await?);
+    }
+    let res = request
+        .send()
+        .await
+        .map_err(|e| format!("unable to reach webhook: {e}"))?;
+    if !res.status().is_success() {
+        return Err(format!("webhook returned {}", res.status()));
+    }
+    Ok(())
+}
+
+/// Attempts the oldest due delivery, if any. Returns true if a delivery was attempted.
+async fn process_next_webhook_delivery(
+    webhook: &WebhookConfig,
+    http_client: &reqwest::Client,
+    pg_pools: &PgConnectionPools,
+    ctx: &Context,
+) -> Result<bool, String> {
+    let client = pg_pool_client(&pg_pools.ordinals).await?;
+    let Some(delivery) = ordinals_pg::get_due_webhook_delivery(&client).await? else {
+        return Ok(false);
+    };
+    let block_height = delivery.block_height.0;
+    match post_webhook_payload(&delivery.payload, webhook, http_client).await {
+        Ok(()) => {
+            ordinals_pg::delete_webhook_delivery(delivery.id, &client).await?;
+            try_debug!(ctx, "Webhook: delivered event for block #{block_height}");
+        }
+        Err(e) => {
+            let attempts = delivery.attempts as u32 + 1;
+            if attempts >= webhook.max_attempts {
+                ordinals_pg::dead_letter_webhook_delivery(delivery.id, &e, &client).await?;
+                try_warn!(
+                    ctx,
+                    "Webhook: giving up on event for block #{block_height} after {attempts} attempts: {e}"
+                );
+            } else {
+                let delay = webhook_retry_delay(attempts);
+                ordinals_pg::postpone_webhook_delivery(
+                    delivery.id,
+                    &e,
+                    delay.as_secs_f64(),
+                    &client,
+                )
+                .await?;
+                try_warn!(
+                    ctx,
+                    "Webhook: unable to deliver event for block #{block_height}, retrying in {}s: {e}",
+                    delay.as_secs()
+                );
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Continuously delivers queued block events to the configured webhook, oldest first.
+pub async fn start_webhook_deliveries(
+    webhook: WebhookConfig,
+    pg_pools: PgConnectionPools,
+    ctx: Context,
+) {
+    let http_client = match build_webhook_client(&webhook.tls) {
+        Ok(http_client) => http_client,
+        Err(e) => {
+            try_warn!(ctx, "Webhook: deliveries disabled: {e}");
+            return;
+        }
+    };
+    try_info!(ctx, "Webhook: delivering block events to {}", webhook.url);
+    loop {
+        match process_next_webhook_delivery(&webhook, &http_client, &pg_pools, &ctx).await {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => try_warn!(ctx, "Webhook: unable to process delivery queue: {e}"),
+        }
+        tokio::time::sleep(WEBHOOK_QUEUE_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::webhook_retry_delay;
+
+    #[test]
+    fn backs_off_exponentially() {
+        assert_eq!(webhook_retry_delay(1), Duration::from_secs(2));
+        assert_eq!(webhook_retry_delay(5), Duration::from_secs(32));
+        assert_eq!(webhook_retry_delay(20), Duration::from_secs(3600));
+        assert_eq!(webhook_retry_delay(100), Duration::from_secs(3600));
+    }
+}
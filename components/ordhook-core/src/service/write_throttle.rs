@@ -0,0 +1,197 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use chainhook_postgres::pg_pool_client;
+use chainhook_sdk::utils::Context;
+use deadpool_postgres::Pool;
+
+use crate::{try_debug, try_info, try_warn};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WriteThrottleStatus {
+    pub enabled: bool,
+    pub max_wal_bytes_per_sec: Option<u64>,
+    /// Unix time at which throttling stops on its own, when it was enabled for a limited time.
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Debug)]
+struct ThrottleSettings {
+    max_wal_bytes_per_sec: u64,
+    expires_at: Option<SystemTime>,
+    /// WAL position of each database after the previous block, and when indexing resumed after it.
+    last_sample: Option<(Instant, Vec<u64>)>,
+}
+
+/// Caps the rate at which indexing generates Postgres WAL, so scheduled backups don't have to compete with catch-up
+/// bursts. Throttling is turned on and off at runtime through the admin API.
+#[derive(Debug, Default)]
+pub struct WriteThrottle {
+    settings: Mutex<Option<ThrottleSettings>>,
+}
+
+/// Pause needed after writing `wal_bytes` in `elapsed` to stay under `max_wal_bytes_per_sec`.
+pub fn throttle_delay(wal_bytes: u64, elapsed: Duration, max_wal_bytes_per_sec: u64) -> Duration {
+    Duration::from_secs_f64(wal_bytes as f64 / max_wal_bytes_per_sec.max(1) as f64)
+        .saturating_sub(elapsed)
+}
+
+async fn current_wal_position(pool: &Pool) -> Result<u64, String> {
+    let client = pg_pool_client(pool).await?;
+    let row = client
+        .query_one(
+            "SELECT (pg_current_wal_lsn() - '0/0'::pg_lsn)::bigint AS position",
+            &[],
+        )
+        .await
+        .map_err(|e| format!("unable to read wal position: {e}"))?;
+    let position: i64 = row.get("position");
+    Ok(position as u64)
+}
+
+impl WriteThrottle {
+    /// Starts throttling, for `duration` or until `disable` is called. A time limit makes sure indexing goes back to full
+    /// speed even if the backup job that asked for throttling dies.
+    pub fn enable(&self, max_wal_bytes_per_sec: u64, duration: Option<Duration>) {
+        if let Ok(mut settings) = self.settings.lock() {
+            *settings = Some(ThrottleSettings {
+                max_wal_bytes_per_sec,
+                expires_at: duration.map(|duration| SystemTime::now() + duration),
+                last_sample: None,
+            });
+        }
+    }
+
+    pub fn disable(&self) {
+        if let Ok(mut settings) = self.settings.lock() {
+            *settings = None;
+        }
+    }
+
+    pub fn status(&self) -> WriteThrottleStatus {
+        let settings = self.settings.lock().ok();
+        match settings.as_ref().and_then(|settings| settings.as_ref()) {
+            Some(settings)
+                if settings
+                    .expires_at
+                    .map_or(true, |expires_at| expires_at > SystemTime::now()) =>
+            {
+                WriteThrottleStatus {
+                    enabled: true,
+                    max_wal_bytes_per_sec: Some(settings.max_wal_bytes_per_sec),
+                    expires_at: settings.expires_at.and_then(|expires_at| {
+                        expires_at
+                            .duration_since(UNIX_EPOCH)
+                            .ok()
+                            .map(|d| d.as_secs())
+                    }),
+                }
+            }
+            _ => WriteThrottleStatus {
+                enabled: false,
+                max_wal_bytes_per_sec: None,
+                expires_at: None,
+            },
+        }
+    }
+
+    /// Called after every committed block. When throttling, waits long enough for the WAL written since the previous block
+    /// to fit under the configured rate. Databases on separate servers are paced on whichever one is written to the most.
+    /// Never fails: a block whose WAL position can't be read is not throttled.
+    pub async fn pace(&self, ordinals_pool: &Pool, brc20_pool: Option<&Pool>, ctx: &Context) {
+        {
+            let Ok(mut settings) = self.settings.lock() else {
+                return;
+            };
+            let Some(current) = settings.as_ref() else {
+                return;
+            };
+            if current
+                .expires_at
+                .is_some_and(|expires_at| expires_at <= SystemTime::now())
+            {
+                *settings = None;
+                try_info!(ctx, "Write throttle expired, resuming full speed");
+                return;
+            }
+        }
+        let mut positions = vec![];
+        for pool in [Some(ordinals_pool), brc20_pool].into_iter().flatten() {
+            match current_wal_position(pool).await {
+                Ok(position) => positions.push(position),
+                Err(e) => {
+                    try_warn!(ctx, "Write throttle: {e}");
+                    return;
+                }
+            }
+        }
+        let delay = {
+            let Ok(mut settings) = self.settings.lock() else {
+                return;
+            };
+            let Some(settings) = settings.as_mut() else {
+                return;
+            };
+            let delay = match &settings.last_sample {
+                Some((sampled_at, previous)) if previous.len() == positions.len() => {
+                    let wal_bytes = positions
+                        .iter()
+                        .zip(previous)
+                        .map(|(current, previous)| current.saturating_sub(*previous))
+                        .max()
+                        .unwrap_or(0);
+                    throttle_delay(
+                        wal_bytes,
+                        sampled_at.elapsed(),
+                        settings.max_wal_bytes_per_sec,
+                    )
+                }
+                _ => Duration::ZERO,
+            };
+            settings.last_sample = Some((Instant::now() + delay, positions));
+            delay
+        };
+        if !delay.is_zero() {
+            try_debug!(ctx, "Write throttle: pausing {}ms", delay.as_millis());
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{throttle_delay, WriteThrottle};
+
+    #[test]
+    fn computes_delay_from_wal_rate() {
+        let mb = 1024 * 1024;
+        assert_eq!(
+            throttle_delay(32 * mb, Duration::from_secs(1), 16 * mb),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            throttle_delay(8 * mb, Duration::from_secs(1), 16 * mb),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn reports_status() {
+        let throttle = WriteThrottle::default();
+        assert!(!throttle.status().enabled);
+        throttle.enable(1024, Some(Duration::from_secs(60)));
+        let status = throttle.status();
+        assert!(status.enabled);
+        assert_eq!(status.max_wal_bytes_per_sec, Some(1024));
+        assert!(status.expires_at.is_some());
+        throttle.enable(1024, Some(Duration::ZERO));
+        assert!(!throttle.status().enabled);
+        throttle.enable(1024, None);
+        throttle.disable();
+        assert!(!throttle.status().enabled);
+    }
+}
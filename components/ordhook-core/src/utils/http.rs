@@ -0,0 +1,77 @@
+use std::{convert::Infallible, future::Future, os::unix::fs::FileTypeExt};
+
+use hyper::{
+    server::accept,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use tokio::net::UnixListener;
+
+use crate::config::ListenAddress;
+
+/// Serves `handler` on a TCP or unix domain socket until the server fails. A stale socket file left by a previous run is
+/// removed before binding, any other file at that path is left alone and makes binding fail.
+pub async fn serve_http<F, Fut>(listen_address: &ListenAddress, handler: F) -> Result<(), String>
+where
+    F: Fn(Request<Body>) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response<Body>, hyper::Error>> + Send + 'static,
+{
+    // The connection type differs between TCP and unix sockets, so each arm builds its own service factory.
+    match listen_address {
+        ListenAddress::Tcp(addr) => {
+            let make_svc = make_service_fn(move |_| {
+                let handler = handler.clone();
+                async move { Ok::<_, Infallible>(service_fn(handler)) }
+            });
+            Server::try_bind(addr)
+                .map_err(|e| format!("unable to bind {listen_address}: {e}"))?
+                .serve(make_svc)
+                .await
+                .map_err(|e| e.to_string())
+        }
+        ListenAddress::Unix(path) => {
+            let make_svc = make_service_fn(move |_| {
+                let handler = handler.clone();
+                async move { Ok::<_, Infallible>(service_fn(handler)) }
+            });
+            if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+                let _ = std::fs::remove_file(path);
+            }
+            let listener = UnixListener::bind(path)
+                .map_err(|e| format!("unable to bind {listen_address}: {e}"))?;
+            let incoming = accept::poll_fn(move |cx| {
+                listener
+                    .poll_accept(cx)
+                    .map(|res| Some(res.map(|(stream, _)| stream)))
+            });
+            Server::builder(incoming)
+                .serve(make_svc)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{Body, Response};
+
+    use crate::config::ListenAddress;
+
+    use super::serve_http;
+
+    #[tokio::test]
+    async fn never_removes_files_that_are_not_sockets() {
+        let path = std::env::temp_dir().join("ordhook-serve-http-test.sock");
+        std::fs::write(&path, "not a socket").unwrap();
+
+        let result = serve_http(&ListenAddress::Unix(path.clone()), |_| async {
+            Ok(Response::new(Body::empty()))
+        })
+        .await;
+
+        assert!(result.unwrap_err().starts_with("unable to bind"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
+pub mod http;
 pub mod logger;
 pub mod monitoring;
+pub mod systemd;
 
 use std::{
     fs,
@@ -7,8 +9,42 @@ use std::{
     path::PathBuf,
 };
 
+use chainhook_sdk::utils::Context;
 use chainhook_types::TransactionIdentifier;
 
+use crate::try_warn;
+
+/// Raises the soft limit of open file descriptors of the process to `wanted`, up to what the system allows, and returns
+/// the limit in effect. macOS starts processes with a soft limit of 256 and rejects limits above `OPEN_MAX` even when
+/// the hard limit is unlimited.
+pub fn raise_open_files_limit(wanted: u64) -> Result<u64, String> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(format!(
+            "unable to read open files limit: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    if limit.rlim_cur >= wanted {
+        return Ok(limit.rlim_cur);
+    }
+    let max = limit.rlim_max;
+    #[cfg(target_os = "macos")]
+    let max = max.min(libc::OPEN_MAX as libc::rlim_t);
+    limit.rlim_cur = wanted.min(max);
+    if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &limit) } != 0 {
+        return Err(format!(
+            "unable to raise open files limit to {}: {}",
+            limit.rlim_cur,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(limit.rlim_cur)
+}
+
 pub fn read_file_content_at_path(file_path: &PathBuf) -> Result<Vec<u8>, String> {
     use std::fs::File;
     use std::io::BufReader;
@@ -41,6 +77,23 @@ pub fn write_file_content_at_path(file_path: &PathBuf, content: &[u8]) -> Result
     Ok(())
 }
 
+/// CPU core the thread at `thread_index` of a pool is pinned to, picked round-robin from `cores`. `None` when no cores are
+/// configured.
+pub fn core_for_thread(cores: &Option<Vec<usize>>, thread_index: usize) -> Option<usize> {
+    let cores = cores.as_ref().filter(|cores| !cores.is_empty())?;
+    Some(cores[thread_index % cores.len()])
+}
+
+/// Pins the calling thread to the core picked by [core_for_thread]. Does nothing when no cores are configured.
+pub fn pin_current_thread_to_cores(cores: &Option<Vec<usize>>, thread_index: usize, ctx: &Context) {
+    let Some(id) = core_for_thread(cores, thread_index) else {
+        return;
+    };
+    if !core_affinity::set_for_current(core_affinity::CoreId { id }) {
+        try_warn!(ctx, "Unable to pin thread to CPU core {id}");
+    }
+}
+
 pub fn format_inscription_id(
     transaction_identifier: &TransactionIdentifier,
     inscription_subindex: usize,
@@ -96,3 +149,18 @@ pub fn parse_outpoint_to_watch(outpoint_to_watch: &str) -> (TransactionIdentifie
     ));
     (tx, output_index)
 }
+
+#[cfg(test)]
+mod test {
+    use test_case::test_case;
+
+    use super::core_for_thread;
+
+    #[test_case(None, 1 => None; "unpinned")]
+    #[test_case(Some(vec![]), 1 => None; "empty core list")]
+    #[test_case(Some(vec![4, 5]), 0 => Some(4); "first core")]
+    #[test_case(Some(vec![4, 5]), 3 => Some(5); "round robin")]
+    fn pool_thread_core(cores: Option<Vec<usize>>, thread_index: usize) -> Option<usize> {
+        core_for_thread(&cores, thread_index)
+    }
+}
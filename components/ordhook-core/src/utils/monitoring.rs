@@ -1,15 +1,22 @@
-use chainhook_sdk::utils::Context;
-use hyper::{
-    header::CONTENT_TYPE,
-    service::{make_service_fn, service_fn},
-    Body, Method, Request, Response, Server,
-};
+use chainhook_sdk::{indexer::bitcoin::BitcoindRpcEndpoints, utils::Context};
+use chainhook_types::{BitcoinBlockData, Brc20Operation, OrdinalOperation};
+use hyper::{header::CONTENT_TYPE, Body, Method, Request, Response};
 use prometheus::{
     core::{AtomicU64, GenericGauge},
-    Encoder, Registry, TextEncoder,
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::{
+    config::ListenAddress, error::OrdhookError, try_debug, try_info, try_warn,
+    utils::http::serve_http,
 };
 
-use crate::{try_debug, try_info, try_warn};
+/// Default and maximum duration of a CPU profile requested over HTTP.
+#[cfg(feature = "profiling")]
+const DEFAULT_CPU_PROFILE_SECONDS: u64 = 30;
+#[cfg(feature = "profiling")]
+const MAX_CPU_PROFILE_SECONDS: u64 = 300;
 
 type UInt64Gauge = GenericGauge<AtomicU64>;
 
@@ -18,6 +25,14 @@ pub struct PrometheusMonitoring {
     pub last_indexed_block_height: UInt64Gauge,
     pub last_indexed_inscription_number: UInt64Gauge,
     pub registered_predicates: UInt64Gauge,
+    pub shadow_last_compared_block_height: UInt64Gauge,
+    pub shadow_divergent_blocks: UInt64Gauge,
+    pub inscription_reveals: IntCounterVec,
+    pub inscription_transfers: IntCounter,
+    pub brc20_operations: IntCounterVec,
+    pub indexing_errors: IntCounterVec,
+    pub blocks_per_commit: Histogram,
+    pub bitcoind_rpc_endpoint_active: IntGaugeVec,
     pub registry: Registry,
 }
 
@@ -40,14 +55,89 @@ impl PrometheusMonitoring {
             "registered_predicates",
             "The current number of predicates registered to receive ordinal events.",
         );
+        let shadow_last_compared_block_height =
+            PrometheusMonitoring::create_and_register_uint64_gauge(
+                &registry,
+                "shadow_last_compared_block_height",
+                "The latest block compared against the primary index while running in shadow mode.",
+            );
+        let shadow_divergent_blocks = PrometheusMonitoring::create_and_register_uint64_gauge(
+            &registry,
+            "shadow_divergent_blocks",
+            "The number of blocks whose indexed data diverged from the primary index while running in shadow mode.",
+        );
+        let inscription_reveals = PrometheusMonitoring::create_and_register_int_counter_vec(
+            &registry,
+            "inscription_reveals_total",
+            "The number of inscription reveals indexed since startup, by blessed or cursed type.",
+            &["type"],
+        );
+        let inscription_transfers = PrometheusMonitoring::create_and_register_int_counter(
+            &registry,
+            "inscription_transfers_total",
+            "The number of inscription transfers indexed since startup.",
+        );
+        let brc20_operations = PrometheusMonitoring::create_and_register_int_counter_vec(
+            &registry,
+            "brc20_operations_total",
+            "The number of valid BRC-20 operations indexed since startup, by operation.",
+            &["operation"],
+        );
+        let indexing_errors = PrometheusMonitoring::create_and_register_int_counter_vec(
+            &registry,
+            "indexing_errors_total",
+            "The number of errors that stopped block indexing, by pipeline stage and whether they can be retried.",
+            &["stage", "retryable"],
+        );
+        let blocks_per_commit = PrometheusMonitoring::create_and_register_histogram(
+            &registry,
+            "blocks_per_commit",
+            "The number of blocks written in each Postgres transaction.",
+            vec![1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0],
+        );
+        let bitcoind_rpc_endpoint_active = PrometheusMonitoring::create_and_register_int_gauge_vec(
+            &registry,
+            "bitcoind_rpc_endpoint_active",
+            "Whether each configured bitcoind RPC endpoint is the one blocks are currently downloaded from.",
+            &["url"],
+        );
         PrometheusMonitoring {
             last_indexed_block_height,
             last_indexed_inscription_number,
             registered_predicates,
+            shadow_last_compared_block_height,
+            shadow_divergent_blocks,
+            inscription_reveals,
+            inscription_transfers,
+            brc20_operations,
+            indexing_errors,
+            blocks_per_commit,
+            bitcoind_rpc_endpoint_active,
             registry,
         }
     }
 
+    pub fn create_and_register_int_counter(
+        registry: &Registry,
+        name: &str,
+        help: &str,
+    ) -> IntCounter {
+        let c = IntCounter::new(name, help).unwrap();
+        registry.register(Box::new(c.clone())).unwrap();
+        c
+    }
+
+    pub fn create_and_register_int_counter_vec(
+        registry: &Registry,
+        name: &str,
+        help: &str,
+        labels: &[&str],
+    ) -> IntCounterVec {
+        let c = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+        registry.register(Box::new(c.clone())).unwrap();
+        c
+    }
+
     pub fn create_and_register_uint64_gauge(
         registry: &Registry,
         name: &str,
@@ -58,6 +148,28 @@ impl PrometheusMonitoring {
         g
     }
 
+    pub fn create_and_register_int_gauge_vec(
+        registry: &Registry,
+        name: &str,
+        help: &str,
+        labels: &[&str],
+    ) -> IntGaugeVec {
+        let g = IntGaugeVec::new(Opts::new(name, help), labels).unwrap();
+        registry.register(Box::new(g.clone())).unwrap();
+        g
+    }
+
+    pub fn create_and_register_histogram(
+        registry: &Registry,
+        name: &str,
+        help: &str,
+        buckets: Vec<f64>,
+    ) -> Histogram {
+        let h = Histogram::with_opts(HistogramOpts::new(name, help).buckets(buckets)).unwrap();
+        registry.register(Box::new(h.clone())).unwrap();
+        h
+    }
+
     pub fn initialize(
         &self,
         total_predicates: u64,
@@ -94,11 +206,81 @@ impl PrometheusMonitoring {
             self.last_indexed_block_height.set(block_height);
         }
     }
+
+    /// Counts the ordinals and BRC-20 operations of an indexed block. Must be called after BRC-20 verification so only
+    /// valid operations are counted.
+    pub fn metrics_block_operations_indexed(&self, block: &BitcoinBlockData) {
+        for tx in block.transactions.iter() {
+            for operation in tx.metadata.ordinal_operations.iter() {
+                match operation {
+                    OrdinalOperation::InscriptionRevealed(reveal) => {
+                        let inscription_type = if reveal.inscription_number.classic < 0 {
+                            "cursed"
+                        } else {
+                            "blessed"
+                        };
+                        self.inscription_reveals
+                            .with_label_values(&[inscription_type])
+                            .inc();
+                    }
+                    OrdinalOperation::InscriptionTransferred(_) => {
+                        self.inscription_transfers.inc();
+                    }
+                }
+            }
+            if let Some(operation) = &tx.metadata.brc20_operation {
+                let operation = match operation {
+                    Brc20Operation::Deploy(_) => "deploy",
+                    Brc20Operation::Mint(_) => "mint",
+                    Brc20Operation::Transfer(_) => "transfer",
+                    Brc20Operation::TransferSend(_) => "transfer_send",
+                };
+                self.brc20_operations.with_label_values(&[operation]).inc();
+            }
+        }
+    }
+
+    pub fn metrics_indexing_error(&self, error: &OrdhookError) {
+        let stage = error
+            .stage()
+            .map(|stage| stage.as_str())
+            .unwrap_or("unknown");
+        let retryable = if error.is_retryable() {
+            "true"
+        } else {
+            "false"
+        };
+        self.indexing_errors
+            .with_label_values(&[stage, retryable])
+            .inc();
+    }
+
+    pub fn metrics_blocks_committed(&self, block_count: usize) {
+        self.blocks_per_commit.observe(block_count as f64);
+    }
+
+    pub fn metrics_bitcoind_rpc_endpoint(&self, endpoints: &BitcoindRpcEndpoints) {
+        let active_index = endpoints.active_index();
+        for (index, url) in endpoints.urls().iter().enumerate() {
+            self.bitcoind_rpc_endpoint_active
+                .with_label_values(&[url])
+                .set((index == active_index) as i64);
+        }
+    }
+
+    pub fn metrics_shadow_block_compared(&self, block_height: u64, diverged: bool) {
+        self.shadow_last_compared_block_height.set(block_height);
+        if diverged {
+            self.shadow_divergent_blocks.inc();
+        }
+    }
 }
 
+#[cfg_attr(not(feature = "profiling"), allow(unused_variables))]
 async fn serve_req(
     req: Request<Body>,
     registry: Registry,
+    cpu_profiling: bool,
     ctx: Context,
 ) -> Result<Response<Body>, hyper::Error> {
     match (req.method(), req.uri().path()) {
@@ -125,6 +307,21 @@ async fn serve_req(
             };
             Ok(response)
         }
+        #[cfg(feature = "profiling")]
+        (&Method::GET, "/debug/pprof/flamegraph") if cpu_profiling => {
+            let seconds = req
+                .uri()
+                .query()
+                .and_then(|query| {
+                    query
+                        .split('&')
+                        .find_map(|pair| pair.strip_prefix("seconds="))
+                        .and_then(|value| value.parse::<u64>().ok())
+                })
+                .unwrap_or(DEFAULT_CPU_PROFILE_SECONDS)
+                .min(MAX_CPU_PROFILE_SECONDS);
+            Ok(serve_cpu_flamegraph(seconds, ctx).await)
+        }
         (_, _) => {
             try_debug!(
                 ctx,
@@ -139,20 +336,66 @@ async fn serve_req(
     }
 }
 
-pub async fn start_serving_prometheus_metrics(port: u16, registry: Registry, ctx: Context) {
-    let addr = ([0, 0, 0, 0], port).into();
-    let ctx_clone = ctx.clone();
-    let make_svc = make_service_fn(|_| {
-        let registry = registry.clone();
-        let ctx_clone = ctx_clone.clone();
-        async move {
-            Ok::<_, hyper::Error>(service_fn(move |r| {
-                serve_req(r, registry.clone(), ctx_clone.clone())
-            }))
+/// Samples the CPU usage of every thread for `seconds` and responds with a flamegraph SVG.
+#[cfg(feature = "profiling")]
+async fn serve_cpu_flamegraph(seconds: u64, ctx: Context) -> Response<Body> {
+    try_info!(ctx, "Profiling: sampling CPU for {seconds} seconds");
+    let profile = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, String> {
+        let guard = pprof::ProfilerGuardBuilder::default()
+            .frequency(99)
+            .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+            .build()
+            .map_err(|e| format!("unable to start profiler: {e}"))?;
+        std::thread::sleep(std::time::Duration::from_secs(seconds));
+        let report = guard
+            .report()
+            .build()
+            .map_err(|e| format!("unable to build profile report: {e}"))?;
+        let mut svg = vec![];
+        report
+            .flamegraph(&mut svg)
+            .map_err(|e| format!("unable to render flamegraph: {e}"))?;
+        Ok(svg)
+    })
+    .await
+    .map_err(|e| format!("profiler thread failed: {e}"))
+    .and_then(|r| r);
+    match profile {
+        Ok(svg) => Response::builder()
+            .status(200)
+            .header(CONTENT_TYPE, "image/svg+xml")
+            .body(Body::from(svg))
+            .unwrap(),
+        Err(e) => {
+            try_warn!(ctx, "Profiling: {e}");
+            Response::builder().status(500).body(Body::empty()).unwrap()
         }
+    }
+}
+
+/// Serves metrics on `listen_address`, and CPU flamegraphs too when `cpu_profiling` is set and ordhook was built with the
+/// `profiling` feature.
+pub async fn start_serving_prometheus_metrics(
+    listen_address: ListenAddress,
+    registry: Registry,
+    cpu_profiling: bool,
+    ctx: Context,
+) {
+    let ctx_clone = ctx.clone();
+    try_info!(
+        ctx,
+        "Prometheus monitoring: listening on {}",
+        listen_address
+    );
+    if cpu_profiling && cfg!(not(feature = "profiling")) {
+        try_warn!(
+            ctx,
+            "Prometheus monitoring: CPU profiling requires ordhook to be built with the profiling feature"
+        );
+    }
+    let serve_future = serve_http(&listen_address, move |r| {
+        serve_req(r, registry.clone(), cpu_profiling, ctx_clone.clone())
     });
-    let serve_future = Server::bind(&addr).serve(make_svc);
-    try_info!(ctx, "Prometheus monitoring: listening on port {}", port);
     if let Err(err) = serve_future.await {
         try_warn!(ctx, "Prometheus monitoring: server error: {}", err);
     }
@@ -160,7 +403,19 @@ pub async fn start_serving_prometheus_metrics(port: u16, registry: Registry, ctx
 
 #[cfg(test)]
 mod test {
-    use crate::utils::monitoring::PrometheusMonitoring;
+    use chainhook_sdk::{indexer::bitcoin::BitcoindRpcEndpoints, utils::Context};
+    use chainhook_types::{
+        Brc20BalanceData, Brc20Operation, OrdinalInscriptionTransferData,
+        OrdinalInscriptionTransferDestination, OrdinalOperation,
+    };
+
+    use crate::{
+        core::{
+            meta_protocols::brc20::test_utils::Brc20RevealBuilder,
+            test_builders::{TestBlockBuilder, TestTransactionBuilder},
+        },
+        utils::monitoring::{serve_req, PrometheusMonitoring},
+    };
 
     #[test]
     fn it_tracks_predicate_registration_deregistration_with_defaults() {
@@ -182,6 +437,15 @@ mod test {
         assert_eq!(prometheus.last_indexed_block_height.get(), 100);
     }
 
+    #[test]
+    fn it_tracks_commit_sizes() {
+        let prometheus = PrometheusMonitoring::new();
+        prometheus.metrics_blocks_committed(1);
+        prometheus.metrics_blocks_committed(50);
+        assert_eq!(prometheus.blocks_per_commit.get_sample_count(), 2);
+        assert_eq!(prometheus.blocks_per_commit.get_sample_sum(), 51.0);
+    }
+
     #[test]
     fn it_tracks_inscription_indexing() {
         let prometheus = PrometheusMonitoring::new();
@@ -189,4 +453,112 @@ mod test {
         prometheus.metrics_inscription_indexed(5000);
         assert_eq!(prometheus.last_indexed_inscription_number.get(), 5000);
     }
+
+    #[test]
+    fn it_tracks_shadow_comparisons() {
+        let prometheus = PrometheusMonitoring::new();
+        prometheus.metrics_shadow_block_compared(840000, false);
+        prometheus.metrics_shadow_block_compared(840001, true);
+        assert_eq!(prometheus.shadow_last_compared_block_height.get(), 840001);
+        assert_eq!(prometheus.shadow_divergent_blocks.get(), 1);
+    }
+
+    #[test]
+    fn it_tracks_active_bitcoind_endpoint() {
+        let prometheus = PrometheusMonitoring::new();
+        let endpoints =
+            BitcoindRpcEndpoints::new("http://node-a:8332", &["http://node-b:8332".to_string()]);
+        endpoints.switch_to(1);
+        prometheus.metrics_bitcoind_rpc_endpoint(&endpoints);
+        let active = |url: &str| {
+            prometheus
+                .bitcoind_rpc_endpoint_active
+                .with_label_values(&[url])
+                .get()
+        };
+        assert_eq!(active("http://node-a:8332"), 0);
+        assert_eq!(active("http://node-b:8332"), 1);
+    }
+
+    #[test]
+    fn it_tracks_operations_by_protocol() {
+        let prometheus = PrometheusMonitoring::new();
+        let block = TestBlockBuilder::new()
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(
+                        Brc20RevealBuilder::new().inscription_number(-1).build(),
+                    ))
+                    .add_ordinal_operation(OrdinalOperation::InscriptionRevealed(
+                        Brc20RevealBuilder::new().inscription_number(5).build(),
+                    ))
+                    .brc20_operation(Some(Brc20Operation::Mint(Brc20BalanceData {
+                        tick: "pepe".to_string(),
+                        amt: "1000".to_string(),
+                        address: "324A7GHA2azecbVBAFy4pzEhcPT1GjbUAp".to_string(),
+                        inscription_id:
+                            "9bb2314d666ae0b1db8161cb373fcc1381681f71445c4e0335aa80ea9c37fcddi0"
+                                .to_string(),
+                    })))
+                    .build(),
+            )
+            .add_transaction(
+                TestTransactionBuilder::new()
+                    .add_ordinal_operation(OrdinalOperation::InscriptionTransferred(
+                        OrdinalInscriptionTransferData {
+                            ordinal_number: 0,
+                            destination: OrdinalInscriptionTransferDestination::SpentInFees,
+                            from_address: None,
+                            satpoint_pre_transfer: "".to_string(),
+                            satpoint_post_transfer: "".to_string(),
+                            post_transfer_output_value: None,
+                            tx_index: 1,
+                        },
+                    ))
+                    .build(),
+            )
+            .build();
+        prometheus.metrics_block_operations_indexed(&block);
+        assert_eq!(
+            prometheus
+                .inscription_reveals
+                .with_label_values(&["blessed"])
+                .get(),
+            1
+        );
+        assert_eq!(
+            prometheus
+                .inscription_reveals
+                .with_label_values(&["cursed"])
+                .get(),
+            1
+        );
+        assert_eq!(prometheus.inscription_transfers.get(), 1);
+        assert_eq!(
+            prometheus
+                .brc20_operations
+                .with_label_values(&["mint"])
+                .get(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_flamegraphs_only_when_cpu_profiling_is_enabled() {
+        let prometheus = PrometheusMonitoring::new();
+        let req = hyper::Request::get("/debug/pprof/flamegraph?seconds=1")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let res = serve_req(req, prometheus.registry.clone(), false, Context::empty())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 404);
+        let req = hyper::Request::get("/metrics")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let res = serve_req(req, prometheus.registry.clone(), false, Context::empty())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+    }
 }
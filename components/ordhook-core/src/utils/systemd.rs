@@ -0,0 +1,172 @@
+use std::{
+    os::unix::net::UnixDatagram,
+    time::{Duration, Instant},
+};
+
+/// Sends `sd_notify` messages to systemd when the service runs as a `Type=notify` unit. Does nothing when
+/// `NOTIFY_SOCKET` is not set, so it is safe to use outside of systemd.
+#[derive(Debug, Clone)]
+pub struct SystemdNotifier {
+    socket: Option<String>,
+    watchdog_interval: Option<Duration>,
+    last_watchdog_ping: Option<Instant>,
+}
+
+impl SystemdNotifier {
+    pub fn from_env() -> Self {
+        let socket = std::env::var("NOTIFY_SOCKET")
+            .ok()
+            .filter(|s| !s.is_empty());
+        let watchdog_pid = std::env::var("WATCHDOG_PID").ok();
+        let watchdog_usec = std::env::var("WATCHDOG_USEC").ok();
+        SystemdNotifier {
+            watchdog_interval: parse_watchdog_interval(
+                watchdog_usec.as_deref(),
+                watchdog_pid.as_deref(),
+                std::process::id(),
+            ),
+            socket,
+            last_watchdog_ping: None,
+        }
+    }
+
+    /// How often the watchdog should be pinged: half of `WatchdogSec`, as systemd recommends.
+    pub fn watchdog_ping_interval(&self) -> Option<Duration> {
+        self.watchdog_interval.map(|interval| interval / 2)
+    }
+
+    pub fn notify(&self, state: &str) -> Result<(), String> {
+        let Some(socket) = &self.socket else {
+            return Ok(());
+        };
+        let datagram =
+            UnixDatagram::unbound().map_err(|e| format!("unable to create notify socket: {e}"))?;
+        match socket.strip_prefix('@') {
+            Some(name) => send_to_abstract_socket(&datagram, name, state),
+            None => datagram.send_to(state.as_bytes(), socket).map(|_| ()),
+        }
+        .map_err(|e| format!("unable to notify systemd at {socket}: {e}"))
+    }
+
+    pub fn ready(&self) -> Result<(), String> {
+        self.notify("READY=1")
+    }
+
+    pub fn status(&self, status: &str) -> Result<(), String> {
+        self.notify(&format!("STATUS={status}"))
+    }
+
+    pub fn stopping(&self) -> Result<(), String> {
+        self.notify("STOPPING=1")
+    }
+
+    /// Pings the watchdog if it is enabled and the last ping is older than the ping interval.
+    pub fn ping_watchdog_if_due(&mut self) -> Result<(), String> {
+        let Some(interval) = self.watchdog_ping_interval() else {
+            return Ok(());
+        };
+        if self
+            .last_watchdog_ping
+            .is_some_and(|last_ping| last_ping.elapsed() < interval)
+        {
+            return Ok(());
+        }
+        self.last_watchdog_ping = Some(Instant::now());
+        self.notify("WATCHDOG=1")
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn send_to_abstract_socket(
+    datagram: &UnixDatagram,
+    name: &str,
+    state: &str,
+) -> std::io::Result<()> {
+    use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+    let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+    datagram.send_to_addr(state.as_bytes(), &addr).map(|_| ())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_to_abstract_socket(
+    _datagram: &UnixDatagram,
+    _name: &str,
+    _state: &str,
+) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "abstract sockets are only supported on linux",
+    ))
+}
+
+/// The watchdog is enabled by `WATCHDOG_USEC`, unless `WATCHDOG_PID` says it is meant for another process.
+fn parse_watchdog_interval(
+    watchdog_usec: Option<&str>,
+    watchdog_pid: Option<&str>,
+    pid: u32,
+) -> Option<Duration> {
+    if let Some(watchdog_pid) = watchdog_pid {
+        if watchdog_pid.parse::<u32>().ok() != Some(pid) {
+            return None;
+        }
+    }
+    let usec = watchdog_usec?.parse::<u64>().ok()?;
+    if usec == 0 {
+        return None;
+    }
+    Some(Duration::from_micros(usec))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{os::unix::net::UnixDatagram, time::Duration};
+
+    use super::{parse_watchdog_interval, SystemdNotifier};
+
+    #[test]
+    fn parses_watchdog_interval() {
+        assert_eq!(
+            parse_watchdog_interval(Some("30000000"), None, 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog_interval(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog_interval(Some("30000000"), Some("7"), 42),
+            None
+        );
+        assert_eq!(parse_watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog_interval(None, None, 42), None);
+    }
+
+    #[test]
+    fn sends_notifications_to_socket() {
+        let dir = std::env::temp_dir().join(format!("ordhook-notify-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket_path = dir.join("notify.sock");
+        let receiver = UnixDatagram::bind(&socket_path).unwrap();
+        let mut notifier = SystemdNotifier {
+            socket: Some(socket_path.to_string_lossy().to_string()),
+            watchdog_interval: Some(Duration::from_secs(60)),
+            last_watchdog_ping: None,
+        };
+
+        notifier.ready().unwrap();
+        notifier.ping_watchdog_if_due().unwrap();
+        // Too early for another ping.
+        notifier.ping_watchdog_if_due().unwrap();
+        notifier.stopping().unwrap();
+
+        let mut buf = [0u8; 64];
+        let mut messages = vec![];
+        receiver.set_nonblocking(true).unwrap();
+        while let Ok(len) = receiver.recv(&mut buf) {
+            messages.push(String::from_utf8_lossy(&buf[..len]).to_string());
+        }
+        assert_eq!(messages, vec!["READY=1", "WATCHDOG=1", "STOPPING=1"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
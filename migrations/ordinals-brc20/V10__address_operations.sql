@@ -0,0 +1,14 @@
+CREATE TABLE address_operations (
+    address TEXT NOT NULL,
+    ticker TEXT NOT NULL,
+    operation TEXT NOT NULL,
+    inscription_id TEXT NOT NULL,
+    block_height NUMERIC NOT NULL,
+    tx_index NUMERIC NOT NULL
+);
+ALTER TABLE address_operations ADD PRIMARY KEY (address, inscription_id, operation);
+CREATE INDEX address_operations_address_block_height_tx_index_index ON address_operations (address, block_height DESC, tx_index DESC);
+CREATE INDEX address_operations_block_height_index ON address_operations (block_height);
+
+INSERT INTO address_operations (address, ticker, operation, inscription_id, block_height, tx_index)
+(SELECT address, ticker, operation, inscription_id, block_height, tx_index FROM operations);
@@ -0,0 +1,5 @@
+ALTER TABLE tokens ADD COLUMN holders INT NOT NULL DEFAULT 0;
+
+UPDATE tokens SET holders = h.count
+FROM (SELECT ticker, COUNT(*) AS count FROM balances WHERE total_balance > 0 GROUP BY ticker) AS h
+WHERE tokens.ticker = h.ticker;
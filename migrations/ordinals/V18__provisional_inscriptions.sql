@@ -0,0 +1,17 @@
+CREATE TABLE provisional_inscriptions (
+    inscription_id TEXT NOT NULL PRIMARY KEY,
+    block_height NUMERIC NOT NULL,
+    block_hash TEXT NOT NULL,
+    tx_id TEXT NOT NULL,
+    tx_index BIGINT NOT NULL,
+    input_index BIGINT NOT NULL,
+    mime_type TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    content_length BIGINT NOT NULL,
+    content BYTEA NOT NULL,
+    curse_type TEXT,
+    metaprotocol TEXT,
+    delegate TEXT,
+    timestamp BIGINT NOT NULL
+);
+CREATE INDEX provisional_inscriptions_block_height_index ON provisional_inscriptions (block_height);
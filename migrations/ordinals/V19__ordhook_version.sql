@@ -0,0 +1,7 @@
+CREATE TABLE ordhook_version (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE,
+    version TEXT NOT NULL,
+    schema_version INT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+ALTER TABLE ordhook_version ADD CONSTRAINT ordhook_version_one_row CHECK(id);
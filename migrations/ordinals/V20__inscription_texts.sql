@@ -0,0 +1,6 @@
+CREATE TABLE inscription_texts (
+    inscription_id TEXT NOT NULL PRIMARY KEY,
+    content_text TSVECTOR NOT NULL
+);
+ALTER TABLE inscription_texts ADD CONSTRAINT inscription_texts_inscription_id_fk FOREIGN KEY(inscription_id) REFERENCES inscriptions(inscription_id) ON DELETE CASCADE;
+CREATE INDEX inscription_texts_content_text_index ON inscription_texts USING GIN (content_text);
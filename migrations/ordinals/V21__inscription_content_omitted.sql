@@ -0,0 +1,2 @@
+ALTER TABLE inscriptions ADD COLUMN content_omitted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE inscriptions ADD COLUMN content_hash TEXT;
@@ -0,0 +1,16 @@
+CREATE TABLE webhook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    block_height NUMERIC NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT
+);
+CREATE TABLE webhook_dead_letters (
+    id BIGINT NOT NULL PRIMARY KEY,
+    block_height NUMERIC NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INT NOT NULL,
+    last_error TEXT,
+    failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,8 @@
+CREATE TABLE indexed_blocks (
+    block_height NUMERIC NOT NULL PRIMARY KEY,
+    block_hash TEXT NOT NULL
+);
+CREATE INDEX indexed_blocks_block_hash_index ON indexed_blocks (block_hash);
+
+INSERT INTO indexed_blocks (block_height, block_hash)
+(SELECT block_height, block_hash FROM counts_by_block);
@@ -0,0 +1 @@
+CREATE INDEX locations_address_block_height_index ON locations (address, block_height);
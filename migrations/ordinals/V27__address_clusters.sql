@@ -0,0 +1,8 @@
+CREATE TABLE address_clusters (
+    address TEXT NOT NULL PRIMARY KEY,
+    cluster_id TEXT NOT NULL,
+    source TEXT NOT NULL,
+    block_height NUMERIC NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX address_clusters_cluster_id_index ON address_clusters (cluster_id);
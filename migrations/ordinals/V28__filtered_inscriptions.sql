@@ -0,0 +1,7 @@
+CREATE TABLE filtered_inscriptions (
+    inscription_id TEXT NOT NULL PRIMARY KEY,
+    policy TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+ALTER TABLE filtered_inscriptions ADD CONSTRAINT filtered_inscriptions_inscription_id_fk FOREIGN KEY(inscription_id) REFERENCES inscriptions(inscription_id) ON DELETE CASCADE;
@@ -0,0 +1,3 @@
+ALTER TABLE indexed_blocks ADD COLUMN first_operation_sequence NUMERIC;
+ALTER TABLE indexed_blocks ADD COLUMN operation_count NUMERIC;
+CREATE INDEX indexed_blocks_first_operation_sequence_index ON indexed_blocks (first_operation_sequence);
@@ -0,0 +1,7 @@
+CREATE TABLE address_watch_digest_entries (
+    id BIGSERIAL PRIMARY KEY,
+    block_height NUMERIC NOT NULL,
+    block_hash TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,5 @@
+CREATE TABLE inscription_takedowns (
+    inscription_id TEXT NOT NULL PRIMARY KEY,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
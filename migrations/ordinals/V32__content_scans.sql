@@ -0,0 +1,9 @@
+CREATE TABLE content_scans (
+    inscription_id TEXT NOT NULL,
+    scanner TEXT NOT NULL,
+    flagged BOOLEAN NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (inscription_id, scanner)
+);
+ALTER TABLE content_scans ADD CONSTRAINT content_scans_inscription_id_fk FOREIGN KEY(inscription_id) REFERENCES inscriptions(inscription_id) ON DELETE CASCADE;
@@ -0,0 +1,9 @@
+CREATE TABLE content_scan_failures (
+    inscription_id TEXT NOT NULL,
+    scanner TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    error TEXT NOT NULL,
+    last_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (inscription_id, scanner)
+);
+ALTER TABLE content_scan_failures ADD CONSTRAINT content_scan_failures_inscription_id_fk FOREIGN KEY(inscription_id) REFERENCES inscriptions(inscription_id) ON DELETE CASCADE;
@@ -0,0 +1,10 @@
+CREATE TABLE inscription_contents (
+    content_hash TEXT NOT NULL PRIMARY KEY,
+    content BYTEA NOT NULL
+);
+UPDATE inscriptions SET content_hash = ENCODE(SHA256(content), 'hex') WHERE content_hash IS NULL;
+INSERT INTO inscription_contents (content_hash, content)
+    SELECT DISTINCT ON (content_hash) content_hash, content FROM inscriptions WHERE NOT content_omitted;
+ALTER TABLE inscriptions DROP COLUMN content;
+ALTER TABLE inscriptions ALTER COLUMN content_hash SET NOT NULL;
+CREATE INDEX inscriptions_content_hash_index ON inscriptions (content_hash);